@@ -69,6 +69,7 @@ fn setup_editor(
         let mut provider = TreeSitterProvider::new();
         provider.set_query(tree_sitter_rust::HIGHLIGHTS_QUERY, language)
             .expect("Failed to create highlight query");
+        provider.set_language_name("rust");
 
 
         // Set the provider in the syntax resource
@@ -93,7 +94,17 @@ fn setup_editor(
     // rootUri is the project root, which is usually the directory containing Cargo.toml
     let project_root = current_dir; 
     let root_uri = lsp_types::Url::from_directory_path(&project_root).expect("Failed to get project root URI");
-    let capabilities = lsp_types::ClientCapabilities::default();
+    // Ask for a nested outline so DocumentSymbolTree keeps the hierarchy
+    let capabilities = lsp_types::ClientCapabilities {
+        text_document: Some(lsp_types::TextDocumentClientCapabilities {
+            document_symbol: Some(lsp_types::DocumentSymbolClientCapabilities {
+                hierarchical_document_symbol_support: Some(true),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
     
     lsp_client.send(bevy_code_editor::lsp::LspMessage::Initialize {
         root_uri: root_uri.clone(),
@@ -105,6 +105,7 @@ fn main() {
     let mut provider = TreeSitterProvider::new();
     provider.set_query(tree_sitter_rust::HIGHLIGHTS_QUERY, language)
         .expect("Failed to create highlight query");
+    provider.set_language_name("rust");
 
     // Set the provider in the syntax resource
     // The tree will be updated automatically when rendering
@@ -1,11 +1,22 @@
 //! Tab Map - Expands tabs to spaces for consistent column display
 //!
 //! The tab map handles tab expansion, converting tab characters to their
-//! visual column positions based on tab stops.
+//! visual column positions based on tab stops. Wide characters (CJK, fullwidth
+//! forms, emoji) occupy two cells, see [`char_cells`].
 
 use ropey::Rope;
+use unicode_width::UnicodeWidthChar;
 use super::{WrapPoint, DisplayPoint, DisplayMapLayer};
 
+/// Number of monospace cells a (non-tab) character occupies
+///
+/// Follows `unicode-width`: East Asian wide and fullwidth characters and emoji
+/// take two cells, combining marks and zero-width characters take none.
+/// Control characters have no defined width and are drawn as one cell.
+pub fn char_cells(ch: char) -> u32 {
+    UnicodeWidthChar::width(ch).unwrap_or(1) as u32
+}
+
 /// Handles tab expansion for display
 #[derive(Clone, Debug)]
 pub struct TabMap {
@@ -43,27 +54,36 @@ impl TabMap {
         self.tab_size
     }
 
+    /// Visual column a tab starting at `visual_col` advances to
+    pub fn next_tab_stop(&self, visual_col: u32) -> u32 {
+        (visual_col / self.tab_size + 1) * self.tab_size
+    }
+
     /// Expand a column position accounting for tabs in the line
     ///
     /// Given a character column (counting tabs as 1), returns the visual column
     /// (counting tabs as expanded to tab stops).
     pub fn expand_column(&self, line: &str, char_column: u32) -> u32 {
+        self.expand_column_chars(line.chars(), char_column)
+    }
+
+    /// `expand_column` over a char iterator, e.g. a rope line, without
+    /// collecting it into a string first
+    pub fn expand_column_chars(&self, chars: impl Iterator<Item = char>, char_column: u32) -> u32 {
         let mut visual_col = 0u32;
         let mut char_col = 0u32;
 
-        for ch in line.chars() {
+        for ch in chars {
             if char_col >= char_column {
                 break;
             }
 
             if ch == '\t' {
-                // Advance to next tab stop
-                let next_stop = ((visual_col / self.tab_size) + 1) * self.tab_size;
-                visual_col = next_stop;
+                visual_col = self.next_tab_stop(visual_col);
             } else if ch == '\n' {
                 break;
             } else {
-                visual_col += 1;
+                visual_col += char_cells(ch);
             }
 
             char_col += 1;
@@ -87,12 +107,11 @@ impl TabMap {
 
             let char_width = if ch == '\t' {
                 // Tab width depends on current visual column
-                let next_stop = ((visual_col / self.tab_size) + 1) * self.tab_size;
-                next_stop - visual_col
+                self.next_tab_stop(visual_col) - visual_col
             } else if ch == '\n' {
                 break;
             } else {
-                1
+                char_cells(ch)
             };
 
             // Check if we'd overshoot
@@ -108,18 +127,17 @@ impl TabMap {
         char_col
     }
 
-    /// Get the visual width of a line (with tabs expanded)
+    /// Get the visual width of a line (with tabs expanded and wide characters as two cells)
     pub fn line_visual_width(&self, line: &str) -> u32 {
         let mut visual_col = 0u32;
 
         for ch in line.chars() {
             if ch == '\t' {
-                let next_stop = ((visual_col / self.tab_size) + 1) * self.tab_size;
-                visual_col = next_stop;
+                visual_col = self.next_tab_stop(visual_col);
             } else if ch == '\n' {
                 break;
             } else {
-                visual_col += 1;
+                visual_col += char_cells(ch);
             }
         }
 
@@ -205,4 +223,36 @@ mod tests {
         assert_eq!(tab_map.line_visual_width("\thello"), 9); // 4 + 5
         assert_eq!(tab_map.line_visual_width("a\tb"), 5); // 1 + 3 + 1
     }
+
+    #[test]
+    fn test_wide_characters_take_two_cells() {
+        let tab_map = TabMap::new(4);
+        let line = "a中文b";
+
+        assert_eq!(tab_map.expand_column(line, 2), 3);
+        assert_eq!(tab_map.expand_column(line, 4), 6);
+        assert_eq!(tab_map.contract_column(line, 3), 2);
+        // A click inside a wide character lands before it
+        assert_eq!(tab_map.contract_column(line, 2), 1);
+        assert_eq!(tab_map.line_visual_width(line), 6);
+    }
+
+    #[test]
+    fn test_mixed_width_line_columns() {
+        let tab_map = TabMap::new(4);
+        // Hiragana, fullwidth Latin, a combining accent and an emoji
+        let line = "xあＡe\u{301}😀\ty";
+
+        assert_eq!(char_cells('あ'), 2);
+        assert_eq!(char_cells('Ａ'), 2);
+        assert_eq!(char_cells('\u{301}'), 0);
+        assert_eq!(char_cells('😀'), 2);
+
+        // x | あ あ | Ａ Ａ | e (+accent) | 😀 😀 | tab to 12 | y
+        let columns: Vec<u32> = (0..=8).map(|col| tab_map.expand_column(line, col)).collect();
+        assert_eq!(columns, vec![0, 1, 3, 5, 6, 6, 8, 12, 13]);
+        assert_eq!(tab_map.line_visual_width(line), 13);
+        assert_eq!(tab_map.contract_column(line, 8), 6);
+        assert_eq!(tab_map.contract_column(line, 12), 7);
+    }
 }
@@ -35,6 +35,34 @@ impl TextEditEvent {
     }
 }
 
+/// Event carrying the precise text changes applied during a frame
+///
+/// Unlike `TextEditEvent` this includes the inserted text, so consumers such as
+/// LSP sync, collaboration backends or persistence can mirror the buffer without
+/// diffing. Changes are in replay order; see `ChangeGranularity` for batching.
+#[derive(Message, Clone, Debug)]
+pub struct ContentChangedEvent {
+    /// Changes in the order they were applied
+    pub changes: Vec<crate::types::ContentChange>,
+    /// Content version after the last change
+    pub content_version: u64,
+}
+
+/// Message sent for every logical edit of the buffer
+///
+/// Unlike `ContentChangedEvent` these are never batched or coalesced: each
+/// insertion, deletion or replacement yields one message, in order.
+#[derive(Message, Clone, Debug)]
+pub struct BufferChanged {
+    /// Content version after this edit
+    pub content_version: u64,
+    /// The edited range, in character offsets
+    pub edit: crate::types::TextEdit,
+    /// Buffer lines spanned by the edit afterwards (exclusive end); `None`
+    /// when the whole buffer was replaced
+    pub lines: Option<std::ops::Range<usize>>,
+}
+
 /// Event requesting code completion at current cursor position
 ///
 /// This event is typically fired when user presses Ctrl+Space or types a trigger character
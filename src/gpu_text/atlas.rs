@@ -7,7 +7,8 @@ use bevy::prelude::*;
 use bevy::asset::RenderAssetUsages;
 use bevy::render::render_resource::{Extent3d, TextureDimension, TextureFormat};
 use cosmic_text::{CacheKey, FontSystem, SwashCache};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use crate::settings::{FontSettings, FontStyle};
 
 /// Size of the glyph atlas texture (power of 2 for GPU efficiency)
 pub const ATLAS_SIZE: u32 = 2048;
@@ -19,6 +20,12 @@ const GLYPH_PADDING: u32 = 2;
 /// Rasterize at 2x resolution for crisp text on Retina/HiDPI displays
 const DPI_SCALE: f32 = 2.0;
 
+/// How far synthetic bold smears each glyph to the right, as a fraction of the font size
+const SYNTHETIC_BOLD_STRENGTH: f32 = 1.0 / 24.0;
+
+/// Horizontal shear per pixel of height for synthetic italic (about 11 degrees)
+const SYNTHETIC_ITALIC_SLANT: f32 = 0.2;
+
 /// A unique identifier for a cached glyph
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub struct GlyphKey {
@@ -26,13 +33,20 @@ pub struct GlyphKey {
     pub character: char,
     /// Font size in pixels (scaled by 10 for sub-pixel precision)
     pub font_size_tenths: u32,
+    /// Weight and slant the glyph is drawn with
+    pub style: FontStyle,
 }
 
 impl GlyphKey {
     pub fn new(character: char, font_size: f32) -> Self {
+        Self::styled(character, font_size, FontStyle::Regular)
+    }
+
+    pub fn styled(character: char, font_size: f32, style: FontStyle) -> Self {
         Self {
             character,
             font_size_tenths: (font_size * 10.0) as u32,
+            style,
         }
     }
 }
@@ -49,6 +63,9 @@ pub struct GlyphInfo {
     pub offset: Vec2,
     /// Advance width (how far to move for next character)
     pub advance: f32,
+    /// Font in the chain that supplied the glyph: 0 for the primary font,
+    /// then `fallback_fonts` in order (`None` for the placeholder rasterizer)
+    pub font: Option<usize>,
 }
 
 /// Row-based packing for the atlas (simple shelf algorithm)
@@ -58,17 +75,43 @@ struct AtlasRow {
     x_cursor: u32,
 }
 
+/// A padded rectangle of atlas pixels owned by one glyph, or free for reuse
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct AtlasRegion {
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+}
+
+/// A glyph in the atlas with its region and when it was last used
+struct CachedGlyph {
+    info: GlyphInfo,
+    /// `None` for empty glyphs (like space), which take no atlas space
+    region: Option<AtlasRegion>,
+    last_used: u64,
+}
+
 /// The glyph atlas resource
 #[derive(Resource)]
 pub struct GlyphAtlas {
     /// The atlas texture handle
     pub texture: Handle<Image>,
     /// Cached glyph information
-    glyphs: HashMap<GlyphKey, GlyphInfo>,
+    glyphs: HashMap<GlyphKey, CachedGlyph>,
     /// Current packing rows
     rows: Vec<AtlasRow>,
     /// Current Y position for new rows
     current_y: u32,
+    /// Regions freed by evicted glyphs, reused before packing new space
+    free_regions: Vec<AtlasRegion>,
+    /// Use counter for least-recently-used eviction
+    clock: u64,
+    /// `clock` at the start of the current frame; glyphs used since are on
+    /// screen and never evicted
+    frame_start: u64,
+    /// Bumped whenever glyphs are evicted or the atlas is cleared
+    generation: u64,
     /// Raw pixel data for CPU-side updates
     pixels: Vec<u8>,
     /// Whether the texture needs to be updated
@@ -79,6 +122,22 @@ pub struct GlyphAtlas {
     swash_cache: SwashCache,
     /// Cached font ID for the configured font
     configured_font_id: Option<cosmic_text::fontdb::ID>,
+    /// Maximum new glyphs rasterized per frame by `get_or_queue` (None = unlimited)
+    frame_budget: Option<usize>,
+    /// New glyphs rasterized since the last `begin_frame`
+    rasterized_this_frame: usize,
+    /// Glyphs requested over budget since the last `begin_frame`
+    pending: HashSet<GlyphKey>,
+    /// Requests deferred since the last `begin_frame`
+    deferred_this_frame: usize,
+    /// Fallback fonts that were found, in the configured order
+    fallback_font_ids: Vec<cosmic_text::fontdb::ID>,
+    /// Which font in the chain satisfied each character, as (chain index,
+    /// font ID, glyph ID); the chain is fixed for the atlas's lifetime
+    resolved_fonts: HashMap<char, Option<(usize, cosmic_text::fontdb::ID, u16)>>,
+    /// Bold/italic faces of the primary font's family, looked up on first use
+    /// (`None` when the family has no such face and glyphs are synthesized)
+    style_font_ids: HashMap<FontStyle, Option<cosmic_text::fontdb::ID>>,
 }
 
 impl GlyphAtlas {
@@ -93,6 +152,15 @@ impl GlyphAtlas {
     /// - A path to a font file (e.g., "fonts/FiraMono-Regular.ttf")
     /// - A font family name (e.g., "Fira Mono", "JetBrains Mono")
     pub fn new_with_font(images: &mut Assets<Image>, font_path: Option<&str>) -> Self {
+        Self::new_with_fonts(images, font_path, &[])
+    }
+
+    /// Create a glyph atlas with a primary font and a fallback chain
+    ///
+    /// Characters the primary font can't render are taken from the first
+    /// fallback font that has a glyph for them. Fallbacks that can't be found
+    /// are skipped.
+    pub fn new_with_fonts(images: &mut Assets<Image>, font_path: Option<&str>, fallback_fonts: &[String]) -> Self {
         // Create RGBA texture
         let pixels = vec![0u8; (ATLAS_SIZE * ATLAS_SIZE * 4) as usize];
 
@@ -122,16 +190,32 @@ impl GlyphAtlas {
             None
         };
 
+        let fallback_font_ids = fallback_fonts
+            .iter()
+            .filter_map(|path| Self::find_or_load_font(&mut font_system, path))
+            .collect();
+
         Self {
             texture,
             glyphs: HashMap::new(),
             rows: Vec::new(),
             current_y: 0,
+            free_regions: Vec::new(),
+            clock: 0,
+            frame_start: 0,
+            generation: 0,
             pixels,
             dirty: false,
             font_system,
             swash_cache,
             configured_font_id,
+            frame_budget: None,
+            rasterized_this_frame: 0,
+            pending: HashSet::new(),
+            deferred_this_frame: 0,
+            fallback_font_ids,
+            resolved_fonts: HashMap::new(),
+            style_font_ids: HashMap::new(),
         }
     }
 
@@ -215,17 +299,106 @@ impl GlyphAtlas {
         None
     }
 
+    /// Ascent and descent (both positive, in logical pixels) of the font at `font_size`
+    pub fn font_metrics(&mut self, font_size: f32) -> (f32, f32) {
+        let metrics = self.font_id().and_then(|id| {
+            let font = self.font_system.get_font(id)?;
+            let metrics = font.as_swash().metrics(&[]).scale(font_size);
+            Some((metrics.ascent, metrics.descent))
+        });
+        metrics.unwrap_or_else(|| GlyphRasterizer::fallback_metrics(font_size))
+    }
+
+    /// The configured font, or the first monospace (then any) system font
+    fn font_id(&self) -> Option<cosmic_text::fontdb::ID> {
+        if let Some(id) = self.configured_font_id {
+            return Some(id);
+        }
+        let db = self.font_system.db();
+        db.faces()
+            .find_map(|face| face.monospaced.then_some(face.id))
+            .or_else(|| db.faces().next().map(|f| f.id))
+    }
+
+    /// The primary font family's true face for `style`, if one is installed
+    fn style_font_id(&mut self, style: FontStyle) -> Option<cosmic_text::fontdb::ID> {
+        if let Some(id) = self.style_font_ids.get(&style) {
+            return *id;
+        }
+        let id = self.find_style_face(style);
+        self.style_font_ids.insert(style, id);
+        id
+    }
+
+    fn find_style_face(&self, style: FontStyle) -> Option<cosmic_text::fontdb::ID> {
+        use cosmic_text::fontdb::{Family, Query, Style, Weight};
+
+        let db = self.font_system.db();
+        let primary = db.face(self.font_id()?)?;
+        let family = primary.families.first()?.0.clone();
+        let id = db.query(&Query {
+            families: &[Family::Name(&family)],
+            weight: if style.is_bold() { Weight::BOLD } else { primary.weight },
+            stretch: primary.stretch,
+            style: if style.is_italic() { Style::Italic } else { primary.style },
+        })?;
+
+        // The query returns the closest face, which may be the regular one
+        let face = db.face(id)?;
+        let bold_ok = !style.is_bold() || face.weight.0 >= Weight::SEMIBOLD.0;
+        let italic_ok = !style.is_italic() || face.style != Style::Normal;
+        (id != primary.id && bold_ok && italic_ok).then_some(id)
+    }
+
+    /// First font in the chain with a glyph for `character`, as
+    /// (chain index, font ID, glyph ID)
+    ///
+    /// The result is cached, so each character walks the chain once even when
+    /// it's rasterized at several sizes or styles.
+    fn resolve_font(&mut self, character: char) -> Option<(usize, cosmic_text::fontdb::ID, u16)> {
+        if let Some(resolved) = self.resolved_fonts.get(&character) {
+            return *resolved;
+        }
+
+        let chain: Vec<_> = self.font_id().into_iter().chain(self.fallback_font_ids.iter().copied()).collect();
+        let resolved = chain.into_iter().enumerate().find_map(|(index, font_id)| {
+            let font = self.font_system.get_font(font_id)?;
+            let glyph_id = font.as_swash().charmap().map(character);
+            // Space may legitimately map to glyph 0 in the primary font
+            (glyph_id != 0 || (character == ' ' && index == 0)).then_some((index, font_id, glyph_id))
+        });
+        self.resolved_fonts.insert(character, resolved);
+        resolved
+    }
+
+    /// Advance width of `character` at `font_size`, rasterizing it if needed
+    pub fn measure_advance(&mut self, character: char, font_size: f32) -> Option<f32> {
+        self.get_or_insert(GlyphKey::new(character, font_size), || {
+            GlyphRasterizer::rasterize(character, font_size, FontSettings::estimated_char_width(font_size), FontStyle::Regular)
+        })
+        .map(|info| info.advance)
+    }
+
     /// Get or create a glyph entry in the atlas
+    ///
+    /// When the atlas is full, least-recently-used glyphs are evicted to make
+    /// room; they are rasterized again the next time they're requested.
     pub fn get_or_insert(&mut self, key: GlyphKey, rasterize: impl FnOnce() -> Option<RasterizedGlyph>) -> Option<GlyphInfo> {
-        if let Some(info) = self.glyphs.get(&key) {
-            return Some(*info);
+        self.clock += 1;
+        if let Some(cached) = self.glyphs.get_mut(&key) {
+            cached.last_used = self.clock;
+            return Some(cached.info);
         }
 
         // Try cosmic_text rasterization first, fall back to provided rasterizer
-        let glyph = self.rasterize_with_cosmic(key).or_else(rasterize)?;
+        let (glyph, font) = match self.rasterize_with_cosmic(key) {
+            Some((glyph, font)) => (glyph, Some(font)),
+            None => (rasterize()?, None),
+        };
 
-        // Find space in the atlas
-        let (x, y) = self.allocate(glyph.width, glyph.height)?;
+        // Find space in the atlas, evicting old glyphs if it's full
+        let region = self.allocate_or_evict(glyph.width, glyph.height)?;
+        let (x, y) = region.map_or((0, 0), |region| (region.x, region.y));
 
         // Copy glyph pixels to atlas
         self.copy_glyph_to_atlas(x, y, &glyph);
@@ -248,16 +421,135 @@ impl GlyphAtlas {
             size: Vec2::new(glyph.width as f32 / DPI_SCALE, glyph.height as f32 / DPI_SCALE),
             offset: Vec2::new(glyph.bearing_x, glyph.bearing_y),
             advance: glyph.advance,
+            font,
         };
 
-        self.glyphs.insert(key, info);
+        self.glyphs.insert(key, CachedGlyph { info, region, last_used: self.clock });
         self.dirty = true;
 
         Some(info)
     }
 
-    /// Rasterize a glyph using cosmic_text/swash
-    fn rasterize_with_cosmic(&mut self, key: GlyphKey) -> Option<RasterizedGlyph> {
+    /// Mark the start of an app frame: glyphs used from here on are kept
+    /// until the next call
+    pub fn start_frame(&mut self) {
+        self.frame_start = self.clock;
+    }
+
+    /// Start a new frame, allowing up to `budget` new glyphs through `get_or_queue`
+    pub fn begin_frame(&mut self, budget: Option<usize>) {
+        self.frame_budget = budget;
+        self.rasterized_this_frame = 0;
+        self.deferred_this_frame = 0;
+        // Still-needed glyphs are queued again by this frame's requests
+        self.pending.clear();
+    }
+
+    /// Like `get_or_insert`, but once this frame's budget is spent new glyphs
+    /// are queued and `None` is returned; callers leave a blank cell and
+    /// render again while `has_pending` is true
+    pub fn get_or_queue(&mut self, key: GlyphKey, rasterize: impl FnOnce() -> Option<RasterizedGlyph>) -> Option<GlyphInfo> {
+        if self.glyphs.contains_key(&key) {
+            return self.get_or_insert(key, rasterize);
+        }
+        if self.frame_budget.is_some_and(|budget| self.rasterized_this_frame >= budget) {
+            self.pending.insert(key);
+            self.deferred_this_frame += 1;
+            return None;
+        }
+        self.rasterized_this_frame += 1;
+        self.pending.remove(&key);
+        self.get_or_insert(key, rasterize)
+    }
+
+    /// Whether the last frame deferred glyphs that still need rasterizing
+    pub fn has_pending(&self) -> bool {
+        !self.pending.is_empty()
+    }
+
+    /// Number of distinct glyphs the last frame deferred
+    pub fn pending_count(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Number of requests deferred since the last `begin_frame`
+    pub fn deferred_this_frame(&self) -> usize {
+        self.deferred_this_frame
+    }
+
+    /// Changes whenever cached glyphs are evicted or cleared
+    ///
+    /// UVs from an older generation may now point at other glyphs, so meshes
+    /// built before the change must be rebuilt.
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// Allocate a region, evicting least-recently-used glyphs until one fits
+    ///
+    /// Returns `Some(None)` for empty glyphs and `None` only when the glyph
+    /// is larger than an empty atlas.
+    fn allocate_or_evict(&mut self, width: u32, height: u32) -> Option<Option<AtlasRegion>> {
+        if width == 0 || height == 0 {
+            return Some(None);
+        }
+
+        loop {
+            if let Some(region) = self.allocate(width, height) {
+                return Some(Some(region));
+            }
+            if !self.evict_least_recently_used() {
+                break;
+            }
+        }
+
+        // Glyphs drawn this frame stay put: when they alone fill the atlas the
+        // new glyph is skipped rather than invalidating meshes every frame
+        if self.rows.is_empty() || self.glyphs.values().any(|cached| cached.region.is_some()) {
+            return None;
+        }
+
+        // Freed regions can be too fragmented to fit the glyph even with
+        // nothing left to evict; start packing from scratch
+        self.clear();
+        self.allocate(width, height).map(Some)
+    }
+
+    /// Evict the least-recently-used glyph that occupies atlas space and
+    /// wasn't used this frame
+    fn evict_least_recently_used(&mut self) -> bool {
+        let frame_start = self.frame_start;
+        let Some((&key, region)) = self
+            .glyphs
+            .iter()
+            .filter(|(_, cached)| cached.last_used <= frame_start)
+            .filter_map(|(key, cached)| cached.region.map(|region| (key, region, cached.last_used)))
+            .min_by_key(|(_, _, last_used)| *last_used)
+            .map(|(key, region, _)| (key, region))
+        else {
+            return false;
+        };
+
+        self.glyphs.remove(&key);
+        self.clear_region(region);
+        self.free_regions.push(region);
+        self.generation += 1;
+        true
+    }
+
+    /// Zero a region's pixels so stale glyph edges can't bleed into neighbours
+    fn clear_region(&mut self, region: AtlasRegion) {
+        for y in region.y..(region.y + region.height).min(ATLAS_SIZE) {
+            let start = ((y * ATLAS_SIZE + region.x) * 4) as usize;
+            let end = ((y * ATLAS_SIZE + (region.x + region.width).min(ATLAS_SIZE)) * 4) as usize;
+            self.pixels[start..end].fill(0);
+        }
+        self.dirty = true;
+    }
+
+    /// Rasterize a glyph using cosmic_text/swash, returning it with the index
+    /// of the font in the chain that supplied it
+    fn rasterize_with_cosmic(&mut self, key: GlyphKey) -> Option<(RasterizedGlyph, usize)> {
         let font_size = key.font_size_tenths as f32 / 10.0;
         let character = key.character;
 
@@ -266,35 +558,32 @@ impl GlyphAtlas {
             return None;
         }
 
-        // Use configured font if available, otherwise fall back to system monospace
-        let font_id = if let Some(id) = self.configured_font_id {
-            id
-        } else {
-            let db = self.font_system.db();
-            // Find a monospace font first for code editing
-            db.faces().find_map(|face| {
-                if face.monospaced {
-                    Some(face.id)
-                } else {
-                    None
+        // Use the first font in the chain (configured or system monospace,
+        // then the fallbacks) that has a glyph for this character
+        let (font_index, mut font_id, mut glyph_id) = self.resolve_font(character)?;
+
+        // Prefer a real bold/italic face of the primary font; otherwise the
+        // regular glyph is emboldened/slanted after rasterizing
+        let mut synthetic_style = key.style;
+        if key.style != FontStyle::Regular && font_index == 0 {
+            if let Some(style_font_id) = self.style_font_id(key.style) {
+                let style_glyph = self
+                    .font_system
+                    .get_font(style_font_id)
+                    .map(|font| font.as_swash().charmap().map(character))
+                    .filter(|&id| id != 0 || character == ' ');
+                if let Some(style_glyph) = style_glyph {
+                    font_id = style_font_id;
+                    glyph_id = style_glyph;
+                    synthetic_style = FontStyle::Regular;
                 }
-            }).or_else(|| {
-                // Fall back to any font
-                db.faces().next().map(|f| f.id)
-            })?
-        };
+            }
+        }
 
         // Get the font
         let font = self.font_system.get_font(font_id)?;
         let swash_font = font.as_swash();
 
-        // Get glyph ID for this character
-        let glyph_id = swash_font.charmap().map(character);
-        if glyph_id == 0 && character != ' ' {
-            // No glyph for this character, try fallback
-            return None;
-        }
-
         // Rasterize at higher resolution for crisp text on HiDPI displays
         let scaled_font_size = font_size * DPI_SCALE;
 
@@ -318,14 +607,14 @@ impl GlyphAtlas {
 
         // Handle empty glyphs (like space)
         if image.placement.width == 0 || image.placement.height == 0 {
-            return Some(RasterizedGlyph {
+            return Some((RasterizedGlyph {
                 width: 0,
                 height: 0,
                 bearing_x: 0.0,
                 bearing_y: 0.0,
                 advance,
                 pixels: Vec::new(),
-            });
+            }, font_index));
         }
 
         // Convert to our format - keep the high-res size for the atlas
@@ -354,32 +643,48 @@ impl GlyphAtlas {
             }
         };
 
-        Some(RasterizedGlyph {
+        let glyph = RasterizedGlyph {
             width,
             height,
             bearing_x,
             bearing_y,
             advance,
             pixels,
-        })
+        };
+        Some((GlyphRasterizer::apply_style(glyph, synthetic_style, font_size, DPI_SCALE), font_index))
     }
 
-    /// Allocate space in the atlas using shelf packing
-    fn allocate(&mut self, width: u32, height: u32) -> Option<(u32, u32)> {
-        if width == 0 || height == 0 {
-            return Some((0, 0));
-        }
-
+    /// Allocate space in the atlas, reusing freed regions before shelf packing
+    fn allocate(&mut self, width: u32, height: u32) -> Option<AtlasRegion> {
         let padded_width = width + GLYPH_PADDING;
         let padded_height = height + GLYPH_PADDING;
 
+        // Reuse a freed region, returning what's left of its width to the free list
+        if let Some(index) = self
+            .free_regions
+            .iter()
+            .position(|free| free.width >= padded_width && free.height >= padded_height)
+        {
+            let free = self.free_regions.swap_remove(index);
+            if free.width > padded_width {
+                self.free_regions.push(AtlasRegion {
+                    x: free.x + padded_width,
+                    width: free.width - padded_width,
+                    ..free
+                });
+            }
+            return Some(AtlasRegion { width: padded_width, ..free });
+        }
+
+        let region = |x, y| AtlasRegion { x, y, width: padded_width, height: padded_height };
+
         // Try to fit in an existing row
         for row in &mut self.rows {
             if row.height >= padded_height && row.x_cursor + padded_width <= ATLAS_SIZE {
                 let x = row.x_cursor;
                 let y = row.y;
                 row.x_cursor += padded_width;
-                return Some((x, y));
+                return Some(region(x, y));
             }
         }
 
@@ -392,7 +697,7 @@ impl GlyphAtlas {
                 height: padded_height,
                 x_cursor: padded_width,
             });
-            return Some((0, y));
+            return Some(region(0, y));
         }
 
         // Atlas is full
@@ -451,9 +756,12 @@ impl GlyphAtlas {
     /// Clear the atlas (e.g., when font changes)
     pub fn clear(&mut self) {
         self.glyphs.clear();
+        self.pending.clear();
         self.rows.clear();
         self.current_y = 0;
+        self.free_regions.clear();
         self.pixels.fill(0);
+        self.generation += 1;
         self.dirty = true;
     }
 
@@ -464,7 +772,7 @@ impl GlyphAtlas {
 
     /// Get cached glyph info
     pub fn get(&self, key: &GlyphKey) -> Option<&GlyphInfo> {
-        self.glyphs.get(key)
+        self.glyphs.get(key).map(|cached| &cached.info)
     }
 }
 
@@ -485,26 +793,198 @@ pub struct GlyphRasterizer;
 
 impl GlyphRasterizer {
     /// Rasterize a character to a bitmap (fallback)
-    pub fn rasterize(character: char, font_size: f32) -> Option<RasterizedGlyph> {
+    ///
+    /// The placeholder is `char_width` wide so it stays on the editor's grid.
+    pub fn rasterize(character: char, font_size: f32, char_width: f32, style: FontStyle) -> Option<RasterizedGlyph> {
         // Skip control characters
         if character.is_control() && character != '\t' {
             return None;
         }
 
-        // Simple monospace approximation for fallback
-        let char_width = (font_size * 0.6).ceil() as u32;
+        let char_width = char_width.ceil() as u32;
         let char_height = font_size.ceil() as u32;
 
         // Create a simple filled rectangle (placeholder)
         let pixels = vec![200u8; (char_width * char_height) as usize];
 
-        Some(RasterizedGlyph {
+        let glyph = RasterizedGlyph {
             width: char_width.max(1),
             height: char_height.max(1),
             bearing_x: 0.0,
             bearing_y: font_size * 0.8,
             advance: char_width as f32,
             pixels,
-        })
+        };
+        Some(Self::apply_style(glyph, style, font_size, 1.0))
+    }
+
+    /// Synthesize bold and italic from a regular glyph
+    ///
+    /// `scale` is bitmap pixels per logical pixel. Bold smears coverage to the
+    /// right; italic shears rows around the baseline. The advance is unchanged
+    /// so styled text stays on the monospace grid.
+    pub fn apply_style(mut glyph: RasterizedGlyph, style: FontStyle, font_size: f32, scale: f32) -> RasterizedGlyph {
+        if glyph.width == 0 || glyph.height == 0 {
+            return glyph;
+        }
+        let coverage = |glyph: &RasterizedGlyph, x: u32, y: u32| {
+            glyph.pixels.get((y * glyph.width + x) as usize).copied().unwrap_or(0)
+        };
+
+        if style.is_bold() {
+            let strength = ((font_size * scale * SYNTHETIC_BOLD_STRENGTH).round() as u32).max(1);
+            let width = glyph.width + strength;
+            let mut pixels = vec![0u8; (width * glyph.height) as usize];
+            for y in 0..glyph.height {
+                for x in 0..width {
+                    let from = x.saturating_sub(strength);
+                    let to = x.min(glyph.width - 1);
+                    pixels[(y * width + x) as usize] = (from..=to).map(|sx| coverage(&glyph, sx, y)).max().unwrap_or(0);
+                }
+            }
+            glyph.width = width;
+            glyph.pixels = pixels;
+        }
+
+        if style.is_italic() {
+            // Rows shift right in proportion to their height above the baseline
+            let top = glyph.bearing_y * scale;
+            let shift = |y: u32| ((top - y as f32 - 0.5) * SYNTHETIC_ITALIC_SLANT).round() as i32;
+            let min_shift = shift(glyph.height - 1);
+            let width = glyph.width + (shift(0) - min_shift) as u32;
+            let mut pixels = vec![0u8; (width * glyph.height) as usize];
+            for y in 0..glyph.height {
+                let dx = (shift(y) - min_shift) as u32;
+                for x in 0..glyph.width {
+                    pixels[(y * width + x + dx) as usize] = coverage(&glyph, x, y);
+                }
+            }
+            glyph.bearing_x += min_shift as f32 / scale;
+            glyph.width = width;
+            glyph.pixels = pixels;
+        }
+
+        glyph
+    }
+
+    /// Ascent and descent matching the fallback glyph placement
+    pub fn fallback_metrics(font_size: f32) -> (f32, f32) {
+        (font_size * 0.8, font_size * 0.2)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_missing_glyph_rendered_from_fallback_font() {
+        // FiraMono-Medium covers Latin Extended-B, FiraMono-Regular does not
+        let character = '\u{0180}';
+        let key = GlyphKey::new(character, 14.0);
+        let mut images = Assets::<Image>::default();
+
+        let mut atlas = GlyphAtlas::new_with_font(&mut images, Some("fonts/FiraMono-Regular.ttf"));
+        let primary_only = atlas.get_or_insert(key, || GlyphRasterizer::rasterize(character, 14.0, FontSettings::estimated_char_width(14.0), FontStyle::Regular));
+        assert_eq!(primary_only.and_then(|info| info.font), None);
+
+        let mut atlas = GlyphAtlas::new_with_fonts(
+            &mut images,
+            Some("fonts/FiraMono-Regular.ttf"),
+            &["fonts/FiraMono-Medium.ttf".to_string()],
+        );
+        let info = atlas.get_or_insert(key, || None).expect("glyph from fallback font");
+        assert_eq!(info.font, Some(1));
+        assert!(info.size.x > 0.0 && info.advance > 0.0);
+
+        // Other sizes reuse the cached resolution instead of walking the chain again
+        assert_eq!(atlas.resolved_fonts.get(&character).copied().flatten().map(|(index, _, _)| index), Some(1));
+        let larger = atlas.get_or_insert(GlyphKey::new(character, 20.0), || None).unwrap();
+        assert_eq!(larger.font, Some(1));
+
+        // Characters the primary font has still come from it
+        let info = atlas.get_or_insert(GlyphKey::new('a', 14.0), || None).unwrap();
+        assert_eq!(info.font, Some(0));
+    }
+
+    #[test]
+    fn test_full_atlas_evicts_least_recently_used_glyph() {
+        // Private-use characters have no font glyph, so the test rasterizer
+        // below supplies them: four fill the atlas (two per row, two rows)
+        let side = ATLAS_SIZE / 2 - GLYPH_PADDING;
+        let rasterized = std::cell::Cell::new(0);
+        let big_glyph = || {
+            rasterized.set(rasterized.get() + 1);
+            Some(RasterizedGlyph {
+                width: side,
+                height: side,
+                bearing_x: 0.0,
+                bearing_y: 0.0,
+                advance: 10.0,
+                pixels: vec![255; (side * side) as usize],
+            })
+        };
+        let keys: Vec<_> = ('\u{E000}'..='\u{E004}').map(|ch| GlyphKey::new(ch, 14.0)).collect();
+
+        let mut images = Assets::<Image>::default();
+        let mut atlas = GlyphAtlas::new(&mut images);
+        for key in &keys[..4] {
+            assert!(atlas.get_or_insert(*key, big_glyph).is_some());
+        }
+        let generation = atlas.generation();
+
+        // Touch the oldest glyph in a new frame so the second one becomes least recently used
+        atlas.start_frame();
+        assert!(atlas.get_or_insert(keys[0], big_glyph).is_some());
+        assert_eq!(rasterized.get(), 4);
+
+        let fifth = atlas.get_or_insert(keys[4], big_glyph).expect("room made by eviction");
+        assert!(atlas.contains(&keys[0]));
+        assert!(!atlas.contains(&keys[1]));
+        assert!(atlas.generation() > generation);
+
+        // The freed region is reused for the new glyph
+        let evicted_uv = Vec2::new(0.5, 0.0);
+        assert_eq!(fifth.uv_min, evicted_uv);
+
+        // The evicted glyph comes back on its next use instead of failing
+        assert!(atlas.get_or_insert(keys[1], big_glyph).is_some());
+        assert_eq!(rasterized.get(), 6);
+        assert!(!atlas.contains(&keys[2]));
+
+        // Once every glyph is in use this frame, nothing is evicted
+        let generation = atlas.generation();
+        atlas.get_or_insert(keys[3], big_glyph);
+        assert!(atlas.get_or_insert(keys[2], big_glyph).is_none());
+        assert_eq!(atlas.generation(), generation);
+        assert!([0, 1, 3, 4].iter().all(|&i| atlas.contains(&keys[i])));
+    }
+
+    #[test]
+    fn test_synthetic_bold_and_italic_keep_the_advance() {
+        let regular = GlyphRasterizer::rasterize('x', 14.0, FontSettings::estimated_char_width(14.0), FontStyle::Regular).unwrap();
+        let bold = GlyphRasterizer::rasterize('x', 14.0, FontSettings::estimated_char_width(14.0), FontStyle::Bold).unwrap();
+        let italic = GlyphRasterizer::rasterize('x', 14.0, FontSettings::estimated_char_width(14.0), FontStyle::Italic).unwrap();
+
+        assert!(bold.width > regular.width);
+        assert!(italic.width > regular.width);
+        assert_eq!(bold.advance, regular.advance);
+        assert_eq!(italic.advance, regular.advance);
+
+        // Italic rows above the baseline lean right: the top row starts further
+        // right than the bottom one
+        let first_lit = |y: u32| (0..italic.width).find(|&x| italic.pixels[(y * italic.width + x) as usize] > 0);
+        assert!(first_lit(0) > first_lit(italic.height - 1));
+
+        // Styles are cached as separate atlas entries
+        let mut images = Assets::<Image>::default();
+        let mut atlas = GlyphAtlas::new(&mut images);
+        let ch = '\u{E000}';
+        let regular_key = GlyphKey::new(ch, 14.0);
+        let bold_key = GlyphKey::styled(ch, 14.0, FontStyle::Bold);
+        let regular_info = atlas.get_or_insert(regular_key, || GlyphRasterizer::rasterize(ch, 14.0, FontSettings::estimated_char_width(14.0), FontStyle::Regular)).unwrap();
+        let bold_info = atlas.get_or_insert(bold_key, || GlyphRasterizer::rasterize(ch, 14.0, FontSettings::estimated_char_width(14.0), FontStyle::Bold)).unwrap();
+        assert!(atlas.contains(&regular_key) && atlas.contains(&bold_key));
+        assert!(bold_info.size.x > regular_info.size.x);
     }
 }
@@ -17,7 +17,7 @@
 //!
 //! // Build a text batch
 //! let mut batch = GlyphBatch::new();
-//! batch.add_string("Hello, World!", 0.0, 0.0, 14.0, Color::WHITE, &mut atlas);
+//! batch.add_string("Hello, World!", 0.0, 0.0, &font, Color::WHITE, &mut atlas);
 //! ```
 
 mod atlas;
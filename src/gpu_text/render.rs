@@ -13,6 +13,8 @@ use bevy::shader::ShaderRef;
 use bevy::asset::RenderAssetUsages;
 
 use super::atlas::{GlyphAtlas, GlyphKey};
+use crate::display_map::TabMap;
+use crate::settings::{FontSettings, FontStyle};
 
 /// A single glyph instance for GPU rendering
 #[derive(Clone, Copy, Debug, Default)]
@@ -62,15 +64,16 @@ impl GlyphBatch {
         character: char,
         x: f32,
         y: f32,
-        font_size: f32,
+        font: &FontSettings,
         color: Color,
         atlas: &mut GlyphAtlas,
     ) -> f32 {
+        let (font_size, char_width) = (font.size, font.char_width);
         let key = GlyphKey::new(character, font_size);
 
         // Get or rasterize the glyph
         let glyph_info = atlas.get_or_insert(key, || {
-            super::atlas::GlyphRasterizer::rasterize(character, font_size)
+            super::atlas::GlyphRasterizer::rasterize(character, font_size, char_width, FontStyle::Regular)
         });
 
         if let Some(info) = glyph_info {
@@ -84,7 +87,7 @@ impl GlyphBatch {
             info.advance
         } else {
             // Fallback advance for missing glyphs
-            font_size * 0.6
+            char_width
         }
     }
 
@@ -94,21 +97,23 @@ impl GlyphBatch {
         text: &str,
         mut x: f32,
         y: f32,
-        font_size: f32,
+        font: &FontSettings,
         color: Color,
         atlas: &mut GlyphAtlas,
     ) -> f32 {
         let start_x = x;
+        let char_width = font.char_width;
+        let tab_map = TabMap::default();
         for ch in text.chars() {
             if ch == '\n' || ch == '\r' {
                 continue;
             }
             if ch == '\t' {
-                // Tab = 4 spaces
-                x += font_size * 0.6 * 4.0;
+                let col = ((x - start_x) / char_width).round() as u32;
+                x = start_x + tab_map.next_tab_stop(col) as f32 * char_width;
                 continue;
             }
-            x += self.add_char(ch, x, y, font_size, color, atlas);
+            x += self.add_char(ch, x, y, font, color, atlas);
         }
         x - start_x
     }
@@ -195,12 +200,20 @@ impl Plugin for GpuTextPlugin {
     fn build(&self, app: &mut App) {
         app.add_plugins(Material2dPlugin::<TextMaterial>::default())
             .init_resource::<TextRenderState>()
-            .add_systems(Startup, setup_gpu_text);
+            .add_systems(Startup, setup_gpu_text)
+            .add_systems(First, start_atlas_frame);
         // Note: update_atlas_texture is called from the main plugin's system chain
         // to ensure it runs AFTER update_gpu_text_display populates the atlas
     }
 }
 
+/// Protect glyphs drawn during this frame from eviction
+fn start_atlas_frame(atlas: Option<ResMut<GlyphAtlas>>) {
+    if let Some(mut atlas) = atlas {
+        atlas.start_frame();
+    }
+}
+
 /// System to update the atlas texture - must run after glyphs are added
 pub fn update_atlas_texture(
     mut atlas: ResMut<GlyphAtlas>,
@@ -218,7 +231,7 @@ fn setup_gpu_text(
     font: Res<crate::settings::FontSettings>,
 ) {
     // Create the glyph atlas with the configured font
-    let atlas = GlyphAtlas::new_with_font(&mut images, Some(&font.family));
+    let atlas = GlyphAtlas::new_with_fonts(&mut images, Some(&font.family), &font.fallback_fonts);
 
     // Create the text material
     let material = TextMaterial {
@@ -241,6 +254,7 @@ pub struct TextBatchBuilder<'a> {
     #[allow(dead_code)]
     line_height: f32,
     char_width: f32,
+    tab_map: TabMap,
 }
 
 impl<'a> TextBatchBuilder<'a> {
@@ -249,16 +263,24 @@ impl<'a> TextBatchBuilder<'a> {
         atlas: &'a mut GlyphAtlas,
         font_size: f32,
         line_height: f32,
+        char_width: f32,
     ) -> Self {
         Self {
             batch,
             atlas,
             font_size,
             line_height,
-            char_width: font_size * 0.6,
+            char_width,
+            tab_map: TabMap::default(),
         }
     }
 
+    /// Expand tabs to stops every `tab_size` columns instead of the default 4
+    pub fn with_tab_size(mut self, tab_size: u32) -> Self {
+        self.tab_map.set_tab_size(tab_size);
+        self
+    }
+
     /// Add a line of text with syntax highlighting segments
     pub fn add_line(
         &mut self,
@@ -270,7 +292,8 @@ impl<'a> TextBatchBuilder<'a> {
         for (text, color) in segments {
             for ch in text.chars() {
                 if ch == '\t' {
-                    x += self.char_width * 4.0;
+                    let col = ((x - start_x) / self.char_width).round() as u32;
+                    x = start_x + self.tab_map.next_tab_stop(col) as f32 * self.char_width;
                     continue;
                 }
                 if ch == '\n' || ch == '\r' {
@@ -279,7 +302,7 @@ impl<'a> TextBatchBuilder<'a> {
 
                 let key = GlyphKey::new(ch, self.font_size);
                 if let Some(info) = self.atlas.get_or_insert(key, || {
-                    super::atlas::GlyphRasterizer::rasterize(ch, self.font_size)
+                    super::atlas::GlyphRasterizer::rasterize(ch, self.font_size, self.char_width, FontStyle::Regular)
                 }) {
                     self.batch.push(GlyphInstance {
                         position: Vec2::new(x + info.offset.x, y - info.offset.y),
@@ -1,10 +1,10 @@
 use crate::types::*;
-use crate::settings::IndentationSettings;
+use crate::settings::{CursorSettings, IndentationSettings};
 #[cfg(feature = "lsp")]
 use crate::settings::LspSettings;
 use super::keybindings::EditorAction;
 use super::cursor::*;
-use arboard::Clipboard;
+use super::clipboard::{get_system_clipboard, set_system_clipboard};
 
 #[cfg(feature = "lsp")]
 use crate::lsp;
@@ -17,6 +17,75 @@ pub struct ActionResult {
     pub horizontal_move: bool,
 }
 
+/// What a press of Escape dismissed
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EscapeOutcome {
+    /// A completion or hover popup was closed
+    ClosedPopup,
+    /// Secondary cursors were removed, keeping the primary
+    CollapsedCursors,
+    /// The selection was collapsed to the cursor
+    CollapsedSelection,
+    /// The goto-line prompt was closed
+    ClosedGotoLine,
+    /// The active find was cleared
+    ClearedFind,
+    /// Nothing to dismiss
+    Nothing,
+}
+
+/// Apply one step of the Escape priority chain
+///
+/// Each press dismisses only the first applicable item, in this order:
+/// popups (closed by `close_popups` in the LSP layer before this is called),
+/// multiple cursors, selection, goto-line prompt, then find.
+pub fn escape(
+    state: &mut CodeEditorState,
+    find_state: &mut FindState,
+    goto_line_state: &mut GotoLineState,
+) -> EscapeOutcome {
+    if state.has_multiple_cursors() {
+        state.clear_secondary_cursors();
+        return EscapeOutcome::CollapsedCursors;
+    }
+    if state.selection_start.is_some() && state.selection_start != state.selection_end {
+        state.selection_start = None;
+        state.selection_end = None;
+        state.pending_update = true;
+        return EscapeOutcome::CollapsedSelection;
+    }
+    if goto_line_state.active {
+        goto_line_state.clear();
+        return EscapeOutcome::ClosedGotoLine;
+    }
+    if find_state.active {
+        find_state.clear();
+        state.selection_start = None;
+        state.selection_end = None;
+        state.pending_update = true;
+        return EscapeOutcome::ClearedFind;
+    }
+    EscapeOutcome::Nothing
+}
+
+/// First step of the Escape chain: close an open completion or hover popup
+///
+/// Returns `None` if neither popup was open.
+#[cfg(feature = "lsp")]
+pub fn close_popups(
+    completion_state: &mut lsp::CompletionState,
+    hover_state: &mut lsp::HoverState,
+) -> Option<EscapeOutcome> {
+    if !completion_state.visible && !hover_state.visible {
+        return None;
+    }
+    completion_state.visible = false;
+    completion_state.filter.clear();
+    completion_state.scroll_offset = 0;
+    hover_state.reset();
+    Some(EscapeOutcome::ClosedPopup)
+}
+
 /// Insert a character at cursor position
 pub fn insert_char(state: &mut CodeEditorState, c: char) {
     // Delete selection if exists
@@ -29,30 +98,57 @@ pub fn insert_char(state: &mut CodeEditorState, c: char) {
 
 /// Insert a closing character at cursor position without moving the cursor
 /// Used for bracket/quote auto-close
+///
+/// Recorded as part of the typing run that inserted the opener, so one undo
+/// removes both.
 pub fn insert_closing_char(state: &mut CodeEditorState, c: char) {
-    let cursor_pos = state.cursor_pos.min(state.rope.len_chars());
+    insert_closing_text(state, c.encode_utf8(&mut [0; 4]));
 
-    // Record for incremental parsing
-    #[cfg(feature = "tree-sitter")]
-    {
-        let start_byte = state.rope.char_to_byte(cursor_pos);
-        let char_len = c.len_utf8();
-        state.record_edit(start_byte, start_byte, start_byte + char_len);
+    // Mark only current line as dirty (not entire rest of file!)
+    let line_idx = state.rope.char_to_line(state.cursor_pos.min(state.rope.len_chars()));
+    let new_line_count = state.rope.len_lines();
+    state.dirty_lines = Some(line_idx..(line_idx + 1).min(new_line_count));
+}
+
+/// Wrap the selection in `open` and `close` as one undo step, keeping the
+/// wrapped text selected
+///
+/// Returns false (changing nothing) if there is no non-empty selection.
+pub fn surround_selection(state: &mut CodeEditorState, open: char, close: char) -> bool {
+    let (Some(anchor), Some(head)) = (state.selection_start, state.selection_end) else {
+        return false;
+    };
+    let (start, end) = (anchor.min(head), anchor.max(head));
+    if start == end {
+        return false;
     }
 
-    // Insert at cursor position
-    state.rope.insert_char(cursor_pos, c);
+    state.sync_cursors_from_primary();
+    let before = SelectionCollection::from_cursors(&state.cursors);
+    let cursor_before = state.cursor_pos;
+
+    // Closer first, so the opener's position stays valid
+    let mut operations = Vec::with_capacity(2);
+    for (position, c) in [(end, close), (start, open)] {
+        state.insert_text_at(position, &c.to_string());
+        operations.push(EditOperation {
+            removed_text: String::new(),
+            inserted_text: c.to_string(),
+            position,
+            cursor_before,
+            cursor_after: cursor_before + 1,
+            kind: EditKind::Other,
+        });
+    }
 
-    // Don't move cursor - it stays between the brackets
-    // OPTIMIZATION: Use debounce instead of immediate update
-    state.pending_update = true;
-    state.content_version += 1;
+    state.cursor_pos = cursor_before + 1;
+    state.selection_start = Some(anchor + 1);
+    state.selection_end = Some(head + 1);
+    state.sync_cursors_from_primary();
 
-    // Mark only current line as dirty (not entire rest of file!)
-    let line_idx = state.rope.char_to_line(cursor_pos);
-    let new_line_count = state.rope.len_lines();
-    state.dirty_lines = Some(line_idx..(line_idx + 1).min(new_line_count));
-    state.previous_line_count = new_line_count;
+    let after = SelectionCollection::from_cursors(&state.cursors);
+    state.record_transaction(operations, before, after);
+    true
 }
 
 /// Get the closing bracket for an opening bracket
@@ -79,6 +175,722 @@ pub fn should_skip_auto_close(state: &CodeEditorState, closing: char) -> bool {
     state.rope.char(cursor_pos) == closing
 }
 
+/// Whether the text ending at `end` matches `pattern`
+fn text_ends_with(rope: &ropey::Rope, end: usize, pattern: &str) -> bool {
+    let len = pattern.chars().count();
+    len > 0 && end >= len && end <= rope.len_chars() && rope.slice(end - len..end) == pattern
+}
+
+/// Whether the text starting at `start` matches `pattern`
+fn text_starts_with(rope: &ropey::Rope, start: usize, pattern: &str) -> bool {
+    let len = pattern.chars().count();
+    len > 0 && start + len <= rope.len_chars() && rope.slice(start..start + len) == pattern
+}
+
+/// Get the closer for a multi-character opener just typed before the cursor
+///
+/// The longest matching opener wins, so `"""` is preferred over a `"` pair.
+pub fn get_multi_char_closing<'a>(state: &CodeEditorState, pairs: &'a [(String, String)]) -> Option<&'a str> {
+    usable_pairs(pairs)
+        .filter(|(open, _)| text_ends_with(&state.rope, state.cursor_pos, open))
+        .max_by_key(|(open, _)| open.chars().count())
+        .map(|(_, close)| close.as_str())
+}
+
+/// Multi-character pairs with both sides non-empty; the others are ignored
+fn usable_pairs(pairs: &[(String, String)]) -> impl Iterator<Item = &(String, String)> {
+    pairs.iter().filter(|(open, close)| !open.is_empty() && !close.is_empty())
+}
+
+/// Check if typing `c` should step over a multi-character closer at the cursor
+///
+/// True when the cursor sits at or inside a closer whose next character is `c`,
+/// so typing `*/` inside `/* |*/` moves past the existing closer.
+pub fn should_skip_multi_char_close(state: &CodeEditorState, c: char, pairs: &[(String, String)]) -> bool {
+    let cursor_pos = state.cursor_pos;
+    if cursor_pos >= state.rope.len_chars() || state.rope.char(cursor_pos) != c {
+        return false;
+    }
+    usable_pairs(pairs).any(|(_, close)| {
+        let len = close.chars().count();
+        (cursor_pos.saturating_sub(len - 1)..=cursor_pos)
+            .any(|start| text_starts_with(&state.rope, start, close))
+    })
+}
+
+/// Insert a multi-character closer at the cursor without moving the cursor
+///
+/// Like `insert_closing_char`, it joins the undo step of the opener.
+pub fn insert_closing_text(state: &mut CodeEditorState, text: &str) {
+    let cursor_pos = state.cursor_pos.min(state.rope.len_chars());
+    state.insert_text_at(cursor_pos, text);
+    state.record_history(EditOperation {
+        removed_text: String::new(),
+        inserted_text: text.to_string(),
+        position: cursor_pos,
+        cursor_before: cursor_pos,
+        cursor_after: cursor_pos,
+        kind: EditKind::Insert,
+    });
+}
+
+/// Delete an empty multi-character pair around the cursor, e.g. `/*|*/`
+///
+/// Returns false (changing nothing) if the cursor is not between an opener and its closer.
+pub fn delete_multi_char_pair(state: &mut CodeEditorState, pairs: &[(String, String)]) -> bool {
+    let cursor_before = state.cursor_pos;
+    let Some((open, close)) = usable_pairs(pairs)
+        .filter(|(open, close)| {
+            text_ends_with(&state.rope, cursor_before, open)
+                && text_starts_with(&state.rope, cursor_before, close)
+        })
+        .max_by_key(|(open, _)| open.chars().count())
+    else {
+        return false;
+    };
+
+    let start = cursor_before - open.chars().count();
+    let end = cursor_before + close.chars().count();
+    let removed_text = state.rope.slice(start..end).to_string();
+
+    state.remove_range(start, end);
+    state.cursor_pos = start;
+    state.sync_cursors_from_primary();
+    state.record_history(EditOperation {
+        removed_text,
+        inserted_text: String::new(),
+        position: start,
+        cursor_before,
+        cursor_after: start,
+        kind: EditKind::Other,
+    });
+    true
+}
+
+/// Paste `text` at the cursor, replacing the selection, as one undo step
+///
+/// A whole-line entry from the kill ring (see `KillRing::push_line`) is
+/// inserted above the cursor's line when nothing is selected.
+pub fn paste_text(state: &mut CodeEditorState, kill_ring: &mut KillRing, text: String) {
+    let cursor_before = state.cursor_pos;
+    let mut deleted_text = String::new();
+    let paste_position;
+
+    // A whole-line copy goes in above the current line instead of splitting it
+    let full_line = kill_ring.full_line_paste
+        && kill_ring.is_full_line(0)
+        && kill_ring.top() == Some(text.as_str())
+        && state.selection_start.is_none();
+
+    // Delete selection if any
+    if let (Some(start), Some(end)) = (state.selection_start, state.selection_end) {
+        let (start, end) = if start < end { (start, end) } else { (end, start) };
+        let start = start.min(state.rope.len_chars());
+        let end = end.min(state.rope.len_chars());
+
+        deleted_text = state.rope.slice(start..end).to_string();
+
+        state.remove_range(start, end);
+        state.cursor_pos = start;
+        state.selection_start = None;
+        state.selection_end = None;
+        paste_position = start;
+    } else {
+        let cursor = state.cursor_pos.min(state.rope.len_chars());
+        paste_position = if full_line {
+            state.rope.line_to_char(state.rope.char_to_line(cursor))
+        } else {
+            cursor
+        };
+    }
+
+    // Insert pasted text
+    #[cfg(feature = "tree-sitter")]
+    let start_byte = state.rope.char_to_byte(paste_position);
+    state.insert_text_at(paste_position, &text);
+
+    // One combined edit for incremental parsing (delete + insert)
+    #[cfg(feature = "tree-sitter")]
+    {
+        state.pending_tree_sitter_edit = Some((start_byte, start_byte + deleted_text.len(), start_byte + text.len()));
+    }
+    let paste_end = paste_position + text.chars().count();
+    // After a whole-line paste the cursor stays on its (now shifted) line
+    state.cursor_pos = if full_line {
+        paste_end + cursor_before.saturating_sub(paste_position)
+    } else {
+        paste_end
+    };
+    state.needs_update = true;
+    state.pending_update = false;
+
+    // Record for undo (combined delete selection + insert paste)
+    state.record_history(EditOperation {
+        removed_text: deleted_text,
+        inserted_text: text.clone(),
+        position: paste_position,
+        cursor_before,
+        cursor_after: state.cursor_pos,
+        kind: EditKind::Paste, // Paste is always its own transaction
+    });
+
+    kill_ring.record_yank(paste_position, paste_end, state.content_version, true);
+}
+
+/// Text each cursor contributes to a multi-cursor copy, in document order
+///
+/// When any cursor has a selection only the selections are copied;
+/// otherwise every cursor copies its line, without the line break.
+fn cursor_copy_texts(state: &CodeEditorState) -> Vec<String> {
+    let mut cursors = state.cursors.clone();
+    cursors.sort_by_key(|cursor| cursor.selection_start());
+    if cursors.iter().any(|cursor| cursor.has_selection()) {
+        cursors
+            .iter()
+            .filter(|cursor| cursor.has_selection())
+            .map(|cursor| state.rope.slice(cursor.selection_start()..cursor.selection_end()).to_string())
+            .collect()
+    } else {
+        let mut lines: Vec<usize> = cursors.iter().map(|cursor| state.rope.char_to_line(cursor.position)).collect();
+        lines.dedup();
+        lines
+            .into_iter()
+            .map(|line| state.rope.line(line).to_string().trim_end_matches(['\r', '\n']).to_string())
+            .collect()
+    }
+}
+
+/// Delete every line holding a cursor, with its line break, as one undo step
+///
+/// Cursors move to the start of the line that takes their line's place.
+/// Returns false if there was nothing to delete.
+fn delete_cursor_lines(state: &mut CodeEditorState) -> bool {
+    let before = SelectionCollection::from_cursors(&state.cursors);
+    let len_chars = state.rope.len_chars();
+    let line_count = state.rope.len_lines();
+
+    // Runs of adjacent lines, as (first, last)
+    let mut blocks: Vec<(usize, usize)> = Vec::new();
+    for line in cursor_lines(state) {
+        match blocks.last_mut() {
+            Some(block) if block.1 + 1 == line => block.1 = line,
+            _ => blocks.push((line, line)),
+        }
+    }
+    let ranges: Vec<(usize, usize)> = blocks
+        .into_iter()
+        .map(|(first, last)| {
+            let start = state.rope.line_to_char(first);
+            if last + 1 < line_count {
+                (start, state.rope.line_to_char(last + 1))
+            } else {
+                // The last line has no line break: take the one before the run instead
+                (start.saturating_sub(1), len_chars)
+            }
+        })
+        .filter(|(start, end)| start < end)
+        .collect();
+    if ranges.is_empty() {
+        return false;
+    }
+
+    let cursor_before = state.cursor_pos;
+    let mut operations = Vec::with_capacity(ranges.len());
+    for &(start, end) in ranges.iter().rev() {
+        let removed_text = state.rope.slice(start..end).to_string();
+        state.remove_range(start, end);
+        operations.push(EditOperation {
+            removed_text,
+            inserted_text: String::new(),
+            position: start,
+            cursor_before,
+            cursor_after: start,
+            kind: EditKind::Other,
+        });
+    }
+
+    let mut removed = 0;
+    let mut cursors = Vec::with_capacity(ranges.len());
+    for &(start, end) in &ranges {
+        let pos = (start - removed).min(state.rope.len_chars());
+        cursors.push(Cursor::new(state.rope.line_to_char(state.rope.char_to_line(pos))));
+        removed += end - start;
+    }
+    state.cursors = cursors;
+    state.sort_and_merge_cursors();
+    state.sync_primary_cursor();
+    state.selection_start = None;
+    state.selection_end = None;
+
+    let after = SelectionCollection::from_cursors(&state.cursors);
+    state.record_transaction(operations, before, after);
+    true
+}
+
+/// Paste at every cursor as one undo step
+///
+/// When the text has one line per cursor (ignoring a trailing line break),
+/// each cursor gets its own line in document order, so a multi-cursor copy
+/// pastes back one piece per cursor. Otherwise every cursor gets the whole text.
+pub fn paste_at_cursors(state: &mut CodeEditorState, text: &str) {
+    state.sync_cursors_from_primary();
+    state.sort_and_merge_cursors();
+    let lines: Vec<&str> = text
+        .strip_suffix('\n')
+        .unwrap_or(text)
+        .split('\n')
+        .map(|line| line.strip_suffix('\r').unwrap_or(line))
+        .collect();
+    if lines.len() == state.cursors.len() {
+        state.insert_each_at_cursors(&lines);
+    } else {
+        state.insert_at_cursors(text);
+    }
+}
+
+/// Duplicate every selection, selecting the new copies, as one undo step
+///
+/// A non-empty selection is copied right after itself. An empty selection
+/// duplicates its line below, keeping the cursor's column on the copy.
+/// Edits are applied right to left so each one's position stays valid.
+pub fn duplicate_selection(state: &mut CodeEditorState) {
+    state.sync_cursors_from_primary();
+    let before = SelectionCollection::from_cursors(&state.cursors);
+    let mut cursors = state.cursors.clone();
+    cursors.sort_by_key(|c| c.selection_start());
+
+    // (insert position, inserted text, new cursor) per cursor, left to right
+    let edits: Vec<(usize, String, Cursor)> = cursors
+        .iter()
+        .map(|cursor| {
+            let (start, end) = (cursor.selection_start(), cursor.selection_end());
+            if start < end {
+                let text = state.rope.slice(start..end).to_string();
+                let len = end - start;
+                // Keep the selection's direction on the copy
+                let copy = if cursor.position == end {
+                    Cursor::with_selection(end + len, end)
+                } else {
+                    Cursor::with_selection(end, end + len)
+                };
+                (end, text, copy)
+            } else {
+                let line = state.rope.char_to_line(cursor.position);
+                let line_start = state.rope.line_to_char(line);
+                let line_text = state.rope.line(line).to_string();
+                let column = cursor.position - line_start;
+                if line_text.ends_with('\n') {
+                    let len = line_text.chars().count();
+                    (line_start, line_text, Cursor::new(line_start + len + column))
+                } else {
+                    // Last line: put the break in front of the copy
+                    let line_end = line_start + line_text.chars().count();
+                    let text = format!("\n{}", line_text);
+                    (line_end, text, Cursor::new(line_end + 1 + column))
+                }
+            }
+        })
+        .collect();
+
+    let mut operations = Vec::with_capacity(edits.len());
+    for (position, text, _) in edits.iter().rev() {
+        state.insert_text_at(*position, text);
+        operations.push(EditOperation {
+            removed_text: String::new(),
+            inserted_text: text.clone(),
+            position: *position,
+            cursor_before: state.cursor_pos,
+            cursor_after: state.cursor_pos,
+            kind: EditKind::Other,
+        });
+    }
+
+    // Shift each new cursor by what was inserted to its left
+    let mut shift = 0;
+    let mut new_cursors = Vec::with_capacity(edits.len());
+    for (_, text, cursor) in &edits {
+        new_cursors.push(Cursor {
+            position: cursor.position + shift,
+            anchor: cursor.anchor.map(|a| a + shift),
+        });
+        shift += text.chars().count();
+    }
+    state.cursors = new_cursors;
+    state.sync_primary_cursor();
+
+    let after = SelectionCollection::from_cursors(&state.cursors);
+    state.record_transaction(operations, before, after);
+}
+
+/// Lines touched by any cursor or selection, top to bottom
+fn cursor_lines(state: &CodeEditorState) -> std::collections::BTreeSet<usize> {
+    let mut lines = std::collections::BTreeSet::new();
+    for cursor in &state.cursors {
+        let (first, last) = cursor_line_span(&state.rope, cursor);
+        lines.extend(first..=last);
+    }
+    lines
+}
+
+/// Whether any cursor has a non-empty selection
+fn has_any_selection(state: &CodeEditorState) -> bool {
+    state.selection_start.is_some_and(|start| Some(start) != state.selection_end)
+        || state.cursors.iter().skip(1).any(Cursor::has_selection)
+}
+
+/// Add one indent level to every line touched by a cursor or selection
+///
+/// `IndentationSettings::indent_unit` is inserted at the start of each line
+/// as one undo step. Selections keep covering the same text, and a selection
+/// starting at column 0 still starts there. Returns false if there was
+/// nothing to indent.
+pub fn indent_lines(state: &mut CodeEditorState, indentation: &IndentationSettings) -> bool {
+    state.sync_cursors_from_primary();
+    let before = SelectionCollection::from_cursors(&state.cursors);
+
+    let unit = indentation.indent_unit();
+    let unit_len = unit.chars().count();
+    let starts: Vec<usize> = cursor_lines(state)
+        .into_iter()
+        .map(|line| state.rope.line_to_char(line))
+        .collect();
+    if starts.is_empty() || unit_len == 0 {
+        return false;
+    }
+
+    let remap = |pos: usize| pos + starts.iter().filter(|&&start| pos > start).count() * unit_len;
+
+    let mut operations = Vec::with_capacity(starts.len());
+    for &start in starts.iter().rev() {
+        state.insert_text_at(start, &unit);
+        operations.push(EditOperation {
+            removed_text: String::new(),
+            inserted_text: unit.clone(),
+            position: start,
+            cursor_before: state.cursor_pos,
+            cursor_after: state.cursor_pos,
+            kind: EditKind::Other,
+        });
+    }
+
+    for cursor in &mut state.cursors {
+        cursor.position = remap(cursor.position);
+        cursor.anchor = cursor.anchor.map(remap);
+    }
+    state.sync_primary_cursor();
+
+    let after = SelectionCollection::from_cursors(&state.cursors);
+    state.record_transaction(operations, before, after);
+    true
+}
+
+/// Remove one indent level from every line touched by a cursor or selection
+///
+/// A leading tab, or up to `indent_size` leading spaces, is removed per line,
+/// as one undo step. Returns false if no line was indented.
+pub fn outdent_lines(state: &mut CodeEditorState, indentation: &IndentationSettings) -> bool {
+    state.sync_cursors_from_primary();
+    let before = SelectionCollection::from_cursors(&state.cursors);
+
+    // (line start, removed char count) per outdented line, top to bottom
+    let removals: Vec<(usize, usize)> = cursor_lines(state)
+        .into_iter()
+        .filter_map(|line| {
+            let line_start = state.rope.line_to_char(line);
+            let mut chars = state.rope.line(line).chars();
+            let len = match chars.next() {
+                Some('\t') => 1,
+                Some(' ') => 1 + chars.take_while(|&c| c == ' ').take(indentation.indent_size.max(1) - 1).count(),
+                _ => 0,
+            };
+            (len > 0).then_some((line_start, len))
+        })
+        .collect();
+    if removals.is_empty() {
+        return false;
+    }
+
+    let remap = |pos: usize| {
+        let removed: usize = removals
+            .iter()
+            .filter(|&&(start, _)| pos > start)
+            .map(|&(start, len)| (pos - start).min(len))
+            .sum();
+        pos - removed
+    };
+
+    let mut operations = Vec::with_capacity(removals.len());
+    for &(start, len) in removals.iter().rev() {
+        let removed_text = state.rope.slice(start..start + len).to_string();
+        state.remove_range(start, start + len);
+        operations.push(EditOperation {
+            removed_text,
+            inserted_text: String::new(),
+            position: start,
+            cursor_before: state.cursor_pos,
+            cursor_after: state.cursor_pos,
+            kind: EditKind::Other,
+        });
+    }
+
+    for cursor in &mut state.cursors {
+        cursor.position = remap(cursor.position);
+        cursor.anchor = cursor.anchor.map(remap);
+    }
+    // Cursors inside the removed indentation collapse onto the same column
+    state.sort_and_merge_cursors();
+    state.sync_primary_cursor();
+
+    let after = SelectionCollection::from_cursors(&state.cursors);
+    state.record_transaction(operations, before, after);
+    true
+}
+
+/// Move cursors on lines hidden by a fold to the fold's header line
+///
+/// The column is kept where the header line allows; selections of moved
+/// cursors are dropped.
+pub fn move_cursors_out_of_folds(state: &mut CodeEditorState, fold_state: &FoldState) {
+    state.sync_cursors_from_primary();
+    let rope = &state.rope;
+    for cursor in &mut state.cursors {
+        let line = rope.char_to_line(cursor.position);
+        let visible = fold_state.visible_line_for(line);
+        if visible == line {
+            continue;
+        }
+        let column = cursor.position - rope.line_to_char(line);
+        let header_start = rope.line_to_char(visible);
+        let header_len = rope.line(visible).len_chars();
+        let header_end = if visible + 1 < rope.len_lines() { header_start + header_len - 1 } else { header_start + header_len };
+        cursor.position = (header_start + column).min(header_end);
+        cursor.anchor = None;
+    }
+    state.sort_and_merge_cursors();
+    state.sync_primary_cursor();
+}
+
+/// First and last line touched by a cursor's selection
+///
+/// A selection ending at the start of a line does not touch that line.
+fn cursor_line_span(rope: &ropey::Rope, cursor: &Cursor) -> (usize, usize) {
+    let (start, end) = (cursor.selection_start(), cursor.selection_end());
+    let first = rope.char_to_line(start);
+    let mut last = rope.char_to_line(end);
+    if end > start && last > first && rope.line_to_char(last) == end {
+        last -= 1;
+    }
+    (first, last)
+}
+
+/// Duplicate the lines touched by each cursor below themselves, as one undo step
+///
+/// Cursors sharing lines duplicate them once; every cursor (and its
+/// selection) moves onto the copy of its line.
+pub fn duplicate_lines(state: &mut CodeEditorState) {
+    state.sync_cursors_from_primary();
+    let before = SelectionCollection::from_cursors(&state.cursors);
+    let mut cursors = state.cursors.clone();
+    cursors.sort_by_key(|c| c.selection_start());
+
+    // Line blocks (first, last) per cursor, merged where they overlap
+    let mut blocks: Vec<(usize, usize)> = Vec::new();
+    let mut cursor_blocks = Vec::with_capacity(cursors.len());
+    for cursor in &cursors {
+        let (first, last) = cursor_line_span(&state.rope, cursor);
+        match blocks.last_mut() {
+            Some(block) if first <= block.1 => block.1 = block.1.max(last),
+            _ => blocks.push((first, last)),
+        }
+        cursor_blocks.push(blocks.len() - 1);
+    }
+
+    // (insert position, inserted text, distance from the block to its copy) per block
+    let edits: Vec<(usize, String, usize)> = blocks
+        .iter()
+        .map(|&(first, last)| {
+            let block_start = state.rope.line_to_char(first);
+            let block_end = state.rope.line_to_char(last + 1);
+            let text = state.rope.slice(block_start..block_end).to_string();
+            if text.ends_with('\n') {
+                (block_end, text, block_end - block_start)
+            } else {
+                // Last line: put the break in front of the copy
+                (block_end, format!("\n{}", text), block_end - block_start + 1)
+            }
+        })
+        .collect();
+
+    let mut operations = Vec::with_capacity(edits.len());
+    for (position, text, _) in edits.iter().rev() {
+        state.insert_text_at(*position, text);
+        operations.push(EditOperation {
+            removed_text: String::new(),
+            inserted_text: text.clone(),
+            position: *position,
+            cursor_before: state.cursor_pos,
+            cursor_after: state.cursor_pos,
+            kind: EditKind::Other,
+        });
+    }
+
+    // Each cursor moves onto its block's copy, plus whatever was inserted above it
+    let mut shifts = Vec::with_capacity(edits.len());
+    let mut shift = 0;
+    for (_, text, to_copy) in &edits {
+        shifts.push(shift + to_copy);
+        shift += text.chars().count();
+    }
+    state.cursors = cursors
+        .iter()
+        .zip(&cursor_blocks)
+        .map(|(cursor, &block)| Cursor {
+            position: cursor.position + shifts[block],
+            anchor: cursor.anchor.map(|a| a + shifts[block]),
+        })
+        .collect();
+    state.sync_primary_cursor();
+
+    let after = SelectionCollection::from_cursors(&state.cursors);
+    state.record_transaction(operations, before, after);
+}
+
+/// Comment or uncomment the lines touched by every cursor, as one undo step
+///
+/// If every non-blank line already starts with `token` (after indentation),
+/// the token and one following space are removed. Otherwise `token` and a
+/// space are inserted after each non-blank line's indentation, so a second
+/// toggle of a mixed selection uncomments it uniformly.
+/// Returns false if there was nothing to change.
+pub fn toggle_line_comment(state: &mut CodeEditorState, token: &str) -> bool {
+    if token.is_empty() {
+        return false;
+    }
+    state.sync_cursors_from_primary();
+    let before = SelectionCollection::from_cursors(&state.cursors);
+
+    let mut lines: Vec<usize> = state
+        .cursors
+        .iter()
+        .flat_map(|cursor| {
+            let (first, last) = cursor_line_span(&state.rope, cursor);
+            first..=last
+        })
+        .collect();
+    lines.sort_unstable();
+    lines.dedup();
+
+    // (line start + indentation, indentation width, text after it) per non-blank line
+    let targets: Vec<(usize, usize, String)> = lines
+        .iter()
+        .filter_map(|&line| {
+            let text = state.rope.line(line).to_string();
+            let content = text.trim_start_matches([' ', '\t']);
+            if content.trim().is_empty() {
+                return None;
+            }
+            let indent = text.chars().count() - content.chars().count();
+            Some((state.rope.line_to_char(line) + indent, indent, content.to_string()))
+        })
+        .collect();
+    if targets.is_empty() {
+        return false;
+    }
+
+    let uncomment = targets.iter().all(|(_, _, content)| content.starts_with(token));
+    let token_len = token.chars().count();
+    // (position, removed length, inserted text, at column 0) per line, top to bottom
+    let edits: Vec<(usize, usize, String, bool)> = targets
+        .iter()
+        .map(|(position, indent, content)| {
+            if uncomment {
+                let space = content[token.len()..].starts_with(' ') as usize;
+                (*position, token_len + space, String::new(), *indent == 0)
+            } else {
+                (*position, 0, format!("{} ", token), *indent == 0)
+            }
+        })
+        .collect();
+
+    let mut operations = Vec::with_capacity(edits.len());
+    for (position, removed, inserted, _) in edits.iter().rev() {
+        let removed_text = state.rope.slice(*position..position + removed).to_string();
+        state.remove_range(*position, position + removed);
+        state.insert_text_at(*position, inserted);
+        operations.push(EditOperation {
+            removed_text,
+            inserted_text: inserted.clone(),
+            position: *position,
+            cursor_before: state.cursor_pos,
+            cursor_after: state.cursor_pos,
+            kind: EditKind::Other,
+        });
+    }
+
+    // Shift every cursor past the edits before it; a cursor at column 0 stays put
+    let map = |offset: usize| -> usize {
+        let mut mapped = offset as isize;
+        for (position, removed, inserted, at_column_zero) in &edits {
+            if offset > *position || (offset == *position && *removed == 0 && !at_column_zero) {
+                let end = position + removed;
+                mapped += inserted.chars().count() as isize - (offset.min(end) - position) as isize;
+            }
+        }
+        mapped as usize
+    };
+    let cursors: Vec<Cursor> = state
+        .cursors
+        .iter()
+        .map(|c| Cursor { position: map(c.position), anchor: c.anchor.map(map) })
+        .collect();
+    state.cursors = cursors;
+    state.sync_primary_cursor();
+
+    let after = SelectionCollection::from_cursors(&state.cursors);
+    state.record_transaction(operations, before, after);
+    true
+}
+
+/// Number of spaces Backspace should remove to reach the previous indent stop
+///
+/// Returns `None` (plain single-char Backspace) unless `backspace_dedents` and
+/// `use_spaces` are set and only spaces precede the cursor on its line.
+fn backspace_dedent_len(state: &CodeEditorState, indentation: &IndentationSettings) -> Option<usize> {
+    if !indentation.backspace_dedents || !indentation.use_spaces || indentation.indent_size == 0 {
+        return None;
+    }
+    let cursor = state.cursor_pos.min(state.rope.len_chars());
+    let line_start = state.rope.line_to_char(state.rope.char_to_line(cursor));
+    let col = cursor - line_start;
+    if col == 0 || !state.rope.slice(line_start..cursor).chars().all(|c| c == ' ') {
+        return None;
+    }
+    let len = match col % indentation.indent_size {
+        0 => indentation.indent_size,
+        partial => partial,
+    };
+    (len > 1).then_some(len)
+}
+
+/// Remove `len` spaces before the cursor as a single undo step
+fn dedent_backspace(state: &mut CodeEditorState, len: usize) {
+    let cursor_before = state.cursor_pos;
+    let start = cursor_before - len;
+    let removed_text = state.rope.slice(start..cursor_before).to_string();
+
+    state.remove_range(start, cursor_before);
+    state.cursor_pos = start;
+    state.sync_cursors_from_primary();
+    state.record_history(EditOperation {
+        removed_text,
+        inserted_text: String::new(),
+        position: start,
+        cursor_before,
+        cursor_after: start,
+        kind: EditKind::Other,
+    });
+}
+
 /// Delete selected text (with undo recording)
 pub fn delete_selection(state: &mut CodeEditorState) {
     delete_selection_with_history(state, true);
@@ -99,21 +911,14 @@ fn delete_selection_with_history(state: &mut CodeEditorState, record_history: bo
         let deleted_text: String = state.rope.slice(start..end).chars().collect();
 
         // Remove selected text
-        let start_byte = state.rope.char_to_byte(start);
-        let end_byte = state.rope.char_to_byte(end);
-
-        // Record edit for incremental parsing
-        #[cfg(feature = "tree-sitter")]
-        state.record_edit(start_byte, end_byte, start_byte);
-
-        state.rope.remove(start_byte..end_byte);
+        state.remove_range(start, end);
 
         // Move cursor to start of selection
         state.cursor_pos = start;
 
         // Record for undo
         if record_history && !deleted_text.is_empty() {
-            state.history.record(EditOperation {
+            state.record_history(EditOperation {
                 removed_text: deleted_text,
                 inserted_text: String::new(),
                 position: start,
@@ -129,17 +934,17 @@ fn delete_selection_with_history(state: &mut CodeEditorState, record_history: bo
 
         state.needs_update = true;
         state.pending_update = false;
-        state.content_version += 1;
-        state.dirty_lines = None;
-        state.previous_line_count = state.rope.len_lines();
     }
 }
 
 /// Apply selected completion item
+///
+/// Snippet items are expanded and start a `SnippetSession` at their first tab stop.
 #[cfg(feature = "lsp")]
 pub fn apply_completion(
     state: &mut CodeEditorState,
     completion_state: &mut lsp::CompletionState,
+    snippet: &mut SnippetSession,
 ) {
     // Get filtered items and select from that list
     let filtered = completion_state.filtered_items();
@@ -150,26 +955,25 @@ pub fn apply_completion(
 
         // Ensure valid range
         if start <= end && end <= state.rope.len_chars() {
-            let start_byte = state.rope.char_to_byte(start);
-            let end_byte = state.rope.char_to_byte(end);
-            let new_end_byte = start_byte + insert_text.len();
-
-            // Record edit for incremental parsing (remove + insert = replace)
-            state.record_edit(start_byte, end_byte, new_end_byte);
+            if item.is_snippet() {
+                snippet.expand(state, start..end, &insert_text);
+            } else {
+                #[cfg(feature = "tree-sitter")]
+                let (start_byte, end_byte) = (state.rope.char_to_byte(start), state.rope.char_to_byte(end));
 
-            state.rope.remove(start_byte..end_byte);
-            state.rope.insert(start, &insert_text);
+                state.remove_range(start, end);
+                state.insert_text_at(start, &insert_text);
 
-            state.cursor_pos = start + insert_text.chars().count();
-            state.needs_update = true;
-            state.pending_update = false;
-            state.content_version += 1;
+                // One combined edit for incremental parsing (remove + insert = replace)
+                #[cfg(feature = "tree-sitter")]
+                {
+                    state.pending_tree_sitter_edit = Some((start_byte, end_byte, start_byte + insert_text.len()));
+                }
 
-            // Mark lines as dirty for highlighting update
-            let line_idx = state.rope.char_to_line(start);
-            let new_line_count = state.rope.len_lines();
-            state.dirty_lines = Some(line_idx..(line_idx + 1).min(new_line_count));
-            state.previous_line_count = new_line_count;
+                state.cursor_pos = start + insert_text.chars().count();
+                state.needs_update = true;
+                state.pending_update = false;
+            }
         }
     }
     completion_state.visible = false;
@@ -315,35 +1119,100 @@ pub fn send_did_change(
     }
 }
 
+/// Ask the server to format the document ahead of a save (format-on-save)
+///
+/// Returns false, so the caller saves right away, when the buffer is
+/// read-only, no document is open or the server cannot format it. A save
+/// already waiting keeps waiting.
+#[cfg(feature = "lsp")]
+pub fn request_format_on_save(
+    state: &CodeEditorState,
+    indentation: &IndentationSettings,
+    lsp_client: &lsp::LspClient,
+    lsp_sync: &mut lsp::LspSyncState,
+    format_on_save: &mut lsp::state::FormatOnSaveState,
+) -> bool {
+    // A read-only buffer can't take the edits
+    if state.read_only {
+        return false;
+    }
+    if format_on_save.is_pending() {
+        return true;
+    }
+    let Some(uri) = lsp_sync.document_uri.clone() else {
+        return false;
+    };
+    if !lsp_client.is_ready()
+        || !lsp_client.capabilities.supports_formatting()
+        || lsp_client.has_pending(lsp::messages::RequestType::Format)
+    {
+        return false;
+    }
+
+    // The server has to format the text being saved
+    send_did_change(state, lsp_client, lsp_sync);
+    let options = lsp_types::FormattingOptions {
+        tab_size: indentation.indent_size as u32,
+        insert_spaces: indentation.use_spaces,
+        ..Default::default()
+    };
+    if !lsp_client.send_request(lsp::LspMessage::Format { uri, options }) {
+        return false;
+    }
+    format_on_save.start(state.content_version);
+    true
+}
+
 /// Core action execution - shared between LSP and non-LSP builds
-fn execute_action_core(
+pub(crate) fn execute_action_core(
     state: &mut CodeEditorState,
     action: EditorAction,
     indentation: &IndentationSettings,
+    cursor: &CursorSettings,
     find_state: &mut FindState,
     goto_line_state: &mut GotoLineState,
     fold_state: &mut FoldState,
+    kill_ring: &mut KillRing,
 ) -> ActionResult {
     let mut result = ActionResult {
         text_changed: false,
         horizontal_move: false,
     };
 
+    if state.read_only && action.modifies_text() {
+        return result;
+    }
+
     match action {
         EditorAction::InsertNewline => {
             insert_char(state, '\n');
             result.text_changed = true;
         }
+        EditorAction::InsertTab if has_any_selection(state) => {
+            result.text_changed = indent_lines(state, indentation);
+        }
         EditorAction::InsertTab => {
             for _ in 0..indentation.tab_width {
                 insert_char(state, ' ');
             }
             result.text_changed = true;
         }
+        EditorAction::IndentSelection => {
+            result.text_changed = indent_lines(state, indentation);
+        }
+        EditorAction::Outdent | EditorAction::OutdentSelection => {
+            result.text_changed = outdent_lines(state, indentation);
+        }
 
+        EditorAction::DeleteBackward | EditorAction::DeleteForward if state.has_multiple_cursors() => {
+            state.sync_cursors_from_primary();
+            result.text_changed = state.delete_at_cursors(action == EditorAction::DeleteForward);
+        }
         EditorAction::DeleteBackward => {
             if state.selection_start.is_some() {
                 delete_selection(state);
+            } else if let Some(len) = backspace_dedent_len(state, indentation) {
+                dedent_backspace(state, len);
             } else {
                 state.delete_backward();
             }
@@ -373,8 +1242,37 @@ fn execute_action_core(
             }
             result.text_changed = true;
         }
+        EditorAction::DuplicateSelection => {
+            duplicate_selection(state);
+            result.text_changed = true;
+        }
+        EditorAction::DuplicateLine => {
+            duplicate_lines(state);
+            result.text_changed = true;
+        }
+        // Needs the language's comment token, handled in `handle_keyboard_input`
+        EditorAction::ToggleLineComment => {}
+        EditorAction::MoveLineUp => {
+            if move_lines(state, fold_state, false) {
+                result.text_changed = true;
+            }
+        }
+        EditorAction::MoveLineDown => {
+            if move_lines(state, fold_state, true) {
+                result.text_changed = true;
+            }
+        }
         EditorAction::DeleteLine => {
-            // TODO: Implement line deletion
+            let version_before = state.content_version;
+            if let Some((deleted_text, start, end)) = delete_line(state) {
+                // Consecutive line deletes accumulate into one kill ring entry
+                if deleted_text.ends_with('\n') {
+                    kill_ring.kill_lines(deleted_text, start, end, version_before, state.content_version);
+                } else {
+                    kill_ring.kill(deleted_text, start, end, version_before, state.content_version);
+                }
+                result.text_changed = true;
+            }
         }
 
         EditorAction::MoveCursorLeft => {
@@ -392,29 +1290,23 @@ fn execute_action_core(
         EditorAction::MoveCursorUp => {
             state.selection_start = None;
             state.selection_end = None;
-            move_cursor_up(state);
+            move_cursor_vertical_visible(state, fold_state, false);
         }
         EditorAction::MoveCursorDown => {
             state.selection_start = None;
             state.selection_end = None;
-            move_cursor_down(state);
+            move_cursor_vertical_visible(state, fold_state, true);
         }
         EditorAction::MoveCursorWordLeft => {
-            state.selection_start = None;
-            state.selection_end = None;
             move_cursor_word_left(state);
             result.horizontal_move = true;
         }
         EditorAction::MoveCursorWordRight => {
-            state.selection_start = None;
-            state.selection_end = None;
             move_cursor_word_right(state);
             result.horizontal_move = true;
         }
         EditorAction::MoveCursorLineStart => {
-            state.selection_start = None;
-            state.selection_end = None;
-            move_cursor_line_start(state);
+            move_cursors_home(state, false, cursor.smart_home);
         }
         EditorAction::MoveCursorLineEnd => {
             state.selection_start = None;
@@ -454,28 +1346,22 @@ fn execute_action_core(
         }
         EditorAction::SelectUp => {
             init_selection(state);
-            move_cursor_up(state);
+            move_cursor_vertical_visible(state, fold_state, false);
             state.selection_end = Some(state.cursor_pos);
         }
         EditorAction::SelectDown => {
             init_selection(state);
-            move_cursor_down(state);
+            move_cursor_vertical_visible(state, fold_state, true);
             state.selection_end = Some(state.cursor_pos);
         }
         EditorAction::SelectWordLeft => {
-            init_selection(state);
-            move_cursor_word_left(state);
-            state.selection_end = Some(state.cursor_pos);
+            move_cursors_with(state, true, find_word_boundary_left);
         }
         EditorAction::SelectWordRight => {
-            init_selection(state);
-            move_cursor_word_right(state);
-            state.selection_end = Some(state.cursor_pos);
+            move_cursors_with(state, true, find_word_boundary_right);
         }
         EditorAction::SelectLineStart => {
-            init_selection(state);
-            move_cursor_line_start(state);
-            state.selection_end = Some(state.cursor_pos);
+            move_cursors_home(state, true, cursor.smart_home);
         }
         EditorAction::SelectLineEnd => {
             init_selection(state);
@@ -491,16 +1377,64 @@ fn execute_action_core(
             state.selection_start = None;
             state.selection_end = None;
         }
+        EditorAction::Escape => {
+            escape(state, find_state, goto_line_state);
+        }
 
+        EditorAction::Copy if state.has_multiple_cursors() => {
+            state.sync_cursors_from_primary();
+            // Without selections each cursor copies its line, when whole-line copies are on
+            if kill_ring.full_line_paste || state.cursors.iter().any(Cursor::has_selection) {
+                let text = cursor_copy_texts(state).join("\n");
+                kill_ring.push(text.clone());
+                set_system_clipboard(&text);
+            }
+        }
+        EditorAction::Cut if state.has_multiple_cursors() => {
+            state.sync_cursors_from_primary();
+            if state.cursors.iter().any(Cursor::has_selection) {
+                let text = cursor_copy_texts(state).join("\n");
+                set_system_clipboard(&text);
+                kill_ring.push(text);
+                state.insert_at_cursors("");
+                result.text_changed = true;
+            } else if kill_ring.full_line_paste {
+                // Without selections each cursor cuts its whole line
+                let text = cursor_copy_texts(state).join("\n");
+                if delete_cursor_lines(state) {
+                    set_system_clipboard(&text);
+                    kill_ring.push(text);
+                    result.text_changed = true;
+                }
+            }
+        }
+        EditorAction::Paste if state.has_multiple_cursors() => {
+            let clipboard_text = get_system_clipboard();
+            if clipboard_text.is_some() && clipboard_text.as_deref() != kill_ring.top() {
+                kill_ring.push(clipboard_text.clone().unwrap_or_default());
+            }
+            if let Some(text) = clipboard_text.or_else(|| kill_ring.top().map(str::to_string)) {
+                paste_at_cursors(state, &text);
+                result.text_changed = true;
+            }
+        }
         EditorAction::Copy => {
             if let (Some(s), Some(e)) = (state.selection_start, state.selection_end) {
                 let (start, end) = if s < e { (s, e) } else { (e, s) };
                 let start = start.min(state.rope.len_chars());
                 let end = end.min(state.rope.len_chars());
                 let text = state.rope.slice(start..end).to_string();
-                if let Ok(mut clipboard) = Clipboard::new() {
-                    let _ = clipboard.set_text(text);
+                kill_ring.push(text.clone());
+                set_system_clipboard(&text);
+            } else if kill_ring.full_line_paste {
+                // Without a selection, copy the whole line
+                let line_idx = state.rope.char_to_line(state.cursor_pos.min(state.rope.len_chars()));
+                let mut text = state.rope.line(line_idx).to_string();
+                if !text.ends_with('\n') {
+                    text.push('\n');
                 }
+                kill_ring.push_line(text.clone());
+                set_system_clipboard(&text);
             }
         }
         EditorAction::Cut => {
@@ -510,25 +1444,17 @@ fn execute_action_core(
                 let end = end.min(state.rope.len_chars());
                 let selected_text = state.rope.slice(start..end).to_string();
                 let cursor_before = state.cursor_pos;
+                let version_before = state.content_version;
 
                 // Copy to clipboard
-                if let Ok(mut clipboard) = Clipboard::new() {
-                    let _ = clipboard.set_text(selected_text.clone());
-                }
+                set_system_clipboard(&selected_text);
 
                 // Delete the selection
-                let start_byte = state.rope.char_to_byte(start);
-                let end_byte = state.rope.char_to_byte(end);
-
-                // Record edit for incremental parsing
-                #[cfg(feature = "tree-sitter")]
-                state.record_edit(start_byte, end_byte, start_byte);
-
-                state.rope.remove(start_byte..end_byte);
+                state.remove_range(start, end);
                 state.cursor_pos = start;
 
                 // Record for undo
-                state.history.record(EditOperation {
+                state.record_history(EditOperation {
                     removed_text: selected_text,
                     inserted_text: String::new(),
                     position: start,
@@ -541,96 +1467,85 @@ fn execute_action_core(
                 state.selection_end = None;
                 state.needs_update = true;
                 state.pending_update = false;
-                state.content_version += 1;
 
-                let new_line_count = state.rope.len_lines();
-                let line_idx = state.rope.char_to_line(start);
-                state.dirty_lines = Some(line_idx..(line_idx + 1).min(new_line_count));
-                state.previous_line_count = new_line_count;
+                kill_ring.kill(selected_text, start, end, version_before, state.content_version);
 
                 result.text_changed = true;
+            } else if kill_ring.full_line_paste {
+                // Without a selection, cut the whole line
+                let version_before = state.content_version;
+                if let Some((deleted_text, start, end)) = delete_line(state) {
+                    // The last line takes the line break before it; the entry
+                    // still reads as one line ending in a line break
+                    let text = if deleted_text.ends_with('\n') {
+                        deleted_text
+                    } else {
+                        format!("{}\n", deleted_text.strip_prefix('\n').unwrap_or(&deleted_text))
+                    };
+                    set_system_clipboard(&text);
+                    kill_ring.kill_lines(text, start, end, version_before, state.content_version);
+                    result.text_changed = true;
+                }
             }
         }
         EditorAction::Paste => {
-            {
-                if let Ok(mut clipboard) = Clipboard::new() {
-                    if let Ok(text) = clipboard.get_text() {
-                        let cursor_before = state.cursor_pos;
-                        let mut deleted_text = String::new();
-                        let paste_position;
-
-                        // Delete selection if any
-                        if let (Some(start), Some(end)) = (state.selection_start, state.selection_end) {
-                            let (start, end) = if start < end { (start, end) } else { (end, start) };
-                            let start = start.min(state.rope.len_chars());
-                            let end = end.min(state.rope.len_chars());
-
-                            deleted_text = state.rope.slice(start..end).to_string();
-
-                            let start_byte = state.rope.char_to_byte(start);
-                            let end_byte = state.rope.char_to_byte(end);
-                            let new_end_byte = start_byte + text.len();
-
-                            // Record combined edit for incremental parsing (delete + insert)
-                            #[cfg(feature = "tree-sitter")]
-                            state.record_edit(start_byte, end_byte, new_end_byte);
-
-                            state.rope.remove(start_byte..end_byte);
-                            state.cursor_pos = start;
-                            state.selection_start = None;
-                            state.selection_end = None;
-                            paste_position = start;
-                        } else {
-                            paste_position = state.cursor_pos.min(state.rope.len_chars());
-
-                            // Record insert-only edit for incremental parsing
-                            #[cfg(feature = "tree-sitter")]
-                            {
-                                let start_byte = state.rope.char_to_byte(paste_position);
-                                state.record_edit(start_byte, start_byte, start_byte + text.len());
-                            }
-                        }
+            // Prefer the system clipboard, falling back to the kill ring
+            let clipboard_text = get_system_clipboard();
+            if clipboard_text.is_some() && clipboard_text.as_deref() != kill_ring.top() {
+                // Text copied outside the editor joins the ring too
+                kill_ring.push(clipboard_text.clone().unwrap_or_default());
+            }
+            if let Some(text) = clipboard_text.or_else(|| kill_ring.top().map(str::to_string)) {
+                paste_text(state, kill_ring, text);
+                result.text_changed = true;
+            }
+        }
+        EditorAction::PasteCycle => {
+            // Replace the text inserted by the last paste with the previous ring entry
+            if let Some((start, end, text)) = kill_ring.cycle(state.content_version) {
+                let cursor_before = state.cursor_pos;
+                let removed_text: String = state.rope.slice(start..end).chars().collect();
 
-                        // Insert pasted text
-                        let line_idx = state.rope.char_to_line(paste_position);
-
-                        state.rope.insert(paste_position, &text);
-                        state.cursor_pos = paste_position + text.chars().count();
-                        state.needs_update = true;
-                        state.pending_update = false;
-                        state.content_version += 1;
-
-                        // Record for undo (combined delete selection + insert paste)
-                        state.history.record(EditOperation {
-                            removed_text: deleted_text,
-                            inserted_text: text.clone(),
-                            position: paste_position,
-                            cursor_before,
-                            cursor_after: state.cursor_pos,
-                            kind: EditKind::Paste, // Paste is always its own transaction
-                        });
-
-                        let new_line_count = state.rope.len_lines();
-                        state.dirty_lines = Some(line_idx..(line_idx + 1).min(new_line_count));
-                        state.previous_line_count = new_line_count;
-
-                        result.text_changed = true;
-                    }
-                }
+                state.remove_range(start, end);
+                state.insert_text_at(start, &text);
+                state.cursor_pos = start + text.chars().count();
+                state.selection_start = None;
+                state.selection_end = None;
+                state.needs_update = true;
+
+                state.record_history(EditOperation {
+                    removed_text,
+                    inserted_text: text,
+                    position: start,
+                    cursor_before,
+                    cursor_after: state.cursor_pos,
+                    kind: EditKind::Paste,
+                });
+
+                kill_ring.record_yank(start, state.cursor_pos, state.content_version, false);
+                result.text_changed = true;
             }
         }
 
         EditorAction::Undo => {
-            if state.undo() {
+            if state.undo_with(cursor.restore_on_undo) {
                 result.text_changed = true;
             }
         }
         EditorAction::Redo => {
-            if state.redo() {
+            if state.redo_with(cursor.restore_on_undo) {
                 result.text_changed = true;
             }
         }
 
+        EditorAction::ToggleCaseSensitive => {
+            find_state.toggle_case_sensitive(&state.rope);
+            state.pending_update = true;
+        }
+        EditorAction::ToggleWholeWord => {
+            find_state.toggle_whole_word(&state.rope);
+            state.pending_update = true;
+        }
         EditorAction::Find => {
             // Search for selected text or word at cursor
             if let (Some(start), Some(end)) = (state.selection_start, state.selection_end) {
@@ -675,34 +1590,33 @@ fn execute_action_core(
                     }
                 }
             }
+            // Open the find input (even with an empty query) and give it focus
+            find_state.active = true;
+            find_state.find_focused = true;
         }
         EditorAction::FindNext => {
+            find_state.ensure_searched(&state.rope);
             if find_state.active && !find_state.matches.is_empty() {
                 find_state.find_next(state.cursor_pos);
-                // Move cursor to the match
-                if let Some(m) = find_state.current_match() {
-                    state.cursor_pos = m.start;
-                    state.selection_start = Some(m.start);
-                    state.selection_end = Some(m.end);
-                    state.pending_update = true;
-                }
+                find_state.select_current_match(state);
             }
         }
         EditorAction::FindPrevious => {
+            find_state.ensure_searched(&state.rope);
             if find_state.active && !find_state.matches.is_empty() {
                 find_state.find_previous(state.cursor_pos);
-                // Move cursor to the match
-                if let Some(m) = find_state.current_match() {
-                    state.cursor_pos = m.start;
-                    state.selection_start = Some(m.start);
-                    state.selection_end = Some(m.end);
-                    state.pending_update = true;
-                }
+                find_state.select_current_match(state);
             }
         }
         EditorAction::Replace => {
             // TODO: Implement replace
         }
+        EditorAction::ReplaceNext => {
+            result.text_changed = find_state.replace_current(state);
+        }
+        EditorAction::ReplaceAll => {
+            result.text_changed = find_state.replace_all(state) > 0;
+        }
         EditorAction::RequestCompletion => {
             // Handled by LSP wrapper
         }
@@ -719,6 +1633,21 @@ fn execute_action_core(
                 goto_line_state.input.clear();
             }
         }
+        EditorAction::GoToLastEdit => {
+            state.goto_last_edit();
+        }
+        EditorAction::GoToNextEdit => {
+            state.goto_next_edit();
+        }
+        EditorAction::ToggleBookmark => {
+            state.toggle_bookmark();
+        }
+        EditorAction::NextBookmark => {
+            state.goto_next_bookmark();
+        }
+        EditorAction::PrevBookmark => {
+            state.goto_prev_bookmark();
+        }
 
         // Multi-cursor actions
         EditorAction::AddCursorAtNextOccurrence => {
@@ -726,15 +1655,19 @@ fn execute_action_core(
             state.sync_cursors_from_primary();
             state.add_cursor_at_next_occurrence();
         }
+        EditorAction::SelectNextOccurrence => {
+            state.select_next_occurrence();
+        }
+        EditorAction::SelectAllOccurrences => {
+            state.select_all_occurrences();
+        }
         EditorAction::AddCursorAbove => {
-            // Add cursor on the line above
             state.sync_cursors_from_primary();
-            add_cursor_above(state);
+            add_cursor_vertically(state, fold_state, true);
         }
         EditorAction::AddCursorBelow => {
-            // Add cursor on the line below
             state.sync_cursors_from_primary();
-            add_cursor_below(state);
+            add_cursor_vertically(state, fold_state, false);
         }
         EditorAction::ClearSecondaryCursors => {
             // Clear all but primary cursor
@@ -742,6 +1675,8 @@ fn execute_action_core(
                 state.clear_secondary_cursors();
             }
         }
+        // Recording and playback need the MacroRecorder, see `handle_keyboard_input`
+        EditorAction::ToggleMacroRecording | EditorAction::PlayMacro => {}
 
         // Code folding actions
         EditorAction::ToggleFold => {
@@ -761,73 +1696,198 @@ fn execute_action_core(
         }
         EditorAction::FoldAll => {
             fold_state.fold_all();
+            move_cursors_out_of_folds(state, fold_state);
             state.pending_update = true;
         }
         EditorAction::UnfoldAll => {
             fold_state.unfold_all();
             state.pending_update = true;
         }
+        EditorAction::FoldAllComments => {
+            fold_state.fold_all_of_kind(FoldKind::Comment);
+            move_cursors_out_of_folds(state, fold_state);
+            state.pending_update = true;
+        }
+        EditorAction::FoldAllImports => {
+            fold_state.fold_all_of_kind(FoldKind::Imports);
+            move_cursors_out_of_folds(state, fold_state);
+            state.pending_update = true;
+        }
+        EditorAction::FoldLevel1
+        | EditorAction::FoldLevel2
+        | EditorAction::FoldLevel3
+        | EditorAction::FoldLevel4
+        | EditorAction::FoldLevel5
+        | EditorAction::FoldLevel6
+        | EditorAction::FoldLevel7
+        | EditorAction::FoldLevel8
+        | EditorAction::FoldLevel9 => {
+            let level = action.fold_level().unwrap_or(1);
+            fold_state.fold_to_level(level, &state.rope, indentation.indent_size);
+            move_cursors_out_of_folds(state, fold_state);
+            state.pending_update = true;
+        }
+        EditorAction::FoldSelection => {
+            if let (Some(sel_start), Some(sel_end)) = (state.selection_start, state.selection_end) {
+                let (start, end) = if sel_start <= sel_end { (sel_start, sel_end) } else { (sel_end, sel_start) };
+                let start_line = state.rope.char_to_line(start);
+                let mut end_line = state.rope.char_to_line(end);
+                // A selection ending at column 0 doesn't include that line
+                if end_line > start_line && state.rope.line_to_char(end_line) == end {
+                    end_line -= 1;
+                }
+
+                if fold_state.add_manual_fold(start_line, end_line) {
+                    // Keep the cursor on the visible header line of the fold
+                    state.cursor_pos = state.rope.line_to_char(start_line);
+                    state.selection_start = None;
+                    state.selection_end = None;
+                    state.pending_update = true;
+                }
+            }
+        }
 
         // File operations are handled in keyboard.rs before execute_action is called
         // These emit events for the host app to handle
         EditorAction::Save | EditorAction::Open => {
             // No-op here - handled via events in keyboard input system
         }
+
+        // Handled by `handle_diagnostic_navigation`, which has access to the diagnostics
+        EditorAction::NextDiagnostic | EditorAction::PrevDiagnostic => {}
+
+        // Custom actions are dispatched to host handlers by handle_custom_actions
+        EditorAction::CustomAction(_) => {}
     }
 
     result
 }
 
-/// Add a cursor on the line above the primary cursor
-fn add_cursor_above(state: &mut CodeEditorState) {
-    if state.cursors.is_empty() {
-        return;
-    }
+/// Add a cursor one display row above the topmost cursor (`up`) or below the
+/// bottommost one, at the column of the cursor the stack grows from
+///
+/// Rows hidden by folds are skipped and the column is clamped to the target
+/// line's length. Nothing happens once the stack reaches the first/last line.
+fn add_cursor_vertically(state: &mut CodeEditorState, fold_state: &FoldState, up: bool) -> bool {
+    let (Some(min), Some(max)) = (
+        state.cursors.iter().map(|c| c.position).min(),
+        state.cursors.iter().map(|c| c.position).max(),
+    ) else {
+        return false;
+    };
+    // Repeated presses extend the stack from its far edge, keeping the column
+    // of the cursor it started from
+    let (edge, origin) = if up { (min, max) } else { (max, min) };
+
+    // With soft wrap the stack grows one display row at a time
+    let new_pos = match row_target(state, fold_state, edge, origin, up) {
+        Some(target) => target,
+        None => line_target(state, fold_state, edge, origin, up),
+    };
+    let Some(new_pos) = new_pos else {
+        return false;
+    };
 
-    // Get the primary cursor's line and column
-    let primary_pos = state.cursors[0].position;
-    let line_idx = state.rope.char_to_line(primary_pos);
+    let mut selections = SelectionCollection::from_cursors(&state.cursors);
+    selections.add_cursor(new_pos);
+    state.cursors = selections.to_cursors();
+    state.sync_primary_cursor();
+    state.pending_update = true;
+    true
+}
 
-    if line_idx == 0 {
-        // Already at top, can't go up
-        return;
+/// Position on the next visible buffer line above or below `edge`, at the
+/// visual column of `origin`; `None` at the start or end of the buffer
+fn line_target(state: &CodeEditorState, fold_state: &FoldState, edge: usize, origin: usize, up: bool) -> Option<usize> {
+    let line_count = state.rope.len_lines();
+    let mut target_line = state.rope.char_to_line(edge);
+    loop {
+        target_line = if up {
+            target_line.checked_sub(1)?
+        } else if target_line + 1 < line_count {
+            target_line + 1
+        } else {
+            return None;
+        };
+        if !fold_state.is_line_hidden(target_line) {
+            break;
+        }
     }
 
-    let line_start = state.rope.line_to_char(line_idx);
-    let col_offset = primary_pos - line_start;
+    // Match visual columns so tabs and wide characters line up
+    let tab_map = &state.display_map.tab_map;
+    let origin_line = state.rope.char_to_line(origin);
+    let origin_col = origin - state.rope.line_to_char(origin_line);
+    let visual_col = tab_map.expand_column_chars(state.rope.line(origin_line).chars(), origin_col as u32);
 
-    // Find position on the line above
-    let prev_line_start = state.rope.line_to_char(line_idx - 1);
-    let prev_line_len = state.rope.line(line_idx - 1).len_chars().saturating_sub(1); // Exclude newline
-    let new_pos = prev_line_start + col_offset.min(prev_line_len);
-
-    state.add_cursor(new_pos);
+    let target_text = state.rope.line(target_line).to_string();
+    let target_len = target_text.trim_end_matches(['\n', '\r']).chars().count();
+    let target_col = (tab_map.contract_column(&target_text, visual_col) as usize).min(target_len);
+    Some(state.rope.line_to_char(target_line) + target_col)
 }
 
-/// Add a cursor on the line below the primary cursor
-fn add_cursor_below(state: &mut CodeEditorState) {
-    if state.cursors.is_empty() {
-        return;
-    }
-
-    // Get the primary cursor's line and column
-    let primary_pos = state.cursors[0].position;
-    let line_idx = state.rope.char_to_line(primary_pos);
-
-    if line_idx + 1 >= state.rope.len_lines() {
-        // Already at bottom, can't go down
-        return;
+/// Like `line_target`, but stepping through the display map's wrapped rows
+///
+/// Returns `None` when wrapping is inactive or the map doesn't cover the
+/// cursors, and `Some(None)` at the first or last row.
+fn row_target(
+    state: &CodeEditorState,
+    fold_state: &FoldState,
+    edge: usize,
+    origin: usize,
+    up: bool,
+) -> Option<Option<usize>> {
+    let map = &state.display_map;
+    if map.wrap_width == 0 || map.rows.is_empty() {
+        return None;
     }
+    let rope = &state.rope;
+    let line_col = |pos: usize| {
+        let line = rope.char_to_line(pos);
+        (line, pos - rope.line_to_char(line))
+    };
+    let (edge_line, edge_col) = line_col(edge);
+    let (origin_line, origin_col) = line_col(origin);
+    let edge_row = display_row_for(map, edge_line, edge_col)?;
+    let origin_row = display_row_for(map, origin_line, origin_col)?;
+
+    let mut target_idx = edge_row;
+    let target = loop {
+        target_idx = if up {
+            match target_idx.checked_sub(1) {
+                Some(idx) => idx,
+                None => return Some(None),
+            }
+        } else {
+            target_idx + 1
+        };
+        match map.rows.get(target_idx) {
+            Some(row) if row.buffer_line >= rope.len_lines() => return Some(None),
+            Some(row) if fold_state.is_line_hidden(row.buffer_line) => continue,
+            Some(row) => break row,
+            None => return Some(None),
+        }
+    };
 
-    let line_start = state.rope.line_to_char(line_idx);
-    let col_offset = primary_pos - line_start;
-
-    // Find position on the line below
-    let next_line_start = state.rope.line_to_char(line_idx + 1);
-    let next_line_len = state.rope.line(line_idx + 1).len_chars().saturating_sub(1); // Exclude newline
-    let new_pos = next_line_start + col_offset.min(next_line_len);
-
-    state.add_cursor(new_pos);
+    // Visual column within the origin's row, with tab stops measured from the line start
+    let tab_map = &map.tab_map;
+    let expand = |line: usize, col: usize| tab_map.expand_column_chars(rope.line(line).chars(), col as u32);
+    let origin_start = map.rows[origin_row].start_offset;
+    let visual_col = expand(origin_line, origin_col) - expand(origin_line, origin_start);
+
+    // Only the line's last row may take the cursor past its final character
+    let ends_line = map
+        .rows
+        .get(target_idx + 1)
+        .is_none_or(|next| next.buffer_line != target.buffer_line);
+    let target_text = rope.line(target.buffer_line).to_string();
+    let text_len = target_text.trim_end_matches(['\n', '\r']).chars().count();
+    let max_col = if ends_line { target.end_offset } else { target.end_offset.saturating_sub(1) };
+    let row_visual_start = expand(target.buffer_line, target.start_offset);
+    let target_col = (tab_map.contract_column(&target_text, row_visual_start + visual_col) as usize)
+        .clamp(target.start_offset, max_col.max(target.start_offset))
+        .min(text_len);
+    Some(Some(rope.line_to_char(target.buffer_line) + target_col))
 }
 
 /// Execute an editor action (Non-LSP version)
@@ -836,9 +1896,11 @@ pub fn execute_action(
     state: &mut CodeEditorState,
     action: EditorAction,
     indentation: &IndentationSettings,
+    cursor: &CursorSettings,
     find_state: &mut FindState,
     goto_line_state: &mut GotoLineState,
     fold_state: &mut FoldState,
+    kill_ring: &mut KillRing,
 ) {
     // Handle Escape to clear multi-cursors, find mode, or goto line mode
     if action == EditorAction::ClearSelection {
@@ -860,7 +1922,7 @@ pub fn execute_action(
         }
     }
 
-    let _ = execute_action_core(state, action, indentation, find_state, goto_line_state, fold_state);
+    let _ = execute_action_core(state, action, indentation, cursor, find_state, goto_line_state, fold_state, kill_ring);
 }
 
 /// Execute an editor action (LSP version)
@@ -869,14 +1931,23 @@ pub fn execute_action(
     state: &mut CodeEditorState,
     action: EditorAction,
     indentation: &IndentationSettings,
+    cursor: &CursorSettings,
     lsp: &LspSettings,
     find_state: &mut FindState,
     goto_line_state: &mut GotoLineState,
     fold_state: &mut FoldState,
+    kill_ring: &mut KillRing,
+    snippet: &mut SnippetSession,
     lsp_client: &lsp::LspClient,
     completion_state: &mut lsp::CompletionState,
+    hover_state: &mut lsp::HoverState,
     lsp_sync: &mut lsp::LspSyncState,
 ) {
+    // Escape closes an open completion or hover popup before anything else in the chain
+    if action == EditorAction::Escape && close_popups(completion_state, hover_state).is_some() {
+        return;
+    }
+
     // Handle Escape to clear multi-cursors, goto line mode, find mode, or completion
     if action == EditorAction::ClearSelection {
         // First priority: clear secondary cursors if we have multiple
@@ -922,11 +1993,11 @@ pub fn execute_action(
                 return;
             }
             EditorAction::InsertNewline | EditorAction::InsertTab => {
-                apply_completion(state, completion_state);
+                apply_completion(state, completion_state, snippet);
                 send_did_change(state, lsp_client, lsp_sync);
                 return;
             }
-            EditorAction::ClearSelection => {
+            EditorAction::ClearSelection | EditorAction::Escape => {
                 completion_state.visible = false;
                 completion_state.filter.clear();
                 completion_state.scroll_offset = 0;
@@ -943,7 +2014,7 @@ pub fn execute_action(
     }
 
     // Execute the core action
-    let result = execute_action_core(state, action, indentation, find_state, goto_line_state, fold_state);
+    let result = execute_action_core(state, action, indentation, cursor, find_state, goto_line_state, fold_state, kill_ring);
 
     // LSP-specific post-processing: dismiss completion on horizontal move
     if result.horizontal_move {
@@ -967,4 +2038,473 @@ pub fn execute_action(
     if result.text_changed {
         send_did_change(state, lsp_client, lsp_sync);
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_escape_collapses_cursors_then_clears_find() {
+        let mut state = CodeEditorState::new("foo bar foo\n");
+        let mut find_state = FindState::default();
+        let mut goto_line_state = GotoLineState::default();
+
+        find_state.active = true;
+        find_state.query = "foo".to_string();
+        find_state.search(&state.rope);
+        state.add_cursor(8);
+        assert!(state.has_multiple_cursors());
+
+        let outcome = escape(&mut state, &mut find_state, &mut goto_line_state);
+        assert_eq!(outcome, EscapeOutcome::CollapsedCursors);
+        assert!(!state.has_multiple_cursors());
+        assert!(find_state.active);
+
+        let outcome = escape(&mut state, &mut find_state, &mut goto_line_state);
+        assert_eq!(outcome, EscapeOutcome::ClearedFind);
+        assert!(!find_state.active);
+
+        let outcome = escape(&mut state, &mut find_state, &mut goto_line_state);
+        assert_eq!(outcome, EscapeOutcome::Nothing);
+    }
+
+    #[test]
+    fn test_duplicate_selection_selects_the_copy() {
+        let mut state = CodeEditorState::new("let x = foo + 1;\n");
+        state.selection_start = Some(8);
+        state.selection_end = Some(11);
+        state.cursor_pos = 11;
+
+        duplicate_selection(&mut state);
+        assert_eq!(state.text(), "let x = foofoo + 1;\n");
+        assert_eq!((state.selection_start, state.selection_end), (Some(11), Some(14)));
+
+        // Empty selection duplicates the line instead
+        state.undo();
+        assert_eq!(state.text(), "let x = foo + 1;\n");
+        state.selection_start = None;
+        state.selection_end = None;
+        state.cursor_pos = 4;
+        duplicate_selection(&mut state);
+        assert_eq!(state.text(), "let x = foo + 1;\nlet x = foo + 1;\n");
+        assert_eq!(state.cursor_pos, 17 + 4);
+    }
+
+    #[test]
+    fn test_backspace_in_indentation_removes_one_level() {
+        let mut state = CodeEditorState::new("        foo\n");
+        state.cursor_pos = 8;
+        let indentation = IndentationSettings {
+            backspace_dedents: true,
+            ..Default::default()
+        };
+        let backspace = |state: &mut CodeEditorState| {
+            execute_action_core(
+                state,
+                EditorAction::DeleteBackward,
+                &indentation,
+                &CursorSettings::default(),
+                &mut FindState::default(),
+                &mut GotoLineState::default(),
+                &mut FoldState::default(),
+                &mut KillRing::default(),
+            );
+        };
+
+        backspace(&mut state);
+        assert_eq!(state.text(), "    foo\n");
+        assert_eq!(state.cursor_pos, 4);
+
+        // A single undo restores the whole level
+        state.undo();
+        assert_eq!(state.text(), "        foo\n");
+
+        // Past the indentation, Backspace removes one character
+        state.cursor_pos = 11;
+        backspace(&mut state);
+        assert_eq!(state.text(), "        fo\n");
+    }
+
+    #[test]
+    fn test_duplicate_line_per_cursor_keeps_cursor_on_copy() {
+        let mut state = CodeEditorState::new("one\ntwo\nthree");
+        state.cursor_pos = 1;
+        state.sync_cursors_from_primary();
+        state.add_cursor(12);
+
+        execute_action_core(
+            &mut state,
+            EditorAction::DuplicateLine,
+            &IndentationSettings::default(),
+            &CursorSettings::default(),
+            &mut FindState::default(),
+            &mut GotoLineState::default(),
+            &mut FoldState::default(),
+            &mut KillRing::default(),
+        );
+        assert_eq!(state.text(), "one\none\ntwo\nthree\nthree");
+        let positions: Vec<usize> = state.cursors.iter().map(|c| c.position).collect();
+        assert_eq!(positions, vec![5, 22]);
+
+        // One undo removes every copy
+        state.undo();
+        assert_eq!(state.text(), "one\ntwo\nthree");
+
+        // A selection ending at a line start duplicates only the lines it covers
+        let mut state = CodeEditorState::new("a\nb\nc\n");
+        state.cursors = vec![Cursor::with_selection(4, 0)];
+        state.sync_primary_cursor();
+        duplicate_lines(&mut state);
+        assert_eq!(state.text(), "a\nb\na\nb\nc\n");
+        assert_eq!((state.selection_start, state.selection_end), (Some(4), Some(8)));
+    }
+
+    #[test]
+    fn test_toggle_line_comment_mixed_selection_comments_all() {
+        let mut state = CodeEditorState::new("fn main() {\n    // a();\n\n    b();\n}\n");
+        // Select from inside line 1 to the start of line 4 (lines 1-3)
+        state.cursors = vec![Cursor::with_selection(state.rope.line_to_char(4), 14)];
+        state.sync_primary_cursor();
+
+        assert!(toggle_line_comment(&mut state, "//"));
+        assert_eq!(state.text(), "fn main() {\n    // // a();\n\n    // b();\n}\n");
+
+        // Every line is commented now, so a second toggle strips one level
+        assert!(toggle_line_comment(&mut state, "//"));
+        assert_eq!(state.text(), "fn main() {\n    // a();\n\n    b();\n}\n");
+
+        // Each toggle is its own undo step
+        state.undo();
+        assert_eq!(state.text(), "fn main() {\n    // // a();\n\n    // b();\n}\n");
+        state.undo();
+        assert_eq!(state.text(), "fn main() {\n    // a();\n\n    b();\n}\n");
+
+        // A cursor after the token keeps its place in the text
+        let mut state = CodeEditorState::new("  x = 1\n");
+        state.cursor_pos = 4;
+        toggle_line_comment(&mut state, "#");
+        assert_eq!(state.text(), "  # x = 1\n");
+        assert_eq!(state.cursor_pos, 6);
+    }
+
+    #[test]
+    fn test_full_line_paste_inserts_above_current_line() {
+        let mut state = CodeEditorState::new("first\nsecond\n");
+        let mut kill_ring = KillRing::default();
+        kill_ring.push_line("copied\n".to_string());
+
+        // Cursor in the middle of "sec|ond"
+        state.cursor_pos = 9;
+        paste_text(&mut state, &mut kill_ring, "copied\n".to_string());
+        assert_eq!(state.text(), "first\ncopied\nsecond\n");
+        assert_eq!(state.cursor_pos, 16);
+
+        // An ordinary copy is pasted at the cursor
+        kill_ring.push("xy".to_string());
+        paste_text(&mut state, &mut kill_ring, "xy".to_string());
+        assert_eq!(state.text(), "first\ncopied\nsecxyond\n");
+    }
+
+    #[test]
+    fn test_multi_cursor_copy_joins_selections_and_paste_distributes_lines() {
+        let mut state = CodeEditorState::new("let a = 1;\nlet bb = 2;\n");
+        state.cursors = vec![Cursor::with_selection(4, 5), Cursor::with_selection(15, 17)];
+        state.sync_primary_cursor();
+        let text = cursor_copy_texts(&state).join("\n");
+        assert_eq!(text, "a\nbb");
+
+        // One line per cursor: each cursor gets its own line
+        state.cursors = vec![Cursor::new(0), Cursor::new(11)];
+        state.sync_primary_cursor();
+        paste_at_cursors(&mut state, &text);
+        assert_eq!(state.text(), "alet a = 1;\nbblet bb = 2;\n");
+
+        // Otherwise every cursor gets the whole text
+        paste_at_cursors(&mut state, "x\ny\nz");
+        assert_eq!(state.text(), "ax\ny\nzlet a = 1;\nbbx\ny\nzlet bb = 2;\n");
+        assert!(state.undo());
+        assert_eq!(state.text(), "alet a = 1;\nbblet bb = 2;\n");
+    }
+
+    #[test]
+    fn test_multi_cursor_cut_without_selections_takes_whole_lines() {
+        let mut state = CodeEditorState::new("one\ntwo\nthree\nfour");
+        state.cursors = vec![Cursor::new(1), Cursor::new(9), Cursor::new(16)];
+        state.sync_primary_cursor();
+        assert_eq!(cursor_copy_texts(&state).join("\n"), "one\nthree\nfour");
+
+        // The run reaching the last line takes the line break before it, and
+        // both cursors end up at the start of the remaining line
+        assert!(delete_cursor_lines(&mut state));
+        assert_eq!(state.text(), "two");
+        assert_eq!(state.cursors.iter().map(|c| c.position).collect::<Vec<_>>(), vec![0]);
+
+        // One undo step restores every line
+        assert!(state.undo());
+        assert_eq!(state.text(), "one\ntwo\nthree\nfour");
+    }
+
+    #[test]
+    fn test_fold_all_nests_and_moves_hidden_cursors_to_header() {
+        let mut state = CodeEditorState::new("fn a() {\n    if x {\n        y();\n    }\n}\nz\n");
+        let mut fold_state = FoldState::default();
+        fold_state.add_region(FoldRegion::new(0, 4, FoldKind::Function));
+        fold_state.add_region(FoldRegion::new(1, 3, FoldKind::Block));
+
+        // Primary cursor inside the inner block, a second one below both folds
+        state.cursor_pos = 26;
+        state.add_cursor(41);
+        execute_action_core(
+            &mut state,
+            EditorAction::FoldAll,
+            &IndentationSettings::default(),
+            &CursorSettings::default(),
+            &mut FindState::default(),
+            &mut GotoLineState::default(),
+            &mut fold_state,
+            &mut KillRing::default(),
+        );
+
+        // Nested folds are folded too, but only the outer one counts towards hidden lines
+        assert!(fold_state.regions.iter().all(|r| r.is_folded));
+        assert_eq!(fold_state.total_hidden_lines(), 4);
+        assert_eq!(fold_state.actual_to_display_line(5), 1);
+
+        // The hidden cursor lands on the header line at the same column
+        assert_eq!(state.cursor_pos, 6);
+        let positions: Vec<usize> = state.cursors.iter().map(|c| c.position).collect();
+        assert_eq!(positions, vec![6, 41]);
+
+        // Opening the outer fold leaves the inner one collapsed
+        fold_state.unfold_at_line(0);
+        assert!(!fold_state.is_line_hidden(1));
+        assert!(fold_state.is_line_hidden(2));
+    }
+
+    #[test]
+    fn test_read_only_blocks_edits_but_allows_navigation() {
+        let mut state = CodeEditorState::new("fn main() {}\n");
+        state.read_only = true;
+        state.cursor_pos = 3;
+        let indentation = IndentationSettings::default();
+        let run = |state: &mut CodeEditorState, action| {
+            execute_action_core(
+                state,
+                action,
+                &indentation,
+                &CursorSettings::default(),
+                &mut FindState::default(),
+                &mut GotoLineState::default(),
+                &mut FoldState::default(),
+                &mut KillRing::default(),
+            )
+        };
+
+        for action in [EditorAction::DeleteBackward, EditorAction::InsertNewline, EditorAction::DeleteLine, EditorAction::Cut] {
+            assert!(!run(&mut state, action).text_changed);
+        }
+        assert_eq!(state.text(), "fn main() {}\n");
+
+        run(&mut state, EditorAction::MoveCursorRight);
+        assert_eq!(state.cursor_pos, 4);
+        run(&mut state, EditorAction::SelectLineEnd);
+        assert_eq!((state.selection_start, state.selection_end), (Some(4), Some(12)));
+
+        // Editing works again once the flag is cleared
+        state.read_only = false;
+        run(&mut state, EditorAction::DeleteBackward);
+        assert_eq!(state.text(), "fn \n");
+    }
+
+    #[test]
+    fn test_tab_with_selection_indents_lines_and_keeps_selection() {
+        let mut state = CodeEditorState::new("foo\n  bar\nbaz\n");
+        state.selection_start = Some(1);
+        state.selection_end = Some(7);
+        state.cursor_pos = 7;
+        let run = |state: &mut CodeEditorState, action, indentation: &IndentationSettings| {
+            execute_action_core(
+                state,
+                action,
+                indentation,
+                &CursorSettings::default(),
+                &mut FindState::default(),
+                &mut GotoLineState::default(),
+                &mut FoldState::default(),
+                &mut KillRing::default(),
+            )
+        };
+        let spaces = IndentationSettings::default();
+
+        run(&mut state, EditorAction::InsertTab, &spaces);
+        assert_eq!(state.text(), "    foo\n      bar\nbaz\n");
+        assert_eq!((state.selection_start, state.selection_end), (Some(5), Some(15)));
+
+        // One undo step for all lines
+        state.undo();
+        assert_eq!(state.text(), "foo\n  bar\nbaz\n");
+
+        // Outdent removes at most one level, leaving other content alone
+        run(&mut state, EditorAction::OutdentSelection, &spaces);
+        assert_eq!(state.text(), "foo\nbar\nbaz\n");
+        assert_eq!((state.selection_start, state.selection_end), (Some(1), Some(5)));
+
+        // Tabs when not using spaces
+        let tabs = IndentationSettings { use_spaces: false, ..Default::default() };
+        run(&mut state, EditorAction::IndentSelection, &tabs);
+        assert_eq!(state.text(), "\tfoo\n\tbar\nbaz\n");
+
+        // Without a selection Tab still inserts at the cursor
+        let mut state = CodeEditorState::new("x");
+        run(&mut state, EditorAction::InsertTab, &spaces);
+        assert_eq!(state.text(), "    x");
+    }
+
+    #[test]
+    fn test_add_cursor_above_and_below_stack_in_a_column() {
+        let mut state = CodeEditorState::new("one\nfirst line\nx\nthird line\nlast\n");
+        let fold_state = FoldState::default();
+        let mut find_state = FindState::default();
+        let mut goto_line_state = GotoLineState::default();
+        state.cursor_pos = state.rope.line_to_char(3) + 6;
+
+        // Stacks downward from the bottom edge, clamped to short lines, and
+        // stops at the last line
+        assert!(add_cursor_vertically(&mut state, &fold_state, false));
+        assert!(add_cursor_vertically(&mut state, &fold_state, false));
+        assert!(!add_cursor_vertically(&mut state, &fold_state, false));
+        let positions: Vec<usize> = state.cursors.iter().map(|c| c.position).collect();
+        let lines = [3, 4, 5].map(|line| state.rope.line_to_char(line));
+        assert_eq!(positions, vec![lines[0] + 6, lines[1] + 4, lines[2]]);
+
+        // Upward it keeps the original column past the one-char line
+        state.clear_secondary_cursors();
+        state.cursor_pos = state.rope.line_to_char(3) + 6;
+        state.sync_cursors_from_primary();
+        assert!(add_cursor_vertically(&mut state, &fold_state, true));
+        assert!(add_cursor_vertically(&mut state, &fold_state, true));
+        assert!(add_cursor_vertically(&mut state, &fold_state, true));
+        let positions: Vec<usize> = state.cursors.iter().map(|c| c.position).collect();
+        assert_eq!(positions, vec![3, state.rope.line_to_char(1) + 6, state.rope.line_to_char(2) + 1, state.rope.line_to_char(3) + 6]);
+
+        // Stops gracefully at the top
+        assert!(!add_cursor_vertically(&mut state, &fold_state, true));
+        assert_eq!(state.cursors.len(), 4);
+
+        // Escape collapses back to a single cursor
+        escape(&mut state, &mut find_state, &mut goto_line_state);
+        assert_eq!(state.cursors.len(), 1);
+    }
+
+    #[test]
+    fn test_add_cursor_below_steps_through_wrapped_rows() {
+        let text = "abcdefghijklmnopqrst\nxyz";
+        let mut state = CodeEditorState::new(text);
+        let lines: Vec<Vec<LineSegment>> = text
+            .split('\n')
+            .map(|line| vec![LineSegment { text: line.to_string(), color: bevy::color::Color::WHITE, style: crate::settings::FontStyle::Regular }])
+            .collect();
+        state.display_map.rebuild(&lines, 10, 0.0);
+        let fold_state = FoldState::default();
+        state.cursor_pos = 3;
+
+        // The second row of the wrapped line gets a cursor before the next line does
+        assert!(add_cursor_vertically(&mut state, &fold_state, false));
+        assert!(add_cursor_vertically(&mut state, &fold_state, false));
+        let positions: Vec<usize> = state.cursors.iter().map(|c| c.position).collect();
+        assert_eq!(positions, vec![3, 13, 24]);
+
+        assert!(!add_cursor_vertically(&mut state, &fold_state, true));
+    }
+
+    #[cfg(feature = "lsp")]
+    #[test]
+    fn test_escape_closes_hover_before_collapsing_cursors() {
+        let (client, _rx) = lsp::LspClient::with_test_channel();
+        let mut state = CodeEditorState::new("foo bar foo\n");
+        let mut completion_state = lsp::CompletionState::default();
+        let mut hover_state = lsp::HoverState { visible: true, content: "fn foo()".to_string(), ..Default::default() };
+        let mut find_state = FindState::default();
+        let mut goto_line_state = GotoLineState::default();
+        let mut escape_once = |state: &mut CodeEditorState, hover_state: &mut lsp::HoverState| {
+            execute_action(
+                state,
+                EditorAction::Escape,
+                &IndentationSettings::default(),
+                &CursorSettings::default(),
+                &LspSettings::default(),
+                &mut find_state,
+                &mut goto_line_state,
+                &mut FoldState::default(),
+                &mut KillRing::default(),
+                &mut SnippetSession::default(),
+                &client,
+                &mut completion_state,
+                hover_state,
+                &mut lsp::LspSyncState::default(),
+            );
+        };
+        state.add_cursor(8);
+
+        // The hover goes first; the cursors stay
+        escape_once(&mut state, &mut hover_state);
+        assert!(!hover_state.visible);
+        assert!(hover_state.content.is_empty());
+        assert!(state.has_multiple_cursors());
+
+        escape_once(&mut state, &mut hover_state);
+        assert!(!state.has_multiple_cursors());
+
+        // Either popup reports ClosedPopup, and nothing is left to close after
+        let mut completion_state = lsp::CompletionState { visible: true, ..Default::default() };
+        let mut hover_state = lsp::HoverState::default();
+        assert_eq!(close_popups(&mut completion_state, &mut hover_state), Some(EscapeOutcome::ClosedPopup));
+        assert!(!completion_state.visible);
+        assert_eq!(close_popups(&mut completion_state, &mut hover_state), None);
+    }
+
+    #[cfg(feature = "lsp")]
+    #[test]
+    fn test_format_on_save_sends_did_change_then_formatting() {
+        use crate::lsp::messages::{LspMessage, RequestType};
+        use lsp_types::{OneOf, ServerCapabilities, Url};
+
+        let (mut client, rx) = lsp::LspClient::with_test_channel();
+        let state = CodeEditorState::new("fn main(){}\n");
+        let indentation = IndentationSettings::default();
+        let mut lsp_sync = lsp::LspSyncState::default();
+        let mut format_on_save = lsp::state::FormatOnSaveState::default();
+
+        // Without a formatter the save goes ahead
+        client.initialized = true;
+        lsp_sync.document_uri = Some(Url::parse("file:///main.rs").unwrap());
+        assert!(!request_format_on_save(&state, &indentation, &client, &mut lsp_sync, &mut format_on_save));
+        assert!(!format_on_save.is_pending());
+
+        client.capabilities.set(ServerCapabilities {
+            document_formatting_provider: Some(OneOf::Left(true)),
+            ..Default::default()
+        });
+        assert!(request_format_on_save(&state, &indentation, &client, &mut lsp_sync, &mut format_on_save));
+        assert!(format_on_save.is_pending());
+        assert!(matches!(rx.try_recv(), Ok((LspMessage::DidChange { .. }, None))));
+        match rx.try_recv() {
+            Ok((LspMessage::Format { options, .. }, Some((_, RequestType::Format)))) => {
+                assert_eq!(options.tab_size, indentation.indent_size as u32);
+                assert_eq!(options.insert_spaces, indentation.use_spaces);
+            }
+            other => panic!("expected a formatting request, got {:?}", other),
+        }
+
+        // Saving again keeps waiting for the same response
+        assert!(request_format_on_save(&state, &indentation, &client, &mut lsp_sync, &mut format_on_save));
+        assert!(rx.try_recv().is_err());
+        assert!(format_on_save.finish());
+
+        // A read-only buffer saves without asking for edits it can't take
+        let mut read_only = CodeEditorState::new("fn main(){}\n");
+        read_only.read_only = true;
+        assert!(!request_format_on_save(&read_only, &indentation, &client, &mut lsp_sync, &mut format_on_save));
+        assert!(rx.try_recv().is_err());
+    }
+}
@@ -0,0 +1,30 @@
+//! System clipboard access
+//!
+//! With the `clipboard` feature (on by default) Copy, Cut and Paste go
+//! through the OS clipboard via `arboard`. Without it, and on wasm where
+//! `arboard` has no backend, these do nothing and Paste falls back to the
+//! kill ring, which keeps the editor's own copies in memory.
+
+/// Write text to the system clipboard, ignoring failures (e.g. no display)
+#[cfg(all(feature = "clipboard", not(target_arch = "wasm32")))]
+pub(crate) fn set_system_clipboard(text: &str) {
+    if let Ok(mut clipboard) = arboard::Clipboard::new() {
+        let _ = clipboard.set_text(text.to_string());
+    }
+}
+
+/// Read text from the system clipboard
+#[cfg(all(feature = "clipboard", not(target_arch = "wasm32")))]
+pub(crate) fn get_system_clipboard() -> Option<String> {
+    arboard::Clipboard::new().ok()?.get_text().ok()
+}
+
+/// Without a system clipboard, copies only reach the kill ring
+#[cfg(not(all(feature = "clipboard", not(target_arch = "wasm32"))))]
+pub(crate) fn set_system_clipboard(_text: &str) {}
+
+/// Without a system clipboard, paste reads the kill ring instead
+#[cfg(not(all(feature = "clipboard", not(target_arch = "wasm32"))))]
+pub(crate) fn get_system_clipboard() -> Option<String> {
+    None
+}
@@ -10,8 +10,22 @@ pub fn init_selection(state: &mut CodeEditorState) {
     }
 }
 
-/// Move cursor up one line
+/// Move the cursor to `pos`, extending the selection from its current anchor
+///
+/// Used for Shift-click so it composes with drags and Shift+Arrow: whichever
+/// started the selection, the anchor stays put and only the head moves.
+pub fn extend_selection_to(state: &mut CodeEditorState, pos: usize) {
+    init_selection(state);
+    state.cursor_pos = pos.min(state.rope.len_chars());
+    state.selection_end = Some(state.cursor_pos);
+    state.sync_cursors_from_primary();
+}
+
+/// Move cursor up one line (one visual row when soft wrapping is active)
 pub fn move_cursor_up(state: &mut CodeEditorState) {
+    if move_cursor_display_row(state, false) {
+        return;
+    }
     if state.cursor_pos > 0 {
         let line_idx = state.rope.char_to_line(state.cursor_pos);
         if line_idx > 0 {
@@ -24,22 +38,166 @@ pub fn move_cursor_up(state: &mut CodeEditorState) {
     }
 }
 
-/// Move cursor down one line
+/// Move cursor down one line (one visual row when soft wrapping is active)
 pub fn move_cursor_down(state: &mut CodeEditorState) {
+    if move_cursor_display_row(state, true) {
+        return;
+    }
     let line_idx = state.rope.char_to_line(state.cursor_pos);
-    if line_idx + 1 < state.rope.len_lines() {
+    let line_count = state.rope.len_lines();
+    if line_idx + 1 < line_count {
         let line_start = state.rope.line_to_char(line_idx);
         let col_offset = state.cursor_pos - line_start;
         let next_line_start = state.rope.line_to_char(line_idx + 1);
         let next_line_len = state.rope.line(line_idx + 1).len_chars();
-        state.cursor_pos = next_line_start + col_offset.min(next_line_len.saturating_sub(1));
+        // The last line has no line break, so its end (EOF) is a valid column
+        let max_col = if line_idx + 2 < line_count { next_line_len.saturating_sub(1) } else { next_line_len };
+        state.cursor_pos = next_line_start + col_offset.min(max_col);
     }
 }
 
-/// Move cursor to line start
-pub fn move_cursor_line_start(state: &mut CodeEditorState) {
-    let line_idx = state.rope.char_to_line(state.cursor_pos);
-    state.cursor_pos = state.rope.line_to_char(line_idx);
+/// Move the cursor one visible line up or down, stepping over folded bodies
+///
+/// Buffer-line movement can land inside a folded region; the cursor is then
+/// carried on to the first visible line in the direction of travel (or back
+/// before the fold when nothing visible follows it), keeping its column.
+pub fn move_cursor_vertical_visible(state: &mut CodeEditorState, fold_state: &FoldState, down: bool) {
+    let cursor = state.cursor_pos.min(state.rope.len_chars());
+    let col = cursor - state.rope.line_to_char(state.rope.char_to_line(cursor));
+
+    if down {
+        move_cursor_down(state);
+    } else {
+        move_cursor_up(state);
+    }
+
+    let line = state.rope.char_to_line(state.cursor_pos.min(state.rope.len_chars()));
+    if !fold_state.is_line_hidden(line) {
+        return;
+    }
+
+    let line_count = state.rope.len_lines();
+    let forward = (line + 1..line_count).find(|&l| !fold_state.is_line_hidden(l));
+    let backward = || (0..line).rev().find(|&l| !fold_state.is_line_hidden(l));
+    let target = if down { forward.or_else(backward) } else { backward().or(forward) };
+    let Some(target) = target else {
+        return;
+    };
+
+    let line_len = state.rope.line(target).len_chars();
+    let max_col = if target + 1 < line_count { line_len.saturating_sub(1) } else { line_len };
+    state.cursor_pos = state.rope.line_to_char(target) + col.min(max_col);
+}
+
+/// Find the display row holding a buffer position, preferring the row that
+/// contains the column and falling back to the line's last row (end of line)
+pub(crate) fn display_row_for(map: &DisplayMap, line: usize, col: usize) -> Option<usize> {
+    let mut last_row = None;
+    for (idx, row) in map.rows.iter().enumerate() {
+        if row.buffer_line == line {
+            if col < row.end_offset {
+                return Some(idx);
+            }
+            last_row = Some(idx);
+        } else if row.buffer_line > line {
+            break;
+        }
+    }
+    last_row
+}
+
+/// Move the cursor one display row up or down through the display map,
+/// keeping the visual column within the row.
+///
+/// Returns false when wrapping is inactive so callers fall back to buffer lines.
+fn move_cursor_display_row(state: &mut CodeEditorState, down: bool) -> bool {
+    let map = &state.display_map;
+    if map.wrap_width == 0 || map.rows.is_empty() {
+        return false;
+    }
+
+    let cursor = state.cursor_pos.min(state.rope.len_chars());
+    let line = state.rope.char_to_line(cursor);
+    let col = cursor - state.rope.line_to_char(line);
+    let Some(row_idx) = display_row_for(map, line, col) else {
+        return false;
+    };
+    let visual_col = col.saturating_sub(map.rows[row_idx].start_offset);
+
+    let target_idx = if down {
+        row_idx + 1
+    } else if row_idx > 0 {
+        row_idx - 1
+    } else {
+        return true;
+    };
+    let Some(target) = map.rows.get(target_idx) else {
+        return true;
+    };
+    if target.buffer_line >= state.rope.len_lines() {
+        return true;
+    }
+
+    // Only the line's last row may place the cursor after its final character
+    let ends_line = map
+        .rows
+        .get(target_idx + 1)
+        .is_none_or(|next| next.buffer_line != target.buffer_line);
+    let row_len = target.end_offset - target.start_offset;
+    let max_col = if ends_line { row_len } else { row_len.saturating_sub(1) };
+
+    let line_start = state.rope.line_to_char(target.buffer_line);
+    let line_len = state.rope.line(target.buffer_line).len_chars();
+    let text_len = if target.buffer_line + 1 < state.rope.len_lines() {
+        line_len.saturating_sub(1)
+    } else {
+        line_len
+    };
+    let new_col = (target.start_offset + visual_col.min(max_col)).min(text_len);
+
+    state.cursor_pos = line_start + new_col;
+    true
+}
+
+/// Where Home takes a cursor at `pos`
+///
+/// With `smart`, the first press goes to the first non-whitespace character
+/// and a press from there goes to column 0. Lines that are all whitespace
+/// always go to column 0.
+pub fn line_home_position(state: &CodeEditorState, pos: usize, smart: bool) -> usize {
+    let pos = pos.min(state.rope.len_chars());
+    let line_idx = state.rope.char_to_line(pos);
+    let line_start = state.rope.line_to_char(line_idx);
+    if !smart {
+        return line_start;
+    }
+    let line = state.rope.line(line_idx);
+    let indent = line.chars().take_while(|c| *c == ' ' || *c == '\t').count();
+    let all_whitespace = line.chars().skip(indent).all(|c| c == '\n' || c == '\r');
+    let first_non_whitespace = line_start + indent;
+    if all_whitespace || pos == first_non_whitespace {
+        line_start
+    } else {
+        first_non_whitespace
+    }
+}
+
+/// Move every cursor to its line's home position, extending selections with `extend`
+///
+/// With `smart`, Home stops at the first non-whitespace character before column 0.
+pub fn move_cursors_home(state: &mut CodeEditorState, extend: bool, smart: bool) {
+    state.sync_cursors_from_primary();
+    let targets: Vec<usize> = state
+        .cursors
+        .iter()
+        .map(|cursor| line_home_position(state, cursor.position, smart))
+        .collect();
+    for (cursor, target) in state.cursors.iter_mut().zip(targets) {
+        cursor.anchor = if extend { cursor.anchor.or(Some(cursor.position)) } else { None };
+        cursor.position = target;
+    }
+    state.sort_and_merge_cursors();
+    state.sync_primary_cursor();
 }
 
 /// Move cursor to line end
@@ -54,14 +212,14 @@ pub fn move_cursor_line_end(state: &mut CodeEditorState) {
 #[derive(PartialEq, Eq, Clone, Copy)]
 enum CharClass {
     Whitespace,
-    Word,       // alphanumeric or underscore
+    Word,       // see `is_word_char`
     Punctuation,
 }
 
 fn classify_char(c: char) -> CharClass {
     if c.is_whitespace() {
         CharClass::Whitespace
-    } else if c.is_alphanumeric() || c == '_' {
+    } else if is_word_char(c) {
         CharClass::Word
     } else {
         CharClass::Punctuation
@@ -92,6 +250,11 @@ pub fn find_word_boundary_left(rope: &ropey::Rope, pos: usize) -> usize {
         return 0;
     }
 
+    // At a line start, step over the line break to the end of the previous line
+    if rope.char(current - 1) == '\n' {
+        return if current == pos { current - 1 } else { current };
+    }
+
     // Determine the class of the character we're about to skip
     let class = classify_char(rope.char(current - 1));
 
@@ -113,7 +276,8 @@ pub fn find_word_boundary_left(rope: &ropey::Rope, pos: usize) -> usize {
 }
 
 /// Find the end of the next word (for Ctrl+Right and Ctrl+Delete)
-/// This matches VSCode/Zed behavior: skip current word, then skip whitespace
+/// This matches VSCode/Zed behavior: skip current word, then skip whitespace;
+/// from whitespace, skip it together with the word that follows
 pub fn find_word_boundary_right(rope: &ropey::Rope, pos: usize) -> usize {
     let len = rope.len_chars();
     if pos >= len {
@@ -125,7 +289,7 @@ pub fn find_word_boundary_right(rope: &ropey::Rope, pos: usize) -> usize {
     // Determine the class of the character at cursor
     let c = rope.char(current);
 
-    // If we're on whitespace, skip it first
+    // If we're on whitespace, skip it first and then take the word after it
     if c.is_whitespace() {
         while current < len {
             let c = rope.char(current);
@@ -140,6 +304,13 @@ pub fn find_word_boundary_right(rope: &ropey::Rope, pos: usize) -> usize {
                 break;
             }
         }
+        if current >= len {
+            return len;
+        }
+        let class = classify_char(rope.char(current));
+        while current < len && rope.char(current) != '\n' && classify_char(rope.char(current)) == class {
+            current += 1;
+        }
         return current;
     }
 
@@ -170,97 +341,465 @@ pub fn find_word_boundary_right(rope: &ropey::Rope, pos: usize) -> usize {
     current
 }
 
-/// Move cursor to the previous word boundary
+/// Move every cursor to `target(rope, position)`
+///
+/// With `extend`, each cursor keeps (or starts) its selection anchor;
+/// otherwise selections are dropped. Cursors that meet are merged.
+pub fn move_cursors_with(
+    state: &mut CodeEditorState,
+    extend: bool,
+    target: impl Fn(&ropey::Rope, usize) -> usize,
+) {
+    state.sync_cursors_from_primary();
+    for cursor in state.cursors.iter_mut() {
+        if !extend {
+            cursor.anchor = None;
+        } else if cursor.anchor.is_none() {
+            cursor.anchor = Some(cursor.position);
+        }
+        cursor.position = target(&state.rope, cursor.position);
+    }
+    state.sort_and_merge_cursors();
+    state.sync_primary_cursor();
+}
+
+/// Move every cursor to its previous word boundary
+///
+/// Uses the same boundaries as `delete_word_backward`.
 pub fn move_cursor_word_left(state: &mut CodeEditorState) {
-    state.cursor_pos = find_word_boundary_left(&state.rope, state.cursor_pos);
+    move_cursors_with(state, false, find_word_boundary_left);
 }
 
-/// Move cursor to the next word boundary
+/// Move every cursor to its next word boundary
+///
+/// Uses the same boundaries as `delete_word_forward`.
 pub fn move_cursor_word_right(state: &mut CodeEditorState) {
-    state.cursor_pos = find_word_boundary_right(&state.rope, state.cursor_pos);
+    move_cursors_with(state, false, find_word_boundary_right);
 }
 
-/// Delete from cursor to previous word boundary
-pub fn delete_word_backward(state: &mut CodeEditorState) {
+/// Remove `start..end` around the primary cursor as a single undo step
+///
+/// Recorded with `EditKind::Other` so it never merges with typing.
+fn delete_word_range(state: &mut CodeEditorState, start: usize, end: usize) {
+    if start >= end {
+        return;
+    }
+    state.sync_cursors_from_primary();
+    let before = SelectionCollection::from_cursors(&state.cursors);
     let cursor_before = state.cursor_pos;
-    let word_start = find_word_boundary_left(&state.rope, state.cursor_pos);
 
-    if word_start < cursor_before {
-        // Get the text being deleted for undo
-        let deleted_text: String = state.rope.slice(word_start..cursor_before).chars().collect();
-
-        // Remove the text
-        let start_byte = state.rope.char_to_byte(word_start);
-        let end_byte = state.rope.char_to_byte(cursor_before);
-
-        // Record edit for incremental parsing
-        #[cfg(feature = "tree-sitter")]
-        state.record_edit(start_byte, end_byte, start_byte);
-
-        state.rope.remove(start_byte..end_byte);
-
-        // Update cursor
-        state.cursor_pos = word_start;
-
-        // Record for undo
-        state.history.record(EditOperation {
-            removed_text: deleted_text,
-            inserted_text: String::new(),
-            position: word_start,
-            cursor_before,
-            cursor_after: word_start,
-            kind: EditKind::DeleteBackward,
-        });
-
-        // Mark for update
-        state.needs_update = true;
-        state.pending_update = false;
-        state.content_version += 1;
-        let line_idx = state.rope.char_to_line(word_start);
-        let new_line_count = state.rope.len_lines();
-        state.dirty_lines = Some(line_idx..(line_idx + 1).min(new_line_count));
-        state.previous_line_count = new_line_count;
-    }
+    let removed_text = state.rope.slice(start..end).to_string();
+    state.remove_range(start, end);
+    state.cursor_pos = start;
+    state.selection_start = None;
+    state.selection_end = None;
+    state.sync_cursors_from_primary();
+
+    let operation = EditOperation {
+        removed_text,
+        inserted_text: String::new(),
+        position: start,
+        cursor_before,
+        cursor_after: start,
+        kind: EditKind::Other,
+    };
+    let after = SelectionCollection::from_cursors(&state.cursors);
+    state.record_transaction(vec![operation], before, after);
+}
+
+/// Delete from cursor to previous word boundary, taking whitespace before the cursor with it
+pub fn delete_word_backward(state: &mut CodeEditorState) {
+    let word_start = find_word_boundary_left(&state.rope, state.cursor_pos);
+    delete_word_range(state, word_start, state.cursor_pos);
 }
 
-/// Delete from cursor to next word boundary
+/// Delete from cursor to next word boundary, taking whitespace before the word with it
 pub fn delete_word_forward(state: &mut CodeEditorState) {
-    let cursor_before = state.cursor_pos;
     let word_end = find_word_boundary_right(&state.rope, state.cursor_pos);
+    delete_word_range(state, state.cursor_pos, word_end);
+}
+
+/// Delete the line containing the cursor, including its line break
+///
+/// Returns the removed text and its char range (before removal) so callers can
+/// feed it to the kill ring.
+pub fn delete_line(state: &mut CodeEditorState) -> Option<(String, usize, usize)> {
+    let cursor_before = state.cursor_pos.min(state.rope.len_chars());
+    let line_idx = state.rope.char_to_line(cursor_before);
+    let line_count = state.rope.len_lines();
+
+    let mut start = state.rope.line_to_char(line_idx);
+    let end = if line_idx + 1 < line_count {
+        state.rope.line_to_char(line_idx + 1)
+    } else {
+        // Last line has no trailing newline: take the preceding one instead
+        if line_idx > 0 {
+            start -= 1;
+        }
+        state.rope.len_chars()
+    };
+
+    if start >= end {
+        return None;
+    }
+
+    let deleted_text: String = state.rope.slice(start..end).chars().collect();
+    state.remove_range(start, end);
+
+    let cursor_after = state.rope.line_to_char(state.rope.char_to_line(start.min(state.rope.len_chars())));
+    state.cursor_pos = cursor_after;
+    state.selection_start = None;
+    state.selection_end = None;
+
+    state.record_history(EditOperation {
+        removed_text: deleted_text.clone(),
+        inserted_text: String::new(),
+        position: start,
+        cursor_before,
+        cursor_after,
+        kind: EditKind::Other,
+    });
+
+    state.needs_update = true;
+
+    Some((deleted_text, start, end))
+}
+
+/// Move the line under the cursor, or every line the selection touches, one line up or down
+///
+/// The moved block swaps places with its neighbour as a single undo step.
+/// Folded neighbours move as a whole so fold regions stay intact, and the
+/// cursor and selection keep their columns on the moved lines. Returns false
+/// if the block is already at the top or bottom of the buffer.
+pub fn move_lines(state: &mut CodeEditorState, fold_state: &mut FoldState, down: bool) -> bool {
+    let len_chars = state.rope.len_chars();
+    let line_count = state.rope.len_lines();
+    let cursor_before = state.cursor_pos.min(len_chars);
+    let (sel_min, sel_max) = match (state.selection_start, state.selection_end) {
+        (Some(a), Some(b)) => (a.min(b).min(len_chars), a.max(b).min(len_chars)),
+        _ => (cursor_before, cursor_before),
+    };
+
+    let mut first_line = state.rope.char_to_line(sel_min);
+    let mut last_line = state.rope.char_to_line(sel_max);
+    // A selection ending at the start of a line doesn't include that line
+    if last_line > first_line && state.rope.line_to_char(last_line) == sel_max {
+        last_line -= 1;
+    }
+    // Keep whole folded regions together with the block
+    let folded_end = |line: usize| {
+        fold_state.regions.iter()
+            .filter(|r| r.is_folded && r.start_line == line)
+            .map(|r| r.end_line)
+            .max()
+    };
+    last_line = last_line.max(folded_end(last_line).unwrap_or(last_line)).min(line_count - 1);
+    first_line = fold_state.regions.iter()
+        .filter(|r| r.hides_line(first_line))
+        .map(|r| r.start_line)
+        .min()
+        .unwrap_or(first_line);
+
+    // The neighbour is a single line, or a whole folded region
+    let (start, mid, end, delta) = if down {
+        if last_line + 1 >= line_count {
+            return false;
+        }
+        let neighbour_end = folded_end(last_line + 1).unwrap_or(last_line + 1).min(line_count - 1);
+        (first_line, last_line + 1, neighbour_end + 1, (neighbour_end - last_line) as isize)
+    } else {
+        if first_line == 0 {
+            return false;
+        }
+        let neighbour_start = fold_state.regions.iter()
+            .filter(|r| r.hides_line(first_line - 1))
+            .map(|r| r.start_line)
+            .min()
+            .unwrap_or(first_line - 1);
+        (neighbour_start, first_line, last_line + 1, -((first_line - neighbour_start) as isize))
+    };
+
+    let line_start = |line: usize| {
+        if line >= line_count { len_chars } else { state.rope.line_to_char(line) }
+    };
+    let (start_char, mid_char, end_char) = (line_start(start), line_start(mid), line_start(end));
+    let first = state.rope.slice(start_char..mid_char).to_string();
+    let second = state.rope.slice(mid_char..end_char).to_string();
+    let swapped = if second.ends_with('\n') {
+        format!("{}{}", second, first)
+    } else {
+        // The second block ends the buffer without a trailing newline
+        format!("{}\n{}", second, first.strip_suffix('\n').unwrap_or(&first))
+    };
+
+    // Positions on the moved lines keep their line-relative column
+    let line_and_column = |pos: usize| {
+        let line = state.rope.char_to_line(pos);
+        (line, pos - state.rope.line_to_char(line))
+    };
+    let cursor = line_and_column(cursor_before);
+    let selection = state.selection_start.zip(state.selection_end)
+        .map(|(a, b)| (line_and_column(a.min(len_chars)), line_and_column(b.min(len_chars))));
+
+    let removed_text = first + &second;
+    state.remove_range(start_char, end_char);
+    state.insert_text_at(start_char, &swapped);
+    fold_state.swap_line_blocks(start, mid, end);
+
+    let relocate = |state: &CodeEditorState, (line, column): (usize, usize)| {
+        // A selection end at the start of the line after the block (line `mid`
+        // when moving down, `end` when moving up) follows the block too
+        let moved = if down { line <= mid } else { line >= mid };
+        let line = if moved { (line as isize + delta) as usize } else { line };
+        if line >= state.rope.len_lines() {
+            return state.rope.len_chars();
+        }
+        let line_len = state.rope.line(line).len_chars();
+        let content_len = if line + 1 < state.rope.len_lines() { line_len.saturating_sub(1) } else { line_len };
+        state.rope.line_to_char(line) + column.min(content_len)
+    };
+    let cursor_after = relocate(state, cursor);
+    state.cursor_pos = cursor_after;
+    if let Some((a, b)) = selection {
+        state.selection_start = Some(relocate(state, a));
+        state.selection_end = Some(relocate(state, b));
+    }
+    state.sync_cursors_from_primary();
+
+    state.record_history(EditOperation {
+        removed_text,
+        inserted_text: swapped,
+        position: start_char,
+        cursor_before,
+        cursor_after,
+        kind: EditKind::Other,
+    });
+    state.needs_update = true;
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wrapped_state(text: &str, wrap_width: usize) -> CodeEditorState {
+        let mut state = CodeEditorState::new(text);
+        let lines: Vec<Vec<LineSegment>> = text
+            .split('\n')
+            .map(|line| vec![LineSegment { text: line.to_string(), color: bevy::color::Color::WHITE, style: crate::settings::FontStyle::Regular }])
+            .collect();
+        state.display_map.rebuild(&lines, wrap_width, 0.0);
+        state
+    }
+
+    #[test]
+    fn test_down_skips_folded_body() {
+        let mut state = CodeEditorState::new("a\nfn f() {\n    x\n    y\n}\nb\n");
+        let mut fold_state = FoldState::default();
+        fold_state.add_manual_fold(1, 4);
+        state.cursor_pos = state.rope.line_to_char(1) + 1;
+
+        move_cursor_vertical_visible(&mut state, &fold_state, true);
+        assert_eq!(state.rope.char_to_line(state.cursor_pos), 5);
+        assert_eq!(state.cursor_pos, state.rope.line_to_char(5) + 1);
+
+        move_cursor_vertical_visible(&mut state, &fold_state, false);
+        assert_eq!(state.rope.char_to_line(state.cursor_pos), 1);
+    }
+
+    #[test]
+    fn test_down_moves_between_visual_rows_of_wrapped_line() {
+        let mut state = wrapped_state("abcdefghijklmnopqrst\nxyz", 10);
+        state.cursor_pos = 3;
+
+        move_cursor_down(&mut state);
+        assert_eq!(state.cursor_pos, 13);
+
+        move_cursor_down(&mut state);
+        assert_eq!(state.cursor_pos, 21 + 3);
+
+        move_cursor_up(&mut state);
+        assert_eq!(state.cursor_pos, 13);
+    }
+
+    fn cursor_line(state: &CodeEditorState) -> usize {
+        state.rope.char_to_line(state.cursor_pos)
+    }
+
+    #[test]
+    fn test_enter_in_empty_buffer_creates_second_line() {
+        let mut state = CodeEditorState::new("");
+        state.insert_char('\n');
+
+        assert_eq!(state.line_count(), 2);
+        assert_eq!(cursor_line(&state), 1);
+
+        move_cursor_up(&mut state);
+        assert_eq!(cursor_line(&state), 0);
+        move_cursor_down(&mut state);
+        assert_eq!(cursor_line(&state), 1);
+    }
+
+    #[test]
+    fn test_enter_at_eof_without_trailing_newline() {
+        let mut state = CodeEditorState::new("abc");
+        state.cursor_pos = 3;
+        state.insert_char('\n');
+
+        assert_eq!(state.text(), "abc\n");
+        assert_eq!(state.line_count(), 2);
+        assert_eq!(cursor_line(&state), 1);
+        // The new line is rebuilt along with the one that was split
+        assert_eq!(state.dirty_lines, Some(0..2));
+
+        // Nothing below the new last line
+        move_cursor_down(&mut state);
+        assert_eq!(state.cursor_pos, 4);
+    }
+
+    #[test]
+    fn test_enter_at_eof_with_trailing_newline() {
+        let mut state = CodeEditorState::new("abc\n");
+        state.cursor_pos = 4;
+        state.insert_char('\n');
+
+        assert_eq!(state.line_count(), 3);
+        assert_eq!(cursor_line(&state), 2);
+
+        move_cursor_up(&mut state);
+        assert_eq!(cursor_line(&state), 1);
+        move_cursor_down(&mut state);
+        assert_eq!(state.cursor_pos, 5);
+    }
+
+    #[test]
+    fn test_down_onto_last_line_reaches_its_end() {
+        let mut state = CodeEditorState::new("abcdef\nxyz");
+        state.cursor_pos = 5;
+
+        move_cursor_down(&mut state);
+        assert_eq!(state.cursor_pos, 7 + 3);
+    }
+
+    #[test]
+    fn test_move_line_down_keeps_column_and_undoes_in_one_step() {
+        let mut state = CodeEditorState::new("one\ntwo\nthree");
+        let mut fold_state = FoldState::default();
+        state.cursor_pos = 5; // "t|wo"
+
+        assert!(move_lines(&mut state, &mut fold_state, true));
+        assert_eq!(state.rope.to_string(), "one\nthree\ntwo");
+        assert_eq!(state.cursor_pos, 11);
+
+        // Already the last line
+        assert!(!move_lines(&mut state, &mut fold_state, true));
+
+        state.undo();
+        assert_eq!(state.rope.to_string(), "one\ntwo\nthree");
+        assert_eq!(state.cursor_pos, 5);
+    }
+
+    #[test]
+    fn test_move_selected_block_up_over_folded_region() {
+        let mut state = CodeEditorState::new("fn a() {\n    x\n}\nb\nc\n");
+        let mut fold_state = FoldState::default();
+        fold_state.add_manual_fold(0, 2);
+        // Select "b\nc\n"
+        state.selection_start = Some(17);
+        state.selection_end = Some(21);
+        state.cursor_pos = 21;
+
+        assert!(move_lines(&mut state, &mut fold_state, false));
+        assert_eq!(state.rope.to_string(), "b\nc\nfn a() {\n    x\n}\n");
+        assert_eq!((state.selection_start, state.selection_end), (Some(0), Some(4)));
+
+        let region = &fold_state.regions[0];
+        assert_eq!((region.start_line, region.end_line, region.is_folded), (2, 4, true));
+
+        assert!(!move_lines(&mut state, &mut fold_state, false));
+    }
+
+    #[test]
+    fn test_smart_home_toggles_between_indent_and_column_zero() {
+        let mut state = CodeEditorState::new("    foo\n  bar\n   \n");
+        state.cursor_pos = 6; // "fo|o"
+        state.sync_cursors_from_primary();
+        state.add_cursor(12); // "ba|r"
+
+        move_cursors_home(&mut state, false, true);
+        let positions: Vec<usize> = state.cursors.iter().map(|c| c.position).collect();
+        assert_eq!(positions, vec![4, 10]);
+
+        move_cursors_home(&mut state, false, true);
+        let positions: Vec<usize> = state.cursors.iter().map(|c| c.position).collect();
+        assert_eq!(positions, vec![0, 8]);
+
+        // Shift+Home extends from the original position
+        let mut state = CodeEditorState::new("    foo");
+        state.cursor_pos = 7;
+        move_cursors_home(&mut state, true, true);
+        assert_eq!((state.selection_start, state.selection_end), (Some(7), Some(4)));
+
+        // Whitespace-only lines go straight to column 0
+        let state = CodeEditorState::new("a\n   \n");
+        assert_eq!(line_home_position(&state, 5, true), 2);
+
+        // Plain Home when disabled
+        let state = CodeEditorState::new("    foo");
+        assert_eq!(line_home_position(&state, 6, false), 0);
+    }
+
+    #[test]
+    fn test_delete_word_takes_leading_whitespace_and_undoes_in_one_step() {
+        let mut state = CodeEditorState::new("foo  bar");
+        state.cursor_pos = 8;
+        delete_word_backward(&mut state);
+        assert_eq!(state.text(), "foo  ");
+        delete_word_backward(&mut state);
+        assert_eq!(state.text(), "");
+
+        // Typing afterwards is a separate undo step
+        state.insert_char('x');
+        state.undo();
+        assert_eq!(state.text(), "");
+        state.undo();
+        assert_eq!(state.text(), "foo  ");
+        state.undo();
+        assert_eq!(state.text(), "foo  bar");
+
+        let mut state = CodeEditorState::new("foo  bar baz");
+        state.cursor_pos = 3;
+        delete_word_forward(&mut state);
+        assert_eq!(state.text(), "foo baz");
+        assert_eq!(state.cursor_pos, 3);
+    }
 
-    if word_end > cursor_before {
-        // Get the text being deleted for undo
-        let deleted_text: String = state.rope.slice(cursor_before..word_end).chars().collect();
-
-        // Remove the text
-        let start_byte = state.rope.char_to_byte(cursor_before);
-        let end_byte = state.rope.char_to_byte(word_end);
-
-        // Record edit for incremental parsing
-        #[cfg(feature = "tree-sitter")]
-        state.record_edit(start_byte, end_byte, start_byte);
-
-        state.rope.remove(start_byte..end_byte);
-
-        // Cursor stays at the same position
-
-        // Record for undo
-        state.history.record(EditOperation {
-            removed_text: deleted_text,
-            inserted_text: String::new(),
-            position: cursor_before,
-            cursor_before,
-            cursor_after: cursor_before,
-            kind: EditKind::DeleteForward,
-        });
-
-        // Mark for update
-        state.needs_update = true;
-        state.pending_update = false;
-        state.content_version += 1;
-        let line_idx = state.rope.char_to_line(cursor_before);
-        let new_line_count = state.rope.len_lines();
-        state.dirty_lines = Some(line_idx..(line_idx + 1).min(new_line_count));
-        state.previous_line_count = new_line_count;
+    #[test]
+    fn test_word_movement_moves_every_cursor_and_matches_deletion() {
+        let mut state = CodeEditorState::new("let foo_bar = baz();\nqux.quux\n");
+        state.cursor_pos = 0;
+        state.add_cursor(21);
+
+        move_cursor_word_right(&mut state);
+        let positions: Vec<usize> = state.cursors.iter().map(|c| c.position).collect();
+        assert_eq!(positions, vec![4, 24]);
+        move_cursor_word_right(&mut state);
+        let positions: Vec<usize> = state.cursors.iter().map(|c| c.position).collect();
+        assert_eq!(positions, vec![12, 25]);
+
+        // Shift variant extends each cursor's own selection
+        move_cursors_with(&mut state, true, find_word_boundary_left);
+        let ranges: Vec<Option<(usize, usize)>> = state.cursors.iter().map(|c| c.selection_range()).collect();
+        assert_eq!(ranges, vec![Some((4, 12)), Some((24, 25))]);
+
+        // At a line start, word-left crosses the line break, and Ctrl+Backspace agrees
+        state.clear_secondary_cursors();
+        state.selection_start = None;
+        state.selection_end = None;
+        state.cursor_pos = 21;
+        assert_eq!(find_word_boundary_left(&state.rope, 21), 20);
+        delete_word_backward(&mut state);
+        assert_eq!(state.text(), "let foo_bar = baz();qux.quux\n");
+        assert_eq!(state.cursor_pos, 20);
     }
 }
@@ -0,0 +1,119 @@
+//! Host-defined editor actions
+//!
+//! `EditorAction::CustomAction(id)` can be bound in the same `InputMap` as the
+//! built-in actions. When it is pressed, the one-shot system registered for `id`
+//! in `CustomActions` is run.
+
+use bevy::ecs::system::SystemId;
+use bevy::prelude::*;
+use leafwing_input_manager::prelude::*;
+use std::collections::HashMap;
+use crate::types::CodeEditorState;
+use crate::plugin::EditorInputManager;
+use super::keybindings::EditorAction;
+
+/// Registry mapping custom action ids to host-provided handler systems
+///
+/// # Example
+/// ```ignore
+/// const RUN: u32 = 1;
+///
+/// fn run_code(state: Res<CodeEditorState>) {
+///     println!("{}", state.text());
+/// }
+///
+/// let handler = app.world_mut().register_system(run_code);
+/// app.world_mut().resource_mut::<CustomActions>().register(RUN, handler);
+///
+/// // Bind it like any other action
+/// input_map.insert(EditorAction::CustomAction(RUN), KeyCode::F5);
+/// ```
+#[derive(Resource, Default)]
+pub struct CustomActions {
+    handlers: HashMap<u32, SystemId>,
+}
+
+impl CustomActions {
+    /// Register the handler system to run when `CustomAction(id)` is pressed
+    ///
+    /// Replaces any handler previously registered for `id`.
+    pub fn register(&mut self, id: u32, handler: SystemId) {
+        self.handlers.insert(id, handler);
+    }
+
+    /// Remove the handler for `id`, returning it if one was registered
+    pub fn unregister(&mut self, id: u32) -> Option<SystemId> {
+        self.handlers.remove(&id)
+    }
+
+    /// Get the handler registered for `id`
+    pub fn handler(&self, id: u32) -> Option<SystemId> {
+        self.handlers.get(&id).copied()
+    }
+}
+
+/// Run the registered handler for every custom action pressed this frame
+pub fn handle_custom_actions(
+    mut commands: Commands,
+    state: Res<CodeEditorState>,
+    custom_actions: Res<CustomActions>,
+    action_query: Query<&ActionState<EditorAction>, With<EditorInputManager>>,
+) {
+    if !state.is_focused || custom_actions.handlers.is_empty() {
+        return;
+    }
+
+    let Ok(action_state) = action_query.single() else {
+        return;
+    };
+
+    for (&id, &handler) in &custom_actions.handlers {
+        if action_state.just_pressed(&EditorAction::CustomAction(id)) {
+            commands.run_system(handler);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Resource, Default)]
+    struct RunCount(usize);
+
+    fn count_runs(mut count: ResMut<RunCount>) {
+        count.0 += 1;
+    }
+
+    #[test]
+    fn test_pressing_custom_action_runs_handler() {
+        let mut app = App::new();
+        let mut state = CodeEditorState::new("fn main() {}");
+        state.is_focused = true;
+        app.insert_resource(state);
+        app.init_resource::<RunCount>();
+        app.init_resource::<CustomActions>();
+        app.add_systems(Update, handle_custom_actions);
+
+        let handler = app.world_mut().register_system(count_runs);
+        app.world_mut().resource_mut::<CustomActions>().register(7, handler);
+
+        // Bind a chord to the custom action, then simulate pressing it
+        let mut input_map = InputMap::default();
+        input_map.insert(
+            EditorAction::CustomAction(7),
+            ButtonlikeChord::new([KeyCode::ControlLeft, KeyCode::KeyR]),
+        );
+        let mut action_state = ActionState::<EditorAction>::default();
+        action_state.press(&EditorAction::CustomAction(7));
+        app.world_mut().spawn((EditorInputManager, input_map, action_state));
+
+        app.update();
+        assert_eq!(app.world().resource::<RunCount>().0, 1);
+
+        // Unbound ids don't run anything
+        app.world_mut().resource_mut::<CustomActions>().unregister(7);
+        app.update();
+        assert_eq!(app.world().resource::<RunCount>().0, 1);
+    }
+}
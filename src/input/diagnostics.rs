@@ -0,0 +1,155 @@
+//! Jumping between diagnostics
+//!
+//! `EditorAction::NextDiagnostic` / `PrevDiagnostic` select the next or previous
+//! entry in `EditorDiagnostics`, wrapping at the ends of the buffer and unfolding
+//! any fold that hides it. Diagnostic ranges follow edits through anchors, see
+//! `track_diagnostic_edits`.
+
+use bevy::prelude::*;
+use leafwing_input_manager::prelude::*;
+use crate::types::*;
+use crate::plugin::EditorInputManager;
+use super::keybindings::EditorAction;
+
+#[cfg(feature = "lsp")]
+use crate::settings::LspSettings;
+
+/// Select the next (or previous) diagnostic relative to the cursor
+///
+/// The diagnostic's range is selected with the cursor at its start. Returns false
+/// when no diagnostic at least `min_level` severe exists.
+pub fn goto_diagnostic(
+    state: &mut CodeEditorState,
+    diagnostics: &EditorDiagnostics,
+    fold_state: &mut FoldState,
+    forward: bool,
+    min_level: DiagnosticLevel,
+) -> bool {
+    let target = if forward {
+        diagnostics.next_after(state.cursor_pos, min_level)
+    } else {
+        diagnostics.prev_before(state.cursor_pos, min_level)
+    };
+    let Some(target) = target else {
+        return false;
+    };
+
+    let len = state.rope.len_chars();
+    let start = target.range.start.min(len);
+    let end = target.range.end.clamp(start, len);
+
+    fold_state.reveal_line(state.rope.char_to_line(start));
+
+    state.cursor_pos = start;
+    if end > start {
+        state.selection_start = Some(end);
+        state.selection_end = Some(start);
+    } else {
+        state.selection_start = None;
+        state.selection_end = None;
+    }
+    state.pending_update = true;
+    state.mark_navigation_jump();
+    true
+}
+
+/// Anchor newly set diagnostics and move existing ones along with edits
+pub fn track_diagnostic_edits(
+    mut state: ResMut<CodeEditorState>,
+    mut diagnostics: ResMut<EditorDiagnostics>,
+) {
+    if diagnostics.needs_tracking(&state) {
+        diagnostics.track_edits(&mut state);
+    }
+}
+
+/// Handle NextDiagnostic / PrevDiagnostic presses
+pub fn handle_diagnostic_navigation(
+    mut state: ResMut<CodeEditorState>,
+    diagnostics: Res<EditorDiagnostics>,
+    mut fold_state: ResMut<FoldState>,
+    action_query: Query<&ActionState<EditorAction>, With<EditorInputManager>>,
+    #[cfg(feature = "lsp")] lsp: Option<Res<LspSettings>>,
+) {
+    if !state.is_focused || diagnostics.items().is_empty() {
+        return;
+    }
+
+    let Ok(action_state) = action_query.single() else {
+        return;
+    };
+
+    let forward = if action_state.just_pressed(&EditorAction::NextDiagnostic) {
+        true
+    } else if action_state.just_pressed(&EditorAction::PrevDiagnostic) {
+        false
+    } else {
+        return;
+    };
+
+    #[cfg(feature = "lsp")]
+    let min_level = lsp.map(|l| l.diagnostic_nav_min_severity).unwrap_or_default();
+    #[cfg(not(feature = "lsp"))]
+    let min_level = DiagnosticLevel::default();
+
+    goto_diagnostic(&mut state, &diagnostics, &mut fold_state, forward, min_level);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error_at(range: std::ops::Range<usize>) -> EditorDiagnostic {
+        EditorDiagnostic {
+            range,
+            level: DiagnosticLevel::Error,
+            message: "error".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_next_diagnostic_moves_forward_and_wraps() {
+        let mut state = CodeEditorState::new("let a = 1;\nlet b = ;\nlet c = x;\n");
+        let mut fold_state = FoldState::default();
+        let mut diagnostics = EditorDiagnostics::default();
+        diagnostics.set(vec![error_at(30..31), error_at(19..20)]);
+
+        assert!(goto_diagnostic(&mut state, &diagnostics, &mut fold_state, true, DiagnosticLevel::Hint));
+        assert_eq!(state.cursor_pos, 19);
+        assert_eq!(state.selection_start, Some(20));
+
+        assert!(goto_diagnostic(&mut state, &diagnostics, &mut fold_state, true, DiagnosticLevel::Hint));
+        assert_eq!(state.cursor_pos, 30);
+
+        // From the last diagnostic, wrap back to the first
+        assert!(goto_diagnostic(&mut state, &diagnostics, &mut fold_state, true, DiagnosticLevel::Hint));
+        assert_eq!(state.cursor_pos, 19);
+
+        diagnostics.clear();
+        assert!(!goto_diagnostic(&mut state, &diagnostics, &mut fold_state, true, DiagnosticLevel::Hint));
+        assert_eq!(state.cursor_pos, 19);
+    }
+
+    #[test]
+    fn test_diagnostic_ranges_follow_edits() {
+        let mut state = CodeEditorState::new("let a = 1;\nlet b = ;\n");
+        let mut diagnostics = EditorDiagnostics::default();
+        diagnostics.set(vec![error_at(19..20)]);
+        assert!(diagnostics.needs_tracking(&state));
+        diagnostics.track_edits(&mut state);
+        assert!(!diagnostics.needs_tracking(&state));
+
+        // Text inserted above shifts the range; text typed at its start stays outside
+        state.insert_text_at(0, "// x\n");
+        state.insert_text_at(24, " ");
+        assert!(diagnostics.needs_tracking(&state));
+        diagnostics.track_edits(&mut state);
+        assert_eq!(diagnostics.items()[0].range, 25..26);
+
+        // Replacing the diagnostics releases the old anchors
+        let anchors = state.anchors.len();
+        diagnostics.set(vec![error_at(0..2)]);
+        diagnostics.track_edits(&mut state);
+        assert_eq!(state.anchors.len(), anchors);
+    }
+}
@@ -11,10 +11,19 @@ pub fn default_input_map() -> InputMap<EditorAction> {
     input_map.insert(EditorAction::DeleteForward, KeyCode::Delete);
     input_map.insert(EditorAction::DeleteWordBackward, ButtonlikeChord::new([KeyCode::ControlLeft, KeyCode::Backspace]));
     input_map.insert(EditorAction::DeleteWordForward, ButtonlikeChord::new([KeyCode::ControlLeft, KeyCode::Delete]));
+    input_map.insert(EditorAction::DeleteLine, ButtonlikeChord::new([KeyCode::ControlLeft, KeyCode::ShiftLeft, KeyCode::KeyK]));
 
     // Special insertion
     input_map.insert(EditorAction::InsertNewline, KeyCode::Enter);
     input_map.insert(EditorAction::InsertTab, KeyCode::Tab);
+    input_map.insert(EditorAction::Outdent, ButtonlikeChord::new([KeyCode::ShiftLeft, KeyCode::Tab]));
+    input_map.insert(EditorAction::IndentSelection, ButtonlikeChord::new([KeyCode::ControlLeft, KeyCode::BracketRight]));
+    input_map.insert(EditorAction::OutdentSelection, ButtonlikeChord::new([KeyCode::ControlLeft, KeyCode::BracketLeft]));
+    input_map.insert(EditorAction::DuplicateSelection, ButtonlikeChord::new([KeyCode::ControlLeft, KeyCode::ShiftLeft, KeyCode::KeyD]));
+    input_map.insert(EditorAction::DuplicateLine, ButtonlikeChord::new([KeyCode::ShiftLeft, KeyCode::AltLeft, KeyCode::ArrowDown]));
+    input_map.insert(EditorAction::ToggleLineComment, ButtonlikeChord::new([KeyCode::ControlLeft, KeyCode::Slash]));
+    input_map.insert(EditorAction::MoveLineUp, ButtonlikeChord::new([KeyCode::AltLeft, KeyCode::ArrowUp]));
+    input_map.insert(EditorAction::MoveLineDown, ButtonlikeChord::new([KeyCode::AltLeft, KeyCode::ArrowDown]));
 
     // Cursor movement
     input_map.insert(EditorAction::MoveCursorLeft, KeyCode::ArrowLeft);
@@ -40,12 +49,13 @@ pub fn default_input_map() -> InputMap<EditorAction> {
     input_map.insert(EditorAction::SelectLineStart, ButtonlikeChord::new([KeyCode::ShiftLeft, KeyCode::Home]));
     input_map.insert(EditorAction::SelectLineEnd, ButtonlikeChord::new([KeyCode::ShiftLeft, KeyCode::End]));
     input_map.insert(EditorAction::SelectAll, ButtonlikeChord::new([KeyCode::ControlLeft, KeyCode::KeyA]));
-    input_map.insert(EditorAction::ClearSelection, KeyCode::Escape);
+    input_map.insert(EditorAction::Escape, KeyCode::Escape);
 
     // Clipboard
     input_map.insert(EditorAction::Copy, ButtonlikeChord::new([KeyCode::ControlLeft, KeyCode::KeyC]));
     input_map.insert(EditorAction::Cut, ButtonlikeChord::new([KeyCode::ControlLeft, KeyCode::KeyX]));
     input_map.insert(EditorAction::Paste, ButtonlikeChord::new([KeyCode::ControlLeft, KeyCode::KeyV]));
+    input_map.insert(EditorAction::PasteCycle, ButtonlikeChord::new([KeyCode::AltLeft, KeyCode::KeyY]));
 
     // Undo/Redo
     input_map.insert(EditorAction::Undo, ButtonlikeChord::new([KeyCode::ControlLeft, KeyCode::KeyZ]));
@@ -57,19 +67,34 @@ pub fn default_input_map() -> InputMap<EditorAction> {
     input_map.insert(EditorAction::FindNext, KeyCode::F3);
     input_map.insert(EditorAction::FindPrevious, ButtonlikeChord::new([KeyCode::ShiftLeft, KeyCode::F3]));
     input_map.insert(EditorAction::Replace, ButtonlikeChord::new([KeyCode::ControlLeft, KeyCode::KeyH]));
+    input_map.insert(EditorAction::ReplaceNext, ButtonlikeChord::new([KeyCode::ControlLeft, KeyCode::ShiftLeft, KeyCode::Digit1]));
+    input_map.insert(EditorAction::ReplaceAll, ButtonlikeChord::new([KeyCode::ControlLeft, KeyCode::AltLeft, KeyCode::Enter]));
+    input_map.insert(EditorAction::ToggleCaseSensitive, ButtonlikeChord::new([KeyCode::AltLeft, KeyCode::KeyC]));
+    input_map.insert(EditorAction::ToggleWholeWord, ButtonlikeChord::new([KeyCode::AltLeft, KeyCode::KeyW]));
 
     // Navigation
     input_map.insert(EditorAction::GotoLine, ButtonlikeChord::new([KeyCode::ControlLeft, KeyCode::KeyG]));
+    input_map.insert(EditorAction::NextDiagnostic, KeyCode::F8);
+    input_map.insert(EditorAction::PrevDiagnostic, ButtonlikeChord::new([KeyCode::ShiftLeft, KeyCode::F8]));
+    input_map.insert(EditorAction::GoToLastEdit, ButtonlikeChord::new([KeyCode::ControlLeft, KeyCode::ShiftLeft, KeyCode::Backspace]));
+    input_map.insert(EditorAction::ToggleBookmark, ButtonlikeChord::new([KeyCode::ControlLeft, KeyCode::AltLeft, KeyCode::KeyK]));
+    input_map.insert(EditorAction::NextBookmark, ButtonlikeChord::new([KeyCode::ControlLeft, KeyCode::AltLeft, KeyCode::KeyL]));
+    input_map.insert(EditorAction::PrevBookmark, ButtonlikeChord::new([KeyCode::ControlLeft, KeyCode::AltLeft, KeyCode::KeyJ]));
 
     // LSP
     input_map.insert(EditorAction::RequestCompletion, ButtonlikeChord::new([KeyCode::ControlLeft, KeyCode::Space]));
     input_map.insert(EditorAction::RenameSymbol, KeyCode::F2);
 
     // Multi-cursor
-    input_map.insert(EditorAction::AddCursorAtNextOccurrence, ButtonlikeChord::new([KeyCode::ControlLeft, KeyCode::KeyD]));
+    input_map.insert(EditorAction::SelectNextOccurrence, ButtonlikeChord::new([KeyCode::ControlLeft, KeyCode::KeyD]));
+    input_map.insert(EditorAction::SelectAllOccurrences, ButtonlikeChord::new([KeyCode::ControlLeft, KeyCode::ShiftLeft, KeyCode::KeyL]));
     input_map.insert(EditorAction::AddCursorAbove, ButtonlikeChord::new([KeyCode::ControlLeft, KeyCode::AltLeft, KeyCode::ArrowUp]));
     input_map.insert(EditorAction::AddCursorBelow, ButtonlikeChord::new([KeyCode::ControlLeft, KeyCode::AltLeft, KeyCode::ArrowDown]));
 
+    // Keyboard macros
+    input_map.insert(EditorAction::ToggleMacroRecording, ButtonlikeChord::new([KeyCode::ControlLeft, KeyCode::AltLeft, KeyCode::KeyR]));
+    input_map.insert(EditorAction::PlayMacro, ButtonlikeChord::new([KeyCode::ControlLeft, KeyCode::AltLeft, KeyCode::KeyP]));
+
     // Code folding
     input_map.insert(EditorAction::ToggleFold, ButtonlikeChord::new([KeyCode::ControlLeft, KeyCode::ShiftLeft, KeyCode::BracketLeft]));
     input_map.insert(EditorAction::Fold, ButtonlikeChord::new([KeyCode::ControlLeft, KeyCode::ShiftLeft, KeyCode::BracketLeft]));
@@ -77,6 +102,14 @@ pub fn default_input_map() -> InputMap<EditorAction> {
     // FoldAll and UnfoldAll typically use Ctrl+K followed by another key - we'll use simpler bindings
     input_map.insert(EditorAction::FoldAll, ButtonlikeChord::new([KeyCode::ControlLeft, KeyCode::AltLeft, KeyCode::BracketLeft]));
     input_map.insert(EditorAction::UnfoldAll, ButtonlikeChord::new([KeyCode::ControlLeft, KeyCode::AltLeft, KeyCode::BracketRight]));
+    input_map.insert(EditorAction::FoldSelection, ButtonlikeChord::new([KeyCode::ControlLeft, KeyCode::AltLeft, KeyCode::Comma]));
+    for (action, digit) in EditorAction::FOLD_LEVELS.into_iter().zip([
+        KeyCode::Digit1, KeyCode::Digit2, KeyCode::Digit3,
+        KeyCode::Digit4, KeyCode::Digit5, KeyCode::Digit6,
+        KeyCode::Digit7, KeyCode::Digit8, KeyCode::Digit9,
+    ]) {
+        input_map.insert(action, ButtonlikeChord::new([KeyCode::ControlLeft, KeyCode::AltLeft, digit]));
+    }
 
     // File operations
     input_map.insert(EditorAction::Save, ButtonlikeChord::new([KeyCode::ControlLeft, KeyCode::KeyS]));
@@ -98,6 +131,26 @@ pub enum EditorAction {
     // Special insertion
     InsertNewline,
     InsertTab,
+    /// Remove one indent level from the current or selected lines (Shift+Tab)
+    Outdent,
+    /// Add one indent level to the current or selected lines (Ctrl+]; Tab
+    /// does this when text is selected)
+    IndentSelection,
+    /// Remove one indent level from the current or selected lines (Ctrl+[;
+    /// same as `Outdent`)
+    OutdentSelection,
+    /// Copy each selection right after itself and select the copy
+    /// (duplicates the line for empty selections)
+    DuplicateSelection,
+    /// Copy the line(s) under each cursor below themselves, moving the cursor
+    /// onto the copy (Shift+Alt+Down)
+    DuplicateLine,
+    /// Comment or uncomment the selected lines with `SyntaxSettings::line_comment` (Ctrl+/)
+    ToggleLineComment,
+    /// Swap the current line (or selected lines) with the line above (Alt+Up)
+    MoveLineUp,
+    /// Swap the current line (or selected lines) with the line below (Alt+Down)
+    MoveLineDown,
 
     // Cursor movement
     MoveCursorLeft,
@@ -124,11 +177,16 @@ pub enum EditorAction {
     SelectLineEnd,
     SelectAll,
     ClearSelection,
+    /// Dismiss the most relevant transient state (Escape), see `escape`
+    Escape,
 
     // Clipboard
     Copy,
     Cut,
     Paste,
+    /// Replace the just-pasted text with the previous kill ring entry (Alt+Y)
+    #[doc(alias = "PasteFromRingPrevious")]
+    PasteCycle,
 
     // Undo/Redo
     Undo,
@@ -139,9 +197,31 @@ pub enum EditorAction {
     FindNext,
     FindPrevious,
     Replace,
+    /// Replace the current match with `FindState::replace_text` (Ctrl+Shift+1)
+    ReplaceNext,
+    /// Replace every match in one undo step (Ctrl+Alt+Enter)
+    ReplaceAll,
+    /// Toggle case-sensitive search (Alt+C)
+    ToggleCaseSensitive,
+    /// Toggle whole-word search (Alt+W)
+    ToggleWholeWord,
 
     // Navigation
     GotoLine,
+    /// Select the next diagnostic, wrapping at the end (F8)
+    NextDiagnostic,
+    /// Select the previous diagnostic, wrapping at the start (Shift+F8)
+    PrevDiagnostic,
+    /// Jump back to the previous edit location (Ctrl+Shift+Backspace)
+    GoToLastEdit,
+    /// Jump forward again after `GoToLastEdit` (unbound by default)
+    GoToNextEdit,
+    /// Add a bookmark on the cursor line, or remove the one already there (Ctrl+Alt+K)
+    ToggleBookmark,
+    /// Jump to the next bookmark, wrapping at the end (Ctrl+Alt+L)
+    NextBookmark,
+    /// Jump to the previous bookmark, wrapping at the start (Ctrl+Alt+J)
+    PrevBookmark,
 
     // LSP
     RequestCompletion,
@@ -150,8 +230,13 @@ pub enum EditorAction {
     RenameSymbol,
 
     // Multi-cursor
-    /// Add cursor at next occurrence of selection (Ctrl+D)
+    /// Add cursor at next occurrence of selection, matching substrings (unbound by default)
     AddCursorAtNextOccurrence,
+    /// Select the word under the cursor, then add the next whole-word
+    /// occurrence of the selection on each press (Ctrl+D)
+    SelectNextOccurrence,
+    /// Select every whole-word occurrence of the selection (Ctrl+Shift+L)
+    SelectAllOccurrences,
     /// Add cursor above current cursor (Ctrl+Alt+Up)
     AddCursorAbove,
     /// Add cursor below current cursor (Ctrl+Alt+Down)
@@ -159,6 +244,12 @@ pub enum EditorAction {
     /// Clear all secondary cursors, keeping only the primary one (Escape when multi-cursor)
     ClearSecondaryCursors,
 
+    // Keyboard macros
+    /// Start recording a keyboard macro, or stop the current recording (Ctrl+Alt+R)
+    ToggleMacroRecording,
+    /// Replay the last recorded keyboard macro (Ctrl+Alt+P)
+    PlayMacro,
+
     // Code folding
     /// Toggle fold at current line (Ctrl+Shift+[)
     ToggleFold,
@@ -170,15 +261,61 @@ pub enum EditorAction {
     FoldAll,
     /// Unfold all regions (Ctrl+K Ctrl+J)
     UnfoldAll,
+    /// Create a manual fold over the selected lines (Ctrl+Alt+,)
+    FoldSelection,
+    /// Fold every comment block (unbound by default)
+    FoldAllComments,
+    /// Fold every import group (unbound by default)
+    FoldAllImports,
+    /// Fold everything indented at level 1 or deeper (Ctrl+Alt+1)
+    FoldLevel1,
+    /// Fold everything indented at level 2 or deeper (Ctrl+Alt+2)
+    FoldLevel2,
+    /// Fold everything indented at level 3 or deeper (Ctrl+Alt+3)
+    FoldLevel3,
+    /// Fold everything indented at level 4 or deeper (Ctrl+Alt+4)
+    FoldLevel4,
+    /// Fold everything indented at level 5 or deeper (Ctrl+Alt+5)
+    FoldLevel5,
+    /// Fold everything indented at level 6 or deeper (Ctrl+Alt+6)
+    FoldLevel6,
+    /// Fold everything indented at level 7 or deeper (Ctrl+Alt+7)
+    FoldLevel7,
+    /// Fold everything indented at level 8 or deeper (Ctrl+Alt+8)
+    FoldLevel8,
+    /// Fold everything indented at level 9 or deeper (Ctrl+Alt+9)
+    FoldLevel9,
 
     // File operations (emit events for host app to handle)
     /// Save the current buffer (Ctrl+S) - emits SaveRequested event
     Save,
     /// Open a file (Ctrl+O) - emits OpenRequested event
     Open,
+
+    // Host-defined actions
+    /// Runs the handler registered for this id in `CustomActions` (unbound by default)
+    CustomAction(u32),
 }
 
 impl EditorAction {
+    /// The fold-to-level actions, `FoldLevel1` through `FoldLevel9`
+    pub const FOLD_LEVELS: [EditorAction; 9] = [
+        EditorAction::FoldLevel1,
+        EditorAction::FoldLevel2,
+        EditorAction::FoldLevel3,
+        EditorAction::FoldLevel4,
+        EditorAction::FoldLevel5,
+        EditorAction::FoldLevel6,
+        EditorAction::FoldLevel7,
+        EditorAction::FoldLevel8,
+        EditorAction::FoldLevel9,
+    ];
+
+    /// The indentation level of a `FoldLevelN` action
+    pub fn fold_level(&self) -> Option<usize> {
+        Self::FOLD_LEVELS.iter().position(|a| a == self).map(|i| i + 1)
+    }
+
     /// Returns true if this action should repeat when the key is held down
     pub fn is_repeatable(&self) -> bool {
         matches!(
@@ -207,4 +344,35 @@ impl EditorAction {
                 | EditorAction::Redo
         )
     }
+
+    /// Whether the action can change the buffer text (blocked when read-only)
+    pub fn modifies_text(&self) -> bool {
+        matches!(
+            self,
+            EditorAction::DeleteBackward
+                | EditorAction::DeleteForward
+                | EditorAction::DeleteWordBackward
+                | EditorAction::DeleteWordForward
+                | EditorAction::DeleteLine
+                | EditorAction::InsertNewline
+                | EditorAction::InsertTab
+                | EditorAction::Outdent
+                | EditorAction::IndentSelection
+                | EditorAction::OutdentSelection
+                | EditorAction::DuplicateSelection
+                | EditorAction::DuplicateLine
+                | EditorAction::ToggleLineComment
+                | EditorAction::MoveLineUp
+                | EditorAction::MoveLineDown
+                | EditorAction::Cut
+                | EditorAction::Paste
+                | EditorAction::PasteCycle
+                | EditorAction::Undo
+                | EditorAction::Redo
+                | EditorAction::ReplaceNext
+                | EditorAction::ReplaceAll
+                | EditorAction::RenameSymbol
+                | EditorAction::PlayMacro
+        )
+    }
 }
\ No newline at end of file
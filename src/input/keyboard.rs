@@ -3,20 +3,23 @@ use bevy::input::keyboard::KeyboardInput;
 use leafwing_input_manager::prelude::*;
 use std::time::Instant;
 use crate::types::*;
-use crate::settings::{CursorSettings, BracketSettings, IndentationSettings};
+use crate::settings::{CursorSettings, BracketSettings, IndentationSettings, SyntaxSettings, TabBehavior};
 #[cfg(feature = "lsp")]
 use crate::settings::LspSettings;
-use crate::plugin::EditorInputManager;
+use crate::plugin::{EditorInputManager, find_enclosing_bracket};
 use super::keybindings::EditorAction;
+use super::macro_recorder::{MacroRecorder, MacroStep};
 use super::actions::{
-    insert_char, execute_action, insert_closing_char,
+    insert_char, execute_action, insert_closing_char, surround_selection,
     get_closing_bracket, get_closing_quote, should_skip_auto_close,
+    get_multi_char_closing, should_skip_multi_char_close, insert_closing_text, delete_multi_char_pair,
+    toggle_line_comment,
 };
 #[cfg(feature = "lsp")]
-use super::actions::{send_did_change, request_completion, update_completion_filter, find_word_start};
+use super::actions::{send_did_change, request_completion, request_format_on_save, update_completion_filter, find_word_start};
 
 /// All possible editor actions for iteration
-const ALL_ACTIONS: [EditorAction; 48] = [
+const ALL_ACTIONS: [EditorAction; 71] = [
     EditorAction::DeleteBackward,
     EditorAction::DeleteForward,
     EditorAction::DeleteWordBackward,
@@ -24,6 +27,14 @@ const ALL_ACTIONS: [EditorAction; 48] = [
     EditorAction::DeleteLine,
     EditorAction::InsertNewline,
     EditorAction::InsertTab,
+    EditorAction::Outdent,
+    EditorAction::IndentSelection,
+    EditorAction::OutdentSelection,
+    EditorAction::DuplicateSelection,
+    EditorAction::DuplicateLine,
+    EditorAction::ToggleLineComment,
+    EditorAction::MoveLineUp,
+    EditorAction::MoveLineDown,
     EditorAction::MoveCursorLeft,
     EditorAction::MoveCursorRight,
     EditorAction::MoveCursorUp,
@@ -46,23 +57,38 @@ const ALL_ACTIONS: [EditorAction; 48] = [
     EditorAction::SelectLineEnd,
     EditorAction::SelectAll,
     EditorAction::ClearSelection,
+    EditorAction::Escape,
     EditorAction::Copy,
     EditorAction::Cut,
     EditorAction::Paste,
+    EditorAction::PasteCycle,
     EditorAction::Undo,
     EditorAction::Redo,
     EditorAction::Find,
     EditorAction::FindNext,
     EditorAction::FindPrevious,
     EditorAction::Replace,
+    EditorAction::ReplaceNext,
+    EditorAction::ReplaceAll,
+    EditorAction::ToggleCaseSensitive,
+    EditorAction::ToggleWholeWord,
     EditorAction::GotoLine,
+    EditorAction::GoToLastEdit,
+    EditorAction::GoToNextEdit,
+    EditorAction::ToggleBookmark,
+    EditorAction::NextBookmark,
+    EditorAction::PrevBookmark,
     EditorAction::RequestCompletion,
     EditorAction::GotoDefinition,
     EditorAction::RenameSymbol,
     EditorAction::AddCursorAtNextOccurrence,
+    EditorAction::SelectNextOccurrence,
+    EditorAction::SelectAllOccurrences,
     EditorAction::AddCursorAbove,
     EditorAction::AddCursorBelow,
     EditorAction::ClearSecondaryCursors,
+    EditorAction::ToggleMacroRecording,
+    EditorAction::PlayMacro,
     EditorAction::Save,
     EditorAction::Open,
 ];
@@ -73,19 +99,25 @@ pub fn handle_keyboard_input(
     mut char_events: MessageReader<KeyboardInput>,
     action_query: Query<&ActionState<EditorAction>, With<EditorInputManager>>,
     cursor: Res<CursorSettings>,
-    brackets: Res<BracketSettings>,
-    indentation: Res<IndentationSettings>,
+    (brackets, syntax): (Res<BracketSettings>, Res<SyntaxSettings>),
+    (indentation, keys): (Res<IndentationSettings>, Res<ButtonInput<KeyCode>>),
     #[cfg(feature = "lsp")] lsp: Res<LspSettings>,
     mut find_state: ResMut<FindState>,
     mut goto_line_state: ResMut<GotoLineState>,
     mut fold_state: ResMut<FoldState>,
-    mut key_repeat_state: ResMut<KeyRepeatState>,
-    mut save_events: MessageWriter<crate::types::SaveRequested>,
-    mut open_events: MessageWriter<crate::types::OpenRequested>,
-    #[cfg(feature = "lsp")] lsp_client: Res<crate::lsp::LspClient>,
-    #[cfg(feature = "lsp")] mut completion_state: ResMut<crate::lsp::CompletionState>,
+    (mut key_repeat_state, mut macro_recorder): (ResMut<KeyRepeatState>, ResMut<MacroRecorder>),
+    (mut kill_ring, mut snippet): (ResMut<KillRing>, ResMut<SnippetSession>),
+    (mut save_events, mut open_events): (MessageWriter<crate::types::SaveRequested>, MessageWriter<crate::types::OpenRequested>),
+    #[cfg(feature = "lsp")] (mut completion_state, mut hover_state): (
+        ResMut<crate::lsp::CompletionState>,
+        ResMut<crate::lsp::HoverState>,
+    ),
     #[cfg(feature = "lsp")] mut rename_state: ResMut<crate::lsp::state::RenameState>,
-    #[cfg(feature = "lsp")] mut lsp_sync: ResMut<crate::lsp::LspSyncState>,
+    #[cfg(feature = "lsp")] (lsp_client, mut lsp_sync, mut format_on_save): (
+        Res<crate::lsp::LspClient>,
+        ResMut<crate::lsp::LspSyncState>,
+        ResMut<crate::lsp::state::FormatOnSaveState>,
+    ),
 ) {
     // Only process input if editor is focused
     if !state.is_focused {
@@ -147,6 +179,39 @@ pub fn handle_keyboard_input(
         return;
     }
 
+    // Find input has focus - typed keys edit the query, not the buffer.
+    // Ctrl/Alt chords (Save, Undo, ...) are not text and go to normal dispatch.
+    let chord = keys.any_pressed([
+        KeyCode::ControlLeft,
+        KeyCode::ControlRight,
+        KeyCode::AltLeft,
+        KeyCode::AltRight,
+        KeyCode::SuperLeft,
+        KeyCode::SuperRight,
+    ]);
+    if find_state.active && find_state.find_focused && !chord {
+        // Search option toggles still work while typing in the find input
+        let mut follow_up = [EditorAction::ToggleCaseSensitive, EditorAction::ToggleWholeWord]
+            .into_iter()
+            .find(|action| action_state.just_pressed(action));
+        if follow_up.is_some() {
+            char_events.clear();
+        }
+        for event in char_events.read() {
+            if event.state.is_pressed() {
+                follow_up = find_input_key(&mut find_state, &state.rope, &event.logical_key).or(follow_up);
+            }
+        }
+        if let Some(action) = follow_up {
+            #[cfg(not(feature = "lsp"))]
+            execute_action(&mut state, action, &indentation, &cursor, &mut find_state, &mut goto_line_state, &mut fold_state, &mut kill_ring);
+            #[cfg(feature = "lsp")]
+            execute_action(&mut state, action, &indentation, &cursor, &lsp, &mut find_state, &mut goto_line_state, &mut fold_state, &mut kill_ring, &mut snippet, &lsp_client, &mut completion_state, &mut hover_state, &mut lsp_sync);
+        }
+        // Consume all events and return - don't process normal editor input
+        return;
+    }
+
     let mut action_to_execute: Option<EditorAction> = None;
     let now = Instant::now();
 
@@ -173,7 +238,10 @@ pub fn handle_keyboard_input(
             EditorAction::Unfold,
             EditorAction::FoldAll,
             EditorAction::UnfoldAll,
-        ] {
+            EditorAction::FoldSelection,
+            EditorAction::FoldAllComments,
+            EditorAction::FoldAllImports,
+        ].into_iter().chain(EditorAction::FOLD_LEVELS) {
             if action_state.just_pressed(&action) {
                 action_to_execute = Some(action);
                 break;
@@ -215,9 +283,15 @@ pub fn handle_keyboard_input(
         }
     }
 
+    // Custom actions are run by handle_custom_actions, but their keys must not type text
+    let custom_action_pressed = action_state
+        .get_just_pressed()
+        .iter()
+        .any(|action| matches!(action, EditorAction::CustomAction(_)));
+
     // Handle character input (for printable characters)
-    // Only process if no keybinding action was triggered
-    if action_to_execute.is_none() {
+    // Only process if no keybinding action was triggered and the buffer is editable
+    if action_to_execute.is_none() && !custom_action_pressed && !state.read_only {
         for event in char_events.read() {
             // Only handle key presses with text
             if event.state.is_pressed() {
@@ -228,60 +302,13 @@ pub fn handle_keyboard_input(
                             if c.is_control() {
                                 continue;
                             }
+                            macro_recorder.record(MacroStep::Char(c));
 
-                            // Check for quote skip-over (typing closing quote when already there)
-                            if brackets.auto_close_quotes
-                                && get_closing_quote(c).is_some()
-                                    && should_skip_auto_close(&state, c) {
-                                        // Just move cursor past the existing quote
-                                        state.move_cursor(1);
-                                        state.pending_update = true;
-                                        continue;
-                                    }
-
-                            // Check for bracket skip-over (typing closing bracket when already there)
-                            if brackets.auto_close {
-                                let is_closing_bracket = brackets.pairs.iter()
-                                    .any(|(_, close)| *close == c);
-                                if is_closing_bracket && should_skip_auto_close(&state, c) {
-                                    // Just move cursor past the existing bracket
-                                    state.move_cursor(1);
-                                    state.pending_update = true;
-                                    continue;
-                                }
-                            }
-
-                            insert_char(&mut state, c);
-
-                            // Auto-close brackets
-                            if brackets.auto_close {
-                                if let Some(closing) = get_closing_bracket(c, &brackets.pairs) {
-                                    insert_closing_char(&mut state, closing);
-                                }
-                            }
-
-                            // Auto-close quotes
-                            if brackets.auto_close_quotes {
-                                if let Some(closing) = get_closing_quote(c) {
-                                    // Only auto-close if we didn't just skip over an existing quote
-                                    // and if the previous char wasn't an alphanumeric (to avoid closing in contractions like "don't")
-                                    let should_close = if c == '\'' {
-                                        // For single quotes, check if previous char is alphanumeric
-                                        let cursor = state.cursor_pos;
-                                        if cursor >= 2 {
-                                            let prev_char = state.rope.char(cursor - 2);
-                                            !prev_char.is_alphanumeric()
-                                        } else {
-                                            true
-                                        }
-                                    } else {
-                                        true
-                                    };
-
-                                    if should_close {
-                                        insert_closing_char(&mut state, closing);
-                                    }
-                                }
+                            // With several cursors, type at all of them in one undo step
+                            if state.has_multiple_cursors() {
+                                state.type_at_cursors(&c.to_string());
+                            } else if !type_char(&mut state, c, &brackets) {
+                                continue;
                             }
 
                             // Notify LSP of text change
@@ -317,6 +344,7 @@ pub fn handle_keyboard_input(
                     }
                     // Bevy sends Space as a separate variant, not Character(" ")
                     bevy::input::keyboard::Key::Space => {
+                        macro_recorder.record(MacroStep::Char(' '));
                         insert_char(&mut state, ' ');
                         // Notify LSP of text change
                         #[cfg(feature = "lsp")]
@@ -332,23 +360,135 @@ pub fn handle_keyboard_input(
             }
         }
     } else {
-        // Drain character events when a keybinding was triggered
-        // This prevents the character from being inserted
+        // Drain character events when a keybinding was triggered (or typing is
+        // blocked). This prevents the character from being inserted
         for _ in char_events.read() {}
     }
 
     // Execute the action if we have one
     if let Some(action) = action_to_execute {
+        if state.read_only && action.modifies_text() {
+            return;
+        }
+        macro_recorder.record(MacroStep::Action(action));
+        if action == EditorAction::ToggleMacroRecording {
+            macro_recorder.toggle();
+            return;
+        }
+
+        let mut dispatch = ActionDispatch {
+            state: &mut state,
+            brackets: &brackets,
+            syntax: &syntax,
+            indentation: &indentation,
+            cursor: &cursor,
+            #[cfg(feature = "lsp")]
+            lsp: &lsp,
+            find_state: &mut find_state,
+            goto_line_state: &mut goto_line_state,
+            fold_state: &mut fold_state,
+            kill_ring: &mut kill_ring,
+            snippet: &mut snippet,
+            save_events: &mut save_events,
+            open_events: &mut open_events,
+            #[cfg(feature = "lsp")]
+            completion_state: &mut completion_state,
+            #[cfg(feature = "lsp")]
+            hover_state: &mut hover_state,
+            #[cfg(feature = "lsp")]
+            rename_state: &mut rename_state,
+            #[cfg(feature = "lsp")]
+            lsp_client: &lsp_client,
+            #[cfg(feature = "lsp")]
+            lsp_sync: &mut lsp_sync,
+            #[cfg(feature = "lsp")]
+            format_on_save: &mut format_on_save,
+        };
+
+        // Replay the recorded macro (ignored while still recording it)
+        if action == EditorAction::PlayMacro {
+            if !macro_recorder.recording {
+                dispatch.replay(&macro_recorder.steps);
+            }
+            return;
+        }
+
+        dispatch.dispatch(action);
+    }
+}
+
+/// Everything a keybinding action can touch, borrowed from the keyboard handler
+///
+/// Key presses and macro replay both run actions through `dispatch`, so a
+/// replayed step behaves exactly like the key that recorded it.
+pub(crate) struct ActionDispatch<'a, 'w> {
+    pub state: &'a mut CodeEditorState,
+    pub brackets: &'a BracketSettings,
+    pub syntax: &'a SyntaxSettings,
+    pub indentation: &'a IndentationSettings,
+    pub cursor: &'a CursorSettings,
+    #[cfg(feature = "lsp")]
+    pub lsp: &'a LspSettings,
+    pub find_state: &'a mut FindState,
+    pub goto_line_state: &'a mut GotoLineState,
+    pub fold_state: &'a mut FoldState,
+    pub kill_ring: &'a mut KillRing,
+    pub snippet: &'a mut SnippetSession,
+    pub save_events: &'a mut MessageWriter<'w, crate::types::SaveRequested>,
+    pub open_events: &'a mut MessageWriter<'w, crate::types::OpenRequested>,
+    #[cfg(feature = "lsp")]
+    pub completion_state: &'a mut crate::lsp::CompletionState,
+    #[cfg(feature = "lsp")]
+    pub hover_state: &'a mut crate::lsp::HoverState,
+    #[cfg(feature = "lsp")]
+    pub rename_state: &'a mut crate::lsp::state::RenameState,
+    #[cfg(feature = "lsp")]
+    pub lsp_client: &'a crate::lsp::LspClient,
+    #[cfg(feature = "lsp")]
+    pub lsp_sync: &'a mut crate::lsp::LspSyncState,
+    #[cfg(feature = "lsp")]
+    pub format_on_save: &'a mut crate::lsp::state::FormatOnSaveState,
+}
+
+impl ActionDispatch<'_, '_> {
+    /// Replay recorded macro steps, then tell the server about the result once
+    pub fn replay(&mut self, steps: &[MacroStep]) {
+        for step in steps {
+            match *step {
+                MacroStep::Char(_) if self.state.read_only => {}
+                MacroStep::Char(c) if self.state.has_multiple_cursors() => self.state.type_at_cursors(&c.to_string()),
+                MacroStep::Char(c) => {
+                    type_char(self.state, c, self.brackets);
+                }
+                MacroStep::Action(action) if self.state.read_only && action.modifies_text() => {}
+                MacroStep::Action(action) => self.dispatch(action),
+            }
+        }
+        #[cfg(feature = "lsp")]
+        send_did_change(self.state, self.lsp_client, self.lsp_sync);
+    }
+
+    /// Run an action as its key binding would
+    pub fn dispatch(&mut self, action: EditorAction) {
+        let brackets = self.brackets;
+
         // Handle Save action - emit event for host app
         if action == EditorAction::Save {
-            let content: String = state.rope.chars().collect();
-            save_events.write(crate::types::SaveRequested { content });
+            // With format-on-save the event is emitted once the formatting edits arrive
+            #[cfg(feature = "lsp")]
+            if self.lsp.format_on_save
+                && request_format_on_save(self.state, self.indentation, self.lsp_client, self.lsp_sync, self.format_on_save)
+            {
+                return;
+            }
+            let content: String = self.state.rope.chars().collect();
+            self.save_events.write(crate::types::SaveRequested { content });
             return;
         }
 
         // Handle Open action - emit event for host app
         if action == EditorAction::Open {
-            open_events.write(crate::types::OpenRequested);
+            self.open_events.write(crate::types::OpenRequested);
             return;
         }
 
@@ -357,16 +497,16 @@ pub fn handle_keyboard_input(
         if action == EditorAction::RenameSymbol {
             eprintln!("[Rename] RenameSymbol action triggered");
             eprintln!("[Rename] supports_rename: {}, supports_prepare_rename: {}",
-                lsp_client.capabilities.supports_rename(),
-                lsp_client.capabilities.supports_prepare_rename());
-            eprintln!("[Rename] document_uri: {:?}", lsp_sync.document_uri);
+                self.lsp_client.capabilities.supports_rename(),
+                self.lsp_client.capabilities.supports_prepare_rename());
+            eprintln!("[Rename] document_uri: {:?}", self.lsp_sync.document_uri);
 
-            if lsp_client.capabilities.supports_rename() {
-                if let Some(uri) = &lsp_sync.document_uri {
+            if self.lsp_client.capabilities.supports_rename() {
+                if let Some(uri) = &self.lsp_sync.document_uri {
                     // Convert cursor position to LSP position
-                    let cursor_pos = state.cursor_pos.min(state.rope.len_chars());
-                    let line = state.rope.char_to_line(cursor_pos);
-                    let line_start = state.rope.line_to_char(line);
+                    let cursor_pos = self.state.cursor_pos.min(self.state.rope.len_chars());
+                    let line = self.state.rope.char_to_line(cursor_pos);
+                    let line_start = self.state.rope.line_to_char(line);
                     let character = cursor_pos - line_start;
 
                     let position = lsp_types::Position {
@@ -377,8 +517,8 @@ pub fn handle_keyboard_input(
                     eprintln!("[Rename] Requesting prepare rename at line={}, char={}", position.line, position.character);
 
                     // Start prepare rename flow
-                    rename_state.start_prepare(position);
-                    crate::lsp::systems::request_prepare_rename(&lsp_client, uri, position);
+                    self.rename_state.start_prepare(position);
+                    crate::lsp::systems::request_prepare_rename(self.lsp_client, uri, position);
                 }
             } else {
                 eprintln!("[Rename] Server doesn't support rename");
@@ -386,9 +526,561 @@ pub fn handle_keyboard_input(
             return;
         }
 
+        // Backspace between an empty multi-character pair removes both halves
+        if action == EditorAction::DeleteBackward
+            && brackets.auto_close
+            && self.state.selection_start.is_none()
+            && !self.state.has_multiple_cursors()
+            && delete_multi_char_pair(self.state, &brackets.multi_char_pairs)
+        {
+            #[cfg(feature = "lsp")]
+            send_did_change(self.state, self.lsp_client, self.lsp_sync);
+            return;
+        }
+
+        // The comment token is language-specific, so it comes from SyntaxSettings
+        if action == EditorAction::ToggleLineComment {
+            if let Some(token) = self.syntax.line_comment.as_deref() {
+                if toggle_line_comment(self.state, token) {
+                    #[cfg(feature = "lsp")]
+                    send_did_change(self.state, self.lsp_client, self.lsp_sync);
+                }
+            }
+            return;
+        }
+
+        #[cfg(feature = "lsp")]
+        let completion_open = self.completion_state.visible && !self.completion_state.filtered_items().is_empty();
+        #[cfg(not(feature = "lsp"))]
+        let completion_open = false;
+
+        // Splitting bracket pairs and continuing lists depend on BracketSettings
+        if action == EditorAction::InsertNewline
+            && !completion_open
+            && insert_newline(self.state, self.indentation, brackets)
+        {
+            #[cfg(feature = "lsp")]
+            send_did_change(self.state, self.lsp_client, self.lsp_sync);
+            return;
+        }
+
+        // Tab / Shift+Tab act on the first applicable context in the configured order
+        if matches!(action, EditorAction::InsertTab | EditorAction::Outdent) {
+            let backward = action == EditorAction::Outdent;
+            match dispatch_tab(self.state, self.snippet, &self.indentation.tab_order, completion_open, backward) {
+                TabBehavior::NextSnippetStop => return,
+                // Keep the open popup from swallowing the indent
+                #[cfg(feature = "lsp")]
+                TabBehavior::Indent => self.completion_state.visible = false,
+                _ => {}
+            }
+        }
+
+        if action == EditorAction::Escape {
+            self.snippet.end(self.state);
+        }
+
         #[cfg(not(feature = "lsp"))]
-        execute_action(&mut state, action, &indentation, &mut find_state, &mut goto_line_state, &mut fold_state);
+        execute_action(self.state, action, self.indentation, self.cursor, self.find_state, self.goto_line_state, self.fold_state, self.kill_ring);
         #[cfg(feature = "lsp")]
-        execute_action(&mut state, action, &indentation, &lsp, &mut find_state, &mut goto_line_state, &mut fold_state, &lsp_client, &mut completion_state, &mut lsp_sync);
+        execute_action(self.state, action, self.indentation, self.cursor, self.lsp, self.find_state, self.goto_line_state, self.fold_state, self.kill_ring, self.snippet, self.lsp_client, self.completion_state, self.hover_state, self.lsp_sync);
+    }
+}
+
+/// First behavior in `order` that applies; indentation always applies
+pub(crate) fn resolve_tab(order: &[TabBehavior], completion_open: bool, snippet_active: bool) -> TabBehavior {
+    order
+        .iter()
+        .copied()
+        .find(|behavior| match behavior {
+            TabBehavior::AcceptCompletion => completion_open,
+            TabBehavior::NextSnippetStop => snippet_active,
+            TabBehavior::Indent => true,
+        })
+        .unwrap_or(TabBehavior::Indent)
+}
+
+/// Resolve Tab (or Shift+Tab when `backward`), moving between snippet stops
+/// if that wins
+///
+/// Accepting a completion and indenting are left to `execute_action`.
+pub(crate) fn dispatch_tab(
+    state: &mut CodeEditorState,
+    snippet: &mut SnippetSession,
+    order: &[TabBehavior],
+    completion_open: bool,
+    backward: bool,
+) -> TabBehavior {
+    let behavior = resolve_tab(order, completion_open, snippet.is_active());
+    if behavior == TabBehavior::NextSnippetStop {
+        if backward {
+            snippet.prev_stop(state);
+        } else {
+            snippet.next_stop(state);
+        }
+    }
+    behavior
+}
+
+/// Type a single character at the cursor, applying bracket/quote auto-close
+///
+/// Returns false if the character only stepped over an existing closer, so no
+/// text changed.
+pub(crate) fn type_char(state: &mut CodeEditorState, c: char, brackets: &BracketSettings) -> bool {
+    // Typing an opener over a selection wraps it instead of replacing it
+    let closer = get_closing_bracket(c, &brackets.pairs)
+        .filter(|_| brackets.auto_close)
+        .or_else(|| get_closing_quote(c).filter(|_| brackets.auto_close_quotes));
+    if let Some(closer) = closer {
+        if surround_selection(state, c, closer) {
+            return true;
+        }
+    }
+
+    // Check for multi-character closer skip-over (typing `*/` in front of an existing `*/`)
+    if brackets.auto_close && should_skip_multi_char_close(state, c, &brackets.multi_char_pairs) {
+        state.move_cursor(1);
+        state.pending_update = true;
+        return false;
+    }
+
+    let types_through = brackets.type_through.contains(&c);
+
+    // Check for quote skip-over (typing closing quote when already there)
+    if brackets.auto_close_quotes
+        && types_through
+        && get_closing_quote(c).is_some()
+            && should_skip_auto_close(state, c) {
+                // Just move cursor past the existing quote
+                state.move_cursor(1);
+                state.pending_update = true;
+                return false;
+            }
+
+    // Check for bracket skip-over (typing closing bracket when already there)
+    if brackets.auto_close {
+        let is_closing_bracket = brackets.pairs.iter()
+            .any(|(_, close)| *close == c);
+        if is_closing_bracket && types_through && should_skip_auto_close(state, c) {
+            // Just move cursor past the existing bracket
+            state.move_cursor(1);
+            state.pending_update = true;
+            return false;
+        }
+    }
+
+    insert_char(state, c);
+
+    // Auto-close multi-character pairs; these take precedence over single characters
+    if brackets.auto_close {
+        if let Some(closing) = get_multi_char_closing(state, &brackets.multi_char_pairs) {
+            insert_closing_text(state, closing);
+            return true;
+        }
+    }
+
+    // Auto-close brackets
+    if brackets.auto_close {
+        if let Some(closing) = get_closing_bracket(c, &brackets.pairs) {
+            insert_closing_char(state, closing);
+        }
+    }
+
+    // Auto-close quotes
+    if brackets.auto_close_quotes {
+        if let Some(closing) = get_closing_quote(c) {
+            // Only auto-close if we didn't just skip over an existing quote
+            // and if the previous char wasn't an alphanumeric (to avoid closing in contractions like "don't")
+            let should_close = if c == '\'' {
+                // For single quotes, check if previous char is alphanumeric
+                let cursor = state.cursor_pos;
+                if cursor >= 2 {
+                    let prev_char = state.rope.char(cursor - 2);
+                    !prev_char.is_alphanumeric()
+                } else {
+                    true
+                }
+            } else {
+                true
+            };
+
+            if should_close {
+                insert_closing_char(state, closing);
+            }
+        }
+    }
+
+    true
+}
+
+/// Break the line at the cursor, splitting an empty bracket pair or
+/// continuing a comma-separated list
+///
+/// Enter between an opener and its closer (`[|]`) moves the closer to its own
+/// line and leaves the cursor on an indented empty line in between. Inside a
+/// list opened by one of `BracketSettings::comma_on_newline`, Enter at the end
+/// of an element first adds the separating comma. Otherwise, with
+/// `auto_indent`, the new line copies the current line's leading whitespace,
+/// plus one level after an opening bracket. Returns false when none of these
+/// apply, leaving the plain newline to `execute_action`.
+pub(crate) fn insert_newline(
+    state: &mut CodeEditorState,
+    indentation: &IndentationSettings,
+    brackets: &BracketSettings,
+) -> bool {
+    if state.selection_start.is_some() || state.has_multiple_cursors() {
+        return false;
+    }
+
+    let pos = state.cursor_pos.min(state.rope.len_chars());
+    let line = state.rope.char_to_line(pos);
+    let line_start = state.rope.line_to_char(line);
+    let line_before: String = state.rope.slice(line_start..pos).chars().collect();
+    let (indent, unit) = if indentation.auto_indent {
+        let indent: String = line_before.chars().take_while(|c| *c == ' ' || *c == '\t').collect();
+        (indent, indentation.indent_unit())
+    } else {
+        (String::new(), String::new())
+    };
+
+    let prev = line_before.chars().last();
+    let next = (pos < state.rope.len_chars()).then(|| state.rope.char(pos));
+    let splits_pair = match (prev, next) {
+        (Some(open), Some(close)) => brackets.pairs.contains(&(open, close)),
+        _ => false,
+    };
+
+    let (text, cursor_offset) = if splits_pair {
+        let inner = format!("\n{}{}", indent, unit);
+        let cursor_offset = inner.chars().count();
+        (format!("{}\n{}", inner, indent), cursor_offset)
+    } else if needs_list_comma(state, pos, &line_before, brackets) {
+        let text = format!(",\n{}", indent);
+        let cursor_offset = text.chars().count();
+        (text, cursor_offset)
+    } else if indentation.auto_indent {
+        let opens_block = line_before
+            .trim_end()
+            .chars()
+            .last()
+            .is_some_and(|c| brackets.pairs.iter().any(|&(open, _)| open == c));
+        let extra = if opens_block { unit.as_str() } else { "" };
+        let text = format!("\n{}{}", indent, extra);
+        let cursor_offset = text.chars().count();
+        (text, cursor_offset)
+    } else {
+        return false;
+    };
+
+    state.sync_cursors_from_primary();
+    let before = SelectionCollection::from_cursors(&state.cursors);
+    state.insert_text_at(pos, &text);
+    state.cursor_pos = pos + cursor_offset;
+    state.sync_cursors_from_primary();
+    let after = SelectionCollection::from_cursors(&state.cursors);
+    state.record_transaction(
+        vec![EditOperation {
+            removed_text: String::new(),
+            inserted_text: text,
+            position: pos,
+            cursor_before: pos,
+            cursor_after: state.cursor_pos,
+            kind: EditKind::Newline,
+        }],
+        before,
+        after,
+    );
+    true
+}
+
+/// Whether Enter at `pos` ends a list element that still needs its comma
+fn needs_list_comma(state: &CodeEditorState, pos: usize, line_before: &str, brackets: &BracketSettings) -> bool {
+    if brackets.comma_on_newline.is_empty() {
+        return false;
+    }
+    // Only at the end of the element's line
+    let line = state.rope.char_to_line(pos);
+    let rest_blank = state.rope.line(line).chars().skip(line_before.chars().count()).all(char::is_whitespace);
+    let last = line_before.trim_end().chars().last();
+    let ends_element = last.is_some_and(|c| c != ',' && !brackets.pairs.iter().any(|&(open, _)| open == c));
+    if !rest_blank || !ends_element {
+        return false;
+    }
+
+    find_enclosing_bracket(&state.rope, pos, &brackets.pairs)
+        .is_some_and(|m| brackets.comma_on_newline.contains(&state.rope.char(m.cursor_bracket_pos)))
+}
+
+/// Apply a key press to the focused find input
+///
+/// Printable keys and Backspace edit the query, Escape hands focus back to the
+/// editor (leaving the matches highlighted), and Enter returns `FindNext` for
+/// the caller to run against the buffer.
+pub(crate) fn find_input_key(
+    find_state: &mut FindState,
+    rope: &ropey::Rope,
+    key: &bevy::input::keyboard::Key,
+) -> Option<EditorAction> {
+    use bevy::input::keyboard::Key;
+
+    match key {
+        Key::Character(text) => {
+            let typed: String = text.chars().filter(|c| !c.is_control()).collect();
+            if !typed.is_empty() {
+                let query = format!("{}{}", find_state.query, typed);
+                find_state.set_query(query, rope);
+            }
+            None
+        }
+        Key::Space => {
+            let query = format!("{} ", find_state.query);
+            find_state.set_query(query, rope);
+            None
+        }
+        Key::Backspace => {
+            let mut query = find_state.query.clone();
+            if query.pop().is_some() {
+                find_state.set_query(query, rope);
+            }
+            None
+        }
+        Key::Enter => Some(EditorAction::FindNext),
+        Key::Escape => {
+            find_state.find_focused = false;
+            None
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::input::keyboard::Key;
+    use crate::input::actions::execute_action_core;
+
+    #[test]
+    fn test_typing_with_find_focused_edits_query_not_buffer() {
+        let state = CodeEditorState::new("let foo = food;\n");
+        let mut find_state = FindState {
+            active: true,
+            find_focused: true,
+            ..Default::default()
+        };
+
+        for c in ["f", "o", "o"] {
+            assert_eq!(find_input_key(&mut find_state, &state.rope, &Key::Character(c.into())), None);
+        }
+
+        assert_eq!(find_state.query, "foo");
+        assert_eq!(find_state.matches.len(), 2);
+        assert_eq!(state.rope.to_string(), "let foo = food;\n");
+
+        find_input_key(&mut find_state, &state.rope, &Key::Backspace);
+        assert_eq!(find_state.query, "fo");
+
+        assert_eq!(
+            find_input_key(&mut find_state, &state.rope, &Key::Enter),
+            Some(EditorAction::FindNext)
+        );
+
+        find_input_key(&mut find_state, &state.rope, &Key::Escape);
+        assert!(!find_state.find_focused);
+        assert!(find_state.active);
+    }
+
+    #[test]
+    fn test_typing_multi_char_opener_inserts_closer() {
+        let mut state = CodeEditorState::new("");
+        let brackets = BracketSettings {
+            multi_char_pairs: vec![("/*".to_string(), "*/".to_string())],
+            ..Default::default()
+        };
+
+        type_char(&mut state, '/', &brackets);
+        type_char(&mut state, '*', &brackets);
+        assert_eq!(state.rope.to_string(), "/**/");
+        assert_eq!(state.cursor_pos, 2);
+
+        // Typing the closer steps over the inserted one
+        type_char(&mut state, '*', &brackets);
+        type_char(&mut state, '/', &brackets);
+        assert_eq!(state.rope.to_string(), "/**/");
+        assert_eq!(state.cursor_pos, 4);
+
+        // Pairs with an empty side are ignored rather than matching everywhere
+        let brackets = BracketSettings {
+            multi_char_pairs: vec![(String::new(), "x".to_string()), ("<!".to_string(), String::new())],
+            ..Default::default()
+        };
+        let mut state = CodeEditorState::new("");
+        type_char(&mut state, 'a', &brackets);
+        type_char(&mut state, '<', &brackets);
+        type_char(&mut state, '!', &brackets);
+        assert_eq!(state.rope.to_string(), "a<!>");
+        type_char(&mut state, '>', &brackets);
+        assert_eq!(state.rope.to_string(), "a<!>");
+        assert_eq!(state.cursor_pos, 4);
+    }
+
+    #[test]
+    fn test_auto_close_is_one_undo_step_and_surrounds_selection() {
+        let brackets = BracketSettings::default();
+
+        let mut state = CodeEditorState::new("");
+        type_char(&mut state, '(', &brackets);
+        assert_eq!(state.rope.to_string(), "()");
+        assert_eq!(state.cursor_pos, 1);
+        assert!(state.undo());
+        assert_eq!(state.rope.to_string(), "");
+        assert!(!state.undo());
+
+        let mut state = CodeEditorState::new("foo");
+        state.selection_start = Some(0);
+        state.selection_end = Some(3);
+        state.cursor_pos = 3;
+        type_char(&mut state, '[', &brackets);
+        assert_eq!(state.rope.to_string(), "[foo]");
+        assert_eq!((state.selection_start, state.selection_end), (Some(1), Some(4)));
+
+        type_char(&mut state, '"', &brackets);
+        assert_eq!(state.rope.to_string(), "[\"foo\"]");
+        assert!(state.undo());
+        assert_eq!(state.rope.to_string(), "[foo]");
+        assert!(state.undo());
+        assert_eq!(state.rope.to_string(), "foo");
+    }
+
+    #[test]
+    fn test_type_through_only_for_whitelisted_chars() {
+        let brackets = BracketSettings {
+            type_through: vec![')'],
+            ..Default::default()
+        };
+
+        let mut state = CodeEditorState::new("f()");
+        state.cursor_pos = 2;
+        type_char(&mut state, ')', &brackets);
+        assert_eq!(state.rope.to_string(), "f()");
+        assert_eq!(state.cursor_pos, 3);
+
+        // `]` is not whitelisted, so it is inserted next to the existing one
+        let mut state = CodeEditorState::new("a[]");
+        state.cursor_pos = 2;
+        type_char(&mut state, ']', &brackets);
+        assert_eq!(state.rope.to_string(), "a[]]");
+        assert_eq!(state.cursor_pos, 3);
+    }
+
+    #[test]
+    fn test_tab_accepts_completion_then_snippet_stop_then_indents() {
+        let indentation = IndentationSettings::default();
+        let order = &indentation.tab_order;
+        let mut state = CodeEditorState::new("call(a, b)\nfn f() {\n    x\n}");
+        let mut snippet = SnippetSession::default();
+        let mut fold_state = FoldState::default();
+        assert!(fold_state.add_manual_fold(1, 3));
+
+        // An open completion wins over everything else and leaves the stop selected
+        snippet.start(&mut state, vec![5..6, 8..9, 10..10]);
+        assert_eq!(dispatch_tab(&mut state, &mut snippet, order, true, false), TabBehavior::AcceptCompletion);
+        assert_eq!((state.selection_start, state.selection_end), (Some(5), Some(6)));
+        assert_eq!(snippet.current(), Some(0));
+
+        // With a snippet active, Tab moves to the next stop without editing
+        assert_eq!(dispatch_tab(&mut state, &mut snippet, order, false, false), TabBehavior::NextSnippetStop);
+        assert_eq!((state.selection_start, state.selection_end), (Some(8), Some(9)));
+        assert_eq!(state.cursor_pos, 9);
+        assert_eq!(dispatch_tab(&mut state, &mut snippet, order, false, true), TabBehavior::NextSnippetStop);
+        assert_eq!((state.selection_start, state.selection_end), (Some(5), Some(6)));
+        assert_eq!(state.cursor_pos, 6);
+
+        // Reaching the final stop ends the session
+        dispatch_tab(&mut state, &mut snippet, order, false, false);
+        dispatch_tab(&mut state, &mut snippet, order, false, false);
+        assert!(!snippet.is_active());
+        assert_eq!(state.cursor_pos, 10);
+        assert_eq!(state.selection_start, None);
+        assert_eq!(state.rope.to_string(), "call(a, b)\nfn f() {\n    x\n}");
+
+        // Otherwise Tab indents at the cursor, leaving other folds alone
+        assert_eq!(dispatch_tab(&mut state, &mut snippet, order, false, false), TabBehavior::Indent);
+        execute_action_core(
+            &mut state,
+            EditorAction::InsertTab,
+            &indentation,
+            &CursorSettings::default(),
+            &mut FindState::default(),
+            &mut GotoLineState::default(),
+            &mut fold_state,
+            &mut KillRing::default(),
+        );
+        assert_eq!(state.rope.to_string(), "call(a, b)    \nfn f() {\n    x\n}");
+        assert_eq!(state.cursor_pos, 14);
+        assert_eq!(fold_state.regions.len(), 1);
+        assert!(fold_state.regions[0].is_folded);
+        assert_eq!((fold_state.regions[0].start_line, fold_state.regions[0].end_line), (1, 3));
+
+        // A custom order can put indentation first
+        assert_eq!(resolve_tab(&[TabBehavior::Indent, TabBehavior::AcceptCompletion], true, true), TabBehavior::Indent);
+    }
+
+    #[test]
+    fn test_enter_between_brackets_leaves_indented_empty_line() {
+        let indentation = IndentationSettings::default();
+        let brackets = BracketSettings::default();
+        let mut state = CodeEditorState::new("    let v = [];");
+        state.cursor_pos = 13;
+
+        assert!(insert_newline(&mut state, &indentation, &brackets));
+        assert_eq!(state.text(), "    let v = [\n        \n    ];");
+        assert_eq!(state.cursor_pos, 22);
+
+        // The split is a single undo step
+        state.undo();
+        assert_eq!(state.text(), "    let v = [];");
+
+        // Lists configured for commas get one before the line break
+        let brackets = BracketSettings { comma_on_newline: vec!['['], ..Default::default() };
+        let mut state = CodeEditorState::new("[\n    1\n]");
+        state.cursor_pos = 7;
+        assert!(insert_newline(&mut state, &indentation, &brackets));
+        assert_eq!(state.text(), "[\n    1,\n    \n]");
+        assert_eq!(state.cursor_pos, 13);
+
+        // No second comma after an existing one, just the indentation
+        state.cursor_pos = 8;
+        assert!(insert_newline(&mut state, &indentation, &brackets));
+        assert_eq!(state.text(), "[\n    1,\n    \n    \n]");
+    }
+
+    #[test]
+    fn test_enter_copies_indentation_and_indents_after_opener() {
+        let brackets = BracketSettings::default();
+        let indentation = IndentationSettings::default();
+        let mut state = CodeEditorState::new("    let x = 1;");
+        state.cursor_pos = 14;
+
+        assert!(insert_newline(&mut state, &indentation, &brackets));
+        assert_eq!(state.text(), "    let x = 1;\n    ");
+        assert_eq!(state.cursor_pos, 19);
+
+        // A single undo removes the newline and its indentation
+        state.undo();
+        assert_eq!(state.text(), "    let x = 1;");
+
+        // One extra level after an opening bracket, as a tab when not using spaces
+        let indentation = IndentationSettings { use_spaces: false, ..Default::default() };
+        let mut state = CodeEditorState::new("\tif x {");
+        state.cursor_pos = 7;
+        assert!(insert_newline(&mut state, &indentation, &brackets));
+        assert_eq!(state.text(), "\tif x {\n\t\t");
+        assert_eq!(state.cursor_pos, 10);
+
+        // Without auto-indent Enter falls through to the plain newline
+        let indentation = IndentationSettings { auto_indent: false, ..Default::default() };
+        let mut state = CodeEditorState::new("    foo");
+        state.cursor_pos = 7;
+        assert!(!insert_newline(&mut state, &indentation, &brackets));
     }
 }
@@ -0,0 +1,60 @@
+//! Keyboard macro recording and playback
+//!
+//! `ToggleMacroRecording` starts a fresh recording of typed characters and
+//! executed actions; pressing it again stops. `PlayMacro` replays the
+//! recorded steps against the current cursors.
+
+use bevy::prelude::*;
+
+use super::keybindings::EditorAction;
+
+/// One recorded step of a keyboard macro
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MacroStep {
+    /// A character typed at the cursor
+    Char(char),
+    /// An action triggered by a keybinding
+    Action(EditorAction),
+}
+
+/// The current keyboard macro and whether it is being recorded
+#[derive(Resource, Clone, Debug, Default)]
+pub struct MacroRecorder {
+    /// Steps are being appended as they happen
+    pub recording: bool,
+    /// The last recorded macro
+    pub steps: Vec<MacroStep>,
+}
+
+impl MacroRecorder {
+    /// Start recording a new macro, discarding the previous one
+    pub fn start(&mut self) {
+        self.recording = true;
+        self.steps.clear();
+    }
+
+    /// Stop recording, keeping the recorded steps for playback
+    pub fn stop(&mut self) {
+        self.recording = false;
+    }
+
+    /// Start or stop recording
+    pub fn toggle(&mut self) {
+        if self.recording {
+            self.stop();
+        } else {
+            self.start();
+        }
+    }
+
+    /// Append a step while recording; the macro actions themselves are never recorded
+    pub fn record(&mut self, step: MacroStep) {
+        if !self.recording {
+            return;
+        }
+        if let MacroStep::Action(EditorAction::ToggleMacroRecording | EditorAction::PlayMacro) = step {
+            return;
+        }
+        self.steps.push(step);
+    }
+}
@@ -5,14 +5,24 @@
 
 mod keybindings;
 mod actions;
+mod clipboard;
 mod keyboard;
 mod mouse;
 mod cursor;
+mod custom_actions;
+mod diagnostics;
+mod macro_recorder;
 
 // Re-export public types
 pub use keybindings::{EditorAction, default_input_map};
 pub use keyboard::handle_keyboard_input;
-pub use mouse::{handle_mouse_input, handle_mouse_wheel, MouseDragState};
+pub use mouse::{handle_mouse_input, handle_mouse_wheel, MouseDragState, SelectionGranularity};
+pub use custom_actions::{CustomActions, handle_custom_actions};
+pub(crate) use actions::move_cursors_out_of_folds;
+#[cfg(feature = "lsp")]
+pub(crate) use actions::{apply_completion, find_word_start};
+pub use diagnostics::{goto_diagnostic, handle_diagnostic_navigation, track_diagnostic_edits};
+pub use macro_recorder::{MacroRecorder, MacroStep};
 
 // Re-export leafwing types for user customization
 pub use leafwing_input_manager::prelude::{InputMap, ButtonlikeChord, ActionState, Actionlike};
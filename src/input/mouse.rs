@@ -3,10 +3,26 @@ use bevy::input::mouse::MouseWheel;
 use bevy::window::PrimaryWindow;
 use crate::types::*;
 use crate::settings::*;
+use super::cursor::extend_selection_to;
 
 #[cfg(feature = "lsp")]
 use crate::lsp::{LspMessage, reset_hover_state};
 
+/// Maximum time between clicks (in seconds) for them to count as a double/triple click
+const MULTI_CLICK_INTERVAL: f64 = 0.4;
+
+/// Unit a drag selection extends by, set by the click that started the drag
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SelectionGranularity {
+    /// Single click: extend character by character
+    #[default]
+    Char,
+    /// Double click: extend by whole words
+    Word,
+    /// Triple click: extend by whole lines
+    Line,
+}
+
 /// Mouse drag state for selection
 #[derive(Resource, Default)]
 pub struct MouseDragState {
@@ -14,18 +30,66 @@ pub struct MouseDragState {
     pub is_dragging: bool,
     /// Position where drag started (character index)
     pub drag_start_pos: Option<usize>,
+    /// Range selected by the initial click (a word or line for double/triple clicks)
+    pub drag_start_range: Option<(usize, usize)>,
+    /// Granularity of the click that started the drag
+    pub granularity: SelectionGranularity,
+    /// Consecutive clicks at the same position (1 = single click)
+    pub click_count: u32,
+    /// Time (in seconds) and position of the last click
+    pub last_click: Option<(f64, usize)>,
+    /// (line, column) where an Alt+drag block selection started; the column
+    /// may lie past the end of the line
+    pub block_anchor: Option<(usize, usize)>,
 }
 
-/// Convert screen coordinates to character position in the editor
-fn screen_to_char_pos(
+/// Range covered by the unit at `pos` for a granularity
+fn granular_range(state: &CodeEditorState, pos: usize, granularity: SelectionGranularity) -> (usize, usize) {
+    match granularity {
+        SelectionGranularity::Char => (pos, pos),
+        SelectionGranularity::Word => state.word_at_position(pos).unwrap_or((pos, pos)),
+        SelectionGranularity::Line => {
+            let line = state.rope.char_to_line(pos);
+            let start = state.rope.line_to_char(line);
+            let end = if line + 1 < state.rope.len_lines() {
+                state.rope.line_to_char(line + 1)
+            } else {
+                state.rope.len_chars()
+            };
+            (start, end)
+        }
+    }
+}
+
+/// Selection (anchor, head) for a drag from `start_range` to `pos`
+///
+/// Both ends snap to the granularity: the anchor keeps the whole initial unit
+/// and the head extends to the far edge of the unit under the pointer.
+pub(crate) fn drag_selection(
+    state: &CodeEditorState,
+    start_range: (usize, usize),
+    pos: usize,
+    granularity: SelectionGranularity,
+) -> (usize, usize) {
+    let (unit_start, unit_end) = granular_range(state, pos, granularity);
+    if pos < start_range.0 {
+        (start_range.1, unit_start)
+    } else {
+        (start_range.0, unit_end.max(start_range.1))
+    }
+}
+
+/// Buffer line and column under a screen position
+///
+/// The column isn't clamped to the line, and the line may be past the end of
+/// the buffer for clicks below the last line.
+fn screen_to_line_col(
     screen_pos: Vec2,
     state: &CodeEditorState,
     font: &FontSettings,
     viewport: &ViewportDimensions,
-    _viewport_width: f32,
-    _viewport_height: f32,
     fold_state: &FoldState,
-) -> usize {
+) -> (usize, usize) {
     // Calculate the clicked position relative to code start, accounting for sidebar offset
     // Note: scroll_offset is negative when scrolled down, and screen_pos.y is 0 at top in window coords
     // But Bevy's cursor_position() returns (0,0) at top-left, so we need to account for that
@@ -36,14 +100,24 @@ fn screen_to_char_pos(
     let relative_y = screen_pos.y - viewport.text_area_top - state.scroll_offset;
 
     // Calculate line and column from pixel position
-    let line_height = font.line_height;
-    let char_width = font.size * 0.6; // Approximate monospace width
-
-    let display_row = (relative_y / line_height).max(0.0) as usize;
-    let col = (relative_x / char_width).max(0.0) as usize;
+    let display_row = (relative_y / font.line_height).max(0.0) as usize;
+    let col = (relative_x / font.char_width).max(0.0) as usize;
 
     // Convert display row to buffer line (accounting for folds)
-    let buffer_line = fold_state.display_to_actual_line(display_row);
+    (fold_state.display_to_actual_line(display_row), col)
+}
+
+/// Convert screen coordinates to character position in the editor
+fn screen_to_char_pos(
+    screen_pos: Vec2,
+    state: &CodeEditorState,
+    font: &FontSettings,
+    viewport: &ViewportDimensions,
+    _viewport_width: f32,
+    _viewport_height: f32,
+    fold_state: &FoldState,
+) -> usize {
+    let (buffer_line, col) = screen_to_line_col(screen_pos, state, font, viewport, fold_state);
 
     // Convert line/col to character position
     let line_count = state.rope.len_lines();
@@ -54,22 +128,61 @@ fn screen_to_char_pos(
 
     let line_start_char = state.rope.line_to_char(buffer_line);
     let line_len = state.rope.line(buffer_line).len_chars().saturating_sub(1); // Exclude newline
-    let char_in_line = col.min(line_len);
+    let char_in_line = visual_to_char_col(state, buffer_line, col).min(line_len);
 
     line_start_char + char_in_line
 }
 
+/// Convert a clicked cell column on `line` to a character column, so clicks
+/// land where tabs and wide characters are drawn
+fn visual_to_char_col(state: &CodeEditorState, line: usize, visual_col: usize) -> usize {
+    let line_text = state.rope.line(line).to_string();
+    state.display_map.tab_map.contract_column(&line_text, visual_col as u32) as usize
+}
+
+/// Cursors for a block selection between two (line, column) corners
+///
+/// Every visible line from the anchor's to the head's gets one selection over
+/// the same visual column range, clamped to the end of lines that are too short.
+pub(crate) fn block_selection(
+    state: &CodeEditorState,
+    fold_state: &FoldState,
+    anchor: (usize, usize),
+    head: (usize, usize),
+) -> Vec<Cursor> {
+    let last_line = state.rope.len_lines().saturating_sub(1);
+    let first = anchor.0.min(head.0).min(last_line);
+    let last = anchor.0.max(head.0).min(last_line);
+    let clamp = |line: usize, col: usize| {
+        let line_len = state.rope.line(line).chars().take_while(|c| *c != '\n' && *c != '\r').count();
+        state.rope.line_to_char(line) + visual_to_char_col(state, line, col).min(line_len)
+    };
+
+    let mut rows = (first..=last)
+        .filter(|&line| !fold_state.is_line_hidden(line))
+        .map(|line| (clamp(line, head.1), clamp(line, anchor.1)));
+    let Some((head_pos, anchor_pos)) = rows.next() else {
+        return vec![Cursor::new(clamp(first, head.1))];
+    };
+    let mut selections = SelectionCollection::with_selection(head_pos, anchor_pos);
+    for (head_pos, anchor_pos) in rows {
+        selections.add_selection_range(head_pos, anchor_pos);
+    }
+    selections.to_cursors()
+}
+
 /// System to handle mouse input
 pub fn handle_mouse_input(
     mut state: ResMut<CodeEditorState>,
     mut drag_state: ResMut<MouseDragState>,
     mouse_button: Res<ButtonInput<MouseButton>>,
     window_query: Query<&Window, With<PrimaryWindow>>,
-    font: Res<FontSettings>,
+    (font, gutter): (Res<FontSettings>, Res<GutterColumns>),
     viewport: Res<ViewportDimensions>,
     keyboard_input: Res<ButtonInput<KeyCode>>,
     mut fold_state: ResMut<FoldState>,
-    #[cfg(feature = "lsp")] time: Res<Time>,
+    mut find_state: ResMut<FindState>,
+    time: Res<Time>,
     #[cfg(feature = "lsp")] lsp_client: Res<crate::lsp::LspClient>,
     #[cfg(feature = "lsp")] lsp_sync: Res<crate::lsp::LspSyncState>,
     #[cfg(feature = "lsp")] mut hover_state: ResMut<crate::lsp::HoverState>,
@@ -166,17 +279,13 @@ pub fn handle_mouse_input(
 
     // Handle mouse button press
     if mouse_button.just_pressed(MouseButton::Left) {
-        // Check for fold indicator click (in the fold gutter area)
-        if let Some(cursor_pos_screen) = cursor_pos_screen {
+        // Check for fold indicator click (in the gutter's fold column)
+        let fold_column = gutter.column(&GutterColumnKind::Fold);
+        if let (Some(cursor_pos_screen), Some(fold_column)) = (cursor_pos_screen, fold_column) {
             let line_height = font.line_height;
 
-            // Fold gutter is a narrow area just before the separator (where fold indicators are)
-            // Fold indicators are positioned at: separator_x - 12.0
-            let gutter_start = viewport.separator_x - 18.0;
-            let gutter_end = viewport.separator_x + 5.0;
-
-            // Check if click is in the fold gutter area (horizontally)
-            if cursor_pos_screen.x >= gutter_start && cursor_pos_screen.x < gutter_end {
+            // Check if click is in the fold column (horizontally)
+            if cursor_pos_screen.x >= fold_column.x && cursor_pos_screen.x < fold_column.x + fold_column.width {
                 // Calculate which display row was clicked
                 let relative_y = cursor_pos_screen.y - viewport.text_area_top + state.scroll_offset;
                 let display_row = (relative_y / line_height).max(0.0) as usize;
@@ -200,8 +309,12 @@ pub fn handle_mouse_input(
         }
 
         if let Some(char_pos) = char_pos {
-            // Focus editor on click
+            // Focus editor on click, taking focus from the find input
             state.is_focused = true;
+            if find_state.find_focused {
+                find_state.find_focused = false;
+            }
+            drag_state.block_anchor = None;
 
             #[cfg(feature = "lsp")]
             {
@@ -231,28 +344,76 @@ pub fn handle_mouse_input(
             let alt_pressed = keyboard_input.pressed(KeyCode::AltLeft) || keyboard_input.pressed(KeyCode::AltRight);
 
             if alt_pressed {
-                // Add cursor at clicked position
+                // Add cursor at clicked position; dragging from here selects a block
                 state.sync_cursors_from_primary();
                 state.add_cursor(char_pos);
+                if let Some(cursor_pos_screen) = cursor_pos_screen {
+                    drag_state.block_anchor = Some(screen_to_line_col(cursor_pos_screen, &state, &font, &viewport, &fold_state));
+                    drag_state.is_dragging = true;
+                    drag_state.drag_start_pos = Some(char_pos);
+                    drag_state.drag_start_range = None;
+                }
                 // Hide hover on click
                 #[cfg(feature = "lsp")]
                 reset_hover_state(&mut hover_state);
                 return;
             }
 
+            // Shift+Click extends the current selection (or starts one at the cursor),
+            // and dragging from there keeps the same anchor
+            let shift_pressed = keyboard_input.pressed(KeyCode::ShiftLeft) || keyboard_input.pressed(KeyCode::ShiftRight);
+
+            if shift_pressed {
+                if state.has_multiple_cursors() {
+                    state.clear_secondary_cursors();
+                }
+                extend_selection_to(&mut state, char_pos);
+                let anchor = state.selection_start.unwrap_or(char_pos);
+                drag_state.is_dragging = true;
+                drag_state.drag_start_pos = Some(anchor);
+                drag_state.drag_start_range = Some((anchor, anchor));
+                drag_state.granularity = SelectionGranularity::Char;
+                drag_state.click_count = 1;
+                drag_state.last_click = Some((time.elapsed_secs_f64(), char_pos));
+                state.pending_update = true;
+                #[cfg(feature = "lsp")]
+                reset_hover_state(&mut hover_state);
+                return;
+            }
+
+            // Count repeated clicks at the same position; a fourth click starts over
+            let now = time.elapsed_secs_f64();
+            let repeated = drag_state.last_click
+                .is_some_and(|(at, pos)| pos == char_pos && now - at <= MULTI_CLICK_INTERVAL);
+            drag_state.click_count = if repeated { drag_state.click_count % 3 + 1 } else { 1 };
+            drag_state.last_click = Some((now, char_pos));
+            drag_state.granularity = match drag_state.click_count {
+                2 => SelectionGranularity::Word,
+                3 => SelectionGranularity::Line,
+                _ => SelectionGranularity::Char,
+            };
+
             // Start drag
+            let start_range = granular_range(&state, char_pos, drag_state.granularity);
             drag_state.is_dragging = true;
             drag_state.drag_start_pos = Some(char_pos);
+            drag_state.drag_start_range = Some(start_range);
 
             // Clear secondary cursors on regular click
             if state.has_multiple_cursors() {
                 state.clear_secondary_cursors();
             }
 
-            // Update cursor and clear selection
-            state.cursor_pos = char_pos;
-            state.selection_start = None;
-            state.selection_end = None;
+            // Update cursor and select the clicked word or line, if any
+            if start_range.0 < start_range.1 {
+                state.cursor_pos = start_range.1;
+                state.selection_start = Some(start_range.0);
+                state.selection_end = Some(start_range.1);
+            } else {
+                state.cursor_pos = char_pos;
+                state.selection_start = None;
+                state.selection_end = None;
+            }
             state.sync_cursors_from_primary();
             state.pending_update = true;
 
@@ -269,11 +430,29 @@ pub fn handle_mouse_input(
     if mouse_button.just_released(MouseButton::Left) {
         drag_state.is_dragging = false;
         drag_state.drag_start_pos = None;
+        drag_state.drag_start_range = None;
+        drag_state.block_anchor = None;
+    }
+
+    // Alt+drag: one selection per row over the same columns
+    if drag_state.is_dragging && mouse_button.pressed(MouseButton::Left) {
+        if let (Some(cursor_pos_screen), Some(anchor)) = (cursor_pos_screen, drag_state.block_anchor) {
+            let head = screen_to_line_col(cursor_pos_screen, &state, &font, &viewport, &fold_state);
+            if head != anchor {
+                let cursors = block_selection(&state, &fold_state, anchor, head);
+                if cursors != state.cursors {
+                    state.cursors = cursors;
+                    state.sync_primary_cursor();
+                    state.pending_update = true;
+                }
+            }
+            return;
+        }
     }
 
     // Handle dragging (mouse held and moving)
     if drag_state.is_dragging && mouse_button.pressed(MouseButton::Left) {
-        if let (Some(cursor_pos_screen), Some(start_pos)) = (cursor_pos_screen, drag_state.drag_start_pos) {
+        if let (Some(cursor_pos_screen), Some(start_range)) = (cursor_pos_screen, drag_state.drag_start_range) {
             let current_pos = screen_to_char_pos(
                 cursor_pos_screen,
                 &state,
@@ -284,11 +463,14 @@ pub fn handle_mouse_input(
                 &fold_state,
             );
 
+            let (anchor, head) = drag_selection(&state, start_range, current_pos, drag_state.granularity);
+
             // Only update if position changed
-            if current_pos != state.cursor_pos {
-                state.cursor_pos = current_pos;
-                state.selection_start = Some(start_pos);
-                state.selection_end = Some(current_pos);
+            if head != state.cursor_pos || state.selection_start != Some(anchor) {
+                state.cursor_pos = head;
+                state.selection_start = Some(anchor);
+                state.selection_end = Some(head);
+                state.sync_cursors_from_primary();
                 state.pending_update = true;
             }
         }
@@ -303,13 +485,40 @@ pub fn handle_mouse_wheel(
     font: Res<FontSettings>,
     scrolling: Res<ScrollingSettings>,
     viewport: Res<ViewportDimensions>,
+    #[cfg(feature = "lsp")] mut hover_state: ResMut<crate::lsp::HoverState>,
+    #[cfg(feature = "lsp")] hover_popup: Query<&crate::lsp::components::HoverPopupData>,
+    #[cfg(feature = "lsp")] window_query: Query<&Window, With<PrimaryWindow>>,
 ) {
+    #[cfg(feature = "lsp")]
+    let pointer = window_query.iter().next().and_then(|window| window.cursor_position());
+
     for event in mouse_wheel_events.read() {
+        // A hover taller than its popup takes vertical scrolling while the pointer is over it
+        #[cfg(feature = "lsp")]
+        if let Some(popup) = hover_popup.iter().next().filter(|popup| {
+            popup.is_scrollable() && pointer.is_some_and(|pointer| popup.contains(pointer))
+        }) {
+            if hover_state.visible && event.y.abs() > 0.0 {
+                use bevy::input::mouse::MouseScrollUnit;
+                let lines = match event.unit {
+                    MouseScrollUnit::Line => event.y,
+                    MouseScrollUnit::Pixel => event.y / popup.line_height,
+                };
+                let step = (lines.abs().round() as usize).max(1);
+                hover_state.scroll_line = if lines > 0.0 {
+                    popup.scroll_line.saturating_sub(step)
+                } else {
+                    (popup.scroll_line + step).min(popup.max_scroll_line())
+                };
+                continue;
+            }
+        }
+
         let mut scrolled = false;
         let use_smooth = scrolling.smooth;
 
-        // Horizontal scrolling (using event.x)
-        if event.x.abs() > 0.0 {
+        // Horizontal scrolling (using event.x), unless lines wrap to the viewport
+        if event.x.abs() > 0.0 && !state.display_map.locks_horizontal_scroll() {
             // Only allow horizontal scrolling if content width exceeds available text area
             let viewport_width = viewport.width as f32;
             // Calculate available width for text (excluding line numbers margin and code margin)
@@ -356,20 +565,20 @@ pub fn handle_mouse_wheel(
             let line_count = state.rope.len_lines();
             let content_height = line_count as f32 * font.line_height;
             let viewport_height = viewport.height as f32;
-            let max_scroll = -(content_height - viewport_height + viewport.text_area_top);
+            let max_scroll = scrolling.max_scroll(content_height, viewport_height, viewport.text_area_top, font.line_height);
 
             if use_smooth {
                 // Update target for smooth scrolling
                 state.target_scroll_offset += scroll_delta;
                 state.target_scroll_offset = state.target_scroll_offset
                     .min(0.0)
-                    .max(max_scroll.min(0.0));
+                    .max(max_scroll);
             } else {
                 // Direct update
                 state.scroll_offset += scroll_delta;
                 state.scroll_offset = state.scroll_offset
                     .min(0.0)
-                    .max(max_scroll.min(0.0));
+                    .max(max_scroll);
             }
 
             scrolled = true;
@@ -386,3 +595,108 @@ pub fn handle_mouse_wheel(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::EditorAction;
+    use crate::input::actions::execute_action_core;
+
+    #[test]
+    fn test_custom_line_height_changes_row_spacing() {
+        let state = CodeEditorState::new("aaa\nbbb\nccc\nddd\n");
+        let viewport = ViewportDimensions::default();
+        let fold_state = FoldState::default();
+        let mut font = FontSettings::default();
+        let size = font.size;
+        font.line_height = 40.0;
+
+        // Halfway down the third row at 40px spacing; default 21px spacing would land on row 4
+        let y = viewport.text_area_top + 40.0 * 2.5;
+        let pos = Vec2::new(viewport.text_area_left, y);
+        let idx = screen_to_char_pos(pos, &state, &font, &viewport, 800.0, 600.0, &fold_state);
+
+        assert_eq!(font.size, size);
+        assert_eq!(state.rope.char_to_line(idx), 2);
+    }
+
+    #[test]
+    fn test_word_drag_selects_whole_words_at_both_ends() {
+        let state = CodeEditorState::new("foo bar baz qux");
+        // Double-clicked on "bar"
+        let start_range = (4, 7);
+
+        // Pointer in the middle of "qux": extends to its end, anchor stays at "bar" start
+        let (anchor, head) = drag_selection(&state, start_range, 13, SelectionGranularity::Word);
+        assert_eq!((anchor, head), (4, 15));
+
+        // Pointer in the middle of "baz"
+        let (anchor, head) = drag_selection(&state, start_range, 9, SelectionGranularity::Word);
+        assert_eq!((anchor, head), (4, 11));
+
+        // Pointer back in "foo": anchor flips to the end of "bar", head snaps to "foo" start
+        let (anchor, head) = drag_selection(&state, start_range, 1, SelectionGranularity::Word);
+        assert_eq!((anchor, head), (7, 0));
+
+        // Within the initial word the whole word stays selected
+        let (anchor, head) = drag_selection(&state, start_range, 5, SelectionGranularity::Word);
+        assert_eq!((anchor, head), (4, 7));
+    }
+
+    #[test]
+    fn test_shift_right_after_drag_extends_the_drag_selection() {
+        let mut state = CodeEditorState::new("foo bar baz qux");
+        let shift_right = |state: &mut CodeEditorState| {
+            execute_action_core(
+                state,
+                EditorAction::SelectRight,
+                &IndentationSettings::default(),
+                &CursorSettings::default(),
+                &mut FindState::default(),
+                &mut GotoLineState::default(),
+                &mut FoldState::default(),
+                &mut KillRing::default(),
+            );
+        };
+
+        // Drag from the start of "bar" to the middle of "baz", as the drag handler does
+        let (anchor, head) = drag_selection(&state, (4, 4), 9, SelectionGranularity::Char);
+        state.cursor_pos = head;
+        state.selection_start = Some(anchor);
+        state.selection_end = Some(head);
+        state.sync_cursors_from_primary();
+
+        shift_right(&mut state);
+        assert_eq!((state.selection_start, state.selection_end), (Some(4), Some(10)));
+        assert_eq!(state.cursors[0].anchor, Some(4));
+        assert_eq!(state.cursors[0].position, 10);
+
+        // Shift-click further on keeps the same anchor, and Shift+Right continues from there
+        extend_selection_to(&mut state, 13);
+        shift_right(&mut state);
+        assert_eq!((state.selection_start, state.selection_end), (Some(4), Some(14)));
+        assert_eq!(state.rope.slice(4..14).to_string(), "bar baz qu");
+    }
+
+    #[test]
+    fn test_block_selection_spans_rows_and_edits_each() {
+        let mut state = CodeEditorState::new("abcdef\nab\nabcdefgh\n");
+        let fold_state = FoldState::default();
+
+        // Columns 1..4 over three lines; the short middle line is clamped to its end
+        state.cursors = block_selection(&state, &fold_state, (0, 1), (2, 4));
+        state.sync_primary_cursor();
+        let ranges: Vec<(Option<usize>, usize)> = state.cursors.iter().map(|c| (c.anchor, c.position)).collect();
+        assert_eq!(ranges, vec![(Some(1), 4), (Some(8), 9), (Some(11), 14)]);
+
+        // Deleting and typing apply to every row
+        assert!(state.delete_at_cursors(false));
+        assert_eq!(state.text(), "aef\na\naefgh\n");
+        state.insert_at_cursors("X");
+        assert_eq!(state.text(), "aXef\naX\naXefgh\n");
+
+        state.undo();
+        state.undo();
+        assert_eq!(state.text(), "abcdef\nab\nabcdefgh\n");
+    }
+}
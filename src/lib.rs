@@ -54,8 +54,8 @@ pub mod lsp;
 pub mod prelude {
     //! Convenient re-exports for common usage
     pub use crate::plugin::{
-        CodeEditorPlugin, EditorInputManager, EditorUiPlugin,
-        ScrollbarPlugin, Scrollbar,
+        CodeEditorPlugin, EditorInputManager, EditorUiPlugin, BuiltinRendering,
+        ScrollbarPlugin, Scrollbar, EditorStatusOverlay,
         InputSet, ApplyStateSet, RenderingSet, EditorSetupSet,
     };
     pub use crate::settings::*;
@@ -77,4 +77,7 @@ pub mod prelude {
     // Re-export LSP plugins (feature-gated)
     #[cfg(feature = "lsp")]
     pub use crate::plugin::{LspPlugin, LspUiPlugin};
+
+    #[cfg(feature = "file-io")]
+    pub use crate::plugin::{EditorFile, language_from_path, language_indentation};
 }
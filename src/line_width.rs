@@ -19,6 +19,8 @@
 pub struct LineWidthTracker {
     /// Maximum width seen so far (grows as user scrolls through document)
     cached_max: u32,
+    /// Line that holds `cached_max`, used to detect when it gets shorter
+    max_line: Option<usize>,
     /// Number of lines in the document
     line_count: usize,
     /// Version for change detection
@@ -37,6 +39,7 @@ impl LineWidthTracker {
         Self {
             line_count: 0,
             cached_max: 0,
+            max_line: None,
             version: 0,
         }
     }
@@ -49,6 +52,7 @@ impl LineWidthTracker {
         Self {
             line_count: rope.len_lines(),
             cached_max: 0, // Will be updated as visible lines are rendered
+            max_line: None,
             version: 1,
         }
     }
@@ -58,6 +62,7 @@ impl LineWidthTracker {
         self.line_count = rope.len_lines();
         // Reset max - it will be rediscovered from visible lines
         self.cached_max = 0;
+        self.max_line = None;
         self.version += 1;
     }
 
@@ -71,38 +76,74 @@ impl LineWidthTracker {
     pub fn update_visible_range(&mut self, rope: &ropey::Rope, start_line: usize, end_line: usize) {
         let end = end_line.min(rope.len_lines());
         for line_idx in start_line..end {
-            let line = rope.line(line_idx);
-            let len = line.len_chars();
-            let width = if len > 0 && line.char(len - 1) == '\n' {
-                (len - 1) as u32
-            } else {
-                len as u32
-            };
-            if width > self.cached_max {
-                self.cached_max = width;
-            }
+            self.observe(line_idx, line_width(rope, line_idx));
         }
     }
 
     /// Update a single line's width after an edit
-    pub fn update_line(&mut self, _line_index: usize, new_width: u32) {
-        if new_width > self.cached_max {
-            self.cached_max = new_width;
-        }
+    pub fn update_line(&mut self, line_index: usize, new_width: u32) {
+        self.observe(line_index, new_width);
         self.version += 1;
     }
 
     /// Update line width from rope (convenience method)
     pub fn update_line_from_rope(&mut self, rope: &ropey::Rope, line_index: usize) {
         if line_index < rope.len_lines() {
-            let line = rope.line(line_index);
-            let len = line.len_chars();
-            let width = if len > 0 && line.char(len - 1) == '\n' {
-                (len - 1) as u32
-            } else {
-                len as u32
-            };
-            self.update_line(line_index, width);
+            self.update_line(line_index, line_width(rope, line_index));
+        }
+    }
+
+    /// Update the tracked max after an edit touching `edited_lines` (post-edit line indices)
+    ///
+    /// Lines after the edit are shifted by the change in line count. If the line
+    /// holding the max was shortened or removed, the whole document is rescanned
+    /// so the max shrinks precisely; otherwise only the edited lines are checked.
+    pub fn update_after_edit(&mut self, rope: &ropey::Rope, edited_lines: std::ops::Range<usize>) {
+        let new_line_count = rope.len_lines();
+        let delta = new_line_count as isize - self.line_count as isize;
+        self.line_count = new_line_count;
+
+        if let Some(max_line) = self.max_line {
+            if max_line > edited_lines.start {
+                self.max_line = Some((max_line as isize + delta).max(0) as usize);
+            }
+        }
+
+        let max_still_valid = match self.max_line {
+            Some(line) => line < new_line_count && line_width(rope, line) == self.cached_max,
+            None => self.cached_max == 0,
+        };
+
+        if max_still_valid {
+            let end = edited_lines.end.min(new_line_count);
+            for line_idx in edited_lines.start.min(end)..end {
+                self.observe(line_idx, line_width(rope, line_idx));
+            }
+        } else {
+            self.recompute(rope);
+        }
+
+        self.version += 1;
+    }
+
+    /// Rescan every line for the exact max width - O(n)
+    pub fn recompute(&mut self, rope: &ropey::Rope) {
+        self.line_count = rope.len_lines();
+        self.cached_max = 0;
+        self.max_line = None;
+        self.update_visible_range(rope, 0, self.line_count);
+        self.version += 1;
+    }
+
+    /// Line currently holding the max width, if known
+    pub fn max_line(&self) -> Option<usize> {
+        self.max_line
+    }
+
+    fn observe(&mut self, line_index: usize, width: u32) {
+        if width > self.cached_max || self.max_line.is_none() {
+            self.cached_max = self.cached_max.max(width);
+            self.max_line = Some(line_index);
         }
     }
 
@@ -132,6 +173,17 @@ impl LineWidthTracker {
     }
 }
 
+/// Width of a line in cells, excluding the trailing newline
+///
+/// Wide characters count as two cells, matching the renderer.
+fn line_width(rope: &ropey::Rope, line_index: usize) -> u32 {
+    rope.line(line_index)
+        .chars()
+        .filter(|&c| c != '\n')
+        .map(crate::display_map::char_cells)
+        .sum()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -190,4 +242,35 @@ mod tests {
         tracker.update_line(1, 20);
         assert_eq!(tracker.max_width(), 20);
     }
+
+    #[test]
+    fn test_shortening_longest_line_rescans() {
+        let mut rope = Rope::from_str("short\nthis is a longer line\nmedium line");
+        let mut tracker = LineWidthTracker::from_rope(&rope);
+        tracker.recompute(&rope);
+        assert_eq!(tracker.max_width(), 21);
+        assert_eq!(tracker.max_line(), Some(1));
+
+        // Shorten the longest line: the max falls back to the next longest
+        let start = rope.line_to_char(1);
+        rope.remove(start + 4..start + 21);
+        tracker.update_after_edit(&rope, 1..2);
+        assert_eq!(tracker.max_width(), 11); // "medium line"
+        assert_eq!(tracker.max_line(), Some(2));
+    }
+
+    #[test]
+    fn test_edit_above_max_line_shifts_it() {
+        let mut rope = Rope::from_str("a\nb\nthe longest line");
+        let mut tracker = LineWidthTracker::from_rope(&rope);
+        tracker.recompute(&rope);
+        assert_eq!(tracker.max_line(), Some(2));
+
+        // Insert a line at the top; the longest line moves down without a rescan
+        rope.insert(0, "new\n");
+        tracker.update_after_edit(&rope, 0..2);
+        assert_eq!(tracker.max_line(), Some(3));
+        assert_eq!(tracker.max_width(), 16);
+    }
 }
+
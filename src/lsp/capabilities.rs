@@ -206,4 +206,19 @@ impl ServerCapabilitiesCache {
             })
             .unwrap_or(false)
     }
+
+    /// Check if server supports document symbols
+    pub fn supports_document_symbols(&self) -> bool {
+        self.inner
+            .read()
+            .ok()
+            .and_then(|guard| {
+                guard.as_ref().map(|c| match &c.document_symbol_provider {
+                    Some(OneOf::Left(b)) => *b,
+                    Some(OneOf::Right(_)) => true,
+                    None => false,
+                })
+            })
+            .unwrap_or(false)
+    }
 }
@@ -220,63 +220,8 @@ impl LspClient {
             return;
         }
 
-        let id_info = match &message {
-            LspMessage::Initialize { .. } => {
-                let id = NEXT_REQUEST_ID.fetch_add(1, Ordering::Relaxed);
-                Some((id, RequestType::Initialize))
-            }
-            LspMessage::Completion { .. } => {
-                let id = NEXT_REQUEST_ID.fetch_add(1, Ordering::Relaxed);
-                Some((id, RequestType::Completion))
-            }
-            LspMessage::Hover { .. } => {
-                let id = NEXT_REQUEST_ID.fetch_add(1, Ordering::Relaxed);
-                Some((id, RequestType::Hover))
-            }
-            LspMessage::GotoDefinition { .. } => {
-                let id = NEXT_REQUEST_ID.fetch_add(1, Ordering::Relaxed);
-                Some((id, RequestType::GotoDefinition))
-            }
-            LspMessage::References { .. } => {
-                let id = NEXT_REQUEST_ID.fetch_add(1, Ordering::Relaxed);
-                Some((id, RequestType::References))
-            }
-            LspMessage::Format { .. } => {
-                let id = NEXT_REQUEST_ID.fetch_add(1, Ordering::Relaxed);
-                Some((id, RequestType::Format))
-            }
-            LspMessage::SignatureHelp { .. } => {
-                let id = NEXT_REQUEST_ID.fetch_add(1, Ordering::Relaxed);
-                Some((id, RequestType::SignatureHelp))
-            }
-            LspMessage::CodeAction { .. } => {
-                let id = NEXT_REQUEST_ID.fetch_add(1, Ordering::Relaxed);
-                Some((id, RequestType::CodeAction))
-            }
-            LspMessage::InlayHint { .. } => {
-                let id = NEXT_REQUEST_ID.fetch_add(1, Ordering::Relaxed);
-                Some((id, RequestType::InlayHint))
-            }
-            LspMessage::ExecuteCommand { .. } => {
-                let id = NEXT_REQUEST_ID.fetch_add(1, Ordering::Relaxed);
-                // Reuse CodeAction type for execute command responses
-                Some((id, RequestType::CodeAction))
-            }
-            LspMessage::DocumentHighlight { .. } => {
-                let id = NEXT_REQUEST_ID.fetch_add(1, Ordering::Relaxed);
-                Some((id, RequestType::DocumentHighlight))
-            }
-            LspMessage::PrepareRename { .. } => {
-                let id = NEXT_REQUEST_ID.fetch_add(1, Ordering::Relaxed);
-                Some((id, RequestType::PrepareRename))
-            }
-            LspMessage::Rename { .. } => {
-                let id = NEXT_REQUEST_ID.fetch_add(1, Ordering::Relaxed);
-                Some((id, RequestType::Rename))
-            }
-            // Notifications don't have IDs
-            LspMessage::Initialized | LspMessage::DidOpen { .. } | LspMessage::DidChange { .. } => None,
-        };
+        let id_info = request_type_of(&message)
+            .map(|request_type| (NEXT_REQUEST_ID.fetch_add(1, Ordering::Relaxed), request_type));
 
         // Track the request
         if let Some((id, request_type)) = id_info {
@@ -294,6 +239,48 @@ impl LspClient {
         let _ = self.tx.send((message, id_info));
     }
 
+    /// Send a request unless the server doesn't support it or one of the same
+    /// type is still waiting for a response
+    ///
+    /// Returns whether the request was sent.
+    pub fn send_request(&self, message: LspMessage) -> bool {
+        if !self.should_send(&message) {
+            return false;
+        }
+        if let Some(request_type) = request_type_of(&message) {
+            if self.has_pending(request_type) {
+                return false;
+            }
+        }
+        self.send(message);
+        true
+    }
+
+    /// Whether a request of this type is still waiting for a response
+    pub fn has_pending(&self, request_type: RequestType) -> bool {
+        self.pending_requests
+            .lock()
+            .map(|pending| pending.values().any(|p| p.request_type == request_type))
+            .unwrap_or(false)
+    }
+
+    /// Client whose outgoing messages can be read back, for tests
+    #[cfg(test)]
+    pub(crate) fn with_test_channel() -> (Self, Receiver<(LspMessage, Option<(i64, RequestType)>)>) {
+        let mut client = Self::new();
+        let (tx, rx) = channel();
+        client.tx = tx;
+        (client, rx)
+    }
+
+    /// Sender whose messages `try_recv` returns as server responses, for tests
+    #[cfg(test)]
+    pub(crate) fn test_response_sender(&mut self) -> Sender<LspResponse> {
+        let (tx, rx) = channel();
+        self.rx = Mutex::new(rx);
+        tx
+    }
+
     /// Check if a message should be sent based on server capabilities
     fn should_send(&self, message: &LspMessage) -> bool {
         match message {
@@ -316,6 +303,7 @@ impl LspClient {
             LspMessage::DocumentHighlight { .. } => self.capabilities.supports_document_highlight(),
             LspMessage::PrepareRename { .. } => self.capabilities.supports_prepare_rename(),
             LspMessage::Rename { .. } => self.capabilities.supports_rename(),
+            LspMessage::DocumentSymbols { .. } => self.capabilities.supports_document_symbols(),
         }
     }
 
@@ -370,6 +358,29 @@ impl Drop for LspClient {
     }
 }
 
+/// Request type used to match the response, or None for notifications
+fn request_type_of(message: &LspMessage) -> Option<RequestType> {
+    match message {
+        LspMessage::Initialize { .. } => Some(RequestType::Initialize),
+        LspMessage::Completion { .. } => Some(RequestType::Completion),
+        LspMessage::Hover { .. } => Some(RequestType::Hover),
+        LspMessage::GotoDefinition { .. } => Some(RequestType::GotoDefinition),
+        LspMessage::References { .. } => Some(RequestType::References),
+        LspMessage::Format { .. } => Some(RequestType::Format),
+        LspMessage::SignatureHelp { .. } => Some(RequestType::SignatureHelp),
+        LspMessage::CodeAction { .. } => Some(RequestType::CodeAction),
+        LspMessage::InlayHint { .. } => Some(RequestType::InlayHint),
+        // Reuse CodeAction type for execute command responses
+        LspMessage::ExecuteCommand { .. } => Some(RequestType::CodeAction),
+        LspMessage::DocumentHighlight { .. } => Some(RequestType::DocumentHighlight),
+        LspMessage::PrepareRename { .. } => Some(RequestType::PrepareRename),
+        LspMessage::Rename { .. } => Some(RequestType::Rename),
+        LspMessage::DocumentSymbols { .. } => Some(RequestType::DocumentSymbols),
+        // Notifications don't have IDs
+        LspMessage::Initialized | LspMessage::DidOpen { .. } | LspMessage::DidChange { .. } => None,
+    }
+}
+
 /// Convert LspMessage to JSON-RPC string
 fn msg_to_json(msg: &LspMessage, id: Option<i64>) -> serde_json::Result<String> {
     let (method, params, is_notification) = match msg {
@@ -508,6 +519,13 @@ fn msg_to_json(msg: &LspMessage, id: Option<i64>) -> serde_json::Result<String>
             }),
             false,
         ),
+        LspMessage::DocumentSymbols { uri } => (
+            "textDocument/documentSymbol",
+            json!({
+                "textDocument": { "uri": uri }
+            }),
+            false,
+        ),
     };
 
     let rpc = if is_notification {
@@ -542,6 +560,11 @@ fn parse_lsp_response(
     // For responses, use the request_type to determine how to parse
     let result = json.get("result")?;
 
+    // A null formatting result means the document is already formatted
+    if result.is_null() && request_type == Some(RequestType::Format) {
+        return Some(LspResponse::Format { edits: Vec::new() });
+    }
+
     // Handle null results
     if result.is_null() {
         // Log null results for debugging
@@ -702,6 +725,13 @@ fn parse_lsp_response(
             }
             None
         }
+        Some(RequestType::DocumentSymbols) => {
+            // Either nested DocumentSymbol[] or flat SymbolInformation[]
+            if let Ok(symbols) = serde_json::from_value::<DocumentSymbolResponse>(result.clone()) {
+                return Some(LspResponse::DocumentSymbols { symbols });
+            }
+            None
+        }
         None => None,
     }
 }
@@ -34,6 +34,7 @@
 
 use bevy::prelude::*;
 
+use super::markdown::HoverLine;
 use super::state::UnifiedCompletionItem;
 
 /// Marker component for the completion popup entity.
@@ -85,18 +86,53 @@ impl From<&UnifiedCompletionItem> for CompletionItemData {
 
 /// Marker component for the hover popup entity.
 /// Contains all data needed to render the hover UI.
-#[derive(Component, Clone, Debug)]
+#[derive(Component, Clone, Debug, PartialEq)]
 pub struct HoverPopupData {
     /// Position in screen space (top-left of popup)
     pub position: Vec2,
     /// Content to display (markdown/plain text)
     pub content: String,
+    /// Content laid out as styled lines wrapped to the popup width
+    pub lines: Vec<HoverLine>,
+    /// First line shown
+    pub scroll_line: usize,
+    /// Number of lines that fit in the popup
+    pub visible_lines: usize,
+    /// Height of one line in pixels
+    pub line_height: f32,
     /// Calculated popup width
     pub width: f32,
     /// Calculated popup height
     pub height: f32,
 }
 
+impl HoverPopupData {
+    /// Lines currently scrolled into view
+    pub fn visible(&self) -> &[HoverLine] {
+        let start = self.scroll_line.min(self.lines.len());
+        let end = (start + self.visible_lines).min(self.lines.len());
+        &self.lines[start..end]
+    }
+
+    /// Last valid `scroll_line`
+    pub fn max_scroll_line(&self) -> usize {
+        self.lines.len().saturating_sub(self.visible_lines)
+    }
+
+    /// Whether the content is taller than the popup
+    pub fn is_scrollable(&self) -> bool {
+        self.max_scroll_line() > 0
+    }
+
+    /// Whether a screen-space point lies inside the popup
+    pub fn contains(&self, point: Vec2) -> bool {
+        point.x >= self.position.x
+            && point.x <= self.position.x + self.width
+            && point.y >= self.position.y
+            && point.y <= self.position.y + self.height
+    }
+}
+
 /// Marker component for the signature help popup entity.
 /// Contains all data needed to render signature help.
 #[derive(Component, Clone, Debug)]
@@ -10,7 +10,8 @@ use crate::events::{
 use crate::lsp::client::LspClient;
 use crate::lsp::messages::LspMessage;
 use crate::lsp::state::{CompletionState, HoverState, LspSyncState, RenameState, SignatureHelpState};
-use crate::types::CodeEditorState;
+use crate::types::{CodeEditorState, SnippetSession};
+use lsp_types::InsertTextFormat;
 
 /// System that listens to TextEditEvent and sends didChange to LSP
 pub fn listen_text_edit_events(
@@ -158,45 +159,39 @@ pub fn listen_dismiss_completion(
 }
 
 /// System that listens to ApplyCompletionEvent
+///
+/// Snippet items are expanded and start a `SnippetSession`, so Tab moves
+/// through their tab stops.
 pub fn listen_apply_completion(
     mut events: MessageReader<ApplyCompletionEvent>,
     mut state: ResMut<CodeEditorState>,
     mut completion_state: ResMut<CompletionState>,
+    mut snippet: ResMut<SnippetSession>,
 ) {
     for event in events.read() {
         if event.item_index < completion_state.items.len() {
             let item = &completion_state.items[event.item_index];
 
-            // Calculate current position from cursor_pos
+            // Replace the partial word before the cursor
             let cursor_pos = state.cursor_pos.min(state.rope.len_chars());
-            let line = state.rope.char_to_line(cursor_pos);
-            let line_start = state.rope.line_to_char(line);
-            let cursor_char = cursor_pos - line_start;
-
-            let line_text = state.rope.line(line).to_string();
-
-            let word_start = line_text[..cursor_char]
-                .rfind(|c: char| !c.is_alphanumeric() && c != '_')
-                .map(|i| i + 1)
-                .unwrap_or(0);
-
-            // Delete the partial word
-            if word_start < cursor_char {
-                let start_pos = line_start + word_start;
-                let end_pos = line_start + cursor_char;
-                state.rope.remove(start_pos..end_pos);
-                state.cursor_pos = start_pos + word_start;
-            }
+            let line_start = state.rope.line_to_char(state.rope.char_to_line(cursor_pos));
+            let word_len = state
+                .rope
+                .slice(line_start..cursor_pos)
+                .chars()
+                .rev()
+                .take_while(|c| c.is_alphanumeric() || *c == '_')
+                .count();
+            let word_start = cursor_pos - word_len;
 
             // Insert the completion text
-            let insert_text = item.insert_text.as_ref().unwrap_or(&item.label);
-            let cursor_pos = state.cursor_pos;
-            state.rope.insert(cursor_pos, insert_text);
-            state.cursor_pos += insert_text.len();
-
-            // Mark as needing update
-            state.pending_update = true;
-            state.content_version += 1;
+            let insert_text = item.insert_text.as_ref().unwrap_or(&item.label).clone();
+            if item.insert_text_format == Some(InsertTextFormat::SNIPPET) {
+                snippet.expand(&mut state, word_start..cursor_pos, &insert_text);
+            } else {
+                state.set_selections(vec![(cursor_pos, Some(word_start))]);
+                state.insert_at_cursors(&insert_text);
+            }
 
             // Dismiss completion
             completion_state.visible = false;
@@ -0,0 +1,580 @@
+//! Markdown layout for hover popups
+//!
+//! Language servers send hover contents as markdown. `layout_hover_markdown`
+//! turns it into lines of styled spans wrapped to the popup width: prose is
+//! reflowed by word, while fenced code keeps its own line breaks, is
+//! hard-wrapped when too long, and can be syntax highlighted by the caller.
+
+use bevy::prelude::*;
+use crate::types::LineSegment;
+
+/// Style of a run of hover text
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum HoverTextStyle {
+    /// Regular paragraph text
+    #[default]
+    Plain,
+    /// `**strong**` or `__strong__`
+    Strong,
+    /// `*emphasis*` or `_emphasis_`
+    Emphasis,
+    /// `` `inline code` ``
+    InlineCode,
+    /// `# Heading` line
+    Heading,
+    /// Text inside a fenced code block
+    CodeBlock,
+}
+
+/// A run of hover text in one style
+#[derive(Clone, Debug, PartialEq)]
+pub struct HoverSpan {
+    /// Text of the run (never contains a newline)
+    pub text: String,
+    /// Markdown style of the run
+    pub style: HoverTextStyle,
+    /// Syntax color of highlighted code, overriding the style's theme color
+    pub color: Option<Color>,
+}
+
+/// One laid-out line of a hover popup
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct HoverLine {
+    /// Styled runs, left to right
+    pub spans: Vec<HoverSpan>,
+    /// Line belongs to a fenced code block (drawn on the code background)
+    pub code_block: bool,
+}
+
+impl HoverLine {
+    /// Width of the line in characters
+    pub fn char_count(&self) -> usize {
+        self.spans.iter().map(|span| span.text.chars().count()).sum()
+    }
+
+    /// The line's text without styling
+    pub fn text(&self) -> String {
+        self.spans.iter().map(|span| span.text.as_str()).collect()
+    }
+
+    /// Append text, merging it into the last span when the styling matches
+    fn push(&mut self, text: &str, style: HoverTextStyle, color: Option<Color>) {
+        if text.is_empty() {
+            return;
+        }
+        match self.spans.last_mut() {
+            Some(last) if last.style == style && last.color == color => last.text.push_str(text),
+            _ => self.spans.push(HoverSpan { text: text.to_string(), style, color }),
+        }
+    }
+}
+
+/// Block-level piece of hover markdown
+#[derive(Debug)]
+enum MarkdownBlock {
+    /// Paragraph, heading or list item; a list item's `prefix` is its bullet
+    Text { prefix: String, spans: Vec<HoverSpan>, list_item: bool },
+    /// Fenced code block with its info string's language
+    Code { language: String, text: String },
+    /// Thematic break (`---`)
+    Rule,
+}
+
+/// Lay out hover markdown as styled lines at most `max_chars` wide
+///
+/// `highlight` is called with each fenced block's language (empty when the
+/// fence has none) and code, and returns per-line colored segments, or
+/// `None` to show the block unhighlighted.
+pub fn layout_hover_markdown(
+    markdown: &str,
+    max_chars: usize,
+    mut highlight: impl FnMut(&str, &str) -> Option<Vec<Vec<LineSegment>>>,
+) -> Vec<HoverLine> {
+    let max_chars = max_chars.max(1);
+    let mut lines: Vec<HoverLine> = Vec::new();
+    let mut rule_rows = Vec::new();
+    let mut previous: Option<&MarkdownBlock> = None;
+
+    let blocks = parse_blocks(markdown);
+    for block in &blocks {
+        // Blocks are separated by a blank line, except around rules and
+        // between consecutive list items
+        let separate = match (previous, block) {
+            (None, _) | (Some(MarkdownBlock::Rule), _) | (_, MarkdownBlock::Rule) => false,
+            (
+                Some(MarkdownBlock::Text { list_item: true, .. }),
+                MarkdownBlock::Text { list_item: true, .. },
+            ) => false,
+            _ => true,
+        };
+        if separate {
+            lines.push(HoverLine::default());
+        }
+
+        match block {
+            MarkdownBlock::Text { prefix, spans, .. } => {
+                wrap_spans(prefix, spans, max_chars, &mut lines);
+            }
+            MarkdownBlock::Code { language, text } => {
+                let highlighted = highlight(language, text);
+                for (row, source_line) in text.lines().enumerate() {
+                    let mut line = HoverLine { spans: Vec::new(), code_block: true };
+                    match highlighted.as_ref().and_then(|rows| rows.get(row)) {
+                        Some(segments) if !segments.is_empty() => {
+                            for segment in segments {
+                                line.push(&segment.text, HoverTextStyle::CodeBlock, Some(segment.color));
+                            }
+                        }
+                        _ => line.push(source_line, HoverTextStyle::CodeBlock, None),
+                    }
+                    hard_wrap(line, max_chars, &mut lines);
+                }
+            }
+            MarkdownBlock::Rule => {
+                rule_rows.push(lines.len());
+                lines.push(HoverLine::default());
+            }
+        }
+        previous = Some(block);
+    }
+
+    // Rules span the widest line, so they never widen the popup themselves
+    let rule_width = lines.iter().map(HoverLine::char_count).max().unwrap_or(0).clamp(3, max_chars);
+    for row in rule_rows {
+        lines[row].push(&"─".repeat(rule_width), HoverTextStyle::Plain, None);
+    }
+
+    while lines.last().is_some_and(|line| line.spans.is_empty() && !line.code_block) {
+        lines.pop();
+    }
+    lines
+}
+
+/// Split hover markdown into blocks
+fn parse_blocks(markdown: &str) -> Vec<MarkdownBlock> {
+    let mut blocks = Vec::new();
+    let mut paragraph: Vec<&str> = Vec::new();
+    // Bullet of the list item being collected, if any
+    let mut list_prefix: Option<String> = None;
+    // Fence marker, language and collected lines of an open code block
+    let mut fence: Option<(String, String, Vec<String>)> = None;
+
+    for raw_line in markdown.lines() {
+        let trimmed = raw_line.trim();
+
+        if let Some((marker, language, code)) = &mut fence {
+            if trimmed.starts_with(marker.as_str()) && trimmed.trim_start_matches(marker.chars().next().unwrap()).is_empty() {
+                let text = code.join("\n");
+                blocks.push(MarkdownBlock::Code { language: std::mem::take(language), text });
+                fence = None;
+            } else {
+                code.push(raw_line.replace('\t', "    "));
+            }
+            continue;
+        }
+
+        if let Some(marker) = fence_marker(trimmed) {
+            flush_paragraph(&mut paragraph, &mut list_prefix, &mut blocks);
+            let language = trimmed[marker.len()..].split_whitespace().next().unwrap_or("").to_string();
+            fence = Some((marker, language, Vec::new()));
+        } else if trimmed.is_empty() {
+            flush_paragraph(&mut paragraph, &mut list_prefix, &mut blocks);
+        } else if is_rule(trimmed) {
+            flush_paragraph(&mut paragraph, &mut list_prefix, &mut blocks);
+            blocks.push(MarkdownBlock::Rule);
+        } else if let Some(title) = heading_text(trimmed) {
+            flush_paragraph(&mut paragraph, &mut list_prefix, &mut blocks);
+            let mut spans = Vec::new();
+            parse_inline(&title.chars().collect::<Vec<_>>(), HoverTextStyle::Heading, &mut spans);
+            blocks.push(MarkdownBlock::Text { prefix: String::new(), spans, list_item: false });
+        } else if let Some((marker, rest)) = list_item(trimmed) {
+            flush_paragraph(&mut paragraph, &mut list_prefix, &mut blocks);
+            let depth = (raw_line.len() - raw_line.trim_start().len()) / 2;
+            list_prefix = Some(format!("{}{} ", "  ".repeat(depth.min(4)), marker));
+            paragraph.push(rest);
+        } else {
+            paragraph.push(trimmed.strip_prefix('>').map(str::trim_start).unwrap_or(trimmed));
+        }
+    }
+
+    // An unclosed fence runs to the end of the hover
+    if let Some((_, language, code)) = fence {
+        blocks.push(MarkdownBlock::Code { language, text: code.join("\n") });
+    }
+    flush_paragraph(&mut paragraph, &mut list_prefix, &mut blocks);
+    blocks
+}
+
+/// Turn the collected paragraph lines into a text block
+fn flush_paragraph(paragraph: &mut Vec<&str>, list_prefix: &mut Option<String>, blocks: &mut Vec<MarkdownBlock>) {
+    if paragraph.is_empty() {
+        return;
+    }
+    let text = paragraph.join(" ");
+    paragraph.clear();
+
+    let mut spans = Vec::new();
+    parse_inline(&text.chars().collect::<Vec<_>>(), HoverTextStyle::Plain, &mut spans);
+    let list_item = list_prefix.is_some();
+    blocks.push(MarkdownBlock::Text { prefix: list_prefix.take().unwrap_or_default(), spans, list_item });
+}
+
+/// Opening fence (three or more backticks or tildes) at the start of a line
+fn fence_marker(line: &str) -> Option<String> {
+    let first = line.chars().next().filter(|c| *c == '`' || *c == '~')?;
+    let marker: String = line.chars().take_while(|c| *c == first).collect();
+    (marker.len() >= 3).then_some(marker)
+}
+
+/// `---`, `***` or `___`, optionally with spaces in between
+fn is_rule(line: &str) -> bool {
+    let marks: Vec<char> = line.chars().filter(|c| !c.is_whitespace()).collect();
+    marks.len() >= 3 && matches!(marks[0], '-' | '*' | '_') && marks.iter().all(|c| *c == marks[0])
+}
+
+/// Text of an ATX heading (`# Title`), without closing hashes
+fn heading_text(line: &str) -> Option<&str> {
+    let level = line.chars().take_while(|c| *c == '#').count();
+    if level == 0 || level > 6 {
+        return None;
+    }
+    let rest = &line[level..];
+    if !rest.is_empty() && !rest.starts_with(' ') {
+        return None;
+    }
+    Some(rest.trim().trim_end_matches('#').trim_end())
+}
+
+/// Bullet to show and the item's text for `- item`, `* item` or `1. item`
+fn list_item(line: &str) -> Option<(String, &str)> {
+    for bullet in ["- ", "* ", "+ "] {
+        if let Some(rest) = line.strip_prefix(bullet) {
+            return Some(("•".to_string(), rest.trim_start()));
+        }
+    }
+    let digits = line.chars().take_while(|c| c.is_ascii_digit()).count();
+    if digits > 0 && digits < 10 {
+        let rest = &line[digits..];
+        if let Some(item) = rest.strip_prefix(". ").or_else(|| rest.strip_prefix(") ")) {
+            return Some((line[..digits + 1].to_string(), item.trim_start()));
+        }
+    }
+    None
+}
+
+/// Parse inline markup (emphasis, code spans, links, escapes) into spans
+fn parse_inline(chars: &[char], style: HoverTextStyle, spans: &mut Vec<HoverSpan>) {
+    let mut text = String::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+
+        // Backslash escapes of ASCII punctuation
+        if c == '\\' && chars.get(i + 1).is_some_and(|next| next.is_ascii_punctuation()) {
+            text.push(chars[i + 1]);
+            i += 2;
+            continue;
+        }
+
+        // Code spans: a run of backticks closed by a run of the same length
+        if c == '`' {
+            let run = run_length(chars, i, '`');
+            if let Some(close) = find_run(chars, i + run, '`', run) {
+                let code: String = chars[i + run..close].iter().collect();
+                let code = if code.len() > 2 && code.starts_with(' ') && code.ends_with(' ') {
+                    code[1..code.len() - 1].to_string()
+                } else {
+                    code
+                };
+                push_span(spans, &mut text, style);
+                text = code;
+                push_span(spans, &mut text, HoverTextStyle::InlineCode);
+                i = close + run;
+            } else {
+                text.extend(&chars[i..i + run]);
+                i += run;
+            }
+            continue;
+        }
+
+        // Links and images show their text; the target is dropped
+        if c == '[' || (c == '!' && chars.get(i + 1) == Some(&'[')) {
+            let open = if c == '!' { i + 1 } else { i };
+            if let Some((label_end, link_end)) = find_link(chars, open) {
+                push_span(spans, &mut text, style);
+                parse_inline(&chars[open + 1..label_end], style, spans);
+                i = link_end + 1;
+                continue;
+            }
+        }
+
+        // Emphasis: `*`/`_` runs of one (emphasis) or two or more (strong)
+        if c == '*' || c == '_' {
+            let run = run_length(chars, i, c);
+            if let Some(close) = find_emphasis_close(chars, i, run) {
+                push_span(spans, &mut text, style);
+                let inner = if run >= 2 { HoverTextStyle::Strong } else { HoverTextStyle::Emphasis };
+                // Headings keep their own style for the whole line
+                let inner = if style == HoverTextStyle::Heading { style } else { inner };
+                parse_inline(&chars[i + run..close], inner, spans);
+                i = close + run;
+            } else {
+                text.extend(&chars[i..i + run]);
+                i += run;
+            }
+            continue;
+        }
+
+        text.push(c);
+        i += 1;
+    }
+    push_span(spans, &mut text, style);
+}
+
+/// Move collected text into a span, merging with the previous one if it matches
+fn push_span(spans: &mut Vec<HoverSpan>, text: &mut String, style: HoverTextStyle) {
+    if text.is_empty() {
+        return;
+    }
+    match spans.last_mut() {
+        Some(last) if last.style == style && last.color.is_none() => last.text.push_str(text),
+        _ => spans.push(HoverSpan { text: text.clone(), style, color: None }),
+    }
+    text.clear();
+}
+
+/// Number of consecutive `c` starting at `start`
+fn run_length(chars: &[char], start: usize, c: char) -> usize {
+    chars[start..].iter().take_while(|&&x| x == c).count()
+}
+
+/// Start of the next run of exactly `len` `c` at or after `from`
+fn find_run(chars: &[char], from: usize, c: char, len: usize) -> Option<usize> {
+    let mut i = from;
+    while i < chars.len() {
+        if chars[i] == c {
+            let run = run_length(chars, i, c);
+            if run == len {
+                return Some(i);
+            }
+            i += run;
+        } else {
+            i += 1;
+        }
+    }
+    None
+}
+
+/// Closing delimiter for an emphasis run opening at `open`
+///
+/// The opener must be followed by non-whitespace and the closer preceded by
+/// it. Underscores also need word boundaries outside, so `snake_case_name`
+/// stays plain.
+fn find_emphasis_close(chars: &[char], open: usize, len: usize) -> Option<usize> {
+    let c = chars[open];
+    let after_open = chars.get(open + len)?;
+    if after_open.is_whitespace() {
+        return None;
+    }
+    if c == '_' && open > 0 && chars[open - 1].is_alphanumeric() {
+        return None;
+    }
+
+    let mut i = open + len;
+    while i < chars.len() {
+        if chars[i] == '`' {
+            // Delimiters inside code spans don't count
+            let run = run_length(chars, i, '`');
+            i = find_run(chars, i + run, '`', run).map(|close| close + run).unwrap_or(i + run);
+            continue;
+        }
+        if chars[i] != c {
+            i += 1;
+            continue;
+        }
+        let run = run_length(chars, i, c);
+        let closes = run == len
+            && i > open + len
+            && !chars[i - 1].is_whitespace()
+            && (c != '_' || !chars.get(i + run).is_some_and(|next| next.is_alphanumeric()));
+        if closes {
+            return Some(i);
+        }
+        i += run;
+    }
+    None
+}
+
+/// End of the label (`]`) and of the target (`)`) for a link starting at `open`
+fn find_link(chars: &[char], open: usize) -> Option<(usize, usize)> {
+    let mut depth = 0;
+    let mut label_end = None;
+    for (i, &c) in chars.iter().enumerate().skip(open) {
+        match c {
+            '[' => depth += 1,
+            ']' => {
+                depth -= 1;
+                if depth == 0 {
+                    label_end = Some(i);
+                    break;
+                }
+            }
+            _ => {}
+        }
+    }
+    let label_end = label_end?;
+    if chars.get(label_end + 1) != Some(&'(') {
+        return None;
+    }
+    let target_end = chars[label_end + 2..].iter().position(|c| *c == ')')? + label_end + 2;
+    Some((label_end, target_end))
+}
+
+/// Word-wrap styled text to `max_chars` after `prefix`, indenting
+/// continuation lines to line up with the first
+fn wrap_spans(prefix: &str, spans: &[HoverSpan], max_chars: usize, lines: &mut Vec<HoverLine>) {
+    // Words as styled pieces; `**a**b` is one word in two styles
+    let mut words: Vec<Vec<(String, HoverTextStyle)>> = Vec::new();
+    let mut in_word = false;
+    for span in spans {
+        // Inline code keeps its spaces so it isn't reflowed mid-span
+        if span.style == HoverTextStyle::InlineCode {
+            if !in_word {
+                words.push(Vec::new());
+            }
+            words.last_mut().unwrap().push((span.text.clone(), span.style));
+            in_word = true;
+            continue;
+        }
+        for (index, piece) in span.text.split(char::is_whitespace).enumerate() {
+            if index > 0 {
+                in_word = false;
+            }
+            if piece.is_empty() {
+                continue;
+            }
+            if !in_word {
+                words.push(Vec::new());
+            }
+            words.last_mut().unwrap().push((piece.to_string(), span.style));
+            in_word = true;
+        }
+    }
+    if words.is_empty() {
+        return;
+    }
+
+    let prefix_width = prefix.chars().count();
+    let hanging = if prefix_width < max_chars { prefix_width } else { 0 };
+    let mut line = HoverLine::default();
+    line.push(prefix, HoverTextStyle::Plain, None);
+    let mut width = prefix_width;
+    let mut at_line_start = true;
+    let mut last_style = HoverTextStyle::Plain;
+
+    for word in words {
+        let word_width: usize = word.iter().map(|(text, _)| text.chars().count()).sum();
+        if !at_line_start && width + 1 + word_width > max_chars {
+            lines.push(std::mem::take(&mut line));
+            line.push(&" ".repeat(hanging), HoverTextStyle::Plain, None);
+            width = hanging;
+            at_line_start = true;
+        }
+        if !at_line_start {
+            // Spaces take the style around them only when both sides share it
+            let style = if word[0].1 == last_style { last_style } else { HoverTextStyle::Plain };
+            line.push(" ", style, None);
+            width += 1;
+        }
+        for (text, style) in word {
+            // Words longer than a whole line are broken anywhere
+            for c in text.chars() {
+                if width >= max_chars && width > hanging {
+                    lines.push(std::mem::take(&mut line));
+                    line.push(&" ".repeat(hanging), HoverTextStyle::Plain, None);
+                    width = hanging;
+                }
+                let mut buf = [0; 4];
+                line.push(c.encode_utf8(&mut buf), style, None);
+                width += 1;
+            }
+            last_style = style;
+        }
+        at_line_start = false;
+    }
+    lines.push(line);
+}
+
+/// Break a code line into pieces of at most `max_chars`
+fn hard_wrap(line: HoverLine, max_chars: usize, lines: &mut Vec<HoverLine>) {
+    let mut current = HoverLine { spans: Vec::new(), code_block: true };
+    let mut width = 0;
+    for span in line.spans {
+        for c in span.text.chars() {
+            if width == max_chars {
+                lines.push(std::mem::replace(&mut current, HoverLine { spans: Vec::new(), code_block: true }));
+                width = 0;
+            }
+            let mut buf = [0; 4];
+            current.push(c.encode_utf8(&mut buf), span.style, span.color);
+            width += 1;
+        }
+    }
+    lines.push(current);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::FontStyle;
+
+    fn texts(lines: &[HoverLine]) -> Vec<String> {
+        lines.iter().map(HoverLine::text).collect()
+    }
+
+    #[test]
+    fn test_hover_markdown_styles_wraps_and_highlights_fences() {
+        let markdown = "```rust\nfn snake_case(value: u32) -> u32\n```\n\n---\n# Returns\n\n\
+                        Doubles **the** _given_ `value`, see [docs](https://example.com).\n\n\
+                        - first\n- second";
+        let red = Color::srgb(1.0, 0.0, 0.0);
+        let mut requested = Vec::new();
+        let lines = layout_hover_markdown(markdown, 24, |language, code| {
+            requested.push((language.to_string(), code.to_string()));
+            Some(vec![vec![
+                LineSegment { text: "fn".to_string(), color: red, style: FontStyle::Regular },
+                LineSegment { text: " snake_case(value: u32) -> u32".to_string(), color: Color::WHITE, style: FontStyle::Regular },
+            ]])
+        });
+
+        assert_eq!(requested, vec![("rust".to_string(), "fn snake_case(value: u32) -> u32".to_string())]);
+        assert_eq!(
+            texts(&lines),
+            vec![
+                "fn snake_case(value: u32",
+                ") -> u32",
+                "────────────────────────",
+                "Returns",
+                "",
+                "Doubles the given value,",
+                "see docs.",
+                "",
+                "• first",
+                "• second",
+            ]
+        );
+
+        // The fence keeps its highlight colors across the hard wrap
+        assert!(lines[0].code_block && lines[1].code_block);
+        assert_eq!(lines[0].spans[0].color, Some(red));
+        assert_eq!(lines[1].spans[0].style, HoverTextStyle::CodeBlock);
+        assert!(!lines[3].code_block);
+        assert_eq!(lines[3].spans[0].style, HoverTextStyle::Heading);
+
+        let styled: Vec<(&str, HoverTextStyle)> = lines[5].spans.iter().map(|s| (s.text.as_str(), s.style)).collect();
+        assert!(styled.contains(&("the", HoverTextStyle::Strong)));
+        assert!(styled.contains(&("given", HoverTextStyle::Emphasis)));
+        assert!(styled.contains(&("value", HoverTextStyle::InlineCode)));
+    }
+}
@@ -18,6 +18,7 @@ pub enum RequestType {
     DocumentHighlight,
     PrepareRename,
     Rename,
+    DocumentSymbols,
 }
 
 /// Messages sent to language server
@@ -120,6 +121,11 @@ pub enum LspMessage {
         position: Position,
         new_name: String,
     },
+
+    /// Request the document's symbol outline
+    DocumentSymbols {
+        uri: Url,
+    },
 }
 
 /// Responses from language server
@@ -195,6 +201,11 @@ pub enum LspResponse {
     Rename {
         edit: WorkspaceEdit,
     },
+
+    /// Document symbols response (nested or flat)
+    DocumentSymbols {
+        symbols: DocumentSymbolResponse,
+    },
 }
 
 /// Code action or command from LSP
@@ -20,6 +20,7 @@
 //! - `state`: Bevy resources for UI state
 //! - `components`: Marker components for UI elements
 //! - `theme`: Theming configuration
+//! - `markdown`: Hover markdown layout into styled lines
 //! - `sync`: Systems that sync state to marker entities
 //! - `render`: Default render systems for UI elements
 //! - `ui`: Legacy rendering systems (deprecated, use render)
@@ -72,8 +73,10 @@ pub mod capabilities;
 pub mod client;
 pub mod components;
 pub mod event_listeners;
+pub mod markdown;
 pub mod messages;
 pub mod render;
+pub mod requests;
 pub mod state;
 pub mod sync;
 pub mod systems;
@@ -97,11 +100,12 @@ pub mod prelude {
         DocumentHighlightData, HoverPopupData, InlayHintData, InlayHintKind, LspUiElement,
         LspUiVisual, RenameInputData, SignatureHelpPopupData,
     };
+    pub use super::markdown::{layout_hover_markdown, HoverLine, HoverSpan, HoverTextStyle};
     pub use super::messages::{CodeActionOrCommand, LspMessage, LspResponse, RequestType};
     pub use super::state::{
-        CodeActionState, CompletionState, HoverState, InlayHintState, LspSyncState,
-        SignatureHelpState, UnifiedCompletionItem, WordCompletionItem,
-        COMPLETION_MAX_VISIBLE_DEFAULT,
+        CodeActionState, CompletionState, DocumentSymbolTree, FormatOnSaveState, HoverState,
+        InlayHintState, LspSyncState, OutlineSymbol, SignatureHelpState, UnifiedCompletionItem,
+        WordCompletionItem, COMPLETION_MAX_VISIBLE_DEFAULT,
     };
     pub use super::sync::{
         sync_code_actions_popup, sync_completion_popup, sync_document_highlights,
@@ -109,13 +113,14 @@ pub mod prelude {
     };
     pub use super::render::{
         cleanup_lsp_ui_visuals, render_code_actions_popup, render_completion_popup,
-        render_document_highlights, render_hover_popup, render_inlay_hints,
-        render_rename_input, render_signature_help_popup,
+        render_diagnostic_underlines, render_document_highlights, render_hover_popup,
+        render_inlay_hints, render_rename_input, render_signature_help_popup,
     };
     pub use super::systems::{
-        cleanup_lsp_timeouts, execute_code_action, process_lsp_messages, request_code_actions,
-        request_inlay_hints, request_signature_help, sync_lsp_document, DiagnosticMarker,
-        LocationType, MultipleLocationsEvent, NavigateToFileEvent,
+        cleanup_lsp_timeouts, execute_code_action, expire_format_on_save, process_lsp_messages,
+        request_code_actions, request_document_symbols, request_inlay_hints, request_signature_help,
+        sync_lsp_document, DiagnosticMarker, LocationType, MultipleLocationsEvent,
+        NavigateToFileEvent,
     };
     pub use super::event_listeners::{
         listen_apply_completion, listen_completion_requests, listen_dismiss_completion,
@@ -123,19 +128,21 @@ pub mod prelude {
         listen_text_edit_events,
     };
     pub use super::theme::{
-        CodeActionsTheme, CommonTheme, CompletionTheme, DocumentHighlightsTheme, HoverTheme,
-        InlayHintsTheme, LspUiTheme, RenameTheme, SignatureHelpTheme,
+        CodeActionsTheme, CommonTheme, CompletionTheme, DiagnosticsTheme, DocumentHighlightsTheme,
+        HoverTheme, InlayHintsTheme, LspUiTheme, PopupTheme, RenameTheme, SignatureHelpTheme,
     };
     pub use super::ui::{
         update_code_action_ui, update_completion_ui, update_hover_ui, update_inlay_hints_ui,
-        update_signature_help_ui, CodeActionUI, CompletionUI, HoverUI, InlayHintText,
-        SignatureHelpUI,
+        update_signature_help_ui, CodeActionUI, CompletionUI, DiagnosticUnderline, HoverUI,
+        InlayHintText, SignatureHelpUI,
     };
+    pub use super::requests::{char_to_lsp_position, lsp_position_to_char, LspRequests};
     pub use super::{LspUiRenderSet, LspUiSyncSet};
 }
 
 // Re-export commonly used types at module level for backward compatibility
 pub use client::LspClient;
+pub use requests::LspRequests;
 pub use messages::{LspMessage, LspResponse};
 pub use state::{CompletionState, HoverState, LspSyncState, UnifiedCompletionItem, WordCompletionItem, COMPLETION_MAX_VISIBLE_DEFAULT};
 pub use systems::{
@@ -26,19 +26,92 @@
 //! }
 //! ```
 
+use bevy::asset::RenderAssetUsages;
+use bevy::mesh::{Indices, PrimitiveTopology};
 use bevy::prelude::*;
 use bevy::sprite::Anchor;
 
-use crate::settings::FontSettings;
-use crate::types::ViewportDimensions;
+use crate::settings::{FontSettings, IndentationSettings, WrappingSettings};
+use crate::types::{CodeEditorState, FoldState, ViewportDimensions};
 
 use super::components::*;
-use super::theme::LspUiTheme;
+use super::systems::DiagnosticMarker;
+use super::theme::{DiagnosticsTheme, LspUiTheme, PopupTheme};
 use super::ui::{
-    CodeActionUI, CompletionUI, HoverUI, InlayHintText, RenameUI, SignatureHelpUI,
-    DocumentHighlightMarker,
+    CodeActionUI, CompletionUI, DiagnosticUnderline, HoverUI, InlayHintText, RenameUI,
+    SignatureHelpUI, DocumentHighlightMarker,
 };
 
+/// Build a rounded rectangle centered on the origin
+pub(crate) fn rounded_rect_mesh(size: Vec2, radius: f32) -> Mesh {
+    const CORNER_SEGMENTS: usize = 6;
+    let half = size / 2.0;
+    let radius = radius.clamp(0.0, half.x.min(half.y));
+
+    // Triangle fan around the center, walking each corner arc counter-clockwise
+    let mut positions: Vec<[f32; 3]> = vec![[0.0, 0.0, 0.0]];
+    let corners = [
+        (Vec2::new(half.x - radius, half.y - radius), 0.0),
+        (Vec2::new(-half.x + radius, half.y - radius), 0.5),
+        (Vec2::new(-half.x + radius, -half.y + radius), 1.0),
+        (Vec2::new(half.x - radius, -half.y + radius), 1.5),
+    ];
+    for (center, start) in corners {
+        for i in 0..=CORNER_SEGMENTS {
+            let angle = (start + 0.5 * i as f32 / CORNER_SEGMENTS as f32) * std::f32::consts::PI;
+            let p = center + Vec2::new(angle.cos(), angle.sin()) * radius;
+            positions.push([p.x, p.y, 0.0]);
+        }
+    }
+
+    let rim = positions.len() as u32 - 1;
+    let mut indices = Vec::with_capacity(rim as usize * 3);
+    for i in 1..=rim {
+        let next = if i == rim { 1 } else { i + 1 };
+        indices.extend_from_slice(&[0, i, next]);
+    }
+
+    let normals = vec![[0.0, 0.0, 1.0]; positions.len()];
+    let uvs = vec![[0.0, 0.0]; positions.len()];
+    Mesh::new(PrimitiveTopology::TriangleList, RenderAssetUsages::RENDER_WORLD)
+        .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, positions)
+        .with_inserted_attribute(Mesh::ATTRIBUTE_NORMAL, normals)
+        .with_inserted_attribute(Mesh::ATTRIBUTE_UV_0, uvs)
+        .with_inserted_indices(Indices::U32(indices))
+}
+
+/// Rounded background for a popup's root entity
+fn popup_fill(
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<ColorMaterial>,
+    style: &PopupTheme,
+    size: Vec2,
+) -> (Mesh2d, MeshMaterial2d<ColorMaterial>) {
+    (
+        Mesh2d(meshes.add(rounded_rect_mesh(size, style.corner_radius))),
+        MeshMaterial2d(materials.add(style.background)),
+    )
+}
+
+/// Rounded border drawn just behind a popup's fill, if the style has one
+fn popup_border(
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<ColorMaterial>,
+    style: &PopupTheme,
+    size: Vec2,
+) -> Option<(Mesh2d, MeshMaterial2d<ColorMaterial>, Transform, LspUiVisual)> {
+    if style.border_width <= 0.0 {
+        return None;
+    }
+    let outer = size + Vec2::splat(style.border_width * 2.0);
+    Some((
+        Mesh2d(meshes.add(rounded_rect_mesh(outer, style.corner_radius + style.border_width))),
+        MeshMaterial2d(materials.add(style.border)),
+        Transform::from_translation(Vec3::new(0.0, 0.0, -0.01)),
+        LspUiVisual,
+    ))
+}
+
 /// Render the completion popup from marker component data
 pub fn render_completion_popup(
     mut commands: Commands,
@@ -47,6 +120,8 @@ pub fn render_completion_popup(
     font: Res<FontSettings>,
     viewport: Res<ViewportDimensions>,
     theme: Res<LspUiTheme>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
 ) {
     for (_popup_entity, popup) in popup_query.iter() {
         // Clear old visuals
@@ -63,20 +138,22 @@ pub fn render_completion_popup(
             viewport_height / 2.0 - popup.position.y - popup.height / 2.0,
             theme.completion.z_index,
         );
+        let size = Vec2::new(popup.width, popup.height);
+        let style = theme.completion_popup();
 
         commands
             .spawn((
-                Sprite {
-                    color: theme.completion.background,
-                    custom_size: Some(Vec2::new(popup.width, popup.height)),
-                    ..default()
-                },
+                popup_fill(&mut meshes, &mut materials, &style, size),
                 Transform::from_translation(pos),
                 CompletionUI,
                 LspUiVisual,
                 Name::new("CompletionBox"),
             ))
             .with_children(|parent| {
+                if let Some(border) = popup_border(&mut meshes, &mut materials, &style, size) {
+                    parent.spawn(border);
+                }
+
                 let visible_items = popup
                     .items
                     .iter()
@@ -163,6 +240,8 @@ pub fn render_hover_popup(
     font: Res<FontSettings>,
     viewport: Res<ViewportDimensions>,
     theme: Res<LspUiTheme>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
 ) {
     for (_popup_entity, popup) in popup_query.iter() {
         for entity in visual_query.iter() {
@@ -177,35 +256,88 @@ pub fn render_hover_popup(
             viewport_height / 2.0 - popup.position.y - popup.height / 2.0,
             theme.hover.z_index,
         );
+        let size = Vec2::new(popup.width, popup.height);
+        let style = theme.hover_popup();
 
         commands
             .spawn((
-                Sprite {
-                    color: theme.hover.background,
-                    custom_size: Some(Vec2::new(popup.width, popup.height)),
-                    ..default()
-                },
+                popup_fill(&mut meshes, &mut materials, &style, size),
                 Transform::from_translation(pos),
                 HoverUI,
                 LspUiVisual,
                 Name::new("HoverBox"),
             ))
             .with_children(|parent| {
-                let text_x = -popup.width / 2.0 + theme.hover.padding;
-                let text_y = popup.height / 2.0 - theme.hover.padding;
+                if let Some(border) = popup_border(&mut meshes, &mut materials, &style, size) {
+                    parent.spawn(border);
+                }
 
-                parent.spawn((
-                    Text2d::new(&popup.content),
-                    TextFont {
-                        font: font.handle.clone().unwrap_or_default(),
-                        font_size: font.size * 0.9,
-                        ..default()
-                    },
-                    TextColor(theme.hover.text_color),
-                    Transform::from_translation(Vec3::new(text_x, text_y, 0.1)),
-                    Anchor::TOP_LEFT,
-                    LspUiVisual,
-                ));
+                let text_x = -popup.width / 2.0 + style.padding;
+                let text_y = popup.height / 2.0 - style.padding;
+                let text_font = TextFont {
+                    font: font.handle.clone().unwrap_or_default(),
+                    font_size: font.size * 0.9,
+                    ..default()
+                };
+
+                for (row, line) in popup.visible().iter().enumerate() {
+                    let line_y = text_y - row as f32 * popup.line_height;
+
+                    // Code blocks sit on a full-width band
+                    if line.code_block {
+                        parent.spawn((
+                            Sprite {
+                                color: theme.hover.code_background,
+                                custom_size: Some(Vec2::new(popup.width - style.padding, popup.line_height)),
+                                ..default()
+                            },
+                            Transform::from_translation(Vec3::new(0.0, line_y - popup.line_height / 2.0, 0.05)),
+                            LspUiVisual,
+                        ));
+                    }
+
+                    // One Text2d per line with a colored TextSpan per run
+                    parent
+                        .spawn((
+                            Text2d::default(),
+                            text_font.clone(),
+                            TextColor(theme.hover.text_color),
+                            Transform::from_translation(Vec3::new(text_x, line_y, 0.1)),
+                            Anchor::TOP_LEFT,
+                            LspUiVisual,
+                        ))
+                        .with_children(|text| {
+                            for span in &line.spans {
+                                text.spawn((
+                                    TextSpan::new(span.text.clone()),
+                                    text_font.clone(),
+                                    TextColor(theme.hover.span_color(span)),
+                                ));
+                            }
+                        });
+                }
+
+                // Scrollbar thumb when the content is taller than the popup
+                if popup.is_scrollable() {
+                    let track_height = popup.height - style.padding * 2.0;
+                    let total = popup.lines.len() as f32;
+                    let thumb_height = (track_height * popup.visible_lines as f32 / total).max(8.0);
+                    let thumb_top = text_y - (track_height - thumb_height) * popup.scroll_line as f32
+                        / popup.max_scroll_line() as f32;
+                    parent.spawn((
+                        Sprite {
+                            color: theme.hover.text_color.with_alpha(0.3),
+                            custom_size: Some(Vec2::new(3.0, thumb_height)),
+                            ..default()
+                        },
+                        Transform::from_translation(Vec3::new(
+                            popup.width / 2.0 - style.padding / 2.0,
+                            thumb_top - thumb_height / 2.0,
+                            0.2,
+                        )),
+                        LspUiVisual,
+                    ));
+                }
             });
     }
 }
@@ -218,6 +350,8 @@ pub fn render_signature_help_popup(
     font: Res<FontSettings>,
     viewport: Res<ViewportDimensions>,
     theme: Res<LspUiTheme>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
 ) {
     for (_popup_entity, popup) in popup_query.iter() {
         for entity in visual_query.iter() {
@@ -232,21 +366,23 @@ pub fn render_signature_help_popup(
             viewport_height / 2.0 - popup.position.y - popup.height / 2.0,
             theme.signature_help.z_index,
         );
+        let size = Vec2::new(popup.width, popup.height);
+        let style = theme.signature_help_popup();
 
         commands
             .spawn((
-                Sprite {
-                    color: theme.signature_help.background,
-                    custom_size: Some(Vec2::new(popup.width, popup.height)),
-                    ..default()
-                },
+                popup_fill(&mut meshes, &mut materials, &style, size),
                 Transform::from_translation(pos),
                 SignatureHelpUI,
                 LspUiVisual,
                 Name::new("SignatureHelpBox"),
             ))
             .with_children(|parent| {
-                let text_x = -popup.width / 2.0 + theme.signature_help.padding;
+                if let Some(border) = popup_border(&mut meshes, &mut materials, &style, size) {
+                    parent.spawn(border);
+                }
+
+                let text_x = -popup.width / 2.0 + style.padding;
 
                 parent.spawn((
                     Text2d::new(&popup.label),
@@ -273,7 +409,7 @@ pub fn render_signature_help_popup(
                         },
                         TextColor(theme.signature_help.counter_color),
                         Transform::from_translation(Vec3::new(
-                            popup.width / 2.0 - theme.signature_help.padding,
+                            popup.width / 2.0 - style.padding,
                             0.0,
                             0.1,
                         )),
@@ -293,6 +429,8 @@ pub fn render_code_actions_popup(
     font: Res<FontSettings>,
     viewport: Res<ViewportDimensions>,
     theme: Res<LspUiTheme>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
 ) {
     for (_popup_entity, popup) in popup_query.iter() {
         for entity in visual_query.iter() {
@@ -308,20 +446,22 @@ pub fn render_code_actions_popup(
             viewport_height / 2.0 - popup.position.y - popup.height / 2.0,
             theme.code_actions.z_index,
         );
+        let size = Vec2::new(popup.width, popup.height);
+        let style = theme.code_actions_popup();
 
         commands
             .spawn((
-                Sprite {
-                    color: theme.code_actions.background,
-                    custom_size: Some(Vec2::new(popup.width, popup.height)),
-                    ..default()
-                },
+                popup_fill(&mut meshes, &mut materials, &style, size),
                 Transform::from_translation(pos),
                 CodeActionUI,
                 LspUiVisual,
                 Name::new("CodeActionBox"),
             ))
             .with_children(|parent| {
+                if let Some(border) = popup_border(&mut meshes, &mut materials, &style, size) {
+                    parent.spawn(border);
+                }
+
                 for (i, action) in popup.actions.iter().enumerate() {
                     let is_selected = i == popup.selected_index;
                     let item_y = (popup.height / 2.0) - (i as f32 * line_height) - (line_height / 2.0) - 5.0;
@@ -521,6 +661,182 @@ pub fn render_document_highlights(
     }
 }
 
+/// Line pieces `(start, end)` of an underline from `start_x` to `end_x`,
+/// relative to the underline's baseline
+///
+/// A wavy underline zig-zags between the baseline and `wave_height` above it.
+fn underline_segments(start_x: f32, end_x: f32, style: &DiagnosticsTheme) -> Vec<(Vec2, Vec2)> {
+    if !style.wavy || style.wave_length <= 0.0 {
+        return vec![(Vec2::new(start_x, 0.0), Vec2::new(end_x, 0.0))];
+    }
+    let mut segments = Vec::new();
+    let mut x = start_x;
+    let mut up = true;
+    while x < end_x {
+        let next = (x + style.wave_length).min(end_x);
+        let (from, to) = if up { (0.0, style.wave_height) } else { (style.wave_height, 0.0) };
+        // A clipped last segment keeps the slope of a full one
+        let to = from + (to - from) * (next - x) / style.wave_length;
+        segments.push((Vec2::new(x, from), Vec2::new(next, to)));
+        x = next;
+        up = !up;
+    }
+    segments
+}
+
+/// Underline each diagnostic's range in its severity color
+///
+/// Ranges come from the markers' anchors, so the underlines stay on the
+/// diagnosed text through edits until the server publishes again. A range
+/// crossing soft-wrapped rows is underlined on each row. Rows outside the
+/// viewport or hidden by a fold are skipped; underline entities are pooled
+/// and reused, and only re-laid out when the markers, the text or the view
+/// change.
+pub fn render_diagnostic_underlines(
+    mut commands: Commands,
+    diagnostics: Query<Ref<DiagnosticMarker>>,
+    mut removed_diagnostics: RemovedComponents<DiagnosticMarker>,
+    mut segment_query: Query<(&mut Sprite, &mut Transform, &mut Visibility), With<DiagnosticUnderline>>,
+    state: Res<CodeEditorState>,
+    font: Res<FontSettings>,
+    viewport: Res<ViewportDimensions>,
+    fold_state: Res<FoldState>,
+    (wrapping, indentation): (Res<WrappingSettings>, Res<IndentationSettings>),
+    theme: Res<LspUiTheme>,
+) {
+    let markers_changed = !removed_diagnostics.is_empty() || diagnostics.iter().any(|d| d.is_changed());
+    removed_diagnostics.clear();
+    if !markers_changed
+        && !state.is_changed()
+        && !font.is_changed()
+        && !viewport.is_changed()
+        && !fold_state.is_changed()
+        && !wrapping.is_changed()
+        && !theme.is_changed()
+    {
+        return;
+    }
+
+    let style = &theme.diagnostics;
+    let char_width = font.char_width;
+    let line_height = font.line_height;
+    let viewport_width = viewport.width as f32;
+    let viewport_height = viewport.height as f32;
+    let use_wrapping = wrapping.enabled && state.display_map.wrap_width > 0;
+    let h_scroll = if use_wrapping { 0.0 } else { state.horizontal_scroll_offset };
+
+    let visible_start_row = ((-state.scroll_offset) / line_height).floor().max(0.0) as usize;
+    let visible_end_row = visible_start_row + (viewport_height / line_height).ceil() as usize + 2;
+
+    let mut pool = segment_query.iter_mut();
+    for diagnostic in diagnostics.iter() {
+        let start = state.resolve_anchor(&diagnostic.start);
+        let end = state.resolve_anchor(&diagnostic.end).max(start);
+        let color = style.color(diagnostic.severity);
+        let start_line = state.rope.char_to_line(start);
+        let end_line = state.rope.char_to_line(end);
+
+        for line in start_line..=end_line {
+            if fold_state.is_line_hidden(line) {
+                continue;
+            }
+
+            let line_start = state.rope.line_to_char(line);
+            let line_slice = state.rope.line(line);
+            let mut content_len = line_slice.len_chars();
+            while content_len > 0 && matches!(line_slice.char(content_len - 1), '\n' | '\r') {
+                content_len -= 1;
+            }
+            let from = if line == start_line { start - line_start } else { 0 };
+            let mut to = if line == end_line { end - line_start } else { content_len };
+            if from == to {
+                if start_line != end_line {
+                    continue;
+                }
+                // An empty range still marks the character it sits on
+                to = from + 1;
+            }
+
+            // (display row, first column, end column, continuation) of each row of the line
+            let rows: Vec<(usize, usize, usize, bool)> = if use_wrapping {
+                let first_row = state.display_map.buffer_line_to_first_row(line);
+                state.display_map.rows[first_row.min(state.display_map.rows.len())..]
+                    .iter()
+                    .take_while(|row| row.buffer_line == line)
+                    .enumerate()
+                    .map(|(i, row)| (first_row + i, row.start_offset, row.end_offset, row.is_continuation))
+                    .collect()
+            } else {
+                vec![(fold_state.actual_to_display_line(line), 0, content_len, false)]
+            };
+            let row_count = rows.len();
+
+            for (i, (display_row, row_start, row_end, is_continuation)) in rows.into_iter().enumerate() {
+                if display_row < visible_start_row || display_row > visible_end_row {
+                    continue;
+                }
+                // The last row also takes the cell past the end of the line
+                let row_end = if i + 1 == row_count { usize::MAX } else { row_end };
+                let (row_from, row_to) = (from.max(row_start), to.min(row_end));
+                if row_from >= row_to {
+                    continue;
+                }
+
+                let tab_map = &state.display_map.tab_map;
+                let expand = |col: usize| {
+                    tab_map.expand_column_chars(line_slice.chars_at(row_start), (col - row_start) as u32) as f32 * char_width
+                };
+                let indent = if is_continuation && wrapping.indent_wrapped_lines {
+                    indentation.indent_size as f32 * char_width
+                } else {
+                    0.0
+                };
+                let from_x = indent + expand(row_from);
+                // At the end of a line there is no character to measure, so mark one cell
+                let to_x = (indent + expand(row_to.min(content_len))).max(from_x + char_width);
+
+                // Lines are centered on `center_y`; the underline sits at the bottom
+                let y_offset = viewport.text_area_top + state.scroll_offset + display_row as f32 * line_height;
+                let center_y = viewport_height / 2.0 - y_offset;
+                let baseline = center_y - line_height / 2.0 + style.thickness / 2.0;
+                let left = -viewport_width / 2.0 + viewport.text_area_left - h_scroll + viewport.offset_x;
+
+                for (a, b) in underline_segments(from_x, to_x, style) {
+                    let delta = b - a;
+                    let midpoint = Vec2::new(left, baseline) + (a + b) / 2.0;
+                    let transform = Transform::from_translation(midpoint.extend(style.z_index))
+                        .with_rotation(Quat::from_rotation_z(delta.y.atan2(delta.x)));
+                    let size = Vec2::new(delta.length(), style.thickness);
+
+                    if let Some((mut sprite, mut segment_transform, mut visibility)) = pool.next() {
+                        sprite.color = color;
+                        sprite.custom_size = Some(size);
+                        *segment_transform = transform;
+                        *visibility = Visibility::Visible;
+                    } else {
+                        commands.spawn((
+                            Sprite {
+                                color,
+                                custom_size: Some(size),
+                                ..default()
+                            },
+                            transform,
+                            DiagnosticUnderline,
+                            LspUiVisual,
+                            Visibility::Visible,
+                        ));
+                    }
+                }
+            }
+        }
+    }
+
+    // Hide segments no longer needed
+    for (_, _, mut visibility) in pool {
+        *visibility = Visibility::Hidden;
+    }
+}
+
 /// Clean up visual entities when marker entities are removed
 pub fn cleanup_lsp_ui_visuals(
     mut commands: Commands,
@@ -561,3 +877,25 @@ pub fn cleanup_lsp_ui_visuals(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wavy_underline_zigzags_across_the_range() {
+        let style = DiagnosticsTheme::default();
+        let segments = underline_segments(0.0, 7.0, &style);
+        assert_eq!(segments.len(), 3);
+        assert_eq!(segments[0], (Vec2::new(0.0, 0.0), Vec2::new(3.0, 2.0)));
+        assert_eq!(segments[1], (Vec2::new(3.0, 2.0), Vec2::new(6.0, 0.0)));
+        // The clipped last piece rises a third of the way
+        let (from, to) = segments[2];
+        assert_eq!(from, Vec2::new(6.0, 0.0));
+        assert_eq!(to.x, 7.0);
+        assert!((to.y - 2.0 / 3.0).abs() < 1e-5);
+
+        let straight = DiagnosticsTheme { wavy: false, ..default() };
+        assert_eq!(underline_segments(2.0, 9.0, &straight), vec![(Vec2::new(2.0, 0.0), Vec2::new(9.0, 0.0))]);
+    }
+}
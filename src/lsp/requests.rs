@@ -0,0 +1,241 @@
+//! Programmatic LSP requests for host code
+//!
+//! Host UIs (toolbar buttons, command palettes) can trigger LSP features at
+//! any buffer position without going through keyboard or mouse input:
+//!
+//! ```rust,ignore
+//! fn hover_button(mut requests: LspRequests, state: Res<CodeEditorState>) {
+//!     requests.request_hover_at(state.cursor_pos);
+//! }
+//! ```
+//!
+//! Responses are handled by the usual `process_lsp_messages` system.
+
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use lsp_types::{Position, Url};
+
+use crate::input::find_word_start;
+use crate::types::CodeEditorState;
+use super::client::LspClient;
+use super::messages::LspMessage;
+use super::state::{CompletionState, HoverState, LspSyncState};
+
+/// Convert a character offset to an LSP position (line, UTF-16 character)
+pub fn char_to_lsp_position(rope: &ropey::Rope, char_pos: usize) -> Position {
+    let char_pos = char_pos.min(rope.len_chars());
+    let line = rope.char_to_line(char_pos);
+    let line_start = rope.line_to_char(line);
+    Position {
+        line: line as u32,
+        character: (rope.char_to_utf16_cu(char_pos) - rope.char_to_utf16_cu(line_start)) as u32,
+    }
+}
+
+/// Convert an LSP position (UTF-16 character) to a character offset, clamped to the buffer
+pub fn lsp_position_to_char(rope: &ropey::Rope, position: Position) -> usize {
+    let line = (position.line as usize).min(rope.len_lines().saturating_sub(1));
+    let line_start = rope.line_to_char(line);
+    let line_end = line_start + rope.line(line).len_chars();
+    let start_cu = rope.char_to_utf16_cu(line_start);
+    let cu = (start_cu + position.character as usize).min(rope.char_to_utf16_cu(line_end));
+    rope.utf16_cu_to_char(cu)
+}
+
+/// System parameter for sending LSP requests for the tracked document
+///
+/// Each method returns whether a request was sent. Nothing is sent when no
+/// document is open, the server lacks the capability, or a request of the
+/// same kind is still in flight.
+#[derive(SystemParam)]
+pub struct LspRequests<'w> {
+    state: Res<'w, CodeEditorState>,
+    client: Res<'w, LspClient>,
+    sync: Res<'w, LspSyncState>,
+    completion_state: ResMut<'w, CompletionState>,
+    hover_state: ResMut<'w, HoverState>,
+}
+
+impl LspRequests<'_> {
+    /// Request completions at a character offset
+    ///
+    /// Accepting an item replaces the word that ends at `pos`.
+    pub fn request_completion_at(&mut self, pos: usize) -> bool {
+        if !self.request_at(pos, |uri, position| LspMessage::Completion { uri, position }) {
+            return false;
+        }
+        let pos = pos.min(self.state.rope.len_chars());
+        self.completion_state.start_char_index = find_word_start(&self.state.rope, pos);
+        self.completion_state.filter.clear();
+        self.completion_state.items.clear();
+        self.completion_state.selected_index = 0;
+        self.completion_state.scroll_offset = 0;
+        true
+    }
+
+    /// Request hover information at a character offset
+    pub fn request_hover_at(&mut self, pos: usize) -> bool {
+        if !self.request_at(pos, |uri, position| LspMessage::Hover { uri, position }) {
+            return false;
+        }
+        // The response is shown only if it answers the position last hovered
+        let pos = pos.min(self.state.rope.len_chars());
+        self.hover_state.trigger_char_index = pos;
+        self.hover_state.pending_char_index = Some(pos);
+        self.hover_state.request_sent = true;
+        true
+    }
+
+    /// Request the definition of the symbol at a character offset
+    pub fn request_definition_at(&self, pos: usize) -> bool {
+        self.request_at(pos, |uri, position| LspMessage::GotoDefinition { uri, position })
+    }
+
+    /// Request references to the symbol at a character offset
+    pub fn request_references_at(&self, pos: usize) -> bool {
+        self.request_at(pos, |uri, position| LspMessage::References { uri, position })
+    }
+
+    /// Request signature help at a character offset
+    pub fn request_signature_help_at(&self, pos: usize) -> bool {
+        self.request_at(pos, |uri, position| LspMessage::SignatureHelp { uri, position })
+    }
+
+    fn request_at(&self, pos: usize, make: impl FnOnce(Url, Position) -> LspMessage) -> bool {
+        let Some(uri) = &self.sync.document_uri else {
+            return false;
+        };
+        let position = char_to_lsp_position(&self.state.rope, pos);
+        self.client.send_request(make(uri.clone(), position))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::ecs::system::RunSystemOnce;
+    use lsp_types::{CompletionItem, CompletionOptions, HoverProviderCapability, ServerCapabilities};
+    use std::sync::mpsc::Sender;
+    use crate::lsp::messages::{LspResponse, RequestType};
+    use crate::lsp::state::{
+        CodeActionState, DocumentHighlightState, DocumentSymbolTree, FormatOnSaveState, InlayHintState, RenameState,
+        SignatureHelpState,
+    };
+    use crate::lsp::systems::{process_lsp_messages, MultipleLocationsEvent, NavigateToFileEvent, WorkspaceEditEvent};
+    use crate::types::{EditorDiagnostics, SaveRequested};
+
+    type SentMessages = std::sync::mpsc::Receiver<(LspMessage, Option<(i64, RequestType)>)>;
+
+    /// App with everything `process_lsp_messages` needs, and a channel for
+    /// faking server responses
+    fn lsp_app(text: &str, capabilities: ServerCapabilities) -> (App, SentMessages, Sender<LspResponse>) {
+        let (mut client, rx) = LspClient::with_test_channel();
+        let responses = client.test_response_sender();
+        client.capabilities.set(capabilities);
+
+        let mut app = App::new();
+        app.add_message::<NavigateToFileEvent>();
+        app.add_message::<MultipleLocationsEvent>();
+        app.add_message::<WorkspaceEditEvent>();
+        app.add_message::<SaveRequested>();
+        app.insert_resource(CodeEditorState::new(text));
+        app.insert_resource(client);
+        app.insert_resource(LspSyncState {
+            document_uri: Some(Url::parse("file:///main.rs").unwrap()),
+            ..Default::default()
+        });
+        app.init_resource::<CompletionState>();
+        app.init_resource::<HoverState>();
+        app.init_resource::<SignatureHelpState>();
+        app.init_resource::<CodeActionState>();
+        app.init_resource::<InlayHintState>();
+        app.init_resource::<DocumentHighlightState>();
+        app.init_resource::<RenameState>();
+        app.init_resource::<EditorDiagnostics>();
+        app.init_resource::<DocumentSymbolTree>();
+        app.init_resource::<FormatOnSaveState>();
+        (app, rx, responses)
+    }
+
+    #[test]
+    fn test_request_hover_at_sends_hover_and_shows_response() {
+        let capabilities = ServerCapabilities {
+            hover_provider: Some(HoverProviderCapability::Simple(true)),
+            ..Default::default()
+        };
+        let (mut app, rx, responses) = lsp_app("fn main() {\n    foo();\n}\n", capabilities);
+        let pos = app.world().resource::<CodeEditorState>().rope.line_to_char(1) + 6;
+
+        let sent = app.world_mut().run_system_once(move |mut requests: LspRequests| requests.request_hover_at(pos));
+        assert!(sent.unwrap());
+
+        match rx.try_recv() {
+            Ok((LspMessage::Hover { position, .. }, Some((_, RequestType::Hover)))) => {
+                assert_eq!(position, Position { line: 1, character: 6 });
+            }
+            other => panic!("expected a hover request, got {:?}", other),
+        }
+
+        // A second hover waits for the first one's response
+        let sent = app.world_mut().run_system_once(move |mut requests: LspRequests| requests.request_hover_at(pos));
+        assert!(!sent.unwrap());
+        assert!(rx.try_recv().is_err());
+
+        // The response is shown for the requested position
+        responses.send(LspResponse::Hover { content: "fn foo()".to_string(), range: None }).unwrap();
+        app.world_mut().run_system_once(process_lsp_messages).unwrap();
+        let hover_state = app.world().resource::<HoverState>();
+        assert!(hover_state.visible);
+        assert_eq!(hover_state.content, "fn foo()");
+        assert_eq!(hover_state.pending_char_index, None);
+    }
+
+    #[test]
+    fn test_host_completion_replaces_only_the_word_at_pos() {
+        let capabilities = ServerCapabilities {
+            completion_provider: Some(CompletionOptions::default()),
+            ..Default::default()
+        };
+        let (mut app, _rx, responses) = lsp_app("let value = fo", capabilities);
+        let pos = app.world().resource::<CodeEditorState>().rope.len_chars();
+        {
+            let mut state = app.world_mut().resource_mut::<CodeEditorState>();
+            state.cursor_pos = pos;
+        }
+
+        let sent = app.world_mut().run_system_once(move |mut requests: LspRequests| requests.request_completion_at(pos));
+        assert!(sent.unwrap());
+        let completion_state = app.world().resource::<CompletionState>();
+        assert_eq!(completion_state.start_char_index, pos - 2);
+        assert!(completion_state.filter.is_empty());
+
+        responses
+            .send(LspResponse::Completion {
+                items: vec![CompletionItem { label: "foo_bar".to_string(), ..Default::default() }],
+                is_incomplete: false,
+            })
+            .unwrap();
+        app.world_mut().run_system_once(process_lsp_messages).unwrap();
+        assert!(app.world().resource::<CompletionState>().visible);
+
+        app.world_mut()
+            .run_system_once(
+                |mut state: ResMut<CodeEditorState>, mut completion_state: ResMut<CompletionState>| {
+                    crate::input::apply_completion(&mut state, &mut completion_state, &mut crate::types::SnippetSession::default());
+                },
+            )
+            .unwrap();
+        let state = app.world().resource::<CodeEditorState>();
+        assert_eq!(state.rope.to_string(), "let value = foo_bar");
+        assert_eq!(state.cursor_pos, state.rope.len_chars());
+    }
+
+    #[test]
+    fn test_lsp_positions_count_utf16_code_units() {
+        let rope = ropey::Rope::from_str("a😀b\nx");
+        // The emoji is one char but two UTF-16 code units
+        assert_eq!(char_to_lsp_position(&rope, 2), Position { line: 0, character: 3 });
+        assert_eq!(lsp_position_to_char(&rope, Position { line: 0, character: 3 }), 2);
+        assert_eq!(lsp_position_to_char(&rope, Position { line: 1, character: 9 }), 5);
+    }
+}
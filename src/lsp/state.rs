@@ -49,6 +49,14 @@ impl UnifiedCompletionItem {
         }
     }
 
+    /// Whether the insert text is a snippet template with tab stops
+    pub fn is_snippet(&self) -> bool {
+        match self {
+            UnifiedCompletionItem::Lsp(item) => item.insert_text_format == Some(InsertTextFormat::SNIPPET),
+            UnifiedCompletionItem::Word(_) => false,
+        }
+    }
+
     /// Check if this is a word completion
     pub fn is_word(&self) -> bool {
         matches!(self, UnifiedCompletionItem::Word(_))
@@ -301,6 +309,8 @@ pub struct HoverState {
     pub range: Option<Range>,
     /// Whether we've already sent a hover request for this position
     pub request_sent: bool,
+    /// First laid-out line shown when the content is taller than the popup
+    pub scroll_line: usize,
 }
 
 impl HoverState {
@@ -312,6 +322,7 @@ impl HoverState {
         self.range = None;
         self.request_sent = false;
         self.pending_char_index = None;
+        self.scroll_line = 0;
     }
 }
 
@@ -421,6 +432,46 @@ impl Default for LspSyncState {
     }
 }
 
+/// A save waiting for `textDocument/formatting` edits (format-on-save)
+///
+/// Started when Save is pressed with `LspSettings::format_on_save`. The
+/// `SaveRequested` message is emitted once the edits are applied, or with
+/// the unformatted text if the server does not answer in time.
+#[derive(Resource, Clone, Debug, Default)]
+pub struct FormatOnSaveState {
+    /// When the formatting request was sent, while a save is pending
+    pub requested_at: Option<std::time::Instant>,
+    /// Buffer version the server is formatting
+    pub content_version: u64,
+    /// The save went ahead unformatted, so a late answer must be dropped
+    pub expired: bool,
+}
+
+impl FormatOnSaveState {
+    /// Whether a save is waiting for formatting edits
+    pub fn is_pending(&self) -> bool {
+        self.requested_at.is_some()
+    }
+
+    /// Hold the save until edits for `content_version` arrive
+    pub fn start(&mut self, content_version: u64) {
+        self.requested_at = Some(std::time::Instant::now());
+        self.content_version = content_version;
+        self.expired = false;
+    }
+
+    /// Stop waiting; returns whether a save was pending
+    pub fn finish(&mut self) -> bool {
+        self.requested_at.take().is_some()
+    }
+
+    /// Give up waiting after a timeout, dropping the edits if they still arrive
+    pub fn expire(&mut self) {
+        self.requested_at = None;
+        self.expired = true;
+    }
+}
+
 /// State for document highlights (all occurrences of symbol under cursor)
 #[derive(Resource, Default)]
 pub struct DocumentHighlightState {
@@ -501,3 +552,164 @@ impl RenameState {
         self.visible && !self.new_name.is_empty() && self.new_name != self.original_text
     }
 }
+
+/// One entry of the document outline, anchored so it follows edits
+#[derive(Clone, Debug)]
+pub struct OutlineSymbol {
+    /// Symbol name
+    pub name: String,
+    /// Symbol kind (function, struct, ...)
+    pub kind: SymbolKind,
+    /// Extra detail such as a signature, when the server sends one
+    pub detail: Option<String>,
+    /// Start of the symbol's whole extent (e.g. a function with its body)
+    pub start: crate::types::Anchor,
+    /// End of the symbol's whole extent
+    pub end: crate::types::Anchor,
+    /// Start of the symbol's name; navigation lands here
+    pub selection: crate::types::Anchor,
+    /// Nested symbols (e.g. methods of an impl)
+    pub children: Vec<OutlineSymbol>,
+}
+
+impl OutlineSymbol {
+    /// Current char range of the symbol's whole extent
+    pub fn range(&self, state: &crate::types::CodeEditorState) -> std::ops::Range<usize> {
+        let start = state.resolve_anchor(&self.start);
+        start..state.resolve_anchor(&self.end).max(start)
+    }
+
+    /// Move the cursor to the symbol's name and scroll it into view
+    pub fn jump_to(&self, state: &mut crate::types::CodeEditorState) {
+        let pos = state.resolve_anchor(&self.selection);
+        state.jump_to(pos);
+    }
+}
+
+/// Symbol outline of the document from `textDocument/documentSymbol`
+///
+/// Refreshed by `request_document_symbols` after the document changes; hosts
+/// can draw an outline whenever this resource changes.
+#[derive(Resource, Clone, Debug, Default)]
+pub struct DocumentSymbolTree {
+    /// Top-level symbols in document order
+    pub symbols: Vec<OutlineSymbol>,
+    /// Content version the last request was sent for
+    pub requested_version: Option<u64>,
+}
+
+impl DocumentSymbolTree {
+    /// Replace the outline with a server response, anchoring each symbol in `state`
+    ///
+    /// A flat `SymbolInformation` response has no hierarchy, so its symbols
+    /// all become top-level entries.
+    pub fn set(&mut self, state: &mut crate::types::CodeEditorState, response: DocumentSymbolResponse) {
+        fn release(state: &mut crate::types::CodeEditorState, symbols: &[OutlineSymbol]) {
+            for symbol in symbols {
+                state.remove_anchor(symbol.start.id);
+                state.remove_anchor(symbol.end.id);
+                state.remove_anchor(symbol.selection.id);
+                release(state, &symbol.children);
+            }
+        }
+
+        fn anchor(state: &mut crate::types::CodeEditorState, position: Position) -> crate::types::Anchor {
+            let offset = super::requests::lsp_position_to_char(&state.rope, position);
+            state.create_anchor(offset, crate::types::AnchorBias::Left)
+        }
+
+        fn convert(state: &mut crate::types::CodeEditorState, symbol: DocumentSymbol) -> OutlineSymbol {
+            OutlineSymbol {
+                start: anchor(state, symbol.range.start),
+                end: anchor(state, symbol.range.end),
+                selection: anchor(state, symbol.selection_range.start),
+                children: symbol
+                    .children
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(|child| convert(state, child))
+                    .collect(),
+                name: symbol.name,
+                kind: symbol.kind,
+                detail: symbol.detail,
+            }
+        }
+
+        release(state, &self.symbols);
+        self.symbols = match response {
+            DocumentSymbolResponse::Nested(symbols) => {
+                symbols.into_iter().map(|symbol| convert(state, symbol)).collect()
+            }
+            DocumentSymbolResponse::Flat(symbols) => symbols
+                .into_iter()
+                .map(|info| {
+                    let range = info.location.range;
+                    OutlineSymbol {
+                        start: anchor(state, range.start),
+                        end: anchor(state, range.end),
+                        selection: anchor(state, range.start),
+                        children: Vec::new(),
+                        name: info.name,
+                        kind: info.kind,
+                        detail: info.container_name,
+                    }
+                })
+                .collect(),
+        };
+    }
+
+    /// All symbols depth-first with their nesting depth, for drawing an indented outline
+    pub fn flatten(&self) -> Vec<(usize, &OutlineSymbol)> {
+        fn walk<'a>(symbols: &'a [OutlineSymbol], depth: usize, out: &mut Vec<(usize, &'a OutlineSymbol)>) {
+            for symbol in symbols {
+                out.push((depth, symbol));
+                walk(&symbol.children, depth + 1, out);
+            }
+        }
+        let mut out = Vec::new();
+        walk(&self.symbols, 0, &mut out);
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::CodeEditorState;
+
+    #[test]
+    fn test_symbol_tree_keeps_nesting_and_follows_edits() {
+        let mut state = CodeEditorState::new("struct Foo;\nimpl Foo {\n    fn bar() {}\n}\n");
+        let response: DocumentSymbolResponse = serde_json::from_value(serde_json::json!([
+            {
+                "name": "Foo", "kind": 23,
+                "range": { "start": { "line": 0, "character": 0 }, "end": { "line": 0, "character": 11 } },
+                "selectionRange": { "start": { "line": 0, "character": 7 }, "end": { "line": 0, "character": 10 } }
+            },
+            {
+                "name": "impl Foo", "kind": 19,
+                "range": { "start": { "line": 1, "character": 0 }, "end": { "line": 3, "character": 1 } },
+                "selectionRange": { "start": { "line": 1, "character": 5 }, "end": { "line": 1, "character": 8 } },
+                "children": [{
+                    "name": "bar", "kind": 12, "detail": "fn()",
+                    "range": { "start": { "line": 2, "character": 4 }, "end": { "line": 2, "character": 15 } },
+                    "selectionRange": { "start": { "line": 2, "character": 7 }, "end": { "line": 2, "character": 10 } }
+                }]
+            }
+        ]))
+        .unwrap();
+
+        let mut tree = DocumentSymbolTree::default();
+        tree.set(&mut state, response);
+        let outline: Vec<(usize, &str)> = tree.flatten().iter().map(|(depth, s)| (*depth, s.name.as_str())).collect();
+        assert_eq!(outline, vec![(0, "Foo"), (0, "impl Foo"), (1, "bar")]);
+
+        // Anchors follow a line inserted above
+        state.insert_text_at(0, "// outline\n");
+        let bar = tree.symbols[1].children[0].clone();
+        bar.jump_to(&mut state);
+        assert_eq!(state.cursor_pos, state.rope.line_to_char(3) + 7);
+        assert_eq!(bar.range(&state), state.rope.line_to_char(3) + 4..state.rope.line_to_char(3) + 15);
+        assert!(state.reveal_cursor);
+    }
+}
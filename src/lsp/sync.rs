@@ -7,12 +7,15 @@
 
 use bevy::prelude::*;
 
+use crate::plugin::SyntaxResource;
 use crate::settings::*;
 use crate::types::{CodeEditorState, ViewportDimensions};
 
 use super::components::*;
+use super::markdown::{layout_hover_markdown, HoverLine};
 use super::messages::CodeActionOrCommand;
 use super::state::*;
+use super::theme::LspUiTheme;
 
 /// Sync completion state to marker entity
 pub fn sync_completion_popup(
@@ -96,17 +99,18 @@ pub fn sync_completion_popup(
     }
 }
 
-/// Sync hover state to marker entity
+/// Sync hover state to marker entity, laying its markdown out into lines
 pub fn sync_hover_popup(
     mut commands: Commands,
     hover_state: Res<HoverState>,
     editor_state: Res<CodeEditorState>,
     settings: Res<EditorSettings>,
     viewport: Res<ViewportDimensions>,
-    existing: Query<Entity, With<HoverPopupData>>,
+    (theme, mut syntax): (Option<Res<LspUiTheme>>, Option<ResMut<SyntaxResource>>),
+    existing: Query<(Entity, &HoverPopupData)>,
 ) {
     if !hover_state.visible || hover_state.content.is_empty() {
-        for entity in existing.iter() {
+        for (entity, _) in existing.iter() {
             commands.entity(entity).despawn();
         }
         return;
@@ -125,37 +129,66 @@ pub fn sync_hover_popup(
         + editor_state.scroll_offset
         + ((line_index + 1) as f32 * line_height);
 
-    let font_size = settings.font.size * 0.9;
-    let padding = 10.0;
-
-    let max_line_chars = hover_state
-        .content
-        .lines()
-        .map(|l| l.chars().count())
-        .max()
-        .unwrap_or(0);
+    let restyled = settings.is_changed() || theme.as_ref().is_some_and(|theme| theme.is_changed());
+    let default_theme;
+    let theme = match &theme {
+        Some(theme) => &**theme,
+        None => {
+            default_theme = LspUiTheme::default();
+            &default_theme
+        }
+    };
+    let hover = &theme.hover;
+    let padding = theme.hover_popup().padding;
+    let hover_line_height = settings.font.size * 0.9 * 1.2;
     let hover_char_width = settings.font.char_width * 0.9;
+    let wrap_chars = ((hover.max_width - padding * 2.0) / hover_char_width).floor().max(1.0) as usize;
+
+    // Re-layout (and re-highlight) only when the content or styling changed
+    let lines = match existing.iter().next() {
+        Some((_, popup)) if popup.content == hover_state.content && !restyled => popup.lines.clone(),
+        _ => layout_hover_markdown(&hover_state.content, wrap_chars, |language, code| {
+            let syntax = syntax.as_mut()?;
+            if !syntax.matches_language(language) {
+                return None;
+            }
+            syntax.highlight_snippet(code, &settings.theme.syntax, hover.code_color)
+        }),
+    };
 
+    let max_line_chars = lines.iter().map(HoverLine::char_count).max().unwrap_or(0);
     let calculated_width = (max_line_chars as f32 * hover_char_width) + padding * 2.0;
-    let box_width = calculated_width.max(100.0).min(600.0);
+    let box_width = calculated_width.max(hover.min_width).min(hover.max_width);
 
-    let line_count = hover_state.content.lines().count().max(1);
-    let box_height = (line_count as f32 * font_size * 1.2) + padding * 2.0;
+    let fitting_lines = ((hover.max_height - padding * 2.0) / hover_line_height).floor().max(1.0) as usize;
+    let visible_lines = lines.len().clamp(1, fitting_lines);
+    let box_height = (visible_lines as f32 * hover_line_height) + padding * 2.0;
+    let scroll_line = hover_state.scroll_line.min(lines.len().saturating_sub(visible_lines));
 
     let popup_data = HoverPopupData {
         position: Vec2::new(x_offset + viewport.offset_x, y_offset),
         content: hover_state.content.clone(),
+        lines,
+        scroll_line,
+        visible_lines,
+        line_height: hover_line_height,
         width: box_width,
         height: box_height,
     };
 
-    if let Some(entity) = existing.iter().next() {
-        commands.entity(entity).insert(popup_data);
-    } else {
-        commands.spawn((popup_data, LspUiElement, Name::new("HoverPopup")));
+    // Only touch the component when something moved, so the popup isn't
+    // rebuilt every frame
+    match existing.iter().next() {
+        Some((_, popup)) if *popup == popup_data => {}
+        Some((entity, _)) => {
+            commands.entity(entity).insert(popup_data);
+        }
+        None => {
+            commands.spawn((popup_data, LspUiElement, Name::new("HoverPopup")));
+        }
     }
 
-    for entity in existing.iter().skip(1) {
+    for (entity, _) in existing.iter().skip(1) {
         commands.entity(entity).despawn();
     }
 }
@@ -4,13 +4,16 @@ use bevy::prelude::*;
 use lsp_types::*;
 
 use crate::settings::*;
-use crate::types::CodeEditorState;
+use crate::types::{
+    Anchor, AnchorBias, CodeEditorState, DiagnosticLevel, EditorDiagnostic, EditorDiagnostics, SaveRequested,
+};
 
 use super::client::LspClient;
 use super::messages::{CodeActionOrCommand, LspMessage, LspResponse};
+use super::requests::lsp_position_to_char;
 use super::state::{
-    CodeActionState, CompletionState, DocumentHighlightState, HoverState, InlayHintState,
-    LspSyncState, RenameState, SignatureHelpState,
+    CodeActionState, CompletionState, DocumentHighlightState, DocumentSymbolTree, FormatOnSaveState,
+    HoverState, InlayHintState, LspSyncState, RenameState, SignatureHelpState,
 };
 
 /// Diagnostic marker for rendering in editor
@@ -22,8 +25,12 @@ pub struct DiagnosticMarker {
     pub severity: DiagnosticSeverity,
     /// Diagnostic message
     pub message: String,
-    /// Text range
+    /// Text range as published by the server
     pub range: Range,
+    /// Start of the range in the buffer, following edits until the next publish
+    pub start: Anchor,
+    /// End of the range in the buffer, following edits until the next publish
+    pub end: Anchor,
 }
 
 /// Message emitted when navigation to a different file is requested
@@ -64,7 +71,10 @@ pub struct WorkspaceEditEvent {
 pub fn process_lsp_messages(
     mut lsp_client: ResMut<LspClient>,
     mut commands: Commands,
-    diagnostics_query: Query<Entity, With<DiagnosticMarker>>,
+    // Markers spawned by earlier publishes with their anchor ids; kept here
+    // because markers from a publish earlier in the same frame aren't
+    // queryable yet
+    mut diagnostic_markers: Local<Vec<(Entity, u64, u64)>>,
     mut completion_state: ResMut<CompletionState>,
     mut hover_state: ResMut<HoverState>,
     mut sig_state: ResMut<SignatureHelpState>,
@@ -73,10 +83,15 @@ pub fn process_lsp_messages(
     mut highlight_state: ResMut<DocumentHighlightState>,
     mut rename_state: ResMut<RenameState>,
     mut editor_state: ResMut<CodeEditorState>,
-    lsp_sync: Res<LspSyncState>,
+    mut lsp_sync: ResMut<LspSyncState>,
     mut navigate_events: MessageWriter<NavigateToFileEvent>,
     mut multi_location_events: MessageWriter<MultipleLocationsEvent>,
-    mut workspace_edit_events: MessageWriter<WorkspaceEditEvent>,
+    (mut workspace_edit_events, mut save_events): (MessageWriter<WorkspaceEditEvent>, MessageWriter<SaveRequested>),
+    (mut editor_diagnostics, mut symbol_tree, mut format_on_save): (
+        ResMut<EditorDiagnostics>,
+        ResMut<DocumentSymbolTree>,
+        ResMut<FormatOnSaveState>,
+    ),
 ) {
     // Clean up timed out requests periodically
     lsp_client.cleanup_timeouts();
@@ -90,18 +105,48 @@ pub fn process_lsp_messages(
             }
 
             LspResponse::Diagnostics { uri: _, diagnostics } => {
-                // Clear old diagnostics
-                for entity in diagnostics_query.iter() {
+                // Clear old diagnostics and the anchors that tracked them
+                for (entity, start_id, end_id) in diagnostic_markers.drain(..) {
+                    editor_state.remove_anchor(start_id);
+                    editor_state.remove_anchor(end_id);
                     commands.entity(entity).despawn();
                 }
 
-                for diagnostic in diagnostics {
-                    commands.spawn(DiagnosticMarker {
-                        line: diagnostic.range.start.line as usize,
-                        severity: diagnostic.severity.unwrap_or(DiagnosticSeverity::HINT),
-                        message: diagnostic.message.clone(),
-                        range: diagnostic.range,
-                    });
+                let rope = &editor_state.rope;
+                let ranges: Vec<std::ops::Range<usize>> = diagnostics
+                    .iter()
+                    .map(|d| lsp_position_to_char(rope, d.range.start)..lsp_position_to_char(rope, d.range.end))
+                    .collect();
+
+                // Mirror into the feature-independent store used for navigation;
+                // a missing severity is left to the client, which treats it as an error
+                editor_diagnostics.set(diagnostics.iter().zip(&ranges).map(|(d, range)| EditorDiagnostic {
+                    range: range.clone(),
+                    level: match d.severity {
+                        None | Some(DiagnosticSeverity::ERROR) => DiagnosticLevel::Error,
+                        Some(DiagnosticSeverity::WARNING) => DiagnosticLevel::Warning,
+                        Some(DiagnosticSeverity::INFORMATION) => DiagnosticLevel::Information,
+                        _ => DiagnosticLevel::Hint,
+                    },
+                    message: d.message.clone(),
+                }).collect());
+
+                for (diagnostic, range) in diagnostics.into_iter().zip(ranges) {
+                    // Text typed at either edge stays outside the range
+                    let start = editor_state.create_anchor(range.start, AnchorBias::Right);
+                    let end = editor_state.create_anchor(range.end, AnchorBias::Left);
+                    let (start_id, end_id) = (start.id, end.id);
+                    let entity = commands
+                        .spawn(DiagnosticMarker {
+                            line: diagnostic.range.start.line as usize,
+                            severity: diagnostic.severity.unwrap_or(DiagnosticSeverity::ERROR),
+                            message: diagnostic.message,
+                            range: diagnostic.range,
+                            start,
+                            end,
+                        })
+                        .id();
+                    diagnostic_markers.push((entity, start_id, end_id));
                 }
             }
 
@@ -124,6 +169,7 @@ pub fn process_lsp_messages(
                             hover_state.content = content;
                             hover_state.range = range;
                             hover_state.visible = true;
+                            hover_state.scroll_line = 0;
                         }
                     }
                 }
@@ -155,8 +201,7 @@ pub fn process_lsp_messages(
 
                     if line_num < editor_state.rope.len_lines() {
                         let line_start_char = editor_state.rope.line_to_char(line_num);
-                        let target_char_pos = line_start_char + char_in_line;
-                        editor_state.cursor_pos = target_char_pos.min(editor_state.rope.len_chars());
+                        editor_state.jump_to(line_start_char + char_in_line);
                         editor_state.needs_update = true;
                     }
                 } else {
@@ -183,7 +228,29 @@ pub fn process_lsp_messages(
             LspResponse::Format { edits } => {
                 #[cfg(debug_assertions)]
                 eprintln!("[LSP] Format: {} edit(s)", edits.len());
-                apply_text_edits(&mut editor_state, edits);
+
+                // Edits computed for text that has changed since would land in the wrong place,
+                // and edits arriving after a timed-out save would leave the saved text stale
+                let pending_save = format_on_save.finish();
+                let expired = std::mem::take(&mut format_on_save.expired);
+                let stale = pending_save && format_on_save.content_version != editor_state.content_version;
+                if !expired && !stale {
+                    let version = editor_state.content_version;
+                    apply_text_edits(&mut editor_state, edits);
+
+                    // Let the server see the formatted text
+                    if editor_state.content_version != version {
+                        lsp_sync.document_version += 1;
+                        lsp_sync.dirty = true;
+                    }
+                }
+
+                // The save was held back until the formatting landed
+                if pending_save {
+                    save_events.write(SaveRequested {
+                        content: editor_state.rope.to_string(),
+                    });
+                }
             }
 
             LspResponse::SignatureHelp {
@@ -251,59 +318,49 @@ pub fn process_lsp_messages(
                 // Close rename dialog
                 rename_state.reset();
             }
+
+            LspResponse::DocumentSymbols { symbols } => {
+                symbol_tree.set(&mut editor_state, symbols);
+            }
         }
     }
 }
 
-/// Apply text edits from formatting
+/// Apply server text edits (rename, format) to the current document as one undo step
 fn apply_text_edits(editor_state: &mut CodeEditorState, edits: Vec<TextEdit>) {
-    // Sort edits in reverse order to preserve positions
-    let mut edits_sorted = edits;
-    edits_sorted.sort_by(|a, b| {
-        let a_pos = (a.range.start.line, a.range.start.character);
-        let b_pos = (b.range.start.line, b.range.start.character);
-        b_pos.cmp(&a_pos)
-    });
-
-    for edit in edits_sorted {
-        let start_line = edit.range.start.line as usize;
-        let end_line = edit.range.end.line as usize;
-        let start_char = edit.range.start.character as usize;
-        let end_char = edit.range.end.character as usize;
-
-        if start_line < editor_state.rope.len_lines() {
-            let start_line_char = editor_state.rope.line_to_char(start_line);
-            let start_pos = start_line_char + start_char;
-
-            let end_pos = if end_line < editor_state.rope.len_lines() {
-                let end_line_char = editor_state.rope.line_to_char(end_line);
-                (end_line_char + end_char).min(editor_state.rope.len_chars())
-            } else {
-                editor_state.rope.len_chars()
-            };
-
-            let start_pos = start_pos.min(editor_state.rope.len_chars());
-            let end_pos = end_pos.min(editor_state.rope.len_chars());
-
-            if start_pos <= end_pos {
-                let start_byte = editor_state.rope.char_to_byte(start_pos);
-                let end_byte = editor_state.rope.char_to_byte(end_pos);
-                let new_end_byte = start_byte + edit.new_text.len();
-
-                #[cfg(feature = "tree-sitter")]
-                editor_state.record_edit(start_byte, end_byte, new_end_byte);
+    let rope = &editor_state.rope;
+    let edits = edits
+        .into_iter()
+        .map(|edit| {
+            let start = lsp_position_to_char(rope, edit.range.start);
+            let end = lsp_position_to_char(rope, edit.range.end);
+            (start..end, edit.new_text)
+        })
+        .collect();
+    editor_state.apply_edits(edits);
+}
 
-                editor_state.rope.remove(start_byte..end_byte);
-                editor_state.rope.insert(start_pos, &edit.new_text);
-            }
-        }
+/// Save unformatted when the server has not answered a format-on-save request in time
+pub fn expire_format_on_save(
+    settings: Res<LspSettings>,
+    editor_state: Res<CodeEditorState>,
+    mut format_on_save: ResMut<FormatOnSaveState>,
+    mut save_events: MessageWriter<SaveRequested>,
+) {
+    let Some(requested_at) = format_on_save.requested_at else {
+        return;
+    };
+    if requested_at.elapsed() < std::time::Duration::from_millis(settings.format_on_save_timeout_ms) {
+        return;
     }
 
-    editor_state.needs_update = true;
-    editor_state.pending_update = false;
-    editor_state.content_version += 1;
-    editor_state.dirty_lines = None;
-    editor_state.previous_line_count = editor_state.rope.len_lines();
+    #[cfg(debug_assertions)]
+    eprintln!("[LSP] Format on save timed out, saving unformatted");
+
+    format_on_save.expire();
+    save_events.write(SaveRequested {
+        content: editor_state.rope.to_string(),
+    });
 }
 
 /// System to sync document with LSP (debounced)
@@ -396,6 +453,31 @@ pub fn request_inlay_hints(
     hint_state.needs_refresh = false;
 }
 
+/// System to request the document outline once the server has the latest text
+pub fn request_document_symbols(
+    lsp_client: Res<LspClient>,
+    editor_state: Res<CodeEditorState>,
+    lsp_sync: Res<LspSyncState>,
+    mut symbol_tree: ResMut<DocumentSymbolTree>,
+) {
+    if !lsp_client.is_ready() || !lsp_client.capabilities.supports_document_symbols() {
+        return;
+    }
+
+    // Wait for the debounced didChange so the outline matches the buffer
+    if lsp_sync.dirty || symbol_tree.requested_version == Some(editor_state.content_version) {
+        return;
+    }
+
+    let Some(uri) = &lsp_sync.document_uri else {
+        return;
+    };
+
+    if lsp_client.send_request(LspMessage::DocumentSymbols { uri: uri.clone() }) {
+        symbol_tree.requested_version = Some(editor_state.content_version);
+    }
+}
+
 /// System to clean up LSP timeout requests
 pub fn cleanup_lsp_timeouts(lsp_client: Res<LspClient>) {
     lsp_client.cleanup_timeouts();
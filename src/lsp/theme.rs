@@ -4,6 +4,9 @@
 //! Users can customize colors, sizing, and styling by modifying the `LspUiTheme` resource.
 
 use bevy::prelude::*;
+use lsp_types::DiagnosticSeverity;
+
+use super::markdown::{HoverSpan, HoverTextStyle};
 
 /// Theme configuration for LSP UI elements
 ///
@@ -14,14 +17,23 @@ use bevy::prelude::*;
 /// - Code actions
 /// - Inlay hints
 /// - Document highlights
+/// - Diagnostic underlines
 /// - Rename input
 ///
+/// The completion, hover, signature help and code action popups share the
+/// frame styling in `popup`; set a field on an individual popup theme to
+/// override it for that popup only.
+///
 /// # Example
 /// ```rust,ignore
 /// fn setup(mut commands: Commands) {
 ///     commands.insert_resource(LspUiTheme {
-///         completion: CompletionTheme {
+///         popup: PopupTheme {
 ///             background: Color::srgba(0.1, 0.1, 0.1, 0.95),
+///             corner_radius: 6.0,
+///             ..default()
+///         },
+///         completion: CompletionTheme {
 ///             selected_background: Color::srgba(0.3, 0.5, 0.9, 0.8),
 ///             ..default()
 ///         },
@@ -31,6 +43,8 @@ use bevy::prelude::*;
 /// ```
 #[derive(Resource, Clone, Debug)]
 pub struct LspUiTheme {
+    /// Shared frame styling for all popups
+    pub popup: PopupTheme,
     /// Theme for completion popup
     pub completion: CompletionTheme,
     /// Theme for hover popup
@@ -43,6 +57,8 @@ pub struct LspUiTheme {
     pub inlay_hints: InlayHintsTheme,
     /// Theme for document highlights
     pub document_highlights: DocumentHighlightsTheme,
+    /// Theme for diagnostic underlines
+    pub diagnostics: DiagnosticsTheme,
     /// Theme for rename input
     pub rename: RenameTheme,
     /// Common styling
@@ -52,28 +68,101 @@ pub struct LspUiTheme {
 impl Default for LspUiTheme {
     fn default() -> Self {
         Self {
+            popup: PopupTheme::default(),
             completion: CompletionTheme::default(),
             hover: HoverTheme::default(),
             signature_help: SignatureHelpTheme::default(),
             code_actions: CodeActionsTheme::default(),
             inlay_hints: InlayHintsTheme::default(),
             document_highlights: DocumentHighlightsTheme::default(),
+            diagnostics: DiagnosticsTheme::default(),
             rename: RenameTheme::default(),
             common: CommonTheme::default(),
         }
     }
 }
 
+impl LspUiTheme {
+    /// Resolved frame style for the completion popup
+    pub fn completion_popup(&self) -> PopupTheme {
+        let c = &self.completion;
+        self.popup.with_overrides(c.background, c.border, c.border_width, c.padding)
+    }
 
-/// Theme for completion popup
+    /// Resolved frame style for the hover popup
+    pub fn hover_popup(&self) -> PopupTheme {
+        let h = &self.hover;
+        self.popup.with_overrides(h.background, h.border, h.border_width, h.padding)
+    }
+
+    /// Resolved frame style for the signature help popup
+    pub fn signature_help_popup(&self) -> PopupTheme {
+        let s = &self.signature_help;
+        self.popup.with_overrides(s.background, s.border, s.border_width, s.padding)
+    }
+
+    /// Resolved frame style for the code actions menu
+    pub fn code_actions_popup(&self) -> PopupTheme {
+        let a = &self.code_actions;
+        self.popup.with_overrides(a.background, a.border, a.border_width, a.padding)
+    }
+}
+
+/// Frame styling shared by all LSP popups
 #[derive(Clone, Debug)]
-pub struct CompletionTheme {
+pub struct PopupTheme {
     /// Background color
     pub background: Color,
     /// Border color
     pub border: Color,
-    /// Border width in pixels
+    /// Border width in pixels (0 disables the border)
     pub border_width: f32,
+    /// Padding inside the popup
+    pub padding: f32,
+    /// Corner radius in pixels
+    pub corner_radius: f32,
+}
+
+impl Default for PopupTheme {
+    fn default() -> Self {
+        Self {
+            background: Color::srgba(0.15, 0.15, 0.15, 0.95),
+            border: Color::srgba(0.3, 0.3, 0.3, 1.0),
+            border_width: 1.0,
+            padding: 8.0,
+            corner_radius: 4.0,
+        }
+    }
+}
+
+impl PopupTheme {
+    /// Copy of this style with any per-popup overrides applied
+    pub fn with_overrides(
+        &self,
+        background: Option<Color>,
+        border: Option<Color>,
+        border_width: Option<f32>,
+        padding: Option<f32>,
+    ) -> Self {
+        Self {
+            background: background.unwrap_or(self.background),
+            border: border.unwrap_or(self.border),
+            border_width: border_width.unwrap_or(self.border_width),
+            padding: padding.unwrap_or(self.padding),
+            corner_radius: self.corner_radius,
+        }
+    }
+}
+
+/// Theme for completion popup
+#[derive(Clone, Debug)]
+pub struct CompletionTheme {
+    /// Background color override (defaults to `PopupTheme::background`)
+    pub background: Option<Color>,
+    /// Border color override
+    pub border: Option<Color>,
+    /// Border width override in pixels
+    pub border_width: Option<f32>,
     /// Selected item background
     pub selected_background: Color,
     /// Text color for labels
@@ -88,8 +177,8 @@ pub struct CompletionTheme {
     pub min_width: f32,
     /// Maximum popup width
     pub max_width: f32,
-    /// Padding inside the popup
-    pub padding: f32,
+    /// Padding override
+    pub padding: Option<f32>,
     /// Z-index for layering
     pub z_index: f32,
 }
@@ -97,9 +186,9 @@ pub struct CompletionTheme {
 impl Default for CompletionTheme {
     fn default() -> Self {
         Self {
-            background: Color::srgba(0.15, 0.15, 0.15, 0.95),
-            border: Color::srgba(0.3, 0.3, 0.3, 1.0),
-            border_width: 1.0,
+            background: None,
+            border: None,
+            border_width: None,
             selected_background: Color::srgba(0.2, 0.4, 0.8, 0.8),
             text_color: Color::WHITE,
             word_text_color: Color::srgba(0.9, 0.9, 0.8, 1.0),
@@ -107,7 +196,7 @@ impl Default for CompletionTheme {
             icon_color: Color::srgba(0.6, 0.6, 0.6, 1.0),
             min_width: 200.0,
             max_width: 600.0,
-            padding: 5.0,
+            padding: None,
             z_index: 100.0,
         }
     }
@@ -116,22 +205,32 @@ impl Default for CompletionTheme {
 /// Theme for hover popup
 #[derive(Clone, Debug)]
 pub struct HoverTheme {
-    /// Background color
-    pub background: Color,
-    /// Border color
-    pub border: Color,
-    /// Border width in pixels
-    pub border_width: f32,
+    /// Background color override (defaults to `PopupTheme::background`)
+    pub background: Option<Color>,
+    /// Border color override
+    pub border: Option<Color>,
+    /// Border width override in pixels
+    pub border_width: Option<f32>,
     /// Text color
     pub text_color: Color,
+    /// Heading text color
+    pub heading_color: Color,
+    /// Bold text color
+    pub strong_color: Color,
+    /// Italic text color
+    pub emphasis_color: Color,
+    /// Inline code and unhighlighted code block color
+    pub code_color: Color,
     /// Code block background
     pub code_background: Color,
     /// Minimum popup width
     pub min_width: f32,
-    /// Maximum popup width
+    /// Maximum popup width; longer lines wrap
     pub max_width: f32,
-    /// Padding inside the popup
-    pub padding: f32,
+    /// Maximum popup height; longer contents scroll with the mouse wheel
+    pub max_height: f32,
+    /// Padding override
+    pub padding: Option<f32>,
     /// Z-index for layering
     pub z_index: f32,
 }
@@ -139,36 +238,54 @@ pub struct HoverTheme {
 impl Default for HoverTheme {
     fn default() -> Self {
         Self {
-            background: Color::srgba(0.1, 0.1, 0.1, 0.95),
-            border: Color::srgba(0.3, 0.3, 0.3, 1.0),
-            border_width: 1.0,
+            background: None,
+            border: None,
+            border_width: None,
             text_color: Color::WHITE,
+            heading_color: Color::srgb(0.55, 0.75, 1.0),
+            strong_color: Color::srgb(1.0, 0.85, 0.55),
+            emphasis_color: Color::srgb(0.8, 0.8, 0.8),
+            code_color: Color::srgb(0.8, 0.9, 0.7),
             code_background: Color::srgba(0.08, 0.08, 0.08, 1.0),
             min_width: 100.0,
             max_width: 600.0,
-            padding: 10.0,
+            max_height: 400.0,
+            padding: None,
             z_index: 100.0,
         }
     }
 }
 
+impl HoverTheme {
+    /// Color for a run of hover text, preferring its syntax color
+    pub fn span_color(&self, span: &HoverSpan) -> Color {
+        span.color.unwrap_or(match span.style {
+            HoverTextStyle::Plain => self.text_color,
+            HoverTextStyle::Strong => self.strong_color,
+            HoverTextStyle::Emphasis => self.emphasis_color,
+            HoverTextStyle::Heading => self.heading_color,
+            HoverTextStyle::InlineCode | HoverTextStyle::CodeBlock => self.code_color,
+        })
+    }
+}
+
 /// Theme for signature help popup
 #[derive(Clone, Debug)]
 pub struct SignatureHelpTheme {
-    /// Background color
-    pub background: Color,
-    /// Border color
-    pub border: Color,
-    /// Border width in pixels
-    pub border_width: f32,
+    /// Background color override (defaults to `PopupTheme::background`)
+    pub background: Option<Color>,
+    /// Border color override
+    pub border: Option<Color>,
+    /// Border width override in pixels
+    pub border_width: Option<f32>,
     /// Text color
     pub text_color: Color,
     /// Active parameter highlight color
     pub active_param_color: Color,
     /// Signature counter color (e.g., "1/3")
     pub counter_color: Color,
-    /// Padding inside the popup
-    pub padding: f32,
+    /// Padding override
+    pub padding: Option<f32>,
     /// Z-index for layering
     pub z_index: f32,
 }
@@ -176,13 +293,13 @@ pub struct SignatureHelpTheme {
 impl Default for SignatureHelpTheme {
     fn default() -> Self {
         Self {
-            background: Color::srgba(0.12, 0.12, 0.12, 0.95),
-            border: Color::srgba(0.3, 0.3, 0.3, 1.0),
-            border_width: 1.0,
+            background: None,
+            border: None,
+            border_width: None,
             text_color: Color::WHITE,
             active_param_color: Color::srgba(0.4, 0.6, 1.0, 1.0),
             counter_color: Color::srgba(0.6, 0.6, 0.6, 1.0),
-            padding: 8.0,
+            padding: None,
             z_index: 100.0,
         }
     }
@@ -191,12 +308,12 @@ impl Default for SignatureHelpTheme {
 /// Theme for code actions menu
 #[derive(Clone, Debug)]
 pub struct CodeActionsTheme {
-    /// Background color
-    pub background: Color,
-    /// Border color
-    pub border: Color,
-    /// Border width in pixels
-    pub border_width: f32,
+    /// Background color override (defaults to `PopupTheme::background`)
+    pub background: Option<Color>,
+    /// Border color override
+    pub border: Option<Color>,
+    /// Border width override in pixels
+    pub border_width: Option<f32>,
     /// Selected item background
     pub selected_background: Color,
     /// Text color
@@ -205,8 +322,8 @@ pub struct CodeActionsTheme {
     pub min_width: f32,
     /// Maximum popup width
     pub max_width: f32,
-    /// Padding inside the popup
-    pub padding: f32,
+    /// Padding override
+    pub padding: Option<f32>,
     /// Z-index for layering
     pub z_index: f32,
 }
@@ -214,14 +331,14 @@ pub struct CodeActionsTheme {
 impl Default for CodeActionsTheme {
     fn default() -> Self {
         Self {
-            background: Color::srgba(0.15, 0.15, 0.15, 0.95),
-            border: Color::srgba(0.3, 0.3, 0.3, 1.0),
-            border_width: 1.0,
+            background: None,
+            border: None,
+            border_width: None,
             selected_background: Color::srgba(0.2, 0.4, 0.8, 0.8),
             text_color: Color::WHITE,
             min_width: 200.0,
             max_width: 400.0,
-            padding: 5.0,
+            padding: None,
             z_index: 100.0,
         }
     }
@@ -272,6 +389,57 @@ impl Default for DocumentHighlightsTheme {
     }
 }
 
+/// Theme for diagnostic underlines
+#[derive(Clone, Debug)]
+pub struct DiagnosticsTheme {
+    /// Underline color for errors
+    pub error_color: Color,
+    /// Underline color for warnings
+    pub warning_color: Color,
+    /// Underline color for information
+    pub info_color: Color,
+    /// Underline color for hints
+    pub hint_color: Color,
+    /// Draw a wavy (squiggly) underline instead of a straight one
+    pub wavy: bool,
+    /// Line thickness in pixels
+    pub thickness: f32,
+    /// Distance between the wave's crest and trough in pixels
+    pub wave_height: f32,
+    /// Horizontal length of one rise or fall of the wave in pixels
+    pub wave_length: f32,
+    /// Z-index for layering
+    pub z_index: f32,
+}
+
+impl Default for DiagnosticsTheme {
+    fn default() -> Self {
+        Self {
+            error_color: Color::srgb(0.976, 0.298, 0.298),
+            warning_color: Color::srgb(0.804, 0.667, 0.0),
+            info_color: Color::srgb(0.294, 0.678, 0.961),
+            hint_color: Color::srgba(0.675, 0.675, 0.675, 0.7),
+            wavy: true,
+            thickness: 1.0,
+            wave_height: 2.0,
+            wave_length: 3.0,
+            z_index: 0.4,
+        }
+    }
+}
+
+impl DiagnosticsTheme {
+    /// Underline color for a severity
+    pub fn color(&self, severity: DiagnosticSeverity) -> Color {
+        match severity {
+            DiagnosticSeverity::ERROR => self.error_color,
+            DiagnosticSeverity::WARNING => self.warning_color,
+            DiagnosticSeverity::INFORMATION => self.info_color,
+            _ => self.hint_color,
+        }
+    }
+}
+
 /// Theme for rename input
 #[derive(Clone, Debug)]
 pub struct RenameTheme {
@@ -319,7 +487,7 @@ impl Default for RenameTheme {
 pub struct CommonTheme {
     /// Default border width
     pub border_width: f32,
-    /// Corner radius for popups (note: sprites don't support this directly)
+    /// Corner radius for non-popup elements (popups use `PopupTheme::corner_radius`)
     pub corner_radius: f32,
     /// Shadow offset
     pub shadow_offset: Vec2,
@@ -340,3 +508,23 @@ impl Default for CommonTheme {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shared_popup_background_propagates_unless_overridden() {
+        let shared = Color::srgb(0.2, 0.1, 0.3);
+        let custom = Color::srgb(0.9, 0.9, 0.9);
+        let mut theme = LspUiTheme::default();
+        theme.popup.background = shared;
+
+        assert_eq!(theme.hover_popup().background, shared);
+        assert_eq!(theme.completion_popup().background, shared);
+
+        theme.completion.background = Some(custom);
+        assert_eq!(theme.completion_popup().background, custom);
+        assert_eq!(theme.hover_popup().background, shared);
+    }
+}
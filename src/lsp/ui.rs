@@ -40,6 +40,10 @@ pub struct DocumentHighlightMarker {
     pub line: u32,
 }
 
+/// Marker for one segment of a diagnostic underline
+#[derive(Component)]
+pub struct DiagnosticUnderline;
+
 /// Marker for rename dialog UI
 #[derive(Component)]
 pub struct RenameUI;
@@ -0,0 +1,97 @@
+//! Bookmark gutter indicators
+//!
+//! Bookmarks are anchors on `CodeEditorState`, so they follow edits on their
+//! own; this only draws a marker on each visible bookmarked line.
+
+use bevy::prelude::*;
+use crate::settings::*;
+use crate::types::*;
+use super::{line_display_row, to_bevy_coords_left_aligned};
+
+/// Marker drawn for each bookmarked line
+const BOOKMARK_MARKER: &str = "●";
+
+pub(crate) fn update_bookmark_indicators(
+    mut commands: Commands,
+    state: Res<CodeEditorState>,
+    (font, theme, ui): (Res<FontSettings>, Res<ThemeSettings>, Res<UiSettings>),
+    (viewport, wrapping): (Res<ViewportDimensions>, Res<WrappingSettings>),
+    gutter: Res<GutterColumns>,
+    fold_state: Res<FoldState>,
+    mut indicator_query: Query<(&mut BookmarkIndicator, &mut Transform, &mut Visibility, &mut TextColor, &mut TextFont)>,
+) {
+    let changed = state.is_changed()
+        || fold_state.is_changed()
+        || viewport.is_changed()
+        || gutter.is_changed()
+        || font.is_changed()
+        || theme.is_changed()
+        || ui.is_changed()
+        || wrapping.is_changed();
+    if !changed {
+        return;
+    }
+
+    let lines = state.bookmark_lines();
+    let use_wrapping = wrapping.enabled && state.display_map.wrap_width > 0;
+
+    let line_height = font.line_height;
+    let first_visible_row = ((-state.scroll_offset) / line_height).floor().max(0.0) as usize;
+    let last_visible_row = first_visible_row + (viewport.height as f32 / line_height).ceil() as usize + 1;
+
+    // In the bookmark column when the layout has one, otherwise in the left padding
+    let x = gutter
+        .column(&GutterColumnKind::Bookmarks)
+        .map(|column| column.center_x())
+        .unwrap_or(ui.gutter_padding_left / 2.0);
+
+    let mut indicators = indicator_query.iter_mut();
+    for line in lines {
+        if fold_state.is_line_hidden(line) {
+            continue;
+        }
+        let display_row = line_display_row(&state, &fold_state, line, use_wrapping);
+        if display_row < first_visible_row || display_row > last_visible_row {
+            continue;
+        }
+
+        let y = viewport.text_area_top + state.scroll_offset + display_row as f32 * line_height;
+        let translation = to_bevy_coords_left_aligned(
+            x,
+            y,
+            viewport.width as f32,
+            viewport.height as f32,
+            viewport.offset_x,
+            0.0,
+        );
+
+        if let Some((mut indicator, mut transform, mut visibility, mut color, mut text_font)) = indicators.next() {
+            indicator.line_index = line;
+            transform.translation = translation;
+            color.0 = theme.bookmark;
+            if text_font.font_size != font.char_width {
+                text_font.font_size = font.char_width;
+            }
+            *visibility = Visibility::Visible;
+        } else {
+            commands.spawn((
+                Text2d::new(BOOKMARK_MARKER),
+                TextFont {
+                    font: font.handle.clone().unwrap_or_default(),
+                    font_size: font.char_width,
+                    ..default()
+                },
+                TextColor(theme.bookmark),
+                Transform::from_translation(translation),
+                BookmarkIndicator { line_index: line },
+                Name::new(format!("BookmarkIndicator_{}", line)),
+                Visibility::Visible,
+            ));
+        }
+    }
+
+    // Hide indicators left over from lines that lost their bookmark or scrolled away
+    for (_, _, mut visibility, _, _) in indicators {
+        *visibility = Visibility::Hidden;
+    }
+}
@@ -62,6 +62,46 @@ pub(crate) fn find_matching_bracket(
     None
 }
 
+/// How far back to look for an enclosing bracket, in characters
+const MAX_ENCLOSING_SCAN: usize = 20_000;
+
+/// Find the innermost bracket pair that encloses `pos`
+///
+/// Scans backwards for an opening bracket that isn't closed before `pos`,
+/// then forwards for its match. `cursor_bracket_pos` is the opening bracket.
+pub(crate) fn find_enclosing_bracket(
+    rope: &ropey::Rope,
+    pos: usize,
+    bracket_pairs: &[(char, char)],
+) -> Option<BracketMatch> {
+    let pos = pos.min(rope.len_chars());
+    let scan_start = pos.saturating_sub(MAX_ENCLOSING_SCAN);
+    // Closing brackets seen so far whose openers we haven't reached yet
+    let mut pending: Vec<char> = Vec::new();
+
+    for open_pos in (scan_start..pos).rev() {
+        let c = rope.char(open_pos);
+        if bracket_pairs.iter().any(|&(_, close)| close == c) {
+            pending.push(c);
+        } else if let Some(&(open, close)) = bracket_pairs.iter().find(|&&(open, _)| open == c) {
+            if pending.last() == Some(&close) {
+                pending.pop();
+            } else if pending.is_empty() {
+                if let Some(match_pos) = find_closing_bracket(rope, open_pos, open, close) {
+                    if match_pos >= pos {
+                        return Some(BracketMatch {
+                            cursor_bracket_pos: open_pos,
+                            matching_bracket_pos: match_pos,
+                        });
+                    }
+                }
+            }
+        }
+    }
+
+    None
+}
+
 /// Find matching closing bracket, handling nesting
 pub(crate) fn find_closing_bracket(
     rope: &ropey::Rope,
@@ -137,7 +177,12 @@ pub(crate) fn update_bracket_match(
         &state.rope,
         cursor_pos,
         &brackets.pairs,
-    );
+    )
+    .or_else(|| {
+        brackets.highlight_enclosing
+            .then(|| find_enclosing_bracket(&state.rope, cursor_pos, &brackets.pairs))
+            .flatten()
+    });
 }
 
 /// Render bracket match highlights
@@ -400,3 +445,24 @@ pub(crate) fn update_find_highlights(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_enclosing_braces_found_away_from_brackets() {
+        let rope = ropey::Rope::from_str("fn f() {\n    let x = (1);\n    x\n}\n");
+        let pairs = BracketSettings::default().pairs;
+        // Cursor on the `x` of the last statement, not next to any bracket
+        let pos = rope.line_to_char(2) + 4;
+
+        assert_eq!(find_matching_bracket(&rope, pos, &pairs), None);
+
+        let enclosing = find_enclosing_bracket(&rope, pos, &pairs).unwrap();
+        assert_eq!(rope.char(enclosing.cursor_bracket_pos), '{');
+        assert_eq!(enclosing.cursor_bracket_pos, 7);
+        assert_eq!(rope.char(enclosing.matching_bracket_pos), '}');
+        assert_eq!(enclosing.matching_bracket_pos, rope.len_chars() - 2);
+    }
+}
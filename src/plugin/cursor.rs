@@ -15,7 +15,7 @@ pub(crate) fn update_cursor(
     indentation: Res<IndentationSettings>,
     viewport: Res<ViewportDimensions>,
     fold_state: Res<FoldState>,
-    mut cursor_query: Query<(Entity, &EditorCursor, &mut Transform, &mut Visibility)>,
+    mut cursor_query: Query<(Entity, &EditorCursor, &mut Transform, &mut Visibility, &mut Sprite)>,
 ) {
     if !state.is_changed() {
         return;
@@ -25,31 +25,25 @@ pub(crate) fn update_cursor(
     let line_height = font.line_height;
     let cursor_height = line_height * cursor_settings.height_multiplier;
     let cursor_count = state.cursors.len();
+    // Dim the cursor while the buffer can't be edited
+    let cursor_color = if state.read_only {
+        theme.cursor.with_alpha(theme.cursor.alpha() * 0.4)
+    } else {
+        theme.cursor
+    };
 
     // Check if we're using soft line wrapping
     let use_wrapping = wrapping.enabled && state.display_map.wrap_width > 0;
 
     // Collect existing cursor entities by their index
     let mut cursor_entities: std::collections::HashMap<usize, Entity> = std::collections::HashMap::new();
-    for (entity, cursor, _, _) in cursor_query.iter() {
+    for (entity, cursor, _, _, _) in cursor_query.iter() {
         cursor_entities.insert(cursor.cursor_index, entity);
     }
 
     // Update or create cursor entities for each cursor
     for (idx, cursor) in state.cursors.iter().enumerate() {
-        let cursor_pos = cursor.position.min(state.rope.len_chars());
-        let line_index = state.rope.char_to_line(cursor_pos);
-        let line_start = state.rope.line_to_char(line_index);
-        let col_index = cursor_pos - line_start;
-
-        // Calculate display row and column based on wrapping and folding
-        let (display_row, display_col) = if use_wrapping {
-            state.display_map.buffer_to_display(line_index, col_index)
-        } else {
-            // Account for folded lines
-            let display_row = fold_state.actual_to_display_line(line_index);
-            (display_row, col_index)
-        };
+        let (display_row, visual_col) = cursor_display_position(&state, &fold_state, cursor.position, use_wrapping);
 
         // For wrapped continuation rows, add indent offset
         let extra_indent = if use_wrapping && wrapping.indent_wrapped_lines {
@@ -62,7 +56,7 @@ pub(crate) fn update_cursor(
             0.0
         };
 
-        let x_offset = viewport.text_area_left + extra_indent + (display_col as f32 * char_width);
+        let x_offset = viewport.text_area_left + extra_indent + (visual_col as f32 * char_width);
         let y_offset = viewport.text_area_top + state.scroll_offset + (display_row as f32 * line_height);
 
         // No horizontal scroll in wrapped mode
@@ -79,16 +73,17 @@ pub(crate) fn update_cursor(
 
         if let Some(&entity) = cursor_entities.get(&idx) {
             // Update existing cursor entity
-            if let Ok((_, _, mut transform, mut visibility)) = cursor_query.get_mut(entity) {
+            if let Ok((_, _, mut transform, mut visibility, mut sprite)) = cursor_query.get_mut(entity) {
                 transform.translation = Vec3::new(translation.x, translation.y, 1.0);
                 *visibility = Visibility::Visible;
+                sprite.color = cursor_color;
             }
             cursor_entities.remove(&idx);
         } else {
             // Spawn new cursor entity
             commands.spawn((
                 Sprite {
-                    color: theme.cursor,
+                    color: cursor_color,
                     custom_size: Some(Vec2::new(cursor_settings.width, cursor_height)),
                     ..default()
                 },
@@ -104,7 +99,7 @@ pub(crate) fn update_cursor(
     for (idx, entity) in cursor_entities {
         if idx < cursor_count {
             // This shouldn't happen, but hide just in case
-            if let Ok((_, _, _, mut visibility)) = cursor_query.get_mut(entity) {
+            if let Ok((_, _, _, mut visibility, _)) = cursor_query.get_mut(entity) {
                 *visibility = Visibility::Hidden;
             }
         } else {
@@ -114,6 +109,42 @@ pub(crate) fn update_cursor(
     }
 }
 
+/// Display row and visual column (in cells) at which a cursor at `pos` is drawn
+///
+/// At the end of a newline-terminated buffer this is column 0 of the empty
+/// line after the last line break, the row its line number is drawn on.
+pub(crate) fn cursor_display_position(
+    state: &CodeEditorState,
+    fold_state: &FoldState,
+    pos: usize,
+    use_wrapping: bool,
+) -> (usize, u32) {
+    let cursor_pos = pos.min(state.rope.len_chars());
+    let line_index = state.rope.char_to_line(cursor_pos);
+    let col_index = cursor_pos - state.rope.line_to_char(line_index);
+
+    // Calculate display row and column based on wrapping and folding
+    let (display_row, display_col) = if use_wrapping {
+        state.display_map.buffer_to_display(line_index, col_index)
+    } else {
+        (fold_state.actual_to_display_line(line_index), col_index)
+    };
+
+    // Tabs and wide characters before the cursor span several cells
+    let row_text: String = state.rope.line(line_index).chars().skip(col_index - display_col.min(col_index)).take(display_col).collect();
+    let visual_col = state.display_map.tab_map.expand_column(&row_text, display_col as u32);
+    (display_row, visual_col)
+}
+
+/// Display row of a buffer line's first row, as used by its line number
+pub(crate) fn line_display_row(state: &CodeEditorState, fold_state: &FoldState, line: usize, use_wrapping: bool) -> usize {
+    if use_wrapping {
+        state.display_map.buffer_to_display(line, 0).0
+    } else {
+        fold_state.actual_to_display_line(line)
+    }
+}
+
 /// Animate cursor blinking for all cursors
 pub(crate) fn animate_cursor(
     time: Res<Time>,
@@ -219,18 +250,7 @@ pub(crate) fn update_cursor_line_highlight(
             continue;
         }
 
-        // Calculate display row
-        let display_row = if use_wrapping {
-            state.display_map.buffer_to_display(line_index, 0).0
-        } else {
-            let mut visible_row = line_index;
-            for i in 0..line_index {
-                if fold_state.is_line_hidden(i) {
-                    visible_row = visible_row.saturating_sub(1);
-                }
-            }
-            visible_row
-        };
+        let display_row = line_display_row(&state, &fold_state, line_index, use_wrapping);
 
         let y_from_top = viewport.text_area_top + state.scroll_offset + (display_row as f32 * line_height);
 
@@ -301,8 +321,6 @@ pub(crate) fn update_cursor_line_highlight(
         let line_chars: Vec<char> = line.chars().collect();
 
         // Check if cursor is on a word character (also check char before cursor if cursor is at end)
-        let is_word_char = |c: char| c.is_alphanumeric() || c == '_';
-
         let on_word = if col < line_chars.len() && is_word_char(line_chars[col]) {
             true
         } else { col > 0 && col <= line_chars.len() && is_word_char(line_chars[col - 1]) };
@@ -335,8 +353,13 @@ pub(crate) fn update_cursor_line_highlight(
 
         // Only show word highlight if we found a word
         if word_end > word_start {
-            let word_width = (word_end - word_start) as f32 * char_width;
-            let word_x_left = viewport.text_area_left + (word_start as f32 * char_width);
+            // Measure in cells so wide characters in or before the word count double
+            let line_text: String = line_chars.iter().collect();
+            let tab_map = &state.display_map.tab_map;
+            let visual_start = tab_map.expand_column(&line_text, word_start as u32);
+            let visual_end = tab_map.expand_column(&line_text, word_end as u32);
+            let word_width = (visual_end - visual_start) as f32 * char_width;
+            let word_x_left = viewport.text_area_left + (visual_start as f32 * char_width);
 
             let word_center_x = -(viewport.width as f32) / 2.0 + word_x_left + word_width / 2.0 + viewport.offset_x - state.horizontal_scroll_offset;
             let word_center_y = (viewport.height as f32) / 2.0 - y_from_top;
@@ -384,3 +407,144 @@ pub(crate) fn update_cursor_line_highlight(
     }
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugin::{cursor_rendering_enabled, BuiltinRendering};
+    use crate::settings::EditorSettingsBuilder;
+
+    fn cursor_app(rendering: BuiltinRendering) -> App {
+        let mut app = App::new();
+        EditorSettingsBuilder::default().build().insert_into(&mut app);
+        app.insert_resource(CodeEditorState::new("hello\nworld"));
+        app.insert_resource(ViewportDimensions::default());
+        app.insert_resource(FoldState::default());
+        app.insert_resource(rendering);
+        app.add_systems(Update, update_cursor.run_if(cursor_rendering_enabled));
+        app
+    }
+
+    fn cursor_entity_count(app: &mut App) -> usize {
+        app.world_mut().query::<&EditorCursor>().iter(app.world()).count()
+    }
+
+    #[test]
+    fn test_cursor_x_uses_measured_char_width() {
+        use crate::gpu_text::GlyphAtlas;
+        use crate::plugin::measure_char_width;
+
+        let mut app = App::new();
+        EditorSettingsBuilder::default()
+            .auto_measure_char_width(true)
+            .build()
+            .insert_into(&mut app);
+        let mut state = CodeEditorState::new("hello world");
+        state.cursor_pos = 4;
+        app.insert_resource(state);
+        app.insert_resource(ViewportDimensions::default());
+        app.insert_resource(FoldState::default());
+
+        let mut images = Assets::<Image>::default();
+        let mut atlas = GlyphAtlas::new(&mut images);
+        let size = app.world().resource::<FontSettings>().size;
+        let advance = atlas.measure_advance('M', size).expect("reference glyph");
+        app.insert_resource(atlas);
+        app.add_systems(Update, (measure_char_width, update_cursor).chain());
+        app.update();
+
+        assert_eq!(app.world().resource::<FontSettings>().char_width, advance);
+
+        let viewport = ViewportDimensions::default();
+        let x = app.world_mut()
+            .query_filtered::<&Transform, With<EditorCursor>>()
+            .single(app.world())
+            .unwrap()
+            .translation
+            .x;
+        let expected = -(viewport.width as f32) / 2.0 + viewport.text_area_left + 4.0 * advance;
+        assert!((x - expected).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_disabled_cursor_rendering_spawns_no_entities() {
+        let mut app = cursor_app(BuiltinRendering { cursor: false, ..default() });
+        app.update();
+        assert_eq!(cursor_entity_count(&mut app), 0);
+
+        // Cursor state still updates and stays queryable
+        app.world_mut().resource_mut::<CodeEditorState>().move_cursor(3);
+        app.update();
+        assert_eq!(app.world().resource::<CodeEditorState>().cursor_pos, 3);
+        assert_eq!(cursor_entity_count(&mut app), 0);
+
+        let mut enabled = cursor_app(BuiltinRendering::default());
+        enabled.update();
+        assert_eq!(cursor_entity_count(&mut enabled), 1);
+    }
+
+    #[test]
+    fn test_cursor_at_end_of_newline_terminated_file_is_on_phantom_line() {
+        let mut app = cursor_app(BuiltinRendering::default());
+        {
+            let mut state = app.world_mut().resource_mut::<CodeEditorState>();
+            state.set_text("abc\ndef\n");
+            state.cursor_pos = 8;
+            state.sync_cursors_from_primary();
+        }
+        app.update();
+
+        let viewport = ViewportDimensions::default();
+        let line_height = app.world().resource::<FontSettings>().line_height;
+        let expected = to_bevy_coords_left_aligned(
+            viewport.text_area_left,
+            viewport.text_area_top + 2.0 * line_height,
+            viewport.width as f32,
+            viewport.height as f32,
+            viewport.offset_x,
+            0.0,
+        );
+        let translation = app.world_mut()
+            .query_filtered::<&Transform, With<EditorCursor>>()
+            .single(app.world())
+            .unwrap()
+            .translation;
+        assert!((translation.x - expected.x).abs() < 1e-3);
+        assert!((translation.y - expected.y).abs() < 1e-3);
+
+        // Same row and column when wrapping, including a trailing newline typed
+        // after the display map was last built
+        let fold_state = FoldState::default();
+        let mut state = CodeEditorState::new("abc\ndef\n");
+        state.rewrap(10);
+        assert_eq!(cursor_display_position(&state, &fold_state, 8, true), (2, 0));
+        assert_eq!(line_display_row(&state, &fold_state, 2, true), 2);
+
+        let mut state = CodeEditorState::new("abc\ndef");
+        state.rewrap(10);
+        state.insert_text_at(7, "\n");
+        assert!(state.has_phantom_line());
+        assert_eq!(cursor_display_position(&state, &fold_state, 8, true), (2, 0));
+    }
+
+    #[test]
+    fn test_cursor_x_counts_wide_characters_as_two_cells() {
+        let mut app = cursor_app(BuiltinRendering::default());
+        let mut state = CodeEditorState::new("中文ab\nx");
+        // Between 'a' and 'b': two wide characters and one ASCII before it
+        state.cursor_pos = 3;
+        app.insert_resource(state);
+        app.update();
+
+        let char_width = app.world().resource::<FontSettings>().char_width;
+        let viewport = ViewportDimensions::default();
+        let x = app.world_mut()
+            .query_filtered::<&Transform, With<EditorCursor>>()
+            .single(app.world())
+            .unwrap()
+            .translation
+            .x;
+        let expected = -(viewport.width as f32) / 2.0 + viewport.text_area_left + 5.0 * char_width;
+        assert!((x - expected).abs() < 1e-3);
+    }
+}
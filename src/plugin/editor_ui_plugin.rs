@@ -9,25 +9,30 @@
 //! - Indent guides
 //! - Fold indicators
 //! - Minimap
+//! - Overview ruler
+//! - Status overlay (opt-in via `UiSettings::show_status_overlay`)
 //!
 //! This plugin is optional - users can implement their own UI by
 //! querying the editor state directly.
 
 use bevy::prelude::*;
 
-use crate::types::{LineNumbers, EditorCursor, Separator, ViewportDimensions, CodeEditorState};
+use crate::types::{LineNumbers, EditorCursor, Separator, ViewportDimensions, CodeEditorState, GutterColumns, GutterColumnRect};
 use crate::settings::*;
 use super::{
-    update_line_numbers, update_fold_indicators,
-    update_selection_highlight, update_cursor_line_highlight,
+    update_line_numbers, update_fold_indicators, update_bookmark_indicators,
+    update_selection_highlight, update_selection_whitespace, update_cursor_line_highlight, update_wrap_guide, update_column_rulers,
     update_indent_guides, update_bracket_match, update_bracket_highlight,
     update_find_highlights, update_minimap_hover, handle_minimap_mouse,
     update_minimap, update_minimap_find_highlights,
+    update_overview_ruler, handle_overview_ruler_click, update_status_overlay_text,
     update_cursor, animate_cursor,
     to_bevy_coords_dynamic, to_bevy_coords_left_aligned,
-    EditorSetupSet,
+    EditorSetupSet, BuiltinRendering,
+    cursor_rendering_enabled, selection_rendering_enabled, minimap_rendering_enabled,
+    line_number_rendering_enabled, indent_guide_rendering_enabled,
     update_gpu_text_display,
-    scrollbar::update_editor_scrollbar,
+    scrollbar::{update_editor_scrollbar, update_horizontal_scrollbar, handle_horizontal_scrollbar_mouse},
 };
 
 /// Editor UI plugin providing default rendering for editor visual elements
@@ -66,6 +71,10 @@ impl EditorUiPlugin {
 
 impl Plugin for EditorUiPlugin {
     fn build(&self, app: &mut App) {
+        // Normally inserted by CodeEditorPlugin; default to rendering everything
+        app.init_resource::<BuiltinRendering>();
+        app.init_resource::<GutterColumns>();
+
         // Startup: compute layout and spawn UI entities
         app.add_systems(Startup, (
             compute_viewport_layout,
@@ -76,12 +85,13 @@ impl Plugin for EditorUiPlugin {
         app.add_systems(Update, compute_viewport_layout.run_if(resource_changed::<UiSettings>));
 
         // All UI rendering systems go in RenderingSet
-        // Line numbers and fold indicators (run after text display)
+        // Line numbers, fold and bookmark indicators (run after text display)
         app.add_systems(
             Update,
             (
-                update_line_numbers,
+                update_line_numbers.run_if(line_number_rendering_enabled),
                 update_fold_indicators,
+                update_bookmark_indicators,
             )
                 .chain()
                 .after(update_gpu_text_display)
@@ -92,9 +102,12 @@ impl Plugin for EditorUiPlugin {
         app.add_systems(
             Update,
             (
-                update_selection_highlight,
+                update_selection_highlight.run_if(selection_rendering_enabled),
+                update_selection_whitespace.run_if(selection_rendering_enabled),
                 update_cursor_line_highlight,
-                update_indent_guides,
+                update_indent_guides.run_if(indent_guide_rendering_enabled),
+                update_wrap_guide,
+                update_column_rulers,
                 update_bracket_match,
                 update_bracket_highlight,
                 update_find_highlights,
@@ -112,6 +125,7 @@ impl Plugin for EditorUiPlugin {
                 handle_minimap_mouse,
             )
                 .chain()
+                .run_if(minimap_rendering_enabled)
                 .in_set(super::InputSet),
         );
 
@@ -123,10 +137,23 @@ impl Plugin for EditorUiPlugin {
                 update_minimap_find_highlights,
             )
                 .chain()
+                .run_if(minimap_rendering_enabled)
+                .after(update_find_highlights)
+                .in_set(super::RenderingSet),
+        );
+
+        // Overview ruler: click-to-jump in InputSet, marks in RenderingSet
+        app.add_systems(Update, handle_overview_ruler_click.in_set(super::InputSet));
+        app.add_systems(
+            Update,
+            update_overview_ruler
                 .after(update_find_highlights)
                 .in_set(super::RenderingSet),
         );
 
+        // Status overlay text in RenderingSet
+        app.add_systems(Update, update_status_overlay_text.in_set(super::RenderingSet));
+
         // Editor scrollbar config update goes in ApplyStateSet
         app.add_systems(
             Update,
@@ -137,6 +164,10 @@ impl Plugin for EditorUiPlugin {
                 .in_set(super::ApplyStateSet),
         );
 
+        // Horizontal scrollbar: drag/click in InputSet, visuals in RenderingSet
+        app.add_systems(Update, handle_horizontal_scrollbar_mouse.in_set(super::InputSet));
+        app.add_systems(Update, update_horizontal_scrollbar.in_set(super::RenderingSet));
+
         // Cursor systems in RenderingSet
         app.add_systems(
             Update,
@@ -145,6 +176,7 @@ impl Plugin for EditorUiPlugin {
                 animate_cursor,
             )
                 .chain()
+                .run_if(cursor_rendering_enabled)
                 .after(update_minimap_find_highlights)
                 .in_set(super::RenderingSet),
         );
@@ -154,17 +186,18 @@ impl Plugin for EditorUiPlugin {
 /// Compute ViewportDimensions layout fields based on UI settings
 fn compute_viewport_layout(
     mut viewport: ResMut<ViewportDimensions>,
+    mut gutter: ResMut<GutterColumns>,
     ui: Res<UiSettings>,
     font: Res<FontSettings>,
 ) {
-    // Compute gutter width based on line number display
-    viewport.gutter_width = if ui.show_line_numbers {
-        ui.gutter_padding_left + ui.gutter_padding_right
-            // Reserve space for at least 4 digits (9999 lines)
-            + (font.char_width * 4.0)
+    // Lay out the gutter sub-columns; the gutter is hidden with line numbers
+    let (columns, gutter_width) = if ui.show_line_numbers {
+        layout_gutter_columns(&ui, font.char_width)
     } else {
-        0.0
+        (Vec::new(), 0.0)
     };
+    gutter.columns = columns;
+    viewport.gutter_width = gutter_width;
 
     // Compute separator position (right edge of gutter)
     viewport.separator_x = viewport.gutter_width;
@@ -176,6 +209,25 @@ fn compute_viewport_layout(
     viewport.text_area_top = ui.margin_top;
 }
 
+/// Place gutter columns left to right, returning them with the total gutter width
+fn layout_gutter_columns(ui: &UiSettings, char_width: f32) -> (Vec<GutterColumnRect>, f32) {
+    let layout = &ui.gutter_layout;
+    let mut x = ui.gutter_padding_left;
+    let mut columns = Vec::with_capacity(layout.columns.len());
+
+    for (index, column) in layout.columns.iter().enumerate() {
+        if index > 0 {
+            x += layout.spacing;
+        }
+        // Reserve space for at least 4 digits (9999 lines) by default
+        let width = column.width.unwrap_or(char_width * 4.0);
+        columns.push(GutterColumnRect { kind: column.kind.clone(), x, width });
+        x += width;
+    }
+
+    (columns, x + ui.gutter_padding_right)
+}
+
 /// Setup UI entities (line numbers, cursor, separator)
 fn setup_editor_ui(
     mut commands: Commands,
@@ -185,6 +237,7 @@ fn setup_editor_ui(
     cursor_settings: Res<CursorSettings>,
     ui: Res<UiSettings>,
     viewport: Res<ViewportDimensions>,
+    rendering: Res<BuiltinRendering>,
 ) {
     // Load font
     let font_handle: Handle<Font> = asset_server.load(&font.family);
@@ -194,24 +247,26 @@ fn setup_editor_ui(
     let viewport_height = viewport.height as f32;
 
     // Spawn line numbers
-    commands.spawn((
-        Text2d::new("1"),
-        TextFont {
-            font: font_handle.clone(),
-            font_size: font.size,
-            ..default()
-        },
-        TextColor(theme.line_numbers),
-        Transform::from_translation(to_bevy_coords_dynamic(
-            viewport.gutter_width / 2.0,
-            viewport.text_area_top,
-            viewport_width,
-            viewport_height,
-            viewport.offset_x,
-        )),
-        LineNumbers,
-        Name::new("LineNumbers"),
-    ));
+    if rendering.line_numbers {
+        commands.spawn((
+            Text2d::new("1"),
+            TextFont {
+                font: font_handle.clone(),
+                font_size: font.size,
+                ..default()
+            },
+            TextColor(theme.line_numbers),
+            Transform::from_translation(to_bevy_coords_dynamic(
+                viewport.gutter_width / 2.0,
+                viewport.text_area_top,
+                viewport_width,
+                viewport_height,
+                viewport.offset_x,
+            )),
+            LineNumbers,
+            Name::new("LineNumbers"),
+        ));
+    }
 
     // Spawn separator line (only if enabled)
     if ui.show_separator {
@@ -235,22 +290,65 @@ fn setup_editor_ui(
     }
 
     // Spawn primary cursor (cursor_index = 0)
-    let cursor_height = font.line_height * cursor_settings.height_multiplier;
-    commands.spawn((
-        Sprite {
-            color: theme.cursor,
-            custom_size: Some(Vec2::new(cursor_settings.width, cursor_height)),
-            ..default()
-        },
-        Transform::from_translation(to_bevy_coords_dynamic(
-            viewport.text_area_left,
-            viewport.text_area_top,
-            viewport_width,
-            viewport_height,
-            viewport.offset_x,
-        )),
-        Visibility::Hidden,
-        EditorCursor { cursor_index: 0 },
-        Name::new("EditorCursor_0"),
-    ));
+    if rendering.cursor {
+        let cursor_height = font.line_height * cursor_settings.height_multiplier;
+        commands.spawn((
+            Sprite {
+                color: theme.cursor,
+                custom_size: Some(Vec2::new(cursor_settings.width, cursor_height)),
+                ..default()
+            },
+            Transform::from_translation(to_bevy_coords_dynamic(
+                viewport.text_area_left,
+                viewport.text_area_top,
+                viewport_width,
+                viewport_height,
+                viewport.offset_x,
+            )),
+            Visibility::Hidden,
+            EditorCursor { cursor_index: 0 },
+            Name::new("EditorCursor_0"),
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_git_column_shifts_fold_column_and_separator() {
+        let mut app = App::new();
+        EditorSettingsBuilder::default().build().insert_into(&mut app);
+        app.insert_resource(ViewportDimensions::default());
+        app.init_resource::<GutterColumns>();
+        app.add_systems(Update, compute_viewport_layout);
+        app.update();
+
+        let fold_x = |app: &App| {
+            app.world()
+                .resource::<GutterColumns>()
+                .column(&GutterColumnKind::Fold)
+                .map(|column| column.x)
+                .unwrap()
+        };
+        let fold_before = fold_x(&app);
+        let viewport_before = *app.world().resource::<ViewportDimensions>();
+
+        // Enable a 4px git column between line numbers and fold markers
+        let spacing = {
+            let mut ui = app.world_mut().resource_mut::<UiSettings>();
+            ui.gutter_layout.columns.insert(1, GutterColumn::new(GutterColumnKind::Git, 4.0));
+            ui.gutter_layout.spacing
+        };
+        app.update();
+
+        let shift = 4.0 + spacing;
+        let viewport = *app.world().resource::<ViewportDimensions>();
+        let git = app.world().resource::<GutterColumns>().column(&GutterColumnKind::Git).cloned().unwrap();
+        assert_eq!(git.x, fold_before);
+        assert_eq!(fold_x(&app), fold_before + shift);
+        assert_eq!(viewport.separator_x, viewport_before.separator_x + shift);
+        assert_eq!(viewport.text_area_left, viewport_before.text_area_left + shift);
+    }
 }
@@ -0,0 +1,180 @@
+//! Loading a file into the editor at startup
+//!
+//! `CodeEditorPlugin::with_file` reads the file on the async compute pool so
+//! startup never blocks on disk I/O. Once loaded, the contents replace the
+//! (empty) initial buffer and indentation follows the detected language.
+//! Highlighting grammars are supplied by the host; `EditorFile::language`
+//! tells it which one to install.
+
+use std::path::{Path, PathBuf};
+
+use bevy::prelude::*;
+use bevy::tasks::{AsyncComputeTaskPool, Task};
+use crate::settings::*;
+use crate::types::*;
+
+/// The file the editor was opened with
+#[derive(Resource, Clone, Debug)]
+pub struct EditorFile {
+    /// Path passed to `CodeEditorPlugin::with_file`
+    pub path: PathBuf,
+    /// Language id detected from the extension (LSP naming, e.g. `"rust"`)
+    pub language: Option<&'static str>,
+    /// Whether the contents have been loaded into the buffer
+    pub loaded: bool,
+}
+
+impl EditorFile {
+    /// Track a file to be loaded, detecting its language from the path
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        let language = language_from_path(&path);
+        Self { path, language, loaded: false }
+    }
+}
+
+/// In-flight read of the editor file
+#[derive(Resource)]
+pub(crate) struct PendingFileLoad(Task<std::io::Result<String>>);
+
+/// Detect a language id from a file's extension or name
+pub fn language_from_path(path: &Path) -> Option<&'static str> {
+    let file_name = path.file_name()?.to_str()?;
+    match file_name {
+        "Makefile" | "makefile" | "GNUmakefile" => return Some("makefile"),
+        "Dockerfile" => return Some("dockerfile"),
+        _ => {}
+    }
+
+    let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+    let language = match extension.as_str() {
+        "rs" => "rust",
+        "py" | "pyi" => "python",
+        "js" | "mjs" | "cjs" => "javascript",
+        "jsx" => "javascriptreact",
+        "ts" | "mts" | "cts" => "typescript",
+        "tsx" => "typescriptreact",
+        "json" => "json",
+        "toml" => "toml",
+        "yaml" | "yml" => "yaml",
+        "md" | "markdown" => "markdown",
+        "go" => "go",
+        "c" | "h" => "c",
+        "cpp" | "cc" | "cxx" | "hpp" | "hh" | "hxx" => "cpp",
+        "java" => "java",
+        "lua" => "lua",
+        "rb" => "ruby",
+        "sh" | "bash" => "shellscript",
+        "html" | "htm" => "html",
+        "css" => "css",
+        "wgsl" => "wgsl",
+        "mk" => "makefile",
+        _ => return None,
+    };
+    Some(language)
+}
+
+/// Conventional indentation for a language, or `None` to keep the configured one
+pub fn language_indentation(language: &str) -> Option<IndentationSettings> {
+    let (use_spaces, width) = match language {
+        "go" | "makefile" => (false, 4),
+        "javascript" | "javascriptreact" | "typescript" | "typescriptreact" | "json" | "yaml"
+        | "ruby" | "lua" | "html" | "css" => (true, 2),
+        "rust" | "python" | "c" | "cpp" | "java" | "wgsl" => (true, 4),
+        _ => return None,
+    };
+    Some(IndentationSettings {
+        use_spaces,
+        tab_width: width,
+        indent_size: width,
+        ..default()
+    })
+}
+
+/// Start reading the editor file and apply the detected indentation
+pub(crate) fn start_file_load(
+    mut commands: Commands,
+    file: Res<EditorFile>,
+    mut indentation: ResMut<IndentationSettings>,
+) {
+    if let Some(language_indent) = file.language.and_then(language_indentation) {
+        *indentation = IndentationSettings {
+            auto_indent: indentation.auto_indent,
+            backspace_dedents: indentation.backspace_dedents,
+            tab_order: indentation.tab_order.clone(),
+            ..language_indent
+        };
+    }
+
+    let path = file.path.clone();
+    let task = AsyncComputeTaskPool::get().spawn(async move { std::fs::read_to_string(path) });
+    commands.insert_resource(PendingFileLoad(task));
+}
+
+/// Move the loaded contents into the buffer, or report the failure
+pub(crate) fn poll_file_load(
+    mut commands: Commands,
+    pending: Option<ResMut<PendingFileLoad>>,
+    mut file: ResMut<EditorFile>,
+    mut state: ResMut<CodeEditorState>,
+    mut failures: MessageWriter<FileLoadFailed>,
+) {
+    let Some(mut pending) = pending else {
+        return;
+    };
+    let Some(result) = futures_lite::future::block_on(futures_lite::future::poll_once(&mut pending.0)) else {
+        return;
+    };
+    commands.remove_resource::<PendingFileLoad>();
+
+    match result {
+        Ok(text) => {
+            state.set_text(&text);
+            state.pending_update = true;
+            file.loaded = true;
+        }
+        Err(error) => {
+            warn!("Failed to load {}: {}", file.path.display(), error);
+            failures.write(FileLoadFailed {
+                path: file.path.clone(),
+                error: error.to_string(),
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::tasks::TaskPool;
+
+    #[test]
+    fn test_with_file_loads_contents_and_language() {
+        let path = std::env::temp_dir().join(format!("bevy_code_editor_{}_main.rs", std::process::id()));
+        std::fs::write(&path, "fn main() {\n    println!(\"hi\");\n}\n").unwrap();
+        AsyncComputeTaskPool::get_or_init(TaskPool::new);
+
+        let mut app = App::new();
+        EditorSettingsBuilder::default().build().insert_into(&mut app);
+        app.insert_resource(CodeEditorState::default());
+        app.insert_resource(EditorFile::new(&path));
+        app.add_message::<FileLoadFailed>();
+        app.add_systems(Startup, start_file_load);
+        app.add_systems(Update, poll_file_load);
+
+        for _ in 0..1000 {
+            app.update();
+            if app.world().resource::<EditorFile>().loaded {
+                break;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(1));
+        }
+        std::fs::remove_file(&path).ok();
+
+        let file = app.world().resource::<EditorFile>();
+        assert!(file.loaded);
+        assert_eq!(file.language, Some("rust"));
+        let state = app.world().resource::<CodeEditorState>();
+        assert_eq!(state.rope.to_string(), "fn main() {\n    println!(\"hi\");\n}\n");
+    }
+}
@@ -37,15 +37,8 @@ pub(crate) fn detect_foldable_regions(
     // Walk the tree and find foldable nodes
     collect_foldable_regions(&root, text_bytes, &state.rope, &mut regions, false);
 
-    // Preserve fold state for existing regions
-    let old_regions = std::mem::take(&mut fold_state.regions);
-    for mut region in regions {
-        // Check if this region was previously folded
-        if let Some(old) = old_regions.iter().find(|r| r.start_line == region.start_line && r.end_line == region.end_line) {
-            region.is_folded = old.is_folded;
-        }
-        fold_state.regions.push(region);
-    }
+    // Preserve fold state for existing regions and keep manual folds
+    fold_state.merge_detected_regions(regions);
 
     fold_state.enabled = true;
 }
@@ -171,6 +164,7 @@ pub(crate) fn node_to_fold_region(
             is_folded: false,
             kind,
             indent_level,
+            manual: false,
         })
     })
 }
@@ -224,24 +218,34 @@ pub(crate) fn detect_foldable_regions(
                         is_folded: false,
                         kind: FoldKind::Block,
                         indent_level: start_indent,
+                        manual: false,
                     });
                 }
             }
         }
     }
 
-    // Preserve fold state for existing regions
-    let old_regions = std::mem::take(&mut fold_state.regions);
-    for mut region in regions {
-        if let Some(old) = old_regions.iter().find(|r| r.start_line == region.start_line && r.end_line == region.end_line) {
-            region.is_folded = old.is_folded;
-        }
-        fold_state.regions.push(region);
-    }
+    // Preserve fold state for existing regions and keep manual folds
+    fold_state.merge_detected_regions(regions);
 
     fold_state.enabled = true;
 }
 
+/// Move cursors off lines hidden by folds changed outside an editor action,
+/// e.g. by `FoldState::import`
+pub(crate) fn move_cursors_out_of_hidden_lines(
+    mut state: ResMut<CodeEditorState>,
+    fold_state: Res<FoldState>,
+) {
+    if !fold_state.is_changed() {
+        return;
+    }
+    let hidden = |pos: usize| fold_state.is_line_hidden(state.rope.char_to_line(pos.min(state.rope.len_chars())));
+    if hidden(state.cursor_pos) || state.cursors.iter().any(|c| hidden(c.position)) {
+        crate::input::move_cursors_out_of_folds(&mut state, &fold_state);
+    }
+}
+
 /// Update fold gutter indicators (arrows/chevrons)
 pub(crate) fn update_fold_indicators(
     mut commands: Commands,
@@ -250,11 +254,13 @@ pub(crate) fn update_fold_indicators(
     theme: Res<ThemeSettings>,
     ui: Res<UiSettings>,
     viewport: Res<ViewportDimensions>,
+    gutter: Res<GutterColumns>,
     fold_state: Res<FoldState>,
     mut indicator_query: Query<(Entity, &FoldIndicator, &mut Transform, &mut Text2d, &mut Visibility)>,
 ) {
-    // Hide all if folding is disabled
-    if !fold_state.enabled || !ui.show_line_numbers {
+    // Hide all if folding is disabled or the gutter has no fold column
+    let fold_column = gutter.column(&GutterColumnKind::Fold);
+    if !fold_state.enabled || !ui.show_line_numbers || fold_column.is_none() {
         for (_, _, _, _, mut visibility) in indicator_query.iter_mut() {
             *visibility = Visibility::Hidden;
         }
@@ -288,7 +294,7 @@ pub(crate) fn update_fold_indicators(
     // We need to count how many lines are hidden before each fold region
     let count_hidden_lines_before = |line: usize| -> usize {
         fold_state.regions.iter()
-            .filter(|r| r.is_folded && r.start_line < line)
+            .filter(|r| r.is_folded && r.start_line < line && !fold_state.is_line_hidden(r.start_line))
             .map(|r| r.end_line.saturating_sub(r.start_line))
             .sum()
     };
@@ -307,9 +313,8 @@ pub(crate) fn update_fold_indicators(
         let hidden_above = count_hidden_lines_before(line_idx);
         let display_line = line_idx.saturating_sub(hidden_above);
 
-        // Position in fold gutter (between line numbers and separator)
-        // In VSCode style, this is a narrow gutter just before the separator
-        let x_offset = viewport.separator_x - 12.0; // Just before the separator
+        // Position in the fold column of the gutter
+        let x_offset = fold_column.map_or(0.0, |column| column.center_x());
         let y_offset = viewport.text_area_top + state.scroll_offset + (display_line as f32 * line_height);
 
         let translation = to_bevy_coords_left_aligned(
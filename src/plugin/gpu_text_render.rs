@@ -4,9 +4,66 @@ use bevy::prelude::*;
 use bevy::tasks::{AsyncComputeTaskPool, Task};
 use crate::settings::*;
 use crate::types::*;
-use crate::gpu_text::{GlyphAtlas, TextRenderState};
+use crate::gpu_text::{GlyphAtlas, GlyphRasterizer, TextRenderState};
 use super::{SyntaxResource, HighlightCache};
 
+/// Glyph whose advance defines the monospace cell width
+const CHAR_WIDTH_REFERENCE_GLYPH: char = 'M';
+
+/// Set `FontSettings::char_width` from the atlas' measured advance
+///
+/// Runs whenever the font family or size changes while auto-measurement is enabled.
+pub(crate) fn measure_char_width(
+    mut font: ResMut<FontSettings>,
+    atlas: Option<ResMut<GlyphAtlas>>,
+    mut measured_for: Local<Option<(String, u32)>>,
+) {
+    if !font.auto_measure_char_width {
+        *measured_for = None;
+        return;
+    }
+    let Some(mut atlas) = atlas else {
+        return;
+    };
+
+    let key = (font.family.clone(), font.size.to_bits());
+    if measured_for.as_ref() == Some(&key) {
+        return;
+    }
+
+    if let Some(advance) = atlas.measure_advance(CHAR_WIDTH_REFERENCE_GLYPH, font.size) {
+        if advance > 0.0 && (font.char_width - advance).abs() > f32::EPSILON {
+            font.char_width = advance;
+        }
+        *measured_for = Some(key);
+    }
+}
+
+/// Refresh `EditorMetrics` when the font, zoom or viewport layout changes
+pub(crate) fn update_editor_metrics(
+    font: Res<FontSettings>,
+    viewport: Res<ViewportDimensions>,
+    atlas: Option<ResMut<GlyphAtlas>>,
+    mut metrics: ResMut<EditorMetrics>,
+) {
+    if !font.is_changed() && !viewport.is_changed() && !metrics.is_added() {
+        return;
+    }
+
+    let (ascent, descent) = match atlas {
+        Some(mut atlas) => atlas.font_metrics(font.size),
+        None => GlyphRasterizer::fallback_metrics(font.size),
+    };
+    metrics.set_if_neq(EditorMetrics {
+        line_height: font.line_height,
+        char_width: font.char_width,
+        ascent,
+        descent,
+        gutter_width: viewport.gutter_width,
+        code_area_left: viewport.text_area_left,
+    });
+}
+
 /// Marker component for the main GPU text mesh (DEPRECATED - being replaced with per-line meshes)
 #[derive(Component)]
 pub struct GpuTextMesh;
@@ -21,6 +78,8 @@ pub struct LineMeshEntity {
     pub display_row: usize,
     /// Tree version when this line's highlights were last updated
     pub tree_version: u64,
+    /// Glyph atlas generation the mesh's UVs were taken from
+    pub atlas_generation: u64,
 }
 
 /// Resource to track active line mesh entities
@@ -191,7 +250,7 @@ pub(crate) fn update_gpu_text_display(
 ) {
     use bevy::mesh::{Mesh2d, Indices, PrimitiveTopology};
     use bevy::asset::RenderAssetUsages;
-    use crate::gpu_text::{GlyphKey, GlyphRasterizer};
+    use crate::gpu_text::GlyphKey;
 
     // Check if we need to update due to tree-sitter parse completion
     #[cfg(feature = "tree-sitter")]
@@ -199,9 +258,11 @@ pub(crate) fn update_gpu_text_display(
     #[cfg(not(feature = "tree-sitter"))]
     let tree_updated = false;
 
-    if !state.needs_update && !tree_updated {
+    // Glyphs deferred by last frame's rasterization budget also need a redraw
+    if !state.needs_update && !tree_updated && !atlas.has_pending() {
         return;
     }
+    atlas.begin_frame(performance.max_glyphs_per_frame);
 
     // NOTE: Tree-sitter update happens in separate async system
     // This allows text to render immediately without waiting for parsing
@@ -210,14 +271,17 @@ pub(crate) fn update_gpu_text_display(
     let line_height = font.line_height;
     let char_width = font.char_width;
 
-    // Calculate visible range
-    let buffer = line_height * performance.viewport_buffer_lines as f32;
+    // Calculate visible range (extended ahead of fast scrolls)
     let total_buffer_lines = state.line_count();
 
-    let scroll_dist = state.scroll_offset.abs();
-    let start_pixels = scroll_dist - viewport.text_area_top - buffer;
-    let first_visible_display_row = (start_pixels / line_height).floor().max(0.0) as usize;
-    let visible_count = ((viewport.height as f32 + buffer * 2.0) / line_height).ceil() as usize;
+    let (first_visible_display_row, visible_count) = render_row_range(
+        state.scroll_offset,
+        state.scroll_velocity,
+        viewport.text_area_top,
+        viewport.height as f32,
+        line_height,
+        &performance,
+    );
     let last_visible_display_row = first_visible_display_row + visible_count;
 
     // Collect all visible glyph quads
@@ -317,6 +381,8 @@ pub(crate) fn update_gpu_text_display(
 
         // Build glyph quads for this line
         let mut x = viewport.text_area_left - state.horizontal_scroll_offset;
+        let line_x = x;
+        let tab_size = state.display_map.tab_map.tab_size();
 
         // Process highlighted segments if available
         if let Some(segments) = segments_ref {
@@ -330,13 +396,13 @@ pub(crate) fn update_gpu_text_display(
                     }
 
                     if ch == '\t' {
-                        x += char_width * 4.0;
+                        x = next_tab_stop_x(x, line_x, char_width, tab_size);
                         continue;
                     }
 
-                    let key = GlyphKey::new(ch, font_size);
-                    if let Some(info) = atlas.get_or_insert(key, || {
-                        GlyphRasterizer::rasterize(ch, font_size)
+                    let key = GlyphKey::styled(ch, font_size, seg.style);
+                    if let Some(info) = atlas.get_or_queue(key, || {
+                        GlyphRasterizer::rasterize(ch, font_size, char_width, seg.style)
                     }) {
                         // Convert to Bevy coordinates (center origin, Y up)
                         let screen_x = x + info.offset.x;
@@ -377,9 +443,9 @@ pub(crate) fn update_gpu_text_display(
                         indices.push(vertex_count + 3);
 
                         vertex_count += 4;
-                        x += info.advance;
+                        x += cell_advance(ch, char_width);
                     } else {
-                        x += char_width;
+                        x += cell_advance(ch, char_width);
                     }
                 }
             }
@@ -395,13 +461,13 @@ pub(crate) fn update_gpu_text_display(
                 }
 
                 if ch == '\t' {
-                    x += char_width * 4.0;
+                    x = next_tab_stop_x(x, line_x, char_width, tab_size);
                     continue;
                 }
 
                 let key = GlyphKey::new(ch, font_size);
-                if let Some(info) = atlas.get_or_insert(key, || {
-                    GlyphRasterizer::rasterize(ch, font_size)
+                if let Some(info) = atlas.get_or_queue(key, || {
+                    GlyphRasterizer::rasterize(ch, font_size, char_width, FontStyle::Regular)
                 }) {
                     // Convert to Bevy coordinates (center origin, Y up)
                     let screen_x = x + info.offset.x;
@@ -442,9 +508,9 @@ pub(crate) fn update_gpu_text_display(
                     indices.push(vertex_count + 3);
 
                     vertex_count += 4;
-                    x += info.advance;
+                    x += cell_advance(ch, char_width);
                 } else {
-                    x += char_width;
+                    x += cell_advance(ch, char_width);
                 }
             }
         }
@@ -517,13 +583,98 @@ pub(crate) fn update_gpu_text_display(
 
 // ==================== PER-LINE MESH RENDERING (NEW SYSTEM) ====================
 
+/// Compute the display rows that should have line entities, as `(first_row, row_count)`
+///
+/// Covers the viewport plus `viewport_buffer_lines` on each side. When the scroll
+/// velocity exceeds `predictive_spawn_threshold`, the range is extended further in
+/// the scroll direction so lines revealed over the next frames already have entities
+/// instead of flashing blank during a fling.
+pub(crate) fn render_row_range(
+    scroll_offset: f32,
+    scroll_velocity: f32,
+    text_area_top: f32,
+    viewport_height: f32,
+    line_height: f32,
+    performance: &PerformanceSettings,
+) -> (usize, usize) {
+    let buffer = line_height * performance.viewport_buffer_lines as f32;
+
+    let start_pixels = scroll_offset.abs() - text_area_top - buffer;
+    let mut first_row = (start_pixels / line_height).floor().max(0.0) as usize;
+    let mut row_count = ((viewport_height + buffer * 2.0) / line_height).ceil() as usize;
+
+    let lines_per_second = scroll_velocity.abs() / line_height;
+    if lines_per_second > performance.predictive_spawn_threshold {
+        let ahead = ((lines_per_second * performance.predictive_spawn_lookahead).ceil() as usize)
+            .min(performance.predictive_spawn_max_lines);
+
+        if scroll_velocity < 0.0 {
+            // Scrolling down: spawn extra rows below the viewport
+            row_count += ahead;
+        } else {
+            // Scrolling up: spawn extra rows above the viewport
+            let shift = ahead.min(first_row);
+            first_row -= shift;
+            row_count += shift;
+        }
+    }
+
+    (first_row, row_count)
+}
+
+/// One display row of a line mesh: the line's chars in `chars`, drawn from
+/// `indent` pixels into the text area with the baseline at `base_y`
+#[derive(Clone, Debug, PartialEq)]
+struct LineRow {
+    chars: std::ops::Range<usize>,
+    indent: f32,
+    base_y: f32,
+}
+
+/// Rows a buffer line is drawn on, the first at `first_row`
+///
+/// With soft wrapping there is one row per `WrappedRow` of the line, continuation
+/// rows indented by `continuation_indent`, as the cursor and selections place
+/// them; otherwise the whole line is one row.
+fn line_rows(
+    display_map: &DisplayMap,
+    buffer_line: usize,
+    first_row: usize,
+    use_wrapping: bool,
+    continuation_indent: f32,
+    row_base_y: impl Fn(usize) -> f32,
+) -> Vec<LineRow> {
+    let wrapped = if use_wrapping { display_map.line_rows(buffer_line) } else { &[] };
+    if wrapped.is_empty() {
+        return vec![LineRow { chars: 0..usize::MAX, indent: 0.0, base_y: row_base_y(first_row) }];
+    }
+    wrapped
+        .iter()
+        .enumerate()
+        .map(|(index, row)| LineRow {
+            chars: row.start_offset..row.end_offset,
+            indent: if row.is_continuation { continuation_indent } else { 0.0 },
+            base_y: row_base_y(first_row + index),
+        })
+        .collect()
+}
+
 /// Per-line GPU text rendering system
 /// OPTIMIZATION: Only rebuilds meshes for dirty lines instead of entire viewport
+///
+/// With soft wrapping each line mesh holds all of the line's wrapped rows.
 pub(crate) fn update_gpu_text_per_line(
     mut commands: Commands,
     mut state: ResMut<CodeEditorState>,
     mut pool: ResMut<LineMeshPool>,
-    (font, theme, syntax_settings, performance): (Res<FontSettings>, Res<ThemeSettings>, Res<SyntaxSettings>, Res<PerformanceSettings>),
+    (font, theme, syntax_settings, performance, wrapping, indentation): (
+        Res<FontSettings>,
+        Res<ThemeSettings>,
+        Res<SyntaxSettings>,
+        Res<PerformanceSettings>,
+        Res<WrappingSettings>,
+        Res<IndentationSettings>,
+    ),
     viewport: Res<ViewportDimensions>,
     fold_state: Res<FoldState>,
     mut atlas: ResMut<GlyphAtlas>,
@@ -544,6 +695,7 @@ pub(crate) fn update_gpu_text_per_line(
     if !state.needs_update && !needs_scroll_update {
         return;
     }
+    atlas.begin_frame(performance.max_glyphs_per_frame);
 
     // Debug: Log why we're updating
     if state.needs_update {
@@ -564,20 +716,38 @@ pub(crate) fn update_gpu_text_per_line(
     let font_size = font.size;
     let line_height = font.line_height;
     let _char_width = font.char_width;
+    let atlas_generation = atlas.generation();
 
-    // Calculate visible range
-    let buffer = line_height * performance.viewport_buffer_lines as f32;
+    // Calculate visible range (extended ahead of fast scrolls)
     let total_buffer_lines = state.line_count();
 
-    let scroll_dist = state.scroll_offset.abs();
-    let start_pixels = scroll_dist - viewport.text_area_top - buffer;
-    let first_visible_display_row = (start_pixels / line_height).floor().max(0.0) as usize;
-    let visible_count = ((viewport.height as f32 + buffer * 2.0) / line_height).ceil() as usize;
+    let (first_visible_display_row, visible_count) = render_row_range(
+        state.scroll_offset,
+        state.scroll_velocity,
+        viewport.text_area_top,
+        viewport.height as f32,
+        line_height,
+        &performance,
+    );
     let last_visible_display_row = first_visible_display_row + visible_count;
 
-    // Map buffer lines to display rows (accounting for folding)
+    // Soft-wrapped lines take one display row per wrapped row
+    let use_wrapping = wrapping.enabled && state.display_map.wrap_width > 0 && !state.display_map.rows.is_empty();
+    let continuation_indent = if wrapping.indent_wrapped_lines {
+        indentation.indent_size as f32 * font.char_width
+    } else {
+        0.0
+    };
+    let row_count_of = |display_map: &DisplayMap, buffer_line: usize| {
+        if use_wrapping { display_map.line_rows(buffer_line).len().max(1) } else { 1 }
+    };
+
+    // Map buffer lines to display rows (accounting for folding and wrapping)
     let has_folding = !fold_state.regions.is_empty();
-    let (start_buffer_line, _) = if has_folding {
+    let (start_buffer_line, start_display_row) = if use_wrapping {
+        let (line, first_row) = state.display_map.line_at_row(first_visible_display_row);
+        (line.min(total_buffer_lines), first_row)
+    } else if has_folding {
         let mut display_row = 0;
         let mut buffer_line = 0;
         while buffer_line < total_buffer_lines && display_row < first_visible_display_row {
@@ -617,19 +787,26 @@ pub(crate) fn update_gpu_text_per_line(
     // Get or create material handle
     let material_handle = render_state.material_handle.clone().unwrap_or_default();
 
+    // Lines drawn with glyphs deferred by the rasterization budget
+    let mut incomplete_lines: Option<std::ops::Range<usize>> = None;
+
     // Hide offscreen entities and collect visible buffer lines
     let mut visible_buffer_lines = std::collections::HashSet::new();
-    let mut current_display_row = if has_folding { 0 } else { start_buffer_line };
+    let mut current_display_row = start_display_row;
 
     for buffer_line in start_buffer_line..total_buffer_lines {
         if fold_state.is_line_hidden(buffer_line) {
+            // Wrapped rows of folded lines keep their place, as for the cursor
+            if use_wrapping {
+                current_display_row += row_count_of(&state.display_map, buffer_line);
+            }
             continue;
         }
         if current_display_row > last_visible_display_row {
             break;
         }
         visible_buffer_lines.insert(buffer_line);
-        current_display_row += 1;
+        current_display_row += row_count_of(&state.display_map, buffer_line);
     }
 
     // Hide entities for offscreen lines
@@ -679,13 +856,17 @@ pub(crate) fn update_gpu_text_per_line(
         let highlighted_lines: Vec<Vec<LineSegment>> = Vec::new();
 
         // Process ONLY visible lines (not all buffer lines!)
-        current_display_row = if has_folding { 0 } else { start_buffer_line };
+        current_display_row = start_display_row;
 
         let mut despawn_count = 0;
         let mut spawn_count = 0;
 
         for buffer_line in start_buffer_line..estimated_end_buffer_line.min(total_buffer_lines) {
+            let row_count = row_count_of(&state.display_map, buffer_line);
             if fold_state.is_line_hidden(buffer_line) {
+                if use_wrapping {
+                    current_display_row += row_count;
+                }
                 continue;
             }
             if current_display_row > last_visible_display_row {
@@ -703,14 +884,19 @@ pub(crate) fn update_gpu_text_per_line(
             // Check if entity has valid mesh/versions
             let entity_is_stale = if let Some(&existing_entity) = pool.active.get(&buffer_line) {
                 if let Ok((_ent, line_comp, _transform, _visibility, _mesh, _material)) = line_query.get(existing_entity) {
+                    // Glyphs evicted from the atlas since the mesh was built leave stale UVs
+                    let atlas_stale = line_comp.atlas_generation != atlas.generation();
+                    // Rows are baked into the mesh, so a line pushed onto other rows
+                    // (a line above it wrapped differently) must be rebuilt
+                    let moved = line_comp.display_row != current_display_row;
                     #[cfg(feature = "tree-sitter")]
                     {
                         // Check if highlights are stale (tree updated since this entity was created)
-                        line_comp.tree_version != syntax.tree_version
+                        atlas_stale || moved || line_comp.tree_version != syntax.tree_version
                     }
                     #[cfg(not(feature = "tree-sitter"))]
                     {
-                        false
+                        atlas_stale || moved
                     }
                 } else {
                     true // Entity query failed, treat as stale
@@ -729,13 +915,21 @@ pub(crate) fn update_gpu_text_per_line(
                         *visibility = Visibility::Visible;
                     }
                 }
-                current_display_row += 1;
+                current_display_row += row_count;
                 continue;
             }
 
             // Build mesh for this line
             let baseline_offset = font_size * 0.32;
-            let base_y = viewport.text_area_top + state.scroll_offset + (current_display_row as f32 * line_height) + baseline_offset;
+            let scroll_offset = state.scroll_offset;
+            let rows = line_rows(
+                &state.display_map,
+                buffer_line,
+                current_display_row,
+                use_wrapping,
+                continuation_indent,
+                |row| viewport.text_area_top + scroll_offset + (row as f32 * line_height) + baseline_offset,
+            );
 
             // Get highlights for this specific line
             // Strategy: Try highlighted batch -> cache -> plain text fallback
@@ -755,6 +949,7 @@ pub(crate) fn update_gpu_text_per_line(
                             vec![LineSegment {
                                 text: line_text.trim_end_matches('\n').to_string(),
                                 color: theme.foreground,
+                                style: FontStyle::Regular,
                             }]
                         }
                     } else {
@@ -780,6 +975,7 @@ pub(crate) fn update_gpu_text_per_line(
                             vec![LineSegment {
                                 text: line_text.trim_end_matches('\n').to_string(),
                                 color: theme.foreground,
+                                style: FontStyle::Regular,
                             }]
                         }
                     }
@@ -804,6 +1000,7 @@ pub(crate) fn update_gpu_text_per_line(
                         vec![LineSegment {
                             text: line_text.trim_end_matches('\n').to_string(),
                             color: theme.foreground,
+                            style: FontStyle::Regular,
                         }]
                     }
                 }
@@ -819,23 +1016,30 @@ pub(crate) fn update_gpu_text_per_line(
                     vec![LineSegment {
                         text: line_text.trim_end_matches('\n').to_string(),
                         color: theme.foreground,
+                        style: FontStyle::Regular,
                     }]
                 }
             };
 
+            let deferred_before = atlas.deferred_this_frame();
             let mesh_handle = build_line_mesh(
                 buffer_line,
                 &segments_vec,
                 &state.rope,
-                base_y,
+                &rows,
                 &viewport,
                 &font,
                 &theme,
                 state.horizontal_scroll_offset,
+                state.display_map.tab_map.tab_size(),
                 &mut atlas,
                 &mut images,
                 &mut meshes,
             );
+            if atlas.deferred_this_frame() > deferred_before {
+                let start = incomplete_lines.as_ref().map_or(buffer_line, |range| range.start);
+                incomplete_lines = Some(start..buffer_line + 1);
+            }
 
             // OPTIMIZED FIX: Despawn and recreate only when dirty
             // Entity mutation causes rendering glitches, so we despawn+respawn instead
@@ -860,42 +1064,68 @@ pub(crate) fn update_gpu_text_per_line(
                     tree_version: syntax.tree_version,
                     #[cfg(not(feature = "tree-sitter"))]
                     tree_version: 0,
+                    atlas_generation: atlas.generation(),
                 },
                 Name::new(format!("LineMesh_{}", buffer_line)),
             )).id();
 
             pool.active.insert(buffer_line, entity);
 
-            current_display_row += 1;
+            current_display_row += row_count;
         }
 
         info!("[PERF] Despawned {} entities, spawned {} entities", despawn_count, spawn_count);
     }
 
-    // Clear update flags
-    state.needs_update = false;
+    // Clear update flags; if glyphs were evicted while building, lines built
+    // earlier this frame may hold stale UVs, so check them again next frame
+    state.needs_update = atlas.generation() != atlas_generation;
     state.needs_scroll_update = false;
     state.dirty_lines = None;
     state.last_render_time = time.elapsed_secs_f64() * 1000.0;
+
+    // Rebuild lines with deferred glyphs next frame, when there is budget again
+    if let Some(range) = incomplete_lines {
+        state.dirty_lines = Some(range);
+        state.needs_update = true;
+    }
+}
+
+/// Advance a tab from `x` to the next tab stop of a line starting at `line_x`
+///
+/// Matches `TabMap::expand_column`, so glyphs, selections and whitespace
+/// markers agree on where a tab ends.
+fn next_tab_stop_x(x: f32, line_x: f32, char_width: f32, tab_size: u32) -> f32 {
+    let col = ((x - line_x) / char_width).round().max(0.0) as u32;
+    line_x + crate::display_map::TabMap::new(tab_size).next_tab_stop(col) as f32 * char_width
+}
+
+/// Advance past a glyph by whole cells, so wide characters take exactly two
+///
+/// Fallback fonts report their own advances; snapping to the cell grid keeps
+/// glyphs in step with `TabMap` columns used by the cursor and selections.
+fn cell_advance(ch: char, char_width: f32) -> f32 {
+    crate::display_map::char_cells(ch) as f32 * char_width
 }
 
-/// Build a mesh for a single line
+/// Build a mesh for a single line, laid out on `rows`
 fn build_line_mesh(
     buffer_line: usize,
     segments: &[LineSegment],
     rope: &ropey::Rope,
-    base_y: f32,
+    rows: &[LineRow],
     viewport: &ViewportDimensions,
     font: &FontSettings,
     theme: &ThemeSettings,
     horizontal_scroll_offset: f32,
+    tab_size: u32,
     atlas: &mut GlyphAtlas,
     _images: &mut Assets<Image>,
     meshes: &mut Assets<Mesh>,
 ) -> Handle<Mesh> {
     use bevy::mesh::{Indices, PrimitiveTopology};
     use bevy::asset::RenderAssetUsages;
-    use crate::gpu_text::{GlyphKey, GlyphRasterizer};
+    use crate::gpu_text::GlyphKey;
 
     let mut positions: Vec<[f32; 3]> = Vec::new();
     let mut uvs: Vec<[f32; 2]> = Vec::new();
@@ -905,118 +1135,265 @@ fn build_line_mesh(
 
     let font_size = font.size;
     let char_width = font.char_width;
-    let _line_height = font.line_height;
 
-    // Start X at text_area_left (accounts for gutter) minus horizontal scroll
-    let mut x = viewport.text_area_left - horizontal_scroll_offset;
+    let to_rgba = |color: Color| {
+        let rgba = color.to_linear();
+        [rgba.red, rgba.green, rgba.blue, rgba.alpha]
+    };
 
-    // Process segments (same logic as monolithic renderer)
-    if !segments.is_empty() {
-        for seg in segments {
-            let color_rgba = seg.color.to_linear();
-            let color_arr = [color_rgba.red, color_rgba.green, color_rgba.blue, color_rgba.alpha];
+    // Highlighted segments, or the plain line text as a fallback
+    let chars: Vec<(char, [f32; 4], FontStyle)> = if !segments.is_empty() {
+        segments
+            .iter()
+            .flat_map(|seg| {
+                let color = to_rgba(seg.color);
+                seg.text.chars().map(move |ch| (ch, color, seg.style))
+            })
+            .collect()
+    } else if buffer_line < rope.len_lines() {
+        let color = to_rgba(theme.foreground);
+        rope.line(buffer_line).chars().map(|ch| (ch, color, FontStyle::Regular)).collect()
+    } else {
+        Vec::new()
+    };
 
-            for ch in seg.text.chars() {
-                if ch == '\n' || ch == '\r' {
-                    continue;
-                }
-                if ch == '\t' {
-                    x += char_width * 4.0;
-                    continue;
-                }
+    // Each row starts at text_area_left (accounts for gutter) minus horizontal scroll
+    let row_x = |row: &LineRow| viewport.text_area_left - horizontal_scroll_offset + row.indent;
+    let mut row_index = 0;
+    let mut line_x = rows.first().map_or(viewport.text_area_left - horizontal_scroll_offset, row_x);
+    let mut x = line_x;
+
+    for (col, (ch, color, style)) in chars.into_iter().enumerate() {
+        // Move on to the row this char is laid out on; tab stops restart with each row
+        while row_index + 1 < rows.len() && col >= rows[row_index].chars.end {
+            row_index += 1;
+            line_x = row_x(&rows[row_index]);
+            x = line_x;
+        }
+        let Some(row) = rows.get(row_index) else {
+            break;
+        };
 
-                let key = GlyphKey::new(ch, font_size);
-                if let Some(info) = atlas.get_or_insert(key, || {
-                    GlyphRasterizer::rasterize(ch, font_size)
-                }) {
-                    let screen_x = x + info.offset.x;
-                    let screen_y = base_y - info.offset.y;
+        if ch == '\n' || ch == '\r' {
+            continue;
+        }
+        if ch == '\t' {
+            x = next_tab_stop_x(x, line_x, char_width, tab_size);
+            continue;
+        }
 
-                    let world_x = screen_x - viewport.width as f32 / 2.0 + viewport.offset_x;
-                    let world_y = viewport.height as f32 / 2.0 - screen_y;
+        let key = GlyphKey::styled(ch, font_size, style);
+        if let Some(info) = atlas.get_or_queue(key, || {
+            GlyphRasterizer::rasterize(ch, font_size, char_width, style)
+        }) {
+            let screen_x = x + info.offset.x;
+            let screen_y = row.base_y - info.offset.y;
 
-                    let w = info.size.x;
-                    let h = info.size.y;
+            let world_x = screen_x - viewport.width as f32 / 2.0 + viewport.offset_x;
+            let world_y = viewport.height as f32 / 2.0 - screen_y;
 
-                    positions.push([world_x, world_y - h, 0.0]);
-                    positions.push([world_x + w, world_y - h, 0.0]);
-                    positions.push([world_x + w, world_y, 0.0]);
-                    positions.push([world_x, world_y, 0.0]);
+            let w = info.size.x;
+            let h = info.size.y;
 
-                    uvs.push([info.uv_min.x, info.uv_max.y]);
-                    uvs.push([info.uv_max.x, info.uv_max.y]);
-                    uvs.push([info.uv_max.x, info.uv_min.y]);
-                    uvs.push([info.uv_min.x, info.uv_min.y]);
+            positions.push([world_x, world_y - h, 0.0]);
+            positions.push([world_x + w, world_y - h, 0.0]);
+            positions.push([world_x + w, world_y, 0.0]);
+            positions.push([world_x, world_y, 0.0]);
 
-                    colors.extend_from_slice(&[color_arr; 4]);
+            uvs.push([info.uv_min.x, info.uv_max.y]);
+            uvs.push([info.uv_max.x, info.uv_max.y]);
+            uvs.push([info.uv_max.x, info.uv_min.y]);
+            uvs.push([info.uv_min.x, info.uv_min.y]);
 
-                    indices.extend_from_slice(&[
-                        vertex_count, vertex_count + 1, vertex_count + 2,
-                        vertex_count, vertex_count + 2, vertex_count + 3,
-                    ]);
-                    vertex_count += 4;
+            colors.extend_from_slice(&[color; 4]);
 
-                    x += info.advance;
-                }
-            }
+            indices.extend_from_slice(&[
+                vertex_count, vertex_count + 1, vertex_count + 2,
+                vertex_count, vertex_count + 2, vertex_count + 3,
+            ]);
+            vertex_count += 4;
+
+            x += cell_advance(ch, char_width);
         }
-    } else if buffer_line < rope.len_lines() {
-        // Plain text fallback
-        let rope_line = rope.line(buffer_line);
-        let color_rgba = theme.foreground.to_linear();
-        let color_arr = [color_rgba.red, color_rgba.green, color_rgba.blue, color_rgba.alpha];
+    }
 
-        for ch in rope_line.chars() {
-            if ch == '\n' || ch == '\r' {
-                continue;
-            }
-            if ch == '\t' {
-                x += char_width * 4.0;
-                continue;
-            }
+    // Build mesh
+    let mut mesh = Mesh::new(PrimitiveTopology::TriangleList, RenderAssetUsages::RENDER_WORLD);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_COLOR, colors);
+    mesh.insert_indices(Indices::U32(indices));
+
+    meshes.add(mesh)
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_editor_metrics_follow_font_size_and_viewport() {
+        let mut app = App::new();
+        EditorSettingsBuilder::default().font_size(14.0).build().insert_into(&mut app);
+        app.insert_resource(ViewportDimensions::default());
+        app.init_resource::<EditorMetrics>();
+        app.add_systems(Update, update_editor_metrics);
+        app.update();
+
+        let metrics = *app.world().resource::<EditorMetrics>();
+        assert_eq!(metrics.ascent, GlyphRasterizer::fallback_metrics(14.0).0);
+        assert_eq!(metrics.code_area_left, ViewportDimensions::default().text_area_left);
+
+        // Zoom in
+        {
+            let mut font = app.world_mut().resource_mut::<FontSettings>();
+            font.size = 20.0;
+            font.line_height = 28.0;
+        }
+        app.update();
+        let metrics = *app.world().resource::<EditorMetrics>();
+        assert_eq!(metrics.ascent, GlyphRasterizer::fallback_metrics(20.0).0);
+        assert_eq!(metrics.line_height, 28.0);
+
+        // Wider gutter after a resize
+        {
+            let mut viewport = app.world_mut().resource_mut::<ViewportDimensions>();
+            viewport.gutter_width = 90.0;
+            viewport.text_area_left = 110.0;
+        }
+        app.update();
+        let metrics = *app.world().resource::<EditorMetrics>();
+        assert_eq!((metrics.gutter_width, metrics.code_area_left), (90.0, 110.0));
+    }
 
-            let key = GlyphKey::new(ch, font_size);
-            if let Some(info) = atlas.get_or_insert(key, || {
-                GlyphRasterizer::rasterize(ch, font_size)
-            }) {
-                let screen_x = x + info.offset.x;
-                let screen_y = base_y - info.offset.y;
+    #[test]
+    fn test_fast_scroll_spawns_ahead_of_viewport() {
+        let performance = PerformanceSettings::default();
+        let line_height = 20.0;
+        let viewport_height = 600.0;
+
+        // Jump 2000px (100 lines) down in a single 60fps frame
+        let scroll_offset = -2000.0;
+        let velocity = -2000.0 / (1.0 / 60.0);
+
+        let (first, count) = render_row_range(scroll_offset, velocity, 0.0, viewport_height, line_height, &performance);
+        let (slow_first, slow_count) = render_row_range(scroll_offset, 0.0, 0.0, viewport_height, line_height, &performance);
+
+        // The new viewport plus the configured buffer is fully covered
+        let viewport_first = 100;
+        let viewport_last = viewport_first + (viewport_height / line_height) as usize;
+        assert!(first <= viewport_first - performance.viewport_buffer_lines);
+        assert!(first + count >= viewport_last + performance.viewport_buffer_lines);
+
+        // And extended further in the scroll direction than a slow scroll
+        assert_eq!(first, slow_first);
+        assert_eq!(count, slow_count + performance.predictive_spawn_max_lines);
+
+        // Scrolling up extends the range above instead
+        let (up_first, up_count) = render_row_range(scroll_offset, -velocity, 0.0, viewport_height, line_height, &performance);
+        assert_eq!(up_first, 0);
+        assert_eq!(up_first + up_count, slow_first + slow_count);
+    }
 
-                let world_x = screen_x - viewport.width as f32 / 2.0 + viewport.offset_x;
-                let world_y = viewport.height as f32 / 2.0 - screen_y;
+    #[test]
+    fn test_glyph_rasterization_is_capped_per_frame() {
+        use crate::gpu_text::GlyphKey;
 
-                let w = info.size.x;
-                let h = info.size.y;
+        let mut images = Assets::<Image>::default();
+        let mut atlas = GlyphAtlas::new(&mut images);
+        let text: Vec<char> = "漢字仮名交じり文の例".chars().collect();
+        let budget = 4;
 
-                positions.push([world_x, world_y - h, 0.0]);
-                positions.push([world_x + w, world_y - h, 0.0]);
-                positions.push([world_x + w, world_y, 0.0]);
-                positions.push([world_x, world_y, 0.0]);
+        let render_frame = |atlas: &mut GlyphAtlas| {
+            atlas.begin_frame(Some(budget));
+            for &ch in &text {
+                atlas.get_or_queue(GlyphKey::new(ch, 14.0), || GlyphRasterizer::rasterize(ch, 14.0, FontSettings::estimated_char_width(14.0), FontStyle::Regular));
+            }
+            text.iter().filter(|&&ch| atlas.contains(&GlyphKey::new(ch, 14.0))).count()
+        };
 
-                uvs.push([info.uv_min.x, info.uv_max.y]);
-                uvs.push([info.uv_max.x, info.uv_max.y]);
-                uvs.push([info.uv_max.x, info.uv_min.y]);
-                uvs.push([info.uv_min.x, info.uv_min.y]);
+        // The first frame only rasterizes up to the budget, the rest wait
+        assert_eq!(render_frame(&mut atlas), budget);
+        assert_eq!(atlas.pending_count(), text.len() - budget);
+        assert!(atlas.has_pending());
 
-                colors.extend_from_slice(&[color_arr; 4]);
+        // Later frames continue where the previous one stopped
+        assert_eq!(render_frame(&mut atlas), 2 * budget);
+        assert_eq!(render_frame(&mut atlas), text.len());
+        assert!(!atlas.has_pending());
+    }
 
-                indices.extend_from_slice(&[
-                    vertex_count, vertex_count + 1, vertex_count + 2,
-                    vertex_count, vertex_count + 2, vertex_count + 3,
-                ]);
-                vertex_count += 4;
+    #[test]
+    fn test_tab_advances_to_tab_stop_for_each_tab_size() {
+        use crate::gpu_text::GlyphKey;
+
+        let mut images = Assets::<Image>::default();
+        let mut meshes = Assets::<Mesh>::default();
+        let mut atlas = GlyphAtlas::new(&mut images);
+        let font = FontSettings::default();
+        let viewport = ViewportDimensions::default();
+        let rope = ropey::Rope::from_str("\tfoo\n");
+
+        for tab_size in [2, 4, 8] {
+            let rows = line_rows(&DisplayMap::default(), 0, 0, false, 0.0, |_| 0.0);
+            let handle = build_line_mesh(
+                0, &[], &rope, &rows, &viewport, &font, &ThemeSettings::default(), 0.0,
+                tab_size, &mut atlas, &mut images, &mut meshes,
+            );
+            let mesh = meshes.get(&handle).unwrap();
+            let Some(bevy::mesh::VertexAttributeValues::Float32x3(positions)) = mesh.attribute(Mesh::ATTRIBUTE_POSITION) else {
+                panic!("line mesh has no positions");
+            };
 
-                x += info.advance;
-            }
+            // 'f' is the first glyph and starts at the column the cursor uses
+            let visual_col = crate::display_map::TabMap::new(tab_size).expand_column("\tfoo", 1);
+            assert_eq!(visual_col, tab_size);
+            let info = *atlas.get(&GlyphKey::new('f', font.size)).unwrap();
+            let expected_x = viewport.text_area_left + visual_col as f32 * font.char_width + info.offset.x
+                - viewport.width as f32 / 2.0 + viewport.offset_x;
+            assert!((positions[0][0] - expected_x).abs() < 1e-3, "tab size {}", tab_size);
         }
     }
 
-    // Build mesh
-    let mut mesh = Mesh::new(PrimitiveTopology::TriangleList, RenderAssetUsages::RENDER_WORLD);
-    mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
-    mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
-    mesh.insert_attribute(Mesh::ATTRIBUTE_COLOR, colors);
-    mesh.insert_indices(Indices::U32(indices));
+    #[test]
+    fn test_wrapped_line_glyphs_follow_display_rows() {
+        use crate::gpu_text::GlyphKey;
+
+        let mut images = Assets::<Image>::default();
+        let mut meshes = Assets::<Mesh>::default();
+        let mut atlas = GlyphAtlas::new(&mut images);
+        let font = FontSettings::default();
+        let viewport = ViewportDimensions::default();
+        let line_height = font.line_height;
+        let indent = 4.0 * font.char_width;
+
+        let mut state = CodeEditorState::new("a\naaaaaaaaaaaaaaaa\n");
+        state.rewrap(10);
+        let first_row = state.display_map.buffer_line_to_first_row(1);
+        let second_row = state.display_map.line_rows(1)[1].clone();
+        assert!(second_row.is_continuation);
+
+        let rows = line_rows(&state.display_map, 1, first_row, true, indent, |row| row as f32 * line_height);
+        assert_eq!(rows.len(), 2);
+        let handle = build_line_mesh(
+            1, &[], &state.rope, &rows, &viewport, &font, &ThemeSettings::default(), 0.0,
+            4, &mut atlas, &mut images, &mut meshes,
+        );
+        let mesh = meshes.get(&handle).unwrap();
+        let Some(bevy::mesh::VertexAttributeValues::Float32x3(positions)) = mesh.attribute(Mesh::ATTRIBUTE_POSITION) else {
+            panic!("line mesh has no positions");
+        };
 
-    meshes.add(mesh)
-}
\ No newline at end of file
+        // The first glyph of the continuation row starts the next display row, indented
+        let info = *atlas.get(&GlyphKey::new('a', font.size)).unwrap();
+        let top_left = positions[second_row.start_offset * 4 + 3];
+        let display_row = first_row + 1;
+        let expected_y = viewport.height as f32 / 2.0 - (display_row as f32 * line_height - info.offset.y);
+        let expected_x = viewport.text_area_left + indent + info.offset.x - viewport.width as f32 / 2.0 + viewport.offset_x;
+        assert!((top_left[1] - expected_y).abs() < 1e-3);
+        assert!((top_left[0] - expected_x).abs() < 1e-3);
+
+        // The glyph before it ends the first row, one row higher
+        let previous = positions[(second_row.start_offset - 1) * 4 + 3];
+        assert!((previous[1] - (expected_y + line_height)).abs() < 1e-3);
+    }
+}
@@ -11,12 +11,13 @@ use bevy::prelude::*;
 
 use crate::lsp::prelude::*;
 use crate::lsp::state::{
-    CodeActionState, CompletionState, DocumentHighlightState, HoverState, InlayHintState,
-    LspSyncState, RenameState, SignatureHelpState,
+    CodeActionState, CompletionState, DocumentHighlightState, DocumentSymbolTree, FormatOnSaveState,
+    HoverState, InlayHintState, LspSyncState, RenameState, SignatureHelpState,
 };
 use crate::lsp::systems::{
-    cleanup_lsp_timeouts, process_lsp_messages, request_document_highlights, request_inlay_hints,
-    sync_lsp_document, MultipleLocationsEvent, NavigateToFileEvent, WorkspaceEditEvent,
+    cleanup_lsp_timeouts, expire_format_on_save, process_lsp_messages, request_document_highlights,
+    request_document_symbols, request_inlay_hints, sync_lsp_document, MultipleLocationsEvent,
+    NavigateToFileEvent, WorkspaceEditEvent,
 };
 use crate::lsp::sync::{
     sync_code_actions_popup, sync_completion_popup, sync_document_highlights, sync_hover_popup,
@@ -75,6 +76,8 @@ impl Plugin for LspPlugin {
         app.insert_resource(InlayHintState::default());
         app.insert_resource(DocumentHighlightState::default());
         app.insert_resource(RenameState::default());
+        app.insert_resource(DocumentSymbolTree::default());
+        app.insert_resource(FormatOnSaveState::default());
 
         // Register LSP output events (LSP -> user code)
         app.add_message::<NavigateToFileEvent>();
@@ -97,9 +100,11 @@ impl Plugin for LspPlugin {
             Update,
             (
                 process_lsp_messages,
+                expire_format_on_save.after(process_lsp_messages),
                 sync_lsp_document,
                 request_inlay_hints,
                 request_document_highlights,
+                request_document_symbols,
                 cleanup_lsp_timeouts,
             ),
         );
@@ -13,8 +13,8 @@ use crate::lsp::{LspUiRenderSet, LspUiSyncSet};
 use crate::lsp::theme::LspUiTheme;
 use crate::lsp::render::{
     cleanup_lsp_ui_visuals, render_code_actions_popup, render_completion_popup,
-    render_document_highlights, render_hover_popup, render_inlay_hints, render_rename_input,
-    render_signature_help_popup,
+    render_diagnostic_underlines, render_document_highlights, render_hover_popup,
+    render_inlay_hints, render_rename_input, render_signature_help_popup,
 };
 
 /// LSP UI plugin providing default rendering for LSP features
@@ -68,6 +68,7 @@ impl Plugin for LspUiPlugin {
                 render_rename_input,
                 render_inlay_hints,
                 render_document_highlights,
+                render_diagnostic_underlines,
                 cleanup_lsp_ui_visuals,
             )
                 .in_set(LspUiRenderSet),
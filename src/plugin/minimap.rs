@@ -6,8 +6,12 @@ use bevy::asset::RenderAssetUsages;
 use bevy::sprite_render::MeshMaterial2d;
 use crate::settings::*;
 use crate::types::*;
+use crate::display_map::char_cells;
 use crate::gpu_text::{GlyphAtlas, GlyphKey, GlyphRasterizer, TextMaterial, TextRenderState};
 
+/// Cells a tab advances in the minimap
+const MINIMAP_TAB_CELLS: usize = 4;
+
 pub(crate) fn update_minimap_hover(
     windows: Query<&Window>,
     viewport: Res<ViewportDimensions>,
@@ -47,11 +51,12 @@ pub(crate) fn handle_minimap_mouse(
     mouse_button: Res<ButtonInput<MouseButton>>,
     mut state: ResMut<CodeEditorState>,
     font: Res<FontSettings>,
-    minimap_settings: Res<MinimapSettings>,
+    (minimap_settings, scrolling): (Res<MinimapSettings>, Res<ScrollingSettings>),
     viewport: Res<ViewportDimensions>,
     hover_state: Res<MinimapHoverState>,
     mut drag_state: ResMut<MinimapDragState>,
     highlight_query: Query<(&Transform, &Sprite), With<MinimapViewportHighlight>>,
+    fold_state: Res<FoldState>,
 ) {
     if !minimap_settings.enabled {
         drag_state.is_dragging = false;
@@ -67,7 +72,8 @@ pub(crate) fn handle_minimap_mouse(
     };
 
     let viewport_height = viewport.height as f32;
-    let line_count = state.rope.len_lines();
+    // Work in display rows so clicks agree with the folded main view
+    let line_count = minimap_row_count(&fold_state, state.rope.len_lines());
     let line_height = font.line_height;
 
     // Minimap settings (same as in update_minimap)
@@ -83,7 +89,7 @@ pub(crate) fn handle_minimap_mouse(
 
     // Calculate minimap scroll offset (same as in update_minimap)
     let content_height = line_count as f32 * line_height;
-    let max_scroll = -(content_height - viewport_height).max(0.0);
+    let max_scroll = scrolling.max_scroll(content_height, viewport_height, viewport.text_area_top, line_height);
     let scroll_progress = if max_scroll < 0.0 {
         (state.scroll_offset / max_scroll).clamp(0.0, 1.0)
     } else {
@@ -152,7 +158,7 @@ pub(crate) fn handle_minimap_mouse(
         let new_scroll = drag_state.drag_start_scroll - (delta_y * minimap_to_content_ratio);
 
         // Clamp to valid range
-        state.scroll_offset = new_scroll.clamp(max_scroll.min(0.0), 0.0);
+        state.scroll_offset = new_scroll.clamp(max_scroll, 0.0);
         state.needs_scroll_update = true;
     }
     // Handle click or drag elsewhere on minimap (jump-to-position behavior)
@@ -175,7 +181,7 @@ pub(crate) fn handle_minimap_mouse(
         let target_scroll = -(target_first_line * line_height);
 
         // Clamp to valid range
-        state.scroll_offset = target_scroll.clamp(max_scroll.min(0.0), 0.0);
+        state.scroll_offset = target_scroll.clamp(max_scroll, 0.0);
         state.needs_scroll_update = true;
     }
 }
@@ -184,7 +190,13 @@ pub(crate) fn handle_minimap_mouse(
 pub(crate) fn update_minimap(
     mut commands: Commands,
     state: ResMut<CodeEditorState>,
-    (font, theme, minimap_settings): (Res<FontSettings>, Res<ThemeSettings>, Res<MinimapSettings>),
+    (font, theme, minimap_settings, wrapping, scrolling): (
+        Res<FontSettings>,
+        Res<ThemeSettings>,
+        Res<MinimapSettings>,
+        Res<WrappingSettings>,
+        Res<ScrollingSettings>,
+    ),
     viewport: Res<ViewportDimensions>,
     hover_state: Res<MinimapHoverState>,
     mut atlas: ResMut<GlyphAtlas>,
@@ -197,6 +209,7 @@ pub(crate) fn update_minimap(
     mut bg_query: Query<(Entity, &mut Transform, &mut Sprite, &mut Visibility), (With<MinimapBackground>, Without<MinimapSlider>, Without<MinimapViewportHighlight>)>,
     mut slider_query: Query<(Entity, &mut Transform, &mut Sprite, &mut Visibility), (With<MinimapSlider>, Without<MinimapBackground>, Without<MinimapViewportHighlight>)>,
     mut highlight_query: Query<(Entity, &mut Transform, &mut Sprite, &mut Visibility), (With<MinimapViewportHighlight>, Without<MinimapBackground>, Without<MinimapSlider>)>,
+    fold_state: Res<FoldState>,
 ) {
     // Hide everything if minimap is disabled
     if !minimap_settings.enabled {
@@ -218,7 +231,8 @@ pub(crate) fn update_minimap(
     let viewport_width = viewport.width as f32;
     let viewport_height = viewport.height as f32;
     let minimap_width = minimap_settings.width;
-    let line_count = state.rope.len_lines();
+    // Folded lines are skipped, so the minimap is laid out in display rows
+    let line_count = minimap_row_count(&fold_state, state.rope.len_lines());
     let line_height = font.line_height;
 
     // Minimap text settings - tiny font like VSCode
@@ -261,11 +275,13 @@ pub(crate) fn update_minimap(
     }
 
     // === Calculate minimap scroll and viewport indicator ===
-    let content_height = line_count as f32 * line_height;
-    let visible_lines = (viewport_height / line_height).ceil();
+    // The editor scrolls through display rows, which outnumber buffer lines when wrapping
+    let use_wrapping = wrapping.enabled && state.display_map.wrap_width > 0;
+    let editor_rows = if use_wrapping { state.display_map.row_count().max(1) } else { line_count };
+    let content_height = editor_rows as f32 * line_height;
 
     // Calculate scroll progress (0 = top, 1 = bottom)
-    let max_scroll = -(content_height - viewport_height).max(0.0);
+    let max_scroll = scrolling.max_scroll(content_height, viewport_height, viewport.text_area_top, line_height);
     let scroll_progress = if max_scroll < 0.0 {
         (state.scroll_offset / max_scroll).clamp(0.0, 1.0)
     } else {
@@ -281,10 +297,11 @@ pub(crate) fn update_minimap(
         0.0
     };
 
-    // Viewport indicator - shows which part of content is visible in editor
-    let visible_fraction = (visible_lines / line_count as f32).min(1.0);
-    let indicator_height_in_minimap = visible_fraction * total_minimap_content_height;
-    let indicator_position_in_minimap = scroll_progress * (total_minimap_content_height - indicator_height_in_minimap);
+    // Viewport indicator - covers the minimap rows of the lines visible in the editor
+    let (first_row, row_span) =
+        minimap_visible_rows(&state, &fold_state, use_wrapping, line_height, viewport_height);
+    let indicator_height_in_minimap = (row_span * minimap_line_height).min(total_minimap_content_height);
+    let indicator_position_in_minimap = first_row * minimap_line_height;
 
     // Convert to screen space with scroll applied
     let indicator_screen_y = indicator_position_in_minimap - minimap_scroll_offset + content_y_offset;
@@ -355,6 +372,8 @@ pub(crate) fn update_minimap(
     // Build GPU mesh for minimap text
     let max_column = minimap_settings.max_column;
     let font_size = minimap_font_size;
+    // Keep the editor font's width-to-size ratio at the minimap's size
+    let char_width = font.char_width * font_size / font.size;
 
     // Calculate visible line range for viewport culling
     let buffer_lines = 100; // Extra lines above/below for smooth scrolling
@@ -370,8 +389,13 @@ pub(crate) fn update_minimap(
     let last_visible_minimap_line = ((minimap_viewport_bottom - content_y_offset) / minimap_line_height)
         .ceil() as usize;
 
-    let start_line = first_visible_minimap_line.saturating_sub(buffer_lines);
-    let end_line = (last_visible_minimap_line + buffer_lines).min(line_count);
+    let start_row = first_visible_minimap_line.saturating_sub(buffer_lines);
+    let end_row = (last_visible_minimap_line + buffer_lines).min(line_count);
+    let rows = minimap_lines(&fold_state, state.rope.len_lines(), start_row, end_row);
+
+    // Buffer line span covered by those rows (hidden lines inside are skipped below)
+    let start_line = rows.first().map(|&(_, line)| line).unwrap_or(0);
+    let end_line = rows.last().map(|&(_, line)| line + 1).unwrap_or(0);
 
     // === LAZY HIGHLIGHTING for minimap (simple version - no cache due to param limit) ===
     #[cfg(feature = "tree-sitter")]
@@ -397,7 +421,9 @@ pub(crate) fn update_minimap(
     #[cfg(not(feature = "tree-sitter"))]
     let highlighted_lines: Vec<Vec<crate::types::LineSegment>> = Vec::new();
 
-    // Build mesh data
+    // Build mesh data; glyphs evicted while building change the generation,
+    // which triggers another rebuild next frame
+    let atlas_generation = atlas.generation();
     let mut positions: Vec<[f32; 3]> = Vec::new();
     let mut uvs: Vec<[f32; 2]> = Vec::new();
     let mut colors: Vec<[f32; 4]> = Vec::new();
@@ -414,19 +440,15 @@ pub(crate) fn update_minimap(
     };
 
     // Render visible lines
-    for line_idx in start_line..end_line {
-        let line = state.rope.line(line_idx);
-        let line_text: String = line.chars()
-            .take(max_column)
-            .filter(|c| *c != '\n' && *c != '\r')
-            .collect();
+    for &(row_idx, line_idx) in &rows {
+        let line_text = minimap_line_text(state.rope.line(line_idx), max_column);
 
         if line_text.trim().is_empty() {
             continue;
         }
 
         // Y position (screen space, top=0) with minimap scroll applied
-        let screen_y = (line_idx as f32 * minimap_line_height) + content_y_offset - minimap_scroll_offset;
+        let screen_y = (row_idx as f32 * minimap_line_height) + content_y_offset - minimap_scroll_offset;
 
         // Convert to world coordinates
         let world_y = viewport_height / 2.0 - screen_y;
@@ -451,13 +473,13 @@ pub(crate) fn update_minimap(
 
         for ch in line_text.chars() {
             if ch == '\t' {
-                x += font_size * 0.6 * 4.0;
+                x += char_width * MINIMAP_TAB_CELLS as f32;
                 continue;
             }
 
             let key = GlyphKey::new(ch, font_size);
             if let Some(info) = atlas.get_or_insert(key, || {
-                GlyphRasterizer::rasterize(ch, font_size)
+                GlyphRasterizer::rasterize(ch, font_size, char_width, FontStyle::Regular)
             }) {
                 let glyph_world_x = x + info.offset.x;
                 let glyph_world_y = world_y - info.offset.y;
@@ -492,10 +514,8 @@ pub(crate) fn update_minimap(
                 indices.push(vertex_count + 3);
 
                 vertex_count += 4;
-                x += info.advance;
-            } else {
-                x += font_size * 0.6;
             }
+            x += char_width * char_cells(ch) as f32;
         }
     }
 
@@ -532,7 +552,10 @@ pub(crate) fn update_minimap(
     if let Some((entity, minimap_mesh, _)) = mesh_query.iter().next() {
         // Check if we need to rebuild (content changed or scroll changed)
         let scroll_changed = (minimap_mesh.built_at_scroll - state.scroll_offset).abs() > 0.01;
-        let needs_rebuild = minimap_mesh.built_at_version != state.content_version || scroll_changed;
+        let needs_rebuild = minimap_mesh.built_at_version != state.content_version
+            || minimap_mesh.built_at_rows != line_count
+            || minimap_mesh.built_at_generation != atlas_generation
+            || scroll_changed;
 
         if needs_rebuild {
             let new_mesh_handle = meshes.add(mesh);
@@ -541,6 +564,8 @@ pub(crate) fn update_minimap(
                 .insert(GpuMinimapMesh {
                     built_at_version: state.content_version,
                     built_at_scroll: state.scroll_offset,
+                    built_at_rows: line_count,
+                    built_at_generation: atlas_generation,
                 })
                 .insert(Visibility::Visible);
         } else {
@@ -556,6 +581,8 @@ pub(crate) fn update_minimap(
             GpuMinimapMesh {
                 built_at_version: state.content_version,
                 built_at_scroll: state.scroll_offset,
+                built_at_rows: line_count,
+                built_at_generation: atlas_generation,
             },
             Name::new("GpuMinimapMesh"),
             Visibility::Visible,
@@ -563,6 +590,98 @@ pub(crate) fn update_minimap(
     }
 }
 
+/// Number of minimap rows: buffer lines minus those hidden by folds
+pub(crate) fn minimap_row_count(fold_state: &FoldState, line_count: usize) -> usize {
+    if !fold_state.regions.iter().any(|r| r.is_folded) {
+        return line_count;
+    }
+    (0..line_count).filter(|&line| !fold_state.is_line_hidden(line)).count()
+}
+
+/// Minimap rows showing what the editor viewport shows, as `(first_row, row_count)`
+///
+/// Rows are fractional: with wrapping, a buffer line split into several
+/// display rows is only partly covered when the viewport cuts through it.
+pub(crate) fn minimap_visible_rows(
+    state: &CodeEditorState,
+    fold_state: &FoldState,
+    use_wrapping: bool,
+    line_height: f32,
+    viewport_height: f32,
+) -> (f32, f32) {
+    let first = (-state.scroll_offset / line_height).max(0.0);
+    let last = first + viewport_height / line_height;
+
+    if !use_wrapping {
+        // Editor display rows and minimap rows both skip folded lines
+        let rows = minimap_row_count(fold_state, state.rope.len_lines()) as f32;
+        let first = first.min(rows);
+        return (first, last.min(rows) - first);
+    }
+
+    let display_map = &state.display_map;
+    let row_count = display_map.row_count();
+    if row_count == 0 {
+        return (0.0, 0.0);
+    }
+    let to_minimap_row = |display_row: f32| {
+        if display_row >= row_count as f32 {
+            let last_line = display_map.row_to_buffer_line(row_count - 1);
+            return fold_state.actual_to_display_line(last_line) as f32 + 1.0;
+        }
+        let row = display_row as usize;
+        let line = display_map.row_to_buffer_line(row);
+        let line_first_row = display_map.buffer_line_to_first_row(line);
+        let line_rows = display_map.rows[line_first_row..]
+            .iter()
+            .take_while(|r| r.buffer_line == line)
+            .count()
+            .max(1);
+        let within = (display_row - line_first_row as f32) / line_rows as f32;
+        fold_state.actual_to_display_line(line) as f32 + within
+    };
+
+    let first_row = to_minimap_row(first);
+    (first_row, to_minimap_row(last) - first_row)
+}
+
+/// Text of a minimap line cut to `max_column` cells
+///
+/// Wide characters take two cells and tabs `MINIMAP_TAB_CELLS`, so lines of
+/// CJK text end at the same visual width as ASCII lines.
+pub(crate) fn minimap_line_text(line: ropey::RopeSlice, max_column: usize) -> String {
+    let mut cells = 0;
+    let mut text = String::new();
+    for ch in line.chars().filter(|c| *c != '\n' && *c != '\r') {
+        cells += if ch == '\t' { MINIMAP_TAB_CELLS } else { char_cells(ch) as usize };
+        if cells > max_column {
+            break;
+        }
+        text.push(ch);
+    }
+    text
+}
+
+/// Pair each minimap row in `start_row..end_row` with the buffer line it shows
+pub(crate) fn minimap_lines(
+    fold_state: &FoldState,
+    line_count: usize,
+    start_row: usize,
+    end_row: usize,
+) -> Vec<(usize, usize)> {
+    let mut rows = Vec::with_capacity(end_row.saturating_sub(start_row));
+    let mut line = fold_state.display_to_actual_line(start_row);
+    let mut row = start_row;
+    while row < end_row && line < line_count {
+        if !fold_state.is_line_hidden(line) {
+            rows.push((row, line));
+            row += 1;
+        }
+        line += 1;
+    }
+    rows
+}
+
 /// Update minimap to show search match highlights
 pub(crate) fn update_minimap_find_highlights(
     mut commands: Commands,
@@ -664,3 +783,57 @@ pub(crate) fn update_minimap_find_highlights(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_folding_shortens_minimap_content() {
+        let line_count = 10;
+        let mut fold_state = FoldState::default();
+        assert_eq!(minimap_row_count(&fold_state, line_count), 10);
+
+        fold_state.add_manual_fold(2, 6);
+
+        assert_eq!(minimap_row_count(&fold_state, line_count), 6);
+        let lines: Vec<usize> = minimap_lines(&fold_state, line_count, 0, 10)
+            .into_iter()
+            .map(|(_, line)| line)
+            .collect();
+        assert_eq!(lines, vec![0, 1, 2, 7, 8, 9]);
+    }
+
+    #[test]
+    fn test_minimap_truncates_wide_text_by_cells() {
+        let rope = ropey::Rope::from_str("中文字符测试\nabcdefghij\n");
+
+        // Six CJK characters are twelve cells: five cells fit two of them
+        assert_eq!(minimap_line_text(rope.line(0), 5), "中文");
+        assert_eq!(minimap_line_text(rope.line(0), 6), "中文字");
+        assert_eq!(minimap_line_text(rope.line(1), 5), "abcde");
+    }
+
+    #[test]
+    fn test_indicator_covers_visible_display_rows_when_wrapping() {
+        // Ten 40-char lines, each wrapped onto four rows
+        let text = vec!["x".repeat(40); 10].join("\n");
+        let mut state = CodeEditorState::new(&text);
+        state.rewrap(10);
+        assert_eq!(state.display_map.row_count(), 40);
+
+        let fold_state = FoldState::default();
+        let (line_height, viewport_height) = (20.0, 200.0);
+
+        // Scrolled to display row 8 with ten rows visible: lines 2 and 3 and
+        // half of line 4, not ten buffer lines
+        state.scroll_offset = -8.0 * line_height;
+        let (first, span) = minimap_visible_rows(&state, &fold_state, true, line_height, viewport_height);
+        assert!((first - 2.0).abs() < 1e-4);
+        assert!((span - 2.5).abs() < 1e-4);
+
+        let (first, span) = minimap_visible_rows(&state, &fold_state, false, line_height, viewport_height);
+        assert!((first - 8.0).abs() < 1e-4);
+        assert!((span - 2.0).abs() < 1e-4);
+    }
+}
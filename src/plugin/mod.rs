@@ -6,12 +6,18 @@ mod ui_elements;
 mod cursor;
 mod brackets;
 mod minimap;
+mod overview_ruler;
+mod status_overlay;
 mod folding;
+mod bookmarks;
 mod gpu_text_render;
 mod scrollbar;
 mod syntax_highlighting;
 mod editor_ui_plugin;
 
+#[cfg(feature = "file-io")]
+mod file_io;
+
 #[cfg(feature = "lsp")]
 mod lsp_plugin;
 
@@ -22,7 +28,10 @@ pub(crate) use ui_elements::*;
 pub(crate) use cursor::*;
 pub(crate) use brackets::*;
 pub(crate) use minimap::*;
+pub(crate) use overview_ruler::*;
+pub(crate) use status_overlay::{update_status_overlay, update_status_overlay_text};
 pub(crate) use folding::*;
+pub(crate) use bookmarks::*;
 pub(crate) use gpu_text_render::*;
 
 // Re-export scrollbar plugin publicly
@@ -31,9 +40,16 @@ pub use scrollbar::{ScrollbarPlugin, Scrollbar, mouse_not_over_scrollbar};
 // Re-export syntax plugin publicly
 pub use syntax_highlighting::{SyntaxPlugin, SyntaxResource, HighlightCache};
 
+// Re-export the status overlay resource publicly
+pub use status_overlay::EditorStatusOverlay;
+
 // Re-export editor UI plugin publicly
 pub use editor_ui_plugin::EditorUiPlugin;
 
+// Re-export file loading publicly (feature-gated)
+#[cfg(feature = "file-io")]
+pub use file_io::{EditorFile, language_from_path, language_indentation};
+
 // Re-export LSP plugins publicly (feature-gated)
 #[cfg(feature = "lsp")]
 pub use lsp_plugin::LspPlugin;
@@ -67,10 +83,71 @@ pub struct ApplyStateSet;
 #[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
 pub struct RenderingSet;
 
+/// Which built-in visual subsystems `EditorUiPlugin` renders
+///
+/// Disabling a subsystem skips spawning and updating its entities while the
+/// underlying state (cursor positions, selection ranges, scroll) keeps updating,
+/// so it can be replaced with custom rendering systems.
+#[derive(Resource, Clone, Copy, Debug)]
+pub struct BuiltinRendering {
+    /// Render cursors
+    pub cursor: bool,
+    /// Render selection highlights
+    pub selection: bool,
+    /// Render and handle input for the minimap
+    pub minimap: bool,
+    /// Render line numbers
+    pub line_numbers: bool,
+    /// Render indent guides
+    pub indent_guides: bool,
+}
+
+impl Default for BuiltinRendering {
+    fn default() -> Self {
+        Self {
+            cursor: true,
+            selection: true,
+            minimap: true,
+            line_numbers: true,
+            indent_guides: true,
+        }
+    }
+}
+
+/// Run condition: built-in cursor rendering is enabled
+pub(crate) fn cursor_rendering_enabled(rendering: Res<BuiltinRendering>) -> bool {
+    rendering.cursor
+}
+
+/// Run condition: built-in selection rendering is enabled
+pub(crate) fn selection_rendering_enabled(rendering: Res<BuiltinRendering>) -> bool {
+    rendering.selection
+}
+
+/// Run condition: built-in minimap is enabled
+pub(crate) fn minimap_rendering_enabled(rendering: Res<BuiltinRendering>) -> bool {
+    rendering.minimap
+}
+
+/// Run condition: built-in line number rendering is enabled
+pub(crate) fn line_number_rendering_enabled(rendering: Res<BuiltinRendering>) -> bool {
+    rendering.line_numbers
+}
+
+/// Run condition: built-in indent guide rendering is enabled
+pub(crate) fn indent_guide_rendering_enabled(rendering: Res<BuiltinRendering>) -> bool {
+    rendering.indent_guides
+}
+
 /// Code editor plugin with GPU-accelerated text rendering
 pub struct CodeEditorPlugin {
     settings: SettingsBundle,
     input_map: InputMap<EditorAction>,
+    rendering: BuiltinRendering,
+    change_granularity: ChangeGranularity,
+    read_only: bool,
+    #[cfg(feature = "file-io")]
+    file: Option<std::path::PathBuf>,
 }
 
 impl CodeEditorPlugin {
@@ -94,6 +171,11 @@ impl CodeEditorPlugin {
         Self {
             settings: EditorSettingsBuilder::default().build(),
             input_map,
+            rendering: BuiltinRendering::default(),
+            change_granularity: ChangeGranularity::default(),
+            read_only: false,
+            #[cfg(feature = "file-io")]
+            file: None,
         }
     }
 
@@ -108,6 +190,61 @@ impl CodeEditorPlugin {
         self.settings = builder.build();
         self
     }
+
+    /// Enable or disable built-in cursor rendering
+    pub fn with_cursor_rendering(mut self, enabled: bool) -> Self {
+        self.rendering.cursor = enabled;
+        self
+    }
+
+    /// Enable or disable built-in selection highlight rendering
+    pub fn with_selection_rendering(mut self, enabled: bool) -> Self {
+        self.rendering.selection = enabled;
+        self
+    }
+
+    /// Enable or disable the built-in minimap
+    pub fn with_minimap_rendering(mut self, enabled: bool) -> Self {
+        self.rendering.minimap = enabled;
+        self
+    }
+
+    /// Enable or disable built-in line number rendering
+    pub fn with_line_number_rendering(mut self, enabled: bool) -> Self {
+        self.rendering.line_numbers = enabled;
+        self
+    }
+
+    /// Enable or disable built-in indent guide rendering
+    pub fn with_indent_guide_rendering(mut self, enabled: bool) -> Self {
+        self.rendering.indent_guides = enabled;
+        self
+    }
+
+    /// Set how finely `ContentChangedEvent` reports edits
+    pub fn with_change_granularity(mut self, granularity: ChangeGranularity) -> Self {
+        self.change_granularity = granularity;
+        self
+    }
+
+    /// Start with a read-only buffer
+    ///
+    /// Toggle at runtime with `CodeEditorState::read_only`.
+    pub fn with_read_only(mut self, read_only: bool) -> Self {
+        self.read_only = read_only;
+        self
+    }
+
+    /// Load a file into the editor at startup
+    ///
+    /// The file is read asynchronously; indentation follows the language detected
+    /// from its extension (see `EditorFile`). If reading fails the buffer stays
+    /// empty and a `FileLoadFailed` event is sent.
+    #[cfg(feature = "file-io")]
+    pub fn with_file(mut self, path: impl Into<std::path::PathBuf>) -> Self {
+        self.file = Some(path.into());
+        self
+    }
 }
 
 impl Default for CodeEditorPlugin {
@@ -126,9 +263,25 @@ impl Plugin for CodeEditorPlugin {
         self.settings.clone().insert_into(app);
 
         // Insert core resources (needed for all render modes)
-        app.insert_resource(CodeEditorState::default());
+        let mut state = CodeEditorState::default();
+        state.read_only = self.read_only;
+        app.insert_resource(state);
         app.insert_resource(crate::input::MouseDragState::default());
         app.insert_resource(KeyRepeatState::default());
+        app.init_resource::<crate::input::CustomActions>();
+        app.init_resource::<EditorDiagnostics>();
+        app.init_resource::<SnippetSession>();
+        app.init_resource::<ViewTransition>();
+        app.init_resource::<EditorMetrics>();
+        app.init_resource::<GutterColumns>();
+        app.init_resource::<crate::input::MacroRecorder>();
+        app.init_resource::<EditorStatusOverlay>();
+        let mut kill_ring = KillRing::new(self.settings.clipboard.kill_ring_capacity);
+        kill_ring.full_line_paste = self.settings.clipboard.full_line_paste;
+        app.insert_resource(kill_ring);
+
+        app.insert_resource(self.rendering);
+        app.insert_resource(self.change_granularity);
 
         // Store the configured input map for the spawn system
         app.insert_resource(PendingInputMap(self.input_map.clone()));
@@ -152,6 +305,10 @@ impl Plugin for CodeEditorPlugin {
             Update,
             (
                 crate::input::handle_keyboard_input,
+                crate::input::handle_custom_actions,
+                (crate::input::track_diagnostic_edits, crate::input::handle_diagnostic_navigation)
+                    .chain()
+                    .after(crate::input::handle_keyboard_input),
                 debounce_updates,
             ).in_set(InputSet),
         );
@@ -160,6 +317,18 @@ impl Plugin for CodeEditorPlugin {
         // These events are emitted by keybindings and should be handled by the host application
         app.add_message::<SaveRequested>();
         app.add_message::<OpenRequested>();
+        app.add_message::<crate::events::ContentChangedEvent>();
+        app.add_message::<crate::events::BufferChanged>();
+
+        #[cfg(feature = "file-io")]
+        {
+            app.add_message::<FileLoadFailed>();
+            if let Some(path) = &self.file {
+                app.insert_resource(file_io::EditorFile::new(path.clone()));
+                app.add_systems(Startup, file_io::start_file_load.after(EditorSetupSet));
+                app.add_systems(Update, file_io::poll_file_load.in_set(InputSet));
+            }
+        }
 
         // Add rendering resources
         app.insert_resource(ClearColor(self.settings.theme.background));
@@ -188,7 +357,7 @@ impl Plugin for CodeEditorPlugin {
         app.add_systems(
             Update,
             (
-                crate::input::handle_mouse_input.run_if(mouse_not_over_scrollbar),
+                crate::input::handle_mouse_input.run_if(mouse_not_over_scrollbar.and(mouse_not_over_overview_ruler)),
                 crate::input::handle_mouse_wheel,
             )
                 .chain()
@@ -199,19 +368,29 @@ impl Plugin for CodeEditorPlugin {
         app.add_systems(
             Update,
             (
+                (publish_content_changes, publish_buffer_changes),
+                apply_search_settings,
+                apply_tab_width,
+                detect_viewport_resize,
+                measure_char_width,
+                update_editor_metrics,
+                update_wrap_layout,
+                update_max_content_width,
                 animate_smooth_scroll,
                 auto_scroll_to_cursor,
-                detect_viewport_resize,
+                animate_view_transition,
                 update_separator_on_resize,
             )
                 .chain()
                 .in_set(ApplyStateSet),
         );
+        app.add_systems(Update, update_status_overlay.in_set(ApplyStateSet));
         // Rendering systems - update visuals based on state
         app.add_systems(
             Update,
             (
                 detect_foldable_regions,
+                move_cursors_out_of_hidden_lines,
                 // Note: handle_scroll_for_gpu_text removed - per-line renderer handles scroll natively
                 update_gpu_text_per_line,  // NEW: Per-line mesh system for incremental updates
             )
@@ -294,6 +473,55 @@ fn debounce_updates(mut state: ResMut<CodeEditorState>, time: Res<Time>) {
     }
 }
 
+/// Copy the match cap and live/on-demand mode from `SearchSettings` into `FindState`
+fn apply_search_settings(search: Res<SearchSettings>, mut find_state: ResMut<FindState>) {
+    if search.is_changed() {
+        find_state.max_matches = search.max_matches;
+        find_state.on_demand = !search.live_highlight;
+    }
+}
+
+/// Copy `IndentationSettings::tab_width` into the display map's tab stops
+///
+/// Glyphs, the cursor, selections and mouse hit-testing all expand tabs
+/// through the same `TabMap`, so they move together when the width changes.
+fn apply_tab_width(indentation: Res<IndentationSettings>, mut state: ResMut<CodeEditorState>) {
+    let tab_size = indentation.tab_width.max(1) as u32;
+    if indentation.is_changed() && state.display_map.tab_map.tab_size() != tab_size {
+        state.display_map.set_tab_size(tab_size);
+        state.dirty_lines = None;
+        state.needs_update = true;
+    }
+}
+
+/// Publish this frame's text changes for external sync consumers
+fn publish_content_changes(
+    mut state: ResMut<CodeEditorState>,
+    granularity: Res<ChangeGranularity>,
+    mut writer: MessageWriter<crate::events::ContentChangedEvent>,
+) {
+    if state.pending_changes.is_empty() {
+        return;
+    }
+
+    let changes = state.take_changes(*granularity);
+    writer.write(crate::events::ContentChangedEvent {
+        changes,
+        content_version: state.content_version,
+    });
+}
+
+/// Send one `BufferChanged` message per edit made this frame
+fn publish_buffer_changes(
+    mut state: ResMut<CodeEditorState>,
+    mut writer: MessageWriter<crate::events::BufferChanged>,
+) {
+    if state.pending_buffer_changes.is_empty() {
+        return;
+    }
+    writer.write_batch(state.take_buffer_changes());
+}
+
 /// Initialize viewport dimensions from the actual window size
 fn init_viewport_from_window(
     mut viewport: ResMut<ViewportDimensions>,
@@ -306,6 +534,9 @@ fn init_viewport_from_window(
 }
 
 /// Detect viewport resize and trigger position update
+///
+/// Runs before `update_wrap_layout`, so viewport-width wrapping follows a
+/// resize in the same frame.
 fn detect_viewport_resize(
     mut viewport: ResMut<ViewportDimensions>,
     windows: Query<&Window>,
@@ -0,0 +1,389 @@
+//! Overview ruler: a thin strip along the right edge with density marks
+//!
+//! The whole buffer is mapped onto the viewport height, so each diagnostic,
+//! search match and edited line gets a mark at its relative position in the
+//! file. Clicking a mark scrolls the editor to it.
+
+use std::collections::BTreeSet;
+
+use bevy::prelude::*;
+use ropey::Rope;
+use crate::settings::*;
+use crate::types::*;
+
+/// Mark height in pixels; taller marks are used when lines are sparse
+const MIN_MARK_HEIGHT: f32 = 2.0;
+
+/// How close (in pixels) a click must be to a mark to jump to it
+const CLICK_TOLERANCE: f32 = 4.0;
+
+/// Most selection occurrences marked at once
+const MAX_OCCURRENCE_MARKS: usize = 10_000;
+
+/// What an overview ruler mark represents
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum OverviewMarkKind {
+    Error,
+    Warning,
+    Info,
+    Match,
+    Occurrence,
+    Change,
+}
+
+impl OverviewMarkKind {
+    fn from_level(level: DiagnosticLevel) -> Self {
+        match level {
+            DiagnosticLevel::Error => Self::Error,
+            DiagnosticLevel::Warning => Self::Warning,
+            DiagnosticLevel::Information | DiagnosticLevel::Hint => Self::Info,
+        }
+    }
+
+    fn color(self, theme: &ThemeSettings) -> Color {
+        match self {
+            Self::Error => Color::srgb(0.9, 0.3, 0.3),
+            Self::Warning => Color::srgb(0.9, 0.75, 0.3),
+            Self::Info => Color::srgb(0.4, 0.6, 0.9),
+            Self::Match => theme.find_match.with_alpha(1.0),
+            Self::Occurrence => theme.selection_background.with_alpha(1.0),
+            Self::Change => theme.line_numbers,
+        }
+    }
+}
+
+/// A single mark on the overview ruler
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct OverviewMark {
+    /// Buffer line the mark points at
+    pub line: usize,
+    /// Vertical position as a fraction of the ruler height (0.0 = top)
+    pub fraction: f32,
+    /// What the mark represents
+    pub kind: OverviewMarkKind,
+}
+
+/// Compute overview ruler marks for diagnostics, search matches, selection
+/// occurrences and changed lines
+///
+/// Marks are returned in draw order: changes, occurrences, matches, then
+/// diagnostics, so diagnostics stay visible where they share a line with other marks.
+pub(crate) fn overview_marks(
+    rope: &Rope,
+    diagnostics: &EditorDiagnostics,
+    find_state: &FindState,
+    occurrences: &[std::ops::Range<usize>],
+    changed_lines: &BTreeSet<usize>,
+) -> Vec<OverviewMark> {
+    let line_count = rope.len_lines().max(1);
+    let len_chars = rope.len_chars();
+    let mark = |line: usize, kind| OverviewMark {
+        line,
+        fraction: line as f32 / line_count as f32,
+        kind,
+    };
+
+    let mut seen = std::collections::HashSet::new();
+    let mut marks = Vec::new();
+
+    for diagnostic in diagnostics.items() {
+        let line = rope.char_to_line(diagnostic.range.start.min(len_chars));
+        let kind = OverviewMarkKind::from_level(diagnostic.level);
+        if seen.insert((line, kind)) {
+            marks.push(mark(line, kind));
+        }
+    }
+
+    if find_state.active {
+        for find_match in &find_state.matches {
+            let line = rope.char_to_line(find_match.start.min(len_chars));
+            if seen.insert((line, OverviewMarkKind::Match)) {
+                marks.push(mark(line, OverviewMarkKind::Match));
+            }
+        }
+    }
+
+    for occurrence in occurrences {
+        let line = rope.char_to_line(occurrence.start.min(len_chars));
+        if seen.insert((line, OverviewMarkKind::Occurrence)) {
+            marks.push(mark(line, OverviewMarkKind::Occurrence));
+        }
+    }
+
+    for &line in changed_lines.range(..line_count) {
+        marks.push(mark(line, OverviewMarkKind::Change));
+    }
+
+    marks.reverse();
+    marks
+}
+
+/// Map the edited char ranges through a change replacing `start..end` with
+/// `new_len` characters, then add the changed text itself
+///
+/// Ranges stay sorted, and overlapping or touching ones are merged.
+fn record_changed_range(ranges: &mut Vec<std::ops::Range<usize>>, start: usize, end: usize, new_len: usize) {
+    let shift = |pos: usize| {
+        if pos >= end {
+            pos - (end - start) + new_len
+        } else {
+            pos.min(start)
+        }
+    };
+    for range in ranges.iter_mut() {
+        *range = shift(range.start)..shift(range.end);
+    }
+    ranges.push(start..start + new_len);
+    ranges.sort_by_key(|range| range.start);
+
+    let mut merged: Vec<std::ops::Range<usize>> = Vec::with_capacity(ranges.len());
+    for range in ranges.drain(..) {
+        match merged.last_mut() {
+            Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+            _ => merged.push(range),
+        }
+    }
+    *ranges = merged;
+}
+
+/// Draw the overview ruler marks
+pub(crate) fn update_overview_ruler(
+    mut commands: Commands,
+    state: Res<CodeEditorState>,
+    (ui, theme): (Res<UiSettings>, Res<ThemeSettings>),
+    viewport: Res<ViewportDimensions>,
+    diagnostics: Res<EditorDiagnostics>,
+    find_state: Res<FindState>,
+    mut changes: MessageReader<crate::events::ContentChangedEvent>,
+    mut changed_ranges: Local<Vec<std::ops::Range<usize>>>,
+    mut occurrence_cache: Local<(Option<(u64, Option<usize>, Option<usize>)>, Vec<std::ops::Range<usize>>)>,
+    mut mark_query: Query<(Entity, &mut OverviewRulerMark, &mut Transform, &mut Sprite, &mut Visibility)>,
+) {
+    // Remember which text was edited, following it through later edits
+    for event in changes.read() {
+        for change in &event.changes {
+            record_changed_range(&mut changed_ranges, change.start, change.end, change.new_text.chars().count());
+        }
+    }
+
+    if !state.is_changed()
+        && !viewport.is_changed()
+        && !diagnostics.is_changed()
+        && !find_state.is_changed()
+        && !ui.is_changed()
+        && !theme.is_changed()
+    {
+        return;
+    }
+
+    let settings = &ui.overview_ruler;
+    if !settings.enabled {
+        for (_, _, _, _, mut visibility) in mark_query.iter_mut() {
+            *visibility = Visibility::Hidden;
+        }
+        return;
+    }
+
+    let viewport_width = viewport.width as f32;
+    let viewport_height = viewport.height as f32;
+    let line_count = state.rope.len_lines().max(1);
+    let mark_height = (viewport_height / line_count as f32).max(MIN_MARK_HEIGHT);
+    let center_x = viewport.offset_x + viewport_width / 2.0 - settings.width / 2.0;
+
+    // Occurrences only change with the text or the selection
+    let (cache_key, occurrences) = &mut *occurrence_cache;
+    let key = (state.content_version, state.selection_start, state.selection_end);
+    if !settings.selection_occurrences {
+        occurrences.clear();
+        *cache_key = None;
+    } else if *cache_key != Some(key) {
+        *occurrences = state.selection_occurrences(MAX_OCCURRENCE_MARKS);
+        *cache_key = Some(key);
+    }
+
+    let len_chars = state.rope.len_chars();
+    let changed_lines: BTreeSet<usize> = changed_ranges
+        .iter()
+        .flat_map(|range| {
+            let start = state.rope.char_to_line(range.start.min(len_chars));
+            let end = state.rope.char_to_line(range.end.min(len_chars));
+            start..=end
+        })
+        .collect();
+    let marks = overview_marks(&state.rope, &diagnostics, &find_state, occurrences, &changed_lines);
+
+    let mut existing = mark_query.iter_mut();
+    for (index, mark) in marks.iter().enumerate() {
+        let y = viewport_height / 2.0 - mark.fraction * viewport_height - mark_height / 2.0;
+        let translation = Vec3::new(center_x, y, 1.6 + index as f32 * 0.00001);
+        let size = Vec2::new(settings.width, mark_height);
+        let color = mark.kind.color(&theme);
+
+        if let Some((_, mut marker, mut transform, mut sprite, mut visibility)) = existing.next() {
+            marker.line = mark.line;
+            transform.translation = translation;
+            sprite.color = color;
+            sprite.custom_size = Some(size);
+            *visibility = Visibility::Visible;
+        } else {
+            commands.spawn((
+                Sprite {
+                    color,
+                    custom_size: Some(size),
+                    ..default()
+                },
+                Transform::from_translation(translation),
+                OverviewRulerMark { line: mark.line },
+                Name::new("OverviewRulerMark"),
+                Visibility::Visible,
+            ));
+        }
+    }
+
+    for (_, _, _, _, mut visibility) in existing {
+        *visibility = Visibility::Hidden;
+    }
+}
+
+/// Whether window x coordinate `x` falls on the overview ruler strip
+fn ruler_contains_x(x: f32, settings: &OverviewRulerSettings, viewport: &ViewportDimensions) -> bool {
+    let right = viewport.offset_x + viewport.width as f32;
+    x >= right - settings.width && x <= right
+}
+
+/// Check that the mouse is not over the overview ruler (used as a run
+/// condition), so clicks on the ruler never move the text cursor
+pub(crate) fn mouse_not_over_overview_ruler(
+    windows: Query<&Window>,
+    ui: Res<UiSettings>,
+    viewport: Res<ViewportDimensions>,
+) -> bool {
+    let Some(cursor_pos) = windows.single().ok().and_then(|window| window.cursor_position()) else {
+        return true;
+    };
+    !ui.overview_ruler.enabled || !ruler_contains_x(cursor_pos.x, &ui.overview_ruler, &viewport)
+}
+
+/// Scroll to the mark under the mouse when the overview ruler is clicked
+pub(crate) fn handle_overview_ruler_click(
+    windows: Query<&Window>,
+    mouse_button: Res<ButtonInput<MouseButton>>,
+    mut state: ResMut<CodeEditorState>,
+    ui: Res<UiSettings>,
+    (font, scrolling): (Res<FontSettings>, Res<ScrollingSettings>),
+    viewport: Res<ViewportDimensions>,
+    fold_state: Res<FoldState>,
+    mark_query: Query<(&OverviewRulerMark, &Visibility)>,
+) {
+    let settings = &ui.overview_ruler;
+    if !settings.enabled || !mouse_button.just_pressed(MouseButton::Left) {
+        return;
+    }
+
+    let Ok(window) = windows.single() else {
+        return;
+    };
+    let Some(cursor_pos) = window.cursor_position() else {
+        return;
+    };
+
+    let viewport_height = viewport.height as f32;
+    if !ruler_contains_x(cursor_pos.x, settings, &viewport) || viewport_height <= 0.0 {
+        return;
+    }
+
+    let line_count = state.rope.len_lines().max(1);
+    let pixels_per_line = viewport_height / line_count as f32;
+    let nearest = mark_query
+        .iter()
+        .filter(|(_, visibility)| **visibility != Visibility::Hidden)
+        .map(|(mark, _)| {
+            let mark_y = mark.line as f32 * pixels_per_line + pixels_per_line.max(MIN_MARK_HEIGHT) / 2.0;
+            (mark.line, (mark_y - cursor_pos.y).abs())
+        })
+        .filter(|(_, distance)| *distance <= CLICK_TOLERANCE + pixels_per_line.max(MIN_MARK_HEIGHT) / 2.0)
+        .min_by(|a, b| a.1.total_cmp(&b.1));
+    let Some((line, _)) = nearest else {
+        return;
+    };
+
+    // Center the marked line in the viewport
+    let line_height = font.line_height;
+    let display_row = fold_state.actual_to_display_line(line);
+    let visible_rows = line_count.saturating_sub(fold_state.total_hidden_lines());
+    let content_height = visible_rows as f32 * line_height;
+    let max_scroll = scrolling.max_scroll(content_height, viewport_height, viewport.text_area_top, line_height);
+    let target = (viewport_height - line_height) / 2.0 - viewport.text_area_top - display_row as f32 * line_height;
+    state.target_scroll_offset = target.min(0.0).max(max_scroll);
+    state.needs_scroll_update = true;
+
+    // Keep auto-scroll from snapping back to the cursor
+    state.last_cursor_pos = state.cursor_pos;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diagnostic_mark_at_fractional_height() {
+        let text = "line\n".repeat(99) + "last";
+        let rope = Rope::from_str(&text);
+        assert_eq!(rope.len_lines(), 100);
+
+        let start = rope.line_to_char(50);
+        let mut diagnostics = EditorDiagnostics::default();
+        diagnostics.set(vec![EditorDiagnostic {
+            range: start..start + 4,
+            level: DiagnosticLevel::Error,
+            message: "unused".to_string(),
+        }]);
+
+        let marks = overview_marks(&rope, &diagnostics, &FindState::default(), &[], &BTreeSet::new());
+
+        assert_eq!(marks.len(), 1);
+        assert_eq!(marks[0].line, 50);
+        assert_eq!(marks[0].kind, OverviewMarkKind::Error);
+        assert!((marks[0].fraction - 0.5).abs() < f32::EPSILON);
+    }
+
+    #[test]
+    fn test_changed_ranges_follow_later_edits() {
+        let mut ranges = Vec::new();
+        // Type "ab" at 10, then insert a line of 5 chars before it
+        record_changed_range(&mut ranges, 10, 10, 1);
+        record_changed_range(&mut ranges, 11, 11, 1);
+        assert_eq!(ranges, vec![10..12]);
+        record_changed_range(&mut ranges, 0, 0, 5);
+        assert_eq!(ranges, vec![0..5, 15..17]);
+
+        // Deleting across the start of a range clamps it to the deletion
+        record_changed_range(&mut ranges, 14, 16, 0);
+        assert_eq!(ranges, vec![0..5, 14..15]);
+    }
+
+    #[test]
+    fn test_selection_occurrences_marked_at_each_line() {
+        let mut state = CodeEditorState::new("let foo = 1;\nbar\nfoo();\nbaz(foo)");
+        state.selection_start = Some(4);
+        state.selection_end = Some(7);
+
+        let occurrences = state.selection_occurrences(MAX_OCCURRENCE_MARKS);
+        assert_eq!(occurrences, vec![4..7, 17..20, 27..30]);
+
+        let marks = overview_marks(
+            &state.rope,
+            &EditorDiagnostics::default(),
+            &FindState::default(),
+            &occurrences,
+            &BTreeSet::new(),
+        );
+        let mut fractions: Vec<f32> = marks
+            .iter()
+            .filter(|m| m.kind == OverviewMarkKind::Occurrence)
+            .map(|m| m.fraction)
+            .collect();
+        fractions.sort_by(f32::total_cmp);
+        assert_eq!(fractions, vec![0.0, 0.5, 0.75]);
+    }
+}
@@ -28,16 +28,32 @@ pub struct ScrollbarDragState {
     pub drag_start_y: f32,
     /// Initial scroll offset when drag started
     pub drag_start_scroll: f32,
+    /// Mouse X and horizontal scroll offset when a horizontal scrollbar drag started
+    pub horizontal_drag_start: Option<(f32, f32)>,
 }
 
 /// Check if mouse is over any scrollbar (used as a run condition)
 pub fn mouse_not_over_scrollbar(
     windows: Query<&Window>,
     scrollbar_query: Query<&Scrollbar, With<EditorScrollbar>>,
+    state: Res<crate::types::CodeEditorState>,
+    viewport: Res<crate::types::ViewportDimensions>,
+    (settings, wrapping): (Res<crate::settings::ScrollbarSettings>, Res<crate::settings::WrappingSettings>),
+    drag_state: Res<ScrollbarDragState>,
 ) -> bool {
     let Ok(window) = windows.single() else { return true; };
     let Some(cursor_pos_window) = window.cursor_position() else { return true; };
 
+    // The horizontal scrollbar keeps the mouse for the whole drag
+    if drag_state.horizontal_drag_start.is_some() {
+        return false;
+    }
+    if HorizontalScrollbarLayout::compute(&state, &viewport, &settings, &wrapping)
+        .is_some_and(|layout| layout.contains(cursor_pos_window))
+    {
+        return false;
+    }
+
     // Convert to world coordinates
     let cursor_x = cursor_pos_window.x - window.width() / 2.0;
 
@@ -124,7 +140,7 @@ fn handle_scrollbar_mouse(
     scrollbar_query: Query<(Entity, &Scrollbar)>,
     _track_query: Query<(&ScrollbarTrack, &Transform, &Sprite)>,
     thumb_query: Query<(&ScrollbarThumb, &Transform, &Sprite)>,
-    font: Res<crate::settings::FontSettings>,
+    (font, scrolling): (Res<crate::settings::FontSettings>, Res<crate::settings::ScrollingSettings>),
     viewport: Res<crate::types::ViewportDimensions>,
 ) {
     let Ok(window) = windows.single() else { return; };
@@ -183,11 +199,11 @@ fn handle_scrollbar_mouse(
                     let total_lines = state.line_count();
                     let total_content_height = total_lines as f32 * line_height;
                     let viewport_height = viewport.height as f32;
-                    let max_scroll = -(total_content_height - viewport_height).max(0.0);
+                    let max_scroll = scrolling.max_scroll(total_content_height, viewport_height, viewport.text_area_top, line_height);
 
                     // Scale pixel delta to scroll offset
                     let scroll_delta = (delta_y / scrollable_range) * max_scroll;
-                    let new_scroll_offset = (drag_state.drag_start_scroll + scroll_delta).clamp(max_scroll.min(0.0), 0.0);
+                    let new_scroll_offset = (drag_state.drag_start_scroll + scroll_delta).clamp(max_scroll, 0.0);
 
                     // Only update target - the apply_scroll system will handle actual scroll update
                     // For scrollbar dragging, we want immediate response (no smoothing)
@@ -220,7 +236,7 @@ fn update_scrollbars(
     mut track_query: Query<(Entity, &ScrollbarTrack, &mut Transform, &mut Sprite, &mut Visibility)>,
     mut thumb_query: Query<(Entity, &ScrollbarThumb, &mut Transform, &mut Sprite, &mut Visibility), Without<ScrollbarTrack>>,
     state: Res<crate::types::CodeEditorState>,
-    font: Res<crate::settings::FontSettings>,
+    (font, scrolling): (Res<crate::settings::FontSettings>, Res<crate::settings::ScrollingSettings>),
     viewport: Res<crate::types::ViewportDimensions>,
     drag_state: Res<ScrollbarDragState>,
     mut last_scroll: Local<f32>,
@@ -255,10 +271,10 @@ fn update_scrollbars(
         let total_lines = state.line_count();
         let total_content_height = total_lines as f32 * line_height;
         let viewport_height = viewport.height as f32;
-        let max_scroll = (total_content_height - viewport_height).max(0.0);
+        let max_scroll = scrolling.max_scroll(total_content_height, viewport_height, viewport.text_area_top, line_height);
 
-        let scroll_progress = if max_scroll > 0.0 {
-            (-state.scroll_offset / max_scroll).clamp(0.0, 1.0)
+        let scroll_progress = if max_scroll < 0.0 {
+            (state.scroll_offset / max_scroll).clamp(0.0, 1.0)
         } else {
             0.0
         };
@@ -395,3 +411,268 @@ pub fn update_editor_scrollbar(
     }
 }
 
+
+/// Shortest the horizontal scrollbar thumb gets, in pixels
+const MIN_HORIZONTAL_THUMB_WIDTH: f32 = 30.0;
+
+/// Marker for the horizontal scrollbar track
+#[derive(Component)]
+pub(crate) struct HorizontalScrollbarTrack;
+
+/// Marker for the horizontal scrollbar thumb
+#[derive(Component)]
+pub(crate) struct HorizontalScrollbarThumb;
+
+/// Horizontal scrollbar geometry in window coordinates (origin top-left)
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct HorizontalScrollbarLayout {
+    pub(crate) track_left: f32,
+    pub(crate) track_width: f32,
+    /// Top edge of the track
+    pub(crate) top: f32,
+    pub(crate) thickness: f32,
+    pub(crate) thumb_left: f32,
+    pub(crate) thumb_width: f32,
+    /// Largest `horizontal_scroll_offset` the content allows
+    pub(crate) max_scroll: f32,
+}
+
+impl HorizontalScrollbarLayout {
+    /// Compute the layout, or `None` when the scrollbar should be hidden
+    ///
+    /// The scrollbar runs along the bottom of the text area, stopping short of
+    /// the vertical scrollbar. It is hidden when scrollbars are disabled, lines
+    /// wrap, or every line fits in the text area.
+    pub(crate) fn compute(
+        state: &crate::types::CodeEditorState,
+        viewport: &crate::types::ViewportDimensions,
+        settings: &crate::settings::ScrollbarSettings,
+        wrapping: &crate::settings::WrappingSettings,
+    ) -> Option<Self> {
+        if !settings.enabled || wrapping.enabled {
+            return None;
+        }
+
+        let viewport_width = viewport.width as f32;
+        let available_width = viewport_width - viewport.text_area_left;
+        let max_scroll = state.max_content_width - available_width;
+        if available_width <= 0.0 || max_scroll <= 0.0 {
+            return None;
+        }
+
+        let track_left = viewport.offset_x + viewport.text_area_left;
+        let track_width = (viewport_width - viewport.text_area_left - settings.width).max(0.0);
+        let thumb_width = (track_width * available_width / state.max_content_width)
+            .max(MIN_HORIZONTAL_THUMB_WIDTH)
+            .min(track_width);
+        let progress = (state.horizontal_scroll_offset / max_scroll).clamp(0.0, 1.0);
+
+        Some(Self {
+            track_left,
+            track_width,
+            top: viewport.height as f32 - settings.width,
+            thickness: settings.width,
+            thumb_left: track_left + progress * (track_width - thumb_width),
+            thumb_width,
+            max_scroll,
+        })
+    }
+
+    /// Whether a window position lies on the track
+    pub(crate) fn contains(&self, pos: Vec2) -> bool {
+        pos.x >= self.track_left
+            && pos.x <= self.track_left + self.track_width
+            && pos.y >= self.top
+            && pos.y <= self.top + self.thickness
+    }
+
+    /// Whether a window position lies on the thumb
+    pub(crate) fn thumb_contains(&self, pos: Vec2) -> bool {
+        self.contains(pos) && pos.x >= self.thumb_left && pos.x <= self.thumb_left + self.thumb_width
+    }
+
+    /// Scroll offset that moves the thumb by `delta_x` pixels from `start_scroll`
+    pub(crate) fn scroll_for_drag(&self, start_scroll: f32, delta_x: f32) -> f32 {
+        let range = self.track_width - self.thumb_width;
+        if range <= 0.0 {
+            return start_scroll;
+        }
+        (start_scroll + delta_x / range * self.max_scroll).clamp(0.0, self.max_scroll)
+    }
+
+    /// Scroll offset that centers the thumb on window x `x`
+    pub(crate) fn scroll_for_click(&self, x: f32) -> f32 {
+        let range = self.track_width - self.thumb_width;
+        if range <= 0.0 {
+            return 0.0;
+        }
+        let progress = (x - self.track_left - self.thumb_width / 2.0) / range;
+        progress.clamp(0.0, 1.0) * self.max_scroll
+    }
+}
+
+/// Click-to-jump and drag on the horizontal scrollbar
+///
+/// Only the target offset is set, so smooth scrolling animates the move.
+pub(crate) fn handle_horizontal_scrollbar_mouse(
+    windows: Query<&Window>,
+    mouse_button: Res<ButtonInput<MouseButton>>,
+    mut drag_state: ResMut<ScrollbarDragState>,
+    mut state: ResMut<crate::types::CodeEditorState>,
+    viewport: Res<crate::types::ViewportDimensions>,
+    settings: Res<crate::settings::ScrollbarSettings>,
+    wrapping: Res<crate::settings::WrappingSettings>,
+) {
+    if mouse_button.just_released(MouseButton::Left) {
+        drag_state.horizontal_drag_start = None;
+    }
+
+    let Some(layout) = HorizontalScrollbarLayout::compute(&state, &viewport, &settings, &wrapping) else {
+        drag_state.horizontal_drag_start = None;
+        return;
+    };
+    let Some(cursor_pos) = windows.single().ok().and_then(|window| window.cursor_position()) else {
+        return;
+    };
+
+    if mouse_button.just_pressed(MouseButton::Left) && layout.contains(cursor_pos) {
+        let mut start_scroll = state.horizontal_scroll_offset;
+        if !layout.thumb_contains(cursor_pos) {
+            start_scroll = layout.scroll_for_click(cursor_pos.x);
+            state.target_horizontal_scroll_offset = start_scroll;
+        }
+        drag_state.horizontal_drag_start = Some((cursor_pos.x, start_scroll));
+        state.last_cursor_pos = state.cursor_pos;
+    } else if let Some((start_x, start_scroll)) = drag_state.horizontal_drag_start {
+        if mouse_button.pressed(MouseButton::Left) {
+            state.target_horizontal_scroll_offset = layout.scroll_for_drag(start_scroll, cursor_pos.x - start_x);
+            // Keep auto-scroll from pulling the view back to the cursor
+            state.last_cursor_pos = state.cursor_pos;
+        }
+    }
+}
+
+/// Draw the horizontal scrollbar along the bottom of the text area
+///
+/// Only re-laid out when the horizontal scroll offset, the content width,
+/// the viewport or the settings change.
+pub(crate) fn update_horizontal_scrollbar(
+    mut commands: Commands,
+    state: Res<crate::types::CodeEditorState>,
+    viewport: Res<crate::types::ViewportDimensions>,
+    settings: Res<crate::settings::ScrollbarSettings>,
+    wrapping: Res<crate::settings::WrappingSettings>,
+    mut track_query: Query<(&mut Transform, &mut Sprite, &mut Visibility), With<HorizontalScrollbarTrack>>,
+    mut thumb_query: Query<
+        (&mut Transform, &mut Sprite, &mut Visibility),
+        (With<HorizontalScrollbarThumb>, Without<HorizontalScrollbarTrack>),
+    >,
+    mut last_scroll_and_width: Local<Option<(f32, f32)>>,
+) {
+    let scroll_and_width = (state.horizontal_scroll_offset, state.max_content_width);
+    let settings_changed = viewport.is_changed() || settings.is_changed() || wrapping.is_changed();
+    if !settings_changed && *last_scroll_and_width == Some(scroll_and_width) {
+        return;
+    }
+    *last_scroll_and_width = Some(scroll_and_width);
+
+    let Some(layout) = HorizontalScrollbarLayout::compute(&state, &viewport, &settings, &wrapping) else {
+        for (_, _, mut visibility) in track_query.iter_mut().chain(thumb_query.iter_mut()) {
+            *visibility = Visibility::Hidden;
+        }
+        return;
+    };
+
+    // Window coordinates (origin top-left) to world coordinates (origin center)
+    let viewport_width = viewport.width as f32;
+    let center_y = viewport.height as f32 / 2.0 - layout.top - layout.thickness / 2.0;
+    let center_x = |left: f32, width: f32| -viewport_width / 2.0 + left + width / 2.0;
+
+    place_horizontal_scrollbar_part(
+        &mut commands,
+        track_query.single_mut().ok(),
+        Vec3::new(center_x(layout.track_left, layout.track_width), center_y, 10.0),
+        Vec2::new(layout.track_width, layout.thickness),
+        settings.background_color,
+        (HorizontalScrollbarTrack, Name::new("HorizontalScrollbarTrack")),
+    );
+    place_horizontal_scrollbar_part(
+        &mut commands,
+        thumb_query.single_mut().ok(),
+        Vec3::new(center_x(layout.thumb_left, layout.thumb_width), center_y, 10.1),
+        Vec2::new(layout.thumb_width, layout.thickness),
+        settings.thumb_color,
+        (HorizontalScrollbarThumb, Name::new("HorizontalScrollbarThumb")),
+    );
+}
+
+/// Update an existing track/thumb sprite, or spawn it with `marker`
+fn place_horizontal_scrollbar_part(
+    commands: &mut Commands,
+    existing: Option<(Mut<Transform>, Mut<Sprite>, Mut<Visibility>)>,
+    translation: Vec3,
+    size: Vec2,
+    color: Color,
+    marker: impl Bundle,
+) {
+    if let Some((mut transform, mut sprite, mut visibility)) = existing {
+        transform.translation = translation;
+        sprite.custom_size = Some(size);
+        sprite.color = color;
+        *visibility = Visibility::Visible;
+    } else {
+        commands.spawn((
+            Sprite {
+                color,
+                custom_size: Some(size),
+                ..default()
+            },
+            Transform::from_translation(translation),
+            marker,
+            Visibility::Visible,
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::{ScrollbarSettings, WrappingSettings};
+    use crate::types::{CodeEditorState, ViewportDimensions};
+
+    #[test]
+    fn test_horizontal_scrollbar_thumb_tracks_content_width() {
+        let viewport = ViewportDimensions::default();
+        let settings = ScrollbarSettings::default();
+        let no_wrap = WrappingSettings {
+            enabled: false,
+            ..Default::default()
+        };
+        let mut state = CodeEditorState::new("short\n");
+        let available = viewport.width as f32 - viewport.text_area_left;
+
+        // Content that fits needs no scrollbar
+        state.max_content_width = available - 1.0;
+        assert!(HorizontalScrollbarLayout::compute(&state, &viewport, &settings, &no_wrap).is_none());
+
+        // Twice the visible width: the thumb covers half the track
+        state.max_content_width = available * 2.0;
+        state.horizontal_scroll_offset = available / 2.0;
+        let layout = HorizontalScrollbarLayout::compute(&state, &viewport, &settings, &no_wrap).unwrap();
+        assert_eq!(layout.max_scroll, available);
+        assert!((layout.thumb_width - layout.track_width / 2.0).abs() < 1e-3);
+        assert!((layout.thumb_left - (layout.track_left + layout.thumb_width / 2.0)).abs() < 1e-3);
+
+        // Dragging the thumb across its free range scrolls the full distance
+        assert!((layout.scroll_for_drag(0.0, layout.track_width - layout.thumb_width) - available).abs() < 1e-3);
+        assert_eq!(layout.scroll_for_click(layout.track_left), 0.0);
+        assert_eq!(layout.scroll_for_click(layout.track_left + layout.track_width), available);
+
+        // Wrapped text never scrolls horizontally
+        let wrap = WrappingSettings {
+            enabled: true,
+            ..Default::default()
+        };
+        assert!(HorizontalScrollbarLayout::compute(&state, &viewport, &settings, &wrap).is_none());
+    }
+}
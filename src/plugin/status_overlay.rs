@@ -0,0 +1,163 @@
+//! Status overlay: transient editor state gathered in one place
+//!
+//! `EditorStatusOverlay` is refreshed every frame from the macro recorder,
+//! the cursors and the active search, so a host can draw a status bar from a
+//! single resource. `EditorUiPlugin` draws a minimal built-in version in the
+//! bottom-right corner when `UiSettings::show_status_overlay` is set.
+
+use bevy::prelude::*;
+use crate::input::MacroRecorder;
+use crate::settings::*;
+use crate::types::*;
+
+/// Distance (in pixels) of the built-in overlay from the viewport corner
+const OVERLAY_PADDING: f32 = 8.0;
+
+/// Read-only summary of transient editor state for status displays
+#[derive(Resource, Clone, Debug, Default, PartialEq)]
+pub struct EditorStatusOverlay {
+    /// A keyboard macro is being recorded
+    pub macro_recording: bool,
+    /// Steps in the macro being recorded (or the last one recorded)
+    pub macro_steps: usize,
+    /// Number of active cursors
+    pub cursor_count: usize,
+    /// Editing mode label owned by the host (e.g. a Vim mode); never changed by the editor
+    pub mode: Option<String>,
+    /// Matches for the active search, `None` while find is closed
+    pub search_matches: Option<usize>,
+    /// The search stopped at `FindState::max_matches`, so there may be more
+    pub search_truncated: bool,
+}
+
+impl EditorStatusOverlay {
+    /// One-line summary, e.g. `REC · 3 cursors · 12 matches`; empty when idle
+    pub fn summary(&self) -> String {
+        let mut parts = Vec::new();
+        if let Some(mode) = &self.mode {
+            parts.push(mode.clone());
+        }
+        if self.macro_recording {
+            parts.push("REC".to_string());
+        }
+        if self.cursor_count > 1 {
+            parts.push(format!("{} cursors", self.cursor_count));
+        }
+        if let Some(count) = self.search_matches {
+            let more = if self.search_truncated { "+" } else { "" };
+            let noun = if count == 1 && !self.search_truncated { "match" } else { "matches" };
+            parts.push(format!("{}{} {}", count, more, noun));
+        }
+        parts.join(" · ")
+    }
+}
+
+/// Refresh the overlay from the editor state, keeping the host-owned `mode`
+pub(crate) fn update_status_overlay(
+    state: Res<CodeEditorState>,
+    recorder: Res<MacroRecorder>,
+    find_state: Res<FindState>,
+    mut overlay: ResMut<EditorStatusOverlay>,
+) {
+    let updated = EditorStatusOverlay {
+        macro_recording: recorder.recording,
+        macro_steps: recorder.steps.len(),
+        cursor_count: state.cursors.len().max(1),
+        mode: overlay.mode.clone(),
+        search_matches: find_state.active.then_some(find_state.matches.len()),
+        search_truncated: find_state.active && find_state.truncated,
+    };
+    overlay.set_if_neq(updated);
+}
+
+/// Draw the built-in overlay text in the bottom-right corner of the viewport
+pub(crate) fn update_status_overlay_text(
+    mut commands: Commands,
+    overlay: Res<EditorStatusOverlay>,
+    (ui, theme, font): (Res<UiSettings>, Res<ThemeSettings>, Res<FontSettings>),
+    viewport: Res<ViewportDimensions>,
+    mut text_query: Query<(&mut Text2d, &mut Transform, &mut Visibility, &mut TextColor), With<StatusOverlayText>>,
+) {
+    if !overlay.is_changed() && !ui.is_changed() && !theme.is_changed() && !font.is_changed() && !viewport.is_changed() {
+        return;
+    }
+
+    let summary = if ui.show_status_overlay { overlay.summary() } else { String::new() };
+    if summary.is_empty() {
+        for (_, _, mut visibility, _) in text_query.iter_mut() {
+            *visibility = Visibility::Hidden;
+        }
+        return;
+    }
+
+    // Text2d is centered on its translation, so offset by half the (monospace) width
+    let width = summary.chars().count() as f32 * font.char_width;
+    let translation = Vec3::new(
+        viewport.width as f32 / 2.0 - OVERLAY_PADDING - width / 2.0,
+        -(viewport.height as f32) / 2.0 + OVERLAY_PADDING + font.line_height / 2.0,
+        5.0,
+    );
+
+    if let Some((mut text, mut transform, mut visibility, mut color)) = text_query.iter_mut().next() {
+        text.0 = summary;
+        transform.translation = translation;
+        color.0 = theme.line_numbers;
+        *visibility = Visibility::Visible;
+    } else {
+        commands.spawn((
+            Text2d::new(summary),
+            TextFont {
+                font: font.handle.clone().unwrap_or_default(),
+                font_size: font.size,
+                ..default()
+            },
+            TextColor(theme.line_numbers),
+            Transform::from_translation(translation),
+            StatusOverlayText,
+            Name::new("StatusOverlayText"),
+            Visibility::Visible,
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::MacroStep;
+
+    #[test]
+    fn test_overlay_tracks_macro_recording_and_cursors() {
+        let mut app = App::new();
+        app.insert_resource(CodeEditorState::new("foo\nfoo\nfoo\n"));
+        app.init_resource::<MacroRecorder>();
+        app.init_resource::<FindState>();
+        app.init_resource::<EditorStatusOverlay>();
+        app.add_systems(Update, update_status_overlay);
+
+        app.update();
+        let overlay = app.world().resource::<EditorStatusOverlay>();
+        assert!(!overlay.macro_recording);
+        assert_eq!(overlay.cursor_count, 1);
+        assert_eq!(overlay.summary(), "");
+
+        {
+            let mut recorder = app.world_mut().resource_mut::<MacroRecorder>();
+            recorder.start();
+            recorder.record(MacroStep::Char('x'));
+        }
+        {
+            let mut state = app.world_mut().resource_mut::<CodeEditorState>();
+            state.add_cursor(4);
+            state.add_cursor(8);
+        }
+        app.world_mut().resource_mut::<EditorStatusOverlay>().mode = Some("INSERT".to_string());
+        app.update();
+
+        let overlay = app.world().resource::<EditorStatusOverlay>();
+        assert!(overlay.macro_recording);
+        assert_eq!(overlay.macro_steps, 1);
+        assert_eq!(overlay.cursor_count, 3);
+        assert_eq!(overlay.search_matches, None);
+        assert_eq!(overlay.summary(), "INSERT · REC · 3 cursors");
+    }
+}
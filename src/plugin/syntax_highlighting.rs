@@ -6,6 +6,7 @@
 use bevy::prelude::*;
 use std::collections::VecDeque;
 use crate::syntax::{SyntaxProvider, TreeSitterProvider};
+use crate::settings::FontStyle;
 use crate::types::{LineSegment, CodeEditorState};
 
 /// Resource that holds the syntax highlighting provider
@@ -85,6 +86,7 @@ impl SyntaxResource {
                         vec![crate::types::LineSegment {
                             text: line.to_string(),
                             color: default_color,
+                            style: FontStyle::Regular,
                         }]
                     }
                 })
@@ -92,6 +94,47 @@ impl SyntaxResource {
         }
     }
 
+    /// Whether a markdown fence language names the document's language;
+    /// an empty fence language counts as the document's
+    pub fn matches_language(&self, language: &str) -> bool {
+        if language.is_empty() {
+            return true;
+        }
+
+        #[cfg(feature = "tree-sitter")]
+        {
+            self.provider
+                .as_ref()
+                .and_then(|p| p.language_name())
+                .is_some_and(|name| name.eq_ignore_ascii_case(language))
+        }
+
+        #[cfg(not(feature = "tree-sitter"))]
+        {
+            false
+        }
+    }
+
+    /// Highlight a standalone code snippet (e.g. a hover code block) in the
+    /// document's language; `None` when no highlighter is configured
+    pub fn highlight_snippet(
+        &mut self,
+        text: &str,
+        theme: &crate::settings::SyntaxTheme,
+        default_color: Color,
+    ) -> Option<Vec<Vec<LineSegment>>> {
+        #[cfg(feature = "tree-sitter")]
+        {
+            self.provider.as_mut()?.highlight_snippet(text, theme, default_color)
+        }
+
+        #[cfg(not(feature = "tree-sitter"))]
+        {
+            let _ = (text, theme, default_color);
+            None
+        }
+    }
+
     /// Invalidate the tree-sitter tree (like Zed does when content changes)
     #[cfg(feature = "tree-sitter")]
     pub fn invalidate_tree(&mut self) {
@@ -5,6 +5,9 @@ use crate::settings::*;
 use crate::types::*;
 use super::to_bevy_coords_left_aligned;
 
+/// Gutter marker drawn for rows past the end of the buffer
+const EOB_MARKER: &str = "~";
+
 pub(crate) fn update_line_numbers(
     mut commands: Commands,
     state: Res<CodeEditorState>,
@@ -14,6 +17,7 @@ pub(crate) fn update_line_numbers(
     wrapping: Res<WrappingSettings>,
     performance: Res<PerformanceSettings>,
     viewport: Res<ViewportDimensions>,
+    gutter: Res<GutterColumns>,
     fold_state: Res<FoldState>,
     mut line_numbers_query: Query<(&mut Text2d, &mut Transform, &mut Visibility, &mut TextColor), With<LineNumbers>>,
 ) {
@@ -25,7 +29,7 @@ pub(crate) fn update_line_numbers(
         return;
     }
 
-    if !state.is_changed() && !fold_state.is_changed() {
+    if !state.is_changed() && !fold_state.is_changed() && !gutter.is_changed() {
         return;
     }
 
@@ -56,6 +60,8 @@ pub(crate) fn update_line_numbers(
         ((viewport_bottom - viewport.text_area_top) / line_height).ceil() as usize;
 
     let total_buffer_lines = state.line_count();
+    let unnumbered_line = (!ui.number_phantom_line && state.has_phantom_line())
+        .then(|| total_buffer_lines - 1);
 
     let mut existing_line_numbers: Vec<_> = line_numbers_query.iter_mut().collect();
     let mut entity_index = 0;
@@ -64,7 +70,11 @@ pub(crate) fn update_line_numbers(
     let has_folding = !fold_state.regions.is_empty();
 
     // Calculate starting buffer line and display row
-    let (start_buffer_line, mut current_display_row) = if has_folding {
+    let (start_buffer_line, mut current_display_row) = if use_wrapping {
+        // Wrapped lines take a row per wrapped row; start at the line owning the first visible row
+        let (line, first_row) = state.display_map.line_at_row(first_visible_display_row);
+        (line.min(total_buffer_lines), first_row)
+    } else if has_folding {
         // With folding, we need to iterate to find the right buffer line
         let mut display_row = 0;
         let mut buffer_line = 0;
@@ -81,28 +91,31 @@ pub(crate) fn update_line_numbers(
         (start, start)
     };
 
+    // Center line numbers in their gutter column
+    let line_number_x = gutter
+        .column(&GutterColumnKind::LineNumbers)
+        .map(|column| column.center_x())
+        .unwrap_or(viewport.gutter_width / 2.0);
+
     // Iterate over buffer lines starting from visible area
     for buffer_line in start_buffer_line..total_buffer_lines {
-        // Skip lines that are hidden due to folding
+        // Continuation rows stay unnumbered, so a wrapped line moves the next number down
+        let row_count = if use_wrapping { state.display_map.line_rows(buffer_line).len().max(1) } else { 1 };
+
+        // Skip lines that are hidden due to folding; wrapped rows keep their place, as for the text
         if fold_state.is_line_hidden(buffer_line) {
+            if use_wrapping {
+                current_display_row += row_count;
+            }
             continue;
         }
 
-        // For wrapped mode, handle continuation rows
-        let is_continuation = if use_wrapping {
-            // In wrapped mode, we need to check if this display row is a continuation
-            // For now, we'll use the simpler approach without wrapping for folded content
-            false
-        } else {
-            false
-        };
-
         // All lines from start_buffer_line should be in or after visible range
         if current_display_row <= last_visible_display_row {
             // Calculate Y position based on display row (not buffer line)
             let y = viewport.text_area_top + state.scroll_offset + (current_display_row as f32 * line_height);
             let translation = to_bevy_coords_left_aligned(
-                viewport.gutter_width / 2.0,  // Center line numbers in gutter area
+                line_number_x,
                 y,
                 viewport.width as f32,
                 viewport.height as f32,
@@ -110,9 +123,8 @@ pub(crate) fn update_line_numbers(
                 0.0,  // line numbers don't scroll horizontally
             );
 
-            // For continuation rows, show empty or continuation indicator
-            let line_number_text = if is_continuation {
-                // Show nothing or a continuation indicator for wrapped lines
+            let line_number_text = if unnumbered_line == Some(buffer_line) {
+                // Show nothing for an unnumbered phantom line
                 String::new()
             } else {
                 // Show actual buffer line number (1-indexed)
@@ -154,7 +166,7 @@ pub(crate) fn update_line_numbers(
             entity_index += 1;
         }
 
-        current_display_row += 1;
+        current_display_row += row_count;
 
         // Early exit if we've passed the visible area
         if current_display_row > last_visible_display_row {
@@ -162,6 +174,51 @@ pub(crate) fn update_line_numbers(
         }
     }
 
+    // End-of-buffer markers for the empty rows below the last line
+    if ui.show_eob_markers {
+        let first_eob_row = current_display_row.max(first_visible_display_row);
+        for display_row in first_eob_row..=last_visible_display_row {
+            let y = viewport.text_area_top + state.scroll_offset + (display_row as f32 * line_height);
+            if y > viewport.height as f32 {
+                break;
+            }
+
+            let translation = to_bevy_coords_left_aligned(
+                line_number_x,
+                y,
+                viewport.width as f32,
+                viewport.height as f32,
+                viewport.offset_x,
+                0.0,
+            );
+
+            if entity_index < existing_line_numbers.len() {
+                let (ref mut text, ref mut transform, ref mut visibility, ref mut text_color) =
+                    &mut existing_line_numbers[entity_index];
+                text.0 = EOB_MARKER.to_string();
+                transform.translation = translation;
+                text_color.0 = theme.line_numbers;
+                **visibility = Visibility::Visible;
+            } else {
+                commands.spawn((
+                    Text2d::new(EOB_MARKER),
+                    TextFont {
+                        font: font.handle.clone().unwrap_or_default(),
+                        font_size,
+                        ..default()
+                    },
+                    TextColor(theme.line_numbers),
+                    Transform::from_translation(translation),
+                    LineNumbers,
+                    Name::new(format!("EobMarker_{}", display_row)),
+                    Visibility::Visible,
+                ));
+            }
+
+            entity_index += 1;
+        }
+    }
+
     // Hide unused line numbers
     for i in entity_index..existing_line_numbers.len() {
         let (_, _, ref mut visibility, _) = &mut existing_line_numbers[i];
@@ -169,93 +226,134 @@ pub(crate) fn update_line_numbers(
     }
 }
 
-/// Update selection highlight rectangles for all cursors
-pub(crate) fn update_selection_highlight(
-    mut commands: Commands,
-    state: Res<CodeEditorState>,
-    font: Res<FontSettings>,
-    theme: Res<ThemeSettings>,
-    wrapping: Res<WrappingSettings>,
-    indentation: Res<IndentationSettings>,
-    viewport: Res<ViewportDimensions>,
-    fold_state: Res<FoldState>,
-    mut selection_query: Query<(
-        Entity,
-        &mut Transform,
-        &mut Sprite,
-        &mut Visibility,
-        &mut SelectionHighlight,
-    )>,
+/// One highlighted row segment of a selection
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct SelectionRect {
+    cursor_index: usize,
+    display_row: usize,
+    start_col: usize,
+    end_col: usize,
+    is_continuation: bool,
+    /// Buffer line this row belongs to
+    buffer_line: usize,
+    /// Column within the buffer line where this display row starts
+    row_offset: usize,
+    /// The selection continues past this row's line break, so a small
+    /// highlight is drawn past end-of-line to show the newline is selected
+    includes_newline: bool,
+}
+
+impl SelectionRect {
+    /// Screen column of `col` (chars into this row) with tabs expanded to tab stops
+    fn visual_col(&self, state: &CodeEditorState, col: usize) -> usize {
+        let row_chars = state.rope.line(self.buffer_line).chars_at(self.row_offset);
+        state.display_map.tab_map.expand_column_chars(row_chars, col as u32) as usize
+    }
+}
+
+/// Split the selection `start..end` (char offsets) into per-row highlight rects
+fn collect_selection_rects(
+    state: &CodeEditorState,
+    fold_state: &FoldState,
+    use_wrapping: bool,
+    cursor_index: usize,
+    start: usize,
+    end: usize,
+    rects: &mut Vec<SelectionRect>,
 ) {
-    if !state.is_changed() {
+    if start == end {
         return;
     }
 
-    let char_width = font.char_width;
-    let line_height = font.line_height;
-
-    // Check if we're using soft line wrapping
-    let use_wrapping = wrapping.enabled && state.display_map.wrap_width > 0;
-
-    // Collect all selection ranges from all cursors
-    // (cursor_idx, display_row, start_col, end_col, is_continuation)
-    let mut selection_rects: Vec<(usize, usize, usize, usize, bool)> = Vec::new();
+    let start_line = state.rope.char_to_line(start);
+    let end_line = state.rope.char_to_line(end);
 
-    for (cursor_idx, cursor) in state.cursors.iter().enumerate() {
-        if let Some((start, end)) = cursor.selection_range() {
-            if start == end {
-                continue;
-            }
+    for line_idx in start_line..=end_line {
+        // Skip hidden lines
+        if fold_state.is_line_hidden(line_idx) {
+            continue;
+        }
 
-            let start_line = state.rope.char_to_line(start);
-            let end_line = state.rope.char_to_line(end);
+        let line_start_char = state.rope.line_to_char(line_idx);
+        let line = state.rope.line(line_idx);
+        let line_len = line.len_chars();
+        let text_len = if line_len > 0 && line.char(line_len - 1) == '\n' {
+            line_len - 1
+        } else {
+            line_len
+        };
 
-            for line_idx in start_line..=end_line {
-                // Skip hidden lines
-                if fold_state.is_line_hidden(line_idx) {
-                    continue;
-                }
+        let sel_start_in_line = if line_idx == start_line {
+            start - line_start_char
+        } else {
+            0
+        };
 
-                let line_start_char = state.rope.line_to_char(line_idx);
-                let line = state.rope.line(line_idx);
+        let sel_end_in_line = if line_idx == end_line {
+            end - line_start_char
+        } else {
+            text_len
+        };
 
-                let sel_start_in_line = if line_idx == start_line {
-                    start - line_start_char
-                } else {
-                    0
-                };
+        // Every line but the last has its line break selected
+        let includes_newline = line_idx < end_line && text_len < line_len;
 
-                let sel_end_in_line = if line_idx == end_line {
-                    end - line_start_char
-                } else {
-                    line.len_chars()
-                };
+        if sel_start_in_line >= sel_end_in_line && !includes_newline {
+            continue;
+        }
 
-                if sel_start_in_line < sel_end_in_line {
-                    if use_wrapping {
-                        // For wrapped mode, split selection across display rows
-                        for (row_idx, row) in state.display_map.rows.iter().enumerate() {
-                            if row.buffer_line != line_idx {
-                                continue;
-                            }
-                            // Calculate overlap between selection and this row
-                            let row_sel_start = sel_start_in_line.max(row.start_offset);
-                            let row_sel_end = sel_end_in_line.min(row.end_offset);
-
-                            if row_sel_start < row_sel_end {
-                                // Convert to display column (relative to row start)
-                                let display_start = row_sel_start - row.start_offset;
-                                let display_end = row_sel_end - row.start_offset;
-                                selection_rects.push((cursor_idx, row_idx, display_start, display_end, row.is_continuation));
-                            }
-                        }
-                    } else {
-                        // Convert buffer line to display row
-                        let display_row = fold_state.actual_to_display_line(line_idx);
-                        selection_rects.push((cursor_idx, display_row, sel_start_in_line, sel_end_in_line, false));
-                    }
+        if use_wrapping {
+            // For wrapped mode, split selection across display rows
+            for (row_idx, row) in state.display_map.rows.iter().enumerate() {
+                if row.buffer_line != line_idx {
+                    continue;
+                }
+                // Calculate overlap between selection and this row
+                let row_sel_start = sel_start_in_line.max(row.start_offset);
+                let row_sel_end = sel_end_in_line.min(row.end_offset);
+                // The newline indicator goes on the line's last display row
+                let row_newline = includes_newline && row.end_offset >= text_len;
+
+                if row_sel_start < row_sel_end || (row_newline && row_sel_start <= row_sel_end) {
+                    rects.push(SelectionRect {
+                        cursor_index,
+                        display_row: row_idx,
+                        start_col: row_sel_start - row.start_offset,
+                        end_col: row_sel_end - row.start_offset,
+                        is_continuation: row.is_continuation,
+                        buffer_line: line_idx,
+                        row_offset: row.start_offset,
+                        includes_newline: row_newline,
+                    });
                 }
             }
+        } else {
+            // Convert buffer line to display row
+            rects.push(SelectionRect {
+                cursor_index,
+                display_row: fold_state.actual_to_display_line(line_idx),
+                start_col: sel_start_in_line,
+                end_col: sel_end_in_line,
+                is_continuation: false,
+                buffer_line: line_idx,
+                row_offset: 0,
+                includes_newline,
+            });
+        }
+    }
+}
+
+/// Collect highlight rects for every cursor's selection
+fn all_selection_rects(
+    state: &CodeEditorState,
+    fold_state: &FoldState,
+    use_wrapping: bool,
+) -> Vec<SelectionRect> {
+    let mut selection_rects: Vec<SelectionRect> = Vec::new();
+
+    for (cursor_idx, cursor) in state.cursors.iter().enumerate() {
+        if let Some((start, end)) = cursor.selection_range() {
+            collect_selection_rects(state, fold_state, use_wrapping, cursor_idx, start, end, &mut selection_rects);
         }
     }
 
@@ -268,57 +366,290 @@ pub(crate) fn update_selection_highlight(
                 (sel_end, sel_start)
             };
 
-            if start != end && selection_rects.is_empty() {
-                let start_line = state.rope.char_to_line(start);
-                let end_line = state.rope.char_to_line(end);
-
-                for line_idx in start_line..=end_line {
-                    // Skip hidden lines
-                    if fold_state.is_line_hidden(line_idx) {
-                        continue;
-                    }
-
-                    let line_start_char = state.rope.line_to_char(line_idx);
-                    let line = state.rope.line(line_idx);
-
-                    let sel_start_in_line = if line_idx == start_line {
-                        start - line_start_char
-                    } else {
-                        0
-                    };
-
-                    let sel_end_in_line = if line_idx == end_line {
-                        end - line_start_char
-                    } else {
-                        line.len_chars()
-                    };
-
-                    if sel_start_in_line < sel_end_in_line {
-                        if use_wrapping {
-                            for (row_idx, row) in state.display_map.rows.iter().enumerate() {
-                                if row.buffer_line != line_idx {
-                                    continue;
-                                }
-                                let row_sel_start = sel_start_in_line.max(row.start_offset);
-                                let row_sel_end = sel_end_in_line.min(row.end_offset);
-
-                                if row_sel_start < row_sel_end {
-                                    let display_start = row_sel_start - row.start_offset;
-                                    let display_end = row_sel_end - row.start_offset;
-                                    selection_rects.push((0, row_idx, display_start, display_end, row.is_continuation));
-                                }
-                            }
-                        } else {
-                            // Convert buffer line to display row
-                            let display_row = fold_state.actual_to_display_line(line_idx);
-                            selection_rects.push((0, display_row, sel_start_in_line, sel_end_in_line, false));
-                        }
-                    }
-                }
+            if selection_rects.is_empty() {
+                collect_selection_rects(state, fold_state, use_wrapping, 0, start, end, &mut selection_rects);
             }
         }
     }
 
+    selection_rects
+}
+
+/// A whitespace character inside a selection, in tab-expanded columns
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct WhitespaceCell {
+    display_row: usize,
+    col: usize,
+    /// Cells covered: 1 for a space, up to the next tab stop for a tab
+    width: usize,
+    ch: char,
+    is_continuation: bool,
+}
+
+/// Find the whitespace covered by the given selection rects
+///
+/// Tabs are always reported; spaces only when `include_spaces` is set.
+fn selection_whitespace_cells(
+    state: &CodeEditorState,
+    rects: &[SelectionRect],
+    include_spaces: bool,
+) -> Vec<WhitespaceCell> {
+    let mut cells = Vec::new();
+    for rect in rects {
+        let line = state.rope.line(rect.buffer_line);
+        for col in rect.start_col..rect.end_col {
+            let ch = line.char(rect.row_offset + col);
+            if ch == '\t' || (include_spaces && ch == ' ') {
+                let visual = rect.visual_col(state, col);
+                cells.push(WhitespaceCell {
+                    display_row: rect.display_row,
+                    col: visual,
+                    width: rect.visual_col(state, col + 1) - visual,
+                    ch,
+                    is_continuation: rect.is_continuation,
+                });
+            }
+        }
+    }
+    cells
+}
+
+/// Add rects for the whitespace `mode` shows on one display row
+///
+/// `row_offset..row_end` is the span of buffer columns the row displays.
+fn push_whitespace_rects(
+    state: &CodeEditorState,
+    mode: WhitespaceMode,
+    display_row: usize,
+    buffer_line: usize,
+    (row_offset, row_end): (usize, usize),
+    is_continuation: bool,
+    rects: &mut Vec<SelectionRect>,
+) {
+    let line = state.rope.line(buffer_line);
+    let is_blank = |ch: char| ch == ' ' || ch == '\t';
+    let chars: Vec<char> = line.chars().take_while(|&ch| ch != '\n' && ch != '\r').collect();
+    let text_len = chars.len();
+    let leading_end = chars.iter().take_while(|&&ch| is_blank(ch)).count();
+    let trailing_start = text_len - chars.iter().rev().take_while(|&&ch| is_blank(ch)).count();
+
+    let spans = match mode {
+        WhitespaceMode::All => vec![(0, text_len)],
+        WhitespaceMode::Trailing => vec![(trailing_start, text_len)],
+        WhitespaceMode::Boundary if leading_end >= trailing_start => vec![(0, text_len)],
+        WhitespaceMode::Boundary => vec![(0, leading_end), (trailing_start, text_len)],
+        WhitespaceMode::None | WhitespaceMode::Selection => Vec::new(),
+    };
+
+    for (start, end) in spans {
+        let start = start.max(row_offset);
+        let end = end.min(row_end);
+        if start < end {
+            rects.push(SelectionRect {
+                cursor_index: 0,
+                display_row,
+                start_col: start - row_offset,
+                end_col: end - row_offset,
+                is_continuation,
+                buffer_line,
+                row_offset,
+                includes_newline: false,
+            });
+        }
+    }
+}
+
+/// Find the whitespace `mode` shows on the display rows in `rows`
+///
+/// Selection-only markers are handled by `selection_whitespace_cells`, so
+/// `None` and `Selection` yield nothing here.
+fn visible_whitespace_cells(
+    state: &CodeEditorState,
+    fold_state: &FoldState,
+    use_wrapping: bool,
+    rows: std::ops::Range<usize>,
+    mode: WhitespaceMode,
+) -> Vec<WhitespaceCell> {
+    if matches!(mode, WhitespaceMode::None | WhitespaceMode::Selection) {
+        return Vec::new();
+    }
+
+    let mut rects = Vec::new();
+    if use_wrapping {
+        for (row_idx, row) in state.display_map.rows.iter().enumerate().take(rows.end).skip(rows.start) {
+            let span = (row.start_offset, row.end_offset);
+            push_whitespace_rects(state, mode, row_idx, row.buffer_line, span, row.is_continuation, &mut rects);
+        }
+    } else {
+        let line_count = state.rope.len_lines();
+        let mut buffer_line = fold_state.display_to_actual_line(rows.start);
+        for display_row in rows {
+            while buffer_line < line_count && fold_state.is_line_hidden(buffer_line) {
+                buffer_line += 1;
+            }
+            if buffer_line >= line_count {
+                break;
+            }
+            push_whitespace_rects(state, mode, display_row, buffer_line, (0, usize::MAX), false, &mut rects);
+            buffer_line += 1;
+        }
+    }
+
+    selection_whitespace_cells(state, &rects, true)
+}
+
+/// Sprite pieces (center, size, rotation) marking one whitespace cell
+///
+/// Spaces get a centered dot; tabs an arrow spanning to the tab stop.
+fn whitespace_marker_parts(cell: &WhitespaceCell, left: f32, center_y: f32, char_width: f32) -> Vec<(Vec2, Vec2, f32)> {
+    let dot = (char_width * 0.2).max(1.0);
+    if cell.ch != '\t' {
+        return vec![(Vec2::new(left + char_width * 0.5, center_y), Vec2::splat(dot), 0.0)];
+    }
+
+    let thickness = (dot * 0.5).max(1.0);
+    let pad = char_width * 0.2;
+    let shaft_start = left + pad;
+    let tip = left + cell.width as f32 * char_width - pad;
+    let barb = char_width * 0.3;
+    let angle = 0.6_f32;
+    let (sin, cos) = angle.sin_cos();
+
+    vec![
+        (Vec2::new((shaft_start + tip) / 2.0, center_y), Vec2::new(tip - shaft_start, thickness), 0.0),
+        (Vec2::new(tip - barb / 2.0 * cos, center_y + barb / 2.0 * sin), Vec2::new(barb, thickness), -angle),
+        (Vec2::new(tip - barb / 2.0 * cos, center_y - barb / 2.0 * sin), Vec2::new(barb, thickness), angle),
+    ]
+}
+
+/// Draw whitespace markers on visible lines and inside selections
+///
+/// `UiSettings::show_whitespace` picks which spaces and tabs on visible lines
+/// get markers. Tabs inside a selection are always drawn as arrows so
+/// tab-indented blocks read clearly; space dots are added there when
+/// `UiSettings::render_whitespace_in_selection` is set or the mode is
+/// `WhitespaceMode::Selection`.
+pub(crate) fn update_selection_whitespace(
+    mut commands: Commands,
+    state: Res<CodeEditorState>,
+    font: Res<FontSettings>,
+    theme: Res<ThemeSettings>,
+    ui: Res<UiSettings>,
+    (wrapping, indentation): (Res<WrappingSettings>, Res<IndentationSettings>),
+    viewport: Res<ViewportDimensions>,
+    fold_state: Res<FoldState>,
+    mut marker_query: Query<(Entity, &mut Transform, &mut Sprite, &mut Visibility), With<SelectionWhitespaceMarker>>,
+) {
+    if !state.is_changed()
+        && !ui.is_changed()
+        && !theme.is_changed()
+        && !viewport.is_changed()
+        && !font.is_changed()
+        && !fold_state.is_changed()
+        && !wrapping.is_changed()
+    {
+        return;
+    }
+
+    let include_spaces = ui.render_whitespace_in_selection || ui.show_whitespace == WhitespaceMode::Selection;
+    let use_wrapping = wrapping.enabled && state.display_map.wrap_width > 0;
+    let char_width = font.char_width;
+    let line_height = font.line_height;
+    let color = theme.whitespace;
+
+    let first_row = ((-state.scroll_offset) / line_height).floor().max(0.0) as usize;
+    let visible_rows = (viewport.height as f32 / line_height).ceil() as usize + 2;
+    let mut cells = visible_whitespace_cells(
+        &state,
+        &fold_state,
+        use_wrapping,
+        first_row..first_row + visible_rows,
+        ui.show_whitespace,
+    );
+    let shown: std::collections::HashSet<_> = cells.iter().map(|cell| (cell.display_row, cell.col)).collect();
+    cells.extend(
+        selection_whitespace_cells(&state, &all_selection_rects(&state, &fold_state, use_wrapping), include_spaces)
+            .into_iter()
+            .filter(|cell| !shown.contains(&(cell.display_row, cell.col))),
+    );
+
+    let mut existing: Vec<_> = marker_query.iter_mut().collect();
+    let mut entity_index = 0;
+    for cell in &cells {
+        let extra_indent = if use_wrapping && cell.is_continuation && wrapping.indent_wrapped_lines {
+            indentation.indent_size as f32 * char_width
+        } else {
+            0.0
+        };
+        let left = viewport.text_area_left + extra_indent + cell.col as f32 * char_width;
+        let y = viewport.text_area_top + state.scroll_offset + cell.display_row as f32 * line_height;
+
+        for (center, size, rotation) in whitespace_marker_parts(cell, left, y, char_width) {
+            let mut transform = Transform::from_xyz(
+                -(viewport.width as f32) / 2.0 + center.x,
+                (viewport.height as f32) / 2.0 - center.y,
+                0.6,
+            );
+            transform.rotation = Quat::from_rotation_z(rotation);
+
+            if let Some((_, existing_transform, sprite, visibility)) = existing.get_mut(entity_index) {
+                **existing_transform = transform;
+                sprite.custom_size = Some(size);
+                sprite.color = color;
+                **visibility = Visibility::Visible;
+            } else {
+                commands.spawn((
+                    Sprite {
+                        color,
+                        custom_size: Some(size),
+                        ..default()
+                    },
+                    transform,
+                    SelectionWhitespaceMarker,
+                    Name::new("SelectionWhitespace"),
+                    Visibility::Visible,
+                ));
+            }
+            entity_index += 1;
+        }
+    }
+
+    for (_, _, _, visibility) in existing.iter_mut().skip(entity_index) {
+        **visibility = Visibility::Hidden;
+    }
+}
+
+/// Update selection highlight rectangles for all cursors
+pub(crate) fn update_selection_highlight(
+    mut commands: Commands,
+    state: Res<CodeEditorState>,
+    font: Res<FontSettings>,
+    theme: Res<ThemeSettings>,
+    ui: Res<UiSettings>,
+    wrapping: Res<WrappingSettings>,
+    indentation: Res<IndentationSettings>,
+    viewport: Res<ViewportDimensions>,
+    fold_state: Res<FoldState>,
+    mut selection_query: Query<(
+        Entity,
+        &mut Transform,
+        &mut Sprite,
+        &mut Visibility,
+        &mut SelectionHighlight,
+    )>,
+) {
+    if !state.is_changed() {
+        return;
+    }
+
+    let char_width = font.char_width;
+    let line_height = font.line_height;
+
+    // Check if we're using soft line wrapping
+    let use_wrapping = wrapping.enabled && state.display_map.wrap_width > 0;
+
+    let selection_rects = all_selection_rects(&state, &fold_state, use_wrapping);
+
     // Clear all if no selections
     if selection_rects.is_empty() {
         for (_, _, _, mut visibility, _) in selection_query.iter_mut() {
@@ -330,8 +661,21 @@ pub(crate) fn update_selection_highlight(
     let mut existing_selections: Vec<_> = selection_query.iter_mut().collect();
     let mut entity_index = 0;
 
-    for (cursor_idx, row_idx, sel_start_col, sel_end_col, is_continuation) in selection_rects {
-        let selection_width = (sel_end_col - sel_start_col) as f32 * char_width;
+    for rect in selection_rects {
+        // Highlight spans tab-expanded columns, so a selected tab covers its full width
+        let (visual_start, visual_end) = (rect.visual_col(&state, rect.start_col), rect.visual_col(&state, rect.end_col));
+        let SelectionRect {
+            cursor_index: cursor_idx,
+            display_row: row_idx,
+            is_continuation,
+            includes_newline,
+            ..
+        } = rect;
+        let newline_width = if includes_newline { ui.selection_newline_width * char_width } else { 0.0 };
+        let selection_width = (visual_end - visual_start) as f32 * char_width + newline_width;
+        if selection_width <= 0.0 {
+            continue;
+        }
 
         // Add continuation indent for wrapped lines
         let extra_indent = if use_wrapping && is_continuation && wrapping.indent_wrapped_lines {
@@ -340,7 +684,7 @@ pub(crate) fn update_selection_highlight(
             0.0
         };
 
-        let x_left_edge = viewport.text_area_left + extra_indent + (sel_start_col as f32 * char_width);
+        let x_left_edge = viewport.text_area_left + extra_indent + (visual_start as f32 * char_width);
         let y_from_top = viewport.text_area_top + state.scroll_offset + (row_idx as f32 * line_height);
 
         let sprite_center_x =
@@ -551,12 +895,16 @@ pub(crate) fn animate_smooth_scroll(
     // When dragging scrollbar or smooth scrolling disabled, apply target immediately
     let use_smooth = scrolling.smooth && !scrollbar_drag.is_dragging;
 
+    let dt = time.delta_secs();
+    let previous_offset = state.scroll_offset;
+
     if !use_smooth {
         // Instant update - no interpolation
         if (state.target_scroll_offset - state.scroll_offset).abs() > 0.001 {
             state.scroll_offset = state.target_scroll_offset;
             state.needs_scroll_update = true;
         }
+        state.scroll_velocity = scroll_velocity(previous_offset, state.scroll_offset, dt);
         if (state.target_horizontal_scroll_offset - state.horizontal_scroll_offset).abs() > 0.001 {
             state.horizontal_scroll_offset = state.target_horizontal_scroll_offset;
             state.needs_update = true;
@@ -567,7 +915,6 @@ pub(crate) fn animate_smooth_scroll(
     // Smooth scrolling interpolation factor (higher = faster)
     // Using exponential decay for natural feel
     let smoothness = 12.0; // Adjust for desired smoothness
-    let dt = time.delta_secs();
     let t = 1.0 - (-smoothness * dt).exp();
 
     // Vertical scroll animation
@@ -580,6 +927,7 @@ pub(crate) fn animate_smooth_scroll(
         state.scroll_offset = state.target_scroll_offset;
         state.needs_scroll_update = true;
     }
+    state.scroll_velocity = scroll_velocity(previous_offset, state.scroll_offset, dt);
 
     // Horizontal scroll animation
     let horizontal_diff = state.target_horizontal_scroll_offset - state.horizontal_scroll_offset;
@@ -593,12 +941,323 @@ pub(crate) fn animate_smooth_scroll(
     }
 }
 
+/// Drive an active `ViewTransition`, overriding the smooth-scroll step
+pub(crate) fn animate_view_transition(
+    mut state: ResMut<CodeEditorState>,
+    mut transition: ResMut<ViewTransition>,
+    time: Res<Time>,
+) {
+    if transition.is_active() {
+        transition.advance(&mut state, time.delta_secs());
+    }
+}
+
+/// Columns of text that fit in the code area at the current viewport width
+///
+/// The code area runs from `text_area_left` to the minimap, when one is shown.
+pub(crate) fn viewport_wrap_column(
+    viewport: &ViewportDimensions,
+    font: &FontSettings,
+    minimap: &MinimapSettings,
+) -> usize {
+    let minimap_width = if minimap.enabled { minimap.width + minimap.edge_padding } else { 0.0 };
+    let code_width = viewport.width as f32 - viewport.text_area_left - minimap_width;
+    if font.char_width <= 0.0 {
+        return 0;
+    }
+    (code_width / font.char_width).floor().max(1.0) as usize
+}
+
+/// Keep the display map soft-wrapped at the wrap column
+///
+/// The column is the fixed one from the settings or, without one, whatever
+/// fits in the viewport, so resizing the window rewraps live. Rewraps when
+/// the text, the wrap settings or the column change; lines matching the
+/// no-wrap rules stay on one row. Rewrapping never moves the cursor in the
+/// buffer, but a width change can push it to a different display row, so the
+/// view follows it.
+pub(crate) fn update_wrap_layout(
+    mut state: ResMut<CodeEditorState>,
+    (wrapping, minimap, indentation): (Res<WrappingSettings>, Res<MinimapSettings>, Res<IndentationSettings>),
+    viewport: Res<ViewportDimensions>,
+    font: Res<FontSettings>,
+    mut last_layout: Local<Option<(u64, usize)>>,
+) {
+    let column = if wrapping.enabled {
+        wrapping.effective_wrap_column().or_else(|| Some(viewport_wrap_column(&viewport, &font, &minimap)))
+    } else {
+        None
+    };
+    let Some(column) = column.filter(|&c| c > 0) else {
+        // Undo our own wrapping when it gets turned off
+        if last_layout.take().is_some() {
+            if state.display_map.wrapped_to_viewport {
+                state.display_map.wrapped_to_viewport = false;
+            }
+            state.rewrap(0);
+        }
+        return;
+    };
+
+    // Every row fits the viewport, so there's nothing to scroll sideways.
+    // Assigning through `ResMut` marks the state changed, so only do it on a change.
+    let wrapped_to_viewport = wrapping.effective_wrap_column().is_none();
+    if state.display_map.wrapped_to_viewport != wrapped_to_viewport {
+        state.display_map.wrapped_to_viewport = wrapped_to_viewport;
+    }
+    if state.display_map.locks_horizontal_scroll() && state.horizontal_scroll_offset != 0.0 {
+        state.horizontal_scroll_offset = 0.0;
+        state.target_horizontal_scroll_offset = 0.0;
+        state.needs_scroll_update = true;
+    }
+
+    // No-wrap rules live in the settings, so any settings change rewraps too
+    let layout = (state.content_version, column);
+    if *last_layout == Some(layout) && !wrapping.is_changed() && !indentation.is_changed() {
+        return;
+    }
+    let width_changed = last_layout.is_some_and(|(_, previous)| previous != column);
+    if last_layout.is_none() || wrapping.is_changed() || indentation.is_changed() {
+        state.display_map.wrap_mode = wrapping.mode;
+        state.display_map.break_words = wrapping.break_words;
+        state.display_map.continuation_indent =
+            if wrapping.indent_wrapped_lines { indentation.indent_size } else { 0 };
+        state.rewrap_except(column, |line| wrapping.is_no_wrap_line(line));
+    } else {
+        // Only the edited lines need wrapping again
+        state.rewrap_edited_except(column, |line| wrapping.is_no_wrap_line(line));
+    }
+    *last_layout = Some(layout);
+
+    if width_changed {
+        keep_cursor_row_visible(&mut state, &viewport, font.line_height);
+    }
+}
+
+/// Scroll just enough to keep the cursor's display row on screen
+fn keep_cursor_row_visible(state: &mut CodeEditorState, viewport: &ViewportDimensions, line_height: f32) {
+    let row_top = viewport.text_area_top + state.target_scroll_offset + state.cursor_display_row() as f32 * line_height;
+    let viewport_height = viewport.height as f32;
+    if row_top < 0.0 {
+        state.target_scroll_offset -= row_top;
+    } else if row_top + line_height > viewport_height {
+        state.target_scroll_offset -= row_top + line_height - viewport_height;
+    } else {
+        return;
+    }
+    state.target_scroll_offset = state.target_scroll_offset.min(0.0);
+    state.needs_scroll_update = true;
+}
+
+/// Draw a faint vertical guide at the wrap-at-ruler column
+pub(crate) fn update_wrap_guide(
+    mut commands: Commands,
+    wrapping: Res<WrappingSettings>,
+    font: Res<FontSettings>,
+    theme: Res<ThemeSettings>,
+    viewport: Res<ViewportDimensions>,
+    mut guide_query: Query<(&mut Transform, &mut Sprite, &mut Visibility), With<WrapGuide>>,
+) {
+    if !wrapping.is_changed() && !viewport.is_changed() && !font.is_changed() && !theme.is_changed() {
+        return;
+    }
+
+    let Some(column) = wrapping.wrap_at_ruler.filter(|_| wrapping.enabled) else {
+        for (_, _, mut visibility) in guide_query.iter_mut() {
+            *visibility = Visibility::Hidden;
+        }
+        return;
+    };
+
+    let viewport_width = viewport.width as f32;
+    let viewport_height = viewport.height as f32;
+    let mut translation = to_bevy_coords_left_aligned(
+        viewport.text_area_left + column as f32 * font.char_width,
+        viewport_height / 2.0,
+        viewport_width,
+        viewport_height,
+        viewport.offset_x,
+        0.0, // wrapped text never scrolls horizontally
+    );
+    translation.z = 0.2;
+    let size = Vec2::new(1.0, viewport_height);
+
+    if let Ok((mut transform, mut sprite, mut visibility)) = guide_query.single_mut() {
+        transform.translation = translation;
+        sprite.custom_size = Some(size);
+        sprite.color = theme.indent_guide;
+        *visibility = Visibility::Visible;
+    } else {
+        commands.spawn((
+            Sprite {
+                color: theme.indent_guide,
+                custom_size: Some(size),
+                ..default()
+            },
+            Transform::from_translation(translation),
+            WrapGuide,
+            Name::new("WrapGuide"),
+            Visibility::Visible,
+        ));
+    }
+}
+
+/// Draw a vertical line at each column in `UiSettings::rulers`
+///
+/// Rulers move with horizontal scrolling and are hidden outside the text
+/// area: past its left edge, or under the scrollbar or a right-side minimap.
+pub(crate) fn update_column_rulers(
+    mut commands: Commands,
+    state: Res<CodeEditorState>,
+    (ui, wrapping): (Res<UiSettings>, Res<WrappingSettings>),
+    (minimap, scrollbar): (Res<MinimapSettings>, Res<ScrollbarSettings>),
+    font: Res<FontSettings>,
+    theme: Res<ThemeSettings>,
+    viewport: Res<ViewportDimensions>,
+    mut ruler_query: Query<(&mut ColumnRuler, &mut Transform, &mut Sprite, &mut Visibility)>,
+) {
+    if !state.is_changed()
+        && !ui.is_changed()
+        && !minimap.is_changed()
+        && !scrollbar.is_changed()
+        && !viewport.is_changed()
+        && !font.is_changed()
+        && !theme.is_changed()
+    {
+        return;
+    }
+
+    let viewport_width = viewport.width as f32;
+    let mut text_area_right = viewport_width;
+    if scrollbar.enabled {
+        text_area_right -= scrollbar.width;
+    }
+    if minimap.enabled && minimap.show_on_right {
+        text_area_right = text_area_right.min(viewport_width - minimap.width - minimap.edge_padding);
+    }
+    let viewport_height = viewport.height as f32;
+    let use_wrapping = wrapping.enabled && state.display_map.wrap_width > 0;
+    let h_scroll = if use_wrapping { 0.0 } else { state.horizontal_scroll_offset };
+    let size = Vec2::new(1.0, viewport_height);
+
+    let mut existing = ruler_query.iter_mut();
+    for &column in &ui.rulers {
+        let x = viewport.text_area_left + column as f32 * font.char_width - h_scroll;
+        let mut translation = to_bevy_coords_left_aligned(
+            x,
+            viewport_height / 2.0,
+            viewport_width,
+            viewport_height,
+            viewport.offset_x,
+            h_scroll,
+        );
+        translation.z = 0.2;
+        let visibility = if x < viewport.text_area_left || x >= text_area_right {
+            Visibility::Hidden
+        } else {
+            Visibility::Visible
+        };
+
+        if let Some((mut ruler, mut transform, mut sprite, mut ruler_visibility)) = existing.next() {
+            ruler.column = column;
+            transform.translation = translation;
+            sprite.custom_size = Some(size);
+            sprite.color = theme.ruler;
+            *ruler_visibility = visibility;
+        } else {
+            commands.spawn((
+                Sprite {
+                    color: theme.ruler,
+                    custom_size: Some(size),
+                    ..default()
+                },
+                Transform::from_translation(translation),
+                ColumnRuler { column },
+                Name::new("ColumnRuler"),
+                visibility,
+            ));
+        }
+    }
+
+    for (_, _, _, mut visibility) in existing {
+        *visibility = Visibility::Hidden;
+    }
+}
+
+/// Keep `max_content_width` in sync with the line width tracker
+///
+/// Edits update the tracker precisely (including shrinking when the longest line
+/// gets shorter, even off-screen); scrolling lets it discover newly visible lines.
+pub(crate) fn update_max_content_width(
+    mut state: ResMut<CodeEditorState>,
+    font: Res<FontSettings>,
+    viewport: Res<ViewportDimensions>,
+    mut last_scroll_offset: Local<Option<f32>>,
+) {
+    let content_changed = state.max_content_width_version != state.content_version;
+    let scrolled = *last_scroll_offset != Some(state.scroll_offset);
+    if !content_changed && !scrolled {
+        return;
+    }
+
+    let state = &mut *state;
+    let line_height = font.line_height;
+
+    if content_changed {
+        // Edited lines: the dirty range plus every cursor line, widened upward by
+        // any lines a multi-line insertion added above the cursor
+        let line_count = state.rope.len_lines();
+        let added_lines = line_count.saturating_sub(state.line_width_tracker.line_count());
+        let len_chars = state.rope.len_chars();
+
+        let mut start = usize::MAX;
+        let mut end = 0;
+        for pos in state.cursors.iter().map(|c| c.position).chain(std::iter::once(state.cursor_pos)) {
+            let line = state.rope.char_to_line(pos.min(len_chars));
+            start = start.min(line.saturating_sub(added_lines));
+            end = end.max(line + 1);
+        }
+        if let Some(dirty) = &state.dirty_lines {
+            start = start.min(dirty.start);
+            end = end.max(dirty.end);
+        }
+
+        state.line_width_tracker.update_after_edit(&state.rope, start.min(end)..end);
+        state.max_content_width_version = state.content_version;
+    }
+
+    // Discover widths of the lines currently on screen
+    let first_visible = ((-state.scroll_offset - viewport.text_area_top) / line_height).floor().max(0.0) as usize;
+    let visible_count = (viewport.height as f32 / line_height).ceil() as usize + 1;
+    state.line_width_tracker.update_visible_range(&state.rope, first_visible, first_visible + visible_count);
+    *last_scroll_offset = Some(state.scroll_offset);
+
+    state.max_content_width = state.line_width_tracker.max_width() as f32 * font.char_width;
+}
+
+/// Vertical scroll velocity (pixels per second) for a single frame step
+fn scroll_velocity(previous_offset: f32, offset: f32, dt: f32) -> f32 {
+    if dt > 0.0 {
+        (offset - previous_offset) / dt
+    } else {
+        0.0
+    }
+}
+
 /// Auto-scroll viewport to keep cursor visible
 /// Writes to target_scroll_offset, not scroll_offset (applied by animate_smooth_scroll)
+///
+/// Off-screen cursors are revealed according to `ScrollingSettings::cursor_reveal`;
+/// in adaptive mode the distance from the previous cursor line picks minimal
+/// reveal or centering. Navigation jumps are centered when
+/// `NavigationSettings::center_on_jump` is set. Positions are in display rows,
+/// so folded lines and soft-wrapped rows are accounted for.
 pub(crate) fn auto_scroll_to_cursor(
     mut state: ResMut<CodeEditorState>,
     font: Res<FontSettings>,
     viewport: Res<ViewportDimensions>,
+    (scrolling, navigation): (Res<ScrollingSettings>, Res<NavigationSettings>),
+    (wrapping, fold_state): (Res<WrappingSettings>, Res<FoldState>),
     scrollbar_drag: Res<super::scrollbar::ScrollbarDragState>,
 ) {
     // Skip auto-scroll when dragging scrollbar (user has manual control priority)
@@ -608,21 +1267,41 @@ pub(crate) fn auto_scroll_to_cursor(
 
     // Only auto-scroll when cursor actually moves (not when scroll changes)
     let cursor_pos = state.cursor_pos.min(state.rope.len_chars());
-    if cursor_pos == state.last_cursor_pos {
+    if cursor_pos == state.last_cursor_pos && !state.reveal_cursor {
         return;
     }
+    state.reveal_cursor = false;
+    let jump = std::mem::take(&mut state.navigation_jump);
 
     // Update last cursor position
+    let previous_line = state.rope.char_to_line(state.last_cursor_pos.min(state.rope.len_chars()));
     state.last_cursor_pos = cursor_pos;
     let line_index = state.rope.char_to_line(cursor_pos);
+    let center = match scrolling.cursor_reveal {
+        _ if jump && navigation.center_on_jump => true,
+        CursorReveal::Minimal => false,
+        CursorReveal::Center => true,
+        CursorReveal::Adaptive => line_index.abs_diff(previous_line) > scrolling.center_jump_threshold,
+    };
     let line_height = font.line_height;
     let viewport_height = viewport.height as f32;
     let viewport_width = viewport.width as f32;
 
     // === VERTICAL AUTO-SCROLL ===
 
+    // Display row of the cursor and the number of rows shown
+    let use_wrapping = wrapping.enabled && state.display_map.wrap_width > 0;
+    let (cursor_row, row_count) = if use_wrapping {
+        (state.cursor_display_row(), state.display_map.row_count())
+    } else {
+        (
+            fold_state.actual_to_display_line(line_index),
+            state.rope.len_lines().saturating_sub(fold_state.total_hidden_lines()),
+        )
+    };
+
     // Calculate cursor's Y position
-    let cursor_y = viewport.text_area_top + state.scroll_offset + (line_index as f32 * line_height);
+    let cursor_y = viewport.text_area_top + state.scroll_offset + (cursor_row as f32 * line_height);
 
     // Define visible range (with some margin)
     let margin_vertical = line_height * 2.0;
@@ -630,7 +1309,10 @@ pub(crate) fn auto_scroll_to_cursor(
     let visible_bottom = viewport_height - margin_vertical;
 
     // Adjust target scroll if cursor is outside visible range
-    if cursor_y < visible_top {
+    if center && (cursor_y < visible_top || cursor_y > visible_bottom) {
+        state.target_scroll_offset =
+            (viewport_height - line_height) / 2.0 - viewport.text_area_top - cursor_row as f32 * line_height;
+    } else if cursor_y < visible_top {
         // Cursor is above visible area - scroll up
         state.target_scroll_offset += visible_top - cursor_y;
     } else if cursor_y > visible_bottom {
@@ -643,20 +1325,25 @@ pub(crate) fn auto_scroll_to_cursor(
 
     // Clamp target_scroll_offset to valid range
     state.target_scroll_offset = state.target_scroll_offset.min(0.0);
-    let line_count = state.rope.len_lines();
-    let content_height = line_count as f32 * line_height;
-    let max_scroll = -(content_height - viewport_height + viewport.text_area_top);
-    state.target_scroll_offset = state.target_scroll_offset.max(max_scroll.min(0.0));
+    let content_height = row_count as f32 * line_height;
+    let max_scroll = scrolling.max_scroll(content_height, viewport_height, viewport.text_area_top, line_height);
+    state.target_scroll_offset = state.target_scroll_offset.max(max_scroll);
 
     // === HORIZONTAL AUTO-SCROLL ===
+    if state.display_map.locks_horizontal_scroll() {
+        state.target_horizontal_scroll_offset = 0.0;
+        return;
+    }
 
     // Calculate cursor's X position (column within line)
     let line_start = state.rope.line_to_char(line_index);
     let col_index = cursor_pos - line_start;
     let char_width = font.char_width;
 
-    // Cursor X position relative to code area (before scrolling)
-    let cursor_x = col_index as f32 * char_width;
+    // Cursor X position relative to code area (before scrolling), in cells
+    let line_text: String = state.rope.line(line_index).chars().take(col_index).collect();
+    let visual_col = state.display_map.tab_map.expand_column(&line_text, col_index as u32);
+    let cursor_x = visual_col as f32 * char_width;
 
     // Define horizontal visible range (with some margin)
     let margin_horizontal = char_width * 5.0; // 5 characters of margin
@@ -680,3 +1367,691 @@ pub(crate) fn auto_scroll_to_cursor(
     let max_horizontal_scroll = (state.max_content_width - viewport_width).max(0.0);
     state.target_horizontal_scroll_offset = state.target_horizontal_scroll_offset.min(max_horizontal_scroll);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_short_buffer_renders_eob_markers() {
+        let mut app = App::new();
+        let mut settings = EditorSettingsBuilder::default().build();
+        settings.ui.show_eob_markers = true;
+        settings.insert_into(&mut app);
+        app.insert_resource(CodeEditorState::new("one\ntwo\nthree"));
+        app.insert_resource(ViewportDimensions::default());
+        app.insert_resource(FoldState::default());
+        app.init_resource::<GutterColumns>();
+        app.add_systems(Update, update_line_numbers);
+        app.update();
+
+        let texts: Vec<String> = app
+            .world_mut()
+            .query_filtered::<&Text2d, With<LineNumbers>>()
+            .iter(app.world())
+            .map(|text| text.0.clone())
+            .collect();
+
+        // Three real line numbers, followed by tildes filling the viewport
+        assert!(texts.iter().any(|t| t == "3"));
+        assert!(!texts.iter().any(|t| t == "4"));
+        let markers = texts.iter().filter(|t| t.as_str() == EOB_MARKER).count();
+        assert!(markers > 10, "expected tilde rows below content, got {}", markers);
+    }
+
+    #[test]
+    fn test_shortening_offscreen_longest_line_shrinks_scroll_bound() {
+        let mut text = String::new();
+        for i in 0..200 {
+            if i == 150 {
+                text.push_str(&"x".repeat(300));
+            } else {
+                text.push_str("short line");
+            }
+            text.push('\n');
+        }
+
+        let mut app = App::new();
+        EditorSettingsBuilder::default().build().insert_into(&mut app);
+        let mut state = CodeEditorState::new(&text);
+        // The long line was seen earlier (e.g. while scrolled down)
+        state.line_width_tracker.recompute(&state.rope);
+        app.insert_resource(state);
+        app.insert_resource(ViewportDimensions::default());
+        app.add_systems(Update, update_max_content_width);
+        app.update();
+
+        let char_width = app.world().resource::<FontSettings>().char_width;
+        assert_eq!(app.world().resource::<CodeEditorState>().max_content_width, 300.0 * char_width);
+
+        // Shorten the longest line while the viewport stays at the top
+        {
+            let mut state = app.world_mut().resource_mut::<CodeEditorState>();
+            let line_start = state.rope.line_to_char(150);
+            state.remove_range(line_start + 20, line_start + 300);
+        }
+        app.update();
+
+        let state = app.world().resource::<CodeEditorState>();
+        assert_eq!(state.max_content_width, 20.0 * char_width);
+    }
+
+    #[test]
+    fn test_selection_whitespace_marks_only_selected_spaces() {
+        let mut state = CodeEditorState::new("ab  \nc d\nxy  \n");
+        // Select all of line 0 including its trailing spaces, but not line 2
+        state.selection_start = Some(0);
+        state.selection_end = Some(5);
+        state.cursor_pos = 5;
+        state.cursors[0].position = 5;
+        state.cursors[0].anchor = Some(0);
+        let fold_state = FoldState::default();
+
+        let rects = all_selection_rects(&state, &fold_state, false);
+        let cells = selection_whitespace_cells(&state, &rects, true);
+
+        let cols: Vec<_> = cells.iter().map(|c| (c.display_row, c.col, c.ch)).collect();
+        assert_eq!(cols, vec![(0, 2, ' '), (0, 3, ' ')]);
+        assert!(cells.iter().all(|c| c.display_row == 0));
+    }
+
+    #[test]
+    fn test_wrap_at_ruler_drives_wrap_width() {
+        use crate::gpu_text::{GlyphAtlas, TextMaterial, TextRenderState};
+        use crate::plugin::{update_gpu_text_per_line, HighlightCache, LineMeshEntity, LineMeshPool, SyntaxResource};
+
+        let mut app = App::new();
+        EditorSettingsBuilder::default()
+            .wrapping(WrappingSettings {
+                enabled: true,
+                wrap_column: Some(40),
+                wrap_at_ruler: Some(10),
+                ..Default::default()
+            })
+            .build()
+            .insert_into(&mut app);
+        app.insert_resource(ViewportDimensions::default());
+        app.insert_resource(CodeEditorState::new(&format!("{}\nshort\n", "x".repeat(25))));
+        app.insert_resource(FoldState::default());
+        let mut images = Assets::<Image>::default();
+        app.insert_resource(GlyphAtlas::new(&mut images));
+        app.insert_resource(images);
+        app.insert_resource(Assets::<Mesh>::default());
+        app.insert_resource(Assets::<TextMaterial>::default());
+        app.init_resource::<TextRenderState>();
+        app.init_resource::<LineMeshPool>();
+        app.init_resource::<SyntaxResource>();
+        app.init_resource::<HighlightCache>();
+        app.init_resource::<Time>();
+        app.add_systems(Update, (update_wrap_layout, update_wrap_guide, update_gpu_text_per_line).chain());
+        app.update();
+
+        let state = app.world().resource::<CodeEditorState>();
+        assert_eq!(app.world().resource::<WrappingSettings>().effective_wrap_column(), Some(10));
+        assert_eq!(state.display_map.wrap_width, 10);
+        let first_line_rows: Vec<_> = state
+            .display_map
+            .rows
+            .iter()
+            .filter(|row| row.buffer_line == 0)
+            .map(|row| (row.start_offset, row.end_offset))
+            .collect();
+        // Continuation rows are indented by 4 cells, so they hold 6
+        assert_eq!(first_line_rows, vec![(0, 10), (10, 16), (16, 22), (22, 25)]);
+
+        let world = app.world_mut();
+        let guide_x = world
+            .query_filtered::<&Transform, With<WrapGuide>>()
+            .single(world)
+            .unwrap()
+            .translation
+            .x;
+
+        // The drawn text wraps with the rows: every glyph starts left of the
+        // guide, and the first line's glyphs sit on four rows
+        let mesh_handle = world
+            .query::<(&LineMeshEntity, &bevy::mesh::Mesh2d)>()
+            .iter(world)
+            .find(|(line, _)| line.buffer_line == 0)
+            .map(|(_, mesh)| mesh.0.clone())
+            .unwrap();
+        let meshes = world.resource::<Assets<Mesh>>();
+        let Some(bevy::mesh::VertexAttributeValues::Float32x3(positions)) =
+            meshes.get(&mesh_handle).unwrap().attribute(Mesh::ATTRIBUTE_POSITION)
+        else {
+            panic!("line mesh has no positions");
+        };
+        let glyph_corners: Vec<[f32; 3]> = positions.iter().skip(3).step_by(4).copied().collect();
+        assert_eq!(glyph_corners.len(), 25);
+        assert!(glyph_corners.iter().all(|corner| corner[0] < guide_x));
+        let mut row_tops: Vec<i32> = glyph_corners.iter().map(|corner| corner[1].round() as i32).collect();
+        row_tops.dedup();
+        assert_eq!(row_tops.len(), 4);
+    }
+
+    #[test]
+    fn test_no_wrap_pattern_keeps_line_on_one_row() {
+        let mut app = App::new();
+        EditorSettingsBuilder::default()
+            .wrapping(WrappingSettings {
+                enabled: true,
+                wrap_column: Some(10),
+                no_wrap_patterns: vec!["://".to_string()],
+                ..Default::default()
+            })
+            .build()
+            .insert_into(&mut app);
+        app.insert_resource(ViewportDimensions::default());
+        let url = "see https://example.com/a/long/path";
+        let text = format!("{}\n{}\n", url, "y".repeat(25));
+        app.insert_resource(CodeEditorState::new(&text));
+        app.add_systems(Update, update_wrap_layout);
+        app.update();
+
+        let display_map = &app.world().resource::<CodeEditorState>().display_map;
+        let rows_for = |line: usize| display_map.rows.iter().filter(|row| row.buffer_line == line).count();
+        assert_eq!(rows_for(0), 1);
+        assert!(display_map.is_no_wrap_line(0));
+        // 10 cells, then 6 per continuation row after its indent
+        assert_eq!(rows_for(1), 4);
+        assert!(!display_map.is_no_wrap_line(1));
+    }
+
+    #[test]
+    fn test_edit_rewraps_only_edited_lines() {
+        let mut app = App::new();
+        EditorSettingsBuilder::default()
+            .wrapping(WrappingSettings {
+                enabled: true,
+                wrap_column: Some(10),
+                ..Default::default()
+            })
+            .build()
+            .insert_into(&mut app);
+        app.insert_resource(ViewportDimensions::default());
+        let text = format!("{}\nshort\n{}\n", "a".repeat(25), "b".repeat(15));
+        app.insert_resource(CodeEditorState::new(&text));
+        app.add_systems(Update, update_wrap_layout);
+        app.update();
+        let version = app.world().resource::<CodeEditorState>().display_map.version;
+
+        // Split the middle line into a long one and a new line
+        let middle = 26 + "short".len();
+        app.world_mut()
+            .resource_mut::<CodeEditorState>()
+            .insert_text_at(middle, &format!("{}\nnew", "c".repeat(12)));
+        assert!(app.world().resource::<CodeEditorState>().display_map.edited_lines.is_some());
+        app.update();
+
+        let rows = |state: &CodeEditorState| -> Vec<_> {
+            state
+                .display_map
+                .rows
+                .iter()
+                .map(|row| (row.buffer_line, row.start_offset, row.end_offset, row.is_continuation))
+                .collect()
+        };
+        let state = app.world().resource::<CodeEditorState>();
+        assert_eq!(state.display_map.version, version + 1);
+        assert!(state.display_map.edited_lines.is_none());
+
+        let mut expected = CodeEditorState::new(&state.text());
+        expected.display_map.continuation_indent = state.display_map.continuation_indent;
+        expected.rewrap(10);
+        assert_eq!(rows(state), rows(&expected));
+    }
+
+    #[test]
+    fn test_word_wrap_keeps_words_whole_and_splits_only_overlong_ones() {
+        let rows_for = |text: &str, mode: WrapMode, break_words: bool| {
+            let mut app = App::new();
+            EditorSettingsBuilder::default()
+                .wrapping(WrappingSettings {
+                    enabled: true,
+                    wrap_column: Some(10),
+                    mode,
+                    break_words,
+                    indent_wrapped_lines: false,
+                    ..Default::default()
+                })
+                .build()
+                .insert_into(&mut app);
+            app.insert_resource(ViewportDimensions::default());
+            app.insert_resource(CodeEditorState::new(text));
+            app.add_systems(Update, update_wrap_layout);
+            app.update();
+            let state = app.world().resource::<CodeEditorState>();
+            state
+                .display_map
+                .rows
+                .iter()
+                .filter(|row| row.buffer_line == 0)
+                .map(|row| state.rope.slice(row.start_offset..row.end_offset).to_string())
+                .collect::<Vec<_>>()
+        };
+
+        // Prose breaks after whitespace, even far back in the row
+        let prose = "a quick brownish fox";
+        assert_eq!(rows_for(prose, WrapMode::Word, true), vec!["a quick ", "brownish ", "fox"]);
+        assert_eq!(rows_for(prose, WrapMode::Character, true), vec!["a quick br", "ownish fox"]);
+
+        // A token wider than the row falls back to character breaking
+        let token = "x abcdefghijklmnopqrstuvwxyz y";
+        assert_eq!(
+            rows_for(token, WrapMode::Word, true),
+            vec!["x ", "abcdefghij", "klmnopqrst", "uvwxyz y"]
+        );
+        // ...unless words may not be split at all
+        assert_eq!(
+            rows_for(token, WrapMode::Word, false),
+            vec!["x ", "abcdefghijklmnopqrstuvwxyz ", "y"]
+        );
+    }
+
+    #[test]
+    fn test_narrower_viewport_rewraps_without_moving_cursor() {
+        let mut app = App::new();
+        EditorSettingsBuilder::default()
+            .wrapping(WrappingSettings {
+                enabled: true,
+                ..Default::default()
+            })
+            .build()
+            .insert_into(&mut app);
+        app.insert_resource(ViewportDimensions::default());
+        let text = format!("{}\nend", "word ".repeat(60));
+        app.insert_resource(CodeEditorState::new(&text));
+        app.add_systems(Update, update_wrap_layout);
+
+        let cursor = text.len() - 1;
+        app.world_mut().resource_mut::<CodeEditorState>().cursor_pos = cursor;
+        app.update();
+        let wide_rows = app.world().resource::<CodeEditorState>().display_map.row_count();
+        let wide_cursor_row = app.world().resource::<CodeEditorState>().cursor_display_row();
+
+        app.world_mut().resource_mut::<ViewportDimensions>().width /= 2;
+        app.update();
+
+        let state = app.world().resource::<CodeEditorState>();
+        assert!(state.display_map.row_count() > wide_rows);
+        assert_eq!(state.cursor_pos, cursor);
+        assert!(state.cursor_display_row() > wide_cursor_row);
+    }
+
+    #[test]
+    fn test_viewport_wrap_follows_resize_and_locks_horizontal_scroll() {
+        let mut app = App::new();
+        EditorSettingsBuilder::default()
+            .wrapping(WrappingSettings {
+                enabled: true,
+                ..Default::default()
+            })
+            .build()
+            .insert_into(&mut app);
+        app.insert_resource(ViewportDimensions::default());
+        let mut state = CodeEditorState::new(&"word ".repeat(200));
+        state.horizontal_scroll_offset = 50.0;
+        state.target_horizontal_scroll_offset = 50.0;
+        app.insert_resource(state);
+        app.add_systems(Update, update_wrap_layout);
+        app.update();
+
+        let char_width = app.world().resource::<FontSettings>().char_width;
+        let minimap = app.world().resource::<MinimapSettings>().clone();
+        let expected_column = |width: u32| {
+            ((width as f32 - ViewportDimensions::default().text_area_left - minimap.width - minimap.edge_padding)
+                / char_width)
+                .floor() as usize
+        };
+        let state = app.world().resource::<CodeEditorState>();
+        assert_eq!(state.display_map.wrap_width, expected_column(800));
+        assert!(state.display_map.locks_horizontal_scroll());
+        assert_eq!(state.horizontal_scroll_offset, 0.0);
+
+        app.world_mut().resource_mut::<ViewportDimensions>().width = 600;
+        app.update();
+        let state = app.world().resource::<CodeEditorState>();
+        assert_eq!(state.display_map.wrap_width, expected_column(600));
+
+        // A fixed column wraps wider than the viewport, so scrolling comes back
+        app.world_mut().resource_mut::<WrappingSettings>().wrap_column = Some(200);
+        app.update();
+        assert!(!app.world().resource::<CodeEditorState>().display_map.locks_horizontal_scroll());
+    }
+
+    #[test]
+    fn test_long_jump_centers_cursor_but_short_move_reveals_minimally() {
+        let mut app = App::new();
+        EditorSettingsBuilder::default().build().insert_into(&mut app);
+        app.insert_resource(ViewportDimensions::default());
+        app.init_resource::<super::super::scrollbar::ScrollbarDragState>();
+        app.insert_resource(FoldState::default());
+        app.insert_resource(CodeEditorState::new(&"line\n".repeat(1000)));
+        app.add_systems(Update, auto_scroll_to_cursor);
+
+        let line_height = app.world().resource::<FontSettings>().line_height;
+        let viewport = ViewportDimensions::default();
+        let visible_bottom = viewport.height as f32 - line_height * 2.0;
+        let cursor_y = |state: &CodeEditorState, line: usize| {
+            viewport.text_area_top + state.target_scroll_offset + line as f32 * line_height
+        };
+
+        // Step one line past the bottom edge: scroll only far enough to show it
+        let last_visible = ((visible_bottom - viewport.text_area_top) / line_height) as usize;
+        let next = last_visible + 1;
+        {
+            let mut state = app.world_mut().resource_mut::<CodeEditorState>();
+            state.last_cursor_pos = state.rope.line_to_char(last_visible);
+            state.cursor_pos = state.rope.line_to_char(next);
+        }
+        app.update();
+        let state = app.world().resource::<CodeEditorState>();
+        assert!((cursor_y(state, next) - visible_bottom).abs() < 1e-3);
+
+        // Jump 500 lines: the target lands in the middle of the viewport
+        let target = next + 500;
+        {
+            let mut state = app.world_mut().resource_mut::<CodeEditorState>();
+            state.cursor_pos = state.rope.line_to_char(target);
+        }
+        app.update();
+        let state = app.world().resource::<CodeEditorState>();
+        let center = (viewport.height as f32 - line_height) / 2.0;
+        assert!((cursor_y(state, target) - center).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_navigation_jumps_center_target() {
+        let mut app = App::new();
+        EditorSettingsBuilder::default()
+            .scrolling(ScrollingSettings {
+                cursor_reveal: CursorReveal::Minimal,
+                ..default()
+            })
+            .build()
+            .insert_into(&mut app);
+        app.insert_resource(ViewportDimensions::default());
+        app.init_resource::<super::super::scrollbar::ScrollbarDragState>();
+        app.insert_resource(FoldState::default());
+        let mut text = "line\n".repeat(1000);
+        text.replace_range(300 * 5..300 * 5 + 4, "find");
+        app.insert_resource(CodeEditorState::new(&text));
+        app.add_systems(Update, auto_scroll_to_cursor);
+
+        let line_height = app.world().resource::<FontSettings>().line_height;
+        let viewport = ViewportDimensions::default();
+        let center = (viewport.height as f32 - line_height) / 2.0;
+        let assert_centered = |app: &mut App, line: usize| {
+            app.update();
+            let state = app.world().resource::<CodeEditorState>();
+            assert_eq!(state.rope.char_to_line(state.cursor_pos), line);
+            let cursor_y = viewport.text_area_top + state.target_scroll_offset + line as f32 * line_height;
+            assert!((cursor_y - center).abs() < 1e-3, "line {} at y {}", line, cursor_y);
+        };
+
+        // Go to line
+        let goto = GotoLineState { active: true, input: "101".to_string() };
+        goto.goto(&mut app.world_mut().resource_mut::<CodeEditorState>());
+        assert_centered(&mut app, 100);
+
+        // Find navigation
+        {
+            let mut state = app.world_mut().resource_mut::<CodeEditorState>();
+            let mut find_state = FindState::default();
+            find_state.set_query("find", &state.rope);
+            find_state.find_next(state.cursor_pos);
+            assert!(find_state.select_current_match(&mut state));
+        }
+        assert_centered(&mut app, 300);
+
+        // Diagnostic navigation
+        {
+            let mut state = app.world_mut().resource_mut::<CodeEditorState>();
+            let start = state.rope.line_to_char(600);
+            let mut diagnostics = EditorDiagnostics::default();
+            diagnostics.set(vec![EditorDiagnostic {
+                range: start..start + 4,
+                level: DiagnosticLevel::Error,
+                message: "unused".to_string(),
+            }]);
+            let found = crate::input::goto_diagnostic(
+                &mut state,
+                &diagnostics,
+                &mut FoldState::default(),
+                true,
+                DiagnosticLevel::Hint,
+            );
+            assert!(found);
+        }
+        assert_centered(&mut app, 600);
+
+        // Go to definition in the same file moves the cursor with `jump_to`
+        {
+            let mut state = app.world_mut().resource_mut::<CodeEditorState>();
+            let target = state.rope.line_to_char(900);
+            state.jump_to(target);
+        }
+        assert_centered(&mut app, 900);
+
+        // With the setting off, a jump back only scrolls far enough to show the target
+        app.world_mut().resource_mut::<NavigationSettings>().center_on_jump = false;
+        goto.goto(&mut app.world_mut().resource_mut::<CodeEditorState>());
+        app.update();
+        let state = app.world().resource::<CodeEditorState>();
+        let cursor_y = viewport.text_area_top + state.target_scroll_offset + 100.0 * line_height;
+        assert!((cursor_y - line_height * 2.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_jump_centers_display_row_below_fold() {
+        let mut app = App::new();
+        EditorSettingsBuilder::default().build().insert_into(&mut app);
+        app.insert_resource(ViewportDimensions::default());
+        app.init_resource::<super::super::scrollbar::ScrollbarDragState>();
+        let mut fold_state = FoldState::default();
+        assert!(fold_state.add_manual_fold(10, 400));
+        app.insert_resource(fold_state);
+        app.insert_resource(CodeEditorState::new(&"line\n".repeat(1000)));
+        app.add_systems(Update, auto_scroll_to_cursor);
+
+        let line_height = app.world().resource::<FontSettings>().line_height;
+        let viewport = ViewportDimensions::default();
+        {
+            let mut state = app.world_mut().resource_mut::<CodeEditorState>();
+            let target = state.rope.line_to_char(600);
+            state.jump_to(target);
+        }
+        app.update();
+
+        // Line 600 sits 390 rows higher with lines 11..=400 folded away
+        let state = app.world().resource::<CodeEditorState>();
+        let row = app.world().resource::<FoldState>().actual_to_display_line(600);
+        assert_eq!(row, 210);
+        let cursor_y = viewport.text_area_top + state.target_scroll_offset + row as f32 * line_height;
+        let center = (viewport.height as f32 - line_height) / 2.0;
+        assert!((cursor_y - center).abs() < 1e-3, "row {} at y {}", row, cursor_y);
+    }
+
+    #[test]
+    fn test_selected_tab_highlight_spans_expanded_width() {
+        let mut app = App::new();
+        EditorSettingsBuilder::default().build().insert_into(&mut app);
+        app.insert_resource(FoldState::default());
+        app.insert_resource(ViewportDimensions::default());
+
+        // Select just the tab after "a": it runs from column 1 to the stop at 4
+        let mut state = CodeEditorState::new("a\tb\n");
+        state.cursors[0].anchor = Some(1);
+        state.cursors[0].position = 2;
+        state.cursor_pos = 2;
+        app.insert_resource(state);
+        app.add_systems(Update, (update_selection_highlight, update_selection_whitespace));
+        app.update();
+
+        let char_width = app.world().resource::<FontSettings>().char_width;
+        let world = app.world_mut();
+        let mut highlights = world.query_filtered::<&Sprite, With<SelectionHighlight>>();
+        let widths: Vec<f32> = highlights.iter(world).filter_map(|s| s.custom_size).map(|s| s.x).collect();
+        assert_eq!(widths, vec![3.0 * char_width]);
+
+        // The tab's arrow is drawn even though space markers are off
+        let mut markers = world.query_filtered::<&Sprite, With<SelectionWhitespaceMarker>>();
+        let shaft = markers.iter(world).filter_map(|s| s.custom_size).map(|s| s.x).fold(0.0, f32::max);
+        assert!(shaft > 2.0 * char_width);
+    }
+
+    #[test]
+    fn test_selection_to_next_line_start_shows_newline_highlight() {
+        let state = CodeEditorState::new("hello\nworld\n");
+        let fold_state = FoldState::default();
+
+        // Select from the middle of line 0 to the start of line 1
+        let mut rects = Vec::new();
+        collect_selection_rects(&state, &fold_state, false, 0, 2, 6, &mut rects);
+
+        assert_eq!(rects.len(), 1);
+        let rect = rects[0];
+        assert_eq!(rect.display_row, 0);
+        assert_eq!((rect.start_col, rect.end_col), (2, 5));
+        assert!(rect.includes_newline);
+    }
+
+    #[test]
+    fn test_undo_moves_cursor_to_reverted_edit_and_reveals_it() {
+        let mut app = App::new();
+        EditorSettingsBuilder::default().build().insert_into(&mut app);
+        app.insert_resource(ViewportDimensions::default());
+        app.init_resource::<super::super::scrollbar::ScrollbarDragState>();
+        app.insert_resource(FoldState::default());
+        app.insert_resource(CodeEditorState::new(&"line\n".repeat(1000)));
+        app.add_systems(Update, auto_scroll_to_cursor);
+
+        // Edit line 900, then jump back to the top and scroll there
+        let edit_pos;
+        {
+            let mut state = app.world_mut().resource_mut::<CodeEditorState>();
+            edit_pos = state.rope.line_to_char(900);
+            state.cursor_pos = edit_pos;
+            state.insert_char('x');
+            state.cursor_pos = 0;
+            state.last_cursor_pos = 0;
+        }
+        app.update();
+        assert_eq!(app.world().resource::<CodeEditorState>().target_scroll_offset, 0.0);
+
+        {
+            let mut state = app.world_mut().resource_mut::<CodeEditorState>();
+            assert!(state.undo());
+            assert_eq!(state.cursor_pos, edit_pos);
+        }
+        app.update();
+
+        let line_height = app.world().resource::<FontSettings>().line_height;
+        let viewport = ViewportDimensions::default();
+        let state = app.world().resource::<CodeEditorState>();
+        let cursor_y = viewport.text_area_top + state.target_scroll_offset + 900.0 * line_height;
+        assert!(cursor_y >= 0.0 && cursor_y <= viewport.height as f32);
+        assert!(!state.reveal_cursor);
+    }
+
+    #[test]
+    fn test_boundary_whitespace_marks_only_leading_and_trailing() {
+        let state = CodeEditorState::new("\tlet a = 1;  \n    \nx y\n");
+        let fold_state = FoldState::default();
+
+        let cells = visible_whitespace_cells(&state, &fold_state, false, 0..10, WhitespaceMode::Boundary);
+        let cols: Vec<_> = cells.iter().map(|c| (c.display_row, c.col, c.ch)).collect();
+        // The tab expands to column 4, so the trailing spaces sit after "let a = 1;"
+        assert_eq!(
+            cols,
+            vec![
+                (0, 0, '\t'),
+                (0, 14, ' '),
+                (0, 15, ' '),
+                (1, 0, ' '),
+                (1, 1, ' '),
+                (1, 2, ' '),
+                (1, 3, ' '),
+            ]
+        );
+
+        let all = visible_whitespace_cells(&state, &fold_state, false, 0..10, WhitespaceMode::All);
+        assert!(all.iter().any(|c| (c.display_row, c.col) == (2, 1)));
+        assert!(visible_whitespace_cells(&state, &fold_state, false, 0..10, WhitespaceMode::Selection).is_empty());
+    }
+
+    #[test]
+    fn test_column_rulers_track_horizontal_scroll() {
+        let mut app = App::new();
+        EditorSettingsBuilder::default()
+            .ui(UiSettings {
+                rulers: vec![20, 40, 100],
+                ..Default::default()
+            })
+            .build()
+            .insert_into(&mut app);
+        app.insert_resource(ViewportDimensions::default());
+        app.insert_resource(CodeEditorState::new("fn main() {}\n"));
+        app.add_systems(Update, update_column_rulers);
+        app.update();
+
+        let char_width = app.world().resource::<FontSettings>().char_width;
+        let ruler_xs = |app: &mut App| {
+            let world = app.world_mut();
+            let mut rulers = world.query::<(&ColumnRuler, &Transform, &Visibility)>();
+            let mut xs: Vec<_> = rulers.iter(world).map(|(r, t, v)| (r.column, t.translation.x, *v)).collect();
+            xs.sort_by_key(|(column, _, _)| *column);
+            xs
+        };
+
+        let before = ruler_xs(&mut app);
+        assert_eq!(before.len(), 3);
+        assert!((before[1].1 - before[0].1 - 20.0 * char_width).abs() < 1e-3);
+        // Column 100 is past the text area, under the minimap
+        assert_eq!(before[2].2, Visibility::Hidden);
+
+        // Scrolling right by ten columns moves both rulers left by the same amount
+        app.world_mut().resource_mut::<CodeEditorState>().horizontal_scroll_offset = 10.0 * char_width;
+        app.update();
+        let after = ruler_xs(&mut app);
+        assert!((before[0].1 - after[0].1 - 10.0 * char_width).abs() < 1e-3);
+        assert!(after[..2].iter().all(|(_, _, v)| *v == Visibility::Visible));
+    }
+
+    #[test]
+    fn test_scroll_past_end_lets_last_line_center() {
+        let reveal_last_line = |scroll_past_end: bool| {
+            let mut app = App::new();
+            EditorSettingsBuilder::default()
+                .scrolling(ScrollingSettings {
+                    cursor_reveal: CursorReveal::Center,
+                    scroll_past_end,
+                    ..Default::default()
+                })
+                .build()
+                .insert_into(&mut app);
+            app.insert_resource(ViewportDimensions::default());
+            app.init_resource::<super::super::scrollbar::ScrollbarDragState>();
+            app.insert_resource(FoldState::default());
+        app.insert_resource(FoldState::default());
+            let mut state = CodeEditorState::new(&"line\n".repeat(1000));
+            state.cursor_pos = state.rope.len_chars();
+            app.insert_resource(state);
+            app.add_systems(Update, auto_scroll_to_cursor);
+            app.update();
+
+            let line_height = app.world().resource::<FontSettings>().line_height;
+            let state = app.world().resource::<CodeEditorState>();
+            let last_line = state.rope.len_lines() - 1;
+            ViewportDimensions::default().text_area_top + state.target_scroll_offset + last_line as f32 * line_height
+        };
+
+        let viewport = ViewportDimensions::default();
+        let line_height = FontSettings::default().line_height;
+        let center = (viewport.height as f32 - line_height) / 2.0;
+
+        // Without scroll-past-end the last line stays pinned to the bottom
+        assert!((reveal_last_line(false) - (viewport.height as f32 - line_height)).abs() < 1e-3);
+        assert!((reveal_last_line(true) - center).abs() < 1e-3);
+    }
+}
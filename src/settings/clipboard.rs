@@ -0,0 +1,24 @@
+//! Clipboard and kill ring settings
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Clipboard settings
+#[derive(Clone, Debug, Resource, Serialize, Deserialize)]
+pub struct ClipboardSettings {
+    /// Maximum number of entries kept in the kill ring
+    pub kill_ring_capacity: usize,
+
+    /// Copy without a selection takes the whole line, and pasting a whole-line
+    /// copy inserts it as a new line above the cursor's line (VS Code style)
+    pub full_line_paste: bool,
+}
+
+impl Default for ClipboardSettings {
+    fn default() -> Self {
+        Self {
+            kill_ring_capacity: 16,
+            full_line_paste: true,
+        }
+    }
+}
@@ -9,6 +9,10 @@ pub struct FontSettings {
     /// Font family path or name
     pub family: String,
 
+    /// Fonts (paths or family names) tried in order for characters the
+    /// primary font has no glyph for, e.g. CJK or emoji
+    pub fallback_fonts: Vec<String>,
+
     /// Font size in pixels
     pub size: f32,
 
@@ -24,21 +28,35 @@ pub struct FontSettings {
     /// Letter spacing adjustment
     pub letter_spacing: f32,
 
+    /// Measure `char_width` from the font's rendered advance instead of
+    /// estimating it from `size` (re-measured when the font or size changes)
+    pub auto_measure_char_width: bool,
+
     /// Cached font handle (set at runtime)
     #[serde(skip)]
     pub handle: Option<Handle<Font>>,
 }
 
+impl FontSettings {
+    /// Monospace advance estimated from the font size, used until (or
+    /// instead of) measuring the real one
+    pub fn estimated_char_width(size: f32) -> f32 {
+        size * 0.6
+    }
+}
+
 impl Default for FontSettings {
     fn default() -> Self {
         let size = 14.0;
         Self {
             family: "fonts/FiraMono-Regular.ttf".to_string(),
+            fallback_fonts: Vec::new(),
             size,
-            char_width: size * 0.6,
+            char_width: Self::estimated_char_width(size),
             line_height: size * 1.5,
             weight: 400,
             letter_spacing: 0.0,
+            auto_measure_char_width: false,
             handle: None,
         }
     }
@@ -80,6 +98,12 @@ pub struct ThemeSettings {
     /// Indent guide line color
     pub indent_guide: Color,
 
+    /// Whitespace marker color (space dots and tab arrows)
+    pub whitespace: Color,
+
+    /// Column ruler line color
+    pub ruler: Color,
+
     /// Matching bracket highlight color
     pub bracket_match: Color,
 
@@ -89,6 +113,9 @@ pub struct ThemeSettings {
     /// Current find match highlight color (the selected one)
     pub find_match_current: Color,
 
+    /// Bookmark gutter marker color
+    pub bookmark: Color,
+
     /// Minimap background color
     pub minimap_background: Color,
 
@@ -130,9 +157,12 @@ impl ThemeSettings {
             gutter_background: Color::srgb(0.098, 0.098, 0.098),
             separator: Color::srgb(0.2, 0.2, 0.2),
             indent_guide: Color::srgba(0.4, 0.4, 0.4, 0.2),
+            whitespace: Color::srgba(0.5, 0.5, 0.5, 0.5),
+            ruler: Color::srgba(0.4, 0.4, 0.4, 0.35),
             bracket_match: Color::srgba(0.0, 1.0, 0.5, 0.3),
             find_match: Color::srgba(1.0, 1.0, 0.0, 0.3),
             find_match_current: Color::srgba(1.0, 0.647, 0.0, 0.5),
+            bookmark: Color::srgb(0.259, 0.569, 0.898),
             minimap_background: Color::srgba(0.08, 0.08, 0.08, 0.8),
             minimap_viewport_highlight: Color::srgba(0.3, 0.3, 0.3, 0.3),
             minimap_slider: Color::srgba(0.4, 0.4, 0.4, 0.5),
@@ -163,9 +193,12 @@ impl ThemeSettings {
             gutter_background: Color::srgb(0.95, 0.95, 0.95),
             separator: Color::srgb(0.85, 0.85, 0.85),
             indent_guide: Color::srgba(0.6, 0.6, 0.6, 0.2),
+            whitespace: Color::srgba(0.45, 0.45, 0.45, 0.5),
+            ruler: Color::srgba(0.6, 0.6, 0.6, 0.35),
             bracket_match: Color::srgba(0.0, 0.8, 0.4, 0.3),
             find_match: Color::srgba(0.9, 0.9, 0.0, 0.3),
             find_match_current: Color::srgba(1.0, 0.647, 0.0, 0.5),
+            bookmark: Color::srgb(0.0, 0.447, 0.788),
             minimap_background: Color::srgba(0.9, 0.9, 0.9, 0.8),
             minimap_viewport_highlight: Color::srgba(0.7, 0.7, 0.7, 0.3),
             minimap_slider: Color::srgba(0.6, 0.6, 0.6, 0.5),
@@ -26,6 +26,13 @@ pub struct CursorSettings {
 
     /// Key repeat settings
     pub key_repeat: KeyRepeatSettings,
+
+    /// Move the cursor to the edit after undo/redo (false keeps it where it is)
+    pub restore_on_undo: bool,
+
+    /// Home moves to the first non-whitespace character, and to column 0 when
+    /// already there
+    pub smart_home: bool,
 }
 
 /// Key repeat settings for cursor movement
@@ -55,6 +62,8 @@ impl Default for CursorSettings {
             smooth_animation: true,
             animation_speed: 10.0,
             key_repeat: KeyRepeatSettings::default(),
+            restore_on_undo: true,
+            smart_home: true,
         }
     }
 }
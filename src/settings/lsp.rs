@@ -2,6 +2,7 @@
 
 use bevy::prelude::*;
 use serde::{Deserialize, Serialize};
+use crate::types::DiagnosticLevel;
 
 /// LSP settings
 #[derive(Clone, Debug, Resource, Serialize, Deserialize)]
@@ -11,6 +12,15 @@ pub struct LspSettings {
 
     /// Hover information settings
     pub hover: HoverSettings,
+
+    /// Least severe diagnostic that NextDiagnostic / PrevDiagnostic stop at
+    pub diagnostic_nav_min_severity: DiagnosticLevel,
+
+    /// Format the document with `textDocument/formatting` before emitting `SaveRequested`
+    pub format_on_save: bool,
+
+    /// How long a save waits for formatting edits before saving unformatted (milliseconds)
+    pub format_on_save_timeout_ms: u64,
 }
 
 /// Auto-completion settings
@@ -74,6 +84,9 @@ impl Default for LspSettings {
         Self {
             completion: CompletionSettings::default(),
             hover: HoverSettings::default(),
+            diagnostic_nav_min_severity: DiagnosticLevel::Hint,
+            format_on_save: false,
+            format_on_save_timeout_ms: 1000,
         }
     }
 }
@@ -18,7 +18,7 @@ pub struct MinimapSettings {
     /// Minimap font size
     pub font_size: f32,
 
-    /// Maximum column to render
+    /// Maximum width to render, in cells (wide characters take two)
     pub max_column: usize,
 
     /// Center minimap content when shorter than viewport
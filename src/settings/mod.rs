@@ -10,9 +10,11 @@ mod scrollbar;
 mod minimap;
 mod cursor;
 mod scrolling;
+mod navigation;
 mod search;
 mod performance;
 mod wrapping;
+mod clipboard;
 
 #[cfg(feature = "lsp")]
 mod lsp;
@@ -24,9 +26,11 @@ pub use scrollbar::*;
 pub use minimap::*;
 pub use cursor::*;
 pub use scrolling::*;
+pub use navigation::*;
 pub use search::*;
 pub use performance::*;
 pub use wrapping::*;
+pub use clipboard::*;
 
 #[cfg(feature = "lsp")]
 pub use lsp::*;
@@ -55,10 +59,17 @@ pub struct EditorSettingsBuilder {
     cursor: CursorSettings,
     cursor_line: CursorLineSettings,
     scrolling: ScrollingSettings,
+    navigation: NavigationSettings,
     search: SearchSettings,
     syntax: SyntaxSettings,
     performance: PerformanceSettings,
     wrapping: WrappingSettings,
+    clipboard: ClipboardSettings,
+
+    /// Explicit line height, kept even if `font_size` is called afterwards
+    line_height: Option<f32>,
+    /// Explicit character width, kept even if `font_size` is called afterwards
+    char_width: Option<f32>,
 
     #[cfg(feature = "lsp")]
     lsp: LspSettings,
@@ -77,10 +88,15 @@ impl Default for EditorSettingsBuilder {
             cursor: CursorSettings::default(),
             cursor_line: CursorLineSettings::default(),
             scrolling: ScrollingSettings::default(),
+            navigation: NavigationSettings::default(),
             search: SearchSettings::default(),
             syntax: SyntaxSettings::default(),
             performance: PerformanceSettings::default(),
             wrapping: WrappingSettings::default(),
+            clipboard: ClipboardSettings::default(),
+
+            line_height: None,
+            char_width: None,
 
             #[cfg(feature = "lsp")]
             lsp: LspSettings::default(),
@@ -97,7 +113,28 @@ impl EditorSettingsBuilder {
     pub fn font_size(mut self, size: f32) -> Self {
         self.font.size = size;
         self.font.line_height = size * 1.5;
-        self.font.char_width = size * 0.6;
+        self.font.char_width = FontSettings::estimated_char_width(size);
+        self
+    }
+
+    /// Set the line height in pixels, independent of the font size
+    pub fn line_height(mut self, line_height: f32) -> Self {
+        self.line_height = Some(line_height);
+        self
+    }
+
+    /// Set the monospace character width in pixels, independent of the font size
+    ///
+    /// Disables `auto_measure_char_width` so the explicit width is kept.
+    pub fn char_width(mut self, char_width: f32) -> Self {
+        self.char_width = Some(char_width);
+        self.font.auto_measure_char_width = false;
+        self
+    }
+
+    /// Measure the character width from the rendered font instead of estimating it
+    pub fn auto_measure_char_width(mut self, enabled: bool) -> Self {
+        self.font.auto_measure_char_width = enabled;
         self
     }
 
@@ -153,6 +190,11 @@ impl EditorSettingsBuilder {
         self
     }
 
+    pub fn navigation(mut self, navigation: NavigationSettings) -> Self {
+        self.navigation = navigation;
+        self
+    }
+
     pub fn search(mut self, search: SearchSettings) -> Self {
         self.search = search;
         self
@@ -188,6 +230,11 @@ impl EditorSettingsBuilder {
         self
     }
 
+    pub fn clipboard(mut self, clipboard: ClipboardSettings) -> Self {
+        self.clipboard = clipboard;
+        self
+    }
+
     #[cfg(feature = "lsp")]
     pub fn lsp(mut self, lsp: LspSettings) -> Self {
         self.lsp = lsp;
@@ -196,7 +243,14 @@ impl EditorSettingsBuilder {
 
     /// Build and return tuple of all settings resources
     /// Insert these into your Bevy app
-    pub fn build(self) -> SettingsBundle {
+    pub fn build(mut self) -> SettingsBundle {
+        if let Some(line_height) = self.line_height {
+            self.font.line_height = line_height;
+        }
+        if let Some(char_width) = self.char_width {
+            self.font.char_width = char_width;
+        }
+
         SettingsBundle {
             font: self.font,
             theme: self.theme,
@@ -208,10 +262,12 @@ impl EditorSettingsBuilder {
             cursor: self.cursor,
             cursor_line: self.cursor_line,
             scrolling: self.scrolling,
+            navigation: self.navigation,
             search: self.search,
             syntax: self.syntax,
             performance: self.performance,
             wrapping: self.wrapping,
+            clipboard: self.clipboard,
 
             #[cfg(feature = "lsp")]
             lsp: self.lsp,
@@ -233,10 +289,12 @@ pub struct SettingsBundle {
     pub cursor: CursorSettings,
     pub cursor_line: CursorLineSettings,
     pub scrolling: ScrollingSettings,
+    pub navigation: NavigationSettings,
     pub search: SearchSettings,
     pub syntax: SyntaxSettings,
     pub performance: PerformanceSettings,
     pub wrapping: WrappingSettings,
+    pub clipboard: ClipboardSettings,
 
     #[cfg(feature = "lsp")]
     pub lsp: LspSettings,
@@ -255,12 +313,35 @@ impl SettingsBundle {
         app.insert_resource(self.cursor);
         app.insert_resource(self.cursor_line);
         app.insert_resource(self.scrolling);
+        app.insert_resource(self.navigation);
         app.insert_resource(self.search);
         app.insert_resource(self.syntax);
         app.insert_resource(self.performance);
         app.insert_resource(self.wrapping);
+        app.insert_resource(self.clipboard);
 
         #[cfg(feature = "lsp")]
         app.insert_resource(self.lsp);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_explicit_metrics_survive_font_size() {
+        let bundle = EditorSettingsBuilder::default()
+            .line_height(30.0)
+            .char_width(9.0)
+            .font_size(16.0)
+            .build();
+
+        assert_eq!(bundle.font.size, 16.0);
+        assert_eq!(bundle.font.line_height, 30.0);
+        assert_eq!(bundle.font.char_width, 9.0);
+
+        let derived = EditorSettingsBuilder::default().font_size(16.0).build();
+        assert_eq!(derived.font.line_height, 24.0);
+    }
+}
@@ -0,0 +1,20 @@
+//! Navigation settings
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// How navigation jumps (go to definition, go to line, find, diagnostics) reveal their target
+#[derive(Clone, Debug, Resource, Serialize, Deserialize)]
+pub struct NavigationSettings {
+    /// Center an off-screen jump target in the viewport instead of scrolling
+    /// just far enough to show it
+    pub center_on_jump: bool,
+}
+
+impl Default for NavigationSettings {
+    fn default() -> Self {
+        Self {
+            center_on_jump: true,
+        }
+    }
+}
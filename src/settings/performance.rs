@@ -11,6 +11,20 @@ pub struct PerformanceSettings {
 
     /// Enable GPU-accelerated text rendering
     pub gpu_text: bool,
+
+    /// Scroll speed (in lines per second) above which extra lines are spawned
+    /// ahead of the viewport in the scroll direction
+    pub predictive_spawn_threshold: f32,
+
+    /// How far ahead (in seconds of scrolling at the current velocity) to spawn lines
+    pub predictive_spawn_lookahead: f32,
+
+    /// Upper bound on the number of extra lines spawned ahead of a fast scroll
+    pub predictive_spawn_max_lines: usize,
+
+    /// Maximum number of new glyphs rasterized per frame (None = unlimited);
+    /// the rest are drawn blank and filled in over the following frames
+    pub max_glyphs_per_frame: Option<usize>,
 }
 
 impl Default for PerformanceSettings {
@@ -18,6 +32,10 @@ impl Default for PerformanceSettings {
         Self {
             viewport_buffer_lines: 10,
             gpu_text: true,
+            predictive_spawn_threshold: 30.0,
+            predictive_spawn_lookahead: 0.15,
+            predictive_spawn_max_lines: 200,
+            max_glyphs_per_frame: Some(64),
         }
     }
 }
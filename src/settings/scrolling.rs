@@ -17,6 +17,49 @@ pub struct ScrollingSettings {
 
     /// Keep cursor visible when scrolling (pixels from edge)
     pub cursor_margin: f32,
+
+    /// How the view follows a cursor that moves off screen
+    pub cursor_reveal: CursorReveal,
+
+    /// With `CursorReveal::Adaptive`, moves of more than this many lines are centered
+    pub center_jump_threshold: usize,
+
+    /// Allow scrolling past the last line, leaving blank space below it
+    pub scroll_past_end: bool,
+
+    /// Blank lines allowed below the last line with `scroll_past_end`;
+    /// 0 lets the last line reach the top of the viewport
+    pub scroll_past_end_lines: u32,
+}
+
+impl ScrollingSettings {
+    /// Most negative vertical scroll offset for content of `content_height` pixels
+    ///
+    /// Without `scroll_past_end` the last line stops at the bottom of the
+    /// viewport. The result is never positive, so short content doesn't scroll.
+    pub fn max_scroll(&self, content_height: f32, viewport_height: f32, text_area_top: f32, line_height: f32) -> f32 {
+        let visible_height = viewport_height - text_area_top;
+        let past_end = if !self.scroll_past_end {
+            0.0
+        } else if self.scroll_past_end_lines == 0 {
+            visible_height - line_height
+        } else {
+            (self.scroll_past_end_lines as f32 * line_height).min(visible_height - line_height)
+        };
+        (-(content_height - visible_height + past_end.max(0.0))).min(0.0)
+    }
+}
+
+/// How auto-scroll brings an off-screen cursor into view
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CursorReveal {
+    /// Scroll just enough to show the cursor inside the margin
+    Minimal,
+    /// Put the cursor line in the middle of the viewport
+    Center,
+    /// Minimal for small moves, centered for long jumps (e.g. go to line)
+    #[default]
+    Adaptive,
 }
 
 impl Default for ScrollingSettings {
@@ -26,6 +69,10 @@ impl Default for ScrollingSettings {
             smooth: true,
             smooth_duration: 0.15,
             cursor_margin: 50.0,
+            cursor_reveal: CursorReveal::Adaptive,
+            center_jump_threshold: 20,
+            scroll_past_end: false,
+            scroll_past_end_lines: 0,
         }
     }
 }
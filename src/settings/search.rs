@@ -20,6 +20,12 @@ pub struct SearchSettings {
 
     /// Highlight all matches
     pub highlight_all: bool,
+
+    /// Recompute matches as the query changes; when false, only FindNext/FindPrevious search
+    pub live_highlight: bool,
+
+    /// Stop collecting matches after this many (None = unlimited)
+    pub max_matches: Option<usize>,
 }
 
 impl Default for SearchSettings {
@@ -30,6 +36,8 @@ impl Default for SearchSettings {
             regex: false,
             wrap_around: true,
             highlight_all: true,
+            live_highlight: true,
+            max_matches: None,
         }
     }
 }
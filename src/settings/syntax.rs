@@ -11,6 +11,9 @@ pub struct SyntaxSettings {
 
     /// Syntax theme colors
     pub theme: SyntaxTheme,
+
+    /// Token prefixed by `ToggleLineComment` (`None` disables the action)
+    pub line_comment: Option<String>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -32,6 +35,52 @@ pub struct SyntaxTheme {
     pub constructor: Color,
     pub escape: Color,
     pub embedded: Color,
+    /// Font style per category (regular unless set)
+    #[serde(default)]
+    pub styles: SyntaxStyles,
+}
+
+/// Weight and slant a glyph is drawn with
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum FontStyle {
+    #[default]
+    Regular,
+    Bold,
+    Italic,
+    BoldItalic,
+}
+
+impl FontStyle {
+    pub fn is_bold(self) -> bool {
+        matches!(self, Self::Bold | Self::BoldItalic)
+    }
+
+    pub fn is_italic(self) -> bool {
+        matches!(self, Self::Italic | Self::BoldItalic)
+    }
+}
+
+/// Font style for each highlight category, e.g. italic comments and bold keywords
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SyntaxStyles {
+    pub keyword: FontStyle,
+    pub function: FontStyle,
+    pub method: FontStyle,
+    pub string: FontStyle,
+    pub number: FontStyle,
+    pub comment: FontStyle,
+    pub variable: FontStyle,
+    pub operator: FontStyle,
+    pub constant: FontStyle,
+    pub type_name: FontStyle,
+    pub parameter: FontStyle,
+    pub property: FontStyle,
+    pub punctuation: FontStyle,
+    pub label: FontStyle,
+    pub constructor: FontStyle,
+    pub escape: FontStyle,
+    pub embedded: FontStyle,
 }
 
 impl Default for SyntaxSettings {
@@ -39,6 +88,7 @@ impl Default for SyntaxSettings {
         Self {
             enabled: true,
             theme: SyntaxTheme::default(),
+            line_comment: Some("//".to_string()),
         }
     }
 }
@@ -69,6 +119,7 @@ impl SyntaxTheme {
             constructor: Color::srgb(0.298, 0.686, 0.914),
             escape: Color::srgb(0.863, 0.863, 0.549),
             embedded: Color::srgb(0.827, 0.827, 0.827),
+            styles: SyntaxStyles::default(),
         }
     }
 
@@ -91,6 +142,7 @@ impl SyntaxTheme {
             constructor: Color::srgb(0.0, 0.502, 0.502),
             escape: Color::srgb(0.933, 0.286, 0.0),
             embedded: Color::srgb(0.0, 0.0, 0.0),
+            styles: SyntaxStyles::default(),
         }
     }
 }
@@ -21,7 +21,7 @@ pub struct UiSettings {
     /// Show indent guides
     pub show_indent_guides: bool,
 
-    /// Show whitespace characters
+    /// Which spaces and tabs are drawn as dots and arrows
     pub show_whitespace: WhitespaceMode,
 
     /// Highlight current line
@@ -30,6 +30,32 @@ pub struct UiSettings {
     /// Show separator line between gutter and code
     pub show_separator: bool,
 
+    /// Show `~` markers in the gutter for empty rows below the last line (vim-style)
+    pub show_eob_markers: bool,
+
+    /// Number the empty line after a trailing newline; when false its gutter
+    /// stays blank, though the cursor can still be placed there
+    pub number_phantom_line: bool,
+
+    /// Width (in characters) of the highlight drawn past end-of-line when a
+    /// selection includes the line break; 0 disables the indicator
+    pub selection_newline_width: f32,
+
+    /// Draw whitespace markers inside selections, regardless of `show_whitespace`
+    pub render_whitespace_in_selection: bool,
+
+    /// Draw a small bottom-right overlay with macro recording, cursor count and search matches
+    pub show_status_overlay: bool,
+
+    /// Columns at which to draw vertical ruler lines (e.g. `vec![80, 120]`)
+    pub rulers: Vec<u32>,
+
+    /// Thin strip along the right edge marking diagnostics, matches and edits
+    pub overview_ruler: OverviewRulerSettings,
+
+    /// Ordered gutter sub-columns (line numbers, fold markers, ...)
+    pub gutter_layout: GutterLayout,
+
     // UI plugin uses these preferences to compute ViewportDimensions layout
     /// Gutter padding left (pixels)
     pub gutter_padding_left: f32,
@@ -44,12 +70,18 @@ pub struct UiSettings {
     pub margin_top: f32,
 }
 
-
+/// Which whitespace characters are rendered as visible markers
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum WhitespaceMode {
+    /// No markers (tabs inside selections are still drawn)
     None,
+    /// Only whitespace inside selections
     Selection,
+    /// Only whitespace after the last non-whitespace character of a line
     Trailing,
+    /// Leading indentation and trailing whitespace, but not gaps between words
+    Boundary,
+    /// Every space and tab on visible lines
     All,
 }
 
@@ -63,6 +95,14 @@ impl Default for UiSettings {
             show_whitespace: WhitespaceMode::None,
             highlight_active_line: true,
             show_separator: true,
+            show_eob_markers: false,
+            number_phantom_line: true,
+            selection_newline_width: 0.5,
+            render_whitespace_in_selection: false,
+            show_status_overlay: false,
+            rulers: Vec::new(),
+            overview_ruler: OverviewRulerSettings::default(),
+            gutter_layout: GutterLayout::default(),
             gutter_padding_left: 10.0,
             gutter_padding_right: 10.0,
             code_margin_left: 10.0,
@@ -71,6 +111,84 @@ impl Default for UiSettings {
     }
 }
 
+/// A gutter sub-column
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GutterColumnKind {
+    LineNumbers,
+    Fold,
+    Diagnostics,
+    Git,
+    /// Bookmark markers; without this column they sit in the left padding
+    Bookmarks,
+    /// Host-defined column, looked up by name
+    Custom(String),
+}
+
+/// One sub-column of the gutter
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct GutterColumn {
+    /// What the column shows
+    pub kind: GutterColumnKind,
+
+    /// Width in pixels (None = fit 4 digits, for line numbers)
+    pub width: Option<f32>,
+}
+
+impl GutterColumn {
+    /// Column with a fixed pixel width
+    pub fn new(kind: GutterColumnKind, width: f32) -> Self {
+        Self { kind, width: Some(width) }
+    }
+}
+
+/// Gutter sub-columns, laid out left to right between the gutter paddings
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct GutterLayout {
+    /// Columns in display order
+    pub columns: Vec<GutterColumn>,
+
+    /// Space between adjacent columns (pixels)
+    pub spacing: f32,
+}
+
+impl Default for GutterLayout {
+    fn default() -> Self {
+        Self {
+            columns: vec![
+                GutterColumn { kind: GutterColumnKind::LineNumbers, width: None },
+                GutterColumn::new(GutterColumnKind::Fold, 12.0),
+            ],
+            spacing: 2.0,
+        }
+    }
+}
+
+/// Overview ruler settings
+///
+/// The ruler maps the whole buffer onto the viewport height, so a mark's
+/// vertical position shows where in the file it is.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct OverviewRulerSettings {
+    /// Show the overview ruler
+    pub enabled: bool,
+
+    /// Strip width (pixels)
+    pub width: f32,
+
+    /// Mark every occurrence of the selected text
+    pub selection_occurrences: bool,
+}
+
+impl Default for OverviewRulerSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            width: 12.0,
+            selection_occurrences: true,
+        }
+    }
+}
+
 /// Indentation settings
 #[derive(Clone, Debug, Resource, Serialize, Deserialize)]
 pub struct IndentationSettings {
@@ -85,6 +203,34 @@ pub struct IndentationSettings {
 
     /// Auto-indent on newline
     pub auto_indent: bool,
+
+    /// Backspace inside leading space indentation removes a whole indent level
+    pub backspace_dedents: bool,
+
+    /// What Tab / Shift+Tab do, tried in order; the first that applies wins
+    pub tab_order: Vec<TabBehavior>,
+}
+
+impl IndentationSettings {
+    /// One indentation level: `indent_size` spaces, or a tab
+    pub fn indent_unit(&self) -> String {
+        if self.use_spaces {
+            " ".repeat(self.indent_size)
+        } else {
+            "\t".to_string()
+        }
+    }
+}
+
+/// A context Tab / Shift+Tab can act on
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TabBehavior {
+    /// Accept the selected completion while the popup is open
+    AcceptCompletion,
+    /// Move between snippet tab stops while a snippet is active
+    NextSnippetStop,
+    /// Indent (Tab) or outdent (Shift+Tab); always applies
+    Indent,
 }
 
 impl Default for IndentationSettings {
@@ -94,6 +240,12 @@ impl Default for IndentationSettings {
             tab_width: 4,
             indent_size: 4,
             auto_indent: true,
+            backspace_dedents: false,
+            tab_order: vec![
+                TabBehavior::AcceptCompletion,
+                TabBehavior::NextSnippetStop,
+                TabBehavior::Indent,
+            ],
         }
     }
 }
@@ -115,6 +267,25 @@ pub struct BracketSettings {
 
     /// Bracket pairs
     pub pairs: Vec<(char, char)>,
+
+    /// Multi-character pairs auto-closed when the opener is typed,
+    /// e.g. `("/*", "*/")` or `("\"\"\"", "\"\"\"")`
+    ///
+    /// The pairs apply to every language, so set them for the language being
+    /// edited. Pairs with an empty opener or closer are ignored.
+    pub multi_char_pairs: Vec<(String, String)>,
+
+    /// Closing characters that are typed over when the same character is
+    /// already next to the cursor, instead of inserting a second one
+    pub type_through: Vec<char>,
+
+    /// When the cursor is not next to a bracket, highlight the nearest
+    /// enclosing pair instead
+    pub highlight_enclosing: bool,
+
+    /// Openers of comma-separated lists (e.g. `[` for JSON); Enter after an
+    /// element inside one adds the missing comma before breaking the line
+    pub comma_on_newline: Vec<char>,
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
@@ -137,6 +308,10 @@ impl Default for BracketSettings {
                 ('{', '}'),
                 ('<', '>'),
             ],
+            multi_char_pairs: Vec::new(),
+            type_through: vec![')', ']', '}', '>', '"', '\'', '`'],
+            highlight_enclosing: false,
+            comma_on_newline: Vec::new(),
         }
     }
 }
@@ -14,6 +14,47 @@ pub struct WrappingSettings {
 
     /// Indent wrapped lines
     pub indent_wrapped_lines: bool,
+
+    /// Wrap at this ruler column and draw a faint guide there (overrides `wrap_column`)
+    pub wrap_at_ruler: Option<u32>,
+
+    /// Never wrap lines longer than this many characters; they scroll horizontally
+    pub no_wrap_longer_than: Option<usize>,
+
+    /// Never wrap lines containing any of these substrings (e.g. `"://"` for URLs)
+    pub no_wrap_patterns: Vec<String>,
+
+    /// Where wrapped rows may end
+    pub mode: WrapMode,
+
+    /// In `WrapMode::Word`, split a word that alone exceeds the wrap width;
+    /// when false it stays whole on its own row and scrolls horizontally
+    pub break_words: bool,
+}
+
+/// Where a wrapped line may be broken
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum WrapMode {
+    /// Break after whitespace; a word is only split when it alone is wider
+    /// than the wrap width (see `WrappingSettings::break_words`)
+    #[default]
+    Word,
+    /// Break at exactly the wrap width, even inside a word
+    Character,
+}
+
+impl WrappingSettings {
+    /// The column text wraps at, if fixed (None = wrap at viewport width)
+    pub fn effective_wrap_column(&self) -> Option<usize> {
+        self.wrap_at_ruler.map(|col| col as usize).or(self.wrap_column)
+    }
+
+    /// Whether `line` is exempt from wrapping
+    pub fn is_no_wrap_line(&self, line: &str) -> bool {
+        self.no_wrap_longer_than
+            .is_some_and(|limit| line.chars().count() > limit)
+            || self.no_wrap_patterns.iter().any(|p| !p.is_empty() && line.contains(p.as_str()))
+    }
 }
 
 impl Default for WrappingSettings {
@@ -22,6 +63,11 @@ impl Default for WrappingSettings {
             enabled: false,
             wrap_column: None,
             indent_wrapped_lines: true,
+            wrap_at_ruler: None,
+            no_wrap_longer_than: None,
+            no_wrap_patterns: Vec::new(),
+            mode: WrapMode::Word,
+            break_words: true,
         }
     }
 }
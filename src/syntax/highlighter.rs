@@ -1,6 +1,7 @@
 //! Syntax highlighting trait and utilities
 
 use bevy::prelude::*;
+use crate::settings::FontStyle;
 use crate::types::LineSegment;
 
 /// Trait for syntax highlighting providers
@@ -42,35 +43,149 @@ pub trait SyntaxProvider: Send + Sync {
     fn is_available(&self) -> bool;
 }
 
+/// Theme category a tree-sitter highlight type belongs to
+#[derive(Clone, Copy)]
+enum HighlightCategory {
+    Keyword,
+    Function,
+    Method,
+    TypeName,
+    Variable,
+    Parameter,
+    Constant,
+    Number,
+    String,
+    Comment,
+    Operator,
+    Punctuation,
+    Property,
+    Constructor,
+    Label,
+    Escape,
+    Embedded,
+}
+
+impl HighlightCategory {
+    fn from_highlight(highlight_type: &str) -> Option<Self> {
+        // Sub-categories with a theme entry of their own
+        if highlight_type.starts_with("function.method") {
+            return Some(Self::Method);
+        }
+        if highlight_type.starts_with("variable.parameter") {
+            return Some(Self::Parameter);
+        }
+        if highlight_type.starts_with("constant.numeric") {
+            return Some(Self::Number);
+        }
+
+        let base_category = highlight_type.split('.').next().unwrap_or(highlight_type);
+
+        Some(match base_category {
+            "keyword" | "conditional" | "repeat" | "exception" => Self::Keyword,
+            "function" => Self::Function,
+            "method" => Self::Method,
+            "type" | "class" | "interface" | "struct" | "enum" => Self::TypeName,
+            "variable" | "field" => Self::Variable,
+            "parameter" => Self::Parameter,
+            "constant" | "boolean" => Self::Constant,
+            "number" | "float" => Self::Number,
+            "string" | "character" => Self::String,
+            "comment" | "note" | "warning" | "danger" => Self::Comment,
+            "operator" => Self::Operator,
+            "punctuation" | "delimiter" | "bracket" | "special" => Self::Punctuation,
+            "property" | "attribute" | "tag" | "decorator" => Self::Property,
+            "constructor" => Self::Constructor,
+            "label" => Self::Label,
+            "escape" => Self::Escape,
+            "embedded" | "include" | "preproc" => Self::Embedded,
+            "namespace" | "module" => Self::TypeName,
+            _ => return None,
+        })
+    }
+}
+
 /// Map tree-sitter highlight type to theme color
 pub fn map_highlight_color(
     highlight_type: Option<&str>,
     syntax_theme: &crate::settings::SyntaxTheme,
     default_color: Color,
 ) -> Color {
-    let hl_type = match highlight_type {
-        Some(t) => t,
-        None => return default_color,
+    let Some(category) = highlight_type.and_then(HighlightCategory::from_highlight) else {
+        return default_color;
+    };
+
+    match category {
+        HighlightCategory::Keyword => syntax_theme.keyword,
+        HighlightCategory::Function => syntax_theme.function,
+        HighlightCategory::Method => syntax_theme.method,
+        HighlightCategory::TypeName => syntax_theme.type_name,
+        HighlightCategory::Variable => syntax_theme.variable,
+        HighlightCategory::Parameter => syntax_theme.parameter,
+        HighlightCategory::Constant => syntax_theme.constant,
+        HighlightCategory::Number => syntax_theme.number,
+        HighlightCategory::String => syntax_theme.string,
+        HighlightCategory::Comment => syntax_theme.comment,
+        HighlightCategory::Operator => syntax_theme.operator,
+        HighlightCategory::Punctuation => syntax_theme.punctuation,
+        HighlightCategory::Property => syntax_theme.property,
+        HighlightCategory::Constructor => syntax_theme.constructor,
+        HighlightCategory::Label => syntax_theme.label,
+        HighlightCategory::Escape => syntax_theme.escape,
+        HighlightCategory::Embedded => syntax_theme.embedded,
+    }
+}
+
+/// Map tree-sitter highlight type to the theme's font style for it
+pub fn map_highlight_style(
+    highlight_type: Option<&str>,
+    syntax_theme: &crate::settings::SyntaxTheme,
+) -> FontStyle {
+    let Some(category) = highlight_type.and_then(HighlightCategory::from_highlight) else {
+        return FontStyle::Regular;
     };
 
-    let base_category = hl_type.split('.').next().unwrap_or(hl_type);
+    let styles = &syntax_theme.styles;
+    match category {
+        HighlightCategory::Keyword => styles.keyword,
+        HighlightCategory::Function => styles.function,
+        HighlightCategory::Method => styles.method,
+        HighlightCategory::TypeName => styles.type_name,
+        HighlightCategory::Variable => styles.variable,
+        HighlightCategory::Parameter => styles.parameter,
+        HighlightCategory::Constant => styles.constant,
+        HighlightCategory::Number => styles.number,
+        HighlightCategory::String => styles.string,
+        HighlightCategory::Comment => styles.comment,
+        HighlightCategory::Operator => styles.operator,
+        HighlightCategory::Punctuation => styles.punctuation,
+        HighlightCategory::Property => styles.property,
+        HighlightCategory::Constructor => styles.constructor,
+        HighlightCategory::Label => styles.label,
+        HighlightCategory::Escape => styles.escape,
+        HighlightCategory::Embedded => styles.embedded,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::SyntaxTheme;
+
+    #[test]
+    fn test_highlight_style_follows_theme_category() {
+        let mut theme = SyntaxTheme::default();
+        theme.styles.comment = FontStyle::Italic;
+        theme.styles.keyword = FontStyle::Bold;
+        theme.styles.parameter = FontStyle::Italic;
 
-    match base_category {
-        "keyword" | "conditional" | "repeat" | "exception" => syntax_theme.keyword,
-        "function" | "method" => syntax_theme.function,
-        "type" | "class" | "interface" | "struct" | "enum" => syntax_theme.type_name,
-        "variable" | "parameter" | "field" => syntax_theme.variable,
-        "constant" | "boolean" | "number" | "float" => syntax_theme.constant,
-        "string" | "character" => syntax_theme.string,
-        "comment" | "note" | "warning" | "danger" => syntax_theme.comment,
-        "operator" => syntax_theme.operator,
-        "punctuation" | "delimiter" | "bracket" | "special" => syntax_theme.punctuation,
-        "property" | "attribute" | "tag" | "decorator" => syntax_theme.property,
-        "constructor" => syntax_theme.constructor,
-        "label" => syntax_theme.label,
-        "escape" => syntax_theme.escape,
-        "embedded" | "include" | "preproc" => syntax_theme.embedded,
-        "namespace" | "module" => syntax_theme.type_name,
-        _ => default_color,
+        assert_eq!(map_highlight_style(Some("comment.line"), &theme), FontStyle::Italic);
+        assert_eq!(map_highlight_style(Some("keyword.control"), &theme), FontStyle::Bold);
+        assert_eq!(map_highlight_style(Some("string"), &theme), FontStyle::Regular);
+        assert_eq!(map_highlight_style(Some("parameter"), &theme), FontStyle::Italic);
+        assert_eq!(map_highlight_style(Some("variable"), &theme), FontStyle::Regular);
+        assert_eq!(map_highlight_style(None, &theme), FontStyle::Regular);
+        assert_eq!(map_highlight_color(Some("comment"), &theme, Color::WHITE), theme.comment);
+        assert_eq!(map_highlight_color(Some("number"), &theme, Color::WHITE), theme.number);
+        assert_eq!(map_highlight_color(Some("function.method"), &theme, Color::WHITE), theme.method);
     }
 }
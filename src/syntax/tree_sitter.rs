@@ -3,8 +3,9 @@
 
 use bevy::prelude::*;
 use tree_sitter::{Language, Parser, Query, QueryCursor, Tree};
+use crate::settings::FontStyle;
 use crate::types::LineSegment;
-use super::highlighter::{SyntaxProvider, map_highlight_color};
+use super::highlighter::{SyntaxProvider, map_highlight_color, map_highlight_style};
 use std::ops::Range;
 use streaming_iterator::StreamingIterator;
 use ropey::Rope;
@@ -115,6 +116,10 @@ pub struct TreeSitterProvider {
 
     /// Cached full document rope (needed for TextProvider)
     pub(crate) cached_rope: Option<Rope>,
+
+    /// Name of the highlighted language (e.g. "rust"), matched against
+    /// markdown fence languages
+    language_name: Option<String>,
 }
 
 impl TreeSitterProvider {
@@ -129,6 +134,7 @@ impl TreeSitterProvider {
             deferred_edits: Vec::new(),
             query_cursor: QueryCursor::new(),
             cached_rope: None,
+            language_name: None,
         }
     }
 
@@ -143,6 +149,16 @@ impl TreeSitterProvider {
         Ok(())
     }
 
+    /// Set the name of the highlighted language (e.g. "rust")
+    pub fn set_language_name(&mut self, name: impl Into<String>) {
+        self.language_name = Some(name.into());
+    }
+
+    /// Name of the highlighted language, if one was set
+    pub fn language_name(&self) -> Option<&str> {
+        self.language_name.as_deref()
+    }
+
     /// Record an edit with full position information for incremental parsing
     pub fn record_edit_with_positions(
         &mut self,
@@ -186,6 +202,28 @@ impl TreeSitterProvider {
         self.cached_tree.as_ref()
     }
 
+    /// Highlight a standalone piece of code (e.g. a hover code block) in this
+    /// provider's language, leaving the document's parse tree untouched
+    pub fn highlight_snippet(
+        &mut self,
+        text: &str,
+        theme: &crate::settings::SyntaxTheme,
+        default_color: Color,
+    ) -> Option<Vec<Vec<LineSegment>>> {
+        self.query.as_ref()?;
+        let mut parser = Parser::new();
+        parser.set_language(self.cached_language.as_ref()?).ok()?;
+        let tree = parser.parse(text, None)?;
+
+        // highlight_range reads the cached tree and rope, so swap the snippet in
+        let document_tree = self.cached_tree.replace(tree);
+        let document_rope = self.cached_rope.replace(Rope::from_str(text));
+        let lines = self.highlight_range(text, 0, text.lines().count(), 0, theme, default_color);
+        self.cached_tree = document_tree;
+        self.cached_rope = document_rope;
+        Some(lines)
+    }
+
     /// Invalidate the tree-sitter tree (like Zed does when content changes)
     /// This clears the cached tree to prevent using stale data with mismatched byte positions
     pub fn invalidate_tree(&mut self) {
@@ -263,6 +301,7 @@ impl SyntaxProvider for TreeSitterProvider {
                             vec![LineSegment {
                                 text: line.to_string(),
                                 color: default_color,
+                                style: FontStyle::Regular,
                             }]
                         }
                     })
@@ -283,6 +322,7 @@ impl SyntaxProvider for TreeSitterProvider {
                             vec![LineSegment {
                                 text: line.to_string(),
                                 color: default_color,
+                                style: FontStyle::Regular,
                             }]
                         }
                     })
@@ -303,6 +343,7 @@ impl SyntaxProvider for TreeSitterProvider {
                             vec![LineSegment {
                                 text: line.to_string(),
                                 color: default_color,
+                                style: FontStyle::Regular,
                             }]
                         }
                     })
@@ -323,6 +364,7 @@ impl SyntaxProvider for TreeSitterProvider {
                         vec![LineSegment {
                             text: line.to_string(),
                             color: default_color,
+                            style: FontStyle::Regular,
                         }]
                     }
                 })
@@ -412,11 +454,13 @@ impl SyntaxProvider for TreeSitterProvider {
                 if segment_end > char_pos {
                     let segment_text = &line[char_pos..segment_end];
                     let color = map_highlight_color(active_highlight, theme, default_color);
+                    let style = map_highlight_style(active_highlight, theme);
 
                     if !segment_text.is_empty() {
                         current_line_segments.push(LineSegment {
                             text: segment_text.to_string(),
                             color,
+                            style,
                         });
                     }
                     char_pos = segment_end;
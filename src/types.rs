@@ -2,11 +2,13 @@
 
 use bevy::prelude::*;
 use ropey::Rope;
+use std::collections::HashMap;
 use std::ops::Range;
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::time::Instant;
 
 use crate::line_width::LineWidthTracker;
+use crate::settings::{FontStyle, WrapMode};
 
 #[cfg(feature = "lsp")]
 use lsp_types::Url;
@@ -213,6 +215,8 @@ impl AnchorSet {
 
     /// Add an anchor to the set and return its ID
     pub fn insert(&mut self, mut anchor: Anchor) -> u64 {
+        // Pending edits predate the new anchor, so they must not move it
+        self.apply_pending_edits();
         anchor.version = self.version;
         let id = anchor.id;
 
@@ -1034,6 +1038,13 @@ pub struct EditOperation {
     pub kind: EditKind,
 }
 
+impl EditOperation {
+    /// Bytes of text held by this operation
+    pub fn size_bytes(&self) -> usize {
+        self.removed_text.len() + self.inserted_text.len()
+    }
+}
+
 /// A transaction groups multiple edits that should be undone/redone together
 #[derive(Clone, Debug)]
 pub struct EditTransaction {
@@ -1041,6 +1052,13 @@ pub struct EditTransaction {
     pub operations: Vec<EditOperation>,
     /// When this transaction was created
     pub timestamp: Instant,
+    /// All cursors and selections before the transaction, restored on undo
+    ///
+    /// Only multi-cursor edits record these; single-cursor transactions fall
+    /// back to the operations' `cursor_before`/`cursor_after`.
+    pub selections_before: Option<SelectionCollection>,
+    /// All cursors and selections after the transaction, restored on redo
+    pub selections_after: Option<SelectionCollection>,
 }
 
 impl EditTransaction {
@@ -1048,12 +1066,19 @@ impl EditTransaction {
         Self {
             operations: Vec::new(),
             timestamp: Instant::now(),
+            selections_before: None,
+            selections_after: None,
         }
     }
 
     pub fn is_empty(&self) -> bool {
         self.operations.is_empty()
     }
+
+    /// Bytes of text held by this transaction's operations
+    pub fn size_bytes(&self) -> usize {
+        self.operations.iter().map(EditOperation::size_bytes).sum()
+    }
 }
 
 impl Default for EditTransaction {
@@ -1062,6 +1087,13 @@ impl Default for EditTransaction {
     }
 }
 
+/// Whether an operation only inserts spaces or tabs
+fn is_whitespace_insertion(operation: &EditOperation) -> bool {
+    operation.removed_text.is_empty()
+        && !operation.inserted_text.is_empty()
+        && operation.inserted_text.chars().all(|c| c == ' ' || c == '\t')
+}
+
 /// History manager for undo/redo operations
 #[derive(Clone, Debug)]
 pub struct EditHistory {
@@ -1075,6 +1107,13 @@ pub struct EditHistory {
     pub group_interval_ms: u64,
     /// Maximum number of transactions to keep
     pub max_history_size: usize,
+    /// Maximum bytes of text kept across undo transactions (`None` = unlimited)
+    ///
+    /// The oldest transactions are dropped first; the newest one is always kept.
+    pub max_history_bytes: Option<usize>,
+    /// Bytes held by `undo_stack`, kept in step with it so trimming
+    /// doesn't have to re-sum the stack
+    undo_bytes: usize,
 }
 
 impl Default for EditHistory {
@@ -1085,13 +1124,21 @@ impl Default for EditHistory {
             current_transaction: None,
             group_interval_ms: 300, // Group edits within 300ms
             max_history_size: 1000,
+            max_history_bytes: None,
+            undo_bytes: 0,
         }
     }
 }
 
 impl EditHistory {
-    /// Record an edit operation
-    pub fn record(&mut self, operation: EditOperation) {
+    /// Record an edit operation, returning whether it started a new transaction
+    pub fn record(&mut self, operation: EditOperation) -> bool {
+        self.group_operation(operation);
+        self.current_transaction.as_ref().is_some_and(|tx| tx.operations.len() == 1)
+    }
+
+    /// Add an operation to the current transaction or start a new one
+    fn group_operation(&mut self, operation: EditOperation) {
         let now = Instant::now();
         let op_kind = operation.kind;
 
@@ -1114,6 +1161,23 @@ impl EditHistory {
                     return self.start_new_transaction(operation, now);
                 }
 
+                // Whitespace typed right after the last insertion (e.g. indentation
+                // after a newline) joins it, taking its kind so the next edit
+                // groups as it would have with the previous one
+                if let Some(last_op) = tx.operations.last() {
+                    let after_insertion = matches!(last_op.kind, EditKind::Insert | EditKind::Newline);
+                    if after_insertion && is_whitespace_insertion(&operation) && operation.position == last_op.cursor_after {
+                        let mut operation = operation;
+                        operation.kind = last_op.kind;
+                        if let Some(tx) = &mut self.current_transaction {
+                            tx.operations.push(operation);
+                            tx.timestamp = now;
+                        }
+                        self.redo_stack.clear();
+                        return;
+                    }
+                }
+
                 // Check if edit kind changed (typing vs deleting)
                 if let Some(last_op) = tx.operations.last() {
                     let last_kind = last_op.kind;
@@ -1172,6 +1236,8 @@ impl EditHistory {
         self.current_transaction = Some(EditTransaction {
             operations: vec![operation],
             timestamp,
+            selections_before: None,
+            selections_after: None,
         });
         // Clear redo stack on new edit
         self.redo_stack.clear();
@@ -1181,20 +1247,80 @@ impl EditHistory {
     pub fn finalize_transaction(&mut self) {
         if let Some(tx) = self.current_transaction.take() {
             if !tx.is_empty() {
-                self.undo_stack.push(tx);
-                // Trim history if needed
-                while self.undo_stack.len() > self.max_history_size {
-                    self.undo_stack.remove(0);
-                }
+                self.push_undo(tx);
             }
         }
     }
 
+    /// Drop the oldest transactions beyond `max_history_size` or `max_history_bytes`
+    fn trim_undo_stack(&mut self) {
+        let budget = self.max_history_bytes.unwrap_or(usize::MAX);
+        while self.undo_stack.len() > self.max_history_size
+            || (self.undo_bytes > budget && self.undo_stack.len() > 1)
+        {
+            self.undo_bytes -= self.undo_stack.remove(0).size_bytes();
+        }
+    }
+
     /// Pop a transaction from the undo stack for undoing
     pub fn pop_undo(&mut self) -> Option<EditTransaction> {
         // First finalize any pending transaction
         self.finalize_transaction();
-        self.undo_stack.pop()
+        let transaction = self.undo_stack.pop()?;
+        self.undo_bytes -= transaction.size_bytes();
+        Some(transaction)
+    }
+
+    /// Record a complete transaction that restores the given selections on undo/redo
+    pub fn record_transaction(
+        &mut self,
+        operations: Vec<EditOperation>,
+        selections_before: SelectionCollection,
+        selections_after: SelectionCollection,
+    ) {
+        if operations.is_empty() {
+            return;
+        }
+        self.finalize_transaction();
+        self.push_undo(EditTransaction {
+            operations,
+            timestamp: Instant::now(),
+            selections_before: Some(selections_before),
+            selections_after: Some(selections_after),
+        });
+        self.redo_stack.clear();
+    }
+
+    /// Record a multi-cursor typing step, joining the previous one when it is
+    /// recent, also only inserted, and left the cursors where this one starts.
+    /// Returns whether it became a new undo step
+    pub fn record_typing_transaction(
+        &mut self,
+        operations: Vec<EditOperation>,
+        selections_before: SelectionCollection,
+        selections_after: SelectionCollection,
+    ) -> bool {
+        if operations.is_empty() {
+            return false;
+        }
+        self.finalize_transaction();
+        let now = Instant::now();
+        if let Some(last) = self.undo_stack.last_mut() {
+            let continues = now.duration_since(last.timestamp).as_millis() as u64 <= self.group_interval_ms
+                && last.selections_after.as_ref().is_some_and(|after| after.iter().eq(selections_before.iter()))
+                && last.operations.iter().chain(&operations).all(|op| op.kind == EditKind::Insert);
+            if continues {
+                self.undo_bytes += operations.iter().map(EditOperation::size_bytes).sum::<usize>();
+                last.operations.extend(operations);
+                last.timestamp = now;
+                last.selections_after = Some(selections_after);
+                self.redo_stack.clear();
+                self.trim_undo_stack();
+                return false;
+            }
+        }
+        self.record_transaction(operations, selections_before, selections_after);
+        true
     }
 
     /// Push a transaction to the redo stack
@@ -1209,11 +1335,9 @@ impl EditHistory {
 
     /// Push a transaction to the undo stack (used when redoing)
     pub fn push_undo(&mut self, transaction: EditTransaction) {
+        self.undo_bytes += transaction.size_bytes();
         self.undo_stack.push(transaction);
-        // Trim history if needed
-        while self.undo_stack.len() > self.max_history_size {
-            self.undo_stack.remove(0);
-        }
+        self.trim_undo_stack();
     }
 
     /// Check if undo is available
@@ -1229,16 +1353,112 @@ impl EditHistory {
     /// Clear all history
     pub fn clear(&mut self) {
         self.undo_stack.clear();
+        self.undo_bytes = 0;
         self.redo_stack.clear();
         self.current_transaction = None;
     }
 }
 
-/// A segment of text with a specific color on a specific line
+// ========== Content Changes ==========
+
+/// A single text change, expressed against the document as it was just before it
+///
+/// Positions are character offsets. Consumers that sync the buffer elsewhere
+/// (LSP, collaboration, persistence) can replay these in order.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ContentChange {
+    /// Start of the replaced range
+    pub start: usize,
+    /// End of the replaced range (equal to `start` for pure insertions)
+    pub end: usize,
+    /// Text inserted at `start`
+    pub new_text: String,
+    /// Content version after this change
+    pub content_version: u64,
+}
+
+impl ContentChange {
+    /// Whether this change only inserts text
+    pub fn is_insertion(&self) -> bool {
+        self.start == self.end && !self.new_text.is_empty()
+    }
+
+    /// Whether this change only removes text
+    pub fn is_deletion(&self) -> bool {
+        self.start < self.end && self.new_text.is_empty()
+    }
+
+    /// Try to fold `next` into this change (typing runs and repeated deletes)
+    fn merge(&mut self, next: &ContentChange) -> bool {
+        if self.is_insertion() && next.is_insertion()
+            && next.start == self.start + self.new_text.chars().count()
+        {
+            self.new_text.push_str(&next.new_text);
+        } else if self.is_deletion() && next.is_deletion() && next.end == self.start {
+            // Backspace run: each delete sits just before the previous one
+            self.start = next.start;
+        } else if self.is_deletion() && next.is_deletion() && next.start == self.start {
+            // Delete-forward run: each delete removes text after the previous one
+            self.end += next.end - next.start;
+        } else {
+            return false;
+        }
+        self.content_version = next.content_version;
+        true
+    }
+}
+
+/// An entry in the collaborative-editing operation log
+///
+/// `seq` is the `content_version` after the operation, so hosts can order and
+/// acknowledge operations when implementing OT/CRDT sync.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Operation {
+    /// `text` was inserted at `position`
+    Insert { seq: u64, position: usize, text: String },
+    /// `text` was removed starting at `position`
+    Delete { seq: u64, position: usize, text: String },
+}
+
+impl Operation {
+    /// Sequence number (content version after this operation)
+    pub fn seq(&self) -> u64 {
+        match self {
+            Operation::Insert { seq, .. } | Operation::Delete { seq, .. } => *seq,
+        }
+    }
+}
+
+/// How finely content changes are reported to external consumers
+#[derive(Resource, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ChangeGranularity {
+    /// One change per primitive edit
+    #[default]
+    PerEdit,
+    /// Adjacent typing and deletion runs are merged into a single change
+    Coalesced,
+}
+
+/// Merge adjacent changes of the same kind, preserving replay order
+pub fn coalesce_changes(changes: Vec<ContentChange>) -> Vec<ContentChange> {
+    let mut result: Vec<ContentChange> = Vec::with_capacity(changes.len());
+    for change in changes {
+        if let Some(last) = result.last_mut() {
+            if last.merge(&change) {
+                continue;
+            }
+        }
+        result.push(change);
+    }
+    result
+}
+
+/// A segment of text with a specific color and font style on a specific line
 #[derive(Clone, Debug)]
 pub struct LineSegment {
     pub text: String,
     pub color: Color,
+    pub style: FontStyle,
 }
 
 // ========== Soft Line Wrapping ==========
@@ -1261,7 +1481,7 @@ pub struct WrappedRow {
 
 /// Display map that handles soft line wrapping
 /// Maps between buffer lines and display rows
-#[derive(Clone, Debug, Default)]
+#[derive(Clone, Debug)]
 pub struct DisplayMap {
     /// All wrapped rows in display order
     pub rows: Vec<WrappedRow>,
@@ -1269,6 +1489,28 @@ pub struct DisplayMap {
     pub wrap_width: usize,
     /// Version counter to track when map needs rebuilding
     pub version: u64,
+    /// Buffer lines kept on a single row despite wrapping
+    pub no_wrap_lines: std::collections::BTreeSet<usize>,
+    /// Tab stops shared by glyphs, the cursor, selections and hit-testing
+    pub tab_map: crate::display_map::TabMap,
+    /// Where rows may break, from `WrappingSettings::mode`
+    pub wrap_mode: WrapMode,
+    /// Split words wider than the wrap width, from `WrappingSettings::break_words`
+    pub break_words: bool,
+    /// Cells continuation rows are indented by, from `WrappingSettings::indent_wrapped_lines`;
+    /// they wrap that much earlier so they end at the same column as first rows
+    pub continuation_indent: usize,
+    /// Wrapped at the viewport width rather than a fixed column
+    pub wrapped_to_viewport: bool,
+    /// Lines edited since the last layout, as (first edited line, unchanged
+    /// lines after the edits), so a rewrap only redoes the lines in between
+    pub edited_lines: Option<(usize, usize)>,
+}
+
+impl Default for DisplayMap {
+    fn default() -> Self {
+        Self::new(0)
+    }
 }
 
 impl DisplayMap {
@@ -1278,9 +1520,42 @@ impl DisplayMap {
             rows: Vec::new(),
             wrap_width,
             version: 0,
+            no_wrap_lines: std::collections::BTreeSet::new(),
+            tab_map: crate::display_map::TabMap::default(),
+            wrap_mode: WrapMode::Word,
+            break_words: true,
+            continuation_indent: 0,
+            wrapped_to_viewport: false,
+            edited_lines: None,
         }
     }
 
+    /// Remember an edit to lines `first_line..` leaving `unchanged_after`
+    /// lines after it, widening what was edited since the last layout
+    pub fn mark_edited(&mut self, first_line: usize, unchanged_after: usize) {
+        self.edited_lines = Some(match self.edited_lines {
+            Some((first, after)) => (first.min(first_line), after.min(unchanged_after)),
+            None => (first_line, unchanged_after),
+        });
+    }
+
+    /// Whether horizontal scrolling is off because every row fits the viewport
+    ///
+    /// Lines exempt from wrapping can still overflow, so they keep it on.
+    pub fn locks_horizontal_scroll(&self) -> bool {
+        self.wrapped_to_viewport && self.no_wrap_lines.is_empty()
+    }
+
+    /// Set the tab size (in columns)
+    pub fn set_tab_size(&mut self, size: u32) {
+        self.tab_map.set_tab_size(size);
+    }
+
+    /// Whether a buffer line was exempted from wrapping in the last rebuild
+    pub fn is_no_wrap_line(&self, buffer_line: usize) -> bool {
+        self.no_wrap_lines.contains(&buffer_line)
+    }
+
     /// Clear all rows
     pub fn clear(&mut self) {
         self.rows.clear();
@@ -1293,26 +1568,31 @@ impl DisplayMap {
     }
 
     /// Convert a buffer position (line, column) to display position (row, column)
+    ///
+    /// The end of a line maps to the end of its last row. Lines added since
+    /// the last rebuild (such as the empty line after a newly typed trailing
+    /// newline) are placed one row each after the last mapped row.
     pub fn buffer_to_display(&self, buffer_line: usize, buffer_col: usize) -> (usize, usize) {
-        let mut display_row = 0;
-
-        for row in &self.rows {
+        let mut last_row_of_line = None;
+        for (display_row, row) in self.rows.iter().enumerate() {
             if row.buffer_line == buffer_line {
-                if buffer_col >= row.start_offset && buffer_col < row.end_offset {
-                    return (display_row, buffer_col - row.start_offset);
-                } else if buffer_col < row.start_offset {
-                    // Column is before this row, must be on previous row
-                    return (display_row.saturating_sub(1), buffer_col);
+                if buffer_col < row.end_offset {
+                    return (display_row, buffer_col.saturating_sub(row.start_offset));
                 }
+                last_row_of_line = Some((display_row, row.start_offset));
             } else if row.buffer_line > buffer_line {
-                // We've passed the target line
                 break;
             }
-            display_row += 1;
         }
 
-        // Fallback: return last row of the buffer line
-        (display_row.saturating_sub(1), buffer_col)
+        match (last_row_of_line, self.rows.last()) {
+            (Some((display_row, start_offset)), _) => (display_row, buffer_col.saturating_sub(start_offset)),
+            (None, Some(last)) if last.buffer_line < buffer_line => {
+                (self.rows.len() + buffer_line - last.buffer_line - 1, buffer_col)
+            }
+            (None, None) => (buffer_line, buffer_col),
+            (None, Some(_)) => (self.rows.len().saturating_sub(1), buffer_col),
+        }
     }
 
     /// Convert a display position (row, column) to buffer position (line, column)
@@ -1342,6 +1622,29 @@ impl DisplayMap {
         self.rows.get(display_row).map(|r| r.buffer_line).unwrap_or(0)
     }
 
+    /// Rows laid out for a buffer line; empty for lines added since the last rebuild
+    pub fn line_rows(&self, buffer_line: usize) -> &[WrappedRow] {
+        let start = self.rows.partition_point(|row| row.buffer_line < buffer_line);
+        let end = self.rows.partition_point(|row| row.buffer_line <= buffer_line);
+        &self.rows[start..end]
+    }
+
+    /// The buffer line shown on a display row, and the first row of that line
+    ///
+    /// Rows past the end of the map are one per buffer line, as in `buffer_to_display`.
+    pub fn line_at_row(&self, display_row: usize) -> (usize, usize) {
+        match self.rows.get(display_row) {
+            Some(row) => {
+                let line = row.buffer_line;
+                (line, self.rows.partition_point(|row| row.buffer_line < line))
+            }
+            None => {
+                let next_line = self.rows.last().map_or(0, |row| row.buffer_line + 1);
+                (next_line + display_row - self.rows.len(), display_row)
+            }
+        }
+    }
+
     /// Check if a display row is a continuation (wrapped) row
     pub fn is_continuation(&self, display_row: usize) -> bool {
         self.rows.get(display_row).map(|r| r.is_continuation).unwrap_or(false)
@@ -1349,45 +1652,99 @@ impl DisplayMap {
 
     /// Build the display map from buffer lines
     pub fn rebuild(
+        &mut self,
+        lines: &[Vec<LineSegment>],
+        wrap_width: usize,
+        char_width: f32,
+    ) {
+        self.rebuild_except(lines, wrap_width, char_width, &[]);
+    }
+
+    /// Rebuild the display map, keeping the `no_wrap` buffer lines on a single row
+    pub fn rebuild_except(
         &mut self,
         lines: &[Vec<LineSegment>],
         wrap_width: usize,
         _char_width: f32,
+        no_wrap: &[usize],
     ) {
         self.rows.clear();
         self.wrap_width = wrap_width;
+        self.no_wrap_lines = no_wrap.iter().copied().collect();
+        self.edited_lines = None;
 
-        if wrap_width == 0 {
-            // No wrapping - each buffer line is one display row
-            for (line_idx, segments) in lines.iter().enumerate() {
-                let total_chars: usize = segments.iter().map(|s| s.text.chars().count()).sum();
-                self.rows.push(WrappedRow {
-                    buffer_line: line_idx,
-                    start_offset: 0,
-                    end_offset: total_chars,
-                    is_continuation: false,
-                    segments: segments.clone(),
-                });
-            }
-        } else {
-            // Wrap lines at wrap_width characters
-            for (line_idx, segments) in lines.iter().enumerate() {
-                self.wrap_line(line_idx, segments, wrap_width);
-            }
+        for (line_idx, segments) in lines.iter().enumerate() {
+            self.push_line(line_idx, segments);
         }
 
         self.version += 1;
     }
 
+    /// Replace the rows of `old_line_count` buffer lines from `first_line` with
+    /// the wrapped `lines`, shifting the rows after them
+    ///
+    /// `no_wrap` holds the buffer lines among the new ones kept on a single row.
+    pub fn replace_lines(
+        &mut self,
+        first_line: usize,
+        old_line_count: usize,
+        lines: &[Vec<LineSegment>],
+        no_wrap: &[usize],
+    ) {
+        let old_end = first_line + old_line_count;
+        let delta = lines.len() as isize - old_line_count as isize;
+        let shift = |line: usize| (line as isize + delta) as usize;
+
+        self.no_wrap_lines = self
+            .no_wrap_lines
+            .iter()
+            .filter(|&&line| line < first_line || line >= old_end)
+            .map(|&line| if line < first_line { line } else { shift(line) })
+            .chain(no_wrap.iter().copied())
+            .collect();
+
+        let start_row = self.rows.partition_point(|row| row.buffer_line < first_line);
+        let end_row = self.rows.partition_point(|row| row.buffer_line < old_end);
+        let tail = self.rows.split_off(end_row);
+        self.rows.truncate(start_row);
+        for (offset, segments) in lines.iter().enumerate() {
+            self.push_line(first_line + offset, segments);
+        }
+        self.rows.extend(tail.into_iter().map(|mut row| {
+            row.buffer_line = shift(row.buffer_line);
+            row
+        }));
+        self.edited_lines = None;
+        self.version += 1;
+    }
+
+    /// Lay out one buffer line as one or more rows at the end of the map
+    fn push_line(&mut self, line_idx: usize, segments: &[LineSegment]) {
+        if self.wrap_width == 0 || self.no_wrap_lines.contains(&line_idx) {
+            // No wrapping - the buffer line is one display row
+            let total_chars: usize = segments.iter().map(|s| s.text.chars().count()).sum();
+            self.rows.push(WrappedRow {
+                buffer_line: line_idx,
+                start_offset: 0,
+                end_offset: total_chars,
+                is_continuation: false,
+                segments: segments.to_vec(),
+            });
+        } else {
+            // Wrap lines at wrap_width characters
+            self.wrap_line(line_idx, segments, self.wrap_width);
+        }
+    }
+
     /// Wrap a single line into multiple rows
     fn wrap_line(&mut self, buffer_line: usize, segments: &[LineSegment], wrap_width: usize) {
         // Collect all text and track segment boundaries
         let mut all_text = String::new();
-        let mut segment_boundaries: Vec<(usize, Color)> = Vec::new();
+        let mut segment_boundaries: Vec<(usize, Color, FontStyle)> = Vec::new();
         let mut current_pos = 0;
 
         for seg in segments {
-            segment_boundaries.push((current_pos, seg.color));
+            segment_boundaries.push((current_pos, seg.color, seg.style));
             all_text.push_str(&seg.text);
             current_pos += seg.text.chars().count();
         }
@@ -1407,29 +1764,30 @@ impl DisplayMap {
         }
 
         let chars: Vec<char> = all_text.chars().collect();
+        // Visual column before each character (and after the last), so rows
+        // are measured in cells: tabs reach the next stop, wide characters take two
+        let mut cols = Vec::with_capacity(total_chars + 1);
+        let mut col = 0u32;
+        for &ch in &chars {
+            cols.push(col);
+            col = if ch == '\t' {
+                self.tab_map.next_tab_stop(col)
+            } else {
+                col + crate::display_map::char_cells(ch)
+            };
+        }
+        cols.push(col);
+
         let mut start = 0;
         let mut is_first_row = true;
 
         while start < total_chars {
-            // Find where to break
-            let mut end = (start + wrap_width).min(total_chars);
-
-            // Try to break at word boundary (space) if not at end
-            if end < total_chars && wrap_width > 0 {
-                // Look backwards for a space to break at
-                let search_start = start;
-                let mut break_pos = end;
-                for i in (search_start..end).rev() {
-                    if chars[i] == ' ' || chars[i] == '\t' {
-                        break_pos = i + 1; // Break after the space
-                        break;
-                    }
-                }
-                // Only use word break if it's not too far back (at least half the wrap width)
-                if break_pos > start + wrap_width / 2 {
-                    end = break_pos;
-                }
-            }
+            let row_width = if is_first_row {
+                wrap_width
+            } else {
+                wrap_width.saturating_sub(self.continuation_indent).max(1)
+            };
+            let end = self.row_end(&chars, &cols, start, row_width);
 
             // Build segments for this row
             let row_segments = self.build_row_segments(&chars, start, end, &segment_boundaries);
@@ -1447,13 +1805,57 @@ impl DisplayMap {
         }
     }
 
-    /// Build segments for a row, respecting color boundaries
+    /// Where a row starting at `start` ends, following `wrap_mode`
+    ///
+    /// `cols` holds the visual column before each character; a row covers at
+    /// most `wrap_width` cells, but always at least one character.
+    fn row_end(&self, chars: &[char], cols: &[u32], start: usize, wrap_width: usize) -> usize {
+        let total_chars = chars.len();
+        let limit = cols[start] + wrap_width as u32;
+        let end = (start + cols[start + 1..].partition_point(|&col| col <= limit)).max(start + 1);
+        if end == total_chars || self.wrap_mode == WrapMode::Character {
+            return end;
+        }
+        // The row already ends between words
+        if chars[end].is_whitespace() || chars[end - 1].is_whitespace() {
+            return end;
+        }
+
+        // Break after the last whitespace that follows some text on this row,
+        // so leading indentation alone never becomes a row
+        let mut seen_text = false;
+        let mut break_pos = None;
+        for (i, &c) in chars.iter().enumerate().take(end).skip(start) {
+            if c.is_whitespace() {
+                if seen_text {
+                    break_pos = Some(i + 1);
+                }
+            } else {
+                seen_text = true;
+            }
+        }
+        if let Some(break_pos) = break_pos {
+            return break_pos;
+        }
+
+        // A single word wider than the row
+        if self.break_words {
+            end
+        } else {
+            chars[end..]
+                .iter()
+                .position(|c| c.is_whitespace())
+                .map_or(total_chars, |offset| end + offset + 1)
+        }
+    }
+
+    /// Build segments for a row, respecting color and style boundaries
     fn build_row_segments(
         &self,
         chars: &[char],
         start: usize,
         end: usize,
-        segment_boundaries: &[(usize, Color)],
+        segment_boundaries: &[(usize, Color, FontStyle)],
     ) -> Vec<LineSegment> {
         let mut result = Vec::new();
         let mut current_pos = start;
@@ -1461,13 +1863,15 @@ impl DisplayMap {
         while current_pos < end {
             // Find which segment we're in
             let mut seg_color = Color::WHITE;
+            let mut seg_style = FontStyle::Regular;
             let mut seg_end = end;
 
-            for (i, (boundary_start, color)) in segment_boundaries.iter().enumerate() {
+            for (i, (boundary_start, color, style)) in segment_boundaries.iter().enumerate() {
                 if *boundary_start <= current_pos {
                     seg_color = *color;
+                    seg_style = *style;
                     // Find where this segment ends
-                    if let Some((next_boundary, _)) = segment_boundaries.get(i + 1) {
+                    if let Some((next_boundary, _, _)) = segment_boundaries.get(i + 1) {
                         seg_end = (*next_boundary).min(end);
                     } else {
                         seg_end = end;
@@ -1485,6 +1889,7 @@ impl DisplayMap {
                 result.push(LineSegment {
                     text,
                     color: seg_color,
+                    style: seg_style,
                 });
             }
 
@@ -1548,6 +1953,123 @@ impl Default for ViewportDimensions {
     }
 }
 
+/// Position of a gutter sub-column, computed from `GutterLayout`
+#[derive(Clone, Debug, PartialEq)]
+pub struct GutterColumnRect {
+    /// What the column shows
+    pub kind: crate::settings::GutterColumnKind,
+    /// Left edge, in pixels from the left of the viewport
+    pub x: f32,
+    /// Width in pixels
+    pub width: f32,
+}
+
+impl GutterColumnRect {
+    /// Horizontal center of the column
+    pub fn center_x(&self) -> f32 {
+        self.x + self.width / 2.0
+    }
+}
+
+/// Computed gutter sub-column positions
+///
+/// Set by the UI plugin alongside `ViewportDimensions`; gutter renderers look
+/// up their column here instead of using fixed offsets.
+#[derive(Resource, Clone, Debug, Default)]
+pub struct GutterColumns {
+    /// Columns in display order
+    pub columns: Vec<GutterColumnRect>,
+}
+
+impl GutterColumns {
+    /// The first column of the given kind
+    pub fn column(&self, kind: &crate::settings::GutterColumnKind) -> Option<&GutterColumnRect> {
+        self.columns.iter().find(|c| &c.kind == kind)
+    }
+}
+
+/// Layout constants for host UIs placing panels around the editor
+///
+/// Kept in sync with `FontSettings` and `ViewportDimensions`; ascent and
+/// descent come from the rasterizer's font metrics at the current size.
+#[derive(Resource, Clone, Copy, Debug, Default, PartialEq)]
+pub struct EditorMetrics {
+    /// Height of one text row in pixels
+    pub line_height: f32,
+    /// Advance width of one monospace cell in pixels
+    pub char_width: f32,
+    /// Distance from the baseline to the top of the tallest glyphs
+    pub ascent: f32,
+    /// Distance from the baseline to the bottom of descenders (positive)
+    pub descent: f32,
+    /// Width of the line number gutter
+    pub gutter_width: f32,
+    /// X position where the code area starts
+    pub code_area_left: f32,
+}
+
+/// Most edit locations remembered for `GoToLastEdit` / `GoToNextEdit`
+const MAX_EDIT_LOCATIONS: usize = 32;
+
+/// Edits within this many lines of the latest location replace it
+const EDIT_LOCATION_PROXIMITY: usize = 1;
+
+/// Recent edit locations, oldest first, tracked with anchors so they follow
+/// later edits
+///
+/// A burst of edits on neighbouring lines is one location: each edit near the
+/// latest location moves it instead of adding a new one.
+#[derive(Clone, Debug, Default)]
+pub struct EditLocations {
+    anchors: Vec<Anchor>,
+    /// Location the last jump went to; None when not navigating
+    index: Option<usize>,
+}
+
+impl EditLocations {
+    /// Number of remembered locations
+    pub fn len(&self) -> usize {
+        self.anchors.len()
+    }
+
+    /// Whether no edit has been recorded
+    pub fn is_empty(&self) -> bool {
+        self.anchors.is_empty()
+    }
+}
+
+/// A bookmarked position, tracked with an anchor so it follows later edits
+#[derive(Clone, Debug)]
+pub struct Bookmark {
+    /// Optional label for hosts that list bookmarks
+    pub name: Option<String>,
+    /// Anchor at the bookmarked position
+    pub anchor: Anchor,
+}
+
+/// Bookmarks visited by `NextBookmark` / `PrevBookmark`, in insertion order
+#[derive(Clone, Debug, Default)]
+pub struct Bookmarks {
+    marks: Vec<Bookmark>,
+}
+
+impl Bookmarks {
+    /// Number of bookmarks
+    pub fn len(&self) -> usize {
+        self.marks.len()
+    }
+
+    /// Whether no bookmark is set
+    pub fn is_empty(&self) -> bool {
+        self.marks.is_empty()
+    }
+
+    /// All bookmarks; resolve positions with `CodeEditorState::resolve_anchor`
+    pub fn iter(&self) -> impl Iterator<Item = &Bookmark> {
+        self.marks.iter()
+    }
+}
+
 /// Main editor state resource
 #[derive(Resource)]
 pub struct CodeEditorState {
@@ -1560,6 +2082,19 @@ pub struct CodeEditorState {
     /// Last cursor position (for detecting cursor movement)
     pub last_cursor_pos: usize,
 
+    /// Scroll the primary cursor into view on the next frame, even if it didn't move
+    pub reveal_cursor: bool,
+
+    /// The cursor was moved by a navigation jump; the next reveal follows
+    /// `NavigationSettings::center_on_jump`
+    pub navigation_jump: bool,
+
+    /// Recent edit locations visited by `GoToLastEdit` / `GoToNextEdit`
+    pub edit_locations: EditLocations,
+
+    /// Bookmarked positions toggled by `ToggleBookmark`
+    pub bookmarks: Bookmarks,
+
     /// Selection start (None = no selection) - primary cursor for backward compatibility
     pub selection_start: Option<usize>,
 
@@ -1573,6 +2108,10 @@ pub struct CodeEditorState {
     /// Is editor focused
     pub is_focused: bool,
 
+    /// Ignore text-changing input; cursor movement, selection, copy, find and
+    /// scrolling still work
+    pub read_only: bool,
+
     /// Needs full re-render
     pub needs_update: bool,
 
@@ -1597,6 +2136,11 @@ pub struct CodeEditorState {
     /// Target horizontal scroll offset for smooth scrolling
     pub target_horizontal_scroll_offset: f32,
 
+    /// Current vertical scroll velocity in pixels per second
+    /// Negative while scrolling down (scroll_offset decreasing), positive while scrolling up.
+    /// Used to predictively spawn line entities ahead of fast scrolls.
+    pub scroll_velocity: f32,
+
     /// Maximum content width (longest line in pixels)
     pub max_content_width: f32,
 
@@ -1667,6 +2211,22 @@ pub struct CodeEditorState {
     #[cfg(feature = "tree-sitter")]
     pub pending_tree_sitter_edit: Option<(usize, usize, usize)>,
 
+    /// Text changes not yet published to external consumers
+    /// Drained each frame into a `ContentChangedEvent`
+    pub pending_changes: Vec<ContentChange>,
+
+    /// Edits not yet sent as `BufferChanged` messages, one per logical edit
+    pub pending_buffer_changes: Vec<crate::events::BufferChanged>,
+
+    /// Whether edits are appended to the operation log (off by default)
+    pub operation_log_enabled: bool,
+
+    /// Operations recorded since the last `drain_operations` call
+    operations: Vec<Operation>,
+
+    /// Nesting depth of `batch_updates` calls
+    batch_depth: usize,
+
 }
 
 impl Default for CodeEditorState {
@@ -1679,10 +2239,15 @@ impl Default for CodeEditorState {
             rope,
             cursor_pos: 0,
             last_cursor_pos: 0,
+            reveal_cursor: false,
+            navigation_jump: false,
+            edit_locations: EditLocations::default(),
+            bookmarks: Bookmarks::default(),
             selection_start: None,
             selection_end: None,
             cursors: vec![Cursor::new(0)],
             is_focused: false,
+            read_only: false,
             needs_update: true,
             needs_scroll_update: false,
             tokens: Vec::new(),
@@ -1691,6 +2256,7 @@ impl Default for CodeEditorState {
             target_scroll_offset: 0.0,
             horizontal_scroll_offset: 0.0,
             target_horizontal_scroll_offset: 0.0,
+            scroll_velocity: 0.0,
             max_content_width: 0.0,
             max_content_width_version: 0,
             max_width_line: None,
@@ -1712,6 +2278,11 @@ impl Default for CodeEditorState {
             line_width_tracker: LineWidthTracker::new(),
             #[cfg(feature = "tree-sitter")]
             pending_tree_sitter_edit: None,
+            pending_changes: Vec::new(),
+            pending_buffer_changes: Vec::new(),
+            operation_log_enabled: false,
+            operations: Vec::new(),
+            batch_depth: 0,
         }
     }
 }
@@ -1727,10 +2298,15 @@ impl CodeEditorState {
             rope,
             cursor_pos: 0,
             last_cursor_pos: 0,
+            reveal_cursor: false,
+            navigation_jump: false,
+            edit_locations: EditLocations::default(),
+            bookmarks: Bookmarks::default(),
             selection_start: None,
             selection_end: None,
             cursors: vec![Cursor::new(0)],
             is_focused: false,
+            read_only: false,
             needs_update: true,
             needs_scroll_update: false,
             tokens: Vec::new(),
@@ -1739,6 +2315,7 @@ impl CodeEditorState {
             target_scroll_offset: 0.0,
             horizontal_scroll_offset: 0.0,
             target_horizontal_scroll_offset: 0.0,
+            scroll_velocity: 0.0,
             max_content_width: 0.0,
             max_content_width_version: 0,
             max_width_line: None,
@@ -1760,19 +2337,38 @@ impl CodeEditorState {
             line_width_tracker,
             #[cfg(feature = "tree-sitter")]
             pending_tree_sitter_edit: None,
+            pending_changes: Vec::new(),
+            pending_buffer_changes: Vec::new(),
+            operation_log_enabled: false,
+            operations: Vec::new(),
+            batch_depth: 0,
         }
     }
 
     /// Get text content as string
+    ///
+    /// This copies the whole buffer; use `rope()` to read it without cloning.
     pub fn text(&self) -> String {
         self.rope.to_string()
     }
 
+    /// The buffer contents
+    pub fn rope(&self) -> &Rope {
+        &self.rope
+    }
+
     /// Get line count
     pub fn line_count(&self) -> usize {
         self.rope.len_lines()
     }
 
+    /// Whether the buffer ends with a line break, making its last line the
+    /// empty (phantom) line after it
+    pub fn has_phantom_line(&self) -> bool {
+        let len = self.rope.len_chars();
+        len > 0 && self.rope.char(len - 1) == '\n'
+    }
+
     /// Insert character at cursor position (with undo recording)
     pub fn insert_char(&mut self, c: char) {
         self.insert_char_with_history(c, true);
@@ -1801,6 +2397,8 @@ impl CodeEditorState {
         // Mark for update with debouncing (avoids rebuilding mesh on every keystroke)
         self.pending_update = true;
         self.content_version += 1;
+        self.record_change(cursor_pos, cursor_pos, c.to_string());
+        self.record_operation(|seq| Operation::Insert { seq, position: cursor_pos, text: c.to_string() });
 
         // Record edit for tree-sitter incremental parsing
         #[cfg(feature = "tree-sitter")]
@@ -1815,7 +2413,7 @@ impl CodeEditorState {
         // Record for undo
         if record_history {
             let kind = if c == '\n' { EditKind::Newline } else { EditKind::Insert };
-            self.history.record(EditOperation {
+            self.record_history(EditOperation {
                 removed_text: String::new(),
                 inserted_text: c.to_string(),
                 position: cursor_before,
@@ -1826,8 +2424,10 @@ impl CodeEditorState {
         }
 
         let new_line_count = self.rope.len_lines();
-        // Only mark current line as dirty - tree-sitter will handle the rest
-        self.dirty_lines = Some(line_idx..(line_idx + 1).min(new_line_count));
+        // Only mark current line as dirty - tree-sitter will handle the rest.
+        // A line break also creates the line after it (the new last line at EOF)
+        let dirty_end = if c == '\n' { line_idx + 2 } else { line_idx + 1 };
+        self.dirty_lines = Some(line_idx..dirty_end.min(new_line_count));
         self.previous_line_count = new_line_count;
     }
 
@@ -1857,6 +2457,9 @@ impl CodeEditorState {
             // Mark for update with debouncing
             self.pending_update = true;
             self.content_version += 1;
+            self.record_change(self.cursor_pos, self.cursor_pos + 1, String::new());
+            let position = self.cursor_pos;
+            self.record_operation(|seq| Operation::Delete { seq, position, text: deleted_char.to_string() });
 
             // Record edit for tree-sitter incremental parsing
             #[cfg(feature = "tree-sitter")]
@@ -1870,7 +2473,7 @@ impl CodeEditorState {
 
             // Record for undo
             if record_history {
-                self.history.record(EditOperation {
+                self.record_history(EditOperation {
                     removed_text: deleted_char.to_string(),
                     inserted_text: String::new(),
                     position: self.cursor_pos,
@@ -1911,6 +2514,9 @@ impl CodeEditorState {
             // Mark for update with debouncing
             self.pending_update = true;
             self.content_version += 1;
+            self.record_change(self.cursor_pos, self.cursor_pos + 1, String::new());
+            let position = self.cursor_pos;
+            self.record_operation(|seq| Operation::Delete { seq, position, text: deleted_char.to_string() });
 
             // Record edit for tree-sitter incremental parsing
             #[cfg(feature = "tree-sitter")]
@@ -1924,7 +2530,7 @@ impl CodeEditorState {
 
             // Record for undo
             if record_history {
-                self.history.record(EditOperation {
+                self.record_history(EditOperation {
                     removed_text: deleted_char.to_string(),
                     inserted_text: String::new(),
                     position: self.cursor_pos,
@@ -1957,6 +2563,8 @@ impl CodeEditorState {
         self.rope.insert(pos, text);
         self.pending_update = true;
         self.content_version += 1;
+        self.record_change(pos, pos, text.to_string());
+        self.record_operation(|seq| Operation::Insert { seq, position: pos, text: text.to_string() });
         self.dirty_lines = None; // Full rehighlight
         self.previous_line_count = self.rope.len_lines();
 
@@ -1979,12 +2587,20 @@ impl CodeEditorState {
             let start_byte = self.rope.char_to_byte(start);
             let end_byte = self.rope.char_to_byte(end);
 
+            let removed_text = self
+                .operation_log_enabled
+                .then(|| self.rope.slice(start..end).to_string());
+
             // Record anchor edit (character-based)
             self.anchors.record_edit(TextEdit::delete(start, end));
 
             self.rope.remove(start_byte..end_byte);
             self.pending_update = true;
             self.content_version += 1;
+            self.record_change(start, end, String::new());
+            if let Some(text) = removed_text {
+                self.record_operation(|seq| Operation::Delete { seq, position: start, text });
+            }
             self.dirty_lines = None; // Full rehighlight
             self.previous_line_count = self.rope.len_lines();
 
@@ -2000,37 +2616,359 @@ impl CodeEditorState {
         }
     }
 
-    /// Perform undo operation
-    pub fn undo(&mut self) -> bool {
-        if let Some(transaction) = self.history.pop_undo() {
-            // Apply operations in reverse order
-            for op in transaction.operations.iter().rev() {
-                // Undo: remove inserted text, insert removed text
-                if !op.inserted_text.is_empty() {
-                    let end_pos = op.position + op.inserted_text.chars().count();
-                    self.remove_range(op.position, end_pos);
-                }
-                if !op.removed_text.is_empty() {
-                    self.insert_text_at(op.position, &op.removed_text);
-                }
-            }
+    /// Replace every selection at once, typing `text` in each as one undo step
+    ///
+    /// Cursors sharing a position are merged first, so each spot gets `text` once.
+    /// Cursors are edited front to back, each shifted by the length change of
+    /// the edits before it; afterwards every cursor sits after its inserted text.
+    pub fn insert_at_cursors(&mut self, text: &str) {
+        self.replace_at_cursors(&[text], false);
+    }
 
-            // Restore cursor to before the first operation
-            if let Some(first_op) = transaction.operations.first() {
-                self.cursor_pos = first_op.cursor_before;
-            }
+    /// Like `insert_at_cursors`, but consecutive calls group into one undo
+    /// step the way single-cursor typing does
+    pub fn type_at_cursors(&mut self, text: &str) {
+        self.replace_at_cursors(&[text], true);
+    }
 
-            // Push to redo stack
-            self.history.push_redo(transaction);
-            true
-        } else {
-            false
-        }
+    /// Like `insert_at_cursors`, but the n-th cursor in document order gets
+    /// `texts[n]`, cycling when there are fewer texts than cursors
+    pub fn insert_each_at_cursors(&mut self, texts: &[&str]) {
+        self.replace_at_cursors(texts, false);
     }
 
-    /// Perform redo operation
-    pub fn redo(&mut self) -> bool {
-        if let Some(transaction) = self.history.pop_redo() {
+    fn replace_at_cursors(&mut self, texts: &[&str], typing: bool) {
+        if texts.is_empty() {
+            return;
+        }
+        // Coincident cursors would insert the text twice
+        self.sort_and_merge_cursors();
+        let before = SelectionCollection::from_cursors(&self.cursors);
+        let mut ranges: Vec<(usize, usize, usize)> = self
+            .cursors
+            .iter()
+            .map(|c| (c.selection_start(), c.selection_end(), c.position))
+            .collect();
+        ranges.sort_by_key(|&(start, ..)| start);
+
+        let mut delta: isize = 0;
+        let mut operations = Vec::with_capacity(ranges.len());
+        let mut cursors = Vec::with_capacity(ranges.len());
+        for (index, (start, end, position)) in ranges.into_iter().enumerate() {
+            let text = texts[index % texts.len()];
+            let text_len = text.chars().count();
+            let start = (start as isize + delta) as usize;
+            let end = (end as isize + delta) as usize;
+            let removed_text = self.rope.slice(start..end).to_string();
+
+            self.remove_range(start, end);
+            // An empty text just deletes the selection; an empty cursor is left alone
+            if !text.is_empty() {
+                self.insert_text_at(start, text);
+            }
+            if !removed_text.is_empty() || !text.is_empty() {
+                operations.push(EditOperation {
+                    inserted_text: text.to_string(),
+                    position: start,
+                    cursor_before: (position as isize + delta) as usize,
+                    cursor_after: start + text_len,
+                    kind: if typing && removed_text.is_empty() { EditKind::Insert } else { EditKind::Other },
+                    removed_text,
+                });
+            }
+            cursors.push(Cursor::new(start + text_len));
+            delta += text_len as isize - (end - start) as isize;
+        }
+
+        self.cursors = cursors;
+        self.sync_primary_cursor();
+        let after = SelectionCollection::from_cursors(&self.cursors);
+        if typing {
+            let pos = operations.last().map(|op| op.cursor_after);
+            let new_step = self.history.record_typing_transaction(operations, before, after);
+            if let Some(pos) = pos.filter(|_| new_step) {
+                self.record_edit_location(pos);
+            }
+        } else {
+            self.record_transaction(operations, before, after);
+        }
+    }
+
+    /// Delete at every cursor as one undo step: each selection, or the
+    /// character before (after, with `forward`) an empty cursor
+    ///
+    /// Returns false if nothing was deleted.
+    pub fn delete_at_cursors(&mut self, forward: bool) -> bool {
+        self.sort_and_merge_cursors();
+        let before = SelectionCollection::from_cursors(&self.cursors);
+        let len = self.rope.len_chars();
+        let mut ranges: Vec<(usize, usize, usize)> = self
+            .cursors
+            .iter()
+            .map(|c| {
+                let (start, end) = if c.has_selection() {
+                    (c.selection_start(), c.selection_end())
+                } else if forward {
+                    (c.position, (c.position + 1).min(len))
+                } else {
+                    (c.position.saturating_sub(1), c.position)
+                };
+                (start, end, c.position)
+            })
+            .collect();
+        ranges.sort_by_key(|&(start, ..)| start);
+        // Neighbouring cursors can reach for the same character
+        ranges.dedup_by(|next, prev| {
+            let overlaps = next.0 < prev.1;
+            if overlaps {
+                prev.1 = prev.1.max(next.1);
+            }
+            overlaps
+        });
+
+        let mut removed = 0;
+        let mut operations = Vec::new();
+        let mut cursors = Vec::with_capacity(ranges.len());
+        for (start, end, position) in ranges {
+            let (start, end) = (start - removed, end - removed);
+            if start < end {
+                let removed_text = self.rope.slice(start..end).to_string();
+                self.remove_range(start, end);
+                operations.push(EditOperation {
+                    removed_text,
+                    inserted_text: String::new(),
+                    position: start,
+                    cursor_before: position - removed,
+                    cursor_after: start,
+                    kind: EditKind::Other,
+                });
+            }
+            cursors.push(Cursor::new(start));
+            removed += end - start;
+        }
+        if operations.is_empty() {
+            return false;
+        }
+
+        self.cursors = cursors;
+        self.sort_and_merge_cursors();
+        self.sync_primary_cursor();
+        let after = SelectionCollection::from_cursors(&self.cursors);
+        self.record_transaction(operations, before, after);
+        true
+    }
+
+    /// Replace several char ranges at once as one undo step, e.g. a formatter's edits
+    ///
+    /// Ranges refer to the current text and must not overlap; insertions at
+    /// the same position keep their order. Cursors and selections are mapped
+    /// through the edits with anchors, so they stay on the same text.
+    pub fn apply_edits(&mut self, mut edits: Vec<(Range<usize>, String)>) {
+        let len = self.rope.len_chars();
+        // Out-of-range edits are dropped, as are no-ops
+        edits.retain(|(range, text)| {
+            range.start <= range.end && range.end <= len && (range.start < range.end || !text.is_empty())
+        });
+        if edits.is_empty() {
+            return;
+        }
+        edits.sort_by_key(|(range, _)| range.start);
+
+        self.sync_cursors_from_primary();
+        let before = SelectionCollection::from_cursors(&self.cursors);
+        let cursor_anchors: Vec<(Anchor, Option<Anchor>)> = self
+            .cursors
+            .clone()
+            .into_iter()
+            .map(|c| {
+                (
+                    self.create_anchor(c.position, AnchorBias::Right),
+                    c.anchor.map(|anchor| self.create_anchor(anchor, AnchorBias::Right)),
+                )
+            })
+            .collect();
+
+        // Back to front, so earlier ranges are still valid when reached
+        let mut operations = Vec::with_capacity(edits.len());
+        for (range, text) in edits.into_iter().rev() {
+            let removed_text = self.rope.slice(range.clone()).to_string();
+            self.remove_range(range.start, range.end);
+            if !text.is_empty() {
+                self.insert_text_at(range.start, &text);
+            }
+            operations.push(EditOperation {
+                removed_text,
+                cursor_before: range.end,
+                cursor_after: range.start + text.chars().count(),
+                inserted_text: text,
+                position: range.start,
+                kind: EditKind::Other,
+            });
+        }
+
+        self.cursors = cursor_anchors
+            .into_iter()
+            .map(|(head, anchor)| {
+                let position = self.resolve_anchor(&head);
+                self.remove_anchor(head.id);
+                let anchor = anchor.map(|anchor| {
+                    let offset = self.resolve_anchor(&anchor);
+                    self.remove_anchor(anchor.id);
+                    offset
+                });
+                Cursor { position, anchor: anchor.filter(|&a| a != position) }
+            })
+            .collect();
+        self.sort_and_merge_cursors();
+        self.sync_primary_cursor();
+        let after = SelectionCollection::from_cursors(&self.cursors);
+        self.record_transaction(operations, before, after);
+    }
+
+    /// Insert `text` at the primary cursor, replacing its selection, and leave
+    /// the inserted text selected
+    ///
+    /// Secondary cursors are dropped. The edit is a single undo step.
+    pub fn insert_and_select(&mut self, text: &str) {
+        let len = text.chars().count();
+        self.replace_primary_selection(text, |start| vec![Cursor::with_selection(start + len, start)]);
+    }
+
+    /// Insert `text` at the primary cursor, replacing its selection, and place
+    /// a cursor at each of `cursor_offsets` (chars into the inserted text)
+    ///
+    /// This is the primitive behind snippet expansion: `insert_with_cursors("()", &[1])`
+    /// leaves the cursor between the parentheses. Offsets past the end of `text`
+    /// are clamped; with no offsets the cursor ends up after the text. The edit
+    /// is a single undo step.
+    pub fn insert_with_cursors(&mut self, text: &str, cursor_offsets: &[usize]) {
+        let len = text.chars().count();
+        self.replace_primary_selection(text, |start| {
+            cursor_offsets
+                .iter()
+                .map(|&offset| Cursor::new(start + offset.min(len)))
+                .collect()
+        });
+    }
+
+    /// Replace the primary selection with `text` as one undo step, then set the
+    /// cursors returned by `place` for the insertion start
+    fn replace_primary_selection(&mut self, text: &str, place: impl FnOnce(usize) -> Vec<Cursor>) {
+        self.sync_cursors_from_primary();
+        let before = SelectionCollection::from_cursors(&self.cursors);
+        let cursor_before = self.cursor_pos.min(self.rope.len_chars());
+        let (start, end) = match (self.selection_start, self.selection_end) {
+            (Some(anchor), Some(head)) => (anchor.min(head), anchor.max(head)),
+            _ => (cursor_before, cursor_before),
+        };
+        let removed_text = self.rope.slice(start..end).to_string();
+
+        self.remove_range(start, end);
+        self.insert_text_at(start, text);
+
+        let text_len = text.chars().count();
+        self.cursors = place(start);
+        if self.cursors.is_empty() {
+            self.cursors.push(Cursor::new(start + text_len));
+        }
+        self.sort_and_merge_cursors();
+        self.sync_primary_cursor();
+
+        let operation = EditOperation {
+            removed_text,
+            inserted_text: text.to_string(),
+            position: start,
+            cursor_before,
+            cursor_after: self.cursor_pos,
+            kind: EditKind::Other,
+        };
+        let after = SelectionCollection::from_cursors(&self.cursors);
+        self.record_transaction(vec![operation], before, after);
+    }
+
+    /// Set all cursors from a saved collection, clamped to the buffer
+    fn restore_selections(&mut self, selections: &SelectionCollection) {
+        let len = self.rope.len_chars();
+        self.cursors = selections
+            .to_cursors()
+            .into_iter()
+            .map(|c| Cursor {
+                position: c.position.min(len),
+                anchor: c.anchor.map(|a| a.min(len)),
+            })
+            .collect();
+        if self.cursors.is_empty() {
+            self.cursors.push(Cursor::new(0));
+        }
+        self.sync_primary_cursor();
+    }
+
+    /// Put a single cursor where an undone/redone edit happened and reveal it
+    fn move_cursor_to_edit(&mut self, pos: usize) {
+        self.cursor_pos = pos.min(self.rope.len_chars());
+        self.selection_start = None;
+        self.selection_end = None;
+        self.cursors.truncate(1);
+        self.sync_cursors_from_primary();
+        self.reveal_cursor = true;
+    }
+
+    /// Keep the cursor where it is, within the (possibly shorter) text
+    fn clamp_cursor_to_text(&mut self) {
+        let len = self.rope.len_chars();
+        self.cursor_pos = self.cursor_pos.min(len);
+        self.selection_start = self.selection_start.map(|p| p.min(len));
+        self.selection_end = self.selection_end.map(|p| p.min(len));
+        self.sync_cursors_from_primary();
+    }
+
+    /// Perform undo operation
+    pub fn undo(&mut self) -> bool {
+        self.undo_with(true)
+    }
+
+    /// Perform undo, moving the cursors back to the edit when `restore_cursor`
+    /// is set (otherwise they stay where they are)
+    pub fn undo_with(&mut self, restore_cursor: bool) -> bool {
+        if let Some(transaction) = self.history.pop_undo() {
+            // Apply operations in reverse order
+            for op in transaction.operations.iter().rev() {
+                // Undo: remove inserted text, insert removed text
+                if !op.inserted_text.is_empty() {
+                    let end_pos = op.position + op.inserted_text.chars().count();
+                    self.remove_range(op.position, end_pos);
+                }
+                if !op.removed_text.is_empty() {
+                    self.insert_text_at(op.position, &op.removed_text);
+                }
+            }
+
+            // Restore cursors to before the transaction
+            if !restore_cursor {
+                self.clamp_cursor_to_text();
+            } else if let Some(selections) = &transaction.selections_before {
+                self.restore_selections(selections);
+                self.reveal_cursor = true;
+            } else if let Some(first_op) = transaction.operations.first() {
+                self.move_cursor_to_edit(first_op.cursor_before);
+            }
+
+            // Push to redo stack
+            self.history.push_redo(transaction);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Perform redo operation
+    pub fn redo(&mut self) -> bool {
+        self.redo_with(true)
+    }
+
+    /// Perform redo, moving the cursors to the edit when `restore_cursor` is set
+    pub fn redo_with(&mut self, restore_cursor: bool) -> bool {
+        if let Some(transaction) = self.history.pop_redo() {
             // Apply operations in forward order
             for op in transaction.operations.iter() {
                 // Redo: remove removed text (it was re-inserted by undo), insert inserted text
@@ -2043,9 +2981,14 @@ impl CodeEditorState {
                 }
             }
 
-            // Restore cursor to after the last operation
-            if let Some(last_op) = transaction.operations.last() {
-                self.cursor_pos = last_op.cursor_after;
+            // Restore cursors to after the transaction
+            if !restore_cursor {
+                self.clamp_cursor_to_text();
+            } else if let Some(selections) = &transaction.selections_after {
+                self.restore_selections(selections);
+                self.reveal_cursor = true;
+            } else if let Some(last_op) = transaction.operations.last() {
+                self.move_cursor_to_edit(last_op.cursor_after);
             }
 
             // Push to undo stack
@@ -2056,6 +2999,429 @@ impl CodeEditorState {
         }
     }
 
+    /// Queue a content change for external consumers
+    fn record_change(&mut self, start: usize, end: usize, new_text: String) {
+        let new_len = new_text.chars().count();
+        let len_chars = self.rope.len_chars();
+        let first_line = self.rope.char_to_line(start.min(len_chars));
+        let last_line = self.rope.char_to_line((start + new_len).min(len_chars));
+        self.display_map.mark_edited(first_line, self.rope.len_lines() - 1 - last_line);
+        self.pending_buffer_changes.push(crate::events::BufferChanged {
+            content_version: self.content_version,
+            edit: TextEdit::replace(start, end, new_len),
+            lines: Some(first_line..last_line + 1),
+        });
+        self.pending_changes.push(ContentChange {
+            start,
+            end,
+            new_text,
+            content_version: self.content_version,
+        });
+    }
+
+    /// Record an operation in the undo history; the first edit of each
+    /// transaction becomes an edit location, which then follows the typing
+    pub fn record_history(&mut self, operation: EditOperation) {
+        let pos = operation.cursor_after;
+        if self.history.record(operation) {
+            self.record_edit_location(pos);
+        }
+    }
+
+    /// Record a complete undo step and remember where its last edit left the cursor
+    pub fn record_transaction(
+        &mut self,
+        operations: Vec<EditOperation>,
+        selections_before: SelectionCollection,
+        selections_after: SelectionCollection,
+    ) {
+        let Some(pos) = operations.last().map(|op| op.cursor_after) else {
+            return;
+        };
+        self.history.record_transaction(operations, selections_before, selections_after);
+        self.record_edit_location(pos);
+    }
+
+    /// Remember `pos` as the latest edit location, replacing a nearby latest one
+    fn record_edit_location(&mut self, pos: usize) {
+        let line = self.rope.char_to_line(pos.min(self.rope.len_chars()));
+        let near_latest = self.edit_locations.anchors.last().is_some_and(|latest| {
+            let latest_line = self.rope.char_to_line(self.resolve_anchor(latest));
+            latest_line.abs_diff(line) <= EDIT_LOCATION_PROXIMITY
+        });
+        if near_latest {
+            if let Some(latest) = self.edit_locations.anchors.pop() {
+                self.remove_anchor(latest.id);
+            }
+        } else if self.edit_locations.anchors.len() >= MAX_EDIT_LOCATIONS {
+            let oldest = self.edit_locations.anchors.remove(0);
+            self.remove_anchor(oldest.id);
+        }
+        let anchor = self.create_anchor(pos, AnchorBias::Right);
+        self.edit_locations.anchors.push(anchor);
+        self.edit_locations.index = None;
+    }
+
+    /// Jump to the edit location before the last one visited, skipping the
+    /// one the cursor is already at; returns false if there is none
+    pub fn goto_last_edit(&mut self) -> bool {
+        let start = self.edit_locations.index.unwrap_or(self.edit_locations.anchors.len());
+        self.goto_edit_location((0..start).rev())
+    }
+
+    /// Jump to the edit location after the last one visited by `goto_last_edit`
+    pub fn goto_next_edit(&mut self) -> bool {
+        let Some(index) = self.edit_locations.index else {
+            return false;
+        };
+        self.goto_edit_location(index + 1..self.edit_locations.anchors.len())
+    }
+
+    /// Jump to the first location in `indices` that isn't on the cursor's line
+    fn goto_edit_location(&mut self, indices: impl Iterator<Item = usize>) -> bool {
+        let cursor_line = self.rope.char_to_line(self.cursor_pos.min(self.rope.len_chars()));
+        for index in indices {
+            let pos = self.resolve_anchor(&self.edit_locations.anchors[index]);
+            if self.rope.char_to_line(pos) != cursor_line {
+                self.edit_locations.index = Some(index);
+                self.jump_to(pos);
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Bookmark `pos`, returning the id of its anchor
+    pub fn add_bookmark(&mut self, pos: usize, name: Option<String>) -> u64 {
+        let anchor = self.create_anchor(pos, AnchorBias::Left);
+        self.bookmarks.marks.push(Bookmark { name, anchor });
+        self.pending_update = true;
+        anchor.id
+    }
+
+    /// Remove every bookmark on a buffer line; returns false if there was none
+    pub fn remove_bookmarks_on_line(&mut self, line: usize) -> bool {
+        let (on_line, kept): (Vec<Bookmark>, Vec<Bookmark>) = std::mem::take(&mut self.bookmarks.marks)
+            .into_iter()
+            .partition(|mark| self.rope.char_to_line(self.resolve_anchor(&mark.anchor)) == line);
+        self.bookmarks.marks = kept;
+        for mark in &on_line {
+            self.remove_anchor(mark.anchor.id);
+        }
+        if !on_line.is_empty() {
+            self.pending_update = true;
+        }
+        !on_line.is_empty()
+    }
+
+    /// Remove the bookmarks on the cursor line, or bookmark the cursor if
+    /// there are none; returns true if a bookmark was added
+    pub fn toggle_bookmark(&mut self) -> bool {
+        let pos = self.cursor_pos.min(self.rope.len_chars());
+        if self.remove_bookmarks_on_line(self.rope.char_to_line(pos)) {
+            return false;
+        }
+        self.add_bookmark(pos, None);
+        true
+    }
+
+    /// Current positions of all bookmarks, in document order
+    pub fn bookmark_positions(&self) -> Vec<usize> {
+        let mut positions: Vec<usize> = self
+            .bookmarks
+            .marks
+            .iter()
+            .map(|mark| self.resolve_anchor(&mark.anchor))
+            .collect();
+        positions.sort_unstable();
+        positions
+    }
+
+    /// Buffer lines holding at least one bookmark, in order
+    pub fn bookmark_lines(&self) -> Vec<usize> {
+        let mut lines: Vec<usize> = self
+            .bookmark_positions()
+            .into_iter()
+            .map(|pos| self.rope.char_to_line(pos))
+            .collect();
+        lines.dedup();
+        lines
+    }
+
+    /// Jump to the first bookmark below the cursor line, wrapping to the
+    /// first one; returns false if there are no bookmarks
+    pub fn goto_next_bookmark(&mut self) -> bool {
+        let positions = self.bookmark_positions();
+        let cursor_line = self.rope.char_to_line(self.cursor_pos.min(self.rope.len_chars()));
+        let target = positions
+            .iter()
+            .find(|&&pos| self.rope.char_to_line(pos) > cursor_line)
+            .or(positions.first());
+        match target {
+            Some(&pos) => {
+                self.jump_to(pos);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Jump to the last bookmark above the cursor line, wrapping to the
+    /// last one; returns false if there are no bookmarks
+    pub fn goto_prev_bookmark(&mut self) -> bool {
+        let positions = self.bookmark_positions();
+        let cursor_line = self.rope.char_to_line(self.cursor_pos.min(self.rope.len_chars()));
+        let target = positions
+            .iter()
+            .rev()
+            .find(|&&pos| self.rope.char_to_line(pos) < cursor_line)
+            .or(positions.last());
+        match target {
+            Some(&pos) => {
+                self.jump_to(pos);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Append to the operation log when it is enabled
+    fn record_operation(&mut self, make: impl FnOnce(u64) -> Operation) {
+        if self.operation_log_enabled {
+            self.operations.push(make(self.content_version));
+        }
+    }
+
+    /// Take all operations recorded since the last drain
+    ///
+    /// Returns nothing unless `operation_log_enabled` is set.
+    pub fn drain_operations(&mut self) -> Vec<Operation> {
+        std::mem::take(&mut self.operations)
+    }
+
+    /// Run a group of edits, cursor moves and scrolls as one rendering update
+    ///
+    /// Each edit normally marks only its own lines dirty, so a scripted sequence
+    /// would leave just the last edit's range queued for rebuild. Inside the
+    /// closure those per-edit flags are collected; when the outermost batch
+    /// finishes they collapse into a single full `needs_update`. Batches nest.
+    pub fn batch_updates<R>(&mut self, f: impl FnOnce(&mut Self) -> R) -> R {
+        let version_before = self.content_version;
+        self.batch_depth += 1;
+        let result = f(self);
+        self.batch_depth -= 1;
+
+        if self.batch_depth == 0 {
+            let touched = self.content_version != version_before
+                || self.needs_update
+                || self.pending_update
+                || self.needs_scroll_update
+                || self.dirty_lines.is_some();
+            if touched {
+                self.pending_update = false;
+                self.dirty_lines = None;
+                self.needs_update = true;
+                self.previous_line_count = self.rope.len_lines();
+            }
+        }
+        result
+    }
+
+    /// Capture scroll, cursors and selection
+    pub fn view_snapshot(&self) -> ViewSnapshot {
+        ViewSnapshot {
+            scroll_offset: self.scroll_offset,
+            horizontal_scroll_offset: self.horizontal_scroll_offset,
+            cursor_pos: self.cursor_pos,
+            selection_start: self.selection_start,
+            selection_end: self.selection_end,
+            cursors: self.cursors.clone(),
+        }
+    }
+
+    /// Jump back to a captured view, clamping positions to the current buffer
+    pub fn restore_view(&mut self, snapshot: &ViewSnapshot) {
+        let len = self.rope.len_chars();
+        self.cursor_pos = snapshot.cursor_pos.min(len);
+        self.selection_start = snapshot.selection_start.map(|p| p.min(len));
+        self.selection_end = snapshot.selection_end.map(|p| p.min(len));
+        self.cursors = snapshot
+            .cursors
+            .iter()
+            .map(|c| Cursor {
+                position: c.position.min(len),
+                anchor: c.anchor.map(|a| a.min(len)),
+            })
+            .collect();
+        if self.cursors.is_empty() {
+            self.cursors.push(Cursor::new(self.cursor_pos));
+        }
+
+        self.scroll_offset = snapshot.scroll_offset;
+        self.target_scroll_offset = snapshot.scroll_offset;
+        self.horizontal_scroll_offset = snapshot.horizontal_scroll_offset;
+        self.target_horizontal_scroll_offset = snapshot.horizontal_scroll_offset;
+        self.needs_scroll_update = true;
+        self.needs_update = true;
+    }
+
+    /// Rebuild the display map, soft-wrapping every line at `wrap_width` columns
+    ///
+    /// A width of 0 lays out one display row per buffer line.
+    pub fn rewrap(&mut self, wrap_width: usize) {
+        self.rewrap_except(wrap_width, |_| false);
+    }
+
+    /// Like `rewrap`, but lines for which `no_wrap` returns true stay on one row
+    pub fn rewrap_except(&mut self, wrap_width: usize, no_wrap: impl Fn(&str) -> bool) {
+        let (lines, exempt) = self.wrap_segments(0..self.rope.len_lines(), no_wrap);
+        self.display_map.rebuild_except(&lines, wrap_width, 0.0, &exempt);
+        self.needs_update = true;
+    }
+
+    /// Like `rewrap_except`, but only re-wraps the lines edited since the last
+    /// layout; a new width (or no previous layout) rewraps everything
+    pub fn rewrap_edited_except(&mut self, wrap_width: usize, no_wrap: impl Fn(&str) -> bool) {
+        let old_line_count = match self.display_map.rows.last() {
+            Some(row) if self.display_map.wrap_width == wrap_width => row.buffer_line + 1,
+            _ => return self.rewrap_except(wrap_width, no_wrap),
+        };
+        let Some((first_line, unchanged_after)) = self.display_map.edited_lines else {
+            return;
+        };
+        let new_line_count = self.rope.len_lines();
+        let first_line = first_line.min(old_line_count).min(new_line_count);
+        let unchanged_after = unchanged_after
+            .min(old_line_count - first_line)
+            .min(new_line_count - first_line);
+
+        let (lines, exempt) = self.wrap_segments(first_line..new_line_count - unchanged_after, no_wrap);
+        self.display_map
+            .replace_lines(first_line, old_line_count - unchanged_after - first_line, &lines, &exempt);
+        self.needs_update = true;
+    }
+
+    /// Plain segments for the buffer lines in `range`, and which of them `no_wrap` exempts
+    fn wrap_segments(
+        &self,
+        range: Range<usize>,
+        no_wrap: impl Fn(&str) -> bool,
+    ) -> (Vec<Vec<LineSegment>>, Vec<usize>) {
+        let mut exempt = Vec::new();
+        let lines = range
+            .map(|line_idx| {
+                let text = self.rope.line(line_idx).to_string();
+                let text = text.trim_end_matches(['\n', '\r']).to_string();
+                if no_wrap(&text) {
+                    exempt.push(line_idx);
+                }
+                vec![LineSegment {
+                    text,
+                    color: Color::WHITE,
+                    style: FontStyle::Regular,
+                }]
+            })
+            .collect();
+        (lines, exempt)
+    }
+
+    /// Display row of the primary cursor (its buffer line when nothing is wrapped)
+    pub fn cursor_display_row(&self) -> usize {
+        let cursor = self.cursor_pos.min(self.rope.len_chars());
+        let line = self.rope.char_to_line(cursor);
+        if self.display_map.rows.is_empty() {
+            return line;
+        }
+        let col = cursor - self.rope.line_to_char(line);
+        self.display_map.buffer_to_display(line, col).0
+    }
+
+    /// Move the cursor to a navigation target, clearing the selection
+    pub fn jump_to(&mut self, pos: usize) {
+        self.cursor_pos = pos.min(self.rope.len_chars());
+        self.selection_start = None;
+        self.selection_end = None;
+        self.pending_update = true;
+        self.mark_navigation_jump();
+    }
+
+    /// Reveal the cursor as a navigation jump on the next auto-scroll,
+    /// centering it when `NavigationSettings::center_on_jump` is set
+    pub fn mark_navigation_jump(&mut self) {
+        self.navigation_jump = true;
+        self.reveal_cursor = true;
+    }
+
+    /// Buffer line shown at the top of the viewport, accounting for folds
+    /// and soft wrap (a wrapped line's continuation row counts as that line)
+    pub fn top_visible_line(&self, line_height: f32, fold_state: &FoldState) -> usize {
+        let row = (-self.scroll_offset / line_height + 0.001).floor().max(0.0) as usize;
+        let line = if self.is_soft_wrapped() {
+            self.display_map.row_to_buffer_line(row.min(self.display_map.row_count() - 1))
+        } else {
+            fold_state.display_to_actual_line(row)
+        };
+        line.min(self.rope.len_lines().saturating_sub(1))
+    }
+
+    /// Whether the display map soft-wraps lines into several rows
+    fn is_soft_wrapped(&self) -> bool {
+        self.display_map.wrap_width > 0 && !self.display_map.rows.is_empty()
+    }
+
+    /// Scroll so that a buffer line is at the top of the viewport
+    ///
+    /// Unlike revealing a line this always puts it at the top, clamped so the
+    /// view never scrolls past the start or end of the content. A line hidden
+    /// in a fold scrolls to the fold's header.
+    pub fn set_top_visible_line(
+        &mut self,
+        line: usize,
+        line_height: f32,
+        fold_state: &FoldState,
+        viewport: &ViewportDimensions,
+        scrolling: &crate::settings::ScrollingSettings,
+    ) {
+        let line = line.min(self.rope.len_lines().saturating_sub(1));
+        let (row, visible_rows) = if self.is_soft_wrapped() {
+            (self.display_map.buffer_line_to_first_row(line), self.display_map.row_count())
+        } else {
+            (
+                fold_state.actual_to_display_line(line),
+                self.rope.len_lines().saturating_sub(fold_state.total_hidden_lines()),
+            )
+        };
+        let content_height = visible_rows as f32 * line_height;
+        let max_scroll = scrolling.max_scroll(content_height, viewport.height as f32, viewport.text_area_top, line_height);
+
+        let offset = (-(row as f32) * line_height).min(0.0).max(max_scroll);
+        self.scroll_offset = offset;
+        self.target_scroll_offset = offset;
+        self.needs_scroll_update = true;
+
+        // Keep auto-scroll from snapping back to the cursor
+        self.last_cursor_pos = self.cursor_pos;
+    }
+
+    /// Whether a `batch_updates` closure is currently running
+    pub fn is_batching(&self) -> bool {
+        self.batch_depth > 0
+    }
+
+    /// Take all content changes recorded since the last call
+    pub fn take_changes(&mut self, granularity: ChangeGranularity) -> Vec<ContentChange> {
+        let changes = std::mem::take(&mut self.pending_changes);
+        match granularity {
+            ChangeGranularity::PerEdit => changes,
+            ChangeGranularity::Coalesced => coalesce_changes(changes),
+        }
+    }
+
+    /// Take the edits recorded since the last call, one per logical edit
+    pub fn take_buffer_changes(&mut self) -> Vec<crate::events::BufferChanged> {
+        std::mem::take(&mut self.pending_buffer_changes)
+    }
+
     /// Move cursor by delta
     pub fn move_cursor(&mut self, delta: isize) {
         if delta < 0 {
@@ -2067,23 +3433,84 @@ impl CodeEditorState {
         }
     }
 
-    /// Set text content
+    /// Replace the whole buffer, e.g. when opening a file
+    ///
+    /// The cursor moves to the start, selections and extra cursors are
+    /// dropped, and undo history is cleared since it describes the old text.
     pub fn set_text(&mut self, text: &str) {
+        self.replace_contents(text);
+        self.reset_cursors(vec![Cursor::new(0)]);
+    }
+
+    /// Replace the whole buffer, keeping each cursor on the same line and
+    /// column where the new text allows (e.g. when reverting to disk)
+    ///
+    /// Selections are dropped and undo history is cleared, like `set_text`.
+    pub fn set_text_preserving_cursor(&mut self, text: &str) {
+        self.sync_cursors_from_primary();
+        let line_columns: Vec<(usize, usize)> = self.cursors
+            .iter()
+            .map(|cursor| {
+                let line = self.rope.char_to_line(cursor.position);
+                (line, cursor.position - self.rope.line_to_char(line))
+            })
+            .collect();
+
+        self.replace_contents(text);
+
+        let last_line = self.rope.len_lines() - 1;
+        let cursors = line_columns
+            .into_iter()
+            .map(|(line, column)| {
+                let line = line.min(last_line);
+                let line_start = self.rope.line_to_char(line);
+                let line_len = self.rope.line(line).chars().take_while(|&c| c != '\n' && c != '\r').count();
+                Cursor::new(line_start + column.min(line_len))
+            })
+            .collect();
+        self.reset_cursors(cursors);
+    }
+
+    /// Install `cursors` after a full replacement, without selections or history
+    fn reset_cursors(&mut self, cursors: Vec<Cursor>) {
+        self.cursors = cursors;
+        self.sort_and_merge_cursors();
+        self.sync_primary_cursor();
+        self.selection_start = None;
+        self.selection_end = None;
+        self.selections = SelectionCollection::with_cursor(self.cursor_pos);
+        self.history.clear();
+    }
+
+    /// Swap in new contents, keeping derived state (anchors, widths, parse) in sync
+    fn replace_contents(&mut self, text: &str) {
         // Record byte length before replacement
         #[cfg(feature = "tree-sitter")]
         let old_byte_len = self.rope.len_bytes();
         #[cfg(feature = "tree-sitter")]
         let new_byte_len = text.len();
 
+        let old_char_len = self.rope.len_chars();
+        let old_text = self.operation_log_enabled.then(|| self.rope.to_string());
         self.rope = Rope::from_str(text);
-        self.cursor_pos = self.cursor_pos.min(self.rope.len_chars());
         self.pending_update = true;
         self.content_version += 1;
+        self.record_change(0, old_char_len, text.to_string());
+        if let Some(change) = self.pending_buffer_changes.last_mut() {
+            change.lines = None;
+        }
+        if let Some(old_text) = old_text.filter(|t| !t.is_empty()) {
+            self.record_operation(|seq| Operation::Delete { seq, position: 0, text: old_text });
+        }
+        if !text.is_empty() {
+            self.record_operation(|seq| Operation::Insert { seq, position: 0, text: text.to_string() });
+        }
         self.dirty_lines = None;
         self.previous_line_count = self.rope.len_lines();
-        // Clear anchors and reset selections when text is replaced entirely
+        // Anchors can't follow a full replacement
         self.anchors.clear();
-        self.selections = SelectionCollection::with_cursor(self.cursor_pos);
+        self.edit_locations = EditLocations::default();
+        self.bookmarks = Bookmarks::default();
         // Rebuild line width tracker for O(log n) max width queries
         self.line_width_tracker.rebuild(&self.rope);
         // Invalidate cached max content width
@@ -2115,8 +3542,12 @@ impl CodeEditorState {
     }
 
     /// Resolve an anchor's current position (applies pending edits)
+    ///
+    /// Stored copies go stale once the set applies its pending edits, so the
+    /// set's own copy is resolved when it still tracks the anchor.
     pub fn resolve_anchor(&self, anchor: &Anchor) -> usize {
-        self.anchors.resolve(anchor).min(self.rope.len_chars())
+        let current = self.anchors.get(anchor.id).unwrap_or(anchor);
+        self.anchors.resolve(current).min(self.rope.len_chars())
     }
 
     /// Apply pending anchor edits (call this periodically or before reading anchors)
@@ -2202,6 +3633,47 @@ impl CodeEditorState {
         self.pending_update = true;
     }
 
+    /// Replace every cursor and selection at once
+    ///
+    /// Each entry is `(head, anchor)`; `None` is a plain cursor. Positions are
+    /// clamped to the buffer, then sorted and merged like any other selection
+    /// set, and the legacy cursor fields are synced. An empty list leaves a
+    /// single cursor at the current position.
+    pub fn set_selections(&mut self, selections: Vec<(usize, Option<usize>)>) {
+        let len = self.rope.len_chars();
+        let mut cursors: Vec<Cursor> = selections
+            .into_iter()
+            .map(|(head, anchor)| Cursor {
+                position: head.min(len),
+                anchor: anchor.map(|a| a.min(len)).filter(|&a| a != head.min(len)),
+            })
+            .collect();
+        if cursors.is_empty() {
+            cursors.push(Cursor::new(self.cursor_pos.min(len)));
+        }
+        self.selections = SelectionCollection::from_cursors(&cursors);
+        self.sync_from_selections();
+        self.pending_update = true;
+    }
+
+    /// All cursors and selections as `(head, anchor)` pairs, sorted by position
+    pub fn get_selections(&self) -> Vec<(usize, Option<usize>)> {
+        let mut cursors = self.cursors.clone();
+        if cursors.is_empty() {
+            cursors.push(Cursor::new(self.cursor_pos));
+        }
+        // The primary cursor's legacy fields may be ahead of cursors[0]
+        cursors[0] = Cursor {
+            position: self.cursor_pos,
+            anchor: self.selection_start,
+        };
+        SelectionCollection::from_cursors(&cursors)
+            .to_cursors()
+            .into_iter()
+            .map(|c| (c.position, c.anchor))
+            .collect()
+    }
+
     /// Move the primary selection to a new position
     pub fn set_primary_selection(&mut self, head: usize, extend: bool) {
         let head = head.min(self.rope.len_chars());
@@ -2235,8 +3707,12 @@ impl CodeEditorState {
     }
 
     /// Add a new cursor at the given position
+    ///
+    /// A cursor already at `position`, including the primary one, is not duplicated.
     pub fn add_cursor(&mut self, position: usize) {
         let position = position.min(self.rope.len_chars());
+        // The primary cursor's fields may be ahead of cursors[0]
+        self.sync_cursors_from_primary();
         // Don't add duplicate cursor at same position
         if !self.cursors.iter().any(|c| c.position == position) {
             self.cursors.push(Cursor::new(position));
@@ -2354,6 +3830,33 @@ impl CodeEditorState {
         }
     }
 
+    /// Every occurrence of the selected text, up to `limit` matches
+    ///
+    /// Empty when nothing is selected or the selection spans lines or is only
+    /// whitespace. The selection itself is included.
+    pub fn selection_occurrences(&self, limit: usize) -> Vec<Range<usize>> {
+        let (Some(anchor), Some(head)) = (self.selection_start, self.selection_end) else {
+            return Vec::new();
+        };
+        let len = self.rope.len_chars();
+        let (start, end) = (anchor.min(head).min(len), anchor.max(head).min(len));
+        let needle = self.rope.slice(start..end).to_string();
+        if needle.trim().is_empty() || needle.contains('\n') {
+            return Vec::new();
+        }
+
+        let needle_chars = needle.chars().count();
+        self.rope
+            .to_string()
+            .match_indices(needle.as_str())
+            .take(limit)
+            .map(|(byte, _)| {
+                let char_start = self.rope.byte_to_char(byte);
+                char_start..char_start + needle_chars
+            })
+            .collect()
+    }
+
     /// Find the next occurrence of text after a given position
     pub fn find_next_occurrence(&self, text: &str, after_pos: usize) -> Option<(usize, usize)> {
         if text.is_empty() {
@@ -2431,21 +3934,122 @@ impl CodeEditorState {
             .max()
             .unwrap_or(0);
 
-        // Find next occurrence
-        if let Some((start, end)) = self.find_next_occurrence(&search_text, search_from) {
-            // Check if this position is already covered by an existing cursor
-            let already_covered = self.cursors.iter().any(|c| {
-                let (cs, ce) = (c.selection_start(), c.selection_end());
-                start >= cs && end <= ce
-            });
+        // Find next occurrence
+        if let Some((start, end)) = self.find_next_occurrence(&search_text, search_from) {
+            // Check if this position is already covered by an existing cursor
+            let already_covered = self.cursors.iter().any(|c| {
+                let (cs, ce) = (c.selection_start(), c.selection_end());
+                start >= cs && end <= ce
+            });
+
+            if !already_covered {
+                self.add_cursor_with_selection(end, start);
+                return true;
+            }
+        }
+
+        false
+    }
+
+    /// Select the next whole-word occurrence of the primary selection (Ctrl+D)
+    ///
+    /// With nothing selected, the word under the primary cursor is selected
+    /// first. Otherwise the first occurrence after the last selection (wrapping
+    /// to the start) that isn't already selected is added as a new selection.
+    /// Returns false when there is nothing to select.
+    pub fn select_next_occurrence(&mut self) -> bool {
+        let Some(query) = self.occurrence_query() else {
+            return self.select_word_at_primary();
+        };
+
+        let search_from = self.cursors.iter().map(|c| c.selection_end()).max().unwrap_or(0);
+        let occurrences = self.whole_word_occurrences(&query);
+        let next = occurrences
+            .iter()
+            .filter(|&&(start, _)| start >= search_from)
+            .chain(occurrences.iter())
+            .copied()
+            .find(|&(start, end)| !self.is_selected(start, end));
+        match next {
+            Some((start, end)) => {
+                self.add_occurrence_selections(&[(start, end)]);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Select every whole-word occurrence of the primary selection (or of the
+    /// word under the primary cursor), returning the number of selections
+    pub fn select_all_occurrences(&mut self) -> usize {
+        let query = self.occurrence_query().or_else(|| {
+            if self.select_word_at_primary() {
+                self.occurrence_query()
+            } else {
+                None
+            }
+        });
+        let Some(query) = query else {
+            return 0;
+        };
+
+        let occurrences = self.whole_word_occurrences(&query);
+        self.add_occurrence_selections(&occurrences);
+        self.cursors.len()
+    }
+
+    /// Text of the primary selection, if it is non-empty
+    fn occurrence_query(&mut self) -> Option<String> {
+        self.sync_cursors_from_primary();
+        let primary = self.cursors.first()?;
+        primary
+            .has_selection()
+            .then(|| self.rope.slice(primary.selection_start()..primary.selection_end()).to_string())
+    }
+
+    /// Select the word under the primary cursor, keeping the other cursors
+    fn select_word_at_primary(&mut self) -> bool {
+        let Some(primary) = self.cursors.first() else {
+            return false;
+        };
+        let Some((start, end)) = self.word_at_position(primary.position) else {
+            return false;
+        };
+        self.cursors[0] = Cursor::with_selection(end, start);
+        self.sync_primary_cursor();
+        self.pending_update = true;
+        true
+    }
+
+    /// Non-overlapping occurrences of `query` that aren't part of a larger word
+    fn whole_word_occurrences(&self, query: &str) -> Vec<(usize, usize)> {
+        let query_len = query.chars().count();
+        let text = self.rope.to_string();
+        text.match_indices(query)
+            .map(|(byte, _)| {
+                let start = self.rope.byte_to_char(byte);
+                (start, start + query_len)
+            })
+            .filter(|&(start, end)| is_whole_word(&self.rope, start, end))
+            .collect()
+    }
+
+    /// Whether `start..end` lies inside an existing selection
+    fn is_selected(&self, start: usize, end: usize) -> bool {
+        self.cursors.iter().any(|c| start >= c.selection_start() && end <= c.selection_end())
+    }
 
-            if !already_covered {
-                self.add_cursor_with_selection(end, start);
-                return true;
+    /// Add each range as a selection with its cursor at the end
+    fn add_occurrence_selections(&mut self, ranges: &[(usize, usize)]) {
+        let mut selections = SelectionCollection::from_cursors(&self.cursors);
+        for &(start, end) in ranges {
+            if !self.is_selected(start, end) {
+                selections.add_selection_range(end, start);
             }
         }
-
-        false
+        self.cursors = selections.to_cursors();
+        self.sync_primary_cursor();
+        self.pending_update = true;
     }
 
     /// Record a text edit for incremental parsing (sends TextEditEvent)
@@ -2491,6 +4095,21 @@ pub struct SelectionHighlight {
     pub cursor_index: usize,
 }
 
+/// Component marker for the wrap-at-ruler guide line
+#[derive(Component)]
+pub struct WrapGuide;
+
+/// Component for a column ruler line from `UiSettings::rulers`
+#[derive(Component)]
+pub struct ColumnRuler {
+    /// Column the ruler is drawn at
+    pub column: u32,
+}
+
+/// Component marker for whitespace markers drawn inside a selection
+#[derive(Component)]
+pub struct SelectionWhitespaceMarker;
+
 /// Component marker for bracket match highlight entities (bounding box style)
 #[derive(Component)]
 pub struct BracketMatchHighlight {
@@ -2555,6 +4174,17 @@ pub struct MinimapFindHighlight {
     pub line_index: usize,
 }
 
+/// Component marker for overview ruler marks
+#[derive(Component)]
+pub struct OverviewRulerMark {
+    /// Buffer line the mark points at
+    pub line: usize,
+}
+
+/// Component marker for the built-in status overlay text
+#[derive(Component)]
+pub struct StatusOverlayText;
+
 /// Component marker for GPU minimap mesh entity
 #[derive(Component)]
 pub struct GpuMinimapMesh {
@@ -2562,6 +4192,10 @@ pub struct GpuMinimapMesh {
     pub built_at_version: u64,
     /// The scroll offset when this mesh was built
     pub built_at_scroll: f32,
+    /// The number of minimap rows (display rows after folding) when this mesh was built
+    pub built_at_rows: usize,
+    /// `GlyphAtlas::generation` when this mesh was built; its UVs are stale once it changes
+    pub built_at_generation: u64,
 }
 
 /// Component marker for the minimap camera
@@ -2610,27 +4244,797 @@ pub struct BracketMatch {
     pub matching_bracket_pos: usize,
 }
 
-/// Resource to track the current bracket match state
-#[derive(Resource, Default, Clone, Debug)]
-pub struct BracketMatchState {
-    /// Current bracket match (if any)
-    pub current_match: Option<BracketMatch>,
-}
+/// Resource to track the current bracket match state
+#[derive(Resource, Default, Clone, Debug)]
+pub struct BracketMatchState {
+    /// Current bracket match (if any)
+    pub current_match: Option<BracketMatch>,
+}
+
+/// Component marker for find/search highlight entities
+#[derive(Component)]
+pub struct FindHighlight {
+    /// Index of this match in the matches list
+    pub match_index: usize,
+}
+
+/// A single search match
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FindMatch {
+    /// Start position (char index)
+    pub start: usize,
+    /// End position (char index)
+    pub end: usize,
+}
+
+/// Emacs-style kill ring of recently cut/copied text
+///
+/// Every copy pushes a new entry; consecutive contiguous kills (cuts, line deletes)
+/// are merged into a single entry. After a paste, `EditorAction::PasteCycle`
+/// replaces the pasted text with progressively older entries.
+#[derive(Resource, Clone, Debug)]
+pub struct KillRing {
+    /// Ring entries, most recent first
+    pub entries: std::collections::VecDeque<String>,
+    /// Maximum number of entries kept
+    pub capacity: usize,
+    /// Paste whole-line entries as new lines (see `ClipboardSettings::full_line_paste`)
+    pub full_line_paste: bool,
+    /// Whether each entry (same order as `entries`) came from a whole-line copy or kill
+    full_line: std::collections::VecDeque<bool>,
+    /// Index of the entry inserted by the last paste
+    yank_index: usize,
+    /// Char range and content version of the last paste, while it can still be cycled
+    last_yank: Option<(usize, usize, u64)>,
+    /// Cursor position and content version right after the last kill
+    last_kill: Option<(usize, u64)>,
+}
+
+impl Default for KillRing {
+    fn default() -> Self {
+        Self::new(16)
+    }
+}
+
+impl KillRing {
+    /// Create an empty kill ring holding at most `capacity` entries
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            entries: std::collections::VecDeque::new(),
+            capacity: capacity.max(1),
+            full_line_paste: true,
+            full_line: std::collections::VecDeque::new(),
+            yank_index: 0,
+            last_yank: None,
+            last_kill: None,
+        }
+    }
+
+    /// Push a new entry (e.g. from a copy), never merging with the previous one
+    pub fn push(&mut self, text: String) {
+        self.push_entry(text, false);
+    }
+
+    /// Push a whole line (ending in a newline), pasted as a new line
+    pub fn push_line(&mut self, text: String) {
+        self.push_entry(text, true);
+    }
+
+    fn push_entry(&mut self, text: String, full_line: bool) {
+        if text.is_empty() {
+            return;
+        }
+        self.last_kill = None;
+        self.entries.push_front(text);
+        self.entries.truncate(self.capacity);
+        self.full_line.push_front(full_line);
+        self.full_line.truncate(self.capacity);
+    }
+
+    /// Record text removed from `start..end` (char offsets before the removal)
+    ///
+    /// If the previous kill left the cursor at the edge of this range and nothing
+    /// else changed the buffer since, the text is appended (forward kill) or
+    /// prepended (backward kill) to the most recent entry instead of creating a new one.
+    pub fn kill(&mut self, text: String, start: usize, end: usize, version_before: u64, version_after: u64) {
+        self.kill_entry(text, start, end, version_before, version_after, false);
+    }
+
+    /// Record whole lines removed from `start..end`, merging like `kill`
+    ///
+    /// The entry is pasted as new lines as long as everything merged into it
+    /// was whole lines too.
+    pub fn kill_lines(&mut self, text: String, start: usize, end: usize, version_before: u64, version_after: u64) {
+        self.kill_entry(text, start, end, version_before, version_after, true);
+    }
+
+    fn kill_entry(
+        &mut self,
+        text: String,
+        start: usize,
+        end: usize,
+        version_before: u64,
+        version_after: u64,
+        full_line: bool,
+    ) {
+        if text.is_empty() {
+            return;
+        }
+
+        let merged = match (self.last_kill, self.entries.front_mut()) {
+            (Some((pos, version)), Some(front)) if version == version_before => {
+                if pos == start {
+                    front.push_str(&text);
+                    true
+                } else if pos == end {
+                    front.insert_str(0, &text);
+                    true
+                } else {
+                    false
+                }
+            }
+            _ => false,
+        };
+
+        if merged {
+            if let Some(front) = self.full_line.front_mut() {
+                *front &= full_line;
+            }
+        } else {
+            self.push_entry(text, full_line);
+        }
+        self.last_kill = Some((start, version_after));
+    }
+
+    /// The most recent entry
+    pub fn top(&self) -> Option<&str> {
+        self.entries.front().map(|s| s.as_str())
+    }
+
+    /// Whether the entry at `index` (0 = most recent) is a whole-line copy
+    pub fn is_full_line(&self, index: usize) -> bool {
+        self.full_line.get(index).copied().unwrap_or(false)
+    }
+
+    /// Remember the range inserted by a paste so it can be cycled
+    ///
+    /// `from_top` resets the cycle to the most recent entry (a fresh paste).
+    pub fn record_yank(&mut self, start: usize, end: usize, content_version: u64, from_top: bool) {
+        if from_top {
+            self.yank_index = 0;
+        }
+        self.last_yank = Some((start, end, content_version));
+    }
+
+    /// Advance to the next older entry for replacing the last paste
+    ///
+    /// Returns the char range of the last paste and the replacement text, or `None`
+    /// if the buffer changed since that paste or the ring has nothing to cycle to.
+    pub fn cycle(&mut self, content_version: u64) -> Option<(usize, usize, String)> {
+        let (start, end, version) = self.last_yank?;
+        if version != content_version || self.entries.len() < 2 {
+            return None;
+        }
+
+        self.yank_index = (self.yank_index + 1) % self.entries.len();
+        let text = self.entries.get(self.yank_index)?.clone();
+        Some((start, end, text))
+    }
+
+    /// Clear all entries
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.full_line.clear();
+        self.yank_index = 0;
+        self.last_yank = None;
+        self.last_kill = None;
+    }
+}
+
+/// Clipboard history: copies and cuts are pushed onto the kill ring
+pub type ClipboardRing = KillRing;
+
+// ========== Snippet Sessions ==========
+
+/// A snippet template expanded to plain text plus the tab stops inside it
+///
+/// Parsed from LSP / VS Code snippet syntax: `$1`, `${2:placeholder}`,
+/// `${3|one,two|}` (the first choice is inserted) and `$0` for the final
+/// cursor position. Variables such as `$TM_FILENAME` expand to their default
+/// (`${NAME:default}`) or to nothing; `\$`, `\}` and `\\` are literal.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Snippet {
+    /// Expanded text with placeholders filled in
+    pub text: String,
+    /// Every tab stop occurrence as (index, char range within `text`), in document order
+    pub stops: Vec<(u32, Range<usize>)>,
+}
+
+/// Parsed piece of a snippet template
+#[derive(Clone, Debug)]
+enum SnippetPiece {
+    Text(String),
+    Stop { index: u32, children: Vec<SnippetPiece> },
+}
+
+impl Snippet {
+    /// Parse a snippet template; malformed syntax is kept as literal text
+    pub fn parse(template: &str) -> Self {
+        let chars: Vec<char> = template.chars().collect();
+        let mut pos = 0;
+        let pieces = parse_snippet_pieces(&chars, &mut pos, false);
+
+        // A mirror without a placeholder shows the placeholder given elsewhere
+        let mut placeholders: HashMap<u32, Vec<SnippetPiece>> = HashMap::new();
+        collect_snippet_placeholders(&pieces, &mut placeholders);
+
+        let mut snippet = Snippet::default();
+        let mut len = 0;
+        snippet.render(&pieces, &placeholders, &mut len, &mut Vec::new());
+        snippet.stops.sort_by_key(|(index, range)| (range.start, range.end, *index));
+        snippet.stops.dedup();
+        snippet
+    }
+
+    /// Indent every line after the first by `indent`, shifting the stops to match
+    pub fn indented(mut self, indent: &str) -> Self {
+        if indent.is_empty() || !self.text.contains('\n') {
+            return self;
+        }
+        let indent_len = indent.chars().count();
+        // Newlines before each char offset of the unindented text
+        let mut newlines_before = Vec::with_capacity(self.text.len() + 1);
+        let mut newlines = 0;
+        let mut text = String::with_capacity(self.text.len());
+        for c in self.text.chars() {
+            newlines_before.push(newlines);
+            text.push(c);
+            if c == '\n' {
+                newlines += 1;
+                text.push_str(indent);
+            }
+        }
+        newlines_before.push(newlines);
+
+        let shift = |offset: usize| offset + newlines_before[offset] * indent_len;
+        for (_, range) in &mut self.stops {
+            *range = shift(range.start)..shift(range.end);
+        }
+        self.text = text;
+        self
+    }
+
+    /// Stop occurrences grouped by index in visiting order: `$1`, `$2`, ... then `$0`
+    ///
+    /// Without an explicit `$0` the snippet ends after its text.
+    pub fn stop_groups(&self) -> Vec<Vec<Range<usize>>> {
+        let mut indices: Vec<u32> = self.stops.iter().map(|(index, _)| *index).collect();
+        indices.sort_by_key(|&index| (index == 0, index));
+        indices.dedup();
+
+        let mut groups: Vec<Vec<Range<usize>>> = indices
+            .iter()
+            .map(|&index| {
+                self.stops
+                    .iter()
+                    .filter(|(i, _)| *i == index)
+                    .map(|(_, range)| range.clone())
+                    .collect()
+            })
+            .collect();
+        if indices.last() != Some(&0) {
+            let end = self.text.chars().count();
+            groups.push(vec![end..end]);
+        }
+        groups
+    }
+
+    fn render(
+        &mut self,
+        pieces: &[SnippetPiece],
+        placeholders: &HashMap<u32, Vec<SnippetPiece>>,
+        len: &mut usize,
+        rendering: &mut Vec<u32>,
+    ) {
+        for piece in pieces {
+            match piece {
+                SnippetPiece::Text(text) => {
+                    self.text.push_str(text);
+                    *len += text.chars().count();
+                }
+                SnippetPiece::Stop { index, children } => {
+                    let start = *len;
+                    let children = if !children.is_empty() {
+                        children.as_slice()
+                    } else if rendering.contains(index) {
+                        // A placeholder containing its own mirror would never end
+                        &[]
+                    } else {
+                        placeholders.get(index).map(Vec::as_slice).unwrap_or_default()
+                    };
+                    rendering.push(*index);
+                    self.render(children, placeholders, len, rendering);
+                    rendering.pop();
+                    self.stops.push((*index, start..*len));
+                }
+            }
+        }
+    }
+}
+
+fn collect_snippet_placeholders(pieces: &[SnippetPiece], placeholders: &mut HashMap<u32, Vec<SnippetPiece>>) {
+    for piece in pieces {
+        if let SnippetPiece::Stop { index, children } = piece {
+            if !children.is_empty() {
+                placeholders.entry(*index).or_insert_with(|| children.clone());
+            }
+            collect_snippet_placeholders(children, placeholders);
+        }
+    }
+}
+
+/// Parse pieces up to the end of input, or up to the closing `}` of a placeholder
+fn parse_snippet_pieces(chars: &[char], pos: &mut usize, in_placeholder: bool) -> Vec<SnippetPiece> {
+    let mut pieces = Vec::new();
+    let mut text = String::new();
+    while *pos < chars.len() {
+        let c = chars[*pos];
+        match c {
+            '}' if in_placeholder => break,
+            '\\' if matches!(chars.get(*pos + 1), Some('$' | '}' | '\\' | ',' | '|')) => {
+                text.push(chars[*pos + 1]);
+                *pos += 2;
+            }
+            '$' => {
+                let start = *pos;
+                match parse_snippet_dollar(chars, pos) {
+                    Some(piece) => {
+                        if !text.is_empty() {
+                            pieces.push(SnippetPiece::Text(std::mem::take(&mut text)));
+                        }
+                        pieces.push(piece);
+                    }
+                    None => {
+                        // Not valid snippet syntax, keep the `$` literally
+                        *pos = start + 1;
+                        text.push('$');
+                    }
+                }
+            }
+            _ => {
+                text.push(c);
+                *pos += 1;
+            }
+        }
+    }
+    if !text.is_empty() {
+        pieces.push(SnippetPiece::Text(text));
+    }
+    pieces
+}
+
+/// Parse a `$...` construct starting at `pos`; `None` if it is not valid syntax
+fn parse_snippet_dollar(chars: &[char], pos: &mut usize) -> Option<SnippetPiece> {
+    *pos += 1;
+    let braced = chars.get(*pos) == Some(&'{');
+    if braced {
+        *pos += 1;
+    }
+
+    let name_start = *pos;
+    let numeric = chars.get(*pos)?.is_ascii_digit();
+    while let Some(c) = chars.get(*pos) {
+        let accepted = if numeric { c.is_ascii_digit() } else { c.is_alphanumeric() || *c == '_' };
+        if !accepted {
+            break;
+        }
+        *pos += 1;
+    }
+    if *pos == name_start {
+        return None;
+    }
+    let name: String = chars[name_start..*pos].iter().collect();
+    let index = numeric.then(|| name.parse::<u32>().ok()).flatten();
+    if numeric && index.is_none() {
+        return None;
+    }
+
+    // A variable has no stop, only its (default) text
+    let finish = |children: Vec<SnippetPiece>| match index {
+        Some(index) => SnippetPiece::Stop { index, children },
+        None => SnippetPiece::Text(snippet_pieces_text(&children)),
+    };
+
+    if !braced {
+        return Some(finish(Vec::new()));
+    }
+    match chars.get(*pos)? {
+        '}' => {
+            *pos += 1;
+            Some(finish(Vec::new()))
+        }
+        ':' => {
+            *pos += 1;
+            let children = parse_snippet_pieces(chars, pos, true);
+            if chars.get(*pos) != Some(&'}') {
+                return None;
+            }
+            *pos += 1;
+            Some(finish(children))
+        }
+        '|' if numeric => {
+            *pos += 1;
+            let mut choice = String::new();
+            let mut first = true;
+            loop {
+                match *chars.get(*pos)? {
+                    '|' if chars.get(*pos + 1) == Some(&'}') => {
+                        *pos += 2;
+                        break;
+                    }
+                    '\\' if chars.get(*pos + 1).is_some() => {
+                        if first {
+                            choice.push(chars[*pos + 1]);
+                        }
+                        *pos += 2;
+                    }
+                    ',' => {
+                        first = false;
+                        *pos += 1;
+                    }
+                    c => {
+                        if first {
+                            choice.push(c);
+                        }
+                        *pos += 1;
+                    }
+                }
+            }
+            Some(finish(vec![SnippetPiece::Text(choice)]))
+        }
+        _ => None,
+    }
+}
+
+/// Plain text of parsed pieces, used for variable defaults
+fn snippet_pieces_text(pieces: &[SnippetPiece]) -> String {
+    pieces
+        .iter()
+        .map(|piece| match piece {
+            SnippetPiece::Text(text) => text.clone(),
+            SnippetPiece::Stop { children, .. } => snippet_pieces_text(children),
+        })
+        .collect()
+}
+
+/// Tab stops of an inserted snippet, visited in order with Tab / Shift+Tab
+///
+/// Stops are tracked with anchors, so they follow edits made while the
+/// session is active. A stop that occurs more than once is selected at every
+/// occurrence, so typing updates all of its mirrors together. The session
+/// ends when the last stop is reached.
+#[derive(Resource, Clone, Debug, Default)]
+pub struct SnippetSession {
+    /// (start, end) anchors of each occurrence of each stop, in visiting order
+    stops: Vec<Vec<(Anchor, Anchor)>>,
+    /// Index of the stop that is currently selected
+    current: usize,
+}
+
+impl SnippetSession {
+    /// Start a session over the given stops (char ranges) and select the first one
+    pub fn start(&mut self, state: &mut CodeEditorState, stops: Vec<Range<usize>>) {
+        self.start_linked(state, stops.into_iter().map(|stop| vec![stop]).collect());
+    }
+
+    /// Start a session where each stop may have several linked occurrences
+    pub fn start_linked(&mut self, state: &mut CodeEditorState, stops: Vec<Vec<Range<usize>>>) {
+        self.end(state);
+        self.stops = stops
+            .into_iter()
+            .filter(|occurrences| !occurrences.is_empty())
+            .map(|occurrences| {
+                occurrences
+                    .into_iter()
+                    .map(|stop| {
+                        (
+                            state.create_anchor(stop.start, AnchorBias::Left),
+                            state.create_anchor(stop.end, AnchorBias::Right),
+                        )
+                    })
+                    .collect()
+            })
+            .collect();
+        self.select(state, 0);
+    }
+
+    /// Replace `range` with the expanded snippet `template` as one undo step
+    /// and select its first stop
+    ///
+    /// Lines after the first get the indentation of the line being edited.
+    pub fn expand(&mut self, state: &mut CodeEditorState, range: Range<usize>, template: &str) {
+        let len = state.rope.len_chars();
+        let (start, end) = (range.start.min(len), range.end.min(len));
+        let line = state.rope.char_to_line(start);
+        let indent: String = state
+            .rope
+            .line(line)
+            .chars()
+            .take_while(|c| *c == ' ' || *c == '\t')
+            .collect();
+        let snippet = Snippet::parse(template).indented(&indent);
+
+        self.end(state);
+        state.set_selections(vec![(end, Some(start))]);
+        state.insert_at_cursors(&snippet.text);
+
+        let stops = snippet
+            .stop_groups()
+            .into_iter()
+            .map(|occurrences| {
+                occurrences
+                    .into_iter()
+                    .map(|stop| start + stop.start..start + stop.end)
+                    .collect()
+            })
+            .collect();
+        self.start_linked(state, stops);
+    }
+
+    /// Whether a snippet has tab stops left to visit
+    pub fn is_active(&self) -> bool {
+        !self.stops.is_empty()
+    }
+
+    /// Index of the current stop, if a session is active
+    pub fn current(&self) -> Option<usize> {
+        self.is_active().then_some(self.current)
+    }
+
+    /// Current char range of a stop's first occurrence
+    pub fn stop_range(&self, state: &CodeEditorState, index: usize) -> Option<Range<usize>> {
+        self.stop_ranges(state, index).into_iter().next()
+    }
+
+    /// Current char ranges of every occurrence of a stop
+    pub fn stop_ranges(&self, state: &CodeEditorState, index: usize) -> Vec<Range<usize>> {
+        self.stops
+            .get(index)
+            .map(|occurrences| {
+                occurrences
+                    .iter()
+                    .map(|(start, end)| state.resolve_anchor(start)..state.resolve_anchor(end))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Select the next stop; returns false if no session is active
+    pub fn next_stop(&mut self, state: &mut CodeEditorState) -> bool {
+        if !self.is_active() {
+            return false;
+        }
+        self.select(state, self.current + 1);
+        true
+    }
+
+    /// Select the previous stop; returns false if no session is active
+    pub fn prev_stop(&mut self, state: &mut CodeEditorState) -> bool {
+        if !self.is_active() {
+            return false;
+        }
+        self.select(state, self.current.saturating_sub(1));
+        true
+    }
+
+    /// End the session, leaving the cursor where it is
+    pub fn end(&mut self, state: &mut CodeEditorState) {
+        for (start, end) in self.stops.drain(..).flatten() {
+            state.remove_anchor(start.id);
+            state.remove_anchor(end.id);
+        }
+        self.current = 0;
+    }
+
+    /// Select every occurrence of a stop, ending the session once the last stop is reached
+    fn select(&mut self, state: &mut CodeEditorState, index: usize) {
+        let index = index.min(self.stops.len().saturating_sub(1));
+        let ranges = self.stop_ranges(state, index);
+        if ranges.is_empty() {
+            return;
+        }
+        self.current = index;
+        state.set_selections(
+            ranges
+                .iter()
+                .map(|range| (range.end, (range.start < range.end).then_some(range.start)))
+                .collect(),
+        );
+        state.pending_update = true;
+
+        if self.current + 1 == self.stops.len() {
+            self.end(state);
+        }
+    }
+}
+
+// ========== View Snapshots ==========
+
+/// Scroll position, cursors and selection captured from the editor
+///
+/// Take one with `CodeEditorState::view_snapshot` before a jump or buffer
+/// switch, then either restore it or animate away from it with `ViewTransition`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ViewSnapshot {
+    pub scroll_offset: f32,
+    pub horizontal_scroll_offset: f32,
+    pub cursor_pos: usize,
+    pub selection_start: Option<usize>,
+    pub selection_end: Option<usize>,
+    pub cursors: Vec<Cursor>,
+}
+
+/// Eased scroll animation from a snapshot to the editor's current scroll target
+///
+/// While running, it overrides the regular smooth-scroll interpolation so
+/// the view glides from where the snapshot was taken to the new position.
+#[derive(Resource, Clone, Debug, Default)]
+pub struct ViewTransition {
+    from: Option<(f32, f32)>,
+    elapsed: f32,
+    duration: f32,
+}
+
+impl ViewTransition {
+    /// Start animating from the snapshot's scroll position over `duration` seconds
+    pub fn start(&mut self, snapshot: &ViewSnapshot, duration: f32) {
+        self.from = Some((snapshot.scroll_offset, snapshot.horizontal_scroll_offset));
+        self.elapsed = 0.0;
+        self.duration = duration.max(0.0);
+    }
+
+    /// Whether an animation is in progress
+    pub fn is_active(&self) -> bool {
+        self.from.is_some()
+    }
+
+    /// Advance by `dt` seconds, writing the interpolated scroll into `state`
+    pub fn advance(&mut self, state: &mut CodeEditorState, dt: f32) {
+        let Some((from_y, from_x)) = self.from else {
+            return;
+        };
+
+        self.elapsed += dt;
+        let t = if self.duration > 0.0 { (self.elapsed / self.duration).min(1.0) } else { 1.0 };
+        // Smoothstep easing
+        let eased = t * t * (3.0 - 2.0 * t);
+
+        state.scroll_offset = from_y + (state.target_scroll_offset - from_y) * eased;
+        state.horizontal_scroll_offset =
+            from_x + (state.target_horizontal_scroll_offset - from_x) * eased;
+        state.needs_scroll_update = true;
+        if from_x != state.target_horizontal_scroll_offset {
+            state.needs_update = true;
+        }
+
+        if t >= 1.0 {
+            self.from = None;
+        }
+    }
+}
+
+// ========== Diagnostics ==========
+
+/// Severity of a diagnostic, ordered from most to least severe
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[derive(serde::Serialize, serde::Deserialize)]
+pub enum DiagnosticLevel {
+    Error,
+    Warning,
+    Information,
+    #[default]
+    Hint,
+}
+
+/// A diagnostic attached to a range of the buffer
+#[derive(Clone, Debug, PartialEq)]
+pub struct EditorDiagnostic {
+    /// Character range the diagnostic covers
+    pub range: Range<usize>,
+    /// Severity
+    pub level: DiagnosticLevel,
+    /// Message shown to the user
+    pub message: String,
+}
+
+/// Diagnostics for the current buffer, from the LSP or set by the host
+///
+/// Ranges follow edits to the buffer once `track_edits` has anchored them.
+#[derive(Resource, Clone, Debug, Default)]
+pub struct EditorDiagnostics {
+    items: Vec<EditorDiagnostic>,
+    /// Start and end anchors of each item, parallel to `items` once anchored
+    anchors: Vec<(Anchor, Anchor)>,
+    /// Anchors of replaced items, released by the next `track_edits`
+    stale_anchors: Vec<Anchor>,
+    /// `content_version` the ranges were last resolved at
+    resolved_version: u64,
+}
+
+impl EditorDiagnostics {
+    /// Replace all diagnostics
+    pub fn set(&mut self, mut items: Vec<EditorDiagnostic>) {
+        items.sort_by_key(|d| (d.range.start, d.range.end));
+        self.items = items;
+        self.release_anchors();
+    }
+
+    /// Remove all diagnostics
+    pub fn clear(&mut self) {
+        self.items.clear();
+        self.release_anchors();
+    }
+
+    fn release_anchors(&mut self) {
+        self.stale_anchors.extend(self.anchors.drain(..).flat_map(|(start, end)| [start, end]));
+    }
+
+    /// Whether `track_edits` has anchors to create, release or resolve
+    pub fn needs_tracking(&self, state: &CodeEditorState) -> bool {
+        !self.stale_anchors.is_empty()
+            || self.anchors.len() != self.items.len()
+            || (!self.items.is_empty() && self.resolved_version != state.content_version)
+    }
+
+    /// Keep ranges in step with edits to the buffer
+    ///
+    /// Newly set items are anchored to the text they cover (text typed at either
+    /// edge stays outside); on later calls their ranges move with the anchors.
+    pub fn track_edits(&mut self, state: &mut CodeEditorState) {
+        for anchor in self.stale_anchors.drain(..) {
+            state.remove_anchor(anchor.id);
+        }
+
+        if self.anchors.len() != self.items.len() {
+            self.anchors = self
+                .items
+                .iter()
+                .map(|d| {
+                    (
+                        state.create_anchor(d.range.start, AnchorBias::Right),
+                        state.create_anchor(d.range.end, AnchorBias::Left),
+                    )
+                })
+                .collect();
+        } else if self.resolved_version != state.content_version {
+            for (item, (start, end)) in self.items.iter_mut().zip(&self.anchors) {
+                let start = state.resolve_anchor(start);
+                item.range = start..state.resolve_anchor(end).max(start);
+            }
+        }
+        self.resolved_version = state.content_version;
+    }
 
-/// Component marker for find/search highlight entities
-#[derive(Component)]
-pub struct FindHighlight {
-    /// Index of this match in the matches list
-    pub match_index: usize,
-}
+    /// All diagnostics, sorted by position
+    pub fn items(&self) -> &[EditorDiagnostic] {
+        &self.items
+    }
 
-/// A single search match
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
-pub struct FindMatch {
-    /// Start position (char index)
-    pub start: usize,
-    /// End position (char index)
-    pub end: usize,
+    /// First diagnostic starting after `pos` that is at least `min_level` severe,
+    /// wrapping around to the start of the buffer
+    pub fn next_after(&self, pos: usize, min_level: DiagnosticLevel) -> Option<&EditorDiagnostic> {
+        let mut eligible = self.items.iter().filter(|d| d.level <= min_level);
+        let first = eligible.clone().next();
+        eligible.find(|d| d.range.start > pos).or(first)
+    }
+
+    /// Last diagnostic starting before `pos` that is at least `min_level` severe,
+    /// wrapping around to the end of the buffer
+    pub fn prev_before(&self, pos: usize, min_level: DiagnosticLevel) -> Option<&EditorDiagnostic> {
+        let mut eligible = self.items.iter().rev().filter(|d| d.level <= min_level);
+        let last = eligible.clone().next();
+        eligible.find(|d| d.range.start < pos).or(last)
+    }
 }
 
 /// Resource to track find/search state
@@ -2651,19 +5055,91 @@ pub struct FindState {
     pub use_regex: bool,
     /// Whole word matching
     pub whole_word: bool,
+    /// Stop collecting after this many matches (None = unlimited)
+    pub max_matches: Option<usize>,
+    /// The last search stopped at `max_matches`, so `matches` is incomplete
+    pub truncated: bool,
+    /// Don't search as the query changes; wait for FindNext/FindPrevious
+    pub on_demand: bool,
+    /// The query changed since matches were last computed
+    pub needs_search: bool,
+    /// The find input has keyboard focus: typed keys edit the query, not the buffer
+    pub find_focused: bool,
+    /// Replacement for `replace_current`/`replace_all` (`$1`, `${name}` expand
+    /// to capture groups in regex mode)
+    pub replace_text: String,
 }
 
 
 impl FindState {
-    /// Find all matches in the given rope
+    /// Replace the query, searching right away unless searches are on demand
+    pub fn set_query(&mut self, query: impl Into<String>, rope: &Rope) {
+        self.query = query.into();
+        self.refresh(rope);
+    }
+
+    /// Flip case-sensitive matching and refresh the matches
+    pub fn toggle_case_sensitive(&mut self, rope: &Rope) {
+        self.case_sensitive = !self.case_sensitive;
+        self.refresh(rope);
+    }
+
+    /// Flip whole-word matching and refresh the matches
+    pub fn toggle_whole_word(&mut self, rope: &Rope) {
+        self.whole_word = !self.whole_word;
+        self.refresh(rope);
+    }
+
+    /// Search again after the query or options changed, unless searches are on demand
+    fn refresh(&mut self, rope: &Rope) {
+        if self.on_demand {
+            self.matches.clear();
+            self.current_match_index = None;
+            self.truncated = false;
+            self.needs_search = true;
+        } else {
+            self.search(rope);
+        }
+    }
+
+    /// Run a search deferred by `set_query`
+    pub fn ensure_searched(&mut self, rope: &Rope) {
+        if self.needs_search {
+            self.search(rope);
+        }
+    }
+
+    /// Find all matches in the given rope, up to `max_matches`
     pub fn search(&mut self, rope: &Rope) {
         self.matches.clear();
         self.current_match_index = None;
+        self.truncated = false;
+        self.needs_search = false;
 
         if self.query.is_empty() {
             return;
         }
 
+        let (matches, truncated) = self.find_matches(rope, self.max_matches);
+        self.matches = matches;
+        self.truncated = truncated;
+
+        // Select first match if any
+        if !self.matches.is_empty() {
+            self.current_match_index = Some(0);
+        }
+    }
+
+    /// Collect matches of the query, returning whether `limit` cut the search short
+    fn find_matches(&self, rope: &Rope, limit: Option<usize>) -> (Vec<FindMatch>, bool) {
+        let mut found = Vec::new();
+        if self.query.is_empty() {
+            return (found, false);
+        }
+        if self.use_regex {
+            return self.find_regex_matches(rope, limit);
+        }
+
         let query_len_chars = self.query.chars().count();
         let total_chars = rope.len_chars();
 
@@ -2677,6 +5153,10 @@ impl FindState {
         // Iterate character by character through the rope
         let mut char_idx = 0;
         while char_idx + query_len_chars <= total_chars {
+            if limit.is_some_and(|max| found.len() >= max) {
+                return (found, true);
+            }
+
             // Check if query matches at this position
             let mut matches = true;
             for (q_idx, q_char) in query_chars.iter().enumerate() {
@@ -2698,22 +5178,8 @@ impl FindState {
                 let end_char = char_idx + query_len_chars;
 
                 // Check whole word if enabled
-                let is_whole_word = if self.whole_word {
-                    let before_ok = start_char == 0 || {
-                        let prev_char = rope.char(start_char - 1);
-                        !prev_char.is_alphanumeric() && prev_char != '_'
-                    };
-                    let after_ok = end_char >= total_chars || {
-                        let next_char = rope.char(end_char);
-                        !next_char.is_alphanumeric() && next_char != '_'
-                    };
-                    before_ok && after_ok
-                } else {
-                    true
-                };
-
-                if is_whole_word {
-                    self.matches.push(FindMatch {
+                if !self.whole_word || is_whole_word(rope, start_char, end_char) {
+                    found.push(FindMatch {
                         start: start_char,
                         end: end_char,
                     });
@@ -2723,10 +5189,154 @@ impl FindState {
             char_idx += 1;
         }
 
-        // Select first match if any
+        (found, false)
+    }
+
+    /// Compile the query as a regex honoring case sensitivity (`None` if invalid)
+    fn regex(&self) -> Option<regex::Regex> {
+        regex::RegexBuilder::new(&self.query)
+            .case_insensitive(!self.case_sensitive)
+            .multi_line(true)
+            .build()
+            .ok()
+    }
+
+    /// Regex counterpart of `find_matches`; empty matches are skipped
+    fn find_regex_matches(&self, rope: &Rope, limit: Option<usize>) -> (Vec<FindMatch>, bool) {
+        let mut found = Vec::new();
+        let Some(regex) = self.regex() else {
+            return (found, false);
+        };
+        let text = rope.to_string();
+        for m in regex.find_iter(&text) {
+            if m.is_empty() {
+                continue;
+            }
+            if limit.is_some_and(|max| found.len() >= max) {
+                return (found, true);
+            }
+            let start = rope.byte_to_char(m.start());
+            let end = rope.byte_to_char(m.end());
+            if !self.whole_word || is_whole_word(rope, start, end) {
+                found.push(FindMatch { start, end });
+            }
+        }
+        (found, false)
+    }
+
+    /// The text replacing `found`, with capture references expanded in regex mode
+    fn replacement_for(&self, regex: Option<&regex::Regex>, text: &str, rope: &Rope, found: FindMatch) -> String {
+        let Some(regex) = regex else {
+            return self.replace_text.clone();
+        };
+        let start_byte = rope.char_to_byte(found.start);
+        match regex.captures_at(text, start_byte) {
+            Some(captures) if captures.get(0).is_some_and(|m| m.start() == start_byte) => {
+                let mut expanded = String::new();
+                captures.expand(&self.replace_text, &mut expanded);
+                expanded
+            }
+            _ => self.replace_text.clone(),
+        }
+    }
+
+    /// Replace the current match with `replace_text` as one undo step
+    ///
+    /// The cursor moves after the replacement, matches are recomputed and the
+    /// next match after it becomes current. Returns false if there was no match.
+    pub fn replace_current(&mut self, state: &mut CodeEditorState) -> bool {
+        self.ensure_searched(&state.rope);
+        let Some(found) = self.current_match() else {
+            return false;
+        };
+        let regex = if self.use_regex { self.regex() } else { None };
+        let text = if regex.is_some() { state.rope.to_string() } else { String::new() };
+        let replacement = self.replacement_for(regex.as_ref(), &text, &state.rope, found);
+
+        let cursor_before = state.cursor_pos;
+        let removed_text = state.rope.slice(found.start..found.end).to_string();
+        state.remove_range(found.start, found.end);
+        state.insert_text_at(found.start, &replacement);
+        let end = found.start + replacement.chars().count();
+
+        state.clear_secondary_cursors();
+        state.cursor_pos = end;
+        state.selection_start = None;
+        state.selection_end = None;
+        state.sync_cursors_from_primary();
+        state.record_history(EditOperation {
+            removed_text,
+            inserted_text: replacement,
+            position: found.start,
+            cursor_before,
+            cursor_after: end,
+            kind: EditKind::Other,
+        });
+
+        self.search(&state.rope);
         if !self.matches.is_empty() {
-            self.current_match_index = Some(0);
+            let next = self.matches.iter().position(|m| m.start >= end).unwrap_or(0);
+            self.current_match_index = Some(next);
+        }
+        true
+    }
+
+    /// Replace every match (ignoring `max_matches`) as one undo step
+    ///
+    /// Cursors keep their place in the surrounding text; a cursor inside a
+    /// match moves after its replacement. Returns the number of replacements.
+    pub fn replace_all(&mut self, state: &mut CodeEditorState) -> usize {
+        let (matches, _) = self.find_matches(&state.rope, None);
+        if matches.is_empty() {
+            return 0;
+        }
+        let regex = if self.use_regex { self.regex() } else { None };
+        let text = if regex.is_some() { state.rope.to_string() } else { String::new() };
+        let replacements: Vec<(FindMatch, String)> = matches
+            .iter()
+            .map(|&found| (found, self.replacement_for(regex.as_ref(), &text, &state.rope, found)))
+            .collect();
+
+        state.sync_cursors_from_primary();
+        let before = SelectionCollection::from_cursors(&state.cursors);
+        let mut operations = Vec::with_capacity(replacements.len());
+        for (found, replacement) in replacements.iter().rev() {
+            let removed_text = state.rope.slice(found.start..found.end).to_string();
+            state.remove_range(found.start, found.end);
+            state.insert_text_at(found.start, replacement);
+            operations.push(EditOperation {
+                removed_text,
+                inserted_text: replacement.clone(),
+                position: found.start,
+                cursor_before: state.cursor_pos,
+                cursor_after: state.cursor_pos,
+                kind: EditKind::Other,
+            });
         }
+
+        let map = |offset: usize| -> usize {
+            let mut shift = 0isize;
+            for (found, replacement) in &replacements {
+                let new_len = replacement.chars().count();
+                if offset >= found.end {
+                    shift += new_len as isize - (found.end - found.start) as isize;
+                } else if offset > found.start {
+                    return (found.start as isize + shift) as usize + new_len;
+                } else {
+                    break;
+                }
+            }
+            (offset as isize + shift) as usize
+        };
+        state.cursors = state.cursors.iter().map(|c| Cursor::new(map(c.position))).collect();
+        state.sort_and_merge_cursors();
+        state.sync_primary_cursor();
+
+        let after = SelectionCollection::from_cursors(&state.cursors);
+        state.record_transaction(operations, before, after);
+
+        self.search(&state.rope);
+        replacements.len()
     }
 
     /// Find the next match from the current cursor position
@@ -2770,15 +5380,46 @@ impl FindState {
         self.current_match_index.and_then(|i| self.matches.get(i).copied())
     }
 
+    /// Select the current match in the editor, revealing it as a navigation jump
+    pub fn select_current_match(&self, state: &mut CodeEditorState) -> bool {
+        let Some(m) = self.current_match() else {
+            return false;
+        };
+        state.cursor_pos = m.start;
+        state.selection_start = Some(m.start);
+        state.selection_end = Some(m.end);
+        state.pending_update = true;
+        state.mark_navigation_jump();
+        true
+    }
+
     /// Clear the search
     pub fn clear(&mut self) {
         self.active = false;
         self.query.clear();
         self.matches.clear();
         self.current_match_index = None;
+        self.truncated = false;
+        self.needs_search = false;
+        self.find_focused = false;
     }
 }
 
+/// Whether `c` belongs to a word (alphanumeric or `_`)
+///
+/// Shared by word movement, word deletion, whole-word search and the
+/// word-under-cursor highlight so they all agree on what a word is.
+pub fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// Whether `start..end` is not part of a larger word
+fn is_whole_word(rope: &Rope, start: usize, end: usize) -> bool {
+    let before_ok = start == 0 || !is_word_char(rope.char(start - 1));
+    let after_ok = end >= rope.len_chars() || !is_word_char(rope.char(end));
+    before_ok && after_ok
+}
+
 /// State for "Go to line" functionality
 #[derive(Clone, Debug, Default, Resource)]
 pub struct GotoLineState {
@@ -2803,11 +5444,7 @@ impl GotoLineState {
             let target_line = line_num.saturating_sub(1).min(total_lines.saturating_sub(1));
 
             // Move cursor to the start of the target line
-            let char_pos = state.rope.line_to_char(target_line);
-            state.cursor_pos = char_pos;
-            state.selection_start = None;
-            state.selection_end = None;
-            state.pending_update = true;
+            state.jump_to(state.rope.line_to_char(target_line));
 
             return true;
         }
@@ -2834,6 +5471,9 @@ pub struct FoldRegion {
     pub kind: FoldKind,
     /// Indentation level (for nested folds)
     pub indent_level: usize,
+    /// Whether this region was created programmatically (fold selection, host app)
+    /// rather than detected from the syntax tree. Manual regions survive reparses.
+    pub manual: bool,
 }
 
 impl FoldRegion {
@@ -2845,6 +5485,19 @@ impl FoldRegion {
             is_folded: false,
             kind,
             indent_level: 0,
+            manual: false,
+        }
+    }
+
+    /// Create a user-defined fold region that starts out folded
+    pub fn manual(start_line: usize, end_line: usize) -> Self {
+        Self {
+            start_line,
+            end_line,
+            is_folded: true,
+            kind: FoldKind::Region,
+            indent_level: 0,
+            manual: true,
         }
     }
 
@@ -2916,6 +5569,8 @@ pub struct FoldState {
     pub content_version: usize,
     /// Whether fold detection is enabled
     pub enabled: bool,
+    /// Folded flags from `import`, re-applied after the next detection
+    imported: Vec<(usize, usize, bool)>,
 }
 
 impl Default for FoldState {
@@ -2925,6 +5580,7 @@ impl Default for FoldState {
             // Use usize::MAX as sentinel to force first detection
             content_version: usize::MAX,
             enabled: true,
+            imported: Vec::new(),
         }
     }
 }
@@ -2950,6 +5606,117 @@ impl FoldState {
         self.regions.insert(pos, region);
     }
 
+    /// Add a user-defined fold over an arbitrary line range (inclusive) and fold it.
+    ///
+    /// Manual folds coexist with auto-detected regions and are kept when
+    /// `detect_foldable_regions` recomputes folds after a content change.
+    /// Returns false if the range spans fewer than two lines.
+    pub fn add_manual_fold(&mut self, start_line: usize, end_line: usize) -> bool {
+        if end_line <= start_line {
+            return false;
+        }
+
+        if let Some(existing) = self.regions.iter_mut()
+            .find(|r| r.manual && r.start_line == start_line && r.end_line == end_line)
+        {
+            existing.is_folded = true;
+            return true;
+        }
+
+        self.add_region(FoldRegion::manual(start_line, end_line));
+        true
+    }
+
+    /// Remove a user-defined fold previously added with `add_manual_fold`
+    ///
+    /// Returns true if a manual fold with exactly this range existed.
+    pub fn remove_manual_fold(&mut self, start_line: usize, end_line: usize) -> bool {
+        let before = self.regions.len();
+        self.regions
+            .retain(|r| !(r.manual && r.start_line == start_line && r.end_line == end_line));
+        self.regions.len() != before
+    }
+
+    /// Remove all user-defined folds, keeping auto-detected regions
+    pub fn clear_manual_folds(&mut self) {
+        self.regions.retain(|r| !r.manual);
+    }
+
+    /// Iterate over user-defined fold regions
+    pub fn manual_folds(&self) -> impl Iterator<Item = &FoldRegion> {
+        self.regions.iter().filter(|r| r.manual)
+    }
+
+    /// Replace the auto-detected regions with a freshly computed set
+    ///
+    /// The folded state of detected regions with an unchanged range is preserved,
+    /// and manual folds are kept as-is.
+    pub fn merge_detected_regions(&mut self, detected: Vec<FoldRegion>) {
+        let old_regions = std::mem::take(&mut self.regions);
+
+        for mut region in detected {
+            if let Some(old) = old_regions.iter().find(|r| {
+                !r.manual && r.start_line == region.start_line && r.end_line == region.end_line
+            }) {
+                region.is_folded = old.is_folded;
+            }
+            self.regions.push(region);
+        }
+
+        let imported = std::mem::take(&mut self.imported);
+        for region in old_regions.into_iter().filter(|r| r.manual) {
+            // An imported range restored as a manual fold gives way to a detected region
+            let same_range = |start: usize, end: usize| start == region.start_line && end == region.end_line;
+            let redetected = imported.iter().any(|&(start, end, _)| same_range(start, end))
+                && self.regions.iter().any(|r| same_range(r.start_line, r.end_line));
+            if !redetected {
+                self.add_region(region);
+            }
+        }
+
+        for (start_line, end_line, is_folded) in imported {
+            self.apply_imported(start_line, end_line, is_folded);
+        }
+    }
+
+    /// Snapshot every region as `(start_line, end_line, is_folded)`, e.g. to
+    /// keep folds across a reload of the same file
+    pub fn export(&self) -> Vec<(usize, usize, bool)> {
+        self.regions
+            .iter()
+            .map(|r| (r.start_line, r.end_line, r.is_folded))
+            .collect()
+    }
+
+    /// Restore regions and folded flags from an `export` snapshot
+    ///
+    /// Flags are applied to regions with the same `(start_line, end_line)`
+    /// now, and again once `detect_foldable_regions` rebuilds the regions, so
+    /// a snapshot can be imported right after `CodeEditorState::set_text`.
+    /// Entries matching no region are restored as manual folds. Cursors on
+    /// lines the snapshot hides are moved out by the fold systems.
+    pub fn import(&mut self, snapshot: Vec<(usize, usize, bool)>) {
+        for &(start_line, end_line, is_folded) in &snapshot {
+            self.apply_imported(start_line, end_line, is_folded);
+        }
+        self.imported = snapshot;
+    }
+
+    fn apply_imported(&mut self, start_line: usize, end_line: usize, is_folded: bool) {
+        let mut matched = false;
+        for region in self
+            .regions
+            .iter_mut()
+            .filter(|r| r.start_line == start_line && r.end_line == end_line)
+        {
+            region.is_folded = is_folded;
+            matched = true;
+        }
+        if !matched && end_line > start_line {
+            self.add_region(FoldRegion { is_folded, ..FoldRegion::manual(start_line, end_line) });
+        }
+    }
+
     /// Get the fold region that starts at the given line
     pub fn region_at_line(&self, line: usize) -> Option<&FoldRegion> {
         self.regions.iter().find(|r| r.start_line == line)
@@ -3008,6 +5775,9 @@ impl FoldState {
     }
 
     /// Fold all regions
+    ///
+    /// Nested regions are folded too, so they stay collapsed when their
+    /// enclosing region is opened; only the outermost ones are visible.
     pub fn fold_all(&mut self) {
         for region in &mut self.regions {
             region.is_folded = true;
@@ -3021,6 +5791,34 @@ impl FoldState {
         }
     }
 
+    /// The nearest visible line for `line`: itself, or the header of the
+    /// outermost folded region hiding it
+    pub fn visible_line_for(&self, line: usize) -> usize {
+        self.regions.iter()
+            .filter(|r| r.hides_line(line))
+            .map(|r| r.start_line)
+            .min()
+            .unwrap_or(line)
+    }
+
+    /// Fold every region of the given kind, e.g. all comments or all imports
+    ///
+    /// Other regions, including manual folds (kind `Region`), are left as they are.
+    /// Detected regions keep their folded state across reparses while their
+    /// line range is unchanged.
+    pub fn fold_all_of_kind(&mut self, kind: FoldKind) {
+        for region in self.regions.iter_mut().filter(|r| r.kind == kind) {
+            region.is_folded = true;
+        }
+    }
+
+    /// Unfold every region of the given kind
+    pub fn unfold_all_of_kind(&mut self, kind: FoldKind) {
+        for region in self.regions.iter_mut().filter(|r| r.kind == kind) {
+            region.is_folded = false;
+        }
+    }
+
     /// Fold all regions at a specific level (0 = top-level functions/classes)
     pub fn fold_level(&mut self, level: usize) {
         for region in &mut self.regions {
@@ -3030,10 +5828,32 @@ impl FoldState {
         }
     }
 
+    /// Fold everything indented at `level` or deeper and unfold the rest
+    ///
+    /// Regions are derived from leading whitespace (tabs count as
+    /// `indent_size` columns) and added alongside existing ones, skipping
+    /// spans that are already present. A region's level is one more than the
+    /// indentation depth of its first line, so level 1 folds top-level blocks.
+    pub fn fold_to_level(&mut self, level: usize, rope: &Rope, indent_size: usize) {
+        for region in indentation_fold_regions(rope, indent_size) {
+            let exists = self.regions.iter()
+                .any(|r| r.start_line == region.start_line && r.end_line == region.end_line);
+            if !exists {
+                self.add_region(region);
+            }
+        }
+
+        for region in &mut self.regions {
+            let depth = indentation_depth(rope, region.start_line, indent_size);
+            region.is_folded = depth + 1 >= level;
+        }
+    }
+
     /// Get total number of hidden lines
     pub fn total_hidden_lines(&self) -> usize {
+        // Folded regions inside another folded region are already hidden by it
         self.regions.iter()
-            .filter(|r| r.is_folded)
+            .filter(|r| r.is_folded && !self.is_line_hidden(r.start_line))
             .map(|r| r.hidden_line_count())
             .sum()
     }
@@ -3084,6 +5904,92 @@ impl FoldState {
             }
         }
     }
+
+    /// Follow a swap of the adjacent line blocks `first.start..mid` and `mid..second_end`
+    ///
+    /// Regions inside either block move with it and regions enclosing both are
+    /// kept. Regions that straddle a block boundary no longer describe the text,
+    /// so they are dropped (detected ones are found again on the next pass).
+    pub fn swap_line_blocks(&mut self, start: usize, mid: usize, end: usize) {
+        let first_len = mid - start;
+        let second_len = end - mid;
+        self.regions.retain_mut(|region| {
+            let (first, last) = (region.start_line, region.end_line);
+            if last < start || first >= end || (first <= start && last >= end - 1) {
+                true
+            } else if first >= start && last < mid {
+                region.start_line += second_len;
+                region.end_line += second_len;
+                true
+            } else if first >= mid && last < end {
+                region.start_line -= first_len;
+                region.end_line -= first_len;
+                true
+            } else {
+                false
+            }
+        });
+        self.regions.sort_by_key(|r| r.start_line);
+    }
+}
+
+/// Indentation depth of a line in units of `indent_size`, or `None` if blank
+fn line_indentation(rope: &Rope, line: usize, indent_size: usize) -> Option<usize> {
+    let indent_size = indent_size.max(1);
+    let mut columns = 0;
+    for c in rope.line(line).chars() {
+        match c {
+            ' ' => columns += 1,
+            '\t' => columns += indent_size,
+            '\n' | '\r' => return None,
+            _ => return Some(columns / indent_size),
+        }
+    }
+    None
+}
+
+/// Indentation depth of a line, treating blank lines as unindented
+fn indentation_depth(rope: &Rope, line: usize, indent_size: usize) -> usize {
+    if line >= rope.len_lines() {
+        return 0;
+    }
+    line_indentation(rope, line, indent_size).unwrap_or(0)
+}
+
+/// Fold regions from indentation: each non-blank line followed by more deeply
+/// indented lines starts a region ending at the last of them
+fn indentation_fold_regions(rope: &Rope, indent_size: usize) -> Vec<FoldRegion> {
+    let mut regions = Vec::new();
+    // (start line, depth, last line inside) of the regions still open
+    let mut open: Vec<(usize, usize, usize)> = Vec::new();
+    let close = |open: &mut Vec<(usize, usize, usize)>, regions: &mut Vec<FoldRegion>| {
+        if let Some((start, depth, last)) = open.pop() {
+            if last > start {
+                let mut region = FoldRegion::new(start, last, FoldKind::Block);
+                region.indent_level = depth;
+                regions.push(region);
+            }
+        }
+    };
+
+    for line in 0..rope.len_lines() {
+        let Some(depth) = line_indentation(rope, line, indent_size) else {
+            continue;
+        };
+        while open.last().is_some_and(|&(_, open_depth, _)| open_depth >= depth) {
+            close(&mut open, &mut regions);
+        }
+        for entry in &mut open {
+            entry.2 = line;
+        }
+        open.push((line, depth, line));
+    }
+    while !open.is_empty() {
+        close(&mut open, &mut regions);
+    }
+
+    regions.sort_by_key(|r| r.start_line);
+    regions
 }
 
 /// Component marker for fold gutter indicator entities
@@ -3093,6 +5999,13 @@ pub struct FoldIndicator {
     pub line_index: usize,
 }
 
+/// Component marker for bookmark gutter indicator entities
+#[derive(Component)]
+pub struct BookmarkIndicator {
+    /// The line this indicator is for
+    pub line_index: usize,
+}
+
 // ========== Editor Events ==========
 
 /// Event emitted when save is requested (Ctrl+S)
@@ -3107,3 +6020,754 @@ pub struct SaveRequested {
 /// The host application should handle this event to show a file picker.
 #[derive(bevy::prelude::Message, Clone, Debug)]
 pub struct OpenRequested;
+
+/// Event emitted when the file given to `CodeEditorPlugin::with_file` could not be read
+/// The editor starts with an empty buffer in that case.
+#[cfg(feature = "file-io")]
+#[derive(bevy::prelude::Message, Clone, Debug)]
+pub struct FileLoadFailed {
+    /// The file that failed to load
+    pub path: std::path::PathBuf,
+    /// The I/O error message
+    pub error: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fold_all_functions_leaves_other_constructs_expanded() {
+        let mut fold_state = FoldState::new();
+        fold_state.add_region(FoldRegion::new(0, 2, FoldKind::Comment));
+        fold_state.add_region(FoldRegion::new(3, 6, FoldKind::Function));
+        fold_state.add_region(FoldRegion::new(8, 12, FoldKind::Class));
+        fold_state.add_region(FoldRegion::new(9, 11, FoldKind::Function));
+        assert!(fold_state.add_manual_fold(14, 16));
+
+        fold_state.fold_all_of_kind(FoldKind::Function);
+        for line in [4, 5, 6, 10, 11] {
+            assert!(fold_state.is_line_hidden(line), "line {} should be hidden", line);
+        }
+        for line in [1, 2, 9, 12] {
+            assert!(!fold_state.is_line_hidden(line), "line {} should be visible", line);
+        }
+        assert!(fold_state.is_line_hidden(15));
+
+        // A reparse that finds the same functions keeps them folded
+        fold_state.merge_detected_regions(vec![
+            FoldRegion::new(0, 2, FoldKind::Comment),
+            FoldRegion::new(3, 6, FoldKind::Function),
+            FoldRegion::new(8, 12, FoldKind::Class),
+            FoldRegion::new(9, 11, FoldKind::Function),
+        ]);
+        assert!(fold_state.is_line_hidden(5) && fold_state.is_line_hidden(10));
+        assert!(!fold_state.is_line_hidden(1));
+
+        // Unfolding functions leaves the manual fold alone
+        fold_state.unfold_all_of_kind(FoldKind::Function);
+        assert!(!fold_state.is_line_hidden(5));
+        assert!(fold_state.is_line_hidden(15));
+    }
+
+    #[test]
+    fn test_fold_to_level_folds_deeper_indentation_without_duplicates() {
+        let rope = Rope::from_str("mod a {\n    fn b() {\n        c();\n    }\n}\nfn d() {\n    e();\n}\n");
+        let mut fold_state = FoldState::new();
+        // A detected region with the same span as an indentation region
+        fold_state.add_region(FoldRegion::new(1, 2, FoldKind::Function));
+
+        fold_state.fold_to_level(2, &rope, 4);
+
+        let spans: Vec<(usize, usize, bool)> = fold_state.regions.iter()
+            .map(|r| (r.start_line, r.end_line, r.is_folded))
+            .collect();
+        assert_eq!(spans, vec![(0, 3, false), (1, 2, true), (5, 6, false)]);
+        assert!(fold_state.is_line_hidden(2));
+        assert!(!fold_state.is_line_hidden(1));
+
+        fold_state.fold_to_level(1, &rope, 4);
+        assert_eq!(fold_state.regions.len(), 3);
+        assert!(fold_state.regions.iter().all(|r| r.is_folded));
+    }
+
+    #[test]
+    fn test_manual_fold_survives_redetection() {
+        let mut fold_state = FoldState::new();
+        fold_state.add_region(FoldRegion::new(0, 3, FoldKind::Function));
+
+        assert!(fold_state.add_manual_fold(5, 10));
+        assert!(!fold_state.is_line_hidden(5));
+        for line in 6..=10 {
+            assert!(fold_state.is_line_hidden(line));
+        }
+        assert!(!fold_state.is_line_hidden(11));
+
+        // Simulate a content change followed by a reparse
+        fold_state.merge_detected_regions(vec![
+            FoldRegion::new(0, 4, FoldKind::Function),
+            FoldRegion::new(12, 20, FoldKind::Block),
+        ]);
+
+        assert_eq!(fold_state.manual_folds().count(), 1);
+        assert_eq!(fold_state.regions.len(), 3);
+        assert!(fold_state.is_folded_line(5));
+        assert!(fold_state.is_line_hidden(8));
+
+        assert!(fold_state.remove_manual_fold(5, 10));
+        assert!(!fold_state.is_line_hidden(8));
+        assert_eq!(fold_state.regions.len(), 2);
+    }
+
+    #[test]
+    fn test_kill_ring_paste_cycle_retrieves_earlier_kill() {
+        let mut ring = KillRing::new(4);
+
+        // Two separate cuts with other edits in between
+        ring.kill("first".to_string(), 0, 5, 0, 1);
+        ring.kill("second".to_string(), 10, 16, 3, 4);
+        assert_eq!(ring.entries.len(), 2);
+        assert_eq!(ring.top(), Some("second"));
+
+        // Paste the top entry, then cycle back to the earlier one
+        ring.record_yank(20, 26, 5, true);
+        let (start, end, text) = ring.cycle(5).unwrap();
+        assert_eq!((start, end), (20, 26));
+        assert_eq!(text, "first");
+
+        // Cycling is only possible right after a paste
+        ring.record_yank(20, 25, 6, false);
+        assert!(ring.cycle(7).is_none());
+    }
+
+    #[test]
+    fn test_every_edit_yields_a_buffer_change() {
+        let mut state = CodeEditorState::new("ab\ncd");
+        state.cursor_pos = 2;
+        state.insert_char('\n');
+        state.delete_backward();
+        state.cursor_pos = 5;
+        state.delete_backward();
+
+        let changes = state.take_buffer_changes();
+        let summary: Vec<_> = changes.iter()
+            .map(|c| (c.content_version, c.edit.start, c.edit.old_end, c.edit.new_end, c.lines.clone()))
+            .collect();
+        assert_eq!(summary, vec![
+            (1, 2, 2, 3, Some(0..2)),
+            (2, 2, 3, 2, Some(0..1)),
+            (3, 4, 5, 4, Some(1..2)),
+        ]);
+        assert!(state.take_buffer_changes().is_empty());
+
+        state.set_text("new");
+        let changes = state.take_buffer_changes();
+        assert_eq!(changes.len(), 1);
+        assert!(changes[0].lines.is_none());
+    }
+
+    #[test]
+    fn test_typing_yields_incremental_changes() {
+        let type_abc = || {
+            let mut state = CodeEditorState::new("");
+            for c in ['a', 'b', 'c'] {
+                state.insert_char(c);
+            }
+            state
+        };
+        let mut state = type_abc();
+        let mut coalesced_state = type_abc();
+
+        let changes = state.take_changes(ChangeGranularity::PerEdit);
+        assert_eq!(changes.len(), 3);
+        for (i, (change, text)) in changes.iter().zip(["a", "b", "c"]).enumerate() {
+            assert_eq!((change.start, change.end), (i, i));
+            assert_eq!(change.new_text, text);
+            assert_eq!(change.content_version, i as u64 + 1);
+        }
+        assert!(state.take_changes(ChangeGranularity::PerEdit).is_empty());
+
+        let changes = coalesced_state.take_changes(ChangeGranularity::Coalesced);
+        assert_eq!(changes, vec![ContentChange {
+            start: 0,
+            end: 0,
+            new_text: "abc".to_string(),
+            content_version: 3,
+        }]);
+    }
+
+    #[test]
+    fn test_operation_log_records_insert_delete_and_undo() {
+        let mut state = CodeEditorState::new("");
+        state.operation_log_enabled = true;
+
+        state.insert_text_at(0, "hello");
+        state.record_history(EditOperation {
+            removed_text: String::new(),
+            inserted_text: "hello".to_string(),
+            position: 0,
+            cursor_before: 0,
+            cursor_after: 5,
+            kind: EditKind::Paste,
+        });
+        state.cursor_pos = 5;
+        state.delete_backward();
+        assert!(state.undo());
+
+        let ops = state.drain_operations();
+        assert_eq!(ops, vec![
+            Operation::Insert { seq: 1, position: 0, text: "hello".to_string() },
+            Operation::Delete { seq: 2, position: 4, text: "o".to_string() },
+            Operation::Insert { seq: 3, position: 4, text: "o".to_string() },
+        ]);
+        assert!(state.drain_operations().is_empty());
+    }
+
+    #[test]
+    fn test_batch_updates_flushes_once() {
+        let mut state = CodeEditorState::new("");
+        state.needs_update = false;
+
+        state.batch_updates(|state| {
+            for _ in 0..100 {
+                state.insert_char('x');
+            }
+            // Nested batches don't flush on their own
+            state.batch_updates(|state| state.insert_char('\n'));
+            assert!(state.is_batching());
+            assert!(!state.needs_update);
+        });
+
+        assert!(!state.is_batching());
+        assert_eq!(state.line_count(), 2);
+        // One full rebuild instead of a trail of debounced per-line updates
+        assert!(state.needs_update);
+        assert!(!state.pending_update);
+        assert_eq!(state.dirty_lines, None);
+    }
+
+    #[test]
+    fn test_view_transition_animates_from_snapshot_to_target() {
+        let mut state = CodeEditorState::new("a\n".repeat(500).as_str());
+        state.scroll_offset = -100.0;
+        state.target_scroll_offset = -100.0;
+        let snapshot = state.view_snapshot();
+
+        // Jump somewhere far away
+        state.target_scroll_offset = -2100.0;
+        let mut transition = ViewTransition::default();
+        transition.start(&snapshot, 0.25);
+
+        let mut offsets = Vec::new();
+        for _ in 0..5 {
+            transition.advance(&mut state, 0.06);
+            offsets.push(state.scroll_offset);
+        }
+
+        // Strictly moving toward the target each frame, landing on it at the end
+        assert!(offsets[0] < -100.0 && offsets[0] > -2100.0);
+        assert!(offsets.windows(2).all(|w| w[1] < w[0]));
+        assert!((offsets[4] + 2100.0).abs() < 1e-3);
+        assert!(!transition.is_active());
+
+        state.restore_view(&snapshot);
+        assert_eq!(state.scroll_offset, -100.0);
+    }
+
+    #[test]
+    fn test_multi_cursor_undo_restores_every_cursor() {
+        let mut state = CodeEditorState::new("one\ntwo\nthree\n");
+        state.cursor_pos = 3;
+        state.sync_cursors_from_primary();
+        state.add_cursor(7);
+        state.add_cursor(13);
+        let before: Vec<usize> = state.cursors.iter().map(|c| c.position).collect();
+
+        state.insert_at_cursors(";");
+        assert_eq!(state.text(), "one;\ntwo;\nthree;\n");
+        let after: Vec<usize> = state.cursors.iter().map(|c| c.position).collect();
+        assert_eq!(after, vec![4, 9, 16]);
+
+        assert!(state.undo());
+        assert_eq!(state.text(), "one\ntwo\nthree\n");
+        let restored: Vec<usize> = state.cursors.iter().map(|c| c.position).collect();
+        assert_eq!(restored, before);
+
+        assert!(state.redo());
+        let redone: Vec<usize> = state.cursors.iter().map(|c| c.position).collect();
+        assert_eq!(redone, after);
+    }
+
+    #[test]
+    fn test_multi_cursor_typing_groups_into_one_undo_step() {
+        let mut state = CodeEditorState::new("a\nb\n");
+        state.cursor_pos = 1;
+        state.sync_cursors_from_primary();
+        state.add_cursor(3);
+
+        for c in ["x", "y", "z"] {
+            state.type_at_cursors(c);
+        }
+        assert_eq!(state.text(), "axyz\nbxyz\n");
+
+        assert!(state.undo());
+        assert_eq!(state.text(), "a\nb\n");
+        let restored: Vec<usize> = state.cursors.iter().map(|c| c.position).collect();
+        assert_eq!(restored, vec![1, 3]);
+    }
+
+    #[test]
+    fn test_wrap_width_counts_display_cells() {
+        let row_offsets = |state: &CodeEditorState| -> Vec<(usize, usize)> {
+            state.display_map.rows.iter().map(|row| (row.start_offset, row.end_offset)).collect()
+        };
+
+        // Wide characters take two cells each
+        let mut state = CodeEditorState::new("中文中文中文");
+        state.rewrap(4);
+        assert_eq!(row_offsets(&state), vec![(0, 2), (2, 4), (4, 6)]);
+
+        // A tab reaches the next tab stop
+        let mut state = CodeEditorState::new("\tabcdef");
+        state.rewrap(6);
+        assert_eq!(row_offsets(&state), vec![(0, 3), (3, 7)]);
+    }
+
+    #[test]
+    fn test_set_selections_sorts_and_merges() {
+        let mut state = CodeEditorState::new("0123456789abcdefghij");
+
+        state.set_selections(vec![(15, Some(12)), (6, Some(2)), (8, Some(4))]);
+
+        assert_eq!(state.get_selections(), vec![(8, Some(2)), (15, Some(12))]);
+        assert_eq!(state.cursor_count(), 2);
+        assert_eq!(state.cursor_pos, 8);
+        assert_eq!(state.selection_start, Some(2));
+        assert_eq!(state.selection_end, Some(8));
+    }
+
+    #[test]
+    fn test_find_stops_at_max_matches() {
+        let rope = Rope::from_str(&"a".repeat(1000));
+        let mut find = FindState {
+            max_matches: Some(100),
+            ..Default::default()
+        };
+
+        find.set_query("a", &rope);
+        assert_eq!(find.matches.len(), 100);
+        assert!(find.truncated);
+
+        // On demand: changing the query defers the search
+        find.on_demand = true;
+        find.set_query("aa", &rope);
+        assert!(find.matches.is_empty() && find.needs_search);
+        find.ensure_searched(&rope);
+        assert_eq!(find.matches.len(), 100);
+
+        find.max_matches = None;
+        find.search(&rope);
+        assert_eq!(find.matches.len(), 999);
+        assert!(!find.truncated);
+    }
+
+    #[test]
+    fn test_kill_ring_merges_contiguous_kills() {
+        let mut ring = KillRing::new(4);
+
+        // Consecutive line deletes at the same position append
+        ring.kill("line 1\n".to_string(), 0, 7, 0, 1);
+        ring.kill("line 2\n".to_string(), 0, 7, 1, 2);
+        assert_eq!(ring.entries.len(), 1);
+        assert_eq!(ring.top(), Some("line 1\nline 2\n"));
+
+        // A copy always starts a new entry
+        ring.push("copied".to_string());
+        ring.kill("more".to_string(), 0, 4, 2, 3);
+        assert_eq!(ring.entries.len(), 3);
+    }
+
+    #[test]
+    fn test_history_byte_budget_drops_oldest_transactions() {
+        let mut history = EditHistory {
+            max_history_bytes: Some(25),
+            ..Default::default()
+        };
+        let insert = |text: &str, position: usize| EditOperation {
+            removed_text: String::new(),
+            inserted_text: text.to_string(),
+            position,
+            cursor_before: position,
+            cursor_after: position + text.len(),
+            kind: EditKind::Other,
+        };
+
+        history.record(insert("aaaaaaaaaa", 0));
+        history.record(insert("bbbbbbbbbb", 10));
+        history.record(insert("cccccccccc", 20));
+        history.finalize_transaction();
+
+        // 30 bytes exceed the 25 byte budget, so the oldest transaction goes
+        let kept: Vec<&str> = history
+            .undo_stack
+            .iter()
+            .map(|tx| tx.operations[0].inserted_text.as_str())
+            .collect();
+        assert_eq!(kept, vec!["bbbbbbbbbb", "cccccccccc"]);
+
+        // The newest transaction is kept even when it alone exceeds the budget
+        history.record(insert(&"d".repeat(40), 30));
+        history.finalize_transaction();
+        assert_eq!(history.undo_stack.len(), 1);
+        assert_eq!(history.undo_stack[0].size_bytes(), 40);
+
+        // Undoing releases its bytes, so two small steps fit the budget again
+        assert!(history.pop_undo().is_some());
+        history.record(insert("eeeeeeeeee", 0));
+        history.record(insert("ffffffffff", 10));
+        history.finalize_transaction();
+        assert_eq!(history.undo_stack.len(), 2);
+    }
+
+    #[test]
+    fn test_indentation_after_newline_undoes_with_it() {
+        let mut state = CodeEditorState::new("fn main() {");
+        state.cursor_pos = 11;
+        state.insert_char('\n');
+        for _ in 0..4 {
+            state.insert_char(' ');
+        }
+        state.insert_char('x');
+        assert_eq!(state.text(), "fn main() {\n    x");
+
+        state.undo();
+        assert_eq!(state.text(), "fn main() {\n    ");
+        state.undo();
+        assert_eq!(state.text(), "fn main() {");
+    }
+
+    #[test]
+    fn test_set_top_visible_line_scrolls_and_clamps() {
+        let mut state = CodeEditorState::new(&"line\n".repeat(199));
+        let fold_state = FoldState::default();
+        let viewport = ViewportDimensions::default();
+        let scrolling = crate::settings::ScrollingSettings::default();
+        let line_height = 20.0;
+
+        state.set_top_visible_line(50, line_height, &fold_state, &viewport, &scrolling);
+        assert_eq!(state.scroll_offset, -1000.0);
+        assert_eq!(state.top_visible_line(line_height, &fold_state), 50);
+
+        // The last page is the furthest the view can scroll
+        state.set_top_visible_line(1000, line_height, &fold_state, &viewport, &scrolling);
+        assert_eq!(state.scroll_offset, -(200.0 * line_height - 600.0 + 10.0));
+        assert_eq!(state.top_visible_line(line_height, &fold_state), 170);
+
+        state.set_top_visible_line(0, line_height, &fold_state, &viewport, &scrolling);
+        assert_eq!(state.scroll_offset, 0.0);
+        assert_eq!(state.top_visible_line(line_height, &fold_state), 0);
+
+        // Soft-wrapped lines take several rows; a continuation row belongs to its line
+        let mut state = CodeEditorState::new(&"abcdefghijklmnopqrst\n".repeat(100));
+        state.rewrap(10);
+        let row = state.display_map.buffer_line_to_first_row(30);
+        assert!(row > 30);
+        state.set_top_visible_line(30, line_height, &fold_state, &viewport, &scrolling);
+        assert_eq!(state.scroll_offset, -(row as f32) * line_height);
+        assert_eq!(state.top_visible_line(line_height, &fold_state), 30);
+        assert!(state.display_map.is_continuation(row + 1));
+        state.scroll_offset = -((row + 1) as f32) * line_height;
+        assert_eq!(state.top_visible_line(line_height, &fold_state), 30);
+    }
+
+    #[test]
+    fn test_replace_current_then_replace_all_with_captures() {
+        let mut state = CodeEditorState::new("foo(1) foo(2) foo(3)");
+        let mut find_state = FindState {
+            replace_text: "bar".to_string(),
+            ..Default::default()
+        };
+        find_state.set_query("foo", &state.rope);
+        find_state.current_match_index = Some(1);
+
+        assert!(find_state.replace_current(&mut state));
+        assert_eq!(state.text(), "foo(1) bar(2) foo(3)");
+        assert_eq!(state.cursor_pos, 10);
+        assert_eq!(find_state.matches.len(), 2);
+        assert_eq!(find_state.current_match(), Some(FindMatch { start: 14, end: 17 }));
+
+        // Regex replace-all expands capture groups, in one undo step
+        find_state.use_regex = true;
+        find_state.replace_text = "call_$1($2)".to_string();
+        find_state.set_query(r"(\w+)\((\d)\)", &state.rope);
+        assert_eq!(find_state.replace_all(&mut state), 3);
+        assert_eq!(state.text(), "call_foo(1) call_bar(2) call_foo(3)");
+
+        state.undo();
+        assert_eq!(state.text(), "foo(1) bar(2) foo(3)");
+    }
+
+    #[test]
+    fn test_whole_word_and_case_toggles_filter_matches() {
+        let rope = Rope::from_str("new newer renew New new_x new");
+        let mut find_state = FindState::default();
+        find_state.set_query("new", &rope);
+        assert_eq!(find_state.matches.len(), 6);
+
+        find_state.toggle_whole_word(&rope);
+        let starts: Vec<usize> = find_state.matches.iter().map(|m| m.start).collect();
+        assert_eq!(starts, vec![0, 16, 26]);
+
+        find_state.toggle_case_sensitive(&rope);
+        let starts: Vec<usize> = find_state.matches.iter().map(|m| m.start).collect();
+        assert_eq!(starts, vec![0, 26]);
+    }
+
+    #[test]
+    fn test_set_text_resets_and_preserving_variant_keeps_line_column() {
+        let mut state = CodeEditorState::new("line one\nline two\nthree");
+        state.cursor_pos = 0;
+        state.insert_char('x');
+        assert!(state.history.can_undo());
+
+        // "line |two" and "three|"
+        state.cursor_pos = 15;
+        state.add_cursor(24);
+        state.set_text_preserving_cursor("a\nlonger line\nxy");
+        let positions: Vec<usize> = state.cursors.iter().map(|c| c.position).collect();
+        assert_eq!(positions, vec![7, 16]);
+        assert_eq!(state.cursor_pos, 7);
+        assert!(!state.history.can_undo());
+
+        state.set_text("fresh");
+        assert_eq!(state.rope().to_string(), "fresh");
+        assert_eq!(state.cursor_pos, 0);
+        assert_eq!(state.cursors.len(), 1);
+        assert_eq!(state.selection_start, None);
+    }
+
+    #[test]
+    fn test_insert_with_cursors_places_cursor_inside_insertion() {
+        let mut state = CodeEditorState::new("foo;");
+        state.cursor_pos = 3;
+
+        state.insert_with_cursors("()", &[1]);
+        assert_eq!(state.rope.to_string(), "foo();");
+        assert_eq!(state.cursors.len(), 1);
+        assert_eq!(state.cursor_pos, 4);
+
+        state.undo();
+        assert_eq!(state.rope.to_string(), "foo;");
+
+        // Multiple edit points, with the selection replaced
+        state.selection_start = Some(0);
+        state.selection_end = Some(3);
+        state.cursor_pos = 3;
+        state.insert_with_cursors("f(a, b)", &[2, 5]);
+        assert_eq!(state.rope.to_string(), "f(a, b);");
+        let positions: Vec<usize> = state.cursors.iter().map(|c| c.position).collect();
+        assert_eq!(positions, vec![2, 5]);
+
+        // Inserting at the primary cursor drops the secondary ones
+        state.insert_and_select("x");
+        assert_eq!(state.rope.to_string(), "f(xa, b);");
+        assert_eq!(state.cursors.len(), 1);
+        assert_eq!(state.selection_start, Some(2));
+        assert_eq!(state.selection_end, Some(3));
+    }
+
+    #[test]
+    fn test_select_next_occurrence_adds_whole_word_matches() {
+        let mut state = CodeEditorState::new("foo foobar foo\nfoo");
+        state.cursor_pos = 1;
+        let ranges = |state: &CodeEditorState| -> Vec<(usize, usize)> {
+            state.cursors.iter().map(|c| (c.selection_start(), c.selection_end())).collect()
+        };
+
+        // First press selects the word under the cursor
+        assert!(state.select_next_occurrence());
+        assert_eq!(ranges(&state), vec![(0, 3)]);
+
+        // Then whole-word matches only, skipping "foobar"
+        assert!(state.select_next_occurrence());
+        assert_eq!(ranges(&state), vec![(0, 3), (11, 14)]);
+        assert!(state.select_next_occurrence());
+        assert_eq!(ranges(&state), vec![(0, 3), (11, 14), (15, 18)]);
+        assert!(!state.select_next_occurrence());
+
+        let mut state = CodeEditorState::new("foo foobar foo\nfoo");
+        state.cursor_pos = 12;
+        assert_eq!(state.select_all_occurrences(), 3);
+        assert_eq!(ranges(&state), vec![(0, 3), (11, 14), (15, 18)]);
+    }
+
+    #[test]
+    fn test_coincident_cursors_merge_and_type_once() {
+        let mut state = CodeEditorState::new("abc def\n");
+
+        // The primary cursor moved without updating cursors[0]
+        state.cursor_pos = 2;
+        state.add_cursor(2);
+        assert_eq!(state.cursors.len(), 1);
+
+        state.add_cursor(6);
+        state.cursors.push(Cursor::new(6));
+        state.insert_at_cursors("x");
+        assert_eq!(state.text(), "abxc dexf\n");
+        assert_eq!(state.cursors.len(), 2);
+
+        let mut selections = SelectionCollection::from_cursors(&state.cursors);
+        selections.add_cursor(3);
+        assert_eq!(selections.len(), 2);
+    }
+
+    #[test]
+    fn test_goto_last_edit_returns_to_earlier_location() {
+        let text = (0..40).map(|i| format!("line {}\n", i)).collect::<String>();
+        let mut state = CodeEditorState::new(&text);
+
+        // Edit near the top, then far below
+        state.cursor_pos = state.rope.line_to_char(2) + 4;
+        state.insert_char('a');
+        state.insert_char('b');
+        state.cursor_pos = state.rope.line_to_char(30);
+        state.insert_char('x');
+        assert_eq!(state.edit_locations.len(), 2);
+
+        assert!(state.goto_last_edit());
+        assert_eq!(state.cursor_pos, state.rope.line_to_char(2) + 6);
+        assert!(!state.goto_last_edit());
+        assert!(state.goto_next_edit());
+        assert_eq!(state.cursor_pos, state.rope.line_to_char(30) + 1);
+        assert!(!state.goto_next_edit());
+
+        // Raw inserts aren't undo steps, so they add no location, but the
+        // recorded locations follow them
+        state.insert_text_at(0, "// header\n");
+        assert_eq!(state.edit_locations.len(), 2);
+        state.cursor_pos = state.rope.line_to_char(20);
+        assert!(state.goto_last_edit());
+        assert_eq!(state.cursor_pos, state.rope.line_to_char(3) + 6);
+        assert!(!state.goto_last_edit());
+        assert!(state.goto_next_edit());
+        assert_eq!(state.cursor_pos, state.rope.line_to_char(31) + 1);
+    }
+
+    #[test]
+    fn test_bookmarks_follow_edits_and_wrap_around() {
+        let text = (0..20).map(|i| format!("line {}\n", i)).collect::<String>();
+        let mut state = CodeEditorState::new(&text);
+
+        state.cursor_pos = state.rope.line_to_char(5) + 2;
+        assert!(state.toggle_bookmark());
+        state.cursor_pos = state.rope.line_to_char(12);
+        assert!(state.toggle_bookmark());
+        assert_eq!(state.bookmark_lines(), vec![5, 12]);
+
+        // Both bookmarks move down with text inserted above them
+        state.insert_text_at(0, "// a\n// b\n");
+        assert_eq!(state.bookmark_lines(), vec![7, 14]);
+
+        state.cursor_pos = state.rope.line_to_char(10);
+        assert!(state.goto_next_bookmark());
+        assert_eq!(state.cursor_pos, state.rope.line_to_char(14));
+        assert!(state.reveal_cursor);
+        assert!(state.goto_next_bookmark());
+        assert_eq!(state.cursor_pos, state.rope.line_to_char(7) + 2);
+        assert!(state.goto_prev_bookmark());
+        assert_eq!(state.cursor_pos, state.rope.line_to_char(14));
+
+        // Toggling on a bookmarked line removes it
+        assert!(!state.toggle_bookmark());
+        assert_eq!(state.bookmark_lines(), vec![7]);
+        state.set_text("fresh\n");
+        assert!(!state.goto_prev_bookmark());
+    }
+
+    #[test]
+    fn test_fold_snapshot_survives_redetection() {
+        let detect = || {
+            vec![
+                FoldRegion::new(1, 3, FoldKind::Function),
+                FoldRegion::new(5, 8, FoldKind::Block),
+                FoldRegion::new(10, 12, FoldKind::Block),
+            ]
+        };
+        let mut fold_state = FoldState::default();
+        fold_state.merge_detected_regions(detect());
+        fold_state.fold_at_line(1);
+        fold_state.fold_at_line(10);
+        fold_state.add_manual_fold(14, 16);
+        let snapshot = fold_state.export();
+        assert_eq!(snapshot, vec![(1, 3, true), (5, 8, false), (10, 12, true), (14, 16, true)]);
+
+        // A reload starts from fresh state; unmatched entries come back as
+        // manual folds until detection finds the same ranges
+        let mut reloaded = FoldState::default();
+        reloaded.import(snapshot);
+        assert_eq!(reloaded.manual_folds().count(), 4);
+        assert!(reloaded.is_line_hidden(2));
+        reloaded.merge_detected_regions(detect());
+        assert_eq!(reloaded.export(), vec![(1, 3, true), (5, 8, false), (10, 12, true), (14, 16, true)]);
+        assert_eq!(reloaded.manual_folds().map(|r| r.start_line).collect::<Vec<_>>(), vec![14]);
+
+        // Later detections keep the flags through the usual preservation logic
+        reloaded.region_at_line_mut(1).unwrap().is_folded = false;
+        reloaded.merge_detected_regions(detect());
+        assert_eq!(reloaded.export(), vec![(1, 3, false), (5, 8, false), (10, 12, true), (14, 16, true)]);
+    }
+
+    #[test]
+    fn test_snippet_expansion_mirrors_stops_and_ends_at_final_stop() {
+        let snippet = Snippet::parse("for ${1:i} in ${2|0..n,iter|} {\n\t$1$0\n} \\$ $TM_FILENAME${UNSET:x}");
+        assert_eq!(snippet.text, "for i in 0..n {\n\ti\n} $ x");
+        assert_eq!(snippet.stop_groups(), vec![vec![4..5, 17..18], vec![9..13], vec![18..18]]);
+
+        let mut state = CodeEditorState::new("    fo");
+        let mut session = SnippetSession::default();
+        session.expand(&mut state, 4..6, "for ${1:i} in ${2:iter} {\n\t$1\n}$0");
+        assert_eq!(state.rope.to_string(), "    for i in iter {\n    \ti\n    }");
+        assert_eq!(state.get_selections(), vec![(9, Some(8)), (26, Some(25))]);
+
+        // Typing over the selected stop updates its mirror
+        state.insert_at_cursors("idx");
+        assert_eq!(state.rope.to_string(), "    for idx in iter {\n    \tidx\n    }");
+        assert_eq!(session.stop_ranges(&state, 0), vec![8..11, 27..30]);
+
+        assert!(session.next_stop(&mut state));
+        assert_eq!(state.get_selections(), vec![(19, Some(15))]);
+        assert!(session.prev_stop(&mut state));
+        assert_eq!(state.cursor_count(), 2);
+
+        // Reaching $0 places the cursor there and ends the session
+        session.next_stop(&mut state);
+        session.next_stop(&mut state);
+        assert!(!session.is_active());
+        assert_eq!(state.cursor_pos, state.rope.len_chars());
+
+        // The whole expansion is one undo step
+        state.undo();
+        assert_eq!(state.rope.to_string(), "    for i in iter {\n    \ti\n    }");
+        state.undo();
+        assert_eq!(state.rope.to_string(), "    fo");
+    }
+
+    #[test]
+    fn test_apply_edits_is_one_undo_step_and_keeps_the_cursor() {
+        let mut state = CodeEditorState::new("fn main(){\nlet x=1;\n}\n");
+        // Before the `1`
+        state.set_selections(vec![(17, None)]);
+
+        state.apply_edits(vec![
+            (16..17, " = ".to_string()),
+            (9..9, " ".to_string()),
+            (11..11, "    ".to_string()),
+        ]);
+        assert_eq!(state.rope.to_string(), "fn main() {\n    let x = 1;\n}\n");
+        assert_eq!(state.cursor_pos, 24);
+        assert_eq!(state.rope.char(state.cursor_pos), '1');
+
+        state.undo();
+        assert_eq!(state.rope.to_string(), "fn main(){\nlet x=1;\n}\n");
+    }
+}